@@ -0,0 +1,80 @@
+/*
+ * C ABI of libriichi, see `src/capi.rs`.
+ *
+ * Build the shared library with `cargo build --release --no-default-features`
+ * (add `--features grpc` for `riichi_bot_connect`), which leaves out the
+ * `python` feature so that it does not link to Python. All the events,
+ * candidates and reactions are mjai JSON strings in UTF-8.
+ *
+ * Every function but the destructors returns RIICHI_OK on success, or one of
+ * the negative RIICHI_ERR_* codes, in which case `riichi_last_error` has the
+ * message. Strings returned through out parameters are owned by the caller
+ * and must be released by `riichi_string_free`.
+ *
+ * A state or a bot must not be used from more than one thread at a time.
+ */
+
+#ifndef RIICHI_H
+#define RIICHI_H
+
+#include <stdbool.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define RIICHI_OK 0
+/* The input is not a well-formed mjai event. */
+#define RIICHI_ERR_PARSE (-1)
+/* The event is well-formed, but it cannot happen in the current state. */
+#define RIICHI_ERR_INVALID_EVENT (-2)
+/* The action is not a valid reaction to the current state. */
+#define RIICHI_ERR_ILLEGAL_ACTION (-3)
+/* The agent failed to produce a reaction. */
+#define RIICHI_ERR_ENGINE (-4)
+/* Anything else, such as invalid arguments. */
+#define RIICHI_ERR_OTHER (-5)
+
+typedef struct RiichiState RiichiState;
+typedef struct RiichiBot RiichiBot;
+
+/* Returns the message of the last error on this thread, or NULL if the last
+ * call succeeded. It is valid until the next call on this thread. */
+const char *riichi_last_error(void);
+
+void riichi_string_free(char *s);
+
+/* Creates a state of `player_id` under Tenhou's rule. */
+int riichi_state_new(uint8_t player_id, RiichiState **out);
+
+void riichi_state_free(RiichiState *state);
+
+/* Updates the state with one mjai event, and returns the action candidates
+ * of the player in JSON. */
+int riichi_state_update(RiichiState *state, const char *mjai_json, char **candidates);
+
+/* Returns RIICHI_ERR_ILLEGAL_ACTION if the event is not a valid reaction to
+ * the current state. */
+int riichi_state_validate_reaction(const RiichiState *state, const char *mjai_json);
+
+/* Creates a bot of `player_id` driven by `agent`, which is one of
+ * "rule_based" and "tsumogiri". */
+int riichi_bot_new(uint8_t player_id, const char *agent, RiichiBot **out);
+
+/* Creates a bot of `player_id`, in [0, 3], whose engine is served by the
+ * gRPC endpoint, such as "http://10.0.0.2:50051". Only available with the
+ * `grpc` feature. */
+int riichi_bot_connect(uint8_t player_id, const char *endpoint, RiichiBot **out);
+
+void riichi_bot_free(RiichiBot *bot);
+
+/* Feeds one mjai event to the bot, and returns its reaction, or NULL if it
+ * does not react. Set `can_act` to false to only update the bot. */
+int riichi_bot_react(RiichiBot *bot, const char *line, bool can_act, char **reaction);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
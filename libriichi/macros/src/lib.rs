@@ -0,0 +1,84 @@
+//! `pyclass` and `pymethods` that are pyo3's with the `python` feature, and
+//! otherwise strip the attributes pyo3 would consume, so the same items build
+//! without pyo3.
+//!
+//! pyo3 0.16 does not look through `cfg_attr`, which is why
+//! `#[cfg_attr(feature = "python", pyo3(get))]` on a field does not work.
+
+use proc_macro::TokenStream;
+#[cfg(not(feature = "python"))]
+use proc_macro::{Delimiter, Group, TokenTree};
+
+/// Attributes consumed by `pyclass` on the struct and its fields.
+const PYCLASS_ATTRS: &[&str] = &["pyo3"];
+/// Attributes consumed by `pymethods` on the methods.
+const PYMETHODS_ATTRS: &[&str] = &[
+    "pyo3",
+    "new",
+    "getter",
+    "setter",
+    "staticmethod",
+    "classmethod",
+    "classattr",
+    "args",
+];
+
+#[proc_macro_attribute]
+pub fn pyclass(args: TokenStream, item: TokenStream) -> TokenStream {
+    expand("pyclass", args, item, PYCLASS_ATTRS)
+}
+
+#[proc_macro_attribute]
+pub fn pymethods(args: TokenStream, item: TokenStream) -> TokenStream {
+    expand("pymethods", args, item, PYMETHODS_ATTRS)
+}
+
+#[cfg(feature = "python")]
+fn expand(name: &str, args: TokenStream, item: TokenStream, _: &[&str]) -> TokenStream {
+    let attr = if args.is_empty() {
+        format!("#[::pyo3::{name}]")
+    } else {
+        format!("#[::pyo3::{name}({args})]")
+    };
+    let mut ret: TokenStream = attr.parse().unwrap();
+    ret.extend(item);
+    ret
+}
+
+#[cfg(not(feature = "python"))]
+fn expand(_: &str, _: TokenStream, item: TokenStream, attrs: &[&str]) -> TokenStream {
+    strip(item, attrs, true)
+}
+
+/// Removes `attrs` from the item and, one level down, from its fields or
+/// methods.
+#[cfg(not(feature = "python"))]
+fn strip(stream: TokenStream, attrs: &[&str], descend: bool) -> TokenStream {
+    let mut ret = vec![];
+    let mut tokens = stream.into_iter().peekable();
+    while let Some(tt) = tokens.next() {
+        match tt {
+            TokenTree::Punct(p) if p.as_char() == '#' => {
+                if let Some(TokenTree::Group(g)) = tokens.peek() {
+                    let is_stripped = g.delimiter() == Delimiter::Bracket
+                        && matches!(
+                            g.stream().into_iter().next(),
+                            Some(TokenTree::Ident(ident)) if attrs.contains(&ident.to_string().as_str())
+                        );
+                    if is_stripped {
+                        tokens.next();
+                        continue;
+                    }
+                }
+                ret.push(TokenTree::Punct(p));
+            }
+            TokenTree::Group(g) if descend && g.delimiter() == Delimiter::Brace => {
+                let mut group = Group::new(Delimiter::Brace, strip(g.stream(), attrs, false));
+                group.set_span(g.span());
+                ret.push(TokenTree::Group(group));
+            }
+            tt => ret.push(tt),
+        }
+    }
+    ret.into_iter().collect()
+}
@@ -114,6 +114,7 @@ impl Agent for AkochanAgent {
             let v = EventWithCanAct {
                 event: events[i].event.clone(),
                 can_act: Some(i == events.len() - 1),
+                timing: events[i].timing,
             };
             writeln!(self.stdin, "{}", json::to_string(&v)?)?;
             self.stdin.flush()?;
@@ -147,6 +148,7 @@ impl Agent for AkochanAgent {
                 shanten: Some(state.shanten()),
                 ..Default::default()
             }),
+            timing: None,
         })
     }
 
@@ -1,16 +1,26 @@
 use crate::arena::GameResult;
 use crate::mjai::EventExt;
 use crate::state::PlayerState;
+use std::future::Future;
+use std::pin::Pin;
+use std::time::Instant;
 
 use anyhow::Result;
 use ndarray::prelude::*;
 
 pub type InvisibleState = Array2<f32>;
 
+/// The futures of `AsyncBatchAgent`, which are `Send` so that they can be run
+/// on multi-threaded runtimes.
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
 /// `react` provides various choices for input, the implementor may choose
 /// one or many of them to produce the result.
 ///
 /// The caller SHOULD call `react` only when `cans.can_act()` holds.
+///
+/// The events in `log` may carry their `timing` from the host, see
+/// `Timing::budget` for the time budget of the reaction.
 pub trait Agent {
     fn name(&self) -> String;
     fn need_oracle_obs(&self) -> bool {
@@ -58,6 +68,66 @@ pub trait BatchAgent {
         invisible_state: Option<InvisibleState>,
     ) -> Result<EventExt>;
 
+    /// Sets the deadline of the reactions of `index` until it is set to
+    /// `None` again. Agents that search or otherwise take their time should
+    /// cut off by then and fall back to their greedy reaction, while the
+    /// others may ignore it.
+    fn set_deadline(&mut self, index: usize, deadline: Option<Instant>) {
+        let _ = index;
+        let _ = deadline;
+    }
+
+    fn start_game(&mut self, index: usize) -> Result<()> {
+        let _ = index;
+        Ok(())
+    }
+
+    fn end_kyoku(&mut self, index: usize) -> Result<()> {
+        let _ = index;
+        Ok(())
+    }
+
+    fn end_game(&mut self, index: usize, game_result: &GameResult) -> Result<()> {
+        let _ = index;
+        let _ = game_result;
+        Ok(())
+    }
+}
+
+/// The asynchronous counterpart of `BatchAgent`, for agents that wait on
+/// something else to react, such as inference served over the network, and
+/// must not block the executor meanwhile.
+///
+/// Only `set_scene` and `get_reaction` are asynchronous, as the rest are
+/// meant to be cheap bookkeeping. It does not depend on any specific runtime.
+pub trait AsyncBatchAgent {
+    fn name(&self) -> String;
+    fn need_oracle_obs(&self) -> bool {
+        false
+    }
+
+    fn set_scene<'a>(
+        &'a mut self,
+        index: usize,
+        log: &'a [EventExt],
+        state: &'a PlayerState,
+        invisible_state: Option<InvisibleState>,
+    ) -> BoxFuture<'a, Result<()>>;
+
+    fn get_reaction<'a>(
+        &'a mut self,
+        index: usize,
+        log: &'a [EventExt],
+        state: &'a PlayerState,
+        invisible_state: Option<InvisibleState>,
+    ) -> BoxFuture<'a, Result<EventExt>>;
+
+    /// See `BatchAgent::set_deadline`.
+    fn set_deadline(&mut self, index: usize, deadline: Option<Instant>) {
+        let _ = index;
+        let _ = deadline;
+    }
+
     fn start_game(&mut self, index: usize) -> Result<()> {
         let _ = index;
         Ok(())
@@ -0,0 +1,302 @@
+use super::{BatchAgent, InvisibleState};
+use crate::arena::GameResult;
+use crate::consts::ACTION_SPACE;
+use crate::mjai::EventExt;
+use crate::state::PlayerState;
+use std::time::Instant;
+
+use anyhow::{ensure, Result};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EnsembleStrategy {
+    /// The reaction proposed by the most members wins, where ties are broken
+    /// by the order of the members.
+    MajorityVote,
+    /// Among the reactions proposed by the members, the one whose action has
+    /// the highest q value averaged over all the members wins.
+    ///
+    /// Members that do not report q values are ignored in the average. Falls
+    /// back to `MajorityVote` if none of them reports.
+    MeanQ,
+}
+
+/// `EnsembleBatchAgent` runs every member on the same scenes and combines
+/// their reactions by `strategy`.
+///
+/// Each member must have been created with the same player IDs in the same
+/// order.
+pub struct EnsembleBatchAgent {
+    agents: Vec<Box<dyn BatchAgent>>,
+    strategy: EnsembleStrategy,
+}
+
+impl EnsembleBatchAgent {
+    pub fn new(agents: Vec<Box<dyn BatchAgent>>, strategy: EnsembleStrategy) -> Result<Self> {
+        ensure!(!agents.is_empty(), "ensemble must have at least one member");
+        Ok(Self { agents, strategy })
+    }
+
+    fn combine(&self, reactions: Vec<EventExt>, state: &PlayerState) -> EventExt {
+        let mean_q = match self.strategy {
+            EnsembleStrategy::MeanQ => mean_q_values(&reactions),
+            EnsembleStrategy::MajorityVote => None,
+        };
+
+        // Distinct reactions along with their number of proposers.
+        let mut candidates: Vec<(EventExt, usize)> = vec![];
+        for reaction in reactions {
+            match candidates
+                .iter_mut()
+                .find(|(r, _)| r.event == reaction.event)
+            {
+                Some((_, votes)) => *votes += 1,
+                None => candidates.push((reaction, 1)),
+            }
+        }
+
+        // `max_by*` returns the last max element, hence the `rev`s, so that
+        // ties are broken by the order of the members.
+        let by_q = mean_q.and_then(|mean_q| {
+            candidates
+                .iter()
+                .enumerate()
+                .filter_map(|(idx, (reaction, _))| {
+                    let q = mean_q[state.action_id_of(&reaction.event)?]?;
+                    Some((idx, q))
+                })
+                .rev()
+                .max_by(|(_, l), (_, r)| l.total_cmp(r))
+                .map(|(idx, _)| idx)
+        });
+        let best_idx = by_q.unwrap_or_else(|| {
+            candidates
+                .iter()
+                .enumerate()
+                .rev()
+                .max_by_key(|(_, (_, votes))| *votes)
+                .map(|(idx, _)| idx)
+                .unwrap_or_default()
+        });
+        candidates.swap_remove(best_idx).0
+    }
+}
+
+/// Averages the q values of each action over the members that report them.
+/// Returns `None` if none of them reports.
+fn mean_q_values(reactions: &[EventExt]) -> Option<[Option<f32>; ACTION_SPACE]> {
+    let mut q_sum = [0.; ACTION_SPACE];
+    let mut q_count = [0_u32; ACTION_SPACE];
+    for meta in reactions.iter().filter_map(|r| r.meta.as_ref()) {
+        let (Some(q_values), Some(mask_bits)) = (&meta.q_values, meta.mask_bits) else {
+            continue;
+        };
+        let actions = (0..ACTION_SPACE).filter(|i| mask_bits & (1 << i) != 0);
+        for (action, &q) in actions.zip(q_values) {
+            q_sum[action] += q;
+            q_count[action] += 1;
+        }
+    }
+
+    if q_count.iter().all(|&c| c == 0) {
+        return None;
+    }
+    let mut ret = [None; ACTION_SPACE];
+    for (action, r) in ret.iter_mut().enumerate() {
+        if q_count[action] > 0 {
+            *r = Some(q_sum[action] / q_count[action] as f32);
+        }
+    }
+    Some(ret)
+}
+
+impl BatchAgent for EnsembleBatchAgent {
+    fn name(&self) -> String {
+        let names: Vec<_> = self.agents.iter().map(|a| a.name()).collect();
+        format!("ensemble({})", names.join(","))
+    }
+
+    fn need_oracle_obs(&self) -> bool {
+        self.agents.iter().any(|a| a.need_oracle_obs())
+    }
+
+    fn set_scene(
+        &mut self,
+        index: usize,
+        log: &[EventExt],
+        state: &PlayerState,
+        invisible_state: Option<InvisibleState>,
+    ) -> Result<()> {
+        for agent in &mut self.agents {
+            let invisible_state = agent
+                .need_oracle_obs()
+                .then(|| invisible_state.clone())
+                .flatten();
+            agent.set_scene(index, log, state, invisible_state)?;
+        }
+        Ok(())
+    }
+
+    fn get_reaction(
+        &mut self,
+        index: usize,
+        log: &[EventExt],
+        state: &PlayerState,
+        invisible_state: Option<InvisibleState>,
+    ) -> Result<EventExt> {
+        let reactions = self
+            .agents
+            .iter_mut()
+            .map(|agent| {
+                let invisible_state = agent
+                    .need_oracle_obs()
+                    .then(|| invisible_state.clone())
+                    .flatten();
+                agent.get_reaction(index, log, state, invisible_state)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(self.combine(reactions, state))
+    }
+
+    fn set_deadline(&mut self, index: usize, deadline: Option<Instant>) {
+        for agent in &mut self.agents {
+            agent.set_deadline(index, deadline);
+        }
+    }
+
+    fn start_game(&mut self, index: usize) -> Result<()> {
+        self.agents.iter_mut().try_for_each(|a| a.start_game(index))
+    }
+
+    fn end_kyoku(&mut self, index: usize) -> Result<()> {
+        self.agents.iter_mut().try_for_each(|a| a.end_kyoku(index))
+    }
+
+    fn end_game(&mut self, index: usize, game_result: &GameResult) -> Result<()> {
+        self.agents
+            .iter_mut()
+            .try_for_each(|a| a.end_game(index, game_result))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::agent::{RuleBased, Tsumogiri};
+    use crate::mjai::{Event, Metadata};
+    use crate::t;
+
+    use serde_json as json;
+
+    /// Always reacts with the given reaction.
+    struct Fixed(EventExt);
+
+    impl BatchAgent for Fixed {
+        fn name(&self) -> String {
+            "fixed".to_owned()
+        }
+        fn set_scene(
+            &mut self,
+            _: usize,
+            _: &[EventExt],
+            _: &PlayerState,
+            _: Option<InvisibleState>,
+        ) -> Result<()> {
+            Ok(())
+        }
+        fn get_reaction(
+            &mut self,
+            _: usize,
+            _: &[EventExt],
+            _: &PlayerState,
+            _: Option<InvisibleState>,
+        ) -> Result<EventExt> {
+            Ok(self.0.clone())
+        }
+    }
+
+    fn state() -> PlayerState {
+        let mut state = PlayerState::new(0);
+        let log = r#"
+            {"type":"start_kyoku","bakaze":"E","dora_marker":"9p","kyoku":1,"honba":0,"kyotaku":0,"oya":0,"scores":[25000,25000,25000,25000],"tehais":[["1m","2m","3m","4p","5pr","6p","7s","8s","9s","E","E","3s","C"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"]]}
+            {"type":"tsumo","actor":0,"pai":"N"}
+        "#;
+        for line in log.trim().lines() {
+            state.update(&json::from_str(line).unwrap()).unwrap();
+        }
+        state
+    }
+
+    fn dahai(pai: &str, q_values: Option<Vec<f32>>) -> EventExt {
+        let pai = pai.parse().unwrap();
+        EventExt {
+            event: Event::Dahai {
+                actor: 0,
+                pai,
+                tsumogiri: false,
+            },
+            meta: q_values.map(|q_values| Metadata {
+                q_values: Some(q_values),
+                // C and N
+                mask_bits: Some(1 << t!(C).as_usize() | 1 << t!(N).as_usize()),
+                ..Default::default()
+            }),
+            timing: None,
+        }
+    }
+
+    #[test]
+    fn majority_vote() {
+        let state = state();
+        let agents: Vec<Box<dyn BatchAgent>> = vec![
+            Box::new(RuleBased::new_batched(&[0]).unwrap()),
+            Box::new(Tsumogiri::new_batched(&[0]).unwrap()),
+            Box::new(Tsumogiri::new_batched(&[0]).unwrap()),
+        ];
+        let mut ensemble = EnsembleBatchAgent::new(agents, EnsembleStrategy::MajorityVote).unwrap();
+        ensemble.set_scene(0, &[], &state, None).unwrap();
+        let ev = ensemble.get_reaction(0, &[], &state, None).unwrap().event;
+        assert_eq!(
+            ev,
+            Event::Dahai {
+                actor: 0,
+                pai: t!(N),
+                tsumogiri: true,
+            },
+        );
+
+        // Ties are broken by the order.
+        let agents: Vec<Box<dyn BatchAgent>> = vec![
+            Box::new(Fixed(dahai("C", None))),
+            Box::new(Fixed(dahai("N", None))),
+        ];
+        let mut ensemble = EnsembleBatchAgent::new(agents, EnsembleStrategy::MajorityVote).unwrap();
+        let ev = ensemble.get_reaction(0, &[], &state, None).unwrap().event;
+        assert!(matches!(ev, Event::Dahai { pai, .. } if pai == t!(C)));
+    }
+
+    #[test]
+    fn mean_q() {
+        let state = state();
+        // Two prefer C slightly, one prefers N strongly. The q values are in
+        // the order of tile IDs, i.e. N then C.
+        let agents: Vec<Box<dyn BatchAgent>> = vec![
+            Box::new(Fixed(dahai("C", Some(vec![0.9, 1.])))),
+            Box::new(Fixed(dahai("C", Some(vec![0.9, 1.])))),
+            Box::new(Fixed(dahai("N", Some(vec![2., 0.])))),
+        ];
+        let mut ensemble = EnsembleBatchAgent::new(agents, EnsembleStrategy::MeanQ).unwrap();
+        let ev = ensemble.get_reaction(0, &[], &state, None).unwrap().event;
+        assert!(matches!(ev, Event::Dahai { pai, .. } if pai == t!(N)));
+
+        let agents: Vec<Box<dyn BatchAgent>> = vec![
+            Box::new(Fixed(dahai("N", None))),
+            Box::new(Fixed(dahai("C", None))),
+            Box::new(Fixed(dahai("C", None))),
+        ];
+        let mut ensemble = EnsembleBatchAgent::new(agents, EnsembleStrategy::MeanQ).unwrap();
+        let ev = ensemble.get_reaction(0, &[], &state, None).unwrap().event;
+        assert!(matches!(ev, Event::Dahai { pai, .. } if pai == t!(C)));
+
+        assert!(EnsembleBatchAgent::new(vec![], EnsembleStrategy::MeanQ).is_err());
+    }
+}
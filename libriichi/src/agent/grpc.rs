@@ -0,0 +1,309 @@
+use super::{
+    BatchAgent, BatchReaction, EngineBackend, EngineConfig, Fallback, InvisibleState,
+    MortalBatchAgent, Sampling,
+};
+use crate::arena::GameResult;
+use crate::consts::{ObsVersion, ACTION_SPACE};
+use crate::mjai::EventExt;
+use crate::state::PlayerState;
+
+use anyhow::{bail, ensure, Context, Result};
+use ndarray::prelude::*;
+use tokio::runtime::{self, Runtime};
+use tonic::client::Grpc;
+use tonic::codec::ProstCodec;
+use tonic::codegen::http::uri::PathAndQuery;
+use tonic::transport::{Channel, Endpoint};
+
+/// `MortalBatchAgent` whose batches are evaluated by a remote engine through
+/// the `mortal.Inference` gRPC service defined in `proto/inference.proto`,
+/// so that the bot can run on a machine without the model.
+///
+/// The calls block on a runtime of its own, so it must not be used from
+/// within an async runtime.
+pub struct GrpcBatchAgent {
+    inner: MortalBatchAgent,
+}
+
+/// The messages of `proto/inference.proto`.
+mod proto {
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct InfoRequest {}
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct InfoResponse {
+        #[prost(string, tag = "1")]
+        pub name: String,
+        #[prost(bool, tag = "2")]
+        pub is_oracle: bool,
+        #[prost(bool, tag = "3")]
+        pub enable_quick_eval: bool,
+        #[prost(bool, tag = "4")]
+        pub enable_rule_based_agari_guard: bool,
+        #[prost(uint32, tag = "5")]
+        pub obs_version: u32,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct ReactBatchRequest {
+        #[prost(uint32, tag = "1")]
+        pub batch_size: u32,
+        #[prost(float, repeated, tag = "2")]
+        pub obs: Vec<f32>,
+        #[prost(uint64, repeated, tag = "3")]
+        pub masks: Vec<u64>,
+        #[prost(float, repeated, tag = "4")]
+        pub invisible_obs: Vec<f32>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct ReactBatchResponse {
+        #[prost(uint32, repeated, tag = "1")]
+        pub actions: Vec<u32>,
+        #[prost(float, repeated, tag = "2")]
+        pub q_values: Vec<f32>,
+        #[prost(uint64, repeated, tag = "3")]
+        pub masks: Vec<u64>,
+        #[prost(bool, repeated, tag = "4")]
+        pub is_greedy: Vec<bool>,
+    }
+}
+
+struct GrpcEngine {
+    runtime: Runtime,
+    client: Grpc<Channel>,
+}
+
+impl GrpcBatchAgent {
+    /// Connects to the engine at `endpoint`, such as `http://10.0.0.2:50051`.
+    pub fn connect(endpoint: &str, player_ids: &[u8]) -> Result<Self> {
+        let runtime = runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        let endpoint = Endpoint::from_shared(endpoint.to_owned())
+            .with_context(|| format!("invalid endpoint {endpoint}"))?;
+        let channel = runtime
+            .block_on(endpoint.connect())
+            .context("failed to connect to the engine")?;
+
+        let mut engine = GrpcEngine {
+            runtime,
+            client: Grpc::new(channel),
+        };
+        let info: proto::InfoResponse = engine
+            .call("/mortal.Inference/Info", proto::InfoRequest {})
+            .context("failed to get the info of the engine")?;
+        let config = EngineConfig {
+            name: info.name,
+            is_oracle: info.is_oracle,
+            enable_quick_eval: info.enable_quick_eval,
+            enable_rule_based_agari_guard: info.enable_rule_based_agari_guard,
+            obs_version: obs_version(info.obs_version)?,
+            ..Default::default()
+        };
+
+        let inner = MortalBatchAgent::with_backend(Box::new(engine), config, player_ids)?;
+        Ok(Self { inner })
+    }
+
+    /// See `MortalBatchAgent::set_sampling`.
+    pub fn set_sampling(&mut self, sampling: Option<Sampling>) {
+        self.inner.set_sampling(sampling);
+    }
+
+    /// See `MortalBatchAgent::set_fallback`.
+    pub fn set_fallback(&mut self, fallback: Option<Fallback>) {
+        self.inner.set_fallback(fallback);
+    }
+}
+
+impl GrpcEngine {
+    fn call<Req, Res>(&mut self, path: &'static str, req: Req) -> Result<Res>
+    where
+        Req: prost::Message + Send + Sync + 'static,
+        Res: prost::Message + Default + Send + Sync + 'static,
+    {
+        let Self { runtime, client } = self;
+        runtime.block_on(async {
+            client.ready().await?;
+            let res = client
+                .unary(
+                    tonic::Request::new(req),
+                    PathAndQuery::from_static(path),
+                    ProstCodec::default(),
+                )
+                .await?;
+            Ok(res.into_inner())
+        })
+    }
+}
+
+impl EngineBackend for GrpcEngine {
+    fn react_batch(
+        &mut self,
+        obs: Array3<f32>,
+        masks: Array2<bool>,
+        invisible_obs: Option<Array3<f32>>,
+    ) -> Result<BatchReaction> {
+        let batch_size = obs.dim().0;
+        let req = to_request(obs, &masks, invisible_obs)?;
+        let res = self
+            .call("/mortal.Inference/ReactBatch", req)
+            .context("failed to call `ReactBatch` on the engine")?;
+        from_response(res, batch_size)
+    }
+}
+
+fn obs_version(v: u32) -> Result<ObsVersion> {
+    match v {
+        0 => Ok(ObsVersion::default()),
+        1 => Ok(ObsVersion::V1),
+        2 => Ok(ObsVersion::V2),
+        3 => Ok(ObsVersion::V3),
+        4 => Ok(ObsVersion::V4),
+        _ => bail!("unknown obs version {v}"),
+    }
+}
+
+fn to_request(
+    obs: Array3<f32>,
+    masks: &Array2<bool>,
+    invisible_obs: Option<Array3<f32>>,
+) -> Result<proto::ReactBatchRequest> {
+    let batch_size = obs.dim().0;
+    if let Some(invisible_obs) = &invisible_obs {
+        ensure!(invisible_obs.dim().0 == batch_size);
+    }
+    let masks = masks
+        .rows()
+        .into_iter()
+        .map(|row| {
+            row.iter()
+                .enumerate()
+                .filter(|(_, &m)| m)
+                .fold(0, |acc, (i, _)| acc | 1 << i)
+        })
+        .collect();
+    Ok(proto::ReactBatchRequest {
+        batch_size: batch_size.try_into()?,
+        obs: obs.iter().copied().collect(),
+        masks,
+        invisible_obs: invisible_obs
+            .map(|v| v.iter().copied().collect())
+            .unwrap_or_default(),
+    })
+}
+
+fn from_response(res: proto::ReactBatchResponse, batch_size: usize) -> Result<BatchReaction> {
+    ensure!(
+        res.actions.len() == batch_size
+            && res.q_values.len() == batch_size * ACTION_SPACE
+            && res.masks.len() == batch_size
+            && res.is_greedy.len() == batch_size,
+        "the response does not match the batch size {batch_size}",
+    );
+
+    let actions = res
+        .actions
+        .iter()
+        .map(|&a| {
+            ensure!((a as usize) < ACTION_SPACE, "invalid action {a}");
+            Ok(a as usize)
+        })
+        .collect::<Result<_>>()?;
+    let q_values = res
+        .q_values
+        .chunks_exact(ACTION_SPACE)
+        .map(|q| q.try_into().unwrap())
+        .collect();
+    let masks = res
+        .masks
+        .iter()
+        .map(|&bits| std::array::from_fn(|i| bits & (1 << i) != 0))
+        .collect();
+    Ok((actions, q_values, masks, res.is_greedy))
+}
+
+impl BatchAgent for GrpcBatchAgent {
+    fn name(&self) -> String {
+        self.inner.name()
+    }
+
+    fn need_oracle_obs(&self) -> bool {
+        self.inner.need_oracle_obs()
+    }
+
+    fn set_scene(
+        &mut self,
+        index: usize,
+        log: &[EventExt],
+        state: &PlayerState,
+        invisible_state: Option<InvisibleState>,
+    ) -> Result<()> {
+        self.inner.set_scene(index, log, state, invisible_state)
+    }
+
+    fn get_reaction(
+        &mut self,
+        index: usize,
+        log: &[EventExt],
+        state: &PlayerState,
+        invisible_state: Option<InvisibleState>,
+    ) -> Result<EventExt> {
+        self.inner.get_reaction(index, log, state, invisible_state)
+    }
+
+    fn start_game(&mut self, index: usize) -> Result<()> {
+        self.inner.start_game(index)
+    }
+
+    fn end_kyoku(&mut self, index: usize) -> Result<()> {
+        self.inner.end_kyoku(index)
+    }
+
+    fn end_game(&mut self, index: usize, game_result: &GameResult) -> Result<()> {
+        self.inner.end_game(index, game_result)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use prost::Message;
+
+    #[test]
+    fn request_and_response() {
+        let obs = Array3::from_shape_fn((2, 3, 34), |(n, c, t)| (n * 1000 + c * 34 + t) as f32);
+        let mut masks = Array2::from_elem((2, ACTION_SPACE), false);
+        masks[(0, 5)] = true;
+        masks[(0, 45)] = true;
+        masks[(1, 37)] = true;
+
+        let req = to_request(obs.clone(), &masks, None).unwrap();
+        let req = proto::ReactBatchRequest::decode(&*req.encode_to_vec()).unwrap();
+        assert_eq!(req.batch_size, 2);
+        assert_eq!(req.obs, obs.into_raw_vec());
+        assert_eq!(req.masks, [1 << 5 | 1 << 45, 1 << 37]);
+        assert!(req.invisible_obs.is_empty());
+
+        let mut q_values = vec![0.; 2 * ACTION_SPACE];
+        q_values[5] = 1.;
+        q_values[ACTION_SPACE + 37] = 2.;
+        let res = proto::ReactBatchResponse {
+            actions: vec![5, 37],
+            q_values,
+            masks: req.masks,
+            is_greedy: vec![true, false],
+        };
+        let res = proto::ReactBatchResponse::decode(&*res.encode_to_vec()).unwrap();
+        let (actions, q_values, masks_recv, is_greedy) = from_response(res.clone(), 2).unwrap();
+        assert_eq!(actions, [5, 37]);
+        assert_eq!(q_values[1][..], res.q_values[ACTION_SPACE..]);
+        assert_eq!(masks_recv[0].to_vec(), masks.row(0).to_vec());
+        assert_eq!(masks_recv[1].to_vec(), masks.row(1).to_vec());
+        assert_eq!(is_greedy, [true, false]);
+
+        assert!(from_response(res, 3).is_err());
+        assert!(obs_version(3).is_err());
+    }
+}
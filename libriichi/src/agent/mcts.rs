@@ -0,0 +1,471 @@
+use super::{BatchAgent, InvisibleState};
+use crate::arena::{GameResult, Policy, Rollout};
+use crate::consts::ACTION_SPACE;
+use crate::mjai::{Event, EventExt, Metadata};
+use crate::state::PlayerState;
+use std::time::Instant;
+
+use anyhow::{ensure, Context, Result};
+use rand::prelude::*;
+use rand_chacha::ChaCha12Rng;
+use riichi_macros::{pyclass, pymethods};
+
+/// Configuration of `MctsBatchAgent`.
+#[pyclass]
+#[pyo3(
+    text_signature = "(*, simulations=64, max_depth=2, c_puct=1.5, value_scale=8000.0, seed=0, policy='rule_based')"
+)]
+#[derive(Debug, Clone)]
+pub struct MctsConfig {
+    /// Number of the determinized worlds to simulate for each decision.
+    pub simulations: usize,
+    /// Number of the consecutive decisions of the player covered by the tree,
+    /// including the current one. The decisions beyond it are made by
+    /// `policy`.
+    pub max_depth: usize,
+    /// Weight of the prior in the selection.
+    pub c_puct: f32,
+    /// Score delta that maps to a value of 1.
+    pub value_scale: f32,
+    pub seed: u64,
+    /// Policy of the opponents, and of the player beyond `max_depth`.
+    pub policy: Policy,
+}
+
+/// `MctsBatchAgent` runs a determinized MCTS on top of the reaction of
+/// `inner`.
+///
+/// For each decision, the legal reactions are searched with the softmax of
+/// the q values of `inner` as priors. Each simulation samples the hidden
+/// hands and the wall consistent with the log (see `arena::Rollout`), picks
+/// the reactions of the player by PUCT for the next `max_depth` decisions,
+/// and plays the rest of the kyoku with `policy`. The values are the score
+/// deltas of the player at the end of the kyoku, divided by `value_scale`.
+///
+/// The most visited reaction is chosen. The metadata is the one of `inner`,
+/// even if the reaction differs.
+///
+/// Under a deadline set by `set_deadline`, the search stops as soon as it is
+/// due, and the reaction of `inner` is chosen if not a single simulation has
+/// been done by then.
+pub struct MctsBatchAgent {
+    inner: Box<dyn BatchAgent + Send>,
+    player_ids: Vec<u8>,
+    config: MctsConfig,
+    rng: ChaCha12Rng,
+    deadlines: Vec<Option<Instant>>,
+}
+
+#[derive(Default)]
+struct Node {
+    children: Vec<Child>,
+}
+
+struct Child {
+    action: Event,
+    prior: f32,
+    visits: u32,
+    value_sum: f32,
+    node: Node,
+}
+
+impl Default for MctsConfig {
+    fn default() -> Self {
+        Self {
+            simulations: 64,
+            max_depth: 2,
+            c_puct: 1.5,
+            value_scale: 8000.,
+            seed: 0,
+            policy: Policy::RuleBased,
+        }
+    }
+}
+
+#[pymethods]
+impl MctsConfig {
+    #[new]
+    #[args(
+        "*",
+        simulations = "64",
+        max_depth = "2",
+        c_puct = "1.5",
+        value_scale = "8000.",
+        seed = "0",
+        policy = "\"rule_based\""
+    )]
+    fn py_new(
+        simulations: usize,
+        max_depth: usize,
+        c_puct: f32,
+        value_scale: f32,
+        seed: u64,
+        policy: &str,
+    ) -> Result<Self> {
+        ensure!(simulations > 0, "simulations must be positive");
+        ensure!(max_depth > 0, "max_depth must be positive");
+        ensure!(c_puct >= 0., "c_puct must not be negative");
+        ensure!(value_scale > 0., "value_scale must be positive");
+        Ok(Self {
+            simulations,
+            max_depth,
+            c_puct,
+            value_scale,
+            seed,
+            policy: policy.parse()?,
+        })
+    }
+
+    fn __repr__(&self) -> String {
+        format!("{self:?}")
+    }
+}
+
+impl MctsBatchAgent {
+    /// `inner` must have been created with `player_ids`.
+    #[must_use]
+    pub fn new(inner: Box<dyn BatchAgent + Send>, player_ids: &[u8], config: MctsConfig) -> Self {
+        let rng = ChaCha12Rng::seed_from_u64(config.seed);
+        Self {
+            inner,
+            player_ids: player_ids.to_vec(),
+            config,
+            rng,
+            deadlines: vec![None; player_ids.len()],
+        }
+    }
+
+    /// Returns the most visited one among `candidates`.
+    fn search(
+        &mut self,
+        state: &PlayerState,
+        events: &[Event],
+        candidates: Vec<Event>,
+        priors: Vec<f32>,
+        deadline: Option<Instant>,
+    ) -> Result<Option<Event>> {
+        let player_id = state.player_id();
+        let rollout = Rollout {
+            player_id,
+            rollouts: 1,
+            seed: 0,
+            policy: self.config.policy,
+            rule: state.rule(),
+        };
+
+        let mut root = Node::default();
+        for _ in 0..self.config.simulations {
+            if deadline.is_some_and(|d| Instant::now() >= d) {
+                break;
+            }
+            let world = rollout.sample_state(events, &mut self.rng)?;
+
+            // Indices of the children along the path.
+            let mut path = vec![root.select(&candidates, &priors, self.config.c_puct)];
+            let action = root.children[path[0]].action.clone();
+            let end = rollout.play_out(world, &action, |state| {
+                if path.len() >= self.config.max_depth {
+                    return Ok(None);
+                }
+                let candidates = state.reaction_candidates();
+                if candidates.len() < 2 {
+                    return Ok(None);
+                }
+                let priors = vec![1. / candidates.len() as f32; candidates.len()];
+                let node = root.descend(&path);
+                let idx = node.select(&candidates, &priors, self.config.c_puct);
+                path.push(idx);
+                Ok(Some(node.children[idx].action.clone()))
+            })?;
+
+            let value = end.delta as f32 / self.config.value_scale;
+            let mut node = &mut root;
+            for idx in path {
+                let child = &mut node.children[idx];
+                child.visits += 1;
+                child.value_sum += value;
+                node = &mut child.node;
+            }
+        }
+
+        // `max_by` returns the last max element, hence the `rev`, so that
+        // ties are broken by the order of the candidates.
+        let best = root.children.into_iter().rev().max_by(|l, r| {
+            (l.visits, l.prior)
+                .partial_cmp(&(r.visits, r.prior))
+                .unwrap()
+        });
+        Ok(best.map(|c| c.action))
+    }
+}
+
+impl Node {
+    /// Returns the index of the child to visit among the ones of
+    /// `candidates`, which are the reactions available in the current world,
+    /// adding the ones not seen before.
+    fn select(&mut self, candidates: &[Event], priors: &[f32], c_puct: f32) -> usize {
+        let idxs: Vec<_> = candidates
+            .iter()
+            .zip(priors)
+            .map(|(action, &prior)| {
+                self.children
+                    .iter()
+                    .position(|c| c.action == *action)
+                    .unwrap_or_else(|| {
+                        self.children.push(Child {
+                            action: action.clone(),
+                            prior,
+                            visits: 0,
+                            value_sum: 0.,
+                            node: Node::default(),
+                        });
+                        self.children.len() - 1
+                    })
+            })
+            .collect();
+
+        // Only the children available in this world count, as in ISMCTS.
+        let total_visits = idxs.iter().map(|&i| self.children[i].visits).sum::<u32>();
+        let sqrt_total = (total_visits.max(1) as f32).sqrt();
+        idxs.into_iter()
+            .rev()
+            .max_by(|&l, &r| {
+                let score = |i: usize| {
+                    let child = &self.children[i];
+                    let q = if child.visits > 0 {
+                        child.value_sum / child.visits as f32
+                    } else {
+                        0.
+                    };
+                    let u = c_puct * child.prior * sqrt_total / (1 + child.visits) as f32;
+                    q + u
+                };
+                score(l).total_cmp(&score(r))
+            })
+            .unwrap()
+    }
+
+    fn descend(&mut self, path: &[usize]) -> &mut Self {
+        path.iter()
+            .fold(self, |node, &idx| &mut node.children[idx].node)
+    }
+}
+
+/// Softmax of the q values in `meta` over the legal actions, where the
+/// probability of an action ID is shared evenly among the candidates of it,
+/// for example multiple kan choices. Falls back to uniform if `meta` does not
+/// have q values.
+fn priors(state: &PlayerState, candidates: &[Event], meta: Option<&Metadata>) -> Vec<f32> {
+    let uniform = vec![1. / candidates.len() as f32; candidates.len()];
+    let Some((q_values, mask_bits)) = meta.and_then(|m| Some((m.q_values.as_ref()?, m.mask_bits?)))
+    else {
+        return uniform;
+    };
+
+    let mut probs = [0.; ACTION_SPACE];
+    let actions = (0..ACTION_SPACE).filter(|i| mask_bits & (1 << i) != 0);
+    let max_q = q_values.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+    for (action, &q) in actions.zip(q_values) {
+        probs[action] = (q - max_q).exp();
+    }
+
+    let action_ids: Vec<_> = candidates.iter().map(|c| state.action_id_of(c)).collect();
+    let mut shares = [0_u8; ACTION_SPACE];
+    for &id in action_ids.iter().flatten() {
+        shares[id] += 1;
+    }
+    let mut ret: Vec<_> = action_ids
+        .iter()
+        .map(|id| id.map_or(0., |id| probs[id] / shares[id] as f32))
+        .collect();
+    let sum = ret.iter().sum::<f32>();
+    if sum <= 0. {
+        return uniform;
+    }
+    for p in &mut ret {
+        *p /= sum;
+    }
+    ret
+}
+
+impl BatchAgent for MctsBatchAgent {
+    fn name(&self) -> String {
+        format!("mcts({})", self.inner.name())
+    }
+
+    fn need_oracle_obs(&self) -> bool {
+        self.inner.need_oracle_obs()
+    }
+
+    fn set_scene(
+        &mut self,
+        index: usize,
+        log: &[EventExt],
+        state: &PlayerState,
+        invisible_state: Option<InvisibleState>,
+    ) -> Result<()> {
+        self.inner.set_scene(index, log, state, invisible_state)
+    }
+
+    fn get_reaction(
+        &mut self,
+        index: usize,
+        log: &[EventExt],
+        state: &PlayerState,
+        invisible_state: Option<InvisibleState>,
+    ) -> Result<EventExt> {
+        ensure!(
+            state.player_id() == self.player_ids[index],
+            "state of player {} at index {index}",
+            state.player_id(),
+        );
+        let reaction = self
+            .inner
+            .get_reaction(index, log, state, invisible_state)?;
+        let candidates = state.reaction_candidates();
+        if candidates.len() < 2 {
+            return Ok(reaction);
+        }
+
+        let priors = priors(state, &candidates, reaction.meta.as_ref());
+        let events: Vec<_> = log.iter().map(|ev| ev.event.clone()).collect();
+        let deadline = self.deadlines[index];
+        let Some(event) = self
+            .search(state, &events, candidates, priors, deadline)
+            .context("failed to search")?
+        else {
+            return Ok(reaction);
+        };
+        Ok(EventExt {
+            event,
+            meta: reaction.meta,
+            timing: reaction.timing,
+        })
+    }
+
+    fn set_deadline(&mut self, index: usize, deadline: Option<Instant>) {
+        self.deadlines[index] = deadline;
+        self.inner.set_deadline(index, deadline);
+    }
+
+    fn start_game(&mut self, index: usize) -> Result<()> {
+        self.inner.start_game(index)
+    }
+
+    fn end_kyoku(&mut self, index: usize) -> Result<()> {
+        self.inner.end_kyoku(index)
+    }
+
+    fn end_game(&mut self, index: usize, game_result: &GameResult) -> Result<()> {
+        self.inner.end_game(index, game_result)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::agent::Tsumogiri;
+    use serde_json as json;
+
+    const LOG: &str = r#"
+        {"type":"start_kyoku","bakaze":"E","dora_marker":"1s","kyoku":1,"honba":0,"kyotaku":0,"oya":0,"scores":[25000,25000,25000,25000],"tehais":[["1m","2m","3m","9m","2p","3p","5p","6p","7s","8s","9s","S","W"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"]]}
+        {"type":"tsumo","actor":0,"pai":"4p"}
+        {"type":"dahai","actor":0,"pai":"9m","tsumogiri":false}
+        {"type":"tsumo","actor":1,"pai":"?"}
+        {"type":"dahai","actor":1,"pai":"E","tsumogiri":false}
+        {"type":"pon","actor":3,"target":1,"pai":"E","consumed":["E","E"]}
+        {"type":"dahai","actor":3,"pai":"1p","tsumogiri":false}
+        {"type":"tsumo","actor":0,"pai":"P"}
+        {"type":"dahai","actor":0,"pai":"P","tsumogiri":true}
+        {"type":"tsumo","actor":1,"pai":"?"}
+        {"type":"dahai","actor":1,"pai":"5s","tsumogiri":false}
+        {"type":"tsumo","actor":2,"pai":"?"}
+        {"type":"dahai","actor":2,"pai":"N","tsumogiri":true}
+        {"type":"tsumo","actor":3,"pai":"?"}
+        {"type":"dahai","actor":3,"pai":"2m","tsumogiri":false}
+        {"type":"tsumo","actor":0,"pai":"S"}
+    "#;
+
+    fn state_and_log() -> (PlayerState, Vec<EventExt>) {
+        let mut state = PlayerState::new(0);
+        let mut log = vec![];
+        for line in LOG.trim().lines() {
+            let ev: Event = json::from_str(line).unwrap();
+            state.update(&ev).unwrap();
+            log.push(EventExt::no_meta(ev));
+        }
+        (state, log)
+    }
+
+    #[test]
+    fn candidates() {
+        let (state, _) = state_and_log();
+        let candidates = state.reaction_candidates();
+        // 13 distinct tiles to discard, plus riichi.
+        assert_eq!(candidates.len(), 14);
+        assert!(candidates.contains(&Event::Reach {
+            actor: 0,
+            open: false,
+        }));
+        for ev in &candidates {
+            state.validate_reaction(ev).unwrap();
+        }
+
+        let meta = Metadata {
+            q_values: Some(vec![1.; 14]),
+            mask_bits: Some(
+                candidates
+                    .iter()
+                    .map(|c| 1 << state.action_id_of(c).unwrap())
+                    .sum(),
+            ),
+            ..Default::default()
+        };
+        let priors = priors(&state, &candidates, Some(&meta));
+        assert!((priors.iter().sum::<f32>() - 1.).abs() < 1e-5);
+        assert!(priors.iter().all(|&p| (p - 1. / 14.).abs() < 1e-5));
+    }
+
+    #[test]
+    fn search() {
+        let (state, log) = state_and_log();
+        let config = MctsConfig {
+            simulations: 16,
+            seed: 1009,
+            ..Default::default()
+        };
+        let react = || {
+            let inner = Tsumogiri::new_batched(&[0]).unwrap();
+            let mut agent = MctsBatchAgent::new(Box::new(inner), &[0], config.clone());
+            agent.set_scene(0, &log, &state, None).unwrap();
+            agent.get_reaction(0, &log, &state, None).unwrap().event
+        };
+
+        let reaction = react();
+        state.validate_reaction(&reaction).unwrap();
+        // Deterministic with the same seed.
+        assert_eq!(react(), reaction);
+    }
+    #[test]
+    fn deadline() {
+        let (state, log) = state_and_log();
+        let inner = Tsumogiri::new_batched(&[0]).unwrap();
+        let mut agent = MctsBatchAgent::new(Box::new(inner), &[0], MctsConfig::default());
+
+        // Already due, so it falls back to the reaction of `inner`.
+        agent.set_deadline(0, Some(Instant::now()));
+        agent.set_scene(0, &log, &state, None).unwrap();
+        let reaction = agent.get_reaction(0, &log, &state, None).unwrap().event;
+        let mut inner = Tsumogiri::new_batched(&[0]).unwrap();
+        inner.set_scene(0, &log, &state, None).unwrap();
+        assert_eq!(
+            reaction,
+            inner.get_reaction(0, &log, &state, None).unwrap().event
+        );
+
+        agent.set_deadline(0, None);
+        agent.set_scene(0, &log, &state, None).unwrap();
+        let reaction = agent.get_reaction(0, &log, &state, None).unwrap().event;
+        state.validate_reaction(&reaction).unwrap();
+    }
+}
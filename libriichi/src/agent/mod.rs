@@ -1,11 +1,23 @@
 mod akochan;
 mod batchify;
 mod defs;
+mod ensemble;
+#[cfg(feature = "grpc")]
+mod grpc;
+mod mcts;
 mod mortal;
+mod rule_based;
 mod tsumogiri;
 
 pub use akochan::AkochanAgent;
 pub use batchify::BatchifiedAgent;
-pub use defs::{Agent, BatchAgent, InvisibleState};
-pub use mortal::MortalBatchAgent;
+pub use defs::{Agent, AsyncBatchAgent, BatchAgent, BoxFuture, InvisibleState};
+pub use ensemble::{EnsembleBatchAgent, EnsembleStrategy};
+#[cfg(feature = "grpc")]
+pub use grpc::GrpcBatchAgent;
+pub use mcts::{MctsBatchAgent, MctsConfig};
+pub use mortal::{
+    BatchReaction, EngineBackend, EngineConfig, Fallback, MortalBatchAgent, Sampling,
+};
+pub use rule_based::RuleBased;
 pub use tsumogiri::Tsumogiri;
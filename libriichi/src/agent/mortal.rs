@@ -1,26 +1,38 @@
-use super::{BatchAgent, InvisibleState};
-use crate::consts::ACTION_SPACE;
+use super::{Agent, BatchAgent, InvisibleState, RuleBased, Tsumogiri};
+use crate::consts::{ObsVersion, ACTION_SPACE, ORACLE_OBS_SHAPE};
 use crate::mjai::{Event, EventExt, Metadata};
-use crate::state::PlayerState;
+use crate::state::{PlayerState, SuitPerm};
 use crate::{must_tile, tu8};
+use std::mem;
+use std::str::FromStr;
 use std::time::{Duration, Instant};
 
-use anyhow::{ensure, Context, Result};
+use anyhow::{anyhow, bail, ensure, Context, Result};
 use ndarray::prelude::*;
-use numpy::{PyArray1, PyArray2};
+#[cfg(feature = "python")]
+use numpy::{PyArray2, PyArray3};
+#[cfg(feature = "python")]
 use pyo3::prelude::*;
+use rand::distributions::{Distribution, WeightedIndex};
+use rand::prelude::*;
+use rand_chacha::ChaCha12Rng;
+use tracing::debug_span;
 
 pub struct MortalBatchAgent {
-    engine: PyObject,
+    engine: Box<dyn EngineBackend + Send>,
     is_oracle: bool,
     enable_quick_eval: bool,
     enable_rule_based_agari_guard: bool,
     name: String,
     player_ids: Vec<u8>,
-
-    states: Vec<Array2<f32>>,
-    invisible_states: Vec<Array2<f32>>,
-    masks: Vec<Array1<bool>>,
+    obs_version: ObsVersion,
+
+    // Flat buffers of the batch to evaluate, where the features are encoded
+    // in place and then handed over to numpy without copying.
+    states: Vec<f32>,
+    invisible_states: Vec<f32>,
+    masks: Vec<bool>,
+    batch_len: usize,
     actions: Vec<usize>,
 
     q_values: Vec<[f32; ACTION_SPACE]>,
@@ -33,29 +45,184 @@ pub struct MortalBatchAgent {
     action_idxs: Vec<usize>,
     kan_action_idxs: Vec<Option<usize>>,
     quick_eval_reactions: Vec<Option<Event>>,
+
+    sampling: Option<Sampling>,
+    rng: ChaCha12Rng,
+
+    fallback: Option<Fallback>,
+    /// The error of the engine on the current batch, if any.
+    batch_error: Option<String>,
+    error_count: u32,
+}
+
+/// How the engine of `MortalBatchAgent` is set up, which a Python engine
+/// tells by its attributes of the same names.
+#[derive(Debug, Clone, Default)]
+pub struct EngineConfig {
+    pub name: String,
+    pub is_oracle: bool,
+    pub enable_quick_eval: bool,
+    pub enable_rule_based_agari_guard: bool,
+    pub obs_version: ObsVersion,
+    pub sampling: Option<Sampling>,
+    /// Seed of the sampling, random if `None`.
+    pub seed: Option<u64>,
+    pub fallback: Option<Fallback>,
+}
+
+/// The actions, the q values, the masks and whether each action is the
+/// greedy one, for each obs of a batch.
+pub type BatchReaction = (
+    Vec<usize>,
+    Vec<[f32; ACTION_SPACE]>,
+    Vec<[bool; ACTION_SPACE]>,
+    Vec<bool>,
+);
+
+/// Where `MortalBatchAgent` evaluates its batches, which is the
+/// `react_batch` method of a Python engine by default.
+pub trait EngineBackend {
+    /// `obs` is of shape `(N, C, 34)`, `masks` of `(N, ACTION_SPACE)`, and
+    /// `invisible_obs`, if the engine is an oracle, of `(N, C', 34)`.
+    fn react_batch(
+        &mut self,
+        obs: Array3<f32>,
+        masks: Array2<bool>,
+        invisible_obs: Option<Array3<f32>>,
+    ) -> Result<BatchReaction>;
+}
+
+#[cfg(feature = "python")]
+impl EngineBackend for PyObject {
+    fn react_batch(
+        &mut self,
+        obs: Array3<f32>,
+        masks: Array2<bool>,
+        invisible_obs: Option<Array3<f32>>,
+    ) -> Result<BatchReaction> {
+        Python::with_gil(|py| {
+            let obs = PyArray3::from_owned_array(py, obs);
+            let masks = PyArray2::from_owned_array(py, masks);
+            let invisible_obs = invisible_obs.map(|v| PyArray3::from_owned_array(py, v));
+
+            let args = (obs, masks, invisible_obs);
+            self.as_ref(py)
+                .call_method1("react_batch", args)
+                .context("failed to execute `react_batch` on Python engine")?
+                .extract()
+                .context("failed to extract to Rust type")
+        })
+    }
+}
+
+/// Overrides the action chosen by the engine by sampling from the q values on
+/// the Rust side, which keeps the batching path intact.
+///
+/// It is read from the optional attributes `sampling_epsilon`,
+/// `sampling_temp`, `sampling_top_k` and `sampling_seed` of the engine, and
+/// is only enabled when `sampling_epsilon` is set.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sampling {
+    /// Probability of sampling instead of taking the argmax.
+    pub epsilon: f32,
+    /// Temperature of the softmax over the q values.
+    pub temperature: f32,
+    /// Only sample from the `top_k` best legal actions, 0 for all.
+    pub top_k: usize,
+}
+
+/// What `MortalBatchAgent` reacts with when the engine fails, instead of
+/// propagating the error and forfeiting the seat.
+///
+/// It is read from the optional attribute `fallback` of the engine, either
+/// `"tsumogiri"` or `"rule_based"`. The incident is reported in the
+/// `engine_error` and `engine_error_count` fields of the metadata of the
+/// reaction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fallback {
+    Tsumogiri,
+    RuleBased,
+}
+
+impl FromStr for Fallback {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "tsumogiri" => Ok(Self::Tsumogiri),
+            "rule_based" => Ok(Self::RuleBased),
+            _ => bail!("unknown fallback {s}, expected tsumogiri or rule_based"),
+        }
+    }
+}
+
+impl Fallback {
+    fn react(self, actor: u8, log: &[EventExt], state: &PlayerState) -> Result<EventExt> {
+        match self {
+            Self::Tsumogiri => Tsumogiri(actor).react(log, state, None),
+            Self::RuleBased => RuleBased(actor).react(log, state, None),
+        }
+    }
 }
 
 impl MortalBatchAgent {
+    #[cfg(feature = "python")]
     pub fn new(engine: PyObject, player_ids: &[u8]) -> Result<Self> {
-        ensure!(player_ids.iter().all(|&id| matches!(id, 0..=3)));
+        let config = Python::with_gil(|py| {
+            let obj = engine.as_ref(py);
+            ensure!(obj.getattr("react_batch")?.is_callable());
+
+            let name = obj.getattr("name")?.extract()?;
+            let is_oracle = obj.getattr("is_oracle")?.extract()?;
+            let enable_quick_eval = obj.getattr("enable_quick_eval")?.extract()?;
+            let enable_rule_based_agari_guard =
+                obj.getattr("enable_rule_based_agari_guard")?.extract()?;
+            let obs_version = extract_opt(obj, "obs_version")?.unwrap_or_default();
+
+            let sampling = extract_opt(obj, "sampling_epsilon")?
+                .map(|epsilon| {
+                    let temperature = extract_opt(obj, "sampling_temp")?.unwrap_or(1.);
+                    let top_k = extract_opt(obj, "sampling_top_k")?.unwrap_or(0);
+                    Sampling::new(epsilon, temperature, top_k)
+                })
+                .transpose()?;
+            let seed = extract_opt(obj, "sampling_seed")?;
+            let fallback = extract_opt::<String>(obj, "fallback")?
+                .map(|s| s.parse())
+                .transpose()?;
+
+            anyhow::Ok(EngineConfig {
+                name,
+                is_oracle,
+                enable_quick_eval,
+                enable_rule_based_agari_guard,
+                obs_version,
+                sampling,
+                seed,
+                fallback,
+            })
+        })?;
+        Self::with_backend(Box::new(engine), config, player_ids)
+    }
 
-        let (name, is_oracle, enable_quick_eval, enable_rule_based_agari_guard) =
-            Python::with_gil(|py| {
-                let obj = engine.as_ref(py);
-                ensure!(obj.getattr("react_batch")?.is_callable());
-
-                let name = obj.getattr("name")?.extract()?;
-                let is_oracle = obj.getattr("is_oracle")?.extract()?;
-                let enable_quick_eval = obj.getattr("enable_quick_eval")?.extract()?;
-                let enable_rule_based_agari_guard =
-                    obj.getattr("enable_rule_based_agari_guard")?.extract()?;
-                Ok((
-                    name,
-                    is_oracle,
-                    enable_quick_eval,
-                    enable_rule_based_agari_guard,
-                ))
-            })?;
+    /// Creates an agent that evaluates its batches on `engine` instead of a
+    /// Python engine.
+    pub fn with_backend(
+        engine: Box<dyn EngineBackend + Send>,
+        config: EngineConfig,
+        player_ids: &[u8],
+    ) -> Result<Self> {
+        ensure!(player_ids.iter().all(|&id| matches!(id, 0..=3)));
+        let EngineConfig {
+            name,
+            is_oracle,
+            enable_quick_eval,
+            enable_rule_based_agari_guard,
+            obs_version,
+            sampling,
+            seed,
+            fallback,
+        } = config;
 
         let size = player_ids.len();
         Ok(Self {
@@ -65,10 +232,12 @@ impl MortalBatchAgent {
             enable_rule_based_agari_guard,
             name,
             player_ids: player_ids.to_vec(),
+            obs_version,
 
             states: vec![],
             invisible_states: vec![],
             masks: vec![],
+            batch_len: 0,
             actions: vec![],
 
             q_values: vec![],
@@ -85,43 +254,68 @@ impl MortalBatchAgent {
             } else {
                 vec![]
             },
+
+            sampling,
+            rng: seed.map_or_else(ChaCha12Rng::from_entropy, ChaCha12Rng::seed_from_u64),
+
+            fallback,
+            batch_error: None,
+            error_count: 0,
         })
     }
 
+    pub fn set_sampling(&mut self, sampling: Option<Sampling>) {
+        self.sampling = sampling;
+    }
+
+    pub fn set_fallback(&mut self, fallback: Option<Fallback>) {
+        self.fallback = fallback;
+    }
+
+    /// Number of reactions made by the fallback so far.
+    #[inline]
+    #[must_use]
+    pub const fn error_count(&self) -> u32 {
+        self.error_count
+    }
+
     fn evaluate(&mut self) -> Result<()> {
-        if self.states.is_empty() {
+        if self.batch_len == 0 {
             return Ok(());
         }
 
         let start = Instant::now();
-        self.last_batch_size = self.states.len();
-
-        (self.actions, self.q_values, self.masks_recv, self.is_greedy) = Python::with_gil(|py| {
-            let states: Vec<_> = self
-                .states
-                .drain(..)
-                .map(|v| PyArray2::from_owned_array(py, v))
-                .collect();
-            let masks: Vec<_> = self
-                .masks
-                .drain(..)
-                .map(|v| PyArray1::from_owned_array(py, v))
-                .collect();
-            let invisible_states: Option<Vec<_>> = self.is_oracle.then(|| {
-                self.invisible_states
-                    .drain(..)
-                    .map(|v| PyArray2::from_owned_array(py, v))
-                    .collect()
-            });
-
-            let args = (states, masks, invisible_states);
-            self.engine
-                .as_ref(py)
-                .call_method1("react_batch", args)
-                .context("failed to execute `react_batch` on Python engine")?
-                .extract()
-                .context("failed to extract to Rust type")
-        })?;
+        self.batch_error = None;
+        let n = mem::take(&mut self.batch_len);
+        self.last_batch_size = n;
+        let _span = debug_span!("engine_call", batch_size = n).entered();
+
+        let (rows, cols) = self.obs_version.obs_shape();
+        let states = take_batch(&mut self.states, (n, rows, cols));
+        let masks = take_batch(&mut self.masks, (n, ACTION_SPACE));
+        let invisible_states = self.is_oracle.then(|| {
+            let shape = (n, ORACLE_OBS_SHAPE.0, ORACLE_OBS_SHAPE.1);
+            take_batch(&mut self.invisible_states, shape)
+        });
+
+        (self.actions, self.q_values, self.masks_recv, self.is_greedy) =
+            self.engine.react_batch(states, masks, invisible_states)?;
+        ensure!(
+            self.actions.len() == n
+                && self.q_values.len() == n
+                && self.masks_recv.len() == n
+                && self.is_greedy.len() == n,
+            "the engine returned a batch of a wrong size, expected {n}",
+        );
+
+        if let Some(sampling) = self.sampling {
+            for (i, action) in self.actions.iter_mut().enumerate() {
+                let (a, is_greedy) =
+                    sampling.choose(&self.q_values[i], &self.masks_recv[i], &mut self.rng);
+                *action = a;
+                self.is_greedy[i] = is_greedy;
+            }
+        }
 
         self.last_eval_elapsed = Instant::now()
             .checked_duration_since(start)
@@ -130,13 +324,36 @@ impl MortalBatchAgent {
         Ok(())
     }
 
+    /// Encodes the obs into the next slot of the batch, and returns the index
+    /// of the slot.
+    fn push_obs(
+        &mut self,
+        state: &PlayerState,
+        at_kan_select: bool,
+        invisible_state: Option<&InvisibleState>,
+    ) -> usize {
+        let shape = self.obs_version.obs_shape();
+        let offset = self.states.len();
+        self.states.resize(offset + shape.0 * shape.1, 0.);
+        let arr = ArrayViewMut2::from_shape(shape, &mut self.states[offset..]).unwrap();
+        let mask = state.encode_obs_into(at_kan_select, self.obs_version, SuitPerm::IDENTITY, arr);
+        self.masks.extend_from_slice(&mask);
+        if let Some(invisible_state) = invisible_state {
+            self.invisible_states.extend(invisible_state.iter());
+        }
+
+        self.batch_len += 1;
+        self.batch_len - 1
+    }
+
     fn gen_meta(&self, state: &PlayerState, action_idx: usize) -> Metadata {
         let q_values = self.q_values[action_idx];
         let masks = self.masks_recv[action_idx];
         let is_greedy = self.is_greedy[action_idx];
+        let action = self.actions[action_idx];
 
         let mut mask_bits = 0;
-        let q_values_compact = q_values
+        let q_values_compact: Vec<_> = q_values
             .into_iter()
             .zip(masks)
             .enumerate()
@@ -147,17 +364,42 @@ impl MortalBatchAgent {
             })
             .collect();
 
+        // Softmax over the legal actions only.
+        let max_q = q_values_compact
+            .iter()
+            .copied()
+            .fold(f32::NEG_INFINITY, f32::max);
+        let exp_sum: f32 = q_values_compact.iter().map(|&q| (q - max_q).exp()).sum();
+        let prob = masks
+            .get(action)
+            .filter(|&&m| m)
+            .map(|_| (q_values[action] - max_q).exp() / exp_sum);
+        let value = Some(max_q).filter(|q| q.is_finite());
+
         Metadata {
             q_values: Some(q_values_compact),
             mask_bits: Some(mask_bits),
             is_greedy: Some(is_greedy),
             shanten: Some(state.shanten()),
             at_furiten: Some(state.at_furiten()),
+            prob,
+            value,
             ..Default::default()
         }
     }
 }
 
+/// Moves the flat buffer out as an array of `shape`, leaving an empty one with
+/// the same capacity for the next batch.
+fn take_batch<T, Sh, D>(buf: &mut Vec<T>, shape: Sh) -> Array<T, D>
+where
+    Sh: ShapeBuilder<Dim = D>,
+    D: Dimension,
+{
+    let v = mem::replace(buf, Vec::with_capacity(buf.capacity()));
+    Array::from_shape_vec(shape, v).expect("batch buffer of a wrong size")
+}
+
 impl BatchAgent for MortalBatchAgent {
     #[inline]
     fn name(&self) -> String {
@@ -227,22 +469,10 @@ impl BatchAgent for MortalBatchAgent {
         };
 
         if need_kan_select {
-            let (kan_feature, kan_mask) = state.encode_obs(true);
-            self.states.push(kan_feature);
-            self.masks.push(kan_mask);
-            if let Some(invisible_state) = invisible_state.clone() {
-                self.invisible_states.push(invisible_state);
-            }
-            self.kan_action_idxs[index] = Some(self.states.len() - 1);
-        }
-
-        let (feature, mask) = state.encode_obs(false);
-        self.states.push(feature);
-        self.masks.push(mask);
-        if let Some(invisible_state) = invisible_state {
-            self.invisible_states.push(invisible_state);
+            self.kan_action_idxs[index] =
+                Some(self.push_obs(state, true, invisible_state.as_ref()));
         }
-        self.action_idxs[index] = self.states.len() - 1;
+        self.action_idxs[index] = self.push_obs(state, false, invisible_state.as_ref());
 
         Ok(())
     }
@@ -250,7 +480,7 @@ impl BatchAgent for MortalBatchAgent {
     fn get_reaction(
         &mut self,
         index: usize,
-        _: &[EventExt],
+        log: &[EventExt],
         state: &PlayerState,
         _: Option<InvisibleState>,
     ) -> Result<EventExt> {
@@ -261,13 +491,46 @@ impl BatchAgent for MortalBatchAgent {
         }
 
         if !self.evaluated {
-            self.evaluate()?;
+            if let Err(err) = self.evaluate() {
+                if self.fallback.is_none() {
+                    return Err(err);
+                }
+                self.batch_error = Some(format!("{err:#}"));
+            }
             self.evaluated = true;
         }
+
+        let kan_select_idx = self.kan_action_idxs[index].take();
+        let result = match &self.batch_error {
+            Some(err) => Err(anyhow!("{err}")),
+            None => self.engine_reaction(index, state, kan_select_idx),
+        };
+        let (err, fallback) = match (result, self.fallback) {
+            (Err(err), Some(fallback)) => (err, fallback),
+            (result, _) => return result,
+        };
+
+        self.error_count += 1;
+        let actor = self.player_ids[index];
+        log::warn!("engine failed for player {actor}, falling back to {fallback:?}: {err:#}");
+        let mut ev = fallback.react(actor, log, state)?;
+        let meta = ev.meta.get_or_insert_with(Default::default);
+        meta.engine_error = Some(format!("{err:#}"));
+        meta.engine_error_count = Some(self.error_count);
+        Ok(ev)
+    }
+}
+
+impl MortalBatchAgent {
+    fn engine_reaction(
+        &mut self,
+        index: usize,
+        state: &PlayerState,
+        kan_select_idx: Option<usize>,
+    ) -> Result<EventExt> {
         let start = Instant::now();
 
         let action_idx = self.action_idxs[index];
-        let kan_select_idx = self.kan_action_idxs[index].take();
 
         let actor = self.player_ids[index];
         let akas_in_hand = state.akas_in_hand();
@@ -313,7 +576,7 @@ impl BatchAgent for MortalBatchAgent {
                     state.brief_info()
                 );
 
-                Event::Reach { actor }
+                Event::Reach { actor, open: false }
             }
 
             38 => {
@@ -329,9 +592,9 @@ impl BatchAgent for MortalBatchAgent {
                 let first = pai.next();
 
                 let can_akaize_consumed = match pai.as_u8() {
-                    tu8!(3m) | tu8!(4m) => akas_in_hand[0],
-                    tu8!(3p) | tu8!(4p) => akas_in_hand[1],
-                    tu8!(3s) | tu8!(4s) => akas_in_hand[2],
+                    tu8!(3m) | tu8!(4m) => akas_in_hand[0] > 0,
+                    tu8!(3p) | tu8!(4p) => akas_in_hand[1] > 0,
+                    tu8!(3s) | tu8!(4s) => akas_in_hand[2] > 0,
                     _ => false,
                 };
                 let consumed = if can_akaize_consumed {
@@ -358,9 +621,9 @@ impl BatchAgent for MortalBatchAgent {
                     .context("invalid state: no last kawa tile")?;
 
                 let can_akaize_consumed = match pai.as_u8() {
-                    tu8!(4m) | tu8!(6m) => akas_in_hand[0],
-                    tu8!(4p) | tu8!(6p) => akas_in_hand[1],
-                    tu8!(4s) | tu8!(6s) => akas_in_hand[2],
+                    tu8!(4m) | tu8!(6m) => akas_in_hand[0] > 0,
+                    tu8!(4p) | tu8!(6p) => akas_in_hand[1] > 0,
+                    tu8!(4s) | tu8!(6s) => akas_in_hand[2] > 0,
                     _ => false,
                 };
                 let consumed = if can_akaize_consumed {
@@ -388,9 +651,9 @@ impl BatchAgent for MortalBatchAgent {
                 let last = pai.prev();
 
                 let can_akaize_consumed = match pai.as_u8() {
-                    tu8!(6m) | tu8!(7m) => akas_in_hand[0],
-                    tu8!(6p) | tu8!(7p) => akas_in_hand[1],
-                    tu8!(6s) | tu8!(7s) => akas_in_hand[2],
+                    tu8!(6m) | tu8!(7m) => akas_in_hand[0] > 0,
+                    tu8!(6p) | tu8!(7p) => akas_in_hand[1] > 0,
+                    tu8!(6s) | tu8!(7s) => akas_in_hand[2] > 0,
                     _ => false,
                 };
                 let consumed = if can_akaize_consumed {
@@ -414,9 +677,9 @@ impl BatchAgent for MortalBatchAgent {
                     .context("invalid state: no last kawa tile")?;
 
                 let can_akaize_consumed = match pai.as_u8() {
-                    tu8!(5m) => akas_in_hand[0],
-                    tu8!(5p) => akas_in_hand[1],
-                    tu8!(5s) => akas_in_hand[2],
+                    tu8!(5m) => akas_in_hand[0] > 0,
+                    tu8!(5p) => akas_in_hand[1] > 0,
+                    tu8!(5s) => akas_in_hand[2] > 0,
                     _ => false,
                 };
                 let consumed = if can_akaize_consumed {
@@ -479,9 +742,9 @@ impl BatchAgent for MortalBatchAgent {
                     }
                 } else {
                     let can_akaize_target = match tile.as_u8() {
-                        tu8!(5m) => akas_in_hand[0],
-                        tu8!(5p) => akas_in_hand[1],
-                        tu8!(5s) => akas_in_hand[2],
+                        tu8!(5m) => akas_in_hand[0] > 0,
+                        tu8!(5p) => akas_in_hand[1] > 0,
+                        tu8!(5s) => akas_in_hand[2] > 0,
                         _ => false,
                     };
                     let (pai, consumed) = if can_akaize_target {
@@ -519,7 +782,10 @@ impl BatchAgent for MortalBatchAgent {
                     state.brief_info()
                 );
 
-                Event::Ryukyoku { deltas: None }
+                Event::Ryukyoku {
+                    deltas: None,
+                    reason: None,
+                }
             }
 
             // 45
@@ -542,6 +808,162 @@ impl BatchAgent for MortalBatchAgent {
         Ok(EventExt {
             event,
             meta: Some(meta),
+            timing: None,
+        })
+    }
+}
+
+/// Returns `None` if `attr` is missing or `None`.
+#[cfg(feature = "python")]
+fn extract_opt<'a, T: FromPyObject<'a>>(obj: &'a PyAny, attr: &str) -> PyResult<Option<T>> {
+    if obj.hasattr(attr)? {
+        obj.getattr(attr)?.extract()
+    } else {
+        Ok(None)
+    }
+}
+
+impl Sampling {
+    pub fn new(epsilon: f32, temperature: f32, top_k: usize) -> Result<Self> {
+        ensure!(
+            (0. ..=1.).contains(&epsilon),
+            "epsilon must be within [0, 1], got {epsilon}",
+        );
+        ensure!(
+            temperature > 0.,
+            "temperature must be positive, got {temperature}",
+        );
+        Ok(Self {
+            epsilon,
+            temperature,
+            top_k,
         })
     }
+
+    /// Returns the chosen action and whether it is the greedy one.
+    fn choose<R: Rng>(
+        &self,
+        q_values: &[f32; ACTION_SPACE],
+        masks: &[bool; ACTION_SPACE],
+        rng: &mut R,
+    ) -> (usize, bool) {
+        let mut legal: Vec<_> = (0..ACTION_SPACE).filter(|&i| masks[i]).collect();
+        legal.sort_by(|&l, &r| q_values[r].total_cmp(&q_values[l]));
+        let greedy = legal.first().copied().unwrap_or(ACTION_SPACE - 1);
+        if legal.len() <= 1 || rng.gen::<f32>() >= self.epsilon {
+            return (greedy, true);
+        }
+
+        if self.top_k > 0 {
+            legal.truncate(self.top_k);
+        }
+        let max_q = q_values[greedy];
+        let weights = legal
+            .iter()
+            .map(|&i| ((q_values[i] - max_q) / self.temperature).exp());
+        let action = WeightedIndex::new(weights).map_or(greedy, |dist| legal[dist.sample(rng)]);
+        (action, action == greedy)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::t;
+
+    #[test]
+    fn sampling() {
+        let mut q_values = [0.; ACTION_SPACE];
+        let mut masks = [false; ACTION_SPACE];
+        for (i, q) in [(3, 1.), (10, 2.), (20, 0.5), (45, 1.9)] {
+            q_values[i] = q;
+            masks[i] = true;
+        }
+        let mut rng = ChaCha12Rng::seed_from_u64(0);
+
+        let greedy = Sampling::new(0., 1., 0).unwrap();
+        for _ in 0..100 {
+            assert_eq!(greedy.choose(&q_values, &masks, &mut rng), (10, true));
+        }
+
+        let top2 = Sampling::new(1., 1., 2).unwrap();
+        let mut counts = [0; ACTION_SPACE];
+        for _ in 0..1000 {
+            let (action, is_greedy) = top2.choose(&q_values, &masks, &mut rng);
+            assert_eq!(is_greedy, action == 10);
+            counts[action] += 1;
+        }
+        assert!(counts[10] > 0 && counts[45] > 0);
+        assert_eq!(counts[10] + counts[45], 1000);
+
+        // A near-zero temperature degenerates to argmax.
+        let cold = Sampling::new(1., 1e-3, 0).unwrap();
+        for _ in 0..100 {
+            assert_eq!(cold.choose(&q_values, &masks, &mut rng).0, 10);
+        }
+
+        Sampling::new(1.5, 1., 0).unwrap_err();
+        Sampling::new(0.5, 0., 0).unwrap_err();
+    }
+
+    struct FailingEngine;
+
+    impl EngineBackend for FailingEngine {
+        fn react_batch(
+            &mut self,
+            _: Array3<f32>,
+            _: Array2<bool>,
+            _: Option<Array3<f32>>,
+        ) -> Result<BatchReaction> {
+            bail!("engine is down")
+        }
+    }
+
+    #[test]
+    fn fallback() {
+        let log = r#"
+            {"type":"start_kyoku","bakaze":"E","dora_marker":"1s","kyoku":1,"honba":0,"kyotaku":0,"oya":0,"scores":[25000,25000,25000,25000],"tehais":[["1m","2m","3m","9m","2p","3p","5p","6p","7s","8s","9s","S","W"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"]]}
+            {"type":"tsumo","actor":0,"pai":"P"}
+        "#;
+        let mut state = PlayerState::new(0);
+        let log: Vec<_> = log
+            .trim()
+            .lines()
+            .map(|line| {
+                let ev: Event = serde_json::from_str(line).unwrap();
+                state.update(&ev).unwrap();
+                EventExt::no_meta(ev)
+            })
+            .collect();
+
+        let config = EngineConfig {
+            fallback: Some(Fallback::Tsumogiri),
+            ..Default::default()
+        };
+        let mut agent =
+            MortalBatchAgent::with_backend(Box::new(FailingEngine), config, &[0]).unwrap();
+        for i in 1..=2 {
+            agent.set_scene(0, &log, &state, None).unwrap();
+            let ev = agent.get_reaction(0, &log, &state, None).unwrap();
+            assert_eq!(
+                ev.event,
+                Event::Dahai {
+                    actor: 0,
+                    pai: t!(P),
+                    tsumogiri: true,
+                },
+            );
+            let meta = ev.meta.unwrap();
+            assert!(meta.engine_error.unwrap().contains("engine is down"));
+            assert_eq!(meta.engine_error_count, Some(i));
+        }
+        assert_eq!(agent.error_count(), 2);
+
+        agent.set_fallback(None);
+        agent.set_scene(0, &log, &state, None).unwrap();
+        agent.get_reaction(0, &log, &state, None).unwrap_err();
+
+        "rule_based".parse::<Fallback>().unwrap();
+        "resign".parse::<Fallback>().unwrap_err();
+    }
 }
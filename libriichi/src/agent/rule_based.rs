@@ -0,0 +1,179 @@
+use super::{Agent, BatchifiedAgent, InvisibleState};
+use crate::mjai::{Event, EventExt};
+use crate::must_tile;
+use crate::state::PlayerState;
+use crate::tile::Tile;
+
+use anyhow::{Context, Result};
+
+/// `RuleBased` is a simple deterministic baseline.
+///
+/// It always declares agari and riichi when possible and never calls. It
+/// discards by the shanten and ukeire after the discard, and folds by
+/// discarding the safest tile when any opponent has declared riichi while it
+/// is still far from tenpai.
+pub struct RuleBased(pub u8);
+
+impl RuleBased {
+    pub fn new_batched(player_ids: &[u8]) -> Result<BatchifiedAgent<Self>> {
+        BatchifiedAgent::new(|id| Ok(Self(id)), player_ids)
+    }
+
+    fn choose_discard(state: &PlayerState) -> Result<Tile> {
+        let riichi_opponents: Vec<_> = (1..4).filter(|&i| state.riichi_accepted()[i]).collect();
+        let fold = !riichi_opponents.is_empty() && state.shanten() >= 2;
+
+        let ukeire = state.ukeire();
+        let best = if fold {
+            ukeire.iter().max_by_key(|u| {
+                let tid = u.discard.as_usize();
+                let safety = riichi_opponents
+                    .iter()
+                    .map(|&i| Self::safety(state, i, tid))
+                    .min()
+                    .unwrap_or_default();
+                (safety, -u.shanten, u.total())
+            })
+        } else {
+            ukeire.iter().max_by_key(|u| {
+                let is_yaokyuu = u.discard.is_yaokyuu();
+                (-u.shanten, u.total(), is_yaokyuu)
+            })
+        }
+        .context("no discard candidate")?;
+
+        // Keep the aka if possible.
+        let tile = best.discard;
+        let candidates = state.discard_candidates_aka();
+        if candidates[tile.as_usize()] {
+            Ok(tile)
+        } else {
+            Ok(tile.akaize())
+        }
+    }
+
+    /// A rough estimation of how safe `tid` is against the player at
+    /// `opponent` (relative).
+    fn safety(state: &PlayerState, opponent: usize, tid: usize) -> u8 {
+        let tile = must_tile!(tid);
+        if state.kawa_overview()[opponent]
+            .iter()
+            .any(|t| t.deaka() == tile)
+        {
+            // genbutsu
+            return 100;
+        }
+        let seen = state.tiles_seen()[tid];
+        if tile.is_jihai() {
+            return 50 + seen * 10;
+        }
+        let num = tile.as_u8() % 9 + 1;
+        let base = match num {
+            1 | 9 => 30,
+            2 | 8 => 20,
+            _ => 10,
+        };
+        base + seen * 2
+    }
+}
+
+impl Agent for RuleBased {
+    fn name(&self) -> String {
+        "rule-based".to_owned()
+    }
+
+    fn react(
+        &mut self,
+        _: &[EventExt],
+        state: &PlayerState,
+        _: Option<InvisibleState>,
+    ) -> Result<EventExt> {
+        let actor = self.0;
+        let cans = state.last_cans();
+
+        let ev = if cans.can_tsumo_agari || cans.can_ron_agari {
+            let target = if cans.can_tsumo_agari {
+                actor
+            } else {
+                cans.target_actor
+            };
+            Event::Hora {
+                actor,
+                target,
+                deltas: None,
+                ura_markers: None,
+            }
+        } else if cans.can_riichi {
+            Event::Reach { actor, open: false }
+        } else if cans.can_discard {
+            let pai = Self::choose_discard(state)?;
+            Event::Dahai {
+                actor,
+                pai,
+                tsumogiri: state.last_self_tsumo() == Some(pai),
+            }
+        } else {
+            Event::None
+        };
+        Ok(EventExt::no_meta(ev))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::t;
+
+    use serde_json as json;
+
+    #[test]
+    fn discard_by_ukeire() {
+        let mut state = PlayerState::new(0);
+        let log = r#"
+            {"type":"start_kyoku","bakaze":"E","dora_marker":"9p","kyoku":1,"honba":0,"kyotaku":0,"oya":0,"scores":[25000,25000,25000,25000],"tehais":[["1m","2m","3m","4p","5pr","6p","7s","8s","9s","E","E","3s","C"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"]]}
+            {"type":"tsumo","actor":0,"pai":"5s"}
+        "#;
+        for line in log.trim().lines() {
+            state.update(&json::from_str(line).unwrap()).unwrap();
+        }
+        assert_eq!(RuleBased::choose_discard(&state).unwrap(), t!(C));
+
+        let mut agent = RuleBased(0);
+        let ev = agent.react(&[], &state, None).unwrap().event;
+        assert_eq!(
+            ev,
+            Event::Reach {
+                actor: 0,
+                open: false,
+            },
+        );
+
+        state.update(&ev).unwrap();
+        let ev = agent.react(&[], &state, None).unwrap().event;
+        assert_eq!(
+            ev,
+            Event::Dahai {
+                actor: 0,
+                pai: t!(C),
+                tsumogiri: false,
+            },
+        );
+    }
+
+    #[test]
+    fn fold() {
+        let mut state = PlayerState::new(0);
+        let log = r#"
+            {"type":"start_kyoku","bakaze":"E","dora_marker":"9p","kyoku":1,"honba":0,"kyotaku":0,"oya":3,"scores":[25000,25000,25000,25000],"tehais":[["1m","4m","7m","2p","5p","8p","3s","6s","9s","E","S","W","N"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"]]}
+            {"type":"tsumo","actor":3,"pai":"?"}
+            {"type":"reach","actor":3}
+            {"type":"dahai","actor":3,"pai":"5p","tsumogiri":true}
+            {"type":"reach_accepted","actor":3}
+            {"type":"tsumo","actor":0,"pai":"C"}
+        "#;
+        for line in log.trim().lines() {
+            state.update(&json::from_str(line).unwrap()).unwrap();
+        }
+        assert_eq!(RuleBased::choose_discard(&state).unwrap(), t!(5p));
+    }
+}
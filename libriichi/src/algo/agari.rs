@@ -8,41 +8,61 @@
 
 use super::point::Point;
 use super::shanten;
+use super::tables::{self, Asset};
 use crate::tile::Tile;
 use crate::{matches_tu8, must_tile, tu8};
-use std::cmp::Ordering;
-use std::io::prelude::*;
-use std::iter;
+use std::cmp::{Ordering, Reverse};
+use std::{io, iter};
 
+use anyhow::{ensure, Result};
 use boomphf::hashmap::BoomHashMap;
 use byteorder::{LittleEndian, ReadBytesExt};
-use flate2::read::GzDecoder;
 use once_cell::sync::Lazy;
+use riichi_macros::{pyclass, pymethods};
 
 const AGARI_TABLE_SIZE: usize = 9_362;
+/// Length of the decompressed data file.
+const AGARI_DATA_LEN: usize = 86_058;
+
+/// The han a yakuman is counted as in `Yaku`.
+pub const YAKUMAN_HAN: u8 = 13;
+
+/// The content of the asset is the decompressed data file, as the hash map
+/// itself cannot be mapped.
+pub(super) const AGARI_ASSET: Asset = Asset {
+    name: "agari.bin",
+    embedded: include_bytes!("data/agari.bin.gz"),
+    len: AGARI_DATA_LEN,
+    build: tables::decompress,
+};
 
 static AGARI_TABLE: Lazy<BoomHashMap<u32, Vec<Div>>> = Lazy::new(|| {
-    let mut raw = GzDecoder::new(&include_bytes!("data/agari.bin.gz")[..]);
+    let bytes = AGARI_ASSET.load();
+    let (keys, values) = parse_agari_table(&bytes).unwrap_or_else(|err| {
+        log::warn!("broken agari table, using the embedded one instead: {err:#}");
+        parse_agari_table(&AGARI_ASSET.build_embedded()).expect("broken embedded agari table")
+    });
+    BoomHashMap::new(keys, values)
+});
 
+fn parse_agari_table(mut raw: &[u8]) -> Result<(Vec<u32>, Vec<Vec<Div>>)> {
     let (keys, values) = (0..AGARI_TABLE_SIZE)
         .map(|_| {
-            let key = raw.read_u32::<LittleEndian>().unwrap();
-            let v_size = raw.read_u8().unwrap();
-            let value: Vec<_> = (0..v_size)
-                .map(|_| raw.read_u32::<LittleEndian>().unwrap())
-                .map(Div::from)
-                .collect();
-            (key, value)
+            let key = raw.read_u32::<LittleEndian>()?;
+            let v_size = raw.read_u8()?;
+            let value = (0..v_size)
+                .map(|_| raw.read_u32::<LittleEndian>().map(Div::from))
+                .collect::<io::Result<_>>()?;
+            Ok((key, value))
         })
+        .collect::<io::Result<Vec<_>>>()?
+        .into_iter()
         .unzip();
 
     // Ensure there is no data left to read.
-    let mut dummy = vec![0];
-    let size = raw.read(&mut dummy).unwrap();
-    assert_eq!(size, 0);
-
-    BoomHashMap::new(keys, values)
-});
+    ensure!(raw.is_empty(), "{} bytes left after the table", raw.len());
+    Ok((keys, values))
+}
 
 #[derive(Debug)]
 struct Div {
@@ -67,6 +87,77 @@ pub enum Agari {
     Yakuman(u8),
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Yaku {
+    pub name: &'static str,
+    /// `YAKUMAN_HAN` for each yakuman.
+    pub han: u8,
+}
+
+/// Fu breakdown of an agari.
+#[pyclass]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Fu {
+    /// 20, or 25 for chitoi which has nothing else.
+    #[pyo3(get)]
+    pub base: u8,
+    /// Kotsus and kantsus, including the open ones.
+    #[pyo3(get)]
+    pub melds: u8,
+    #[pyo3(get)]
+    pub pair: u8,
+    /// Kanchan, penchan and tanki waits.
+    #[pyo3(get)]
+    pub wait: u8,
+    /// Not counted for 平和自摸.
+    #[pyo3(get)]
+    pub tsumo: u8,
+    #[pyo3(get)]
+    pub menzen_ron: u8,
+    /// The sum rounded up to tens, and at least 30 except for 平和自摸.
+    #[pyo3(get)]
+    pub total: u8,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AgariDetail {
+    pub agari: Agari,
+    /// Yakus of the highest scoring division, followed by the additional
+    /// ones. Doras are not included.
+    pub yakus: Vec<Yaku>,
+    /// Fu of the highest scoring division, even if `agari` has its fu
+    /// omitted. Zero for kokushi.
+    pub fu: Fu,
+}
+
+/// A mentsu of a `Decomposition`, represented by its smallest tile.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mentsu {
+    Shuntsu(u8),
+    Ankou(u8),
+    /// A concealed kotsu completed by ron, which counts as open.
+    Minkou(u8),
+    Chi(u8),
+    Pon(u8),
+    /// Daiminkan or kakan.
+    Minkan(u8),
+    Ankan(u8),
+}
+
+/// One way to read an agari hand, with the melds included.
+#[pyclass]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Decomposition {
+    /// The pair, or the seven pairs of chitoi.
+    pub pairs: Vec<u8>,
+    /// The concealed ones first, followed by the melds. Empty for chitoi.
+    pub mentsu: Vec<Mentsu>,
+    /// Yakus of this reading alone, without doras or situational yakus.
+    /// `None` if there is no yaku in this reading.
+    pub agari: Option<Agari>,
+    pub fu: Fu,
+}
+
 #[derive(Debug)]
 pub struct AgariCalculator<'a> {
     /// Must include the winning tile (i.e. must be 3n+2)
@@ -87,6 +178,8 @@ pub struct AgariCalculator<'a> {
     /// ankou/ankan-related yakus like 三/四暗刻. It will not be used to
     /// determine 門前清自摸和.
     pub is_ron: bool,
+    /// Whether 断幺九 counts for an open hand.
+    pub kuitan: bool,
 }
 
 struct DivWorker<'sup, 'a> {
@@ -176,6 +269,118 @@ impl Ord for Agari {
     }
 }
 
+#[pymethods]
+impl Fu {
+    fn __repr__(&self) -> String {
+        format!("{self:?}")
+    }
+}
+
+impl Mentsu {
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Shuntsu(_) => "shuntsu",
+            Self::Ankou(_) => "ankou",
+            Self::Minkou(_) => "minkou",
+            Self::Chi(_) => "chi",
+            Self::Pon(_) => "pon",
+            Self::Minkan(_) => "minkan",
+            Self::Ankan(_) => "ankan",
+        }
+    }
+
+    #[must_use]
+    pub const fn tile(self) -> u8 {
+        match self {
+            Self::Shuntsu(t)
+            | Self::Ankou(t)
+            | Self::Minkou(t)
+            | Self::Chi(t)
+            | Self::Pon(t)
+            | Self::Minkan(t)
+            | Self::Ankan(t) => t,
+        }
+    }
+
+    /// The tile IDs of the mentsu, which are 4 for kans.
+    pub fn tiles(self) -> impl Iterator<Item = u8> {
+        let (t, n, step) = match self {
+            Self::Shuntsu(t) | Self::Chi(t) => (t, 3, 1),
+            Self::Ankou(t) | Self::Minkou(t) | Self::Pon(t) => (t, 3, 0),
+            Self::Minkan(t) | Self::Ankan(t) => (t, 4, 0),
+        };
+        (0..n).map(move |i| t + i * step)
+    }
+}
+
+impl Decomposition {
+    /// Returns all the tile IDs of the hand, melds included, sorted.
+    #[must_use]
+    pub fn tiles(&self) -> Vec<u8> {
+        let mut tiles: Vec<_> = self
+            .pairs
+            .iter()
+            .flat_map(|&p| [p, p])
+            .chain(self.mentsu.iter().flat_map(|m| m.tiles()))
+            .collect();
+        tiles.sort_unstable();
+        tiles
+    }
+}
+
+#[pymethods]
+impl Decomposition {
+    #[getter]
+    fn pairs(&self) -> Vec<String> {
+        self.pairs
+            .iter()
+            .map(|&t| must_tile!(t).to_string())
+            .collect()
+    }
+    /// A list of `(kind, tile)` where `tile` is the smallest one.
+    #[getter]
+    fn mentsu(&self) -> Vec<(&'static str, String)> {
+        self.mentsu
+            .iter()
+            .map(|m| (m.name(), must_tile!(m.tile()).to_string()))
+            .collect()
+    }
+    #[getter]
+    fn fu(&self) -> Fu {
+        self.fu.clone()
+    }
+    /// 0 for yakuman or if there is no yaku.
+    #[getter]
+    const fn han(&self) -> u8 {
+        match self.agari {
+            Some(Agari::Normal { han, .. }) => han,
+            _ => 0,
+        }
+    }
+    /// Number of yakumans, 0 if not a yakuman.
+    #[getter]
+    const fn yakuman(&self) -> u8 {
+        match self.agari {
+            Some(Agari::Yakuman(n)) => n,
+            _ => 0,
+        }
+    }
+
+    #[pyo3(name = "tiles")]
+    #[pyo3(text_signature = "($self, /)")]
+    fn tiles_py(&self) -> Vec<String> {
+        self.tiles()
+            .into_iter()
+            .map(|t| must_tile!(t).to_string())
+            .collect()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("{self:?}")
+    }
+}
+
 impl Agari {
     #[must_use]
     pub fn into_point(self, is_oya: bool) -> Point {
@@ -231,7 +436,7 @@ impl AgariCalculator<'_> {
             let fu = divs
                 .iter()
                 .map(|div| DivWorker::new(self, &tile14, div))
-                .map(|w| w.calc_fu(false))
+                .map(|w| w.calc_fu(false).total)
                 .max()?;
             Some(Agari::Normal {
                 fu,
@@ -240,6 +445,92 @@ impl AgariCalculator<'_> {
         }
     }
 
+    /// Same as `agari`, but also tells the yakus and fu breakdown behind the
+    /// result. `additional_yakus` are the situational ones described in
+    /// `agari`.
+    #[must_use]
+    pub fn agari_detail(&self, additional_yakus: &[Yaku], doras: u8) -> Option<AgariDetail> {
+        let additional_hans = additional_yakus.iter().map(|y| y.han).sum();
+        let agari = self.agari(additional_hans, doras)?;
+
+        let (mut yakus, fu) = if self.is_menzen && shanten::calc_kokushi(self.tehai) == -1 {
+            // 国士無双
+            let yaku = Yaku {
+                name: "kokushi_musou",
+                han: YAKUMAN_HAN,
+            };
+            (vec![yaku], Fu::default())
+        } else {
+            let (tile14, key) = get_tile14_and_key(self.tehai);
+            let divs = AGARI_TABLE.get(&key)?;
+            divs.iter()
+                .map(|div| DivWorker::new(self, &tile14, div))
+                .map(|w| {
+                    let mut yakus = vec![];
+                    let agari = w.search_yakus::<false>(Some(&mut yakus));
+                    (agari, yakus, w.calc_fu(w.has_pinfu()))
+                })
+                .max_by_key(|(agari, _, fu)| (*agari, fu.total))
+                .map(|(_, yakus, fu)| (yakus, fu))?
+        };
+        match agari {
+            Agari::Normal { .. } => yakus.extend_from_slice(additional_yakus),
+            // Normal yakus do not count along with a yakuman.
+            Agari::Yakuman(_) => yakus.retain(|y| y.han == YAKUMAN_HAN),
+        }
+
+        Some(AgariDetail { agari, yakus, fu })
+    }
+
+    /// Returns every way to read the hand into a pair and mentsu, or into
+    /// seven pairs, along with the yakus and fu of each, the highest scoring
+    /// first. Empty if the hand is not agari, or is kokushi, which has no such
+    /// reading.
+    #[must_use]
+    pub fn decompositions(&self) -> Vec<Decomposition> {
+        let (tile14, key) = get_tile14_and_key(self.tehai);
+        let Some(divs) = AGARI_TABLE.get(&key) else {
+            return vec![];
+        };
+
+        let melds: Vec<_> = self
+            .chis
+            .iter()
+            .map(|&t| Mentsu::Chi(t))
+            .chain(self.pons.iter().map(|&t| Mentsu::Pon(t)))
+            .chain(self.minkans.iter().map(|&t| Mentsu::Minkan(t)))
+            .chain(self.ankans.iter().map(|&t| Mentsu::Ankan(t)))
+            .collect();
+        let mut ret: Vec<_> = divs
+            .iter()
+            .map(|div| DivWorker::new(self, &tile14, div))
+            .map(|w| {
+                let (pairs, mentsu) = if w.div.has_chitoi {
+                    (w.chitoi_pairs().collect(), vec![])
+                } else {
+                    let kotsu = w.menzen_kotsu.iter().map(|&t| {
+                        if w.winning_tile_makes_minkou && t == self.winning_tile {
+                            Mentsu::Minkou(t)
+                        } else {
+                            Mentsu::Ankou(t)
+                        }
+                    });
+                    let shuntsu = w.menzen_shuntsu.iter().map(|&t| Mentsu::Shuntsu(t));
+                    let mentsu = shuntsu.chain(kotsu).chain(melds.iter().copied());
+                    (vec![w.pair_tile], mentsu.collect())
+                };
+                Decomposition {
+                    pairs,
+                    mentsu,
+                    agari: w.search_yakus::<false>(None),
+                    fu: w.calc_fu(w.has_pinfu()),
+                }
+            })
+            .collect();
+        ret.sort_by_key(|d| Reverse((d.agari, d.fu.total)));
+        ret
+    }
+
     fn search_yakus_impl(&self, return_if_any: bool) -> Option<Agari> {
         assert_eq!(
             self.is_menzen,
@@ -260,11 +551,11 @@ impl AgariCalculator<'_> {
             // Benchmark result indicates it is too trivial to use rayon here.
             divs.iter()
                 .map(|div| DivWorker::new(self, &tile14, div))
-                .find_map(|w| w.search_yakus::<true>())
+                .find_map(|w| w.search_yakus::<true>(None))
         } else {
             divs.iter()
                 .map(|div| DivWorker::new(self, &tile14, div))
-                .filter_map(|w| w.search_yakus::<false>())
+                .filter_map(|w| w.search_yakus::<false>(None))
                 .max()
         }
     }
@@ -337,13 +628,20 @@ impl<'sup, 'a> DivWorker<'sup, 'a> {
         self.all_kotsu_and_kantsu().chain(self.all_shuntsu())
     }
 
-    fn calc_fu(&self, has_pinfu: bool) -> u8 {
+    fn calc_fu(&self, has_pinfu: bool) -> Fu {
         if self.div.has_chitoi {
-            return 25;
+            return Fu {
+                base: 25,
+                total: 25,
+                ..Default::default()
+            };
         }
-        let mut fu = 20;
+        let mut fu = Fu {
+            base: 20,
+            ..Default::default()
+        };
 
-        fu += self
+        fu.melds += self
             .menzen_kotsu
             .iter()
             .map(|&t| {
@@ -355,19 +653,19 @@ impl<'sup, 'a> DivWorker<'sup, 'a> {
                 }
             })
             .sum::<u8>();
-        fu += self
+        fu.melds += self
             .sup
             .pons
             .iter()
             .map(|&t| if must_tile!(t).is_yaokyuu() { 4 } else { 2 })
             .sum::<u8>();
-        fu += self
+        fu.melds += self
             .sup
             .ankans
             .iter()
             .map(|&t| if must_tile!(t).is_yaokyuu() { 32 } else { 16 })
             .sum::<u8>();
-        fu += self
+        fu.melds += self
             .sup
             .minkans
             .iter()
@@ -375,44 +673,31 @@ impl<'sup, 'a> DivWorker<'sup, 'a> {
             .sum::<u8>();
 
         if matches_tu8!(self.pair_tile, P | F | C) {
-            fu += 2;
+            fu.pair += 2;
         } else {
             // As per [Tenhou's rule](https://tenhou.net/man/#RULE):
             //
             // > 連風牌は4符
             if self.pair_tile == self.sup.bakaze {
-                fu += 2;
+                fu.pair += 2;
             }
             if self.pair_tile == self.sup.jikaze {
-                fu += 2;
+                fu.pair += 2;
             }
         }
 
-        if fu == 20 {
-            return if !self.sup.is_menzen {
-                30
-            } else if has_pinfu {
-                if self.sup.is_ron {
-                    30
-                } else {
-                    20
-                }
-            } else if self.sup.is_ron {
-                40
-            } else {
-                30
-            };
-        }
-
         if !self.sup.is_ron {
-            fu += 2;
+            // 平和自摸 is fixed at 20 fu.
+            if !has_pinfu || !self.sup.is_menzen {
+                fu.tsumo = 2;
+            }
         } else if self.sup.is_menzen {
-            fu += 10;
+            fu.menzen_ron = 10;
         }
 
-        if !self.winning_tile_makes_minkou {
+        if !self.winning_tile_makes_minkou && !has_pinfu {
             if self.pair_tile == self.sup.winning_tile {
-                fu += 2;
+                fu.wait = 2;
             } else {
                 let is_kanchan_penchan = self.menzen_shuntsu.iter().any(|&s| {
                     s + 1 == self.sup.winning_tile
@@ -420,26 +705,39 @@ impl<'sup, 'a> DivWorker<'sup, 'a> {
                         || s % 9 == 6 && s == self.sup.winning_tile
                 });
                 if is_kanchan_penchan {
-                    fu += 2;
+                    fu.wait = 2;
                 }
             }
         }
 
-        ((fu - 1) / 10 + 1) * 10
+        let sum = fu.base + fu.melds + fu.pair + fu.wait + fu.tsumo + fu.menzen_ron;
+        fu.total = ((sum - 1) / 10 + 1) * 10;
+        if !(has_pinfu && self.sup.is_menzen && !self.sup.is_ron) {
+            // An open hand without any fu (喰い平和) is rounded up to 30.
+            fu.total = fu.total.max(30);
+        }
+        fu
     }
 
-    fn search_yakus<const RETURN_IF_ANY: bool>(&self) -> Option<Agari> {
-        let mut han = 0;
-        let mut yakuman = 0;
-
-        let has_pinfu = self.menzen_shuntsu.len() == 4
+    fn has_pinfu(&self) -> bool {
+        self.menzen_shuntsu.len() == 4
             && !matches_tu8!(self.pair_tile, P | F | C)
             && self.pair_tile != self.sup.bakaze
             && self.pair_tile != self.sup.jikaze
             && self.menzen_shuntsu.iter().any(|&s| {
                 let num = s % 9 + 1;
                 num <= 6 && s == self.sup.winning_tile || num >= 2 && s + 2 == self.sup.winning_tile
-            });
+            })
+    }
+
+    /// Pushes the found yakus into `yakus` if given.
+    fn search_yakus<const RETURN_IF_ANY: bool>(
+        &self,
+        mut yakus: Option<&mut Vec<Yaku>>,
+    ) -> Option<Agari> {
+        let mut han = 0;
+        let mut yakuman = 0;
+        let has_pinfu = self.has_pinfu();
 
         macro_rules! make_return {
             () => {
@@ -449,7 +747,7 @@ impl<'sup, 'a> DivWorker<'sup, 'a> {
                     let fu = if RETURN_IF_ANY || han >= 5 {
                         0
                     } else {
-                        self.calc_fu(has_pinfu)
+                        self.calc_fu(has_pinfu).total
                     };
                     Some(Agari::Normal { fu, han })
                 } else {
@@ -458,8 +756,15 @@ impl<'sup, 'a> DivWorker<'sup, 'a> {
             };
         }
         macro_rules! check_early_return {
-            ($($block:tt)*) => {{
+            ($name:expr, $($block:tt)*) => {{
+                let prev = han + yakuman * YAKUMAN_HAN;
                 $($block)*;
+                if let Some(yakus) = &mut yakus {
+                    yakus.push(Yaku {
+                        name: $name,
+                        han: han + yakuman * YAKUMAN_HAN - prev,
+                    });
+                }
                 if RETURN_IF_ANY {
                     make_return!();
                 }
@@ -468,19 +773,19 @@ impl<'sup, 'a> DivWorker<'sup, 'a> {
 
         if has_pinfu {
             // 平和
-            check_early_return! { han += 1 };
+            check_early_return! { "pinfu", han += 1 };
         }
         if self.div.has_chitoi {
             // 七対子
-            check_early_return! { han += 2 };
+            check_early_return! { "chiitoitsu", han += 2 };
         }
         if self.div.has_ryanpeikou {
             // 二盃口
-            check_early_return! { han += 3 };
+            check_early_return! { "ryanpeikou", han += 3 };
         }
         if self.div.has_chuuren {
             // 九蓮宝燈
-            check_early_return! { yakuman += 1 };
+            check_early_return! { "chuuren_poutou", yakuman += 1 };
         }
 
         let has_tanyao = if self.div.has_chitoi {
@@ -502,16 +807,16 @@ impl<'sup, 'a> DivWorker<'sup, 'a> {
                     kind < 3 && num > 0 && num < 8
                 })
         };
-        if has_tanyao {
+        if has_tanyao && (self.sup.kuitan || self.sup.is_menzen) {
             // 断幺九
-            check_early_return! { han += 1 };
+            check_early_return! { "tanyao", han += 1 };
         }
 
         let has_toitoi =
             !self.div.has_chitoi && self.menzen_shuntsu.is_empty() && self.sup.chis.is_empty();
         if has_toitoi {
             // 対々和
-            check_early_return! { han += 2 };
+            check_early_return! { "toitoi", han += 2 };
         }
 
         let mut isou_kind = None;
@@ -543,17 +848,18 @@ impl<'sup, 'a> DivWorker<'sup, 'a> {
         }
         if isou_kind.is_none() {
             // 字一色
-            check_early_return! { yakuman += 1 };
+            check_early_return! { "tsuuiisou", yakuman += 1 };
         } else if is_chinitsu_or_honitsu {
             // 混一色, 清一色
             let n = if has_jihai { 2 } else { 5 } + self.sup.is_menzen as u8;
-            check_early_return! { han += n };
+            let name = if has_jihai { "honitsu" } else { "chinitsu" };
+            check_early_return! { name, han += n };
         }
 
         if !self.div.has_chitoi {
             // 一盃口
             if self.div.has_ipeikou {
-                check_early_return! { han += 1 };
+                check_early_return! { "iipeikou", han += 1 };
             } else if !self.sup.ankans.is_empty()
                 && self.sup.is_menzen
                 && self.menzen_shuntsu.len() >= 2
@@ -571,15 +877,15 @@ impl<'sup, 'a> DivWorker<'sup, 'a> {
                     }
                 });
                 if has_ipeikou {
-                    check_early_return! { han += 1 };
+                    check_early_return! { "iipeikou", han += 1 };
                 }
             }
 
             // 一気通貫
             if self.sup.is_menzen && self.div.has_ittsuu {
-                check_early_return! { han += 2 };
+                check_early_return! { "ittsuu", han += 2 };
             } else if self.sup.chis.is_empty() && self.div.has_ittsuu {
-                check_early_return! { han += 1 };
+                check_early_return! { "ittsuu", han += 1 };
             } else if self.menzen_shuntsu.len() + self.sup.chis.len() >= 3 {
                 let mut kinds = [0; 3];
                 for s in self.all_shuntsu() {
@@ -593,7 +899,7 @@ impl<'sup, 'a> DivWorker<'sup, 'a> {
                     };
                 }
                 if kinds.contains(&0b111) {
-                    check_early_return! { han += 1 };
+                    check_early_return! { "ittsuu", han += 1 };
                 }
             }
 
@@ -606,7 +912,7 @@ impl<'sup, 'a> DivWorker<'sup, 'a> {
             if s_counter.contains(&0b111) {
                 // 三色同順
                 let n = if self.sup.is_menzen { 2 } else { 1 };
-                check_early_return! { han += n };
+                check_early_return! { "sanshoku_doujun", han += n };
             } else {
                 let mut k_counter = [0; 9];
                 for k in self.all_kotsu_and_kantsu() {
@@ -618,7 +924,7 @@ impl<'sup, 'a> DivWorker<'sup, 'a> {
                 }
                 if k_counter.contains(&0b111) {
                     // 三色同刻
-                    check_early_return! { han += 2 };
+                    check_early_return! { "sanshoku_doukou", han += 2 };
                 }
             }
 
@@ -626,34 +932,29 @@ impl<'sup, 'a> DivWorker<'sup, 'a> {
                 - self.winning_tile_makes_minkou as usize;
             match ankous_count {
                 // 四暗刻
-                4 => check_early_return! { yakuman += 1 },
+                4 => check_early_return! { "suuankou", yakuman += 1 },
                 // 三暗刻
-                3 => check_early_return! { han += 2 },
+                3 => check_early_return! { "sanankou", han += 2 },
                 _ => (),
             };
 
             let kans_count = self.sup.ankans.len() + self.sup.minkans.len();
             match kans_count {
                 // 四槓子
-                4 => check_early_return! { yakuman += 1 },
+                4 => check_early_return! { "suukantsu", yakuman += 1 },
                 // 三槓子
-                3 => check_early_return! { han += 2 },
+                3 => check_early_return! { "sankantsu", han += 2 },
                 _ => (),
             };
 
             let has_ryuisou = self
                 .all_kotsu_and_kantsu()
                 .chain(iter::once(self.pair_tile))
-                .all(|k| {
-                    matches_tu8!(
-                        k,
-                        2s | 3s | 4s | 6s | 8s | F
-                    )
-                })
+                .all(|k| matches_tu8!(k, 2s | 3s | 4s | 6s | 8s | F))
                 && self.all_shuntsu().all(|s| s == tu8!(2s)); // only 234s is possible for shuntsu in ryuisou
             if has_ryuisou {
                 // 緑一色
-                check_early_return! { yakuman += 1 };
+                check_early_return! { "ryuuiisou", yakuman += 1 };
             }
 
             if !has_tanyao {
@@ -665,35 +966,40 @@ impl<'sup, 'a> DivWorker<'sup, 'a> {
                     }
                 }
                 if has_jihai[self.sup.bakaze as usize - 3 * 9] {
-                    // 役牌:門風牌
-                    check_early_return! { han += 1 };
+                    // 役牌:場風牌
+                    check_early_return! { "yakuhai_bakaze", han += 1 };
                 }
                 if has_jihai[self.sup.jikaze as usize - 3 * 9] {
-                    // 役牌:場風牌
-                    check_early_return! { han += 1 };
+                    // 役牌:門風牌
+                    check_early_return! { "yakuhai_jikaze", han += 1 };
                 }
 
-                let saneins = (4..7).filter(|&i| has_jihai[i]).count() as u8;
-                if saneins > 0 {
-                    // 役牌:三元牌
-                    check_early_return! { han += saneins };
-                    if saneins == 3 {
-                        // 大三元
-                        check_early_return! { yakuman += 1 };
-                    } else if saneins == 2 && matches_tu8!(self.pair_tile, P | F | C) {
-                        // 小三元
-                        check_early_return! { han += 2 };
+                // 役牌:三元牌
+                for (i, name) in [
+                    (4, "yakuhai_haku"),
+                    (5, "yakuhai_hatsu"),
+                    (6, "yakuhai_chun"),
+                ] {
+                    if has_jihai[i] {
+                        check_early_return! { name, han += 1 };
                     }
                 }
+                let saneins = (4..7).filter(|&i| has_jihai[i]).count();
+                if saneins == 3 {
+                    // 大三元
+                    check_early_return! { "daisangen", yakuman += 1 };
+                } else if saneins == 2 && matches_tu8!(self.pair_tile, P | F | C) {
+                    // 小三元
+                    check_early_return! { "shousangen", han += 2 };
+                }
 
                 let winds = (0..4).filter(|&i| has_jihai[i]).count() as u8;
-                #[allow(clippy::if_same_then_else)]
                 if winds == 4 {
                     // 大四喜
-                    check_early_return! { yakuman += 1 };
+                    check_early_return! { "daisuushii", yakuman += 1 };
                 } else if winds == 3 && matches_tu8!(self.pair_tile, E | S | W | N) {
                     // 小四喜
-                    check_early_return! { yakuman += 1 };
+                    check_early_return! { "shousuushii", yakuman += 1 };
                 }
             }
         }
@@ -721,10 +1027,10 @@ impl<'sup, 'a> DivWorker<'sup, 'a> {
                 if self.div.has_chitoi || has_toitoi {
                     if has_jihai {
                         // 混老頭
-                        check_early_return! { han += 2 };
+                        check_early_return! { "honroutou", han += 2 };
                     } else {
                         // 清老頭
-                        check_early_return! { yakuman += 1 };
+                        check_early_return! { "chinroutou", yakuman += 1 };
                     }
                 } else {
                     let is_junchan_or_chanta = self.all_shuntsu().all(|s| {
@@ -734,7 +1040,8 @@ impl<'sup, 'a> DivWorker<'sup, 'a> {
                     if is_junchan_or_chanta {
                         // 混全帯幺九, 純全帯幺九
                         let n = if has_jihai { 1 } else { 2 } + self.sup.is_menzen as u8;
-                        check_early_return! { han += n };
+                        let name = if has_jihai { "chanta" } else { "junchan" };
+                        check_early_return! { name, han += n };
                     }
                 }
             }
@@ -904,7 +1211,7 @@ pub fn check_ankan_after_riichi(tehai: &[u8; 34], len_div3: u8, tile: Tile, stri
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::hand::hand;
+    use crate::hand::{hand, tile34_to_vec};
 
     #[test]
     fn ankan_after_riichi() {
@@ -960,6 +1267,7 @@ mod test {
             jikaze: tu8!(S),
             winning_tile: tu8!(3m),
             is_ron: true,
+            kuitan: true,
         };
         let yaku = calc.search_yakus().unwrap();
         assert_eq!(yaku, Agari::Normal { fu: 40, han: 4 });
@@ -976,6 +1284,7 @@ mod test {
             jikaze: tu8!(E),
             winning_tile: tu8!(3m),
             is_ron: false,
+            kuitan: true,
         };
         let points = calc.agari(2, 0).unwrap().into_point(true);
         // 立直, 門前清自摸和
@@ -1000,6 +1309,7 @@ mod test {
             jikaze: tu8!(S),
             winning_tile: tu8!(5p),
             is_ron: true,
+            kuitan: true,
         };
         let yaku = calc.search_yakus().unwrap();
         assert_eq!(yaku, Agari::Normal { fu: 25, han: 3 });
@@ -1017,6 +1327,7 @@ mod test {
             jikaze: tu8!(S),
             winning_tile: tu8!(4m),
             is_ron: true,
+            kuitan: true,
         };
         let yaku = calc.search_yakus().unwrap();
         assert_eq!(yaku, Agari::Normal { fu: 30, han: 1 });
@@ -1033,6 +1344,7 @@ mod test {
             jikaze: tu8!(N),
             winning_tile: tu8!(3m),
             is_ron: false,
+            kuitan: true,
         };
         let yaku = calc.search_yakus().unwrap();
         assert_eq!(yaku, Agari::Normal { fu: 30, han: 4 });
@@ -1049,6 +1361,7 @@ mod test {
             jikaze: tu8!(E),
             winning_tile: tu8!(8p),
             is_ron: true,
+            kuitan: true,
         };
         assert_eq!(calc.search_yakus(), None);
 
@@ -1064,6 +1377,7 @@ mod test {
             jikaze: tu8!(E),
             winning_tile: tu8!(8p),
             is_ron: true,
+            kuitan: true,
         };
         let yaku = calc.search_yakus().unwrap();
         // 一盃口 (without ankan)
@@ -1081,6 +1395,7 @@ mod test {
             jikaze: tu8!(E),
             winning_tile: tu8!(8p),
             is_ron: true,
+            kuitan: true,
         };
         let yaku = calc.search_yakus().unwrap();
         // 一盃口 (with ankan)
@@ -1098,6 +1413,7 @@ mod test {
             jikaze: tu8!(E),
             winning_tile: tu8!(7m),
             is_ron: false,
+            kuitan: true,
         };
         let yaku = calc.search_yakus().unwrap();
         // 四暗刻
@@ -1120,6 +1436,7 @@ mod test {
             jikaze: tu8!(E),
             winning_tile: tu8!(8m),
             is_ron: true,
+            kuitan: true,
         };
         let yaku = calc.search_yakus().unwrap();
         // 平和, 二盃口
@@ -1142,6 +1459,7 @@ mod test {
             jikaze: tu8!(E),
             winning_tile: tu8!(9m),
             is_ron: true,
+            kuitan: true,
         };
         let yaku = calc.search_yakus().unwrap();
         // 一気通貫
@@ -1159,6 +1477,7 @@ mod test {
             jikaze: tu8!(E),
             winning_tile: tu8!(9m),
             is_ron: true,
+            kuitan: true,
         };
         let yaku = calc.search_yakus().unwrap();
         // 一気通貫
@@ -1176,6 +1495,7 @@ mod test {
             jikaze: tu8!(E),
             winning_tile: tu8!(8p),
             is_ron: false,
+            kuitan: true,
         };
         let yaku = calc.search_yakus().unwrap();
         // 門前清自摸和 is not accounted.
@@ -1193,6 +1513,7 @@ mod test {
             jikaze: tu8!(E),
             winning_tile: tu8!(C),
             is_ron: true,
+            kuitan: true,
         };
         let yaku = calc.search_yakus().unwrap();
         assert_eq!(yaku, Agari::Yakuman(3));
@@ -1209,6 +1530,7 @@ mod test {
             jikaze: tu8!(E),
             winning_tile: tu8!(1m),
             is_ron: false,
+            kuitan: true,
         };
         let yaku = calc.search_yakus().unwrap();
         // 純全, 三色
@@ -1226,6 +1548,7 @@ mod test {
             jikaze: tu8!(S),
             winning_tile: tu8!(5s),
             is_ron: true,
+            kuitan: true,
         };
         let yaku = calc.search_yakus().unwrap();
         // 三暗刻 (5s is ankou)
@@ -1245,6 +1568,7 @@ mod test {
             jikaze: tu8!(S),
             winning_tile: tu8!(E),
             is_ron: true,
+            kuitan: true,
         };
         let yaku = calc.search_yakus().unwrap();
         // 混全帯幺九, 役牌*1
@@ -1263,6 +1587,7 @@ mod test {
             jikaze: tu8!(N),
             winning_tile: tu8!(9m),
             is_ron: true,
+            kuitan: true,
         };
         let yaku = calc.search_yakus().unwrap();
         // 混一色, 混老頭, 役牌*3, 対々和
@@ -1272,7 +1597,7 @@ mod test {
         let fu = divs
             .iter()
             .map(|div| DivWorker::new(&calc, &tile14, div))
-            .map(|w| w.calc_fu(false))
+            .map(|w| w.calc_fu(false).total)
             .max()
             .unwrap();
         // 20 + tanki(2) + 1m(8) + 2z(4) + 7z(4) + 4z(32) = 70
@@ -1291,6 +1616,7 @@ mod test {
             jikaze: tu8!(E),
             winning_tile: tu8!(9m),
             is_ron: true,
+            kuitan: true,
         };
         let yaku = calc.search_yakus().unwrap();
         // 清一色, 一気通貫
@@ -1309,6 +1635,7 @@ mod test {
             jikaze: tu8!(E),
             winning_tile: tu8!(5p),
             is_ron: true,
+            kuitan: true,
         };
         let yaku = calc.search_yakus().unwrap();
         // 清一色, 断么九
@@ -1327,6 +1654,7 @@ mod test {
             jikaze: tu8!(E),
             winning_tile: tu8!(1s),
             is_ron: true,
+            kuitan: true,
         };
         let yaku = calc.search_yakus().unwrap();
         // 清一色, 一気通貫
@@ -1345,6 +1673,7 @@ mod test {
             jikaze: tu8!(E),
             winning_tile: tu8!(1m),
             is_ron: true,
+            kuitan: true,
         };
         let yaku = calc.search_yakus().unwrap();
         // 清一色, 一気通貫
@@ -1362,9 +1691,108 @@ mod test {
             jikaze: tu8!(S),
             winning_tile: tu8!(C),
             is_ron: true,
+            kuitan: true,
         };
         let yaku = calc.search_yakus().unwrap();
         // 三暗刻, 対々和, 混一色, 混老頭, 小三元, double 南, 白, 中
         assert!(matches!(yaku, Agari::Normal { han: 15, .. }));
     }
+
+    #[test]
+    fn decompositions() {
+        let tehai = hand("111222333m 45p 11z 6p").unwrap();
+        let mut calc = AgariCalculator {
+            tehai: &tehai,
+            is_menzen: true,
+            chis: &[],
+            pons: &[],
+            minkans: &[],
+            ankans: &[],
+            bakaze: tu8!(E),
+            jikaze: tu8!(S),
+            winning_tile: tu8!(6p),
+            is_ron: true,
+            kuitan: true,
+        };
+        // 三暗刻 over 一盃口
+        let divs = calc.decompositions();
+        assert_eq!(divs.len(), 2);
+        assert_eq!(divs[0].agari, Some(Agari::Normal { fu: 50, han: 2 }));
+        assert_eq!(divs[0].pairs, [tu8!(E)]);
+        assert!(divs[0].mentsu.contains(&Mentsu::Ankou(tu8!(1m))));
+        assert_eq!(divs[1].agari, Some(Agari::Normal { fu: 40, han: 1 }));
+        assert_eq!(divs[1].mentsu.len(), 4);
+        let tiles: Vec<_> = tile34_to_vec(&tehai).iter().map(|t| t.as_u8()).collect();
+        for div in &divs {
+            assert_eq!(div.tiles(), tiles);
+        }
+
+        let tehai = hand("1133m 5577p 99s 112z 2z").unwrap();
+        calc.tehai = &tehai;
+        calc.winning_tile = tu8!(S);
+        calc.is_ron = false;
+        let divs = calc.decompositions();
+        assert_eq!(divs.len(), 1);
+        assert_eq!(divs[0].pairs.len(), 7);
+        assert!(divs[0].mentsu.is_empty());
+        assert_eq!(divs[0].agari, Some(Agari::Normal { fu: 25, han: 2 }));
+
+        // 二盃口 is never read as 七対子.
+        let tehai = hand("112233m 445566p 7z 7z").unwrap();
+        calc.tehai = &tehai;
+        calc.winning_tile = tu8!(C);
+        let divs = calc.decompositions();
+        assert_eq!(divs.len(), 1);
+        assert_eq!(divs[0].mentsu.len(), 4);
+
+        // The melds are included and come last.
+        let tehai = hand("123m 456p 789s 1z 1z").unwrap();
+        calc.tehai = &tehai;
+        calc.is_menzen = false;
+        calc.pons = &[tu8!(P)];
+        calc.winning_tile = tu8!(E);
+        let divs = calc.decompositions();
+        assert_eq!(divs.len(), 1);
+        assert_eq!(divs[0].mentsu.last(), Some(&Mentsu::Pon(tu8!(P))));
+        let tiles = divs[0].tiles();
+        assert_eq!(tiles.len(), 14);
+        assert_eq!(tiles.iter().filter(|&&t| t == tu8!(P)).count(), 3);
+
+        // Kokushi has no reading.
+        let tehai = hand("19m 19p 19s 1234567z 1m").unwrap();
+        calc.tehai = &tehai;
+        calc.is_menzen = true;
+        calc.pons = &[];
+        assert!(calc.decompositions().is_empty());
+    }
+
+    #[test]
+    fn kuitan() {
+        let tehai = hand("234m 567m 34s 66p 5s").unwrap();
+        let mut calc = AgariCalculator {
+            tehai: &tehai,
+            is_menzen: false,
+            chis: &[],
+            pons: &[tu8!(2p)],
+            minkans: &[],
+            ankans: &[],
+            bakaze: tu8!(E),
+            jikaze: tu8!(S),
+            winning_tile: tu8!(5s),
+            is_ron: true,
+            kuitan: true,
+        };
+        // 断幺九
+        assert_eq!(calc.search_yakus(), Some(Agari::Normal { fu: 30, han: 1 }));
+
+        calc.kuitan = false;
+        assert!(!calc.has_yaku());
+
+        // Menzen tanyao is not affected.
+        let tehai = hand("234m 567m 34s 66p 222p 5s").unwrap();
+        calc.tehai = &tehai;
+        calc.is_menzen = true;
+        calc.pons = &[];
+        assert!(calc.has_yaku());
+    }
 }
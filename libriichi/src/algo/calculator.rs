@@ -0,0 +1,330 @@
+//! Python wrappers of the shanten and agari calculators, which work on a bare
+//! hand without any `PlayerState`.
+use super::agari::{self, Agari};
+use super::shanten;
+use crate::tu8;
+
+use anyhow::{ensure, Context, Result};
+use riichi_macros::{pyclass, pymethods};
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+/// Tehai is given as a 34-length list of tile counts, in the order of 1-9m,
+/// 1-9p, 1-9s and ESWNPFC.
+#[pyclass]
+#[pyo3(text_signature = "()")]
+#[derive(Debug, Clone, Default)]
+pub struct ShantenCalculator;
+
+/// Melds are given as lists of tile IDs in the order of `tehai`, where a chi
+/// is represented by its smallest tile.
+#[pyclass]
+#[pyo3(text_signature = "(*, bakaze, jikaze, kuitan=True)")]
+#[derive(Debug, Clone)]
+pub struct AgariCalculator {
+    /// Tile ID of the round wind, e.g. 27 for E.
+    #[pyo3(get, set)]
+    pub bakaze: u8,
+    /// Tile ID of the seat wind.
+    #[pyo3(get, set)]
+    pub jikaze: u8,
+    #[pyo3(get, set)]
+    pub kuitan: bool,
+}
+
+#[pyclass]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AgariResult {
+    /// 0 for yakuman.
+    #[pyo3(get)]
+    pub han: u8,
+    /// May be 0 if `han` is greater than 4.
+    #[pyo3(get)]
+    pub fu: u8,
+    /// Number of yakumans, 0 if not a yakuman.
+    #[pyo3(get)]
+    pub yakuman: u8,
+    #[pyo3(get)]
+    pub ron: i32,
+    #[pyo3(get)]
+    pub tsumo_oya: i32,
+    #[pyo3(get)]
+    pub tsumo_ko: i32,
+}
+
+#[pymethods]
+impl ShantenCalculator {
+    #[new]
+    const fn new() -> Self {
+        Self
+    }
+
+    /// Returns the minimum shanten among normal, chitoi and kokushi shapes,
+    /// where -1 means agari.
+    #[pyo3(text_signature = "($self, tehai, /)")]
+    pub(crate) fn calc_all(&self, tehai: Vec<u8>) -> Result<i8> {
+        let (tehai, len_div3) = to_tehai(&tehai)?;
+        Ok(shanten::calc_all(&tehai, len_div3))
+    }
+
+    #[pyo3(text_signature = "($self, tehai, /)")]
+    fn calc_normal(&self, tehai: Vec<u8>) -> Result<i8> {
+        let (tehai, len_div3) = to_tehai(&tehai)?;
+        Ok(shanten::calc_normal(&tehai, len_div3))
+    }
+
+    #[pyo3(text_signature = "($self, tehai, /)")]
+    fn calc_chitoi(&self, tehai: Vec<u8>) -> Result<i8> {
+        let (tehai, _) = to_tehai(&tehai)?;
+        Ok(shanten::calc_chitoi(&tehai))
+    }
+
+    #[pyo3(text_signature = "($self, tehai, /)")]
+    fn calc_kokushi(&self, tehai: Vec<u8>) -> Result<i8> {
+        let (tehai, _) = to_tehai(&tehai)?;
+        Ok(shanten::calc_kokushi(&tehai))
+    }
+}
+
+#[pymethods]
+impl AgariCalculator {
+    #[new]
+    #[args("*", kuitan = "true")]
+    pub(crate) fn new(bakaze: u8, jikaze: u8, kuitan: bool) -> Result<Self> {
+        for kaze in [bakaze, jikaze] {
+            ensure!(
+                (tu8!(E)..=tu8!(N)).contains(&kaze),
+                "{kaze} is not a wind tile",
+            );
+        }
+        Ok(Self {
+            bakaze,
+            jikaze,
+            kuitan,
+        })
+    }
+
+    /// Returns `None` if the hand is not agari or has no yaku.
+    ///
+    /// `tehai` must include `winning_tile` and exclude the melds.
+    /// `additional_hans` consists of yakus that depend on the situation, such
+    /// as riichi, menzen tsumo and haitei, see `agari::AgariCalculator::agari`
+    /// for details.
+    #[pyo3(text_signature = "(
+        $self,
+        tehai,
+        winning_tile,
+        is_ron,
+        /,
+        *,
+        chis = [],
+        pons = [],
+        minkans = [],
+        ankans = [],
+        additional_hans = 0,
+        doras = 0,
+    )")]
+    #[args(
+        "*",
+        chis = "vec![]",
+        pons = "vec![]",
+        minkans = "vec![]",
+        ankans = "vec![]",
+        additional_hans = "0",
+        doras = "0"
+    )]
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn calc(
+        &self,
+        tehai: Vec<u8>,
+        winning_tile: u8,
+        is_ron: bool,
+        chis: Vec<u8>,
+        pons: Vec<u8>,
+        minkans: Vec<u8>,
+        ankans: Vec<u8>,
+        additional_hans: u8,
+        doras: u8,
+    ) -> Result<Option<AgariResult>> {
+        let (tehai, len_div3) = to_tehai(&tehai)?;
+        check_agari_input(
+            &tehai,
+            len_div3,
+            winning_tile,
+            &chis,
+            &pons,
+            &minkans,
+            &ankans,
+        )?;
+
+        let calc = agari::AgariCalculator {
+            tehai: &tehai,
+            is_menzen: chis.is_empty() && pons.is_empty() && minkans.is_empty(),
+            chis: &chis,
+            pons: &pons,
+            minkans: &minkans,
+            ankans: &ankans,
+            bakaze: self.bakaze,
+            jikaze: self.jikaze,
+            winning_tile,
+            is_ron,
+            kuitan: self.kuitan,
+        };
+        let is_oya = self.jikaze == tu8!(E);
+        let ret = calc.agari(additional_hans, doras).map(|agari| {
+            let point = agari.into_point(is_oya);
+            let (han, fu, yakuman) = match agari {
+                Agari::Normal { fu, han } => (han, fu, 0),
+                Agari::Yakuman(n) => (0, 0, n),
+            };
+            AgariResult {
+                han,
+                fu,
+                yakuman,
+                ron: point.ron,
+                tsumo_oya: point.tsumo_oya,
+                tsumo_ko: point.tsumo_ko,
+            }
+        });
+        Ok(ret)
+    }
+
+    fn __repr__(&self) -> String {
+        format!("{self:?}")
+    }
+}
+
+#[pymethods]
+impl AgariResult {
+    fn __repr__(&self) -> String {
+        format!("{self:?}")
+    }
+}
+
+/// Validates a 34-length tehai and returns it along with its `len_div3`.
+pub(super) fn to_tehai(tehai: &[u8]) -> Result<([u8; 34], u8)> {
+    let tehai: [u8; 34] = tehai
+        .try_into()
+        .ok()
+        .with_context(|| format!("tehai must have 34 elements, got {}", tehai.len()))?;
+    ensure!(tehai.iter().all(|&c| c <= 4), "invalid tile count in tehai");
+    let len = tehai.iter().sum::<u8>();
+    ensure!(len <= 14 && len % 3 != 0, "invalid tehai length {len}");
+    Ok((tehai, len / 3))
+}
+
+/// Checks a 3n+2 tehai from `to_tehai` and its melds for
+/// `agari::AgariCalculator`.
+pub(super) fn check_agari_input(
+    tehai: &[u8; 34],
+    len_div3: u8,
+    winning_tile: u8,
+    chis: &[u8],
+    pons: &[u8],
+    minkans: &[u8],
+    ankans: &[u8],
+) -> Result<()> {
+    ensure!(tehai.iter().sum::<u8>() % 3 == 2, "tehai is not 3n+2");
+    let melds = chis.len() + pons.len() + minkans.len() + ankans.len();
+    ensure!(
+        len_div3 as usize + melds == 4,
+        "tehai has {len_div3} blocks with {melds} melds",
+    );
+    ensure!(
+        tehai.get(winning_tile as usize).copied().unwrap_or(0) > 0,
+        "winning tile {winning_tile} is not in tehai",
+    );
+    ensure!(
+        chis.iter().all(|&t| t < tu8!(E) && t % 9 < 7)
+            && [pons, minkans, ankans]
+                .into_iter()
+                .flatten()
+                .all(|&t| t < 34),
+        "invalid meld",
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::hand::hand;
+    use crate::{t, tuz};
+
+    #[test]
+    fn shanten() {
+        let calc = ShantenCalculator::new();
+        let tehai = hand("123456789m 1p 1s 12z").unwrap().to_vec();
+        assert_eq!(calc.calc_all(tehai.clone()).unwrap(), 2);
+        assert_eq!(calc.calc_normal(tehai).unwrap(), 2);
+
+        let tehai = hand("19m 19p 19s 1234567z").unwrap().to_vec();
+        assert_eq!(calc.calc_kokushi(tehai).unwrap(), 0);
+
+        calc.calc_all(vec![0; 33]).unwrap_err();
+        calc.calc_all(vec![0; 34]).unwrap_err();
+    }
+
+    #[test]
+    fn agari() {
+        agari::ensure_init();
+        let calc = AgariCalculator::new(tu8!(E), tu8!(S), true).unwrap();
+
+        // 2 han 40 fu: riichi and tanyao, closed ron on a kanchan.
+        let tehai = hand("234m 456p 567s 22s 777p").unwrap().to_vec();
+        let ret = calc
+            .calc(
+                tehai,
+                t!(5p).as_u8(),
+                true,
+                vec![],
+                vec![],
+                vec![],
+                vec![],
+                1,
+                0,
+            )
+            .unwrap()
+            .unwrap();
+        assert_eq!((ret.han, ret.fu, ret.yakuman), (2, 40, 0));
+        assert_eq!(ret.ron, 2600);
+
+        // Open hand without yaku.
+        let tehai = hand("234m 567s 11z 999p").unwrap().to_vec();
+        let ret = calc
+            .calc(
+                tehai,
+                tuz!(9p) as u8,
+                true,
+                vec![t!(6p).as_u8()],
+                vec![],
+                vec![],
+                vec![],
+                0,
+                0,
+            )
+            .unwrap();
+        assert!(ret.is_none());
+
+        let tehai = hand("19m 19p 19s 12345677z").unwrap().to_vec();
+        let ret = calc
+            .calc(
+                tehai,
+                t!(C).as_u8(),
+                false,
+                vec![],
+                vec![],
+                vec![],
+                vec![],
+                0,
+                0,
+            )
+            .unwrap()
+            .unwrap();
+        assert_eq!(ret.yakuman, 1);
+        assert_eq!((ret.tsumo_oya, ret.tsumo_ko), (16000, 8000));
+
+        AgariCalculator::new(tu8!(P), tu8!(E), true).unwrap_err();
+    }
+}
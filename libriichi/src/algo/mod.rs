@@ -2,5 +2,40 @@
 //! calculations and score lookups.
 
 pub mod agari;
+pub mod calculator;
 pub mod point;
+pub mod score;
 pub mod shanten;
+pub mod sp;
+pub mod tables;
+
+#[cfg(feature = "python")]
+use crate::py_helper::add_submodule;
+#[cfg(feature = "python")]
+use agari::{Decomposition, Fu};
+#[cfg(feature = "python")]
+use calculator::{AgariCalculator, AgariResult, ShantenCalculator};
+#[cfg(feature = "python")]
+use score::{ScoreConditions, ScoreResult};
+#[cfg(feature = "python")]
+use sp::{SpCalculator, SpCandidate};
+
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+
+#[cfg(feature = "python")]
+pub(crate) fn register_module(py: Python<'_>, prefix: &str, super_mod: &PyModule) -> PyResult<()> {
+    let m = PyModule::new(py, "algo")?;
+    m.add_class::<ShantenCalculator>()?;
+    m.add_class::<AgariCalculator>()?;
+    m.add_class::<AgariResult>()?;
+    m.add_class::<SpCalculator>()?;
+    m.add_class::<SpCandidate>()?;
+    m.add_class::<ScoreConditions>()?;
+    m.add_class::<ScoreResult>()?;
+    m.add_class::<Fu>()?;
+    m.add_class::<Decomposition>()?;
+    m.add_function(wrap_pyfunction!(score::calc_score_py, m)?)?;
+    m.add_function(wrap_pyfunction!(score::detect_yaku_py, m)?)?;
+    add_submodule(py, prefix, super_mod, m)
+}
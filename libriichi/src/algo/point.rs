@@ -216,3 +216,43 @@ impl Point {
         }
     }
 }
+
+/// Score deltas of an exhaustive ryukyoku by seat, where `oya` is the seat of
+/// the oya. Nagashi mangan is paid as a tsumo mangan, and replaces noten
+/// bappu if anyone has it.
+#[must_use]
+pub fn exhaustive_ryukyoku_deltas(
+    tenpai: [bool; 4],
+    nagashi_mangan: [bool; 4],
+    oya: usize,
+) -> [i32; 4] {
+    let mut deltas = [0; 4];
+    if nagashi_mangan.contains(&true) {
+        for (winner, _) in nagashi_mangan.iter().enumerate().filter(|&(_, &b)| b) {
+            let is_oya = winner == oya;
+            let point = Point::mangan(is_oya);
+            for (seat, delta) in deltas.iter_mut().enumerate() {
+                *delta += if seat == winner {
+                    point.tsumo_total(is_oya)
+                } else if seat == oya {
+                    -point.tsumo_oya
+                } else {
+                    -point.tsumo_ko
+                };
+            }
+        }
+        return deltas;
+    }
+
+    let (plus, minus) = match tenpai.iter().filter(|&&b| b).count() {
+        1 => (3000, -1000),
+        2 => (1500, -1500),
+        3 => (1000, -3000),
+        // 0 | 4
+        _ => (0, 0),
+    };
+    for (delta, is_tenpai) in deltas.iter_mut().zip(tenpai) {
+        *delta = if is_tenpai { plus } else { minus };
+    }
+    deltas
+}
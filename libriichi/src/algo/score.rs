@@ -0,0 +1,562 @@
+//! Full score calculation of an agari, from the hand and the situation to the
+//! points paid, with the yakus and fu breakdown along the way.
+use super::agari::{self, Agari, AgariDetail, Fu, Yaku};
+use super::calculator::check_agari_input;
+#[cfg(feature = "python")]
+use super::calculator::to_tehai;
+use crate::tu8;
+
+use anyhow::{bail, ensure, Result};
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+use riichi_macros::{pyclass, pymethods};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Meld {
+    /// Represented by its smallest tile.
+    Chi(u8),
+    Pon(u8),
+    /// Daiminkan or kakan.
+    Minkan(u8),
+    Ankan(u8),
+}
+
+/// The situation of an agari, everything other than the hand itself.
+#[pyclass]
+#[pyo3(text_signature = "(
+    *,
+    bakaze,
+    jikaze,
+    is_ron,
+    riichi = False,
+    double_riichi = False,
+    ippatsu = False,
+    haitei = False,
+    rinshan = False,
+    chankan = False,
+    doras = 0,
+    akas = 0,
+    uras = 0,
+    honba = 0,
+    kyotaku = 0,
+    kuitan = True,
+)")]
+#[derive(Debug, Clone, Default)]
+pub struct ScoreConditions {
+    /// Tile ID of the round wind, e.g. 27 for E.
+    #[pyo3(get, set)]
+    pub bakaze: u8,
+    /// Tile ID of the seat wind.
+    #[pyo3(get, set)]
+    pub jikaze: u8,
+    #[pyo3(get, set)]
+    pub is_ron: bool,
+    #[pyo3(get, set)]
+    pub riichi: bool,
+    /// Implies `riichi`.
+    #[pyo3(get, set)]
+    pub double_riichi: bool,
+    #[pyo3(get, set)]
+    pub ippatsu: bool,
+    /// Winning on the last tile, i.e. 海底摸月 for tsumo and 河底撈魚 for ron.
+    #[pyo3(get, set)]
+    pub haitei: bool,
+    #[pyo3(get, set)]
+    pub rinshan: bool,
+    #[pyo3(get, set)]
+    pub chankan: bool,
+    /// Number of doras in the hand and melds, excluding akas and uras.
+    #[pyo3(get, set)]
+    pub doras: u8,
+    #[pyo3(get, set)]
+    pub akas: u8,
+    #[pyo3(get, set)]
+    pub uras: u8,
+    #[pyo3(get, set)]
+    pub honba: u8,
+    #[pyo3(get, set)]
+    pub kyotaku: u8,
+    #[pyo3(get, set)]
+    pub kuitan: bool,
+}
+
+/// Points in `ron`, `tsumo_oya` and `tsumo_ko` include honba, while `total`
+/// also includes kyotaku.
+#[pyclass]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScoreResult {
+    /// Yakus with their han, followed by doras, akas and uras if any.
+    pub yakus: Vec<Yaku>,
+    /// 0 for yakuman.
+    #[pyo3(get)]
+    pub han: u8,
+    /// Always calculated, even for mangan and above.
+    #[pyo3(get)]
+    pub fu: Fu,
+    /// Number of yakumans, 0 if not a yakuman.
+    #[pyo3(get)]
+    pub yakuman: u8,
+    /// Paid by the discarder.
+    #[pyo3(get)]
+    pub ron: i32,
+    /// Paid by the oya for a tsumo by ko.
+    #[pyo3(get)]
+    pub tsumo_oya: i32,
+    /// Paid by each ko for a tsumo.
+    #[pyo3(get)]
+    pub tsumo_ko: i32,
+    /// Points the winner gains in total.
+    #[pyo3(get)]
+    pub total: i32,
+}
+
+impl Meld {
+    fn parse(kind: &str, tile: u8) -> Result<Self> {
+        let meld = match kind {
+            "chi" => Self::Chi(tile),
+            "pon" => Self::Pon(tile),
+            "minkan" | "daiminkan" | "kakan" => Self::Minkan(tile),
+            "ankan" => Self::Ankan(tile),
+            _ => bail!("invalid meld kind {kind}, expected chi, pon, minkan or ankan"),
+        };
+        Ok(meld)
+    }
+}
+
+#[pymethods]
+impl ScoreConditions {
+    #[new]
+    #[args(
+        "*",
+        riichi = "false",
+        double_riichi = "false",
+        ippatsu = "false",
+        haitei = "false",
+        rinshan = "false",
+        chankan = "false",
+        doras = "0",
+        akas = "0",
+        uras = "0",
+        honba = "0",
+        kyotaku = "0",
+        kuitan = "true"
+    )]
+    #[allow(clippy::too_many_arguments)]
+    fn py_new(
+        bakaze: u8,
+        jikaze: u8,
+        is_ron: bool,
+        riichi: bool,
+        double_riichi: bool,
+        ippatsu: bool,
+        haitei: bool,
+        rinshan: bool,
+        chankan: bool,
+        doras: u8,
+        akas: u8,
+        uras: u8,
+        honba: u8,
+        kyotaku: u8,
+        kuitan: bool,
+    ) -> Result<Self> {
+        let ret = Self {
+            bakaze,
+            jikaze,
+            is_ron,
+            riichi: riichi || double_riichi,
+            double_riichi,
+            ippatsu,
+            haitei,
+            rinshan,
+            chankan,
+            doras,
+            akas,
+            uras,
+            honba,
+            kyotaku,
+            kuitan,
+        };
+        ret.validate()?;
+        Ok(ret)
+    }
+
+    fn __repr__(&self) -> String {
+        format!("{self:?}")
+    }
+}
+
+#[pymethods]
+impl ScoreResult {
+    #[getter]
+    fn yakus(&self) -> Vec<(&'static str, u8)> {
+        self.yakus.iter().map(|y| (y.name, y.han)).collect()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("{self:?}")
+    }
+}
+
+impl ScoreConditions {
+    pub fn validate(&self) -> Result<()> {
+        for kaze in [self.bakaze, self.jikaze] {
+            ensure!(
+                (tu8!(E)..=tu8!(N)).contains(&kaze),
+                "{kaze} is not a wind tile",
+            );
+        }
+        ensure!(
+            self.riichi || !self.double_riichi,
+            "double riichi without riichi",
+        );
+        ensure!(self.riichi || !self.ippatsu, "ippatsu without riichi");
+        ensure!(self.riichi || self.uras == 0, "uras without riichi");
+        ensure!(!self.rinshan || !self.is_ron, "rinshan on ron");
+        ensure!(!self.chankan || self.is_ron, "chankan on tsumo");
+        ensure!(
+            !self.haitei || !self.rinshan && !self.chankan,
+            "haitei along with rinshan or chankan",
+        );
+        Ok(())
+    }
+
+    /// 門前清自摸和, (両)立直, 一発, 海底摸月, 河底撈魚, 嶺上開花 and 槍槓.
+    fn situational_yakus(&self, is_menzen: bool) -> Vec<Yaku> {
+        let candidates = [
+            ("double_riichi", 2, self.double_riichi),
+            ("riichi", 1, self.riichi && !self.double_riichi),
+            ("ippatsu", 1, self.ippatsu),
+            ("menzen_tsumo", 1, is_menzen && !self.is_ron),
+            ("haitei", 1, self.haitei && !self.is_ron),
+            ("houtei", 1, self.haitei && self.is_ron),
+            ("rinshan", 1, self.rinshan),
+            ("chankan", 1, self.chankan),
+        ];
+        candidates
+            .into_iter()
+            .filter(|&(_, _, cond)| cond)
+            .map(|(name, han, _)| Yaku { name, han })
+            .collect()
+    }
+}
+
+/// Returns `None` if the hand is not agari or has no yaku.
+///
+/// `tehai` must include `winning_tile` and exclude the melds.
+#[cfg(feature = "python")]
+#[pyfunction]
+#[pyo3(name = "calc_score")]
+#[pyo3(text_signature = "(tehai, melds, winning_tile, conditions, /)")]
+pub(super) fn calc_score_py(
+    tehai: Vec<u8>,
+    melds: Vec<(String, u8)>,
+    winning_tile: u8,
+    conditions: ScoreConditions,
+) -> Result<Option<ScoreResult>> {
+    let (tehai, _) = to_tehai(&tehai)?;
+    let melds = melds
+        .iter()
+        .map(|(kind, tile)| Meld::parse(kind, *tile))
+        .collect::<Result<Vec<_>>>()?;
+    calc_score(&tehai, &melds, winning_tile, &conditions)
+}
+
+/// Returns the yakus of the agari without doras, which is empty if it has no
+/// yaku.
+///
+/// `tehai` must include `winning_tile` and exclude the melds.
+#[cfg(feature = "python")]
+#[pyfunction]
+#[pyo3(name = "detect_yaku")]
+#[pyo3(text_signature = "(tehai, melds, winning_tile, conditions, /)")]
+pub(super) fn detect_yaku_py(
+    tehai: Vec<u8>,
+    melds: Vec<(String, u8)>,
+    winning_tile: u8,
+    conditions: ScoreConditions,
+) -> Result<Vec<(&'static str, u8)>> {
+    let (tehai, _) = to_tehai(&tehai)?;
+    let melds = melds
+        .iter()
+        .map(|(kind, tile)| Meld::parse(kind, *tile))
+        .collect::<Result<Vec<_>>>()?;
+    let yakus = detect_yaku(&tehai, &melds, winning_tile, &conditions)?;
+    Ok(yakus.into_iter().map(|y| (y.name, y.han)).collect())
+}
+
+pub fn calc_score(
+    tehai: &[u8; 34],
+    melds: &[Meld],
+    winning_tile: u8,
+    conditions: &ScoreConditions,
+) -> Result<Option<ScoreResult>> {
+    let Some(detail) = agari_detail(tehai, melds, winning_tile, conditions)? else {
+        return Ok(None);
+    };
+
+    let mut yakus = detail.yakus;
+    let (han, yakuman) = match detail.agari {
+        Agari::Normal { han, .. } => {
+            let doras = [
+                ("dora", conditions.doras),
+                ("aka_dora", conditions.akas),
+                ("ura_dora", conditions.uras),
+            ];
+            for (name, han) in doras {
+                if han > 0 {
+                    yakus.push(Yaku { name, han });
+                }
+            }
+            (han, 0)
+        }
+        Agari::Yakuman(n) => (0, n),
+    };
+
+    let is_oya = conditions.jikaze == tu8!(E);
+    let point = detail.agari.into_point(is_oya);
+    let honba = conditions.honba as i32;
+    let ron = point.ron + honba * 300;
+    let tsumo_oya = if is_oya {
+        0
+    } else {
+        point.tsumo_oya + honba * 100
+    };
+    let tsumo_ko = point.tsumo_ko + honba * 100;
+    let gain = if conditions.is_ron {
+        ron
+    } else {
+        point.tsumo_total(is_oya) + honba * 300
+    };
+
+    Ok(Some(ScoreResult {
+        yakus,
+        han,
+        fu: detail.fu,
+        yakuman,
+        ron,
+        tsumo_oya,
+        tsumo_ko,
+        total: gain + conditions.kyotaku as i32 * 1000,
+    }))
+}
+
+/// Returns the yakus of the agari without doras, which is empty if it has no
+/// yaku.
+pub fn detect_yaku(
+    tehai: &[u8; 34],
+    melds: &[Meld],
+    winning_tile: u8,
+    conditions: &ScoreConditions,
+) -> Result<Vec<Yaku>> {
+    let detail = agari_detail(tehai, melds, winning_tile, conditions)?;
+    Ok(detail.map(|d| d.yakus).unwrap_or_default())
+}
+
+fn agari_detail(
+    tehai: &[u8; 34],
+    melds: &[Meld],
+    winning_tile: u8,
+    conditions: &ScoreConditions,
+) -> Result<Option<AgariDetail>> {
+    conditions.validate()?;
+
+    let mut chis = vec![];
+    let mut pons = vec![];
+    let mut minkans = vec![];
+    let mut ankans = vec![];
+    for &meld in melds {
+        match meld {
+            Meld::Chi(t) => chis.push(t),
+            Meld::Pon(t) => pons.push(t),
+            Meld::Minkan(t) => minkans.push(t),
+            Meld::Ankan(t) => ankans.push(t),
+        }
+    }
+    let len_div3 = tehai.iter().sum::<u8>() / 3;
+    check_agari_input(
+        tehai,
+        len_div3,
+        winning_tile,
+        &chis,
+        &pons,
+        &minkans,
+        &ankans,
+    )?;
+    let is_menzen = chis.is_empty() && pons.is_empty() && minkans.is_empty();
+    ensure!(is_menzen || !conditions.riichi, "riichi with an open hand");
+
+    let calc = agari::AgariCalculator {
+        tehai,
+        is_menzen,
+        chis: &chis,
+        pons: &pons,
+        minkans: &minkans,
+        ankans: &ankans,
+        bakaze: conditions.bakaze,
+        jikaze: conditions.jikaze,
+        winning_tile,
+        is_ron: conditions.is_ron,
+        kuitan: conditions.kuitan,
+    };
+    let additional_yakus = conditions.situational_yakus(is_menzen);
+    let doras = conditions.doras + conditions.akas + conditions.uras;
+    Ok(calc.agari_detail(&additional_yakus, doras))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::hand::hand;
+    use crate::t;
+
+    fn names(ret: &ScoreResult) -> Vec<(&'static str, u8)> {
+        ret.yakus.iter().map(|y| (y.name, y.han)).collect()
+    }
+
+    #[test]
+    fn riichi_tsumo() {
+        agari::ensure_init();
+        let conditions = ScoreConditions {
+            bakaze: tu8!(E),
+            jikaze: tu8!(S),
+            riichi: true,
+            doras: 1,
+            honba: 1,
+            kyotaku: 1,
+            ..Default::default()
+        };
+        let tehai = hand("234m 678m 345p 456s 55s").unwrap();
+        let ret = calc_score(&tehai, &[], t!(4s).as_u8(), &conditions)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            names(&ret),
+            [
+                ("pinfu", 1),
+                ("tanyao", 1),
+                ("riichi", 1),
+                ("menzen_tsumo", 1),
+                ("dora", 1),
+            ],
+        );
+        assert_eq!((ret.han, ret.yakuman), (5, 0));
+        // 平和自摸 has no tsumo fu.
+        assert_eq!(
+            ret.fu,
+            Fu {
+                base: 20,
+                total: 20,
+                ..Default::default()
+            },
+        );
+        assert_eq!((ret.tsumo_oya, ret.tsumo_ko), (4100, 2100));
+        assert_eq!(ret.total, 9300);
+    }
+
+    #[test]
+    fn ron_fu_breakdown() {
+        agari::ensure_init();
+        let conditions = ScoreConditions {
+            bakaze: tu8!(E),
+            jikaze: tu8!(S),
+            is_ron: true,
+            riichi: true,
+            ..Default::default()
+        };
+        let tehai = hand("234m 456p 567s 22s 777p").unwrap();
+        let ret = calc_score(&tehai, &[], t!(5p).as_u8(), &conditions)
+            .unwrap()
+            .unwrap();
+        assert_eq!(names(&ret), [("tanyao", 1), ("riichi", 1)]);
+        assert_eq!(
+            ret.fu,
+            Fu {
+                base: 20,
+                melds: 4,
+                pair: 0,
+                wait: 2,
+                tsumo: 0,
+                menzen_ron: 10,
+                total: 40,
+            },
+        );
+        assert_eq!((ret.han, ret.ron, ret.total), (2, 2600, 2600));
+
+        // Open hand without yaku.
+        let tehai = hand("234m 567s 11z 999p").unwrap();
+        let conditions = ScoreConditions {
+            riichi: false,
+            ..conditions
+        };
+        let melds = [Meld::Chi(t!(6p).as_u8())];
+        let ret = calc_score(&tehai, &melds, t!(9p).as_u8(), &conditions).unwrap();
+        assert!(ret.is_none());
+        let conditions = ScoreConditions {
+            riichi: true,
+            ..conditions
+        };
+        calc_score(&tehai, &melds, t!(9p).as_u8(), &conditions).unwrap_err();
+    }
+
+    #[test]
+    fn yakuman() {
+        agari::ensure_init();
+        let conditions = ScoreConditions {
+            bakaze: tu8!(E),
+            jikaze: tu8!(E),
+            doras: 2,
+            ..Default::default()
+        };
+        let tehai = hand("19m 19p 19s 12345677z").unwrap();
+        let ret = calc_score(&tehai, &[], t!(C).as_u8(), &conditions)
+            .unwrap()
+            .unwrap();
+        assert_eq!(names(&ret), [("kokushi_musou", agari::YAKUMAN_HAN)]);
+        assert_eq!((ret.han, ret.yakuman), (0, 1));
+        assert_eq!((ret.tsumo_ko, ret.total), (16000, 48000));
+
+        let tehai = hand("111m 999m 111p 999p 11s").unwrap();
+        let ret = calc_score(&tehai, &[], t!(1s).as_u8(), &conditions)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            names(&ret),
+            [
+                ("suuankou", agari::YAKUMAN_HAN),
+                ("chinroutou", agari::YAKUMAN_HAN)
+            ],
+        );
+        assert_eq!(ret.yakuman, 2);
+    }
+
+    #[test]
+    fn yakunashi() {
+        agari::ensure_init();
+        let conditions = ScoreConditions {
+            bakaze: tu8!(E),
+            jikaze: tu8!(E),
+            is_ron: true,
+            ..Default::default()
+        };
+        let tehai = hand("123m 456p 789s 123s 11z").unwrap();
+        assert!(detect_yaku(&tehai, &[], t!(1s).as_u8(), &conditions)
+            .unwrap()
+            .is_empty());
+        assert!(calc_score(&tehai, &[], t!(1s).as_u8(), &conditions)
+            .unwrap()
+            .is_none());
+
+        let conditions = ScoreConditions {
+            is_ron: false,
+            ..conditions
+        };
+        let yakus = detect_yaku(&tehai, &[], t!(1s).as_u8(), &conditions).unwrap();
+        assert_eq!(
+            yakus,
+            [Yaku {
+                name: "menzen_tsumo",
+                han: 1
+            }],
+        );
+    }
+}
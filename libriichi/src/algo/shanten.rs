@@ -2,45 +2,83 @@
 //!
 //! Source: <https://github.com/tomohxx/shanten-number-calculator/>
 
+use super::tables::{self, Asset, TableBytes};
 use crate::tuz;
-use std::io::prelude::*;
 
-use flate2::read::GzDecoder;
 use once_cell::sync::Lazy;
 
 const JIHAI_TABLE_SIZE: usize = 78_032;
 const SUHAI_TABLE_SIZE: usize = 1_940_777;
 
-static JIHAI_TABLE: Lazy<Vec<[u8; 10]>> = Lazy::new(|| {
-    read_table(
-        include_bytes!("data/shanten_jihai.bin.gz"),
-        JIHAI_TABLE_SIZE,
-    )
-});
-static SUHAI_TABLE: Lazy<Vec<[u8; 10]>> = Lazy::new(|| {
-    read_table(
-        include_bytes!("data/shanten_suhai.bin.gz"),
-        SUHAI_TABLE_SIZE,
-    )
-});
-
-fn read_table(gzipped: &[u8], length: usize) -> Vec<[u8; 10]> {
-    let mut gz = GzDecoder::new(gzipped);
-    let mut raw = vec![];
-    gz.read_to_end(&mut raw).unwrap();
-
-    let mut ret = Vec::with_capacity(length);
-    let mut entry = [0; 10];
-    for (i, b) in raw.into_iter().enumerate() {
-        entry[i * 2 % 10] = b & 0b1111;
-        entry[i * 2 % 10 + 1] = (b >> 4) & 0b1111;
-        if (i + 1) % 5 == 0 {
-            ret.push(entry);
+/// Number of hands whose lookups are issued together in `calc_all_many`.
+const LOOKUP_CHUNK: usize = 64;
+
+pub(super) const JIHAI_ASSET: Asset = Asset {
+    name: "shanten_jihai.bin",
+    embedded: include_bytes!("data/shanten_jihai.bin.gz"),
+    len: Table::packed_len(JIHAI_TABLE_SIZE),
+    build: |gzipped| Table::pack(gzipped, JIHAI_TABLE_SIZE),
+};
+pub(super) const SUHAI_ASSET: Asset = Asset {
+    name: "shanten_suhai.bin",
+    embedded: include_bytes!("data/shanten_suhai.bin.gz"),
+    len: Table::packed_len(SUHAI_TABLE_SIZE),
+    build: |gzipped| Table::pack(gzipped, SUHAI_TABLE_SIZE),
+};
+
+static JIHAI_TABLE: Lazy<Table> = Lazy::new(|| Table(JIHAI_ASSET.load()));
+static SUHAI_TABLE: Lazy<Table> = Lazy::new(|| Table(SUHAI_ASSET.load()));
+
+/// A table whose entries are 10 values of 4 bits each, packed into 5 bytes,
+/// which halves the memory footprint compared to one byte per value, and so
+/// the cache misses of the lookups with it.
+///
+/// The low nibbles of the 5 bytes are the values 0 to 4 and the high ones are
+/// the values 5 to 9, so that an entry is unpacked by two masks on a `u64`.
+/// The raw bytes are padded by 3 bytes at the end, so that any entry can be
+/// loaded as one unaligned `u64`.
+///
+/// The packed bytes are also the content of the table asset.
+struct Table(TableBytes);
+
+impl Table {
+    fn pack(gzipped: &[u8], length: usize) -> Vec<u8> {
+        let raw = tables::decompress(gzipped);
+        assert_eq!(raw.len(), length * 5);
+
+        // In the data file, the values are in the order of the nibbles.
+        let mut packed = Vec::with_capacity(Self::packed_len(length));
+        for b in raw.chunks_exact(5) {
+            let value = |i: usize| (b[i / 2] >> (i % 2 * 4)) & 0b1111;
+            packed.extend((0..5).map(|i| value(i) | value(i + 5) << 4));
         }
+        packed.extend([0; 3]);
+        packed
     }
-    assert_eq!(ret.len(), length);
 
-    ret
+    const fn packed_len(length: usize) -> usize {
+        length * 5 + 3
+    }
+
+    fn len(&self) -> usize {
+        (self.0.len() - 3) / 5
+    }
+
+    /// Returns the entry at `index`, or all zeros if it is out of range.
+    #[inline]
+    fn get(&self, index: usize) -> [u8; 10] {
+        let Some(b) = self.0.get(index * 5..index * 5 + 8) else {
+            return [0; 10];
+        };
+        let packed = u64::from_le_bytes(b.try_into().unwrap());
+        let lo = (packed & 0x000f_0f0f_0f0f).to_le_bytes();
+        let hi = (packed >> 4 & 0x000f_0f0f_0f0f).to_le_bytes();
+
+        let mut ret = [0; 10];
+        ret[..5].copy_from_slice(&lo[..5]);
+        ret[5..].copy_from_slice(&hi[..5]);
+        ret
+    }
 }
 
 pub fn ensure_init() {
@@ -48,9 +86,7 @@ pub fn ensure_init() {
     assert_eq!(SUHAI_TABLE.len(), SUHAI_TABLE_SIZE);
 }
 
-fn add_suhai(lhs: &mut [u8; 10], index: usize, m: usize) {
-    let tab = SUHAI_TABLE.get(index).copied().unwrap_or_default();
-
+fn add_suhai(lhs: &mut [u8; 10], tab: [u8; 10], m: usize) {
     for j in (5..=(5 + m)).rev() {
         let mut sht = (lhs[j] + tab[0]).min(lhs[0] + tab[j]);
         for k in 5..j {
@@ -68,9 +104,7 @@ fn add_suhai(lhs: &mut [u8; 10], index: usize, m: usize) {
     }
 }
 
-fn add_jihai(lhs: &mut [u8; 10], index: usize, m: usize) {
-    let tab = JIHAI_TABLE.get(index).copied().unwrap_or_default();
-
+fn add_jihai(lhs: &mut [u8; 10], tab: [u8; 10], m: usize) {
     let j = m + 5;
     let mut sht = (lhs[j] + tab[0]).min(lhs[0] + tab[j]);
     for k in 5..j {
@@ -83,22 +117,32 @@ fn sum_tiles(tiles: &[u8]) -> usize {
     tiles.iter().fold(0, |acc, &x| acc * 5 + x as usize)
 }
 
-/// `len_div3` must be within [0, 4].
-#[must_use]
-pub fn calc_normal(tiles: &[u8; 34], len_div3: u8) -> i8 {
-    let len_div3 = len_div3 as usize;
+/// Looks up the entries of the three suits and the honors of `tiles`.
+#[inline]
+fn lookup(tiles: &[u8; 34]) -> [[u8; 10]; 4] {
+    [
+        SUHAI_TABLE.get(sum_tiles(&tiles[..9])),
+        SUHAI_TABLE.get(sum_tiles(&tiles[9..2 * 9])),
+        SUHAI_TABLE.get(sum_tiles(&tiles[2 * 9..3 * 9])),
+        JIHAI_TABLE.get(sum_tiles(&tiles[3 * 9..])),
+    ]
+}
 
-    let mut ret = SUHAI_TABLE
-        .get(sum_tiles(&tiles[..9]))
-        .copied()
-        .unwrap_or_default();
-    add_suhai(&mut ret, sum_tiles(&tiles[9..2 * 9]), len_div3);
-    add_suhai(&mut ret, sum_tiles(&tiles[2 * 9..3 * 9]), len_div3);
-    add_jihai(&mut ret, sum_tiles(&tiles[3 * 9..]), len_div3);
+fn combine(entries: [[u8; 10]; 4], len_div3: usize) -> i8 {
+    let [mut ret, m, p, z] = entries;
+    add_suhai(&mut ret, m, len_div3);
+    add_suhai(&mut ret, p, len_div3);
+    add_jihai(&mut ret, z, len_div3);
 
     (ret[5 + len_div3] as i8) - 1
 }
 
+/// `len_div3` must be within [0, 4].
+#[must_use]
+pub fn calc_normal(tiles: &[u8; 34], len_div3: u8) -> i8 {
+    combine(lookup(tiles), len_div3 as usize)
+}
+
 #[must_use]
 pub fn calc_chitoi(tiles: &[u8; 34]) -> i8 {
     let mut pairs = 0;
@@ -136,7 +180,31 @@ pub fn calc_kokushi(tiles: &[u8; 34]) -> i8 {
 
 #[must_use]
 pub fn calc_all(tiles: &[u8; 34], len_div3: u8) -> i8 {
-    let mut shanten = calc_normal(tiles, len_div3);
+    with_special(tiles, len_div3, calc_normal(tiles, len_div3))
+}
+
+/// Same as calling `calc_all` on each of `hands`, which all have the same
+/// `len_div3`, but faster for many hands, as the table lookups of a chunk of
+/// hands are all issued before any of them is used, so that their cache
+/// misses overlap.
+#[must_use]
+pub fn calc_all_many(hands: &[[u8; 34]], len_div3: u8) -> Vec<i8> {
+    let mut ret = Vec::with_capacity(hands.len());
+    let mut entries = [[[0; 10]; 4]; LOOKUP_CHUNK];
+    for chunk in hands.chunks(LOOKUP_CHUNK) {
+        for (tiles, entry) in chunk.iter().zip(&mut entries) {
+            *entry = lookup(tiles);
+        }
+        ret.extend(chunk.iter().zip(entries).map(|(tiles, entry)| {
+            with_special(tiles, len_div3, combine(entry, len_div3 as usize))
+        }));
+    }
+    ret
+}
+
+/// Takes chitoi and kokushi into account on top of the `shanten` of the
+/// normal form.
+fn with_special(tiles: &[u8; 34], len_div3: u8, mut shanten: i8) -> i8 {
     if shanten <= 0 || len_div3 < 4 {
         return shanten;
     }
@@ -172,6 +240,27 @@ mod test {
         assert_eq!(calc_all(&tehai, 4), 3);
     }
 
+    #[test]
+    fn calc_many() {
+        let hands: Vec<_> = [
+            "1111m 333p 222s 444z",
+            "147m 258p 369s 1234z",
+            "15559m 19p 19s 1234z",
+            "2344456m 14p 127s 2z 7p",
+            "12223456m 78889p 2m",
+            "1199m 1199p 1199s 11z",
+        ]
+        .iter()
+        .map(|h| hand(h).unwrap())
+        .collect();
+        // More than one chunk.
+        let hands: Vec<_> = hands.iter().copied().cycle().take(150).collect();
+        let expected: Vec<_> = hands.iter().map(|h| calc_all(h, 4)).collect();
+        assert_eq!(calc_all_many(&hands, 4), expected);
+        assert_eq!(&expected[..6], &[1, 6, 3, 3, -1, -1]);
+        assert!(calc_all_many(&[], 4).is_empty());
+    }
+
     #[test]
     fn calc_3n_plus_2() {
         let tehai = hand("2344456m 14p 127s 2z 7p").unwrap();
@@ -0,0 +1,334 @@
+//! Single-player expected value calculator for a closed hand.
+//!
+//! The model assumes no calls and no interference from the other players:
+//! every turn the player draws one tile from the unseen pool uniformly, keeps
+//! it only if it reduces the shanten, and wins by tsumo once the hand is
+//! complete.
+use super::agari::AgariCalculator;
+#[cfg(feature = "python")]
+use super::calculator::to_tehai;
+use super::shanten;
+use crate::tile::Tile;
+use crate::tu8;
+use std::collections::HashMap;
+
+use anyhow::{ensure, Context, Result};
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+use riichi_macros::{pyclass, pymethods};
+
+/// Discards with the hand farther than this from tenpai are not searched, as
+/// the search grows exponentially with the shanten.
+pub const MAX_SHANTEN: i8 = 3;
+
+/// Tiles are given as tile IDs in the order of 1-9m, 1-9p, 1-9s and ESWNPFC,
+/// same as `AgariCalculator`.
+#[pyclass]
+#[pyo3(text_signature = "(*, bakaze, jikaze, dora_indicators=[], riichi=False)")]
+#[derive(Debug, Clone)]
+pub struct SpCalculator {
+    /// Tile ID of the round wind, e.g. 27 for E.
+    pub bakaze: u8,
+    /// Tile ID of the seat wind.
+    pub jikaze: u8,
+    /// Number of doras of each tile.
+    pub doras: [u8; 34],
+    /// Whether the player declares riichi, which adds one han to every win.
+    pub riichi: bool,
+}
+
+/// The win probability and EV of a discard, over the remaining draws.
+#[pyclass]
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpCandidate {
+    #[pyo3(get)]
+    pub discard: u8,
+    /// Shanten of the hand after the discard.
+    #[pyo3(get)]
+    pub shanten: i8,
+    #[pyo3(get)]
+    pub win_prob: f32,
+    /// Expected points of the tsumo, 0 if not winning.
+    #[pyo3(get)]
+    pub ev: f32,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Value {
+    win_prob: f32,
+    ev: f32,
+}
+
+struct Search<'a> {
+    calc: &'a SpCalculator,
+    len_div3: u8,
+    /// The hand before the search, to derive how many copies of each tile
+    /// are drawn during the search.
+    origin: [u8; 34],
+    unseen: [u8; 34],
+    unseen_total: f32,
+    memo: HashMap<([u8; 34], u8), Value>,
+}
+
+#[pymethods]
+impl SpCalculator {
+    #[new]
+    #[args("*", dora_indicators = "vec![]", riichi = "false")]
+    fn py_new(bakaze: u8, jikaze: u8, dora_indicators: Vec<u8>, riichi: bool) -> Result<Self> {
+        for kaze in [bakaze, jikaze] {
+            ensure!(
+                (tu8!(E)..=tu8!(N)).contains(&kaze),
+                "{kaze} is not a wind tile",
+            );
+        }
+        let dora_indicators = dora_indicators
+            .into_iter()
+            .map(|t| Tile::try_from(t).context("invalid dora indicator"))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self::new(bakaze, jikaze, &dora_indicators, riichi))
+    }
+
+    /// Returns a list of `SpCandidate` sorted by EV in descending order, one
+    /// for each discard.
+    ///
+    /// `tehai` is a closed hand of 14 tiles as a 34-length list of tile
+    /// counts, `unseen` is the number of unseen copies of each tile, and
+    /// `turns` is the number of draws left for the player.
+    #[cfg(feature = "python")]
+    #[pyo3(name = "calc")]
+    #[pyo3(text_signature = "($self, tehai, unseen, turns, /)")]
+    fn calc_py(
+        &self,
+        tehai: Vec<u8>,
+        unseen: [u8; 34],
+        turns: u8,
+        py: Python<'_>,
+    ) -> Result<Vec<SpCandidate>> {
+        let (tehai, _) = to_tehai(&tehai)?;
+        py.allow_threads(move || self.calc(&tehai, &unseen, turns))
+    }
+
+    fn __repr__(&self) -> String {
+        format!("{self:?}")
+    }
+}
+
+#[pymethods]
+impl SpCandidate {
+    fn __repr__(&self) -> String {
+        format!("{self:?}")
+    }
+}
+
+impl SpCalculator {
+    #[must_use]
+    pub fn new(bakaze: u8, jikaze: u8, dora_indicators: &[Tile], riichi: bool) -> Self {
+        let mut doras = [0; 34];
+        for &ind in dora_indicators {
+            doras[Tile::dora_from_indicator(ind).as_usize()] += 1;
+        }
+        Self {
+            bakaze,
+            jikaze,
+            doras,
+            riichi,
+        }
+    }
+
+    /// See the Python method for the parameters. Discards leaving the hand
+    /// at more than `MAX_SHANTEN` shanten are reported with zero win
+    /// probability and EV.
+    pub fn calc(&self, tehai: &[u8; 34], unseen: &[u8; 34], turns: u8) -> Result<Vec<SpCandidate>> {
+        ensure!(
+            tehai.iter().sum::<u8>() == 14,
+            "tehai must be a closed hand of 14 tiles",
+        );
+        ensure!(
+            tehai.iter().zip(unseen).all(|(&t, &u)| t + u <= 4),
+            "tehai and unseen have more than 4 copies of a tile",
+        );
+
+        let mut search = Search {
+            calc: self,
+            len_div3: 4,
+            origin: *tehai,
+            unseen: *unseen,
+            unseen_total: unseen.iter().map(|&n| n as f32).sum(),
+            memo: HashMap::new(),
+        };
+
+        let mut ret: Vec<_> = (0..34)
+            .filter(|&d| tehai[d] > 0)
+            .map(|d| {
+                let mut hand = *tehai;
+                hand[d] -= 1;
+                let shanten = shanten::calc_all(&hand, 4);
+                let value = if shanten <= MAX_SHANTEN {
+                    search.after_discard(&hand, shanten, turns)
+                } else {
+                    Value::default()
+                };
+                SpCandidate {
+                    discard: d as u8,
+                    shanten,
+                    win_prob: value.win_prob,
+                    ev: value.ev,
+                }
+            })
+            .collect();
+        ret.sort_by(|l, r| r.ev.total_cmp(&l.ev));
+        Ok(ret)
+    }
+
+    /// Points of a tsumo with `hand`, which is complete.
+    fn tsumo_points(&self, hand: &[u8; 34], winning_tile: usize) -> f32 {
+        let calc = AgariCalculator {
+            tehai: hand,
+            is_menzen: true,
+            chis: &[],
+            pons: &[],
+            minkans: &[],
+            ankans: &[],
+            bakaze: self.bakaze,
+            jikaze: self.jikaze,
+            winning_tile: winning_tile as u8,
+            is_ron: false,
+            kuitan: true,
+        };
+        let doras = hand.iter().zip(self.doras).map(|(&n, d)| n * d).sum();
+        // 門前清自摸和, plus 立直 if declared.
+        let additional_hans = 1 + self.riichi as u8;
+        let is_oya = self.jikaze == tu8!(E);
+        calc.agari(additional_hans, doras)
+            .map(|agari| agari.into_point(is_oya).tsumo_total(is_oya) as f32)
+            .unwrap_or_default()
+    }
+}
+
+impl Search<'_> {
+    /// Value of a 3n+1 `hand` at `shanten` with `turns` draws left.
+    fn after_discard(&mut self, hand: &[u8; 34], shanten: i8, turns: u8) -> Value {
+        if turns == 0 {
+            return Value::default();
+        }
+        if let Some(&value) = self.memo.get(&(*hand, turns)) {
+            return value;
+        }
+
+        let mut value = Value::default();
+        let mut miss_prob = 1.;
+        for tsumo in 0..34 {
+            // The copies drawn during the search are no longer unseen.
+            let drawn = hand[tsumo].saturating_sub(self.origin[tsumo]);
+            let live = self.unseen[tsumo].saturating_sub(drawn);
+            if live == 0 {
+                continue;
+            }
+            let mut hand_3n2 = *hand;
+            hand_3n2[tsumo] += 1;
+            if shanten::calc_all(&hand_3n2, self.len_div3) >= shanten {
+                continue;
+            }
+
+            let prob = live as f32 / self.unseen_total;
+            miss_prob -= prob;
+            let next = if shanten == 0 {
+                Value {
+                    win_prob: 1.,
+                    ev: self.calc.tsumo_points(&hand_3n2, tsumo),
+                }
+            } else {
+                self.best_discard(&hand_3n2, shanten - 1, turns - 1)
+            };
+            value.win_prob += prob * next.win_prob;
+            value.ev += prob * next.ev;
+        }
+
+        // Tsumogiri the draws that do not help.
+        let rest = self.after_discard(hand, shanten, turns - 1);
+        value.win_prob += miss_prob * rest.win_prob;
+        value.ev += miss_prob * rest.ev;
+
+        self.memo.insert((*hand, turns), value);
+        value
+    }
+
+    /// Picks the discard with the highest EV among the ones keeping the 3n+2
+    /// `hand` at `shanten`.
+    fn best_discard(&mut self, hand: &[u8; 34], shanten: i8, turns: u8) -> Value {
+        let mut best = Value::default();
+        for discard in 0..34 {
+            if hand[discard] == 0 {
+                continue;
+            }
+            let mut hand_3n1 = *hand;
+            hand_3n1[discard] -= 1;
+            if shanten::calc_all(&hand_3n1, self.len_div3) != shanten {
+                continue;
+            }
+            let value = self.after_discard(&hand_3n1, shanten, turns);
+            if value.ev > best.ev {
+                best = value;
+            }
+        }
+        best
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::hand::hand;
+    use crate::{t, tuz};
+
+    fn unseen_except(tehai: &[u8; 34]) -> [u8; 34] {
+        tehai.map(|n| 4 - n)
+    }
+
+    #[test]
+    fn tenpai() {
+        let calc = SpCalculator::new(tu8!(E), tu8!(S), &[t!(1m)], false);
+        let tehai = hand("234m 678m 345p 68s 99s 4z").unwrap();
+        let unseen = unseen_except(&tehai);
+        let candidates = calc.calc(&tehai, &unseen, 10).unwrap();
+
+        let best = &candidates[0];
+        assert_eq!(best.discard as usize, tuz!(N));
+        assert_eq!(best.shanten, 0);
+        // Kanchan wait on 7s with 4 copies out of 122 unseen tiles.
+        let miss = 1. - 4. / 122_f32;
+        let expected = 1. - miss.powi(10);
+        assert!((best.win_prob - expected).abs() < 1e-4);
+        assert!(best.ev > 0.);
+
+        // More turns only help.
+        let longer = calc.calc(&tehai, &unseen, 15).unwrap();
+        assert!(longer[0].win_prob > best.win_prob);
+        // Riichi adds value.
+        let riichi = SpCalculator::new(tu8!(E), tu8!(S), &[t!(1m)], true);
+        assert!(riichi.calc(&tehai, &unseen, 10).unwrap()[0].ev > best.ev);
+    }
+
+    #[test]
+    fn one_shanten() {
+        let calc = SpCalculator::new(tu8!(E), tu8!(E), &[], false);
+        let tehai = hand("234m 678m 345p 68s 9s 15z").unwrap();
+        let unseen = unseen_except(&tehai);
+        let candidates = calc.calc(&tehai, &unseen, 8).unwrap();
+        assert_eq!(candidates.len(), 14);
+
+        // Cutting an isolated honor is better than breaking a complete set.
+        let win_prob_of = |tid: usize| {
+            candidates
+                .iter()
+                .find(|c| c.discard as usize == tid)
+                .unwrap()
+                .win_prob
+        };
+        assert!(win_prob_of(tuz!(P)) > win_prob_of(tuz!(3p)));
+        assert!(candidates.iter().all(|c| (0. ..=1.).contains(&c.win_prob)));
+
+        assert!(calc.calc(&tehai, &[4; 34], 8).is_err());
+        assert!(calc.calc(&[0; 34], &unseen, 8).is_err());
+    }
+}
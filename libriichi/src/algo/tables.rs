@@ -0,0 +1,232 @@
+//! Precomputed lookup tables of the shanten and agari algorithms stored as
+//! binary assets.
+//!
+//! The assets are generated offline by `gen_tables` into a directory, which is
+//! then given by the environment variable `LIBRIICHI_TABLES_DIR`. They are
+//! memory-mapped on first use, which saves decoding the tables embedded at
+//! build time on every start-up and lets all the processes on one machine
+//! share the pages. Without the `mmap` feature, such as for WASM, they are
+//! read into memory instead. The agari table is an exception, as its hash map
+//! cannot be mapped and is still built on every start-up, so its asset only
+//! saves the gzip decoding.
+//!
+//! An asset starts with a header of the format version, the length of the
+//! content and the SHA3-256 of the data file it was generated from. The
+//! embedded tables are used if the variable is not set, or if an asset is
+//! missing, broken, or generated from a different data file than the one
+//! embedded.
+
+use std::env;
+use std::fs;
+#[cfg(feature = "mmap")]
+use std::fs::File;
+use std::io::prelude::*;
+use std::ops::Deref;
+use std::path::Path;
+
+use anyhow::{ensure, Context, Result};
+use flate2::read::GzDecoder;
+#[cfg(feature = "mmap")]
+use memmap2::Mmap;
+use sha3::{Digest, Sha3_256};
+
+pub const TABLES_DIR_ENV: &str = "LIBRIICHI_TABLES_DIR";
+
+const MAGIC: &[u8; 8] = b"RIICHITB";
+const FORMAT_VERSION: u32 = 1;
+/// Magic, format version, reserved, content length and hash.
+const HEADER_LEN: usize = 8 + 4 + 4 + 8 + 32;
+
+/// A table that can be stored in an asset.
+pub(crate) struct Asset {
+    pub(crate) name: &'static str,
+    /// The gzipped data file embedded at build time.
+    pub(crate) embedded: &'static [u8],
+    /// Length of the content.
+    pub(crate) len: usize,
+    /// Builds the content of the asset from `embedded`, which is the exact
+    /// in-memory representation used by the algorithm.
+    pub(crate) build: fn(&[u8]) -> Vec<u8>,
+}
+
+/// The content of a table.
+pub(crate) enum TableBytes {
+    #[cfg(feature = "mmap")]
+    Mapped(Mmap),
+    Owned(Vec<u8>),
+}
+
+impl Deref for TableBytes {
+    type Target = [u8];
+
+    #[inline]
+    fn deref(&self) -> &[u8] {
+        match self {
+            #[cfg(feature = "mmap")]
+            Self::Mapped(mmap) => &mmap[HEADER_LEN..],
+            Self::Owned(v) => v,
+        }
+    }
+}
+
+impl Asset {
+    /// Loads the asset from the directory of `LIBRIICHI_TABLES_DIR` if it is
+    /// set, or builds it from the embedded table otherwise.
+    pub(crate) fn load(&self) -> TableBytes {
+        let Some(dir) = env::var_os(TABLES_DIR_ENV) else {
+            return TableBytes::Owned(self.build_embedded());
+        };
+        let path = Path::new(&dir).join(self.name);
+        match self.open(&path) {
+            Ok(bytes) => bytes,
+            Err(err) => {
+                log::warn!(
+                    "failed to load {}, using the embedded table instead: {err:#}",
+                    path.display(),
+                );
+                TableBytes::Owned(self.build_embedded())
+            }
+        }
+    }
+
+    pub(crate) fn build_embedded(&self) -> Vec<u8> {
+        let content = (self.build)(self.embedded);
+        assert_eq!(
+            content.len(),
+            self.len,
+            "{} has an unexpected length",
+            self.name
+        );
+        content
+    }
+
+    fn hash(&self) -> [u8; 32] {
+        Sha3_256::digest(self.embedded).into()
+    }
+
+    fn header(&self) -> Vec<u8> {
+        let mut header = Vec::with_capacity(HEADER_LEN);
+        header.extend_from_slice(MAGIC);
+        header.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+        header.extend_from_slice(&[0; 4]);
+        header.extend_from_slice(&(self.len as u64).to_le_bytes());
+        header.extend_from_slice(&self.hash());
+        header
+    }
+
+    #[cfg(feature = "mmap")]
+    fn open(&self, path: &Path) -> Result<TableBytes> {
+        let file = File::open(path)?;
+        // SAFETY: the assets are never modified after being generated, and
+        // their content is checked against the embedded tables by the header.
+        let mmap = unsafe { Mmap::map(&file)? };
+        self.check(&mmap)?;
+        Ok(TableBytes::Mapped(mmap))
+    }
+
+    #[cfg(not(feature = "mmap"))]
+    fn open(&self, path: &Path) -> Result<TableBytes> {
+        let mut data = fs::read(path)?;
+        self.check(&data)?;
+        data.drain(..HEADER_LEN);
+        Ok(TableBytes::Owned(data))
+    }
+
+    fn check(&self, data: &[u8]) -> Result<()> {
+        ensure!(
+            data.len() >= HEADER_LEN && &data[..8] == MAGIC,
+            "not a table asset",
+        );
+        let version = u32::from_le_bytes(data[8..12].try_into().unwrap());
+        ensure!(
+            version == FORMAT_VERSION,
+            "unsupported format version {version}, expected {FORMAT_VERSION}",
+        );
+        let len = u64::from_le_bytes(data[16..24].try_into().unwrap());
+        ensure!(
+            len == self.len as u64,
+            "content of {len} bytes, expected {}",
+            self.len,
+        );
+        ensure!(
+            data[24..HEADER_LEN] == self.hash(),
+            "generated from a different data file than the embedded one",
+        );
+        ensure!(
+            data.len() - HEADER_LEN == self.len,
+            "truncated asset, expected {} bytes of content",
+            self.len,
+        );
+        Ok(())
+    }
+}
+
+/// Decompresses a table embedded at build time.
+pub(crate) fn decompress(gzipped: &[u8]) -> Vec<u8> {
+    let mut raw = vec![];
+    GzDecoder::new(gzipped).read_to_end(&mut raw).unwrap();
+    raw
+}
+
+/// Writes the assets of all the tables into `dir`.
+pub fn write_assets(dir: impl AsRef<Path>) -> Result<()> {
+    let dir = dir.as_ref();
+    fs::create_dir_all(dir)?;
+    for asset in [
+        &super::shanten::SUHAI_ASSET,
+        &super::shanten::JIHAI_ASSET,
+        &super::agari::AGARI_ASSET,
+    ] {
+        let content = asset.build_embedded();
+        let mut data = asset.header();
+        data.extend_from_slice(&content);
+
+        // Write to a temporary file first, so that a running process never
+        // maps a partially written asset.
+        let path = dir.join(asset.name);
+        let tmp = dir.join(format!("{}.tmp", asset.name));
+        fs::write(&tmp, data).with_context(|| format!("failed to write {}", tmp.display()))?;
+        fs::rename(&tmp, &path)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::agari::AGARI_ASSET;
+    use super::super::shanten::JIHAI_ASSET;
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let dir = env::temp_dir().join(format!("libriichi-tables-{}", std::process::id()));
+        write_assets(&dir).unwrap();
+
+        for asset in [&JIHAI_ASSET, &AGARI_ASSET] {
+            let bytes = asset.open(&dir.join(asset.name)).unwrap();
+            assert_eq!(&*bytes, &asset.build_embedded()[..]);
+        }
+
+        let path = dir.join(AGARI_ASSET.name);
+        let data = fs::read(&path).unwrap();
+        let broken = |f: fn(&mut Vec<u8>)| {
+            let mut data = data.clone();
+            f(&mut data);
+            fs::write(&path, data).unwrap();
+            AGARI_ASSET.open(&path).map(|_| ()).unwrap_err()
+        };
+        broken(|d| d.truncate(d.len() - 1));
+        broken(|d| d.push(0));
+        broken(|d| d[8] += 1);
+        let err = broken(|d| d[HEADER_LEN - 1] ^= 1);
+        assert!(err.to_string().contains("different data file"), "{err}");
+        fs::write(&path, b"garbage").unwrap();
+        AGARI_ASSET.open(&path).map(|_| ()).unwrap_err();
+
+        // An asset of another table has the wrong length and hash.
+        fs::copy(dir.join(JIHAI_ASSET.name), &path).unwrap();
+        AGARI_ASSET.open(&path).map(|_| ()).unwrap_err();
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,30 +1,28 @@
 use super::result::KyokuResult;
-use crate::consts::ORACLE_OBS_SHAPE;
-use crate::mjai::{Event, EventExt};
+use super::wall::Wall;
+use crate::algo::point::exhaustive_ryukyoku_deltas;
+use crate::consts::{ORACLE_OBS_SHAPE, TILES_LEFT_AT_START};
+use crate::mjai::{Event, EventExt, RyukyokuReason};
+use crate::rule::{akas_per_suit, RuleSet};
 use crate::state::PlayerState;
 use crate::tile::Tile;
 use crate::vec_ops::vec_add_assign;
-use crate::{matches_tu8, must_tile, t, tu8};
-use std::convert::TryInto;
+use crate::{matches_tu8, must_tile, t, tu8, tuz};
 use std::mem;
 
 use anyhow::{bail, Context, Result};
 use derivative::Derivative;
 use ndarray::prelude::*;
-use rand::prelude::*;
-use rand_chacha::ChaCha12Rng;
-use sha3::{Digest, Sha3_256};
 
 /// The fields are all pub on purpose so the caller will be able to set the
 /// yama, doras, scores directly.
 ///
-/// Other than what is mentioned below, everything else is identical to Tenhou's
-/// Rule.
+/// Other than what is mentioned below and what is configured by `rule`,
+/// everything else is identical to Tenhou's Rule.
 ///
-/// 1. No triple-ron ryukyoku.
-/// 2. Tenhou (the yaku) and chihou do not accumulate with other yakus; they are
+/// 1. Tenhou (the yaku) and chihou do not accumulate with other yakus; they are
 ///    always 1x yakuman.
-#[derive(Debug, Default)]
+#[derive(Debug, Clone, Default)]
 pub struct Board {
     /// Counts from 0
     pub kyoku: u8,
@@ -43,9 +41,11 @@ pub struct Board {
     pub dora_indicators: Vec<Tile>,
     /// Goes forward (iter)
     pub ura_indicators: Vec<Tile>,
+
+    pub rule: RuleSet,
 }
 
-#[derive(Derivative)]
+#[derive(Clone, Derivative)]
 #[derivative(Default)]
 pub struct BoardState {
     board: Board,
@@ -58,7 +58,7 @@ pub struct BoardState {
     has_abortive_ryukyoku: bool,
     kyoku_deltas: [i32; 4],
 
-    #[derivative(Default(value = "70"))]
+    #[derivative(Default(value = "TILES_LEFT_AT_START"))]
     tiles_left: u8,
     tsumo_actor: u8,
     // Just a fancy bool
@@ -66,8 +66,6 @@ pub struct BoardState {
     need_new_dora_at_discard: Option<()>,
     need_new_dora_at_tsumo: Option<()>,
     riichi_to_be_accepted: Option<u8>,
-    #[derivative(Default(value = "[true; 4]"))]
-    can_nagashi_mangan: [bool; 4],
     #[derivative(Default(value = "true"))]
     can_four_wind: bool,
     four_wind_tile: Option<Tile>,
@@ -75,6 +73,12 @@ pub struct BoardState {
     kans: u8,
     check_four_kan: bool,
     paos: [Option<u8>; 4],
+    // The discarder of the daiminkan whose rinshan tsumo is pending.
+    rinshan_pao: Option<u8>,
+    // Who actually paid under pao for each winner.
+    settled_paos: [Option<u8>; 4],
+    // Who was tenpai at an exhaustive ryukyoku.
+    tenpai: Option<[bool; 4]>,
 
     log: Vec<EventExt>,
 
@@ -95,49 +99,32 @@ pub enum Poll {
 
 impl Board {
     pub fn init_from_seed(&mut self, game_seed: (u64, u64)) {
-        let (nonce, key) = game_seed;
-        let kyoku_seed = Sha3_256::new()
-            .chain_update(nonce.to_le_bytes())
-            .chain_update(key.to_le_bytes())
-            .chain_update([self.kyoku, self.honba])
-            .finalize()
-            .try_into()
-            .unwrap();
-        let mut rng = ChaCha12Rng::from_seed(kyoku_seed);
-        let mut seq = UNSHUFFLED;
-        seq.shuffle(&mut rng);
-
-        self.haipai = [
-            seq[..13].try_into().unwrap(),
-            seq[13..13 * 2].try_into().unwrap(),
-            seq[13 * 2..13 * 3].try_into().unwrap(),
-            seq[13 * 3..13 * 4].try_into().unwrap(),
-        ];
-        let mut idx = 13 * 4;
-
-        self.rinshan = seq[idx..idx + 4].to_vec();
-        idx += 4;
-        self.dora_indicators = seq[idx..idx + 5].to_vec();
-        idx += 5;
-        self.ura_indicators = seq[idx..idx + 5].to_vec();
-        idx += 5;
-        self.yama = seq[idx..idx + 70].to_vec();
-        idx += 70;
-        assert_eq!(idx, seq.len());
+        let wall = Wall::from_seed(game_seed, self.kyoku, self.honba, self.rule.aka_count);
+        self.init_from_wall(wall);
+    }
+
+    /// `kyoku` and `honba` of `wall` are ignored.
+    pub fn init_from_wall(&mut self, wall: Wall) {
+        self.haipai = wall.haipai;
+        self.yama = wall.yama;
+        self.rinshan = wall.rinshan;
+        self.dora_indicators = wall.dora_indicators;
+        self.ura_indicators = wall.ura_indicators;
     }
 
     pub fn into_state(self) -> BoardState {
         let oya = self.kyoku % 4;
         let dora_indicators_full = self.dora_indicators.clone();
+        let rule = self.rule;
 
         BoardState {
             board: self,
             oya,
             player_states: [
-                PlayerState::new(0),
-                PlayerState::new(1),
-                PlayerState::new(2),
-                PlayerState::new(3),
+                PlayerState::with_rule(0, rule),
+                PlayerState::with_rule(1, rule),
+                PlayerState::with_rule(2, rule),
+                PlayerState::with_rule(3, rule),
             ],
             dora_indicators_full,
             ..Default::default()
@@ -186,6 +173,8 @@ impl BoardState {
             has_abortive_ryukyoku: self.has_abortive_ryukyoku,
             kyotaku_left: self.board.kyotaku,
             scores: self.board.scores,
+            paos: self.settled_paos,
+            tenpai: self.tenpai,
         }
     }
 
@@ -205,10 +194,11 @@ impl BoardState {
     }
 
     #[inline]
-    fn broadcast(&mut self, ev: &Event) {
+    fn broadcast(&mut self, ev: &Event) -> Result<()> {
         for s in &mut self.player_states {
-            s.update(ev);
+            s.update(ev)?;
         }
+        Ok(())
     }
 
     fn haipai(&mut self) -> Result<()> {
@@ -227,7 +217,7 @@ impl BoardState {
             scores: self.board.scores,
             tehais: self.board.haipai,
         };
-        self.broadcast(&start_kyoku);
+        self.broadcast(&start_kyoku)?;
         self.add_log_no_meta(start_kyoku);
 
         let tile = self
@@ -240,76 +230,43 @@ impl BoardState {
             actor: self.oya,
             pai: tile,
         };
-        self.broadcast(&first_tsumo);
+        self.broadcast(&first_tsumo)?;
         self.add_log_no_meta(first_tsumo);
 
         Ok(())
     }
 
     fn exhaustive_ryukyoku(&mut self) {
-        let mut deltas = [0; 4];
-        self.can_renchan = self.player_states[self.oya as usize].shanten() == 0;
-
-        let mut has_nagashi_mangan = false;
-        self.can_nagashi_mangan
-            .iter()
-            .enumerate()
-            .filter(|&(_, &b)| b)
-            .map(|(i, _)| i)
-            .for_each(|i| {
-                has_nagashi_mangan = true;
-                if i as u8 == self.oya {
-                    let mut dod = [-4000; 4];
-                    dod[i] = 12000;
-                    vec_add_assign(&mut deltas, &dod);
-                } else {
-                    let mut dod = [-2000; 4];
-                    dod[i] = 8000;
-                    dod[self.oya as usize] = -4000;
-                    vec_add_assign(&mut deltas, &dod);
-                };
-            });
+        let tenpai = [0, 1, 2, 3].map(|i| self.player_states[i].is_ryukyoku_tenpai());
+        self.can_renchan = tenpai[self.oya as usize];
 
-        if !has_nagashi_mangan {
-            let tenpai_actors: Vec<_> = self
-                .player_states
-                .iter()
-                .enumerate()
-                .filter(|(_, s)| s.shanten() == 0)
-                .map(|(i, _)| i)
-                .collect();
-
-            let (plus, minus) = match tenpai_actors.len() {
-                1 => (3000, -1000),
-                2 => (1500, -1500),
-                3 => (1000, -3000),
-                // 0 | 4
-                _ => (0, 0),
-            };
-            if plus > 0 {
-                let mut dod = [minus; 4];
-                tenpai_actors.into_iter().for_each(|i| dod[i] = plus);
-                vec_add_assign(&mut deltas, &dod);
-            }
-        }
+        let nagashi_mangan = [0, 1, 2, 3].map(|i| self.player_states[i].nagashi_mangan()[0]);
+        // No one pays if no one is tenpai.
+        let paid_tenpai = if self.board.rule.noten_bappu {
+            tenpai
+        } else {
+            [false; 4]
+        };
+        let deltas = exhaustive_ryukyoku_deltas(paid_tenpai, nagashi_mangan, self.oya as usize);
+        self.tenpai = Some(tenpai);
+        let reason = if nagashi_mangan.contains(&true) {
+            RyukyokuReason::Nagashimangan
+        } else {
+            RyukyokuReason::Fanpai
+        };
 
         vec_add_assign(&mut self.kyoku_deltas, &deltas);
         let ryukyoku = Event::Ryukyoku {
             deltas: Some(deltas),
+            reason: Some(reason),
         };
         self.add_log_no_meta(ryukyoku);
         // no need to broadcast
     }
 
-    fn update_nagashi_mangan_and_four_wind(&mut self, ev: &Event) {
+    fn update_four_wind(&mut self, ev: &Event) {
         match *ev {
-            Event::Dahai { actor, pai, .. } => {
-                self.can_nagashi_mangan[actor as usize] &= pai.is_yaokyuu();
-            }
-            Event::Chi { target, .. }
-            | Event::Pon { target, .. }
-            | Event::Daiminkan { target, .. } => {
-                self.can_nagashi_mangan[target as usize] = false;
+            Event::Chi { .. } | Event::Pon { .. } | Event::Daiminkan { .. } => {
                 self.can_four_wind = false;
             }
             Event::Ankan { .. } => {
@@ -347,15 +304,16 @@ impl BoardState {
         Ok(false)
     }
 
-    fn check_riichi_accepted(&mut self) {
+    fn check_riichi_accepted(&mut self) -> Result<()> {
         if let Some(actor) = self.riichi_to_be_accepted.take() {
             let riichi_accepted = Event::ReachAccepted { actor };
-            self.broadcast(&riichi_accepted);
+            self.broadcast(&riichi_accepted)?;
             self.add_log_no_meta(riichi_accepted);
             self.board.scores[actor as usize] -= 1000;
             self.board.kyotaku += 1;
             self.accepted_riichis += 1;
         }
+        Ok(())
     }
 
     fn add_new_dora(&mut self) -> Result<()> {
@@ -365,7 +323,7 @@ impl BoardState {
             .pop()
             .context("illegal kan: already 4 kans and this is the 5th")?;
         let dora_ev = Event::Dora { dora_marker: dora };
-        self.broadcast(&dora_ev);
+        self.broadcast(&dora_ev)?;
         self.add_log_no_meta(dora_ev);
 
         Ok(())
@@ -392,7 +350,6 @@ impl BoardState {
             .iter()
             .map(|ev| match ev.event {
                 Event::Hora { actor, .. } => {
-                    self.can_renchan |= actor == self.oya;
                     let point =
                         self.player_states[actor as usize].agari_points(is_ron, &ura_indicators);
                     Some(point).transpose()
@@ -402,7 +359,8 @@ impl BoardState {
             .collect::<Result<Vec<_>>>()?;
 
         if is_ron {
-            // Multi-ron will be handled
+            // Multi-ron will be handled, unless it is head bump.
+            let max_winners = if self.board.rule.double_ron { 3 } else { 1 };
             points
                 .into_iter()
                 .enumerate()
@@ -410,9 +368,12 @@ impl BoardState {
                 .skip(single_target as usize + 1)
                 .take(3)
                 .filter_map(|(actor, v)| v.map(|point| (actor, point)))
+                .take(max_winners)
                 .for_each(|(actor, point)| {
+                    self.can_renchan |= actor as u8 == self.oya;
                     let mut deltas = [0; 4];
                     if let Some(pao_target) = self.paos[actor] {
+                        self.settled_paos[actor] = Some(pao_target);
                         // As per [Tenhou's rule](https://tenhou.net/man/#RULE):
                         //
                         // > 複合役満を含む得点を、ツモ＝全額・ロン＝折半で支払
@@ -425,7 +386,9 @@ impl BoardState {
                     deltas[actor] = point.ron + kyotaku_point + honba_left * 300;
 
                     kyotaku_point = 0;
-                    honba_left = 0;
+                    if !self.board.rule.honba_per_winner {
+                        honba_left = 0;
+                    }
 
                     vec_add_assign(&mut self.kyoku_deltas, &deltas);
                     let ura_markers = self.player_states[actor as usize]
@@ -445,20 +408,27 @@ impl BoardState {
             return Ok(());
         }
 
+        self.can_renchan |= single_actor == self.oya;
         let point = points[single_actor as usize].unwrap();
         let mut deltas = [0; 4];
-        if let Some(pao_target) = self.paos[single_actor as usize] {
-            // For pao to happen, the agari must have at least 1 yakuman so ron
-            // point and sum of tsumo point should be equal.
+        let pao_target = self.paos[single_actor as usize].or_else(|| {
+            // Only a rinshan kaihou can be a tsumo with `rinshan_pao` set.
+            self.rinshan_pao.filter(|_| self.board.rule.rinshan_pao)
+        });
+        let gain = if let Some(pao_target) = pao_target {
+            self.settled_paos[single_actor as usize] = Some(pao_target);
+            // The liable one pays it as if it were a ron. For yakuman pao, the
+            // ron point and the sum of tsumo point are equal anyways.
             deltas[pao_target as usize] = -point.ron - honba_left * 300;
+            point.ron
         } else {
             deltas.fill(-point.tsumo_ko - honba_left * 100);
             if single_actor != self.oya {
                 deltas[self.oya as usize] = -point.tsumo_oya - honba_left * 100;
             }
+            point.tsumo_total(single_actor == self.oya)
         };
-        deltas[single_actor as usize] =
-            point.tsumo_total(single_actor == self.oya) + kyotaku_point + honba_left * 300;
+        deltas[single_actor as usize] = gain + kyotaku_point + honba_left * 300;
 
         vec_add_assign(&mut self.kyoku_deltas, &deltas);
         let ura_markers = self.player_states[single_actor as usize]
@@ -479,6 +449,14 @@ impl BoardState {
     }
 
     fn update_paos(&mut self, ev: &Event) {
+        match *ev {
+            Event::Daiminkan { target, .. } => self.rinshan_pao = Some(target),
+            Event::Dahai { .. } | Event::Ankan { .. } | Event::Kakan { .. } => {
+                self.rinshan_pao = None;
+            }
+            _ => (),
+        }
+
         match *ev {
             Event::Pon {
                 target, actor, pai, ..
@@ -507,9 +485,10 @@ impl BoardState {
     }
 
     #[inline]
-    fn abortive_ryukyoku(&mut self) {
+    fn abortive_ryukyoku(&mut self, reason: RyukyokuReason) {
         let ryukyoku = Event::Ryukyoku {
             deltas: Some([0; 4]),
+            reason: Some(reason),
         };
         self.add_log_no_meta(ryukyoku);
         self.has_abortive_ryukyoku = true;
@@ -517,14 +496,14 @@ impl BoardState {
     }
 
     fn step(&mut self, reactions: &[EventExt; 4]) -> Result<Poll> {
-        if self.tiles_left == 70 {
+        if self.tiles_left == TILES_LEFT_AT_START {
             self.haipai()?;
             return Ok(Poll::InGame);
         }
 
-        if self.accepted_riichis == 4 {
+        if self.board.rule.abortive_ryukyoku && self.accepted_riichis == 4 {
             // 四家立直
-            self.abortive_ryukyoku();
+            self.abortive_ryukyoku(RyukyokuReason::Suuchariichi);
             return Ok(Poll::End);
         }
 
@@ -552,11 +531,11 @@ impl BoardState {
 
         if self.check_four_kan && !matches!(ev.event, Event::Hora { .. }) {
             // 四槓散了
-            self.abortive_ryukyoku();
+            self.abortive_ryukyoku(RyukyokuReason::Suukaikan);
             return Ok(Poll::End);
         }
 
-        self.update_nagashi_mangan_and_four_wind(&ev.event);
+        self.update_four_wind(&ev.event);
 
         match ev.event {
             Event::None => {
@@ -564,7 +543,7 @@ impl BoardState {
                     self.exhaustive_ryukyoku();
                     return Ok(Poll::End);
                 }
-                self.check_riichi_accepted();
+                self.check_riichi_accepted()?;
 
                 let tile = if self.deal_from_rinshan.take().is_some() {
                     self.board
@@ -588,7 +567,7 @@ impl BoardState {
                     self.add_new_dora()?;
                 }
 
-                self.broadcast(&tsumo);
+                self.broadcast(&tsumo)?;
                 self.add_log_no_meta(tsumo);
             }
 
@@ -597,25 +576,31 @@ impl BoardState {
                     self.add_new_dora()?;
                 }
 
-                self.broadcast(&ev.event);
+                self.broadcast(&ev.event)?;
                 self.add_log(ev.clone());
                 self.tsumo_actor = (actor + 1) % 4;
 
                 // 四風連打
-                if self.can_four_wind && self.check_four_wind(pai)? {
-                    self.abortive_ryukyoku();
+                if self.board.rule.abortive_ryukyoku
+                    && self.can_four_wind
+                    && self.check_four_wind(pai)?
+                {
+                    self.abortive_ryukyoku(RyukyokuReason::Suufonrenta);
                     return Ok(Poll::End);
                 }
 
-                if self.kans == 4 && self.player_states.iter().all(|s| s.kans_count() < 4) {
+                if self.board.rule.abortive_ryukyoku
+                    && self.kans == 4
+                    && self.player_states.iter().all(|s| s.kans_count() < 4)
+                {
                     // 四槓散了
                     self.check_four_kan = true;
                 }
             }
 
             Event::Chi { .. } | Event::Pon { .. } => {
-                self.check_riichi_accepted();
-                self.broadcast(&ev.event);
+                self.check_riichi_accepted()?;
+                self.broadcast(&ev.event)?;
                 self.add_log(ev.clone());
             }
 
@@ -625,7 +610,7 @@ impl BoardState {
                     self.add_new_dora()?;
                 }
 
-                self.broadcast(&ev.event);
+                self.broadcast(&ev.event)?;
                 self.add_log(ev.clone());
 
                 // Immediately add new dora
@@ -643,9 +628,9 @@ impl BoardState {
                 }
 
                 // For Daiminkan only
-                self.check_riichi_accepted();
+                self.check_riichi_accepted()?;
 
-                self.broadcast(&ev.event);
+                self.broadcast(&ev.event)?;
                 self.add_log(ev.clone());
 
                 self.need_new_dora_at_discard = Some(());
@@ -655,20 +640,30 @@ impl BoardState {
                 self.kans += 1;
             }
 
-            Event::Reach { actor } => {
-                self.broadcast(&ev.event);
+            Event::Reach { actor, .. } => {
+                self.broadcast(&ev.event)?;
                 self.add_log(ev.clone());
                 self.riichi_to_be_accepted = Some(actor);
             }
 
             Event::Hora { actor, target, .. } => {
+                let rons = reactions
+                    .iter()
+                    .filter(|ev| matches!(ev.event, Event::Hora { .. }))
+                    .count();
+                let rule = &self.board.rule;
+                if rons == 3 && rule.double_ron && rule.abortive_ryukyoku {
+                    // 三家和了
+                    self.abortive_ryukyoku(RyukyokuReason::Sanchaho);
+                    return Ok(Poll::End);
+                }
                 self.handle_hora(actor, target, reactions)?;
                 return Ok(Poll::End);
             }
 
             Event::Ryukyoku { .. } => {
                 // 九種九牌
-                self.abortive_ryukyoku();
+                self.abortive_ryukyoku(RyukyokuReason::Kyushukyuhai);
                 return Ok(Poll::End);
             }
 
@@ -710,7 +705,7 @@ impl BoardState {
                     .akas_in_hand()
                     .iter()
                     .enumerate()
-                    .filter(|(_, &has_it)| has_it)
+                    .filter(|(_, &n)| n > 0)
                     .for_each(|(i, _)| {
                         arr.slice_mut(s![idx + i, ..]).fill(1.);
                     });
@@ -752,7 +747,7 @@ impl BoardState {
                 encode_tile(idx, tile);
                 idx += 2;
             });
-        idx += (69 - self.tiles_left as usize) * 2;
+        idx += (TILES_LEFT_AT_START - 1 - self.tiles_left) as usize * 2;
 
         self.board.rinshan.iter().copied().rev().for_each(|tile| {
             encode_tile(idx, tile);
@@ -780,7 +775,7 @@ impl BoardState {
 }
 
 #[rustfmt::skip]
-const UNSHUFFLED: [Tile; 136] = [
+pub(super) const UNSHUFFLED: [Tile; 136] = [
     t!(1m),  t!(1m), t!(1m), t!(1m),
     t!(2m),  t!(2m), t!(2m), t!(2m),
     t!(3m),  t!(3m), t!(3m), t!(3m),
@@ -819,3 +814,332 @@ const UNSHUFFLED: [Tile; 136] = [
     t!(F), t!(F), t!(F), t!(F),
     t!(C), t!(C), t!(C), t!(C),
 ];
+
+/// Returns all the 136 tiles in the order of `UNSHUFFLED`, with the akas of
+/// `aka_count`, which are always the first copies of the 5s.
+pub(super) fn unshuffled(aka_count: u8) -> [Tile; 136] {
+    let akas = akas_per_suit(aka_count);
+    let mut seq = UNSHUFFLED;
+    for (i, five) in [tuz!(5m), tuz!(5p), tuz!(5s)].into_iter().enumerate() {
+        let first = five * 4;
+        for (j, tile) in seq[first..first + 4].iter_mut().enumerate() {
+            *tile = if j < akas[i] as usize {
+                tile.akaize()
+            } else {
+                tile.deaka()
+            };
+        }
+    }
+    seq
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::wall::Wall;
+    use super::*;
+    use crate::t;
+
+    /// Plays a kyoku from a wall where the given tiles are dealt first, the
+    /// rest of the haipai being filled with other tiles, and `draws` are the
+    /// first tsumos in order. `script` is played in order by whoever it
+    /// belongs to, where consecutive reactions of different players are
+    /// given at once, and everyone else tsumogiris.
+    fn play(
+        rule: RuleSet,
+        haipai: [&[Tile]; 4],
+        dead_wall: [Tile; 14],
+        draws: &[Tile],
+        script: &[Event],
+    ) -> BoardState {
+        let board = Board {
+            scores: [25000; 4],
+            rule,
+            ..Default::default()
+        };
+        play_on(board, haipai, dead_wall, draws, script)
+    }
+
+    /// Same as `play`, on a board with `honba`, `kyotaku` and the like given.
+    fn play_on(
+        mut board: Board,
+        haipai: [&[Tile]; 4],
+        dead_wall: [Tile; 14],
+        draws: &[Tile],
+        script: &[Event],
+    ) -> BoardState {
+        let mut rest = UNSHUFFLED.to_vec();
+        for tile in haipai
+            .iter()
+            .copied()
+            .flatten()
+            .chain(&dead_wall)
+            .chain(draws)
+        {
+            let pos = rest.iter().position(|t| t == tile).unwrap();
+            rest.remove(pos);
+        }
+
+        let mut tiles = vec![];
+        for part in haipai {
+            tiles.extend_from_slice(part);
+            tiles.extend(rest.drain(..13 - part.len()));
+        }
+        tiles.extend_from_slice(&dead_wall);
+        tiles.extend(rest);
+        tiles.extend(draws.iter().rev());
+        let wall = Wall::from_tiles(0, 0, tiles.try_into().unwrap()).unwrap();
+
+        board.init_from_wall(wall);
+        let mut state = board.into_state();
+
+        let mut script = script.iter().peekable();
+        let mut reactions = <[EventExt; 4]>::default();
+        while matches!(state.poll(reactions).unwrap(), Poll::InGame) {
+            reactions = Default::default();
+            let ctx = state.agent_context();
+            let mut scripted = false;
+            while let Some(&ev) = script.peek() {
+                let actor = ev.actor().unwrap() as usize;
+                if !matches!(reactions[actor].event, Event::None)
+                    || ctx.player_states[actor].validate_reaction(ev).is_err()
+                {
+                    break;
+                }
+                reactions[actor] = EventExt::no_meta(ev.clone());
+                script.next();
+                scripted = true;
+            }
+            if scripted {
+                continue;
+            }
+            for (actor, ps) in ctx.player_states.iter().enumerate() {
+                if ps.last_cans().can_discard {
+                    reactions[actor] = EventExt::no_meta(Event::Dahai {
+                        actor: actor as u8,
+                        pai: ps.last_self_tsumo().unwrap(),
+                        tsumogiri: true,
+                    });
+                }
+            }
+        }
+        assert!(script.next().is_none());
+        state
+    }
+
+    fn hora(actor: u8, target: u8) -> Event {
+        Event::Hora {
+            actor,
+            target,
+            deltas: None,
+            ura_markers: None,
+        }
+    }
+
+    #[test]
+    fn daisangen_pao() {
+        let dahai = |actor, pai| Event::Dahai {
+            actor,
+            pai,
+            tsumogiri: false,
+        };
+        let pon = |target, pai| Event::Pon {
+            actor: 1,
+            target,
+            pai,
+            consumed: [pai; 2],
+        };
+        let script = [
+            dahai(0, t!(P)),
+            pon(0, t!(P)),
+            dahai(1, t!(7p)),
+            dahai(2, t!(F)),
+            pon(2, t!(F)),
+            dahai(1, t!(8p)),
+            // Seat 2 is liable for the third dragon.
+            dahai(2, t!(C)),
+            pon(2, t!(C)),
+            dahai(1, t!(3m)),
+            hora(1, 1),
+        ];
+        let state = play(
+            RuleSet::default(),
+            [
+                &[t!(P)],
+                &t![P, P, F, F, C, C, 1m, 2m, 3m, 9s, 9s, 7p, 8p],
+                &t![F, C],
+                &[],
+            ],
+            t![2s, 2s, 2s, 2s, 3s, 3s, 3s, 3s, 1s, 4s, 4s, 4s, 4s, 1s],
+            &t![9m, 9m, 9m, 9m, 1p, 1p, 3m],
+            &script,
+        );
+        let result = state.end();
+        assert_eq!(result.scores, [25000, 25000 + 32000, 25000 - 32000, 25000]);
+        assert_eq!(result.paos, [None, Some(2), None, None]);
+    }
+
+    #[test]
+    fn rinshan_pao() {
+        let script = [
+            Event::Dahai {
+                actor: 0,
+                pai: t!(5sr),
+                tsumogiri: false,
+            },
+            Event::Daiminkan {
+                actor: 1,
+                target: 0,
+                pai: t!(5sr),
+                consumed: t![5s, 5s, 5s],
+            },
+            hora(1, 1),
+        ];
+        let run = |rinshan_pao| {
+            let rule = RuleSet {
+                rinshan_pao,
+                ..Default::default()
+            };
+            play(
+                rule,
+                [
+                    &[t!(5sr)],
+                    &t![5s, 5s, 5s, 1m, 2m, 3m, 4p, 5p, 6p, 7p, 8p, 9p, 9s],
+                    &[],
+                    &[],
+                ],
+                t![2s, 2s, 2s, 9s, 3s, 3s, 3s, 3s, 1s, 4s, 4s, 4s, 4s, 1s],
+                &[],
+                &script,
+            )
+            .end()
+        };
+
+        // 40 fu 2 han, paid as a ron of 2600.
+        let result = run(true);
+        assert_eq!(result.scores, [25000 - 2600, 25000 + 2600, 25000, 25000]);
+        assert_eq!(result.paos, [None, Some(0), None, None]);
+
+        let result = run(false);
+        assert_eq!(
+            result.scores,
+            [25000 - 1300, 25000 + 2700, 25000 - 700, 25000 - 700],
+        );
+        assert_eq!(result.paos, [None; 4]);
+    }
+
+    #[test]
+    fn sanchaho() {
+        let script = [
+            Event::Dahai {
+                actor: 0,
+                pai: t!(5pr),
+                tsumogiri: false,
+            },
+            hora(1, 0),
+            hora(2, 0),
+            hora(3, 0),
+        ];
+        let run = |abortive_ryukyoku| {
+            let rule = RuleSet {
+                abortive_ryukyoku,
+                ..Default::default()
+            };
+            play(
+                rule,
+                [
+                    &[t!(5pr)],
+                    &t![2m, 3m, 4m, 4m, 5m, 6m, 6m, 7m, 8m, 2s, 3s, 4s, 5p],
+                    &t![2m, 3m, 4m, 4s, 5s, 6s, 6s, 7s, 8s, 2p, 3p, 4p, 5p],
+                    &t![2m, 3m, 4m, 6p, 7p, 8p, 3s, 4s, 5s, 6s, 7s, 8s, 5p],
+                ],
+                t![1s, 1s, 1s, 1s, 9s, 9s, 9s, 9s, E, 9p, 9p, 9p, 9p, E],
+                &[],
+                &script,
+            )
+        };
+
+        let state = run(true);
+        let result = state.end();
+        assert!(result.has_abortive_ryukyoku && !result.has_hora);
+        assert_eq!(result.scores, [25000; 4]);
+        assert!(state.log.iter().any(|ev| matches!(
+            ev.event,
+            Event::Ryukyoku {
+                reason: Some(RyukyokuReason::Sanchaho),
+                ..
+            },
+        )));
+
+        let result = run(false).end();
+        assert!(!result.has_abortive_ryukyoku && result.has_hora);
+        assert!(result.scores[0] < 25000);
+    }
+
+    #[test]
+    fn multi_ron() {
+        let script = [
+            Event::Dahai {
+                actor: 0,
+                pai: t!(5pr),
+                tsumogiri: false,
+            },
+            hora(1, 0),
+            hora(2, 0),
+        ];
+        let run = |double_ron, honba_per_winner| {
+            let board = Board {
+                scores: [25000; 4],
+                honba: 2,
+                kyotaku: 1,
+                rule: RuleSet {
+                    double_ron,
+                    honba_per_winner,
+                    ..Default::default()
+                },
+                ..Default::default()
+            };
+            let state = play_on(
+                board,
+                [
+                    &[t!(5pr)],
+                    &t![2m, 3m, 4m, 4m, 5m, 6m, 6m, 7m, 8m, 2s, 3s, 4s, 5p],
+                    &t![2m, 3m, 4m, 4s, 5s, 6s, 6s, 7s, 8s, 2p, 3p, 4p, 5p],
+                    &[],
+                ],
+                t![1s, 1s, 1s, 1s, 9s, 9s, 9s, 9s, E, 9p, 9p, 9p, 9p, E],
+                &[],
+                &script,
+            );
+            state
+                .log
+                .iter()
+                .filter_map(|ev| match ev.event {
+                    Event::Hora { actor, deltas, .. } => Some((actor, deltas.unwrap())),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        };
+
+        // The closest one takes the kyotaku and the honba.
+        let horas = run(true, false);
+        assert_eq!(horas.len(), 2);
+        let (first, second) = (horas[0].1, horas[1].1);
+        assert_eq!(horas[0].0, 1);
+        assert_eq!(first[1] + first[0], 1000);
+        assert_eq!(second[2] + second[0], 0);
+        let no_honba = second[2];
+
+        // Both get the honba, and only the closest one takes the kyotaku.
+        let horas = run(true, true);
+        assert_eq!(horas[0].1, first);
+        let second = horas[1].1;
+        assert_eq!(second[2] + second[0], 0);
+        assert_eq!(second[2], no_honba + 600);
+
+        // Head bump
+        for honba_per_winner in [false, true] {
+            let horas = run(false, honba_per_winner);
+            assert_eq!(horas, [(1, first)]);
+        }
+    }
+}
@@ -0,0 +1,352 @@
+use super::game::{BatchGame, Index};
+use super::result::GameResult;
+use crate::agent::BatchAgent;
+#[cfg(feature = "python")]
+use crate::agent::{AkochanAgent, MortalBatchAgent, RuleBased, Tsumogiri};
+use crate::log_io;
+use crate::par::*;
+use std::path::PathBuf;
+use std::{fs, iter};
+
+#[cfg(feature = "python")]
+use anyhow::bail;
+use anyhow::{ensure, Result};
+use indicatif::{ProgressBar, ProgressStyle};
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+use riichi_macros::{pyclass, pymethods};
+
+/// Duplicate mode, where each seed is played twice, first with `lineup` and
+/// then with the seats swapped, so that the challenger and the champion get
+/// the exact same walls and deals.
+///
+/// `lineup` consists of 0 for the challenger and 1 for the champion, for
+/// example `[0, 1, 1, 1]` for 1v3 and `[0, 1, 0, 1]` for 2v2.
+#[pyclass]
+#[pyo3(text_signature = "(
+    *,
+    lineup = [0, 1, 0, 1],
+    disable_progress_bar = False,
+    log_dir = None,
+)")]
+#[derive(Clone)]
+pub struct Duplicate {
+    pub lineup: [usize; 4],
+    pub disable_progress_bar: bool,
+    pub log_dir: Option<String>,
+}
+
+/// The results of one seed played in both the original and the swapped
+/// lineup.
+#[derive(Debug, Clone)]
+pub struct PairedResult {
+    pub lineup: [usize; 4],
+    pub original: GameResult,
+    pub swapped: GameResult,
+}
+
+#[pymethods]
+impl Duplicate {
+    #[new]
+    #[args(
+        "*",
+        lineup = "[0, 1, 0, 1]",
+        disable_progress_bar = "false",
+        log_dir = "None"
+    )]
+    pub(super) fn new(
+        lineup: [usize; 4],
+        disable_progress_bar: bool,
+        log_dir: Option<String>,
+    ) -> Result<Self> {
+        ensure!(
+            lineup.iter().all(|&a| a <= 1) && lineup.contains(&0) && lineup.contains(&1),
+            "lineup must consist of both 0 and 1, got {lineup:?}",
+        );
+        Ok(Self {
+            lineup,
+            disable_progress_bar,
+            log_dir,
+        })
+    }
+
+    /// Returns the paired score deltas of the challenger, one for each seed,
+    /// see `PairedResult::score_delta`.
+    #[cfg(feature = "python")]
+    #[pyo3(text_signature = "($self, challenger, champion, seed_start, seed_count)")]
+    pub fn py_vs_py(
+        &self,
+        challenger: PyObject,
+        champion: PyObject,
+        seed_start: (u64, u64),
+        seed_count: u64,
+        py: Python<'_>,
+    ) -> Result<Vec<f64>> {
+        py.allow_threads(move || {
+            let results = self.run_batch(
+                |player_ids| MortalBatchAgent::new(challenger, player_ids),
+                |player_ids| MortalBatchAgent::new(champion, player_ids),
+                seed_start,
+                seed_count,
+            )?;
+            Ok(results.iter().map(PairedResult::score_delta).collect())
+        })
+    }
+
+    /// Same as `py_vs_py`, with akochan as the champion.
+    #[cfg(feature = "python")]
+    #[pyo3(text_signature = "($self, engine, seed_start, seed_count)")]
+    pub fn py_vs_ako(
+        &self,
+        engine: PyObject,
+        seed_start: (u64, u64),
+        seed_count: u64,
+        py: Python<'_>,
+    ) -> Result<Vec<f64>> {
+        py.allow_threads(move || {
+            let results = self.run_batch(
+                |player_ids| MortalBatchAgent::new(engine, player_ids),
+                AkochanAgent::new_batched,
+                seed_start,
+                seed_count,
+            )?;
+            Ok(results.iter().map(PairedResult::score_delta).collect())
+        })
+    }
+
+    /// Same as `py_vs_py`, with a built-in baseline as the champion, which is
+    /// either `"rule_based"` or `"tsumogiri"`.
+    #[cfg(feature = "python")]
+    #[pyo3(text_signature = "($self, engine, baseline, seed_start, seed_count)")]
+    pub fn py_vs_baseline(
+        &self,
+        engine: PyObject,
+        baseline: &str,
+        seed_start: (u64, u64),
+        seed_count: u64,
+        py: Python<'_>,
+    ) -> Result<Vec<f64>> {
+        py.allow_threads(move || {
+            let new_challenger = |player_ids: &[u8]| MortalBatchAgent::new(engine, player_ids);
+            let results = match baseline {
+                "rule_based" => self.run_batch(
+                    new_challenger,
+                    RuleBased::new_batched,
+                    seed_start,
+                    seed_count,
+                )?,
+                "tsumogiri" => self.run_batch(
+                    new_challenger,
+                    Tsumogiri::new_batched,
+                    seed_start,
+                    seed_count,
+                )?,
+                _ => bail!("unknown baseline {baseline}"),
+            };
+            Ok(results.iter().map(PairedResult::score_delta).collect())
+        })
+    }
+}
+
+impl Duplicate {
+    pub fn run_batch<C, M, CA, MA>(
+        &self,
+        new_challenger_agent: C,
+        new_champion_agent: M,
+        seed_start: (u64, u64),
+        seed_count: u64,
+    ) -> Result<Vec<PairedResult>>
+    where
+        C: FnOnce(&[u8]) -> Result<CA>,
+        M: FnOnce(&[u8]) -> Result<MA>,
+        CA: BatchAgent + 'static,
+        MA: BatchAgent + 'static,
+    {
+        if let Some(dir) = &self.log_dir {
+            fs::create_dir_all(dir)?;
+        }
+
+        log::info!(
+            "seed: [{}, {}) w/ {}, start {} groups, {} hanchans",
+            seed_start.0,
+            seed_start.0 + seed_count,
+            seed_start.1,
+            seed_count,
+            seed_count * 2,
+        );
+
+        let seeds: Vec<_> = (seed_start.0..seed_start.0 + seed_count)
+            .flat_map(|seed| [(seed, seed_start.1); 2])
+            .collect();
+
+        let swapped = self.lineup.map(|a| 1 - a);
+        let mut player_ids = [vec![], vec![]];
+        for _ in 0..seed_count {
+            for (seat, &agent_idx) in self.lineup.iter().chain(&swapped).enumerate() {
+                player_ids[agent_idx].push(seat as u8 % 4);
+            }
+        }
+
+        let mut agents: [Box<dyn BatchAgent>; 2] = [
+            Box::new(new_challenger_agent(&player_ids[0])?),
+            Box::new(new_champion_agent(&player_ids[1])?),
+        ];
+        let batch_game = BatchGame::tenhou_hanchan(self.disable_progress_bar);
+
+        let mut player_id_idxs = [0; 2];
+        let mut make_idx_group = |agent_idxs: [usize; 4]| {
+            agent_idxs.map(|agent_idx| {
+                let player_id_idx = player_id_idxs[agent_idx];
+                player_id_idxs[agent_idx] += 1;
+                Index {
+                    agent_idx,
+                    player_id_idx,
+                }
+            })
+        };
+        let indexes: Vec<_> = (0..seed_count)
+            .flat_map(|_| [make_idx_group(self.lineup), make_idx_group(swapped)])
+            .collect();
+
+        let results = batch_game.run(&mut agents, &indexes, &seeds)?;
+
+        if let Some(dir) = &self.log_dir {
+            log::info!("dumping game logs");
+
+            let bar = if self.disable_progress_bar {
+                ProgressBar::hidden()
+            } else {
+                ProgressBar::new(seed_count * 2)
+            };
+            bar.set_style(
+                ProgressStyle::default_bar()
+                    .template("{spinner:.cyan} [{elapsed_precise}] [{wide_bar}] {pos}/{len} {percent:>3}%")
+                    .tick_chars(".oOo")
+                    .progress_chars("#-"),
+            );
+            bar.enable_steady_tick(150);
+
+            results
+                .par_iter()
+                .progress_with(bar)
+                .enumerate()
+                .try_for_each(|(i, game_result)| {
+                    let split_name = ["original", "swapped"][i % 2];
+                    let filename: PathBuf = [
+                        dir,
+                        &format!(
+                            "{}_{}_{split_name}.json.gz",
+                            game_result.seed.0, game_result.seed.1,
+                        ),
+                    ]
+                    .iter()
+                    .collect();
+
+                    fs::write(
+                        filename.with_extension("").with_extension("kyokus.jsonl"),
+                        game_result.dump_kyoku_records()?,
+                    )?;
+                    log_io::write_log(filename, &game_result.dump_json_log()?)?;
+
+                    anyhow::Ok(())
+                })?;
+        }
+
+        let mut results = results.into_iter();
+        let paired = iter::from_fn(|| {
+            Some(PairedResult {
+                lineup: self.lineup,
+                original: results.next()?,
+                swapped: results.next()?,
+            })
+        })
+        .collect();
+        Ok(paired)
+    }
+}
+
+impl PairedResult {
+    /// Returns the average final score of the challenger minus the one of
+    /// the champion, over both games.
+    ///
+    /// Both of them play every seat exactly once across the two games, so the
+    /// luck of the walls and deals cancels out to a large extent.
+    #[must_use]
+    pub fn score_delta(&self) -> f64 {
+        self.delta(|game, seat| game.scores[seat] as f64)
+    }
+
+    /// Returns the average rank (0-3) of the challenger minus the one of the
+    /// champion, over both games. Lower is better.
+    #[must_use]
+    pub fn rank_delta(&self) -> f64 {
+        self.delta(|game, seat| game.rankings().rank_by_player[seat] as f64)
+    }
+
+    fn delta<F>(&self, f: F) -> f64
+    where
+        F: Fn(&GameResult, usize) -> f64,
+    {
+        let mut sums = [0.; 2];
+        for (seat, &agent_idx) in self.lineup.iter().enumerate() {
+            sums[agent_idx] += f(&self.original, seat);
+            sums[1 - agent_idx] += f(&self.swapped, seat);
+        }
+        // Each of them plays 4 seats in total.
+        (sums[0] - sums[1]) / 4.
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::agent::{RuleBased, Tsumogiri};
+
+    #[test]
+    fn paired_result() {
+        let game = |scores| GameResult {
+            scores,
+            ..Default::default()
+        };
+        let paired = PairedResult {
+            lineup: [0, 1, 1, 1],
+            original: game([40000, 20000, 20000, 20000]),
+            swapped: game([10000, 30000, 30000, 30000]),
+        };
+        // Challenger: 40000 + 30000 * 3, champion: 20000 * 3 + 10000.
+        assert!((paired.score_delta() - (130000. - 70000.) / 4.).abs() < 1e-9);
+        // Challenger: 0 + (0 + 1 + 2), champion: (1 + 2 + 3) + 3.
+        assert!((paired.rank_delta() - (3. - 9.) / 4.).abs() < 1e-9);
+    }
+
+    #[test]
+    fn same_walls() {
+        let duplicate = Duplicate::new([0, 1, 1, 1], true, None).unwrap();
+        let results = duplicate
+            .run_batch(RuleBased::new_batched, Tsumogiri::new_batched, (1009, 0), 2)
+            .unwrap();
+        assert_eq!(results.len(), 2);
+
+        for (i, paired) in results.iter().enumerate() {
+            assert_eq!(paired.original.seed, (1009 + i as u64, 0));
+            assert_eq!(paired.swapped.seed, paired.original.seed);
+            assert_eq!(
+                paired.original.names,
+                ["rule-based", "tsumogiri", "tsumogiri", "tsumogiri"],
+            );
+            assert_eq!(
+                paired.swapped.names,
+                ["tsumogiri", "rule-based", "rule-based", "rule-based"],
+            );
+
+            // The deals of the first kyoku are the same regardless of the
+            // lineup.
+            let start = |game: &GameResult| game.game_log[0][0].event.clone();
+            assert_eq!(start(&paired.original), start(&paired.swapped));
+
+            assert!(paired.score_delta() > 0.);
+        }
+
+        assert!(Duplicate::new([0, 0, 0, 0], true, None).is_err());
+    }
+}
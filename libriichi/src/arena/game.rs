@@ -1,19 +1,31 @@
 use super::board::{Board, BoardState, Poll};
-use super::result::GameResult;
+use super::result::{GameResult, KyokuResult};
+use super::wall::Wall;
 use crate::agent::BatchAgent;
 use crate::mjai::EventExt;
+use crate::rule::RuleSet;
 use std::collections::VecDeque;
 use std::mem;
 
 use anyhow::{ensure, Result};
 use indicatif::{ProgressBar, ProgressStyle};
 use ndarray::prelude::*;
+use tracing::{debug_span, info_span};
 
 pub struct BatchGame {
-    /// 8 for hanchan and 4 for tonpuu
-    pub length: u8,
-    pub init_scores: [i32; 4],
+    pub rule: RuleSet,
     pub disable_progress_bar: bool,
+    /// Maximum number of games in progress at the same time, 0 for no limit.
+    ///
+    /// All the games in progress are stepped together, so that their scenes
+    /// are evaluated by each agent in one batch. Limiting it bounds the batch
+    /// size and the memory, with new games started as soon as others end,
+    /// which keeps the batches full.
+    pub max_concurrent_games: usize,
+    /// Names of the agents by `agent_idx`, such as the model IDs of the
+    /// checkpoints, which go to `GameResult::names` and thus the `start_game`
+    /// of the logs. Agents out of its range are named by `BatchAgent::name`.
+    pub agent_names: Vec<String>,
 }
 
 #[derive(Clone, Copy, Default)]
@@ -26,9 +38,10 @@ pub struct Index {
 
 #[derive(Default)]
 struct Game {
-    length: u8,
+    rule: RuleSet,
     seed: (u64, u64),
     indexes: [Index; 4],
+    names: [String; 4],
 
     need_invisible_state: [bool; 4],
     invisible_state_cache: [Option<Array2<f32>>; 4],
@@ -36,16 +49,29 @@ struct Game {
     last_reactions: [EventExt; 4], // cached for poll phase
 
     board: BoardState,
-    kyoku: u8,
-    honba: u8,
-    kyotaku: u8,
-    scores: [i32; 4],
+    progress: Progress,
     game_log: Vec<Vec<EventExt>>,
+    walls: Vec<Wall>,
+    paos: Vec<[Option<u8>; 4]>,
+    tenpai: Vec<Option<[bool; 4]>>,
+    /// Walls to use instead of the ones generated from `seed`, matched by
+    /// kyoku and honba.
+    preset_walls: Vec<Wall>,
 
     kyoku_started: bool,
-    ended: bool,
-    /// Used in 西入 where the oya and another player get to 30000 at the same
-    /// time, but the game continues because oya is not the top.
+}
+
+/// Where a game is at between kyokus, which decides the next kyoku and the
+/// end of the game.
+#[derive(Debug, Clone, Default)]
+pub(super) struct Progress {
+    pub(super) kyoku: u8,
+    pub(super) honba: u8,
+    pub(super) kyotaku: u8,
+    pub(super) scores: [i32; 4],
+    pub(super) ended: bool,
+    /// Used in 西入 where the oya and another player get to `goal_points` at
+    /// the same time, but the game continues because oya is not the top.
     ///
     /// As per [Tenhou's rule](https://tenhou.net/man/#RULE):
     ///
@@ -54,30 +80,137 @@ struct Game {
     in_renchan: bool,
 }
 
+impl Progress {
+    pub(super) const fn new(rule: &RuleSet) -> Self {
+        Self {
+            kyoku: 0,
+            honba: 0,
+            kyotaku: 0,
+            scores: [rule.starting_points; 4],
+            ended: false,
+            in_renchan: false,
+        }
+    }
+
+    /// Checks if the game is over before the next kyoku starts, in which case
+    /// `ended` is set and true is returned.
+    pub(super) fn check_end(&mut self, rule: &RuleSet) -> bool {
+        let length = rule.length();
+        if self.kyoku >= length + rule.extra_kyokus // no more 西入
+            || self.kyoku >= length // in 西入
+                && !self.in_renchan // oya is not in renchan
+                && self.scores.iter().any(|&s| s >= rule.goal_points)
+        {
+            self.ended = true;
+        }
+        self.ended
+    }
+
+    /// The board of the next kyoku, without a wall.
+    pub(super) fn next_board(&self, rule: RuleSet) -> Board {
+        Board {
+            kyoku: self.kyoku,
+            honba: self.honba,
+            kyotaku: self.kyotaku,
+            scores: self.scores,
+            rule,
+            ..Default::default()
+        }
+    }
+
+    /// Moves on after a kyoku ends with `kyoku_result`, which may end the
+    /// game.
+    pub(super) fn advance(&mut self, rule: &RuleSet, kyoku_result: &KyokuResult) {
+        self.in_renchan = false;
+        self.kyotaku = kyoku_result.kyotaku_left;
+        self.scores = kyoku_result.scores;
+
+        let has_tobi = self.scores.iter().any(|&s| s < 0);
+        if has_tobi {
+            self.ended = true;
+            return;
+        }
+
+        if kyoku_result.has_abortive_ryukyoku {
+            self.honba += 1;
+            return;
+        }
+
+        if !kyoku_result.can_renchan {
+            self.kyoku += 1;
+            if kyoku_result.has_hora {
+                self.honba = 0;
+            } else {
+                self.honba += 1;
+            }
+            return;
+        }
+
+        // renchan owari
+        //
+        // Conditions:
+        // 1. can renchan
+        // 2. agari-yame or tenpai-yame is enabled for how it renchans
+        // 3. is at all-last
+        // 4. oya has at least `goal_points`
+        // 5. oya is the top
+        let oya = kyoku_result.kyoku as usize % 4;
+        let can_yame = if kyoku_result.has_hora {
+            rule.agari_yame
+        } else {
+            rule.tenpai_yame
+        };
+        if can_yame && rule.is_all_last(kyoku_result.kyoku) && self.scores[oya] >= rule.goal_points
+        {
+            let top = kyoku_result
+                .scores
+                .iter()
+                .enumerate()
+                .min_by_key(|&(_, &s)| -s)
+                .map(|(i, _)| i)
+                .unwrap();
+            if top == oya {
+                self.ended = true;
+                return;
+            }
+        }
+
+        // renchan
+        self.in_renchan = true;
+        self.honba += 1;
+    }
+
+    /// The final scores, where the kyotaku left goes to the top.
+    pub(super) fn final_scores(&self) -> [i32; 4] {
+        let mut scores = self.scores;
+        if self.kyotaku > 0 {
+            *scores.iter_mut().min_by_key(|s| -**s).unwrap() += self.kyotaku as i32 * 1000;
+        }
+        scores
+    }
+}
+
 impl Game {
     fn poll(&mut self, agents: &mut [Box<dyn BatchAgent>]) -> Result<()> {
-        if self.ended {
+        if self.progress.ended {
             return Ok(());
         }
 
         if !self.kyoku_started {
-            if self.kyoku >= self.length + 4 // no 北入
-                || self.kyoku >= self.length // in 西入
-                    && !self.in_renchan // oya is not in renchan
-                    && self.scores.iter().any(|&s| s >= 30000)
-            {
-                self.ended = true;
+            if self.progress.check_end(&self.rule) {
                 return Ok(());
             }
 
-            let mut next_board = Board {
-                kyoku: self.kyoku,
-                honba: self.honba,
-                kyotaku: self.kyotaku,
-                scores: self.scores,
-                ..Default::default()
-            };
-            next_board.init_from_seed(self.seed);
+            let mut next_board = self.progress.next_board(self.rule);
+            let Progress { kyoku, honba, .. } = self.progress;
+            let wall = self
+                .preset_walls
+                .iter()
+                .find(|w| w.kyoku == kyoku && w.honba == honba)
+                .cloned()
+                .unwrap_or_else(|| Wall::from_seed(self.seed, kyoku, honba, self.rule.aka_count));
+            self.walls.push(wall.clone());
+            next_board.init_from_wall(wall);
             self.board = next_board.into_state();
             self.kyoku_started = true;
         }
@@ -108,65 +241,18 @@ impl Game {
 
             Poll::End => {
                 self.kyoku_started = false;
-                self.in_renchan = false;
 
                 for idx in &self.indexes {
                     agents[idx.agent_idx].end_kyoku(idx.player_id_idx)?;
                 }
 
                 let kyoku_result = self.board.end();
-                self.kyotaku = kyoku_result.kyotaku_left;
-                self.scores = kyoku_result.scores;
+                self.paos.push(kyoku_result.paos);
+                self.tenpai.push(kyoku_result.tenpai);
+                self.progress.advance(&self.rule, &kyoku_result);
 
                 let logs = self.board.take_log();
                 self.game_log.push(logs);
-
-                let has_tobi = self.scores.iter().any(|&s| s < 0);
-                if has_tobi {
-                    self.ended = true;
-                    return Ok(());
-                }
-
-                if kyoku_result.has_abortive_ryukyoku {
-                    self.honba += 1;
-                    return Ok(());
-                }
-
-                if !kyoku_result.can_renchan {
-                    self.kyoku += 1;
-                    if kyoku_result.has_hora {
-                        self.honba = 0;
-                    } else {
-                        self.honba += 1;
-                    }
-                    return Ok(());
-                }
-
-                // renchan owari
-                //
-                // Conditions:
-                // 1. can renchan
-                // 2. is at all-last
-                // 3. oya has at least 30000
-                // 4. oya is the top
-                let oya = kyoku_result.kyoku as usize % 4;
-                if kyoku_result.kyoku >= self.length - 1 && self.scores[oya] >= 30000 {
-                    let top = kyoku_result
-                        .scores
-                        .iter()
-                        .enumerate()
-                        .min_by_key(|&(_, &s)| -s)
-                        .map(|(i, _)| i)
-                        .unwrap();
-                    if top == oya {
-                        self.ended = true;
-                        return Ok(());
-                    }
-                }
-
-                // renchan
-                self.in_renchan = true;
-                self.honba += 1;
             }
         };
 
@@ -174,22 +260,15 @@ impl Game {
     }
 
     fn commit(&mut self, agents: &mut [Box<dyn BatchAgent>]) -> Result<Option<GameResult>> {
-        if self.ended {
-            if self.kyotaku > 0 {
-                *self.scores.iter_mut().min_by_key(|s| -**s).unwrap() += self.kyotaku as i32 * 1000;
-            }
-
-            let names = [
-                agents[self.indexes[0].agent_idx].name(),
-                agents[self.indexes[1].agent_idx].name(),
-                agents[self.indexes[2].agent_idx].name(),
-                agents[self.indexes[3].agent_idx].name(),
-            ];
+        if self.progress.ended {
             let game_result = GameResult {
-                names,
-                scores: self.scores,
+                names: mem::take(&mut self.names),
+                scores: self.progress.final_scores(),
                 seed: self.seed,
                 game_log: mem::take(&mut self.game_log),
+                walls: mem::take(&mut self.walls),
+                paos: mem::take(&mut self.paos),
+                tenpai: mem::take(&mut self.tenpai),
             };
 
             for idx in &self.indexes {
@@ -221,10 +300,15 @@ impl Game {
 
 impl BatchGame {
     pub const fn tenhou_hanchan(disable_progress_bar: bool) -> Self {
+        Self::with_rule(RuleSet::tenhou(), disable_progress_bar)
+    }
+
+    pub const fn with_rule(rule: RuleSet, disable_progress_bar: bool) -> Self {
         Self {
-            length: 8,
-            init_scores: [25000; 4],
+            rule,
             disable_progress_bar,
+            max_concurrent_games: 0,
+            agent_names: vec![],
         }
     }
 
@@ -233,6 +317,38 @@ impl BatchGame {
         agents: &mut [Box<dyn BatchAgent>],
         indexes: &[[Index; 4]],
         seeds: &[(u64, u64)],
+    ) -> Result<Vec<GameResult>> {
+        self.run_with_walls(agents, indexes, seeds, &[])
+    }
+
+    /// Replays games with the walls recorded in `GameResult::walls`, which
+    /// may also be loaded from files by `load_walls`.
+    ///
+    /// Kyokus not covered by the given walls, which may happen if the agents
+    /// behave differently from the original games, fall back to the ones
+    /// generated from `seeds`.
+    pub fn replay(
+        &self,
+        agents: &mut [Box<dyn BatchAgent>],
+        indexes: &[[Index; 4]],
+        seeds: &[(u64, u64)],
+        walls: &[Vec<Wall>],
+    ) -> Result<Vec<GameResult>> {
+        ensure!(
+            walls.len() == seeds.len(),
+            "expected `walls.len() == seeds.len()`, got {} and {}",
+            walls.len(),
+            seeds.len(),
+        );
+        self.run_with_walls(agents, indexes, seeds, walls)
+    }
+
+    fn run_with_walls(
+        &self,
+        agents: &mut [Box<dyn BatchAgent>],
+        indexes: &[[Index; 4]],
+        seeds: &[(u64, u64)],
+        walls: &[Vec<Wall>],
     ) -> Result<Vec<GameResult>> {
         ensure!(!agents.is_empty());
         ensure!(!indexes.is_empty());
@@ -243,37 +359,22 @@ impl BatchGame {
             seeds.len(),
         );
 
-        let mut games = indexes
-            .iter()
-            .zip(seeds)
-            .enumerate()
-            .map(|(game_idx, (idxs, &seed))| {
-                let mut need_invisible_state = [false; 4];
-                for (i, idx) in idxs.iter().enumerate() {
-                    agents[idx.agent_idx].start_game(idx.player_id_idx)?;
-                    need_invisible_state[i] = agents[idx.agent_idx].need_oracle_obs();
-                }
-
-                let game = Box::new(Game {
-                    length: self.length,
-                    seed,
-                    indexes: *idxs,
-                    scores: self.init_scores,
-                    need_invisible_state,
-                    ..Default::default()
-                });
-                Ok((game_idx, game))
-            })
-            .collect::<Result<VecDeque<_>>>()?;
+        let max_concurrent_games = if self.max_concurrent_games == 0 {
+            indexes.len()
+        } else {
+            self.max_concurrent_games
+        };
+        let mut pending = indexes.iter().zip(seeds).enumerate();
+        let mut games = VecDeque::with_capacity(max_concurrent_games);
 
-        let mut records = vec![GameResult::default(); games.len()];
+        let mut records = vec![GameResult::default(); indexes.len()];
         let mut to_remove = vec![];
         let mut steps = 0; // for stats only
 
         let bar = if self.disable_progress_bar {
             ProgressBar::hidden()
         } else {
-            ProgressBar::new(games.len() as u64)
+            ProgressBar::new(indexes.len() as u64)
         };
         bar.set_style(
             ProgressStyle::default_bar()
@@ -283,11 +384,25 @@ impl BatchGame {
         );
         bar.enable_steady_tick(150);
 
-        while !games.is_empty() {
+        let _span = info_span!("batch_game", games = indexes.len()).entered();
+        loop {
+            let _step = debug_span!("step", steps, games = games.len()).entered();
+            while games.len() < max_concurrent_games {
+                let Some((game_idx, (idxs, &seed))) = pending.next() else {
+                    break;
+                };
+                let preset_walls = walls.get(game_idx).cloned().unwrap_or_default();
+                let game = self.new_game(agents, *idxs, seed, preset_walls)?;
+                games.push_back((game_idx, game));
+            }
+            if games.is_empty() {
+                break;
+            }
+
             for (_, game) in &mut games {
                 loop {
                     game.poll(agents)?;
-                    if game.ended || game.kyoku_started {
+                    if game.progress.ended || game.kyoku_started {
                         break;
                     }
                 }
@@ -314,12 +429,90 @@ impl BatchGame {
 
         Ok(records)
     }
+
+    fn new_game(
+        &self,
+        agents: &mut [Box<dyn BatchAgent>],
+        indexes: [Index; 4],
+        seed: (u64, u64),
+        preset_walls: Vec<Wall>,
+    ) -> Result<Box<Game>> {
+        let mut need_invisible_state = [false; 4];
+        for (i, idx) in indexes.iter().enumerate() {
+            agents[idx.agent_idx].start_game(idx.player_id_idx)?;
+            need_invisible_state[i] = agents[idx.agent_idx].need_oracle_obs();
+        }
+
+        let names = indexes.map(|idx| {
+            self.agent_names
+                .get(idx.agent_idx)
+                .cloned()
+                .unwrap_or_else(|| agents[idx.agent_idx].name())
+        });
+
+        Ok(Box::new(Game {
+            rule: self.rule,
+            seed,
+            indexes,
+            names,
+            progress: Progress::new(&self.rule),
+            need_invisible_state,
+            preset_walls,
+            ..Default::default()
+        }))
+    }
 }
 
 #[cfg(test)]
 mod test {
+    use super::super::board::UNSHUFFLED;
+    use super::super::wall::{dump_walls, load_walls, Wall};
     use super::*;
-    use crate::agent::{BatchAgent, Tsumogiri};
+    use crate::agent::{BatchAgent, RuleBased, Tsumogiri};
+    use crate::arena::KyokuEndState;
+    use crate::consts::TILES_LEFT_AT_START;
+    use crate::mjai::{Event, RyukyokuReason};
+    use crate::t;
+
+    #[test]
+    fn renchan_owari() {
+        // S4 where the oya is the top with 40000.
+        let kyoku_result = |has_hora| KyokuResult {
+            kyoku: 7,
+            honba: 0,
+            can_renchan: true,
+            has_hora,
+            has_abortive_ryukyoku: false,
+            kyotaku_left: 0,
+            scores: [20000, 20000, 20000, 40000],
+            paos: [None; 4],
+            tenpai: (!has_hora).then_some([false, false, false, true]),
+        };
+        let advance = |rule: &RuleSet, has_hora| {
+            let mut progress = Progress::new(rule);
+            progress.kyoku = 7;
+            progress.advance(rule, &kyoku_result(has_hora));
+            progress.ended
+        };
+
+        let tenhou = RuleSet::default();
+        assert!(advance(&tenhou, true));
+        assert!(advance(&tenhou, false));
+
+        let no_agari_yame = RuleSet {
+            agari_yame: false,
+            ..Default::default()
+        };
+        assert!(!advance(&no_agari_yame, true));
+        assert!(advance(&no_agari_yame, false));
+
+        let no_tenpai_yame = RuleSet {
+            tenpai_yame: false,
+            ..Default::default()
+        };
+        assert!(advance(&no_tenpai_yame, true));
+        assert!(!advance(&no_tenpai_yame, false));
+    }
 
     #[test]
     fn tsumogiri() {
@@ -370,4 +563,247 @@ mod test {
         g.run(&mut agents, indexes, &[(1009, 0), (1021, 0)])
             .unwrap();
     }
+
+    #[test]
+    fn rule_based() {
+        let g = BatchGame::tenhou_hanchan(true);
+        let mut agents: Vec<Box<dyn BatchAgent>> = vec![
+            Box::new(RuleBased::new_batched(&[0, 1]).unwrap()),
+            Box::new(Tsumogiri::new_batched(&[2, 3]).unwrap()),
+        ];
+        let indexes = &[[
+            Index {
+                agent_idx: 0,
+                player_id_idx: 0,
+            },
+            Index {
+                agent_idx: 0,
+                player_id_idx: 1,
+            },
+            Index {
+                agent_idx: 1,
+                player_id_idx: 0,
+            },
+            Index {
+                agent_idx: 1,
+                player_id_idx: 1,
+            },
+        ]];
+
+        let results = g.run(&mut agents, indexes, &[(1009, 0)]).unwrap();
+        // Any kyotaku left at the end goes to the top, so nothing is lost.
+        let scores = results[0].scores;
+        assert_eq!(scores.iter().sum::<i32>(), 100_000);
+
+        let events: Vec<Event> = results[0]
+            .dump_json_log()
+            .unwrap()
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        assert_eq!(crate::validate::validate(&events), []);
+        assert!(matches!(events.last(), Some(Event::EndGame)));
+
+        // Unlike Tsumogiri, RuleBased goes for its hands.
+        for seat in [0, 1] {
+            assert!(
+                events.iter().any(|ev| matches!(
+                    *ev,
+                    Event::Hora { actor, .. } | Event::Reach { actor, .. } if actor == seat,
+                )),
+                "seat {seat} never declares riichi or wins",
+            );
+        }
+        assert!(!events.iter().any(|ev| matches!(
+            *ev,
+            Event::Hora { actor: 2 | 3, .. } | Event::Reach { actor: 2 | 3, .. },
+        )));
+    }
+
+    #[test]
+    fn kyoku_records() {
+        let g = BatchGame::tenhou_hanchan(true);
+        let mut agents: Vec<Box<dyn BatchAgent>> =
+            vec![Box::new(RuleBased::new_batched(&[0, 1, 2, 3]).unwrap())];
+        let indexes = &[[0, 1, 2, 3].map(|i| Index {
+            agent_idx: 0,
+            player_id_idx: i,
+        })];
+        let result = g.run(&mut agents, indexes, &[(1009, 0)]).unwrap().remove(0);
+
+        let records = result.kyoku_records().unwrap();
+        assert_eq!(records.len(), result.game_log.len());
+        for (record, next) in records.iter().zip(&records[1..]) {
+            for i in 0..4 {
+                assert_eq!(record.scores[i] + record.deltas[i], next.scores[i]);
+            }
+        }
+        for record in &records {
+            let wins = record
+                .end_states
+                .iter()
+                .filter(|&&s| s == KyokuEndState::Win)
+                .count();
+            assert_eq!(wins > 0, record.ryukyoku_reason.is_none());
+            if record.tenpai.is_some() {
+                assert_eq!(record.ryukyoku_reason, Some(RyukyokuReason::Fanpai));
+            }
+            assert!(record.tiles_left < TILES_LEFT_AT_START);
+        }
+        assert!(records.iter().any(|r| r.riichi.contains(&true)));
+
+        let dumped = result.dump_kyoku_records().unwrap();
+        assert_eq!(dumped.lines().count(), records.len());
+    }
+
+    #[test]
+    fn replay() {
+        let g = BatchGame::tenhou_hanchan(true);
+        let indexes = &[[0, 1, 2, 3].map(|player_id_idx| Index {
+            agent_idx: 0,
+            player_id_idx,
+        })];
+        let new_agents = || -> Vec<Box<dyn BatchAgent>> {
+            vec![Box::new(RuleBased::new_batched(&[0, 1, 2, 3]).unwrap())]
+        };
+
+        let original = g
+            .run(&mut new_agents(), indexes, &[(1009, 0)])
+            .unwrap()
+            .remove(0);
+        assert_eq!(original.walls.len(), original.game_log.len());
+
+        let path = std::env::temp_dir().join(format!("riichi_replay_{}.jsonl", std::process::id()));
+        dump_walls(&path, &original.walls).unwrap();
+        let walls = load_walls(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        // The seed given here is not used at all.
+        let replayed = g
+            .replay(&mut new_agents(), indexes, &[(0, 0)], &[walls])
+            .unwrap()
+            .remove(0);
+        assert_eq!(replayed.scores, original.scores);
+        assert_eq!(
+            replayed
+                .dump_json_log()
+                .unwrap()
+                .lines()
+                .skip(1)
+                .collect::<Vec<_>>(),
+            original
+                .dump_json_log()
+                .unwrap()
+                .lines()
+                .skip(1)
+                .collect::<Vec<_>>(),
+        );
+    }
+
+    #[test]
+    fn custom_wall_haitei() {
+        // Seat 1 is tenpai on 5p tanki from the start, and the only 5p left
+        // in the yama is the haitei, which is drawn by seat 1 as the 70th
+        // tsumo.
+        let tehai_1 = t![1m, 2m, 3m, 4m, 5m, 6m, 7m, 8m, 9m, 1p, 2p, 3p, 5p];
+        let haitei = t!(5p);
+        let dead_wall = t![5p, 5pr, E, E, S, S, W, W, N, N, P, P, F, F];
+
+        let mut rest: Vec<_> = UNSHUFFLED.to_vec();
+        for tile in tehai_1.iter().chain(&dead_wall).chain([&haitei]) {
+            let pos = rest.iter().position(|t| t == tile).unwrap();
+            rest.remove(pos);
+        }
+        rest.sort_by_key(|t| t.deaka() == t!(5p));
+        assert!(rest.iter().all(|t| t.deaka() != t!(5p)));
+
+        let mut tiles = vec![];
+        tiles.extend_from_slice(&rest[..13]);
+        tiles.extend_from_slice(&tehai_1);
+        tiles.extend_from_slice(&rest[13..13 * 3]);
+        tiles.extend_from_slice(&dead_wall);
+        tiles.push(haitei);
+        tiles.extend_from_slice(&rest[13 * 3..]);
+        let wall = Wall::from_tiles(0, 0, tiles.try_into().unwrap()).unwrap();
+
+        let g = BatchGame::tenhou_hanchan(true);
+        let mut agents: Vec<Box<dyn BatchAgent>> = vec![
+            Box::new(RuleBased::new_batched(&[1]).unwrap()),
+            Box::new(Tsumogiri::new_batched(&[0, 2, 3]).unwrap()),
+        ];
+        let indexes = &[[
+            Index {
+                agent_idx: 1,
+                player_id_idx: 0,
+            },
+            Index {
+                agent_idx: 0,
+                player_id_idx: 0,
+            },
+            Index {
+                agent_idx: 1,
+                player_id_idx: 1,
+            },
+            Index {
+                agent_idx: 1,
+                player_id_idx: 2,
+            },
+        ]];
+        let result = g
+            .replay(&mut agents, indexes, &[(1009, 0)], &[vec![wall]])
+            .unwrap()
+            .remove(0);
+
+        let kyoku = &result.game_log[0];
+        let tsumo_count = kyoku
+            .iter()
+            .filter(|ev| matches!(ev.event, Event::Tsumo { .. }))
+            .count();
+        assert_eq!(tsumo_count, 70);
+        let hora = kyoku
+            .iter()
+            .find(|ev| matches!(ev.event, Event::Hora { .. }))
+            .unwrap();
+        assert!(matches!(
+            hora.event,
+            Event::Hora {
+                actor: 1,
+                target: 1,
+                ..
+            },
+        ));
+    }
+
+    #[test]
+    fn max_concurrent_games() {
+        let seeds = [(1009, 0), (1021, 0), (1031, 0)];
+        let indexes: Vec<_> = (0..seeds.len())
+            .map(|game_idx| {
+                [0, 1, 2, 3].map(|seat| Index {
+                    agent_idx: seat % 2,
+                    player_id_idx: game_idx * 2 + seat / 2,
+                })
+            })
+            .collect();
+        let run = |max_concurrent_games| {
+            let g = BatchGame {
+                max_concurrent_games,
+                ..BatchGame::tenhou_hanchan(true)
+            };
+            let mut agents: Vec<Box<dyn BatchAgent>> = vec![
+                Box::new(RuleBased::new_batched(&[0, 2].repeat(seeds.len())).unwrap()),
+                Box::new(Tsumogiri::new_batched(&[1, 3].repeat(seeds.len())).unwrap()),
+            ];
+            g.run(&mut agents, &indexes, &seeds).unwrap()
+        };
+
+        let unlimited = run(0);
+        for limit in [1, 2] {
+            let limited = run(limit);
+            for (l, u) in limited.iter().zip(&unlimited) {
+                assert_eq!(l.seed, u.seed);
+                assert_eq!(l.scores, u.scores);
+            }
+        }
+    }
 }
@@ -0,0 +1,442 @@
+use super::board::{BoardState, Poll};
+use super::game::Progress;
+#[cfg(feature = "python")]
+use crate::error::Error;
+use crate::mjai::{Event, EventExt, GameMeta};
+use crate::rule::RuleSet;
+use crate::state::{InvalidReaction, PlayerState};
+use crate::t;
+use std::mem;
+
+use anyhow::{ensure, Result};
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+use riichi_macros::{pyclass, pymethods};
+use serde_json as json;
+
+/// A full-information referee of one game, for hosting games where the
+/// players, be they humans or bots, act through mjai events.
+///
+/// The game is dealt from `seed` the same way as in the arena. Each seat gets
+/// its own view of the events from `take_events`, where the tiles of others
+/// are hidden as `?`, and reacts by `act` whenever `can_act` holds for it.
+/// The game moves on once every seat that can act has reacted.
+///
+/// Unless `RuleSet::chombo` is disabled, an illegal reaction that counts as a
+/// chombo ends the kyoku with an `Event::Chombo` instead of being rejected,
+/// after which the kyoku is replayed. The replay is dealt anew from the nonce
+/// of `seed` plus the number of chombos so far.
+#[pyclass]
+#[pyo3(text_signature = "(seed, *, names = None, rule = None)")]
+pub struct GameState {
+    rule: RuleSet,
+    seed: (u64, u64),
+    progress: Progress,
+    board: BoardState,
+    reactions: [Option<Event>; 4],
+    /// The whole game so far.
+    log: Vec<Event>,
+    /// Index into `log` of the first event not yet taken by each seat.
+    cursors: [usize; 4],
+    chombos: u64,
+}
+
+#[pymethods]
+impl GameState {
+    #[new]
+    #[args("*", names = "None", rule = "None")]
+    fn py_new(seed: (u64, u64), names: Option<[String; 4]>, rule: Option<RuleSet>) -> Result<Self> {
+        Self::new(seed, names.unwrap_or_default(), rule.unwrap_or_default())
+    }
+
+    /// Returns the events since the last call for `seat`, as seen by it, as
+    /// a list of mjai JSON strings.
+    #[pyo3(name = "take_events")]
+    #[pyo3(text_signature = "($self, seat, /)")]
+    fn take_events_py(&mut self, seat: u8) -> Result<Vec<String>> {
+        self.take_events(seat)?
+            .iter()
+            .map(|ev| Ok(json::to_string(ev)?))
+            .collect()
+    }
+
+    /// Plays `mjai_json` as the reaction of `seat`, where `{"type":"none"}`
+    /// passes.
+    ///
+    /// Raises an `InvalidReactionError`, or one of its subclasses, if the
+    /// reaction is not valid, in which case nothing changes and the seat may
+    /// try again, unless it is played as a chombo.
+    #[cfg(feature = "python")]
+    #[pyo3(name = "act")]
+    #[pyo3(text_signature = "($self, seat, mjai_json, /)")]
+    fn act_py(&mut self, seat: u8, mjai_json: &str) -> PyResult<()> {
+        let action = json::from_str(mjai_json).map_err(Error::Parse)?;
+        self.act(seat, action)
+            .map_err(|err| match err.downcast::<InvalidReaction>() {
+                Ok(err) => err.into(),
+                Err(err) => err.into(),
+            })
+    }
+
+    #[pyo3(name = "can_act")]
+    #[pyo3(text_signature = "($self, seat, /)")]
+    fn can_act_py(&self, seat: u8) -> Result<bool> {
+        ensure!(seat < 4, "{seat} is not a valid seat");
+        Ok(self.can_act(seat))
+    }
+
+    /// Returns a copy of the `PlayerState` of `seat`, which holds its action
+    /// candidates.
+    #[pyo3(name = "player_state")]
+    #[pyo3(text_signature = "($self, seat, /)")]
+    fn player_state_py(&self, seat: u8) -> Result<PlayerState> {
+        ensure!(seat < 4, "{seat} is not a valid seat");
+        Ok(self.player_state(seat).clone())
+    }
+
+    #[getter(ended)]
+    const fn ended_py(&self) -> bool {
+        self.ended()
+    }
+
+    /// The scores as of now, or the final scores if the game has ended.
+    #[getter(scores)]
+    fn scores_py(&self) -> [i32; 4] {
+        self.scores()
+    }
+
+    /// The whole game so far as an mjai log in JSON lines, with nothing
+    /// hidden.
+    #[getter(log)]
+    fn log_py(&self) -> Result<String> {
+        let mut ret = String::new();
+        for ev in &self.log {
+            ret.push_str(&json::to_string(ev)?);
+            ret.push('\n');
+        }
+        Ok(ret)
+    }
+}
+
+impl GameState {
+    pub fn new(seed: (u64, u64), names: [String; 4], rule: RuleSet) -> Result<Self> {
+        let start_game = Event::StartGame {
+            names,
+            seed: Some(seed),
+            meta: Some(GameMeta {
+                rule: Some(rule),
+                ..Default::default()
+            }),
+        };
+
+        let mut ret = Self {
+            rule,
+            seed,
+            progress: Progress::new(&rule),
+            board: BoardState::default(),
+            reactions: Default::default(),
+            log: vec![start_game],
+            cursors: [0; 4],
+            chombos: 0,
+        };
+        ret.start_kyoku();
+        if !ret.progress.ended {
+            ret.poll(Default::default())?;
+        }
+        Ok(ret)
+    }
+
+    #[must_use]
+    pub const fn ended(&self) -> bool {
+        self.progress.ended
+    }
+
+    #[must_use]
+    pub fn scores(&self) -> [i32; 4] {
+        if self.progress.ended {
+            self.progress.final_scores()
+        } else {
+            self.progress.scores
+        }
+    }
+
+    #[must_use]
+    pub fn log(&self) -> &[Event] {
+        &self.log
+    }
+
+    #[must_use]
+    pub fn player_state(&self, seat: u8) -> &PlayerState {
+        &self.board.agent_context().player_states[seat as usize]
+    }
+
+    /// Whether `seat` is to react and has not yet.
+    #[must_use]
+    pub fn can_act(&self, seat: u8) -> bool {
+        !self.progress.ended
+            && self.reactions[seat as usize].is_none()
+            && self.player_state(seat).last_cans().can_act()
+    }
+
+    /// Returns the events since the last call for `seat`, as seen by it.
+    pub fn take_events(&mut self, seat: u8) -> Result<Vec<Event>> {
+        ensure!(seat < 4, "{seat} is not a valid seat");
+        let cursor = mem::replace(&mut self.cursors[seat as usize], self.log.len());
+        let events = self.log[cursor..]
+            .iter()
+            .map(|ev| hide_for(ev, seat))
+            .collect();
+        Ok(events)
+    }
+
+    /// Plays `action` as the reaction of `seat`, where `Event::None` passes.
+    ///
+    /// An invalid reaction is rejected with an `InvalidReaction` error, which
+    /// leaves the game untouched, unless it is a chombo under
+    /// `RuleSet::chombo`, which is settled right away.
+    pub fn act(&mut self, seat: u8, action: Event) -> Result<()> {
+        ensure!(seat < 4, "{seat} is not a valid seat");
+        if !self.can_act(seat) {
+            return Err(InvalidReaction::Unavailable {
+                action: "react now",
+            }
+            .into());
+        }
+        let state = self.player_state(seat);
+        if matches!(action, Event::None) && state.last_cans().is_forced() {
+            return Err(InvalidReaction::Unavailable { action: "pass" }.into());
+        }
+        if let Err(err) = state.validate_reaction(&action) {
+            let oya = self.progress.kyoku % 4;
+            return match self.rule.chombo.deltas(seat, oya, self.rule.chombo_points) {
+                Some(deltas) if err.is_chombo() => self.chombo(seat, deltas),
+                _ => Err(err.into()),
+            };
+        }
+        self.reactions[seat as usize] = Some(action);
+
+        let all_reacted = (0..4).all(|s| {
+            self.reactions[s as usize].is_some() || !self.player_state(s).last_cans().can_act()
+        });
+        if all_reacted {
+            let reactions =
+                mem::take(&mut self.reactions).map(|r| EventExt::no_meta(r.unwrap_or_default()));
+            self.poll(reactions)?;
+        }
+        Ok(())
+    }
+
+    /// Sets `ended` and logs `end_game` if there is no more kyoku, otherwise
+    /// deals the next one.
+    fn start_kyoku(&mut self) {
+        if self.progress.check_end(&self.rule) {
+            self.log.push(Event::EndGame);
+            return;
+        }
+        let mut board = self.progress.next_board(self.rule);
+        let (nonce, key) = self.seed;
+        board.init_from_seed((nonce.wrapping_add(self.chombos), key));
+        self.board = board.into_state();
+    }
+
+    /// Ends the kyoku with a chombo of `seat` and replays it, or ends the game
+    /// on a tobi.
+    ///
+    /// The kyoku is called off as a whole, so the deltas apply to the scores
+    /// at its start, which gives the riichi sticks of it back.
+    fn chombo(&mut self, seat: u8, deltas: [i32; 4]) -> Result<()> {
+        self.log.push(Event::Chombo {
+            actor: seat,
+            deltas: Some(deltas),
+        });
+        self.log.push(Event::EndKyoku);
+        for (s, d) in self.progress.scores.iter_mut().zip(deltas) {
+            *s += d;
+        }
+        self.reactions = Default::default();
+        self.chombos += 1;
+
+        if self.progress.scores.iter().any(|&s| s < 0) {
+            self.progress.ended = true;
+            self.log.push(Event::EndGame);
+            return Ok(());
+        }
+        self.start_kyoku();
+        if self.progress.ended {
+            return Ok(());
+        }
+        self.poll(Default::default())
+    }
+
+    /// Runs the game until someone is to react or the game ends.
+    fn poll(&mut self, mut reactions: [EventExt; 4]) -> Result<()> {
+        loop {
+            let poll = self.board.poll(reactions)?;
+            self.log
+                .extend(self.board.take_log().into_iter().map(|ev| ev.event));
+            match poll {
+                Poll::InGame => return Ok(()),
+                Poll::End => {
+                    self.progress.advance(&self.rule, &self.board.end());
+                    if self.progress.ended {
+                        self.log.push(Event::EndGame);
+                        return Ok(());
+                    }
+                    self.start_kyoku();
+                    if self.progress.ended {
+                        return Ok(());
+                    }
+                }
+            }
+            reactions = Default::default();
+        }
+    }
+}
+
+/// Hides what `seat` cannot see in `ev`, which are the seed, the haipai of
+/// others and the tsumos of others.
+fn hide_for(ev: &Event, seat: u8) -> Event {
+    match ev {
+        Event::StartGame { names, meta, .. } => Event::StartGame {
+            names: names.clone(),
+            seed: None,
+            meta: meta.clone(),
+        },
+        Event::StartKyoku {
+            bakaze,
+            dora_marker,
+            kyoku,
+            honba,
+            kyotaku,
+            oya,
+            scores,
+            tehais,
+        } => {
+            let mut tehais = *tehais;
+            for (i, tehai) in tehais.iter_mut().enumerate() {
+                if i != seat as usize {
+                    tehai.fill(t!(?));
+                }
+            }
+            Event::StartKyoku {
+                bakaze: *bakaze,
+                dora_marker: *dora_marker,
+                kyoku: *kyoku,
+                honba: *honba,
+                kyotaku: *kyotaku,
+                oya: *oya,
+                scores: *scores,
+                tehais,
+            }
+        }
+        &Event::Tsumo { actor, .. } if actor != seat => Event::Tsumo { actor, pai: t!(?) },
+        _ => ev.clone(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::agent::{Agent, RuleBased};
+    use crate::rule::Chombo;
+
+    #[test]
+    fn rule_based_game() {
+        let mut game = GameState::new((1009, 0), Default::default(), RuleSet::tenhou()).unwrap();
+        let mut agents = [0, 1, 2, 3].map(RuleBased);
+        let mut views = [0, 1, 2, 3].map(PlayerState::new);
+
+        while !game.ended() {
+            for seat in 0..4 {
+                for ev in game.take_events(seat).unwrap() {
+                    if let Event::Tsumo { actor, pai } = ev {
+                        assert_eq!(pai == t!(?), actor != seat);
+                    }
+                    views[seat as usize].update(&ev).unwrap();
+                }
+            }
+
+            // Seats react one at a time, and the events only come once all
+            // of them have.
+            let seat = (0..4).find(|&s| game.can_act(s)).unwrap();
+            let state = &views[seat as usize];
+            assert!(state.last_cans().can_act());
+
+            let other = (seat + 1) % 4;
+            if !game.can_act(other) {
+                let err = game.act(other, Event::None).unwrap_err();
+                assert!(err.downcast_ref::<InvalidReaction>().is_some());
+            }
+
+            let reaction = agents[seat as usize].react(&[], state, None).unwrap();
+            game.act(seat, reaction.event).unwrap();
+        }
+
+        assert!(matches!(game.log().last(), Some(Event::EndGame)));
+        assert_eq!(crate::validate::validate(game.log()), []);
+        assert_eq!(game.scores().iter().sum::<i32>(), 100_000);
+    }
+
+    #[test]
+    fn chombo() {
+        let false_tsumo = Event::Hora {
+            actor: 0,
+            target: 0,
+            deltas: None,
+            ura_markers: None,
+        };
+
+        let mut game = GameState::new((1009, 0), Default::default(), RuleSet::tenhou()).unwrap();
+        assert!(game.can_act(0));
+        assert!(!game.player_state(0).last_cans().can_tsumo_agari);
+        let err = game.act(0, false_tsumo.clone()).unwrap_err();
+        assert!(err.downcast_ref::<InvalidReaction>().unwrap().is_chombo());
+
+        let rule = RuleSet {
+            chombo: Chombo::ReverseMangan,
+            ..RuleSet::tenhou()
+        };
+        let mut game = GameState::new((1009, 0), Default::default(), rule).unwrap();
+        let haipai = game.player_state(0).tehai();
+        // Malformed reactions are still rejected.
+        let err = game.act(0, Event::ReachAccepted { actor: 0 }).unwrap_err();
+        assert!(!err.downcast_ref::<InvalidReaction>().unwrap().is_chombo());
+
+        game.act(0, false_tsumo).unwrap();
+        let n = game.log().len();
+        assert_eq!(
+            game.log()[n - 4..n - 2],
+            [
+                Event::Chombo {
+                    actor: 0,
+                    deltas: Some([-12000, 4000, 4000, 4000]),
+                },
+                Event::EndKyoku,
+            ],
+        );
+        match &game.log()[n - 2] {
+            Event::StartKyoku {
+                kyoku,
+                honba,
+                scores,
+                ..
+            } => {
+                assert_eq!((*kyoku, *honba), (1, 0));
+                assert_eq!(*scores, [13000, 29000, 29000, 29000]);
+            }
+            ev => panic!("unexpected {ev:?}"),
+        }
+        // The replay is dealt anew.
+        assert_ne!(game.player_state(0).tehai(), haipai);
+
+        let mut agents = [0, 1, 2, 3].map(RuleBased);
+        while !game.ended() {
+            let seat = (0..4).find(|&s| game.can_act(s)).unwrap();
+            let state = game.player_state(seat);
+            let reaction = agents[seat as usize].react(&[], state, None).unwrap();
+            game.act(seat, reaction.event).unwrap();
+        }
+        assert_eq!(crate::validate::validate(game.log()), []);
+        assert_eq!(game.scores().iter().sum::<i32>(), 100_000);
+    }
+}
@@ -0,0 +1,327 @@
+use super::game::{BatchGame, Index};
+use super::result::GameResult;
+#[cfg(feature = "python")]
+use super::tournament::AgentFactory;
+use super::tournament::Entrant;
+#[cfg(feature = "python")]
+use crate::agent::{BatchAgent, MortalBatchAgent};
+
+use anyhow::{bail, ensure, Result};
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+use riichi_macros::{pyclass, pymethods};
+use serde::Serialize;
+#[cfg(feature = "python")]
+use serde_json as json;
+
+/// Which seat permutations of the four entrants every seed is played in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SeatSchedule {
+    /// The 4 rotations of the given order, where every entrant takes every
+    /// seat exactly once, while the order around the table is kept.
+    Rotations,
+    /// All the 24 permutations, where every entrant also sits after each of
+    /// the others equally often.
+    Permutations,
+}
+
+/// Four possibly distinct entrants at one table, where every seed is played
+/// in several seat permutations according to `seats`, so that the seats and
+/// the walls are balanced among them.
+///
+/// This is for comparing more than two engines at once, as opposed to the
+/// challenger and the champion of `OneVsThree`, `TwoVsTwo` and `Duplicate`.
+#[pyclass]
+#[pyo3(text_signature = "(
+    *,
+    seats = 'rotations',
+    disable_progress_bar = False,
+)")]
+#[derive(Debug, Clone)]
+pub struct Lineup {
+    pub seats: SeatSchedule,
+    pub disable_progress_bar: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct LineupStanding {
+    pub name: String,
+    /// Number of hanchans played.
+    pub games: u32,
+    /// Counts of 1st to 4th.
+    pub rank_counts: [u32; 4],
+    /// Counts from 1.
+    pub avg_rank: f64,
+    pub avg_score: f64,
+}
+
+#[pymethods]
+impl Lineup {
+    #[new]
+    #[args("*", seats = "\"rotations\"", disable_progress_bar = "false")]
+    fn py_new(seats: &str, disable_progress_bar: bool) -> Result<Self> {
+        let seats = match seats {
+            "rotations" => SeatSchedule::Rotations,
+            "permutations" => SeatSchedule::Permutations,
+            _ => bail!("unknown seat schedule {seats}"),
+        };
+        Ok(Self {
+            seats,
+            disable_progress_bar,
+        })
+    }
+
+    /// `engines` is a list of exactly 4 `(name, engine)`, in the order of the
+    /// seats of the first game of each seed. The same engine may appear more
+    /// than once under different names, which are the names of the seats in
+    /// the logs.
+    ///
+    /// Returns the standing of each entrant as a JSON string, in the order of
+    /// `engines`.
+    #[cfg(feature = "python")]
+    #[pyo3(name = "run")]
+    #[pyo3(text_signature = "($self, engines, seed_start, seed_count, /)")]
+    fn run_py(
+        &self,
+        engines: Vec<(String, PyObject)>,
+        seed_start: (u64, u64),
+        seed_count: u64,
+        py: Python<'_>,
+    ) -> Result<String> {
+        let entrants = engines
+            .into_iter()
+            .map(|(name, engine)| {
+                let new_agent: AgentFactory = Box::new(move |player_ids: &[u8]| {
+                    let engine = Python::with_gil(|py| engine.clone_ref(py));
+                    let agent = MortalBatchAgent::new(engine, player_ids)?;
+                    Ok(Box::new(agent) as Box<dyn BatchAgent>)
+                });
+                Entrant { name, new_agent }
+            })
+            .collect::<Vec<_>>();
+        py.allow_threads(move || {
+            let results = self.run(&entrants, seed_start, seed_count)?;
+            let standings = self.standings(&entrants, &results);
+            Ok(json::to_string(&standings)?)
+        })
+    }
+}
+
+impl SeatSchedule {
+    /// Returns the permutations, where `perm[seat]` is the index of the
+    /// entrant at `seat`.
+    #[must_use]
+    pub fn permutations(self) -> Vec<[usize; 4]> {
+        match self {
+            Self::Rotations => (0..4).map(|r| [0, 1, 2, 3].map(|s| (s + r) % 4)).collect(),
+            Self::Permutations => {
+                let mut ret = vec![];
+                for a in 0..4 {
+                    for b in (0..4).filter(|&b| b != a) {
+                        for c in (0..4).filter(|&c| c != a && c != b) {
+                            ret.push([a, b, c, 6 - a - b - c]);
+                        }
+                    }
+                }
+                ret
+            }
+        }
+    }
+}
+
+impl Lineup {
+    /// Runs `seed_count` seeds starting from `seed_start`, each in all the
+    /// permutations of `self.seats`.
+    ///
+    /// The results are grouped by seed, and then in the order of
+    /// `SeatSchedule::permutations`, which tells who sat where.
+    pub fn run(
+        &self,
+        entrants: &[Entrant],
+        seed_start: (u64, u64),
+        seed_count: u64,
+    ) -> Result<Vec<GameResult>> {
+        ensure!(
+            entrants.len() == 4,
+            "exactly 4 entrants are required, got {}",
+            entrants.len(),
+        );
+        for (i, e) in entrants.iter().enumerate() {
+            ensure!(
+                entrants[..i].iter().all(|other| other.name != e.name),
+                "duplicate entrant name {}",
+                e.name,
+            );
+        }
+        let perms = self.seats.permutations();
+
+        log::info!(
+            "seed: [{}, {}) w/ {}, start {} groups, {} hanchans",
+            seed_start.0,
+            seed_start.0 + seed_count,
+            seed_start.1,
+            seed_count,
+            seed_count * perms.len() as u64,
+        );
+
+        let mut seeds = vec![];
+        let mut player_ids = [(); 4].map(|_| vec![]);
+        let mut indexes = vec![];
+        for seed in seed_start.0..seed_start.0 + seed_count {
+            for perm in &perms {
+                seeds.push((seed, seed_start.1));
+                indexes.push([0, 1, 2, 3].map(|seat| {
+                    let ids = &mut player_ids[perm[seat]];
+                    ids.push(seat as u8);
+                    Index {
+                        agent_idx: perm[seat],
+                        player_id_idx: ids.len() - 1,
+                    }
+                }));
+            }
+        }
+
+        let mut agents = entrants
+            .iter()
+            .zip(&player_ids)
+            .map(|(e, ids)| (e.new_agent)(ids))
+            .collect::<Result<Vec<_>>>()?;
+        let batch_game = BatchGame {
+            agent_names: entrants.iter().map(|e| e.name.clone()).collect(),
+            ..BatchGame::tenhou_hanchan(self.disable_progress_bar)
+        };
+        batch_game.run(&mut agents, &indexes, &seeds)
+    }
+
+    /// Summarizes `results` of `run` for each of `entrants`, in the same
+    /// order.
+    #[must_use]
+    pub fn standings(&self, entrants: &[Entrant], results: &[GameResult]) -> Vec<LineupStanding> {
+        let by_name = standings_by_name(results);
+        entrants
+            .iter()
+            .map(|e| {
+                by_name
+                    .iter()
+                    .find(|s| s.name == e.name)
+                    .cloned()
+                    .unwrap_or_else(|| LineupStanding {
+                        name: e.name.clone(),
+                        ..Default::default()
+                    })
+            })
+            .collect()
+    }
+}
+
+/// Summarizes `results` by the names of the seats, which tell the model of
+/// each seat when the games mix several of them, regardless of the driver.
+/// The standings are in the order of first appearance.
+#[must_use]
+pub fn standings_by_name(results: &[GameResult]) -> Vec<LineupStanding> {
+    let mut standings: Vec<LineupStanding> = vec![];
+    let mut score_sums: Vec<i64> = vec![];
+
+    for result in results {
+        let rankings = result.rankings();
+        for (seat, name) in result.names.iter().enumerate() {
+            let idx = match standings.iter().position(|s| s.name == *name) {
+                Some(idx) => idx,
+                None => {
+                    standings.push(LineupStanding {
+                        name: name.clone(),
+                        ..Default::default()
+                    });
+                    score_sums.push(0);
+                    standings.len() - 1
+                }
+            };
+            let s = &mut standings[idx];
+            s.games += 1;
+            s.rank_counts[rankings.rank_by_player[seat] as usize] += 1;
+            score_sums[idx] += result.scores[seat] as i64;
+        }
+    }
+
+    for (s, score_sum) in standings.iter_mut().zip(score_sums) {
+        let rank_sum: u32 = s.rank_counts.iter().zip(1..).map(|(n, r)| n * r).sum();
+        s.avg_rank = rank_sum as f64 / s.games as f64;
+        s.avg_score = score_sum as f64 / s.games as f64;
+    }
+    standings
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::agent::{RuleBased, Tsumogiri};
+
+    #[test]
+    fn permutations() {
+        for seats in [SeatSchedule::Rotations, SeatSchedule::Permutations] {
+            let perms = seats.permutations();
+            // Every entrant takes every seat equally often.
+            for entrant in 0..4 {
+                for seat in 0..4 {
+                    let n = perms.iter().filter(|p| p[seat] == entrant).count();
+                    assert_eq!(n * 4, perms.len());
+                }
+            }
+        }
+        let mut perms = SeatSchedule::Permutations.permutations();
+        perms.sort_unstable();
+        perms.dedup();
+        assert_eq!(perms.len(), 24);
+    }
+
+    #[test]
+    fn four_entrants() {
+        let rule_based = |name: &str| Entrant {
+            name: name.to_owned(),
+            new_agent: Box::new(|ids: &[u8]| Ok(Box::new(RuleBased::new_batched(ids)?) as _)),
+        };
+        let tsumogiri = |name: &str| Entrant {
+            name: name.to_owned(),
+            new_agent: Box::new(|ids: &[u8]| Ok(Box::new(Tsumogiri::new_batched(ids)?) as _)),
+        };
+        let entrants = vec![
+            tsumogiri("tsumogiri_a"),
+            rule_based("rule_based_a"),
+            tsumogiri("tsumogiri_b"),
+            rule_based("rule_based_b"),
+        ];
+
+        let lineup = Lineup {
+            seats: SeatSchedule::Rotations,
+            disable_progress_bar: true,
+        };
+        let results = lineup.run(&entrants, (1009, 0), 2).unwrap();
+        assert_eq!(results.len(), 8);
+        // The seats are named after the entrants, rotated.
+        assert_eq!(
+            results[1].names,
+            ["rule_based_a", "tsumogiri_b", "rule_based_b", "tsumogiri_a"],
+        );
+        assert_eq!(results[0].seed, results[3].seed);
+        assert_ne!(results[3].seed, results[4].seed);
+
+        let standings = lineup.standings(&entrants, &results);
+        assert!(standings.iter().all(|s| s.games == 8));
+        assert!(standings
+            .iter()
+            .all(|s| s.rank_counts.iter().sum::<u32>() == 8));
+        let avg_rank_sum: f64 = standings.iter().map(|s| s.avg_rank).sum();
+        assert!((avg_rank_sum - 10.).abs() < 1e-9);
+        assert!(standings[1].avg_score > standings[0].avg_score);
+        assert!(standings[3].avg_score > standings[2].avg_score);
+
+        assert!(lineup.run(&entrants[..3], (1009, 0), 1).is_err());
+        let same_names = vec![
+            tsumogiri("a"),
+            rule_based("b"),
+            tsumogiri("a"),
+            rule_based("c"),
+        ];
+        assert!(lineup.run(&same_names, (1009, 0), 1).is_err());
+    }
+}
@@ -1,21 +1,49 @@
 mod board;
+mod duplicate;
 mod game;
+mod game_state;
+mod lineup;
 mod one_vs_three;
 mod result;
+mod rollout;
+mod sprt;
+mod tournament;
 mod two_vs_two;
+mod wall;
 
 pub use board::Board;
+pub use game_state::GameState;
 pub use result::{GameResult, KyokuEndState};
+pub use rollout::{Policy, Rollout};
 
+#[cfg(feature = "python")]
 use crate::py_helper::add_submodule;
+#[cfg(feature = "python")]
+use duplicate::Duplicate;
+#[cfg(feature = "python")]
+use lineup::Lineup;
+#[cfg(feature = "python")]
 use one_vs_three::OneVsThree;
+#[cfg(feature = "python")]
+use sprt::Sprt;
+#[cfg(feature = "python")]
+use tournament::Tournament;
+#[cfg(feature = "python")]
 use two_vs_two::TwoVsTwo;
 
+#[cfg(feature = "python")]
 use pyo3::prelude::*;
 
+#[cfg(feature = "python")]
 pub(crate) fn register_module(py: Python<'_>, prefix: &str, super_mod: &PyModule) -> PyResult<()> {
     let m = PyModule::new(py, "arena")?;
+    m.add_class::<Duplicate>()?;
+    m.add_class::<GameState>()?;
+    m.add_class::<Lineup>()?;
     m.add_class::<OneVsThree>()?;
+    m.add_class::<Rollout>()?;
+    m.add_class::<Sprt>()?;
+    m.add_class::<Tournament>()?;
     m.add_class::<TwoVsTwo>()?;
     add_submodule(py, prefix, super_mod, m)
 }
@@ -1,17 +1,22 @@
 use super::game::{BatchGame, Index};
 use super::result::GameResult;
-use crate::agent::{AkochanAgent, BatchAgent, MortalBatchAgent};
-use std::fs::{self, File};
-use std::io::prelude::*;
-use std::iter;
+use super::wall::load_walls;
+use crate::agent::BatchAgent;
+#[cfg(feature = "python")]
+use crate::agent::{AkochanAgent, MortalBatchAgent, RuleBased, Tsumogiri};
+use crate::dataset::{Gameplay, GameplayLoader};
+use crate::log_io;
+use crate::par::*;
 use std::path::PathBuf;
+use std::{fs, iter};
 
-use anyhow::Result;
-use flate2::read::GzEncoder;
-use flate2::Compression;
-use indicatif::{ParallelProgressIterator, ProgressBar, ProgressStyle};
+#[cfg(feature = "python")]
+use anyhow::bail;
+use anyhow::{ensure, Result};
+use indicatif::{ProgressBar, ProgressStyle};
+#[cfg(feature = "python")]
 use pyo3::prelude::*;
-use rayon::prelude::*;
+use riichi_macros::{pyclass, pymethods};
 
 #[pyclass]
 #[pyo3(text_signature = "(
@@ -37,6 +42,7 @@ impl OneVsThree {
     }
 
     /// Returns the rankings of the challenger.
+    #[cfg(feature = "python")]
     #[pyo3(text_signature = "($self, challenger, champion, seed_start, seed_count)")]
     pub fn py_vs_py(
         &self,
@@ -66,7 +72,44 @@ impl OneVsThree {
         })
     }
 
+    /// Same as `py_vs_py`, but also returns the `Gameplay` of the challenger
+    /// of each game, encoded by `loader` straight from the games in memory
+    /// instead of from dumped logs, see `challenger_gameplays`.
+    ///
+    /// The rewards of each kyoku are in `Gameplay.kyoku_rewards` if `loader`
+    /// has a `reward_table`, and the final scores and rankings of the game are
+    /// in `Gameplay.grp`.
+    #[cfg(feature = "python")]
+    #[pyo3(text_signature = "($self, challenger, champion, seed_start, seed_count, loader)")]
+    pub fn py_vs_py_gameplay(
+        &self,
+        challenger: PyObject,
+        champion: PyObject,
+        seed_start: (u64, u64),
+        seed_count: u64,
+        loader: GameplayLoader,
+        py: Python<'_>,
+    ) -> Result<([i32; 4], Vec<Gameplay>)> {
+        py.allow_threads(move || {
+            let results = self.run_batch(
+                |player_ids| MortalBatchAgent::new(challenger, player_ids),
+                |player_ids| MortalBatchAgent::new(champion, player_ids),
+                seed_start,
+                seed_count,
+            )?;
+
+            let mut rankings = [0; 4];
+            for (i, result) in results.iter().enumerate() {
+                let rank = result.rankings().rank_by_player[i % 4];
+                rankings[rank as usize] += 1;
+            }
+            let gameplays = Self::challenger_gameplays(&results, &loader)?;
+            Ok((rankings, gameplays))
+        })
+    }
+
     /// Returns the rankings of the challenger (akochan in this case).
+    #[cfg(feature = "python")]
     #[pyo3(text_signature = "($self, engine, seed_start, seed_count)")]
     pub fn ako_vs_py(
         &self,
@@ -93,6 +136,7 @@ impl OneVsThree {
     }
 
     /// Returns the rankings of the challenger (python agent in this case).
+    #[cfg(feature = "python")]
     #[pyo3(text_signature = "($self, engine, seed_start, seed_count)")]
     pub fn py_vs_ako(
         &self,
@@ -117,6 +161,72 @@ impl OneVsThree {
             Ok(rankings)
         })
     }
+
+    /// Returns the rankings of the challenger (python agent in this case)
+    /// against a built-in baseline, which is either `"rule_based"` or
+    /// `"tsumogiri"`.
+    #[cfg(feature = "python")]
+    #[pyo3(text_signature = "($self, engine, baseline, seed_start, seed_count)")]
+    pub fn py_vs_baseline(
+        &self,
+        engine: PyObject,
+        baseline: &str,
+        seed_start: (u64, u64),
+        seed_count: u64,
+        py: Python<'_>,
+    ) -> Result<[i32; 4]> {
+        py.allow_threads(move || {
+            let new_challenger = |player_ids: &[u8]| MortalBatchAgent::new(engine, player_ids);
+            let results = match baseline {
+                "rule_based" => self.run_batch(
+                    new_challenger,
+                    RuleBased::new_batched,
+                    seed_start,
+                    seed_count,
+                )?,
+                "tsumogiri" => self.run_batch(
+                    new_challenger,
+                    Tsumogiri::new_batched,
+                    seed_start,
+                    seed_count,
+                )?,
+                _ => bail!("unknown baseline {baseline}"),
+            };
+
+            let mut rankings = [0; 4];
+            for (i, result) in results.iter().enumerate() {
+                let rank = result.rankings().rank_by_player[i % 4];
+                rankings[rank as usize] += 1;
+            }
+            Ok(rankings)
+        })
+    }
+
+    /// Replays the game of `split` (0-3, the seat of the challenger) with the
+    /// walls dumped in `log_dir`, e.g. to debug an anomalous game. Returns
+    /// the final scores.
+    #[cfg(feature = "python")]
+    #[pyo3(text_signature = "($self, challenger, champion, seed, split, walls_file)")]
+    pub fn py_vs_py_replay(
+        &self,
+        challenger: PyObject,
+        champion: PyObject,
+        seed: (u64, u64),
+        split: usize,
+        walls_file: &str,
+        py: Python<'_>,
+    ) -> Result<[i32; 4]> {
+        py.allow_threads(move || {
+            let result = self.replay_one(
+                |player_ids| MortalBatchAgent::new(challenger, player_ids),
+                |player_ids| MortalBatchAgent::new(champion, player_ids),
+                seed,
+                split,
+                walls_file,
+            )?;
+            Ok(result.scores)
+        })
+    }
 }
 
 impl OneVsThree {
@@ -235,14 +345,14 @@ impl OneVsThree {
                     .iter()
                     .collect();
 
-                    let log = game_result.dump_json_log()?;
-                    let mut comp = GzEncoder::new(log.as_bytes(), Compression::best());
-                    let mut data = vec![];
-                    comp.read_to_end(&mut data)?;
+                    game_result
+                        .dump_walls(filename.with_extension("").with_extension("walls.jsonl"))?;
+                    fs::write(
+                        filename.with_extension("").with_extension("kyokus.jsonl"),
+                        game_result.dump_kyoku_records()?,
+                    )?;
 
-                    let mut f = File::create(filename)?;
-                    f.write_all(&data)?;
-                    f.sync_all()?;
+                    log_io::write_log(filename, &game_result.dump_json_log()?)?;
 
                     anyhow::Ok(())
                 })?;
@@ -250,4 +360,106 @@ impl OneVsThree {
 
         Ok(results)
     }
+
+    /// Encodes the seat of the challenger in each of `results` from
+    /// `run_batch` with `loader`, skipping the mjai log round trip. The seat
+    /// filters of `loader`, including `player_name`, are overridden.
+    pub fn challenger_gameplays(
+        results: &[GameResult],
+        loader: &GameplayLoader,
+    ) -> Result<Vec<Gameplay>> {
+        let gameplays = results
+            .par_iter()
+            .enumerate()
+            .map(|(i, result)| {
+                let loader = GameplayLoader {
+                    player_name: None,
+                    excludes: vec![],
+                    seats: Some(vec![(i % 4) as u8]),
+                    ..loader.clone()
+                };
+                loader.load_events(&result.events())
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(gameplays.into_iter().flatten().collect())
+    }
+
+    pub fn replay_one<C, M, CA, MA>(
+        &self,
+        new_challenger_agent: C,
+        new_champion_agent: M,
+        seed: (u64, u64),
+        split: usize,
+        walls_file: &str,
+    ) -> Result<GameResult>
+    where
+        C: FnOnce(&[u8]) -> Result<CA>,
+        M: FnOnce(&[u8]) -> Result<MA>,
+        CA: BatchAgent + 'static,
+        MA: BatchAgent + 'static,
+    {
+        ensure!(split < 4, "split must be within [0, 3], got {split}");
+        let walls = load_walls(walls_file)?;
+
+        log::info!(
+            "seed: {} w/ {}, split: {split}, replay 1 hanchan from {walls_file}",
+            seed.0,
+            seed.1,
+        );
+
+        let champion_player_ids: Vec<_> = (0..4).filter(|&i| i != split as u8).collect();
+        let mut agents: [Box<dyn BatchAgent>; 2] = [
+            Box::new(new_challenger_agent(&[split as u8])?),
+            Box::new(new_champion_agent(&champion_player_ids)?),
+        ];
+        let batch_game = BatchGame::tenhou_hanchan(self.disable_progress_bar);
+
+        let mut champion_idx = 0;
+        let indexes = [[0, 1, 2, 3].map(|seat| {
+            if seat == split {
+                Index {
+                    agent_idx: 0,
+                    player_id_idx: 0,
+                }
+            } else {
+                champion_idx += 1;
+                Index {
+                    agent_idx: 1,
+                    player_id_idx: champion_idx - 1,
+                }
+            }
+        })];
+
+        let mut results = batch_game.replay(&mut agents, &indexes, &[seed], &[walls])?;
+        Ok(results.remove(0))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::agent::Tsumogiri;
+
+    #[test]
+    fn challenger_gameplays() {
+        let one_vs_three = OneVsThree::new(true, None);
+        let results = one_vs_three
+            .run_batch(Tsumogiri::new_batched, Tsumogiri::new_batched, (1009, 0), 1)
+            .unwrap();
+        assert_eq!(results.len(), 4);
+
+        let loader = GameplayLoader {
+            player_name: Some("nobody".to_owned()),
+            ..Default::default()
+        };
+        let gameplays = OneVsThree::challenger_gameplays(&results, &loader).unwrap();
+        assert_eq!(gameplays.len(), 4);
+        for (i, (gameplay, result)) in gameplays.iter().zip(&results).enumerate() {
+            assert_eq!(gameplay.player_id, i as u8);
+            assert!(!gameplay.actions.is_empty());
+            assert_eq!(gameplay.actions.len(), gameplay.masks.len());
+            assert_eq!(gameplay.grp.len(), result.game_log.len());
+            assert_eq!(gameplay.grp.final_scores, result.scores);
+        }
+    }
 }
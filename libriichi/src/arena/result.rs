@@ -1,6 +1,12 @@
-use crate::mjai::{Event, EventExt};
+use super::wall::{self, Wall};
+use crate::consts::TILES_LEFT_AT_START;
+use crate::mjai::{Event, EventExt, RyukyokuReason};
+use crate::tile::Tile;
+use std::iter;
+use std::path::Path;
 
-use anyhow::Result;
+use anyhow::{Context, Result};
+use serde::Serialize;
 use serde_json as json;
 
 #[derive(Debug, Clone)]
@@ -12,6 +18,10 @@ pub struct KyokuResult {
     pub has_abortive_ryukyoku: bool,
     pub kyotaku_left: u8,
     pub scores: [i32; 4],
+    /// The seat that paid under pao for each winner, if any.
+    pub paos: [Option<u8>; 4],
+    /// Who was tenpai, if it ended in an exhaustive ryukyoku.
+    pub tenpai: Option<[bool; 4]>,
 }
 
 #[derive(Debug, Clone, Default)]
@@ -20,6 +30,12 @@ pub struct GameResult {
     pub scores: [i32; 4],
     pub seed: (u64, u64),
     pub game_log: Vec<Vec<EventExt>>,
+    /// The wall of each kyoku in `game_log`.
+    pub walls: Vec<Wall>,
+    /// `KyokuResult::paos` of each kyoku in `game_log`.
+    pub paos: Vec<[Option<u8>; 4]>,
+    /// `KyokuResult::tenpai` of each kyoku in `game_log`.
+    pub tenpai: Vec<Option<[bool; 4]>>,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -28,7 +44,8 @@ pub struct Rankings {
     pub rank_by_player: [u8; 4],
 }
 
-#[derive(Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
 pub enum KyokuEndState {
     Passive = 0,
     Draw = 1,
@@ -36,6 +53,33 @@ pub enum KyokuEndState {
     DealIn = 3,
 }
 
+/// What happened to each seat in one kyoku, for analyses finer than the final
+/// placements without going through the full log.
+#[derive(Debug, Clone, Serialize)]
+pub struct KyokuRecord {
+    pub bakaze: Tile,
+    /// Counts from 1.
+    pub kyoku: u8,
+    pub honba: u8,
+    pub kyotaku: u8,
+    /// Scores at the start of the kyoku.
+    pub scores: [i32; 4],
+    /// Score changes over the kyoku, including riichi deposits.
+    pub deltas: [i32; 4],
+    pub end_states: [KyokuEndState; 4],
+    /// Who was tenpai, if it ended in an exhaustive ryukyoku.
+    pub tenpai: Option<[bool; 4]>,
+    pub ryukyoku_reason: Option<RyukyokuReason>,
+    /// Whose riichi was accepted.
+    pub riichi: [bool; 4],
+    /// Who made any chi, pon or daiminkan.
+    pub called: [bool; 4],
+    /// The seat that paid under pao for each winner, if any.
+    pub paos: [Option<u8>; 4],
+    /// Tiles left in the wall at the end.
+    pub tiles_left: u8,
+}
+
 impl GameResult {
     pub fn rankings(&self) -> Rankings {
         let mut v: Vec<_> = self.scores.iter().copied().enumerate().collect();
@@ -58,10 +102,30 @@ impl GameResult {
         }
     }
 
+    /// Dumps `walls` to `path`, see `dump_walls`.
+    pub fn dump_walls(&self, path: impl AsRef<Path>) -> Result<()> {
+        wall::dump_walls(path, &self.walls)
+    }
+
+    /// Returns the whole game as mjai events, from `StartGame` to `EndGame`,
+    /// the same as `dump_json_log` without metadata.
+    #[must_use]
+    pub fn events(&self) -> Vec<Event> {
+        iter::once(Event::StartGame {
+            names: self.names.clone(),
+            seed: Some(self.seed),
+            meta: None,
+        })
+        .chain(self.game_log.iter().flatten().map(|ev| ev.event.clone()))
+        .chain(iter::once(Event::EndGame))
+        .collect()
+    }
+
     pub fn dump_json_log(&self) -> Result<String> {
         let mut ret = json::to_string(&Event::StartGame {
             names: self.names.clone(),
             seed: Some(self.seed),
+            meta: None,
         })? + "\n";
 
         for kyoku in &self.game_log {
@@ -74,6 +138,86 @@ impl GameResult {
         Ok(ret)
     }
 
+    /// Returns the record of each kyoku in `game_log`.
+    pub fn kyoku_records(&self) -> Result<Vec<KyokuRecord>> {
+        self.game_log
+            .iter()
+            .enumerate()
+            .map(|(i, log)| {
+                let (bakaze, kyoku, honba, kyotaku, scores) = log
+                    .iter()
+                    .find_map(|ev| match ev.event {
+                        Event::StartKyoku {
+                            bakaze,
+                            kyoku,
+                            honba,
+                            kyotaku,
+                            scores,
+                            ..
+                        } => Some((bakaze, kyoku, honba, kyotaku, scores)),
+                        _ => None,
+                    })
+                    .context("kyoku without start_kyoku")?;
+
+                let mut record = KyokuRecord {
+                    bakaze,
+                    kyoku,
+                    honba,
+                    kyotaku,
+                    scores,
+                    deltas: [0; 4],
+                    end_states: [KyokuEndState::Passive; 4],
+                    tenpai: self.tenpai.get(i).copied().flatten(),
+                    ryukyoku_reason: None,
+                    riichi: [false; 4],
+                    called: [false; 4],
+                    paos: self.paos.get(i).copied().unwrap_or_default(),
+                    tiles_left: TILES_LEFT_AT_START,
+                };
+                for ev in log {
+                    match ev.event {
+                        Event::Tsumo { .. } => record.tiles_left -= 1,
+                        Event::ReachAccepted { actor } => {
+                            record.riichi[actor as usize] = true;
+                            record.deltas[actor as usize] -= 1000;
+                        }
+                        Event::Chi { actor, .. }
+                        | Event::Pon { actor, .. }
+                        | Event::Daiminkan { actor, .. } => record.called[actor as usize] = true,
+                        Event::Hora {
+                            actor,
+                            target,
+                            deltas,
+                            ..
+                        } => {
+                            record.end_states[actor as usize] = KyokuEndState::Win;
+                            if target != actor {
+                                record.end_states[target as usize] = KyokuEndState::DealIn;
+                            }
+                            add_deltas(&mut record.deltas, deltas);
+                        }
+                        Event::Ryukyoku { deltas, reason } => {
+                            record.end_states = [KyokuEndState::Draw; 4];
+                            record.ryukyoku_reason = reason;
+                            add_deltas(&mut record.deltas, deltas);
+                        }
+                        _ => (),
+                    }
+                }
+                Ok(record)
+            })
+            .collect()
+    }
+
+    /// Dumps `kyoku_records` as JSONL.
+    pub fn dump_kyoku_records(&self) -> Result<String> {
+        let mut ret = String::new();
+        for record in self.kyoku_records()? {
+            ret += &(json::to_string(&record)? + "\n");
+        }
+        Ok(ret)
+    }
+
     pub fn kyoku_end_states(&self, perspective: u8) -> Vec<KyokuEndState> {
         self.game_log
             .iter()
@@ -104,6 +248,12 @@ impl GameResult {
     }
 }
 
+fn add_deltas(sum: &mut [i32; 4], deltas: Option<[i32; 4]>) {
+    if let Some(deltas) = deltas {
+        sum.iter_mut().zip(deltas).for_each(|(s, d)| *s += d);
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
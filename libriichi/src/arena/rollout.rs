@@ -0,0 +1,738 @@
+use super::board::{unshuffled, Board, BoardState, Poll};
+use crate::agent::{Agent, RuleBased, Tsumogiri};
+use crate::consts::TILES_LEFT_AT_START;
+use crate::mjai::{Event, EventExt};
+use crate::par::*;
+use crate::rule::RuleSet;
+use crate::state::PlayerState;
+use crate::tile::Tile;
+use crate::tu8;
+use std::str::FromStr;
+
+use anyhow::{bail, ensure, Context, Result};
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+use rand::prelude::*;
+use rand_chacha::ChaCha12Rng;
+use riichi_macros::{pyclass, pymethods};
+use serde::Serialize;
+#[cfg(feature = "python")]
+use serde_json as json;
+
+/// Maximum number of worlds sampled for one rollout before giving up, as a
+/// sampled world may fail to reproduce the log, for example when it cannot
+/// make a riichi valid.
+const MAX_ATTEMPTS: usize = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Policy {
+    RuleBased,
+    Tsumogiri,
+}
+
+/// Monte Carlo rollouts of the rest of a kyoku from a decision point of
+/// `player_id`, for what-if analysis.
+///
+/// Each rollout samples the hidden hands and the wall from the tiles unseen
+/// by the player, such that the sampled world reproduces the log so far, and
+/// then plays every candidate action in the same world, with all the
+/// following decisions made by `policy`.
+#[pyclass]
+#[pyo3(text_signature = "(player_id, *, rollouts=64, seed=0, policy='rule_based', rule=None)")]
+#[derive(Debug, Clone)]
+pub struct Rollout {
+    pub player_id: u8,
+    pub rollouts: usize,
+    pub seed: u64,
+    pub policy: Policy,
+    pub rule: RuleSet,
+}
+
+/// The outcomes of one candidate action over all the rollouts.
+#[derive(Debug, Clone, Serialize)]
+pub struct Outcome {
+    pub action: Event,
+    /// Score deltas of the player from the decision point to the end of the
+    /// kyoku, one for each rollout.
+    pub deltas: Vec<i32>,
+    pub avg_delta: f64,
+    pub win_rate: f64,
+    pub deal_in_rate: f64,
+    /// Including abortive draws.
+    pub ryukyoku_rate: f64,
+}
+
+/// The end of one rollout, from the view of the player.
+#[derive(Debug, Clone, Copy)]
+pub struct RolloutEnd {
+    /// Score delta of the player from the decision point.
+    pub delta: i32,
+    pub win: bool,
+    pub deal_in: bool,
+    pub ryukyoku: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Slot {
+    /// Seat and index
+    Haipai(usize, usize),
+    /// Index in `World::draws`
+    Draw(usize),
+}
+
+/// The tiles of the kyoku so far, where the ones unknown to the player are
+/// `None`.
+#[derive(Debug, Default)]
+struct World {
+    haipai: [[Option<Tile>; 13]; 4],
+    /// All the draws in order, along with whether it is from rinshan.
+    draws: Vec<(Option<Tile>, bool)>,
+    dora_markers: Vec<Tile>,
+    /// Slots of the unknown tiles currently in the hand of each opponent.
+    hands: [Vec<Slot>; 4],
+    riichi: [bool; 4],
+}
+
+impl FromStr for Policy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "rule_based" => Ok(Self::RuleBased),
+            "tsumogiri" => Ok(Self::Tsumogiri),
+            _ => bail!("unknown policy {s}"),
+        }
+    }
+}
+
+impl Policy {
+    fn new_agent(self, seat: u8) -> Box<dyn Agent> {
+        match self {
+            Self::RuleBased => Box::new(RuleBased(seat)),
+            Self::Tsumogiri => Box::new(Tsumogiri(seat)),
+        }
+    }
+}
+
+#[pymethods]
+impl Rollout {
+    #[new]
+    #[args(
+        "*",
+        rollouts = "64",
+        seed = "0",
+        policy = "\"rule_based\"",
+        rule = "None"
+    )]
+    fn py_new(
+        player_id: u8,
+        rollouts: usize,
+        seed: u64,
+        policy: &str,
+        rule: Option<RuleSet>,
+    ) -> Result<Self> {
+        ensure!(player_id < 4, "{player_id} is not a valid player ID");
+        ensure!(rollouts > 0, "rollouts must be positive");
+        Ok(Self {
+            player_id,
+            rollouts,
+            seed,
+            policy: policy.parse()?,
+            rule: rule.unwrap_or_default(),
+        })
+    }
+
+    /// `log` is the mjai log in JSON lines as seen by the player, which must
+    /// end at a decision point of the player. `actions` is a list of the
+    /// candidate reactions in mjai JSON.
+    ///
+    /// Returns the outcomes of each action as a JSON string.
+    #[cfg(feature = "python")]
+    #[pyo3(name = "run")]
+    #[pyo3(text_signature = "($self, log, actions, /)")]
+    fn run_py(&self, log: &str, actions: Vec<String>, py: Python<'_>) -> Result<String> {
+        py.allow_threads(move || {
+            let events = log
+                .lines()
+                .filter(|l| !l.trim().is_empty())
+                .map(json::from_str)
+                .collect::<Result<Vec<Event>, _>>()
+                .context("failed to parse log")?;
+            let actions = actions
+                .iter()
+                .map(|a| json::from_str(a))
+                .collect::<Result<Vec<Event>, _>>()
+                .context("failed to parse actions")?;
+            let outcomes = self.run(&events, &actions)?;
+            Ok(json::to_string(&outcomes)?)
+        })
+    }
+}
+
+impl Rollout {
+    /// Only the events since the last `start_kyoku` in `events` are used.
+    pub fn run(&self, events: &[Event], actions: &[Event]) -> Result<Vec<Outcome>> {
+        ensure!(!actions.is_empty(), "no candidate action");
+        let ends = (0..self.rollouts as u64)
+            .into_par_iter()
+            .map(|i| {
+                let mut rng = ChaCha12Rng::seed_from_u64(self.seed.wrapping_add(i));
+                let state = self.sample_state(events, &mut rng)?;
+                actions
+                    .iter()
+                    .map(|action| self.play_out(state.clone(), action, |_| Ok(None)))
+                    .collect::<Result<Vec<_>>>()
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let n = self.rollouts as f64;
+        let outcomes = actions
+            .iter()
+            .enumerate()
+            .map(|(i, action)| {
+                let ends: Vec<_> = ends.iter().map(|e| e[i]).collect();
+                let rate =
+                    |f: fn(&RolloutEnd) -> bool| ends.iter().filter(|e| f(e)).count() as f64 / n;
+                let deltas: Vec<_> = ends.iter().map(|e| e.delta).collect();
+                Outcome {
+                    action: action.clone(),
+                    avg_delta: deltas.iter().map(|&d| d as f64).sum::<f64>() / n,
+                    deltas,
+                    win_rate: rate(|e| e.win),
+                    deal_in_rate: rate(|e| e.deal_in),
+                    ryukyoku_rate: rate(|e| e.ryukyoku),
+                }
+            })
+            .collect();
+        Ok(outcomes)
+    }
+
+    /// Samples a world consistent with `events`, where only the events since
+    /// the last `start_kyoku` are used, and replays it up to the decision
+    /// point at the end of `events`.
+    pub(crate) fn sample_state(
+        &self,
+        events: &[Event],
+        rng: &mut ChaCha12Rng,
+    ) -> Result<BoardState> {
+        let start_idx = events
+            .iter()
+            .rposition(|ev| matches!(ev, Event::StartKyoku { .. }))
+            .context("no start_kyoku in log")?;
+        let (start, events) = (&events[start_idx], &events[start_idx + 1..]);
+
+        let mut last_err = None;
+        for _ in 0..MAX_ATTEMPTS {
+            let board = self.sample(start, events, rng)?;
+            match self.replay(board, events) {
+                Ok(state) => return Ok(state),
+                Err(err) => last_err = Some(err),
+            }
+        }
+        Err(last_err
+            .unwrap()
+            .context(format!("no consistent world in {MAX_ATTEMPTS} attempts")))
+    }
+
+    /// Samples a full `Board` that is consistent with what the player has
+    /// seen.
+    fn sample(&self, start: &Event, events: &[Event], rng: &mut ChaCha12Rng) -> Result<Board> {
+        let Event::StartKyoku {
+            bakaze,
+            kyoku,
+            honba,
+            kyotaku,
+            oya,
+            scores,
+            ..
+        } = *start
+        else {
+            unreachable!();
+        };
+        let kyoku = (bakaze.as_u8() - tu8!(E)) * 4 + kyoku - 1;
+        ensure!(kyoku % 4 == oya, "oya {oya} does not match the kyoku");
+
+        let mut world = self.observe(start, events, rng)?;
+
+        let mut pool = unshuffled(self.rule.aka_count).to_vec();
+        let known = world
+            .haipai
+            .iter()
+            .flatten()
+            .chain(world.draws.iter().map(|(t, _)| t))
+            .flatten()
+            .chain(&world.dora_markers);
+        for &tile in known {
+            let idx = pool
+                .iter()
+                .position(|&t| t == tile)
+                .with_context(|| format!("more {tile} in log than in the tile set"))?;
+            pool.swap_remove(idx);
+        }
+        pool.shuffle(rng);
+
+        for seat in 0..4 {
+            let slots = world.hands[seat].clone();
+            let tiles = if world.riichi[seat] {
+                draw_tenpai(&mut pool, slots.len(), rng)
+            } else {
+                None
+            };
+            let tiles = match tiles {
+                Some(tiles) => tiles,
+                None => pool.split_off(pool.len() - slots.len()),
+            };
+            for (slot, tile) in slots.into_iter().zip(tiles) {
+                world.assign(slot, tile);
+            }
+        }
+
+        let mut fill = |drawn: Vec<Tile>, len: usize| -> Result<Vec<Tile>> {
+            ensure!(drawn.len() <= len, "too many tiles drawn");
+            let mut tiles = drawn;
+            tiles.extend(pool.drain(..len - tiles.len()));
+            // They are popped from the back.
+            tiles.reverse();
+            Ok(tiles)
+        };
+        let drawn = |from_rinshan: bool| {
+            world
+                .draws
+                .iter()
+                .filter(|&&(_, r)| r == from_rinshan)
+                .map(|(t, _)| t.unwrap())
+                .collect::<Vec<_>>()
+        };
+        let yama = fill(drawn(false), TILES_LEFT_AT_START as usize)?;
+        let rinshan = fill(drawn(true), 4)?;
+        let dora_indicators = fill(world.dora_markers.clone(), 5)?;
+        let ura_indicators = fill(vec![], 5)?;
+
+        Ok(Board {
+            kyoku,
+            honba,
+            kyotaku,
+            scores,
+            haipai: world.haipai.map(|h| h.map(Option::unwrap)),
+            yama,
+            rinshan,
+            dora_indicators,
+            ura_indicators,
+            rule: self.rule,
+        })
+    }
+
+    /// Builds the `World` from the log, assigning each tile revealed by an
+    /// opponent to a random unknown slot in their hand.
+    fn observe(&self, start: &Event, events: &[Event], rng: &mut ChaCha12Rng) -> Result<World> {
+        let Event::StartKyoku {
+            dora_marker,
+            tehais,
+            ..
+        } = *start
+        else {
+            unreachable!();
+        };
+        let player_id = self.player_id as usize;
+
+        let mut world = World {
+            dora_markers: vec![dora_marker],
+            ..Default::default()
+        };
+        world.haipai[player_id] = tehais[player_id].map(Some);
+        for seat in (0..4).filter(|&s| s != player_id) {
+            world.hands[seat] = (0..13).map(|i| Slot::Haipai(seat, i)).collect();
+        }
+
+        let mut from_rinshan = false;
+        // The draw that has not been discarded yet of each seat.
+        let mut last_draws = [None; 4];
+        for ev in events {
+            let actor = ev.actor().map(|a| a as usize);
+            let is_opponent = actor.is_some_and(|a| a != player_id);
+            match *ev {
+                Event::Tsumo { actor, pai } => {
+                    let actor = actor as usize;
+                    let tile = (actor == player_id).then_some(pai);
+                    world.draws.push((tile, from_rinshan));
+                    from_rinshan = false;
+                    let idx = world.draws.len() - 1;
+                    if actor != player_id {
+                        world.hands[actor].push(Slot::Draw(idx));
+                    }
+                    last_draws[actor] = Some(idx);
+                }
+                Event::Dahai {
+                    actor,
+                    pai,
+                    tsumogiri,
+                } => {
+                    let actor = actor as usize;
+                    let last_draw = last_draws[actor].take();
+                    if actor == player_id {
+                        continue;
+                    }
+                    if tsumogiri {
+                        let idx = last_draw.context("tsumogiri without tsumo")?;
+                        world.reveal(actor, Slot::Draw(idx), pai)?;
+                    } else {
+                        let except = last_draw.map(Slot::Draw);
+                        world.reveal_any(actor, pai, except, rng)?;
+                    }
+                }
+                Event::Chi { consumed, .. } | Event::Pon { consumed, .. } if is_opponent => {
+                    for tile in consumed {
+                        world.reveal_any(actor.unwrap(), tile, None, rng)?;
+                    }
+                }
+                Event::Daiminkan { consumed, .. } => {
+                    if is_opponent {
+                        for tile in consumed {
+                            world.reveal_any(actor.unwrap(), tile, None, rng)?;
+                        }
+                    }
+                    from_rinshan = true;
+                }
+                Event::Ankan { consumed, .. } => {
+                    if is_opponent {
+                        for tile in consumed {
+                            world.reveal_any(actor.unwrap(), tile, None, rng)?;
+                        }
+                    }
+                    from_rinshan = true;
+                }
+                Event::Kakan { pai, .. } => {
+                    if is_opponent {
+                        world.reveal_any(actor.unwrap(), pai, None, rng)?;
+                    }
+                    from_rinshan = true;
+                }
+                Event::Dora { dora_marker } => world.dora_markers.push(dora_marker),
+                Event::Reach { actor, .. } => world.riichi[actor as usize] = true,
+                Event::Hora { .. }
+                | Event::Ryukyoku { .. }
+                | Event::EndKyoku
+                | Event::EndGame
+                | Event::StartKyoku { .. } => bail!("the kyoku has already ended"),
+                _ => (),
+            };
+        }
+
+        Ok(world)
+    }
+
+    /// Replays the log in the sampled `board`, up to the decision point.
+    fn replay(&self, board: Board, events: &[Event]) -> Result<BoardState> {
+        let mut state = board.into_state();
+        let mut reactions = <[EventExt; 4]>::default();
+        let mut queue = events
+            .iter()
+            .filter(|ev| {
+                matches!(
+                    ev,
+                    Event::Dahai { .. }
+                        | Event::Chi { .. }
+                        | Event::Pon { .. }
+                        | Event::Daiminkan { .. }
+                        | Event::Ankan { .. }
+                        | Event::Kakan { .. }
+                        | Event::Reach { .. }
+                )
+            })
+            .peekable();
+
+        loop {
+            if matches!(state.poll(reactions)?, Poll::End) {
+                bail!("the kyoku ended during replay");
+            }
+            reactions = Default::default();
+
+            let ctx = state.agent_context();
+            let Some(&ev) = queue.peek() else {
+                // The log of the board starts with `start_kyoku`.
+                ensure!(
+                    ctx.log.len() <= events.len() + 1,
+                    "replay went past the log"
+                );
+                if ctx.log.len() == events.len() + 1 {
+                    break;
+                }
+                continue;
+            };
+            let actor = ev.actor().context("reaction without actor")? as usize;
+            let player_state = &ctx.player_states[actor];
+            if player_state.validate_reaction(ev).is_ok() {
+                reactions[actor] = EventExt::no_meta(ev.clone());
+                queue.next();
+            } else if player_state.last_cans().can_discard {
+                bail!("the sampled world cannot reproduce {ev:?}");
+            }
+        }
+
+        ensure!(
+            state.agent_context().player_states[self.player_id as usize]
+                .last_cans()
+                .can_act(),
+            "the log does not end at a decision point of the player",
+        );
+        Ok(state)
+    }
+
+    /// Plays `action` and then the rest of the kyoku with `policy`, except
+    /// for the later decisions of the player for which `decide` returns a
+    /// reaction.
+    pub(crate) fn play_out<F>(
+        &self,
+        mut state: BoardState,
+        action: &Event,
+        mut decide: F,
+    ) -> Result<RolloutEnd>
+    where
+        F: FnMut(&PlayerState) -> Result<Option<Event>>,
+    {
+        let player_id = self.player_id as usize;
+        state.agent_context().player_states[player_id]
+            .validate_reaction(action)
+            .with_context(|| format!("invalid candidate action {action:?}"))?;
+        let score_before = state.end().scores[player_id];
+
+        let mut agents: Vec<_> = (0..4).map(|seat| self.policy.new_agent(seat)).collect();
+        let mut reactions = <[EventExt; 4]>::default();
+        reactions[player_id] = EventExt::no_meta(action.clone());
+        let mut first = true;
+        loop {
+            let ctx = state.agent_context();
+            for (seat, agent) in agents.iter_mut().enumerate() {
+                if first && seat == player_id {
+                    continue;
+                }
+                let player_state = &ctx.player_states[seat];
+                if !player_state.last_cans().can_act() {
+                    continue;
+                }
+                if seat == player_id {
+                    if let Some(ev) = decide(player_state)? {
+                        reactions[seat] = EventExt::no_meta(ev);
+                        continue;
+                    }
+                }
+                reactions[seat] = agent.react(ctx.log, player_state, None)?;
+            }
+            first = false;
+
+            if matches!(state.poll(reactions)?, Poll::End) {
+                break;
+            }
+            reactions = Default::default();
+        }
+
+        let delta = state.end().scores[player_id] - score_before;
+        let log = state.take_log();
+        let mut end = RolloutEnd {
+            delta,
+            win: false,
+            deal_in: false,
+            ryukyoku: false,
+        };
+        for ev in &log {
+            match ev.event {
+                Event::Hora { actor, target, .. } => {
+                    end.win |= actor == self.player_id;
+                    end.deal_in |= target == self.player_id && actor != self.player_id;
+                }
+                Event::Ryukyoku { .. } => end.ryukyoku = true,
+                _ => (),
+            }
+        }
+        Ok(end)
+    }
+}
+
+impl World {
+    fn assign(&mut self, slot: Slot, tile: Tile) {
+        match slot {
+            Slot::Haipai(seat, idx) => self.haipai[seat][idx] = Some(tile),
+            Slot::Draw(idx) => self.draws[idx].0 = Some(tile),
+        }
+    }
+
+    fn reveal(&mut self, seat: usize, slot: Slot, tile: Tile) -> Result<()> {
+        let idx = self.hands[seat]
+            .iter()
+            .position(|&s| s == slot)
+            .with_context(|| format!("{slot:?} is not in the hand of {seat}"))?;
+        self.hands[seat].swap_remove(idx);
+        self.assign(slot, tile);
+        Ok(())
+    }
+
+    /// Reveals `tile` in a random slot of the hand of `seat`, other than
+    /// `except`.
+    fn reveal_any(
+        &mut self,
+        seat: usize,
+        tile: Tile,
+        except: Option<Slot>,
+        rng: &mut ChaCha12Rng,
+    ) -> Result<()> {
+        let slot = self.hands[seat]
+            .iter()
+            .copied()
+            .filter(|&s| Some(s) != except)
+            .choose(rng)
+            .with_context(|| format!("no tile left in the hand of {seat} to reveal {tile}"))?;
+        self.reveal(seat, slot, tile)
+    }
+}
+
+/// Takes `len` tiles forming a tenpai hand out of `pool`, by building a
+/// complete hand at random and then removing one tile from it. Returns
+/// `None` if it fails.
+fn draw_tenpai(pool: &mut Vec<Tile>, len: usize, rng: &mut ChaCha12Rng) -> Option<Vec<Tile>> {
+    if len % 3 != 1 {
+        return None;
+    }
+    let mut avail = [0_u8; 34];
+    for t in pool.iter() {
+        avail[t.deaka().as_usize()] += 1;
+    }
+
+    'attempt: for _ in 0..MAX_ATTEMPTS {
+        let mut left = avail;
+        let mut hand = vec![];
+        let pair = rng.gen_range(0..34);
+        if left[pair] < 2 {
+            continue;
+        }
+        left[pair] -= 2;
+        hand.extend([pair; 2]);
+
+        for _ in 0..len / 3 {
+            let mut found = false;
+            for _ in 0..MAX_ATTEMPTS {
+                let t = rng.gen_range(0..34);
+                let is_shuntsu = rng.gen_bool(0.7) && t < 27 && t % 9 < 7;
+                let set = if is_shuntsu {
+                    [t, t + 1, t + 2]
+                } else {
+                    [t; 3]
+                };
+                let mut next = left;
+                if set.iter().all(|&s| {
+                    let ok = next[s] > 0;
+                    next[s] = next[s].saturating_sub(1);
+                    ok
+                }) {
+                    left = next;
+                    hand.extend(set);
+                    found = true;
+                    break;
+                }
+            }
+            if !found {
+                continue 'attempt;
+            }
+        }
+
+        hand.swap_remove(rng.gen_range(0..hand.len()));
+        let tiles = hand
+            .into_iter()
+            .map(|tid| {
+                let idx = pool
+                    .iter()
+                    .position(|t| t.deaka().as_usize() == tid)
+                    .unwrap();
+                pool.swap_remove(idx)
+            })
+            .collect();
+        return Some(tiles);
+    }
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use serde_json as json;
+
+    const LOG: &str = r#"
+        {"type":"start_kyoku","bakaze":"E","dora_marker":"1s","kyoku":1,"honba":0,"kyotaku":0,"oya":0,"scores":[25000,25000,25000,25000],"tehais":[["1m","2m","3m","9m","2p","3p","5p","6p","7s","8s","9s","S","W"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"]]}
+        {"type":"tsumo","actor":0,"pai":"4p"}
+        {"type":"dahai","actor":0,"pai":"9m","tsumogiri":false}
+        {"type":"tsumo","actor":1,"pai":"?"}
+        {"type":"dahai","actor":1,"pai":"E","tsumogiri":false}
+        {"type":"pon","actor":3,"target":1,"pai":"E","consumed":["E","E"]}
+        {"type":"dahai","actor":3,"pai":"1p","tsumogiri":false}
+        {"type":"tsumo","actor":0,"pai":"P"}
+        {"type":"dahai","actor":0,"pai":"P","tsumogiri":true}
+        {"type":"tsumo","actor":1,"pai":"?"}
+        {"type":"reach","actor":1}
+        {"type":"dahai","actor":1,"pai":"9s","tsumogiri":false}
+        {"type":"reach_accepted","actor":1}
+        {"type":"tsumo","actor":2,"pai":"?"}
+        {"type":"dahai","actor":2,"pai":"5s","tsumogiri":true}
+        {"type":"tsumo","actor":3,"pai":"?"}
+        {"type":"dahai","actor":3,"pai":"2m","tsumogiri":false}
+        {"type":"tsumo","actor":0,"pai":"6p"}
+    "#;
+
+    fn events(log: &str) -> Vec<Event> {
+        log.trim()
+            .lines()
+            .map(|l| json::from_str(l).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn rollout() {
+        let rollout = Rollout {
+            player_id: 0,
+            rollouts: 8,
+            seed: 1009,
+            policy: Policy::RuleBased,
+            rule: RuleSet::default(),
+        };
+        let actions = events(
+            r#"
+                {"type":"dahai","actor":0,"pai":"S","tsumogiri":false}
+                {"type":"dahai","actor":0,"pai":"6p","tsumogiri":true}
+            "#,
+        );
+        let outcomes = rollout.run(&events(LOG), &actions).unwrap();
+        assert_eq!(outcomes.len(), 2);
+        for outcome in &outcomes {
+            assert_eq!(outcome.deltas.len(), 8);
+            for rate in [
+                outcome.win_rate,
+                outcome.deal_in_rate,
+                outcome.ryukyoku_rate,
+            ] {
+                assert!((0. ..=1.).contains(&rate));
+            }
+        }
+        // Deterministic with the same seed.
+        let again = rollout.run(&events(LOG), &actions).unwrap();
+        assert_eq!(again[0].deltas, outcomes[0].deltas);
+
+        let invalid = events(r#"{"type":"dahai","actor":0,"pai":"C","tsumogiri":false}"#);
+        assert!(rollout.run(&events(LOG), &invalid).is_err());
+        // Not at a decision point of the player.
+        let log = events(LOG);
+        assert!(rollout.run(&log[..log.len() - 1], &actions).is_err());
+    }
+
+    #[test]
+    fn tenpai_for_riichi() {
+        let mut rng = ChaCha12Rng::seed_from_u64(0);
+        let mut pool = unshuffled(3).to_vec();
+        let tiles = draw_tenpai(&mut pool, 13, &mut rng).unwrap();
+        assert_eq!(pool.len(), 136 - 13);
+
+        let mut hand = [0; 34];
+        for t in tiles {
+            hand[t.deaka().as_usize()] += 1;
+        }
+        assert_eq!(crate::algo::shanten::calc_all(&hand, 4), 0);
+    }
+}
@@ -0,0 +1,346 @@
+use super::duplicate::{Duplicate, PairedResult};
+use crate::agent::BatchAgent;
+#[cfg(feature = "python")]
+use crate::agent::MortalBatchAgent;
+
+use anyhow::{ensure, Result};
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+use riichi_macros::{pyclass, pymethods};
+use serde::Serialize;
+#[cfg(feature = "python")]
+use serde_json as json;
+
+/// Sequential evaluation of a challenger against a champion, which runs
+/// `Duplicate` seeds in batches of `batch_seeds` and stops as soon as the
+/// sequential probability ratio test decides, or after `max_seeds`.
+///
+/// Each seed counts as one match, won by the challenger if its
+/// `PairedResult::score_delta` is positive, lost if negative and drawn
+/// otherwise. The test is between H0: the Elo difference of the matches is
+/// `elo0` and H1: it is `elo1`, with the false positive rate `alpha` and the
+/// false negative rate `beta`, using the GSPRT approximation of the
+/// log-likelihood ratio.
+#[pyclass]
+#[pyo3(text_signature = "(
+    *,
+    elo0 = 0.0,
+    elo1 = 10.0,
+    alpha = 0.05,
+    beta = 0.05,
+    batch_seeds = 64,
+    max_seeds = 2500,
+    lineup = [0, 1, 0, 1],
+    disable_progress_bar = False,
+)")]
+#[derive(Clone)]
+pub struct Sprt {
+    pub elo0: f64,
+    pub elo1: f64,
+    pub alpha: f64,
+    pub beta: f64,
+    pub batch_seeds: u64,
+    pub max_seeds: u64,
+    pub duplicate: Duplicate,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct Wdl {
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Verdict {
+    /// H1 is accepted, the challenger is stronger by at least `elo1`.
+    H1,
+    /// H0 is accepted, the challenger is not stronger by `elo1`.
+    H0,
+    /// `max_seeds` is reached before either bound.
+    Inconclusive,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SprtReport {
+    pub verdict: Verdict,
+    pub wdl: Wdl,
+    /// Number of seeds played, twice as many hanchans.
+    pub seeds: u64,
+    pub llr: f64,
+    /// The bounds of `llr`, below which H0 is accepted and above which H1 is.
+    pub lower_bound: f64,
+    pub upper_bound: f64,
+    /// Estimated Elo difference of the matches and its 95% confidence
+    /// interval.
+    pub elo: f64,
+    pub elo_95: (f64, f64),
+}
+
+#[pymethods]
+impl Sprt {
+    #[new]
+    #[args(
+        "*",
+        elo0 = "0.",
+        elo1 = "10.",
+        alpha = "0.05",
+        beta = "0.05",
+        batch_seeds = "64",
+        max_seeds = "2500",
+        lineup = "[0, 1, 0, 1]",
+        disable_progress_bar = "false"
+    )]
+    #[allow(clippy::too_many_arguments)]
+    fn py_new(
+        elo0: f64,
+        elo1: f64,
+        alpha: f64,
+        beta: f64,
+        batch_seeds: u64,
+        max_seeds: u64,
+        lineup: [usize; 4],
+        disable_progress_bar: bool,
+    ) -> Result<Self> {
+        ensure!(elo0 < elo1, "elo0 must be less than elo1");
+        ensure!(
+            alpha > 0. && alpha < 1. && beta > 0. && beta < 1.,
+            "alpha and beta must be in (0, 1)",
+        );
+        ensure!(batch_seeds > 0, "batch_seeds must be positive");
+        let duplicate = Duplicate::new(lineup, disable_progress_bar, None)?;
+        Ok(Self {
+            elo0,
+            elo1,
+            alpha,
+            beta,
+            batch_seeds,
+            max_seeds,
+            duplicate,
+        })
+    }
+
+    /// Returns the `SprtReport` as a JSON string.
+    #[cfg(feature = "python")]
+    #[pyo3(text_signature = "($self, challenger, champion, seed_start)")]
+    pub fn py_vs_py(
+        &self,
+        challenger: PyObject,
+        champion: PyObject,
+        seed_start: (u64, u64),
+        py: Python<'_>,
+    ) -> Result<String> {
+        let new_agent = |engine: &PyObject, player_ids: &[u8]| {
+            let engine = Python::with_gil(|py| engine.clone_ref(py));
+            MortalBatchAgent::new(engine, player_ids)
+        };
+        py.allow_threads(move || {
+            let report = self.run(
+                |player_ids| new_agent(&challenger, player_ids),
+                |player_ids| new_agent(&champion, player_ids),
+                seed_start,
+            )?;
+            Ok(json::to_string(&report)?)
+        })
+    }
+}
+
+impl Sprt {
+    /// The agents are created anew for every batch.
+    pub fn run<C, M, CA, MA>(
+        &self,
+        new_challenger_agent: C,
+        new_champion_agent: M,
+        seed_start: (u64, u64),
+    ) -> Result<SprtReport>
+    where
+        C: Fn(&[u8]) -> Result<CA>,
+        M: Fn(&[u8]) -> Result<MA>,
+        CA: BatchAgent + 'static,
+        MA: BatchAgent + 'static,
+    {
+        let mut wdl = Wdl::default();
+        let mut seeds = 0;
+        loop {
+            let verdict = self.verdict(wdl);
+            if verdict != Verdict::Inconclusive || seeds >= self.max_seeds {
+                return Ok(self.report(verdict, wdl, seeds));
+            }
+
+            let count = self.batch_seeds.min(self.max_seeds - seeds);
+            let results = self.duplicate.run_batch(
+                &new_challenger_agent,
+                &new_champion_agent,
+                (seed_start.0 + seeds, seed_start.1),
+                count,
+            )?;
+            for paired in &results {
+                wdl.add(paired);
+            }
+            seeds += count;
+
+            log::info!("sprt: {seeds} seeds, {wdl:?}, llr {:.3}", self.llr(wdl),);
+        }
+    }
+
+    /// Returns the GSPRT approximation of the log-likelihood ratio of H1 over
+    /// H0 given `wdl`.
+    #[must_use]
+    pub fn llr(&self, wdl: Wdl) -> f64 {
+        if wdl.total() == 0 {
+            return 0.;
+        }
+        let n = wdl.total() as f64;
+        let score = wdl.score();
+        let var = wdl.variance();
+        let (s0, s1) = (elo_to_score(self.elo0), elo_to_score(self.elo1));
+        // A zero variance, such as all wins, is as decisive as it gets, which
+        // is capped rather than dividing by zero.
+        (s1 - s0) * 2_f64.mul_add(score, -s0 - s1) / (2. * var.max(1e-6) / n)
+    }
+
+    /// Returns the bounds of the LLR by Wald.
+    #[must_use]
+    pub fn bounds(&self) -> (f64, f64) {
+        (
+            (self.beta / (1. - self.alpha)).ln(),
+            ((1. - self.beta) / self.alpha).ln(),
+        )
+    }
+
+    #[must_use]
+    pub fn verdict(&self, wdl: Wdl) -> Verdict {
+        let llr = self.llr(wdl);
+        let (lower, upper) = self.bounds();
+        if llr >= upper {
+            Verdict::H1
+        } else if llr <= lower {
+            Verdict::H0
+        } else {
+            Verdict::Inconclusive
+        }
+    }
+
+    fn report(&self, verdict: Verdict, wdl: Wdl, seeds: u64) -> SprtReport {
+        let (lower_bound, upper_bound) = self.bounds();
+        let score = wdl.score();
+        let margin = 1.96 * (wdl.variance() / wdl.total().max(1) as f64).sqrt();
+        SprtReport {
+            verdict,
+            wdl,
+            seeds,
+            llr: self.llr(wdl),
+            lower_bound,
+            upper_bound,
+            elo: score_to_elo(score),
+            elo_95: (score_to_elo(score - margin), score_to_elo(score + margin)),
+        }
+    }
+}
+
+impl Wdl {
+    pub fn add(&mut self, paired: &PairedResult) {
+        let delta = paired.score_delta();
+        if delta > 0. {
+            self.wins += 1;
+        } else if delta < 0. {
+            self.losses += 1;
+        } else {
+            self.draws += 1;
+        }
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn total(self) -> u32 {
+        self.wins + self.draws + self.losses
+    }
+
+    /// Average score of the challenger, where a draw counts as half a win.
+    /// 0.5 if nothing has been played.
+    #[must_use]
+    pub fn score(self) -> f64 {
+        if self.total() == 0 {
+            return 0.5;
+        }
+        (self.wins as f64 + self.draws as f64 / 2.) / self.total() as f64
+    }
+
+    /// Variance of the score of one match.
+    #[must_use]
+    pub fn variance(self) -> f64 {
+        if self.total() == 0 {
+            return 0.;
+        }
+        let s = self.score();
+        let n = self.total() as f64;
+        [(self.wins, 1.), (self.draws, 0.5), (self.losses, 0.)]
+            .iter()
+            .map(|&(count, x)| count as f64 * (x - s).powi(2))
+            .sum::<f64>()
+            / n
+    }
+}
+
+fn elo_to_score(elo: f64) -> f64 {
+    1. / (1. + 10_f64.powf(-elo / 400.))
+}
+
+/// The score is clamped so that a perfect record stays finite.
+fn score_to_elo(score: f64) -> f64 {
+    let score = score.clamp(1e-3, 1. - 1e-3);
+    -400. * (1. / score - 1.).log10()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::agent::{RuleBased, Tsumogiri};
+
+    fn sprt() -> Sprt {
+        Sprt::py_new(0., 10., 0.05, 0.05, 4, 40, [0, 1, 1, 1], true).unwrap()
+    }
+
+    #[test]
+    fn llr() {
+        let sprt = sprt();
+        let (lower, upper) = sprt.bounds();
+        assert!((lower - (0.05_f64 / 0.95).ln()).abs() < 1e-9);
+        assert!((upper - (0.95_f64 / 0.05).ln()).abs() < 1e-9);
+
+        assert!(sprt.llr(Wdl::default()).abs() < 1e-9);
+        assert_eq!(sprt.verdict(Wdl::default()), Verdict::Inconclusive);
+
+        // Even results favor H0, a dominant challenger H1.
+        let even = Wdl {
+            wins: 10000,
+            draws: 0,
+            losses: 10000,
+        };
+        assert!(sprt.llr(even) < 0.);
+        assert_eq!(sprt.verdict(even), Verdict::H0);
+        let dominant = Wdl {
+            wins: 150,
+            draws: 0,
+            losses: 50,
+        };
+        assert!(sprt.llr(dominant) > 0.);
+        assert_eq!(sprt.verdict(dominant), Verdict::H1);
+
+        let report = sprt.report(Verdict::H1, dominant, 200);
+        assert!((report.elo - score_to_elo(0.75)).abs() < 1e-9);
+        assert!(report.elo_95.0 < report.elo && report.elo < report.elo_95.1);
+    }
+
+    #[test]
+    fn stops_early() {
+        let sprt = sprt();
+        let report = sprt
+            .run(RuleBased::new_batched, Tsumogiri::new_batched, (1009, 0))
+            .unwrap();
+        assert_eq!(report.verdict, Verdict::H1);
+        assert!(report.seeds < sprt.max_seeds);
+        assert_eq!(report.wdl.total() as u64, report.seeds);
+    }
+}
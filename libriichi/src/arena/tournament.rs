@@ -0,0 +1,344 @@
+use super::game::{BatchGame, Index};
+use super::result::GameResult;
+use crate::agent::BatchAgent;
+#[cfg(feature = "python")]
+use crate::agent::MortalBatchAgent;
+
+use anyhow::{bail, ensure, Result};
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+use riichi_macros::{pyclass, pymethods};
+use serde::Serialize;
+#[cfg(feature = "python")]
+use serde_json as json;
+
+const INITIAL_RATING: f64 = 1500.;
+
+pub type AgentFactory = Box<dyn Fn(&[u8]) -> Result<Box<dyn BatchAgent>> + Send + Sync>;
+
+pub struct Entrant {
+    pub name: String,
+    /// Creates a batch agent for the given player IDs.
+    pub new_agent: AgentFactory,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Schedule {
+    /// Every pair of entrants meets once.
+    RoundRobin,
+    /// Entrants are sorted by their ratings and paired with their neighbors
+    /// in each round.
+    Swiss { rounds: usize },
+}
+
+/// Tournament between an arbitrary number of entrants.
+///
+/// Every match is between two entrants, and consists of `games_per_match`
+/// seeds, each of which is played twice in 2v2 with the seats swapped, as in
+/// `TwoVsTwo`. All the matches of the same round are run in one batch.
+///
+/// Ratings are Elo ratings, where a 4-player game is treated as pairwise
+/// results between the seats of different entrants, decided by their ranks.
+#[pyclass]
+#[pyo3(text_signature = "(
+    *,
+    schedule = 'round_robin',
+    rounds = 1,
+    games_per_match = 1,
+    seed_start = (0, 0),
+    k = 16.0,
+    disable_progress_bar = False,
+)")]
+#[derive(Debug, Clone)]
+pub struct Tournament {
+    pub schedule: Schedule,
+    pub games_per_match: u64,
+    pub seed_start: (u64, u64),
+    /// K-factor of Elo.
+    pub k: f64,
+    pub disable_progress_bar: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Standing {
+    pub name: String,
+    pub rating: f64,
+    /// Number of hanchans played.
+    pub games: u32,
+    /// Counts from 1, averaged over the seats.
+    pub avg_rank: f64,
+    pub avg_score: f64,
+}
+
+#[pymethods]
+impl Tournament {
+    #[new]
+    #[args(
+        "*",
+        schedule = "\"round_robin\"",
+        rounds = "1",
+        games_per_match = "1",
+        seed_start = "(0, 0)",
+        k = "16.",
+        disable_progress_bar = "false"
+    )]
+    fn py_new(
+        schedule: &str,
+        rounds: usize,
+        games_per_match: u64,
+        seed_start: (u64, u64),
+        k: f64,
+        disable_progress_bar: bool,
+    ) -> Result<Self> {
+        let schedule = match schedule {
+            "round_robin" => Schedule::RoundRobin,
+            "swiss" => Schedule::Swiss { rounds },
+            _ => bail!("unknown schedule {schedule}"),
+        };
+        ensure!(games_per_match > 0, "games_per_match must be positive");
+        Ok(Self {
+            schedule,
+            games_per_match,
+            seed_start,
+            k,
+            disable_progress_bar,
+        })
+    }
+
+    /// `engines` is a list of `(name, engine)`. Returns the leaderboard as a
+    /// JSON string, which is a list of standings sorted by rating.
+    #[cfg(feature = "python")]
+    #[pyo3(name = "run")]
+    #[pyo3(text_signature = "($self, engines, /)")]
+    fn run_py(&self, engines: Vec<(String, PyObject)>, py: Python<'_>) -> Result<String> {
+        let entrants = engines
+            .into_iter()
+            .map(|(name, engine)| {
+                let new_agent: AgentFactory = Box::new(move |player_ids: &[u8]| {
+                    let engine = Python::with_gil(|py| engine.clone_ref(py));
+                    let agent = MortalBatchAgent::new(engine, player_ids)?;
+                    Ok(Box::new(agent) as Box<dyn BatchAgent>)
+                });
+                Entrant { name, new_agent }
+            })
+            .collect::<Vec<_>>();
+        py.allow_threads(move || {
+            let standings = self.run(&entrants)?;
+            Ok(json::to_string(&standings)?)
+        })
+    }
+}
+
+impl Tournament {
+    pub fn run(&self, entrants: &[Entrant]) -> Result<Vec<Standing>> {
+        ensure!(entrants.len() >= 2, "at least 2 entrants are required");
+
+        let mut standings: Vec<_> = entrants
+            .iter()
+            .map(|e| Standing {
+                name: e.name.clone(),
+                rating: INITIAL_RATING,
+                games: 0,
+                avg_rank: 0.,
+                avg_score: 0.,
+            })
+            .collect();
+        let mut rank_sums = vec![0_u32; entrants.len()];
+        let mut score_sums = vec![0_i64; entrants.len()];
+
+        let mut next_nonce = self.seed_start.0;
+        for _ in 0..self.num_rounds() {
+            let pairs = self.pairs(&standings);
+            let seeds: Vec<_> = (0..pairs.len() as u64 * self.games_per_match)
+                .map(|i| (next_nonce + i, self.seed_start.1))
+                .collect();
+            next_nonce += seeds.len() as u64;
+
+            let results = self.run_round(entrants, &pairs, &seeds)?;
+            for (game_idx, result) in results.iter().enumerate() {
+                let (a, b) = pairs[game_idx / (self.games_per_match as usize * 2)];
+                // Seats 0 and 2 belong to `a` in the first split, and to `b`
+                // in the second.
+                let owners = if game_idx % 2 == 0 {
+                    [a, b, a, b]
+                } else {
+                    [b, a, b, a]
+                };
+                let rankings = result.rankings();
+                for (seat, &owner) in owners.iter().enumerate() {
+                    rank_sums[owner] += rankings.rank_by_player[seat] as u32 + 1;
+                    score_sums[owner] += result.scores[seat] as i64;
+                }
+                standings[a].games += 1;
+                standings[b].games += 1;
+                self.update_ratings(&mut standings, &owners, result);
+            }
+        }
+
+        for (i, s) in standings.iter_mut().enumerate() {
+            // Each entrant takes 2 seats in every game.
+            let seats = s.games as f64 * 2.;
+            if seats > 0. {
+                s.avg_rank = rank_sums[i] as f64 / seats;
+                s.avg_score = score_sums[i] as f64 / seats;
+            }
+        }
+        standings.sort_by(|l, r| r.rating.total_cmp(&l.rating));
+        Ok(standings)
+    }
+
+    const fn num_rounds(&self) -> usize {
+        match self.schedule {
+            Schedule::RoundRobin => 1,
+            Schedule::Swiss { rounds } => rounds,
+        }
+    }
+
+    /// Swiss rounds depend on the ratings so far, so the pairs are decided
+    /// right before each round.
+    fn pairs(&self, standings: &[Standing]) -> Vec<(usize, usize)> {
+        let n = standings.len();
+        match self.schedule {
+            Schedule::RoundRobin => (0..n)
+                .flat_map(|a| (a + 1..n).map(move |b| (a, b)))
+                .collect(),
+            Schedule::Swiss { .. } => {
+                let mut order: Vec<_> = (0..n).collect();
+                order.sort_by(|&l, &r| standings[r].rating.total_cmp(&standings[l].rating));
+                // With an odd number of entrants, the last one gets a bye.
+                order.chunks_exact(2).map(|c| (c[0], c[1])).collect()
+            }
+        }
+    }
+
+    fn run_round(
+        &self,
+        entrants: &[Entrant],
+        pairs: &[(usize, usize)],
+        seeds: &[(u64, u64)],
+    ) -> Result<Vec<GameResult>> {
+        let mut agents = vec![];
+        let mut agent_names = vec![];
+        let mut indexes = vec![];
+        let mut all_seeds = vec![];
+        for (match_idx, &(a, b)) in pairs.iter().enumerate() {
+            let match_seeds = &seeds[match_idx * self.games_per_match as usize..]
+                [..self.games_per_match as usize];
+            let player_ids: Vec<_> = match_seeds.iter().flat_map(|_| [0, 2, 1, 3]).collect();
+
+            let a_idx = agents.len();
+            agents.push((entrants[a].new_agent)(&player_ids)?);
+            agents.push((entrants[b].new_agent)(&player_ids)?);
+            agent_names.extend([entrants[a].name.clone(), entrants[b].name.clone()]);
+
+            for (i, &seed) in match_seeds.iter().enumerate() {
+                let idx = |agent_idx, player_id_idx| Index {
+                    agent_idx,
+                    player_id_idx: i * 4 + player_id_idx,
+                };
+                indexes.push([
+                    idx(a_idx, 0),
+                    idx(a_idx + 1, 2),
+                    idx(a_idx, 1),
+                    idx(a_idx + 1, 3),
+                ]);
+                indexes.push([
+                    idx(a_idx + 1, 0),
+                    idx(a_idx, 2),
+                    idx(a_idx + 1, 1),
+                    idx(a_idx, 3),
+                ]);
+                all_seeds.extend([seed, seed]);
+            }
+        }
+
+        let batch_game = BatchGame {
+            agent_names,
+            ..BatchGame::tenhou_hanchan(self.disable_progress_bar)
+        };
+        batch_game.run(&mut agents, &indexes, &all_seeds)
+    }
+
+    fn update_ratings(&self, standings: &mut [Standing], owners: &[usize; 4], result: &GameResult) {
+        let rankings = result.rankings();
+        let before: Vec<_> = standings.iter().map(|s| s.rating).collect();
+        for i in 0..4 {
+            for j in 0..4 {
+                let (a, b) = (owners[i], owners[j]);
+                if a == b {
+                    continue;
+                }
+                let score = match rankings.rank_by_player[i].cmp(&rankings.rank_by_player[j]) {
+                    std::cmp::Ordering::Less => 1.,
+                    std::cmp::Ordering::Equal => 0.5,
+                    std::cmp::Ordering::Greater => 0.,
+                };
+                let expected = 1. / (1. + 10_f64.powf((before[b] - before[a]) / 400.));
+                // Each seat faces 2 seats of the opponent.
+                standings[a].rating += self.k * (score - expected) / 2.;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::agent::{RuleBased, Tsumogiri};
+
+    use serde_json as json;
+
+    fn entrants() -> Vec<Entrant> {
+        let rule_based = Entrant {
+            name: "rule_based".to_owned(),
+            new_agent: Box::new(|ids: &[u8]| Ok(Box::new(RuleBased::new_batched(ids)?) as _)),
+        };
+        let tsumogiri = |name: &str| Entrant {
+            name: name.to_owned(),
+            new_agent: Box::new(|ids: &[u8]| Ok(Box::new(Tsumogiri::new_batched(ids)?) as _)),
+        };
+        vec![
+            tsumogiri("tsumogiri_a"),
+            rule_based,
+            tsumogiri("tsumogiri_b"),
+        ]
+    }
+
+    #[test]
+    fn round_robin() {
+        let tournament = Tournament {
+            schedule: Schedule::RoundRobin,
+            games_per_match: 1,
+            seed_start: (1009, 0),
+            k: 16.,
+            disable_progress_bar: true,
+        };
+        let standings = tournament.run(&entrants()).unwrap();
+
+        assert_eq!(standings[0].name, "rule_based");
+        assert!(standings.iter().all(|s| s.games == 4));
+        // Elo is zero-sum.
+        let rating_avg = standings.iter().map(|s| s.rating).sum::<f64>() / 3.;
+        assert!((rating_avg - INITIAL_RATING).abs() < 1e-6);
+
+        let value = json::to_value(&standings).unwrap();
+        assert_eq!(value[0]["name"], "rule_based");
+        assert!(value[0]["avg_rank"].as_f64().unwrap() < 2.5);
+    }
+
+    #[test]
+    fn swiss() {
+        let tournament = Tournament {
+            schedule: Schedule::Swiss { rounds: 2 },
+            games_per_match: 1,
+            seed_start: (1009, 0),
+            k: 16.,
+            disable_progress_bar: true,
+        };
+        let standings = tournament.run(&entrants()).unwrap();
+        // One of them gets a bye in each round.
+        assert_eq!(standings.iter().map(|s| s.games).sum::<u32>(), 2 * 2 * 2);
+
+        assert!(tournament.run(&entrants()[..1]).is_err());
+    }
+}
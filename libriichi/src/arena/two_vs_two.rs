@@ -1,17 +1,18 @@
 use super::game::{BatchGame, Index};
 use super::result::GameResult;
-use crate::agent::{AkochanAgent, BatchAgent, MortalBatchAgent};
-use std::fs::{self, File};
-use std::io::prelude::*;
-use std::iter;
+use crate::agent::BatchAgent;
+#[cfg(feature = "python")]
+use crate::agent::{AkochanAgent, MortalBatchAgent};
+use crate::log_io;
+use crate::par::*;
 use std::path::PathBuf;
+use std::{fs, iter};
 
 use anyhow::Result;
-use flate2::read::GzEncoder;
-use flate2::Compression;
-use indicatif::{ParallelProgressIterator, ProgressBar, ProgressStyle};
+use indicatif::{ProgressBar, ProgressStyle};
+#[cfg(feature = "python")]
 use pyo3::prelude::*;
-use rayon::prelude::*;
+use riichi_macros::{pyclass, pymethods};
 
 #[pyclass]
 #[pyo3(text_signature = "(
@@ -36,6 +37,7 @@ impl TwoVsTwo {
         }
     }
 
+    #[cfg(feature = "python")]
     #[pyo3(text_signature = "(challenger, champion, seed_start, seed_count)")]
     pub fn py_vs_py(
         &self,
@@ -59,6 +61,7 @@ impl TwoVsTwo {
         })
     }
 
+    #[cfg(feature = "python")]
     #[pyo3(text_signature = "($self, engine, seed_start, seed_count)")]
     pub fn ako_vs_py(
         &self,
@@ -78,6 +81,7 @@ impl TwoVsTwo {
         })
     }
 
+    #[cfg(feature = "python")]
     #[pyo3(text_signature = "($self, engine, seed_start, seed_count)")]
     pub fn py_vs_ako(
         &self,
@@ -97,6 +101,7 @@ impl TwoVsTwo {
         })
     }
 
+    #[cfg(feature = "python")]
     #[pyo3(text_signature = "($self, engine, seed, split)")]
     pub fn py_vs_ako_one(
         &self,
@@ -234,14 +239,11 @@ impl TwoVsTwo {
                     .iter()
                     .collect();
 
-                    let log = game_result.dump_json_log()?;
-                    let mut comp = GzEncoder::new(log.as_bytes(), Compression::best());
-                    let mut data = vec![];
-                    comp.read_to_end(&mut data)?;
-
-                    let mut f = File::create(filename)?;
-                    f.write_all(&data)?;
-                    f.sync_all()?;
+                    fs::write(
+                        filename.with_extension("").with_extension("kyokus.jsonl"),
+                        game_result.dump_kyoku_records()?,
+                    )?;
+                    log_io::write_log(filename, &game_result.dump_json_log()?)?;
 
                     anyhow::Ok(())
                 })?;
@@ -333,14 +335,7 @@ impl TwoVsTwo {
                 .iter()
                 .collect();
 
-            let log = results[0].dump_json_log()?;
-            let mut comp = GzEncoder::new(log.as_bytes(), Compression::best());
-            let mut data = vec![];
-            comp.read_to_end(&mut data)?;
-
-            let mut f = File::create(filename)?;
-            f.write_all(&data)?;
-            f.sync_all()?;
+            log_io::write_log(filename, &results[0].dump_json_log()?)?;
         }
 
         Ok(results.into_iter().next().unwrap())
@@ -0,0 +1,214 @@
+use super::board::unshuffled;
+use crate::consts::TILES_LEFT_AT_START;
+use crate::tile::Tile;
+use std::convert::TryInto;
+use std::fs;
+use std::path::Path;
+
+use anyhow::{ensure, Context, Result};
+use rand::prelude::*;
+use rand_chacha::ChaCha12Rng;
+use serde::{Deserialize, Serialize};
+use serde_json as json;
+use sha3::{Digest, Sha3_256};
+
+/// All the tiles of one kyoku, laid out in the same way as `Board`.
+///
+/// A wall generated by `from_seed` is fully determined by the game seed,
+/// `kyoku`, `honba` and `aka_count`. Walls can be dumped and loaded back with
+/// `dump_walls` and `load_walls`, and replayed with `BatchGame::replay`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Wall {
+    /// Counts from 0
+    pub kyoku: u8,
+    pub honba: u8,
+
+    pub haipai: [[Tile; 13]; 4],
+    /// Goes backward (pop)
+    pub yama: Vec<Tile>,
+    /// Goes backward (pop)
+    pub rinshan: Vec<Tile>,
+    /// Goes backward (pop)
+    pub dora_indicators: Vec<Tile>,
+    /// Goes forward (iter)
+    pub ura_indicators: Vec<Tile>,
+}
+
+impl Wall {
+    #[must_use]
+    pub fn from_seed(game_seed: (u64, u64), kyoku: u8, honba: u8, aka_count: u8) -> Self {
+        let (nonce, key) = game_seed;
+        let kyoku_seed = Sha3_256::new()
+            .chain_update(nonce.to_le_bytes())
+            .chain_update(key.to_le_bytes())
+            .chain_update([kyoku, honba])
+            .finalize()
+            .try_into()
+            .unwrap();
+        let mut rng = ChaCha12Rng::from_seed(kyoku_seed);
+        let mut seq = unshuffled(aka_count);
+        seq.shuffle(&mut rng);
+
+        Self::from_seq(kyoku, honba, seq)
+    }
+
+    /// Builds a wall from an explicit sequence of 136 tiles, e.g. to set up
+    /// corner cases in tests.
+    ///
+    /// The layout of `tiles` is: haipai of seat 0 to 3 (13 * 4), rinshan (4,
+    /// drawn from the last one), dora indicators (5, revealed from the last
+    /// one), ura indicators (5, in order), and yama (70, drawn from the last
+    /// one, so the first tile is the haitei).
+    pub fn from_tiles(kyoku: u8, honba: u8, tiles: [Tile; 136]) -> Result<Self> {
+        let wall = Self::from_seq(kyoku, honba, tiles);
+        wall.validate()?;
+        Ok(wall)
+    }
+
+    fn from_seq(kyoku: u8, honba: u8, seq: [Tile; 136]) -> Self {
+        let haipai = [
+            seq[..13].try_into().unwrap(),
+            seq[13..13 * 2].try_into().unwrap(),
+            seq[13 * 2..13 * 3].try_into().unwrap(),
+            seq[13 * 3..13 * 4].try_into().unwrap(),
+        ];
+        let mut idx = 13 * 4;
+
+        let rinshan = seq[idx..idx + 4].to_vec();
+        idx += 4;
+        let dora_indicators = seq[idx..idx + 5].to_vec();
+        idx += 5;
+        let ura_indicators = seq[idx..idx + 5].to_vec();
+        idx += 5;
+        let yama = seq[idx..idx + TILES_LEFT_AT_START as usize].to_vec();
+        idx += TILES_LEFT_AT_START as usize;
+        assert_eq!(idx, seq.len());
+
+        Self {
+            kyoku,
+            honba,
+            haipai,
+            yama,
+            rinshan,
+            dora_indicators,
+            ura_indicators,
+        }
+    }
+
+    /// Checks the sizes of each part, since a loaded wall may have been
+    /// edited by hand.
+    pub fn validate(&self) -> Result<()> {
+        ensure!(
+            self.yama.len() == TILES_LEFT_AT_START as usize,
+            "yama must have {TILES_LEFT_AT_START} tiles, got {}",
+            self.yama.len(),
+        );
+        ensure!(self.rinshan.len() == 4, "rinshan must have 4 tiles");
+        ensure!(
+            self.dora_indicators.len() == 5 && self.ura_indicators.len() == 5,
+            "there must be 5 dora and 5 ura indicators",
+        );
+
+        let mut counts = [0_u8; 34];
+        let all = self
+            .haipai
+            .iter()
+            .flatten()
+            .chain(&self.yama)
+            .chain(&self.rinshan)
+            .chain(&self.dora_indicators)
+            .chain(&self.ura_indicators);
+        for tile in all {
+            let tid = tile.deaka().as_usize();
+            ensure!(tid < 34, "wall must not contain unknown tiles");
+            counts[tid] += 1;
+        }
+        ensure!(counts.iter().all(|&c| c == 4), "wall is not a full set");
+        Ok(())
+    }
+}
+
+/// Dumps `walls` to `path` as JSON lines, one wall per line.
+pub fn dump_walls(path: impl AsRef<Path>, walls: &[Wall]) -> Result<()> {
+    let mut out = String::new();
+    for wall in walls {
+        out += &json::to_string(wall)?;
+        out.push('\n');
+    }
+    fs::write(path, out)?;
+    Ok(())
+}
+
+pub fn load_walls(path: impl AsRef<Path>) -> Result<Vec<Wall>> {
+    let path = path.as_ref();
+    let raw = fs::read_to_string(path)?;
+    raw.lines()
+        .filter(|l| !l.trim().is_empty())
+        .enumerate()
+        .map(|(i, line)| {
+            let wall: Wall = json::from_str(line)
+                .with_context(|| format!("failed to parse wall at line {}", i + 1))?;
+            wall.validate()
+                .with_context(|| format!("invalid wall at line {}", i + 1))?;
+            Ok(wall)
+        })
+        .collect::<Result<_>>()
+        .with_context(|| format!("in {}", path.display()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::t;
+    use std::env;
+
+    #[test]
+    fn from_seed() {
+        let wall = Wall::from_seed((1009, 0), 2, 1, 3);
+        wall.validate().unwrap();
+        assert_eq!(wall, Wall::from_seed((1009, 0), 2, 1, 3));
+        assert_ne!(wall, Wall::from_seed((1009, 0), 2, 2, 3));
+        assert_ne!(wall, Wall::from_seed((1009, 1), 2, 1, 3));
+
+        let aka_count = |wall: &Wall, aka: Tile| {
+            wall.haipai
+                .iter()
+                .flatten()
+                .chain(&wall.yama)
+                .chain(&wall.rinshan)
+                .chain(&wall.dora_indicators)
+                .chain(&wall.ura_indicators)
+                .filter(|&&t| t == aka)
+                .count()
+        };
+        let wall = Wall::from_seed((1009, 0), 2, 1, 0);
+        assert_eq!(aka_count(&wall, t!(5mr)) + aka_count(&wall, t!(5pr)), 0);
+        // Only the akas are different from 3 akas.
+        let wall4 = Wall::from_seed((1009, 0), 2, 1, 4);
+        wall4.validate().unwrap();
+        assert_eq!(aka_count(&wall4, t!(5pr)), 2);
+        assert_eq!(aka_count(&wall4, t!(5mr)), 1);
+        assert_eq!(aka_count(&wall4, t!(5sr)), 1);
+        let deaka = |wall: &Wall| wall.yama.iter().map(|t| t.deaka()).collect::<Vec<_>>();
+        assert_eq!(deaka(&wall4), deaka(&Wall::from_seed((1009, 0), 2, 1, 3)));
+    }
+
+    #[test]
+    fn dump_and_load() {
+        let walls: Vec<_> = (0..3)
+            .map(|kyoku| Wall::from_seed((42, 7), kyoku, 0, 3))
+            .collect();
+
+        let path = env::temp_dir().join(format!("riichi_walls_{}.jsonl", std::process::id()));
+        dump_walls(&path, &walls).unwrap();
+        let loaded = load_walls(&path).unwrap();
+        assert_eq!(loaded, walls);
+
+        let mut broken = walls[0].clone();
+        broken.yama.pop();
+        dump_walls(&path, &[broken]).unwrap();
+        load_walls(&path).unwrap_err();
+
+        fs::remove_file(&path).unwrap();
+    }
+}
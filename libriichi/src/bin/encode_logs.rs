@@ -0,0 +1,34 @@
+use riichi::GameplayLoader;
+use std::env;
+
+use anyhow::{Context, Result};
+
+const USAGE: &str = "Usage: encode_logs <LOG_DIR> <OUT_DIR> [LOGS_PER_SHARD]";
+
+fn main() -> Result<()> {
+    let args: Vec<_> = env::args().collect();
+    let log_dir = args.get(1).context(USAGE)?;
+    let out_dir = args.get(2).context(USAGE)?;
+    let logs_per_shard = args
+        .get(3)
+        .map(|n| n.parse())
+        .transpose()
+        .context(USAGE)?
+        .unwrap_or(1000);
+
+    // Same as the defaults of the Python constructor.
+    let loader = GameplayLoader {
+        oracle: true,
+        always_include_kan_select: true,
+        ..Default::default()
+    };
+    let summary = loader.encode_logs(log_dir, out_dir, logs_per_shard, false)?;
+    println!(
+        "\n{} games of {} logs in {} shards",
+        summary.games,
+        summary.logs,
+        summary.shards.len(),
+    );
+
+    Ok(())
+}
@@ -0,0 +1,16 @@
+use riichi::algo::tables::{write_assets, TABLES_DIR_ENV};
+use std::env;
+
+use anyhow::{Context, Result};
+
+const USAGE: &str = "Usage: gen_tables <DIR>";
+
+fn main() -> Result<()> {
+    let args: Vec<_> = env::args().collect();
+    let dir = args.get(1).context(USAGE)?;
+
+    write_assets(dir)?;
+    println!("tables written, set {TABLES_DIR_ENV}={dir} to use them");
+
+    Ok(())
+}
@@ -0,0 +1,76 @@
+use riichi::agent::{BatchAgent, RuleBased, Tsumogiri};
+use riichi::mjai::client::{self, JoinOptions};
+use riichi::mjai::Bot;
+use std::env;
+use std::io;
+
+use anyhow::{bail, Context, Result};
+
+const USAGE: &str =
+    "Usage: mortal_bot [--name <NAME>] [--room <ROOM>] [--agent <AGENT>] [--connect <URL>] [ID]
+
+Speaks the mjai protocol over stdin and stdout, replying to every message, or
+over TCP to the server given by --connect.
+
+ARGS:
+    [ID]    The player ID, an integer within [0, 3]. Only required if the
+            server does not send `id` in `start_game`.
+
+OPTIONS:
+    --name <NAME>      Name to send in `join` [default: mortal]
+    --room <ROOM>      Room to send in `join` [default: default]
+    --agent <AGENT>    One of `rule_based` and `tsumogiri` [default: rule_based]
+    --connect <URL>    The mjai server to play on, either `mjsonp://host:port/room`
+                       or `host:port`";
+
+struct Args {
+    join: JoinOptions,
+    agent: String,
+    connect: Option<String>,
+}
+
+fn main() -> Result<()> {
+    let args = parse_args().context(USAGE)?;
+    let new_bot = |player_id| new_bot(&args.agent, player_id);
+
+    match &args.connect {
+        Some(url) => client::connect(url, &args.join, new_bot),
+        None => client::play(io::stdin().lock(), io::stdout().lock(), &args.join, new_bot),
+    }
+}
+
+fn parse_args() -> Result<Args> {
+    let mut args = Args {
+        join: JoinOptions::default(),
+        agent: "rule_based".to_owned(),
+        connect: None,
+    };
+
+    let mut iter = env::args().skip(1);
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--name" => args.join.name = iter.next().context("missing value for --name")?,
+            "--room" => args.join.room = iter.next().context("missing value for --room")?,
+            "--agent" => args.agent = iter.next().context("missing value for --agent")?,
+            "--connect" => {
+                args.connect = Some(iter.next().context("missing value for --connect")?);
+            }
+            "-h" | "--help" => bail!("help requested"),
+            id => {
+                let id = id.parse().ok().filter(|id| matches!(id, 0..=3));
+                args.join.player_id = Some(id.context("invalid player ID")?);
+            }
+        }
+    }
+
+    Ok(args)
+}
+
+fn new_bot(agent: &str, player_id: u8) -> Result<Bot> {
+    let agent: Box<dyn BatchAgent + Send> = match agent {
+        "rule_based" => Box::new(RuleBased::new_batched(&[player_id])?),
+        "tsumogiri" => Box::new(Tsumogiri::new_batched(&[player_id])?),
+        _ => bail!("unknown agent {agent}"),
+    };
+    Ok(Bot::new(agent, player_id))
+}
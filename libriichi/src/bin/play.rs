@@ -0,0 +1,286 @@
+use riichi::agent::{BatchAgent, MortalBatchAgent, RuleBased, Tsumogiri};
+use riichi::mjai::Bot;
+use riichi::mjai::Event;
+use riichi::rule::RuleSet;
+use riichi::GameState;
+use std::collections::VecDeque;
+use std::env;
+use std::fs;
+use std::io::{self, prelude::*};
+
+use anyhow::{bail, Context, Result};
+use crossterm::cursor::MoveTo;
+use crossterm::execute;
+use crossterm::style::Stylize;
+use crossterm::terminal::{Clear, ClearType};
+use pyo3::prelude::*;
+use serde_json as json;
+
+const USAGE: &str =
+    "Usage: play [--seat <SEAT>] [--seed <SEED>] [--agent <AGENT>] [--engine <FILE>] [--tonpuusen]
+
+Plays a game against three engine seats in the terminal, under Tenhou rules.
+
+OPTIONS:
+    --seat <SEAT>      The seat to play, an integer within [0, 3] [default: 0]
+    --seed <SEED>      Seed of the walls [default: random]
+    --agent <AGENT>    One of `rule_based` and `tsumogiri` [default: rule_based]
+    --engine <FILE>    A Python script defining `load_engine()`, which returns
+                       an engine for `libriichi.mjai.Bot`, used instead of
+                       --agent
+    --tonpuusen        Play a tonpuusen instead of a hanchan";
+
+/// Number of the recent events shown.
+const HISTORY_LEN: usize = 12;
+
+struct Args {
+    seat: u8,
+    seed: u64,
+    agent: String,
+    engine: Option<String>,
+    tonpuusen: bool,
+}
+
+fn main() -> Result<()> {
+    let args = parse_args().context(USAGE)?;
+    let seat = args.seat;
+
+    let engine = args.engine.as_deref().map(load_engine).transpose()?;
+    let mut bots = vec![];
+    let mut names: [String; 4] = Default::default();
+    for (i, name) in names.iter_mut().enumerate() {
+        let i = i as u8;
+        if i == seat {
+            *name = "you".to_owned();
+            continue;
+        }
+        let agent: Box<dyn BatchAgent + Send> = match &engine {
+            Some(engine) => Box::new(MortalBatchAgent::new(engine.clone(), &[i])?),
+            None => match args.agent.as_str() {
+                "rule_based" => Box::new(RuleBased::new_batched(&[i])?),
+                "tsumogiri" => Box::new(Tsumogiri::new_batched(&[i])?),
+                agent => bail!("unknown agent {agent}"),
+            },
+        };
+        *name = agent.name();
+        bots.push((i, Bot::new(agent, i)));
+    }
+
+    let rule = RuleSet {
+        tonpuusen: args.tonpuusen,
+        ..RuleSet::tenhou()
+    };
+    let mut game = GameState::new((args.seed, 0), names.clone(), rule)?;
+    let mut history = VecDeque::with_capacity(HISTORY_LEN);
+
+    while !game.ended() {
+        for (bot_seat, bot) in &mut bots {
+            let events = game.take_events(*bot_seat)?;
+            let can_act = game.can_act(*bot_seat);
+            let mut reaction = None;
+            for (i, ev) in events.iter().enumerate() {
+                let is_last = i + 1 == events.len();
+                reaction = bot.react(&json::to_string(ev)?, is_last && can_act)?;
+            }
+            if can_act {
+                let action = match reaction {
+                    Some(reaction) => json::from_str(&reaction)?,
+                    None => Event::None,
+                };
+                game.act(*bot_seat, action)?;
+            }
+        }
+
+        let events = game.take_events(seat)?;
+        let kyoku_ended = events.iter().any(|ev| {
+            matches!(
+                ev,
+                Event::Hora { .. } | Event::Ryukyoku { .. } | Event::Chombo { .. }
+            )
+        });
+        for ev in &events {
+            if let Some(line) = describe(ev, &names) {
+                if history.len() == HISTORY_LEN {
+                    history.pop_front();
+                }
+                history.push_back(line);
+            }
+        }
+
+        if game.can_act(seat) {
+            let candidates = game.player_state(seat).reaction_candidates();
+            render(&game, seat, &history)?;
+            let action = prompt(&candidates, &names)?;
+            game.act(seat, action)?;
+        } else if kyoku_ended {
+            // Hold on so that the outcome can be read before the next kyoku.
+            render(&game, seat, &history)?;
+            wait_for_enter()?;
+        }
+    }
+
+    println!("\n{}", "Final scores".bold());
+    for (name, score) in names.iter().zip(game.scores()) {
+        println!("{name:>12}: {score}");
+    }
+    Ok(())
+}
+
+fn parse_args() -> Result<Args> {
+    let mut args = Args {
+        seat: 0,
+        seed: rand_seed(),
+        agent: "rule_based".to_owned(),
+        engine: None,
+        tonpuusen: false,
+    };
+
+    let mut iter = env::args().skip(1);
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--seat" => {
+                let seat = iter.next().context("missing value for --seat")?;
+                let seat = seat.parse().ok().filter(|s| matches!(s, 0..=3));
+                args.seat = seat.context("invalid seat")?;
+            }
+            "--seed" => {
+                let seed = iter.next().context("missing value for --seed")?;
+                args.seed = seed.parse().context("invalid seed")?;
+            }
+            "--agent" => args.agent = iter.next().context("missing value for --agent")?,
+            "--engine" => args.engine = Some(iter.next().context("missing value for --engine")?),
+            "--tonpuusen" => args.tonpuusen = true,
+            "-h" | "--help" => bail!("help requested"),
+            arg => bail!("unexpected argument {arg}"),
+        }
+    }
+
+    Ok(args)
+}
+
+fn rand_seed() -> u64 {
+    use std::time::{SystemTime, UNIX_EPOCH};
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or_default()
+}
+
+fn load_engine(filename: &str) -> Result<PyObject> {
+    let code = fs::read_to_string(filename)?;
+    Python::with_gil(|py| {
+        let module = PyModule::from_code(py, &code, filename, "engine")?;
+        let engine = module.getattr("load_engine")?.call0()?;
+        Ok(engine.into())
+    })
+}
+
+fn render(game: &GameState, seat: u8, history: &VecDeque<String>) -> Result<()> {
+    let mut stdout = io::stdout();
+    execute!(stdout, Clear(ClearType::All), MoveTo(0, 0))?;
+
+    for line in history {
+        println!("{}", line.as_str().dark_grey());
+    }
+    println!();
+    println!("{}", game.player_state(seat).table_info());
+    Ok(())
+}
+
+fn prompt(candidates: &[Event], names: &[String; 4]) -> Result<Event> {
+    println!();
+    for (i, action) in candidates.iter().enumerate() {
+        let desc = describe(action, names).unwrap_or_else(|| "pass".to_owned());
+        println!("{}. {desc}", format!("{i:>3}").bold());
+    }
+
+    let stdin = io::stdin();
+    loop {
+        print!("{} ", ">".cyan());
+        io::stdout().flush()?;
+
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line)? == 0 {
+            bail!("stdin closed");
+        }
+        match line.trim().parse::<usize>() {
+            Ok(i) if i < candidates.len() => return Ok(candidates[i].clone()),
+            _ => println!("enter a number within [0, {})", candidates.len()),
+        }
+    }
+}
+
+fn wait_for_enter() -> Result<()> {
+    print!("\n{}", "Press Enter to continue".cyan());
+    io::stdout().flush()?;
+    io::stdin().lock().read_line(&mut String::new())?;
+    Ok(())
+}
+
+/// Describes an event in a line, or returns `None` for the ones that are not
+/// worth showing, such as tsumo.
+fn describe(ev: &Event, names: &[String; 4]) -> Option<String> {
+    let name = |actor: &u8| &names[*actor as usize];
+    let line = match ev {
+        Event::StartKyoku {
+            bakaze,
+            kyoku,
+            honba,
+            ..
+        } => format!("--- {bakaze}{kyoku}-{honba} ---"),
+        Event::Dahai {
+            actor,
+            pai,
+            tsumogiri,
+        } => {
+            let how = if *tsumogiri { " (tsumogiri)" } else { "" };
+            format!("{} discards {pai}{how}", name(actor))
+        }
+        Event::Chi {
+            actor,
+            pai,
+            consumed,
+            ..
+        } => format!(
+            "{} chis {pai} with {}{}",
+            name(actor),
+            consumed[0],
+            consumed[1]
+        ),
+        Event::Pon { actor, pai, .. } => format!("{} pons {pai}", name(actor)),
+        Event::Daiminkan { actor, pai, .. } | Event::Kakan { actor, pai, .. } => {
+            format!("{} kans {pai}", name(actor))
+        }
+        Event::Ankan { actor, consumed } => {
+            format!("{} ankans {}", name(actor), consumed[1])
+        }
+        Event::Dora { dora_marker } => format!("new dora indicator {dora_marker}"),
+        Event::Reach { actor, .. } => format!("{} declares riichi", name(actor)),
+        Event::Hora {
+            actor,
+            target,
+            deltas,
+            ..
+        } => {
+            let how = if actor == target {
+                "tsumo".to_owned()
+            } else {
+                format!("ron from {}", name(target))
+            };
+            match deltas {
+                Some(deltas) => format!("{} wins by {how}, deltas {deltas:?}", name(actor)),
+                None => format!("{} wins by {how}", name(actor)),
+            }
+        }
+        Event::Ryukyoku { deltas, .. } => match deltas {
+            Some(deltas) => format!("ryukyoku, deltas {deltas:?}"),
+            None => "ryukyoku".to_owned(),
+        },
+        Event::Chombo { actor, deltas } => match deltas {
+            Some(deltas) => format!("chombo by {}, deltas {deltas:?}", name(actor)),
+            None => format!("chombo by {}", name(actor)),
+        },
+        _ => return None,
+    };
+    Some(line)
+}
@@ -0,0 +1,397 @@
+//! A C ABI over `PlayerState` and `Bot`, declared in `include/riichi.h`, for
+//! embedding into clients written in other languages.
+//!
+//! Build the cdylib with `--no-default-features`, which leaves out the
+//! `python` feature, so that it neither depends on nor links to Python. The
+//! default features build the Python extension module instead. All the
+//! events, candidates and reactions are mjai JSON strings.
+//!
+//! Every function but the destructors returns a status, which is `RIICHI_OK`
+//! on success, or one of the negative codes matching the variants of
+//! `Error`, in which case `riichi_last_error` has the message. Strings
+//! returned through out parameters are owned by the caller and must be
+//! released by `riichi_string_free`.
+
+use crate::agent::{BatchAgent, RuleBased, Tsumogiri};
+use crate::error::Error;
+use crate::mjai::Bot;
+use crate::state::PlayerState;
+use std::cell::RefCell;
+use std::ffi::{c_char, c_int, CStr, CString};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+
+use anyhow::{anyhow, bail, ensure, Context};
+use serde_json as json;
+
+pub const RIICHI_OK: c_int = 0;
+pub const RIICHI_ERR_PARSE: c_int = -1;
+pub const RIICHI_ERR_INVALID_EVENT: c_int = -2;
+pub const RIICHI_ERR_ILLEGAL_ACTION: c_int = -3;
+pub const RIICHI_ERR_ENGINE: c_int = -4;
+pub const RIICHI_ERR_OTHER: c_int = -5;
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// Runs `f`, turning its error or panic into a status.
+fn ffi_call<F>(f: F) -> c_int
+where
+    F: FnOnce() -> Result<(), Error>,
+{
+    let ret = panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or_else(|payload| {
+        let msg = payload
+            .downcast_ref::<&str>()
+            .copied()
+            .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+            .unwrap_or("unknown panic");
+        Err(Error::Other(anyhow!("panicked: {msg}")))
+    });
+
+    let (status, msg) = match ret {
+        Ok(()) => (RIICHI_OK, None),
+        Err(err) => {
+            let status = match err {
+                Error::Parse(_) => RIICHI_ERR_PARSE,
+                Error::InvalidEvent(_) => RIICHI_ERR_INVALID_EVENT,
+                Error::IllegalAction(_) => RIICHI_ERR_ILLEGAL_ACTION,
+                Error::Engine(_) => RIICHI_ERR_ENGINE,
+                Error::Other(_) => RIICHI_ERR_OTHER,
+            };
+            let msg = err.to_string().replace('\0', " ");
+            (status, CString::new(msg).ok())
+        }
+    };
+    LAST_ERROR.with(|e| *e.borrow_mut() = msg);
+    status
+}
+
+unsafe fn str_arg<'a>(s: *const c_char) -> Result<&'a str, Error> {
+    if s.is_null() {
+        return Err(Error::Other(anyhow!("unexpected null string")));
+    }
+    CStr::from_ptr(s)
+        .to_str()
+        .context("string is not UTF-8")
+        .map_err(Error::Other)
+}
+
+unsafe fn ref_arg<'a, T>(p: *const T) -> Result<&'a T, Error> {
+    p.as_ref()
+        .ok_or_else(|| Error::Other(anyhow!("unexpected null pointer")))
+}
+
+unsafe fn mut_arg<'a, T>(p: *mut T) -> Result<&'a mut T, Error> {
+    p.as_mut()
+        .ok_or_else(|| Error::Other(anyhow!("unexpected null pointer")))
+}
+
+/// Hands `s` over to the caller through `out`, or NULL if `s` is `None`.
+unsafe fn set_out_str(out: *mut *mut c_char, s: Option<String>) -> Result<(), Error> {
+    let out = mut_arg(out)?;
+    *out = match s {
+        Some(s) => CString::new(s)
+            .context("string contains NUL")
+            .map_err(Error::Other)?
+            .into_raw(),
+        None => ptr::null_mut(),
+    };
+    Ok(())
+}
+
+/// Returns the message of the last error on this thread, or NULL if the
+/// last call succeeded. It is valid until the next call on this thread.
+#[no_mangle]
+pub extern "C" fn riichi_last_error() -> *const c_char {
+    LAST_ERROR.with(|e| e.borrow().as_ref().map_or(ptr::null(), |s| s.as_ptr()))
+}
+
+/// # Safety
+///
+/// `s` must be NULL or a string returned by this library, which is not used
+/// afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn riichi_string_free(s: *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s));
+    }
+}
+
+/// Creates a state of `player_id` under Tenhou's rule into `*out`.
+///
+/// # Safety
+///
+/// `out` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn riichi_state_new(player_id: u8, out: *mut *mut PlayerState) -> c_int {
+    ffi_call(|| {
+        let out = mut_arg(out)?;
+        if player_id >= 4 {
+            return Err(Error::Other(anyhow!("{player_id} is not in range [0, 3]")));
+        }
+        *out = Box::into_raw(Box::new(PlayerState::new(player_id)));
+        Ok(())
+    })
+}
+
+/// # Safety
+///
+/// `state` must be NULL or created by `riichi_state_new`, and not used
+/// afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn riichi_state_free(state: *mut PlayerState) {
+    if !state.is_null() {
+        drop(Box::from_raw(state));
+    }
+}
+
+/// Updates `state` with one mjai event, and writes the resulting
+/// `ActionCandidate` in JSON into `*candidates`.
+///
+/// # Safety
+///
+/// `state` must be created by `riichi_state_new`, `mjai_json` must be a NUL
+/// terminated string, and `candidates` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn riichi_state_update(
+    state: *mut PlayerState,
+    mjai_json: *const c_char,
+    candidates: *mut *mut c_char,
+) -> c_int {
+    ffi_call(|| {
+        let state = mut_arg(state)?;
+        let event = json::from_str(str_arg(mjai_json)?).map_err(Error::Parse)?;
+        let cans = state.update(&event).map_err(Error::InvalidEvent)?;
+        let cans = json::to_string(&cans).map_err(|err| Error::Other(err.into()))?;
+        set_out_str(candidates, Some(cans))
+    })
+}
+
+/// Checks whether the mjai event is a valid reaction to the current state,
+/// returning `RIICHI_ERR_ILLEGAL_ACTION` if it is not.
+///
+/// # Safety
+///
+/// `state` must be created by `riichi_state_new`, and `mjai_json` must be a
+/// NUL terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn riichi_state_validate_reaction(
+    state: *const PlayerState,
+    mjai_json: *const c_char,
+) -> c_int {
+    ffi_call(|| {
+        let state = ref_arg(state)?;
+        let action = json::from_str(str_arg(mjai_json)?).map_err(Error::Parse)?;
+        Ok(state.validate_reaction(&action)?)
+    })
+}
+
+/// Creates a bot of `player_id` into `*out`, driven by `agent`, which is one
+/// of `rule_based` and `tsumogiri`.
+///
+/// # Safety
+///
+/// `agent` must be a NUL terminated string, and `out` must be valid for
+/// writes.
+#[no_mangle]
+pub unsafe extern "C" fn riichi_bot_new(
+    player_id: u8,
+    agent: *const c_char,
+    out: *mut *mut Bot,
+) -> c_int {
+    ffi_call(|| {
+        let out = mut_arg(out)?;
+        let agent = str_arg(agent)?;
+        let new_agent = || -> anyhow::Result<Box<dyn BatchAgent + Send>> {
+            ensure!(player_id < 4, "{player_id} is not in range [0, 3]");
+            let agent: Box<dyn BatchAgent + Send> = match agent {
+                "rule_based" => Box::new(RuleBased::new_batched(&[player_id])?),
+                "tsumogiri" => Box::new(Tsumogiri::new_batched(&[player_id])?),
+                _ => bail!("unknown agent {agent}"),
+            };
+            Ok(agent)
+        };
+        let agent = new_agent().map_err(Error::Other)?;
+        *out = Box::into_raw(Box::new(Bot::new(agent, player_id)));
+        Ok(())
+    })
+}
+
+/// Creates a bot of `player_id` into `*out`, whose engine is served by the
+/// gRPC endpoint, such as `http://10.0.0.2:50051`.
+///
+/// # Safety
+///
+/// `endpoint` must be a NUL terminated string, and `out` must be valid for
+/// writes.
+#[cfg(feature = "grpc")]
+#[no_mangle]
+pub unsafe extern "C" fn riichi_bot_connect(
+    player_id: u8,
+    endpoint: *const c_char,
+    out: *mut *mut Bot,
+) -> c_int {
+    use crate::agent::GrpcBatchAgent;
+
+    ffi_call(|| {
+        let out = mut_arg(out)?;
+        let endpoint = str_arg(endpoint)?;
+        if player_id >= 4 {
+            return Err(Error::Other(anyhow!("{player_id} is not in range [0, 3]")));
+        }
+        let agent = GrpcBatchAgent::connect(endpoint, &[player_id]).map_err(Error::Engine)?;
+        *out = Box::into_raw(Box::new(Bot::new(Box::new(agent), player_id)));
+        Ok(())
+    })
+}
+
+/// # Safety
+///
+/// `bot` must be NULL or created by this library, and not used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn riichi_bot_free(bot: *mut Bot) {
+    if !bot.is_null() {
+        drop(Box::from_raw(bot));
+    }
+}
+
+/// Feeds one mjai event to `bot`, and writes its reaction into `*reaction`,
+/// or NULL if it does not react, see `Bot::react`.
+///
+/// # Safety
+///
+/// `bot` must be created by this library, `line` must be a NUL terminated
+/// string, and `reaction` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn riichi_bot_react(
+    bot: *mut Bot,
+    line: *const c_char,
+    can_act: bool,
+    reaction: *mut *mut c_char,
+) -> c_int {
+    ffi_call(|| {
+        let bot = mut_arg(bot)?;
+        let ret = bot.react(str_arg(line)?, can_act)?;
+        set_out_str(reaction, ret)
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn c(s: &str) -> CString {
+        CString::new(s).unwrap()
+    }
+
+    unsafe fn take(s: *mut c_char) -> String {
+        let ret = CStr::from_ptr(s).to_str().unwrap().to_owned();
+        riichi_string_free(s);
+        ret
+    }
+
+    const LINES: &str = r#"
+        {"type":"start_game","names":["a","b","c","d"]}
+        {"type":"start_kyoku","bakaze":"E","dora_marker":"1m","kyoku":1,"honba":0,"kyotaku":0,"oya":0,"scores":[25000,25000,25000,25000],"tehais":[["1m","2m","3m","4p","5p","6p","7s","8s","9s","E","E","S","S"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"]]}
+        {"type":"tsumo","actor":0,"pai":"N"}
+    "#;
+
+    #[test]
+    fn state() {
+        unsafe {
+            let mut state = ptr::null_mut();
+            assert_eq!(riichi_state_new(4, &raw mut state), RIICHI_ERR_OTHER);
+            assert!(!riichi_last_error().is_null());
+            assert_eq!(riichi_state_new(0, &raw mut state), RIICHI_OK);
+            assert!(riichi_last_error().is_null());
+
+            let mut cans = ptr::null_mut();
+            let mut value = json::Value::Null;
+            for line in LINES.trim().lines() {
+                let line = c(line.trim());
+                assert_eq!(
+                    riichi_state_update(state, line.as_ptr(), &raw mut cans),
+                    RIICHI_OK
+                );
+                value = json::from_str(&take(cans)).unwrap();
+            }
+            assert_eq!(value["can_discard"], true);
+            assert_eq!(value["can_riichi"], true);
+
+            let dahai = c(r#"{"type":"dahai","actor":0,"pai":"N","tsumogiri":true}"#);
+            assert_eq!(
+                riichi_state_validate_reaction(state, dahai.as_ptr()),
+                RIICHI_OK,
+            );
+            let dahai = c(r#"{"type":"dahai","actor":0,"pai":"C","tsumogiri":false}"#);
+            assert_eq!(
+                riichi_state_validate_reaction(state, dahai.as_ptr()),
+                RIICHI_ERR_ILLEGAL_ACTION,
+            );
+            let msg = CStr::from_ptr(riichi_last_error()).to_str().unwrap();
+            assert_eq!(msg, "illegal action: C is not in hand");
+
+            let broken = c("{");
+            assert_eq!(
+                riichi_state_update(state, broken.as_ptr(), &raw mut cans),
+                RIICHI_ERR_PARSE,
+            );
+            assert_eq!(
+                riichi_state_update(state, ptr::null(), &raw mut cans),
+                RIICHI_ERR_OTHER,
+            );
+
+            riichi_state_free(state);
+        }
+    }
+
+    #[test]
+    fn bot() {
+        unsafe {
+            let mut bot = ptr::null_mut();
+            let agent = c("random");
+            assert_eq!(
+                riichi_bot_new(0, agent.as_ptr(), &raw mut bot),
+                RIICHI_ERR_OTHER
+            );
+            let agent = c("tsumogiri");
+            assert_eq!(riichi_bot_new(0, agent.as_ptr(), &raw mut bot), RIICHI_OK);
+
+            let mut reaction = ptr::null_mut();
+            for line in LINES.trim().lines() {
+                let line = c(line.trim());
+                assert_eq!(
+                    riichi_bot_react(bot, line.as_ptr(), true, &raw mut reaction),
+                    RIICHI_OK,
+                );
+            }
+            let ev: json::Value = json::from_str(&take(reaction)).unwrap();
+            assert_eq!(ev["type"], "dahai");
+            assert_eq!(ev["pai"], "N");
+
+            let line = c(r#"{"type":"dahai","actor":0,"pai":"N","tsumogiri":true}"#);
+            assert_eq!(
+                riichi_bot_react(bot, line.as_ptr(), true, &raw mut reaction),
+                RIICHI_OK,
+            );
+            assert!(reaction.is_null());
+
+            riichi_bot_free(bot);
+        }
+    }
+
+    #[cfg(feature = "grpc")]
+    #[test]
+    fn bot_connect() {
+        unsafe {
+            let mut bot = ptr::null_mut();
+            let endpoint = c("http://127.0.0.1:50051");
+            assert_eq!(
+                riichi_bot_connect(4, endpoint.as_ptr(), &raw mut bot),
+                RIICHI_ERR_OTHER
+            );
+            assert!(bot.is_null());
+        }
+    }
+}
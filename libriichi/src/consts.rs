@@ -1,9 +1,32 @@
+#[cfg(feature = "python")]
 use crate::py_helper::add_submodule;
 
+#[cfg(feature = "python")]
 use pyo3::prelude::*;
-use static_assertions::const_assert;
+use riichi_macros::{pyclass, pymethods};
+use static_assertions::{const_assert, const_assert_eq};
 
+/// Shape of the obs of `ObsVersion::V1`.
 pub const OBS_SHAPE: (usize, usize) = (938, 34);
+/// Shape of the obs of `ObsVersion::V2`, which appends
+/// `OBS_V2_EXTRA_CHANNELS` to `OBS_SHAPE`.
+pub const OBS_SHAPE_V2: (usize, usize) = (OBS_SHAPE.0 + OBS_V2_EXTRA_CHANNELS, 34);
+/// Discard timing, tedashi and tsumogiri of each player, plus dora and its
+/// neighbours at distance 1 and 2.
+pub const OBS_V2_EXTRA_CHANNELS: usize = 4 * 3 + 3;
+/// Shape of the obs of `ObsVersion::V3`, which appends
+/// `OBS_V3_EXTRA_CHANNELS` to `OBS_SHAPE_V2`.
+pub const OBS_SHAPE_V3: (usize, usize) = (OBS_SHAPE_V2.0 + OBS_V3_EXTRA_CHANNELS, 34);
+/// Tenpai, tile-hold and wait probabilities of each opponent estimated by
+/// `state::BaselineOpponentModel`, plus whether the opponent has declared
+/// open riichi.
+pub const OBS_V3_EXTRA_CHANNELS: usize = 3 * 4;
+/// Shape of the obs of `ObsVersion::V4`, which appends
+/// `OBS_V4_EXTRA_CHANNELS` to `OBS_SHAPE_V3`.
+pub const OBS_SHAPE_V4: (usize, usize) = (OBS_SHAPE_V3.0 + OBS_V4_EXTRA_CHANNELS, 34);
+/// The riichi tile of each opponent, its discards before it, and the tiles it
+/// called.
+pub const OBS_V4_EXTRA_CHANNELS: usize = 3 * 3;
 pub const ORACLE_OBS_SHAPE: (usize, usize) = (211, 34);
 pub const ACTION_SPACE: usize = 37 // discard | kan (choice)
                               + 1  // riichi
@@ -16,13 +39,73 @@ pub const ACTION_SPACE: usize = 37 // discard | kan (choice)
                                    // = 46
 pub const GRP_SIZE: usize = 7;
 
+/// Version of the obs encoding, so that a model keeps the encoding it was
+/// trained with while newer ones are added.
+#[pyclass]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum ObsVersion {
+    #[default]
+    V1,
+    V2,
+    V3,
+    V4,
+}
+
+#[pymethods]
+impl ObsVersion {
+    #[pyo3(name = "obs_shape")]
+    #[pyo3(text_signature = "($self, /)")]
+    // pyo3 does not take `self` by value.
+    #[allow(clippy::trivially_copy_pass_by_ref)]
+    const fn obs_shape_py(&self) -> (usize, usize) {
+        self.obs_shape()
+    }
+}
+
+impl ObsVersion {
+    #[must_use]
+    pub const fn obs_shape(self) -> (usize, usize) {
+        match self {
+            Self::V1 => OBS_SHAPE,
+            Self::V2 => OBS_SHAPE_V2,
+            Self::V3 => OBS_SHAPE_V3,
+            Self::V4 => OBS_SHAPE_V4,
+        }
+    }
+}
+
+/// Rinshan tiles plus dora and ura dora indicators.
+pub const DEAD_WALL_SIZE: u8 = 14;
+/// Initial value of `tiles_left` in a 4-player game.
+pub const TILES_LEFT_AT_START: u8 = live_wall_size(4);
+
+/// Returns the number of tiles that can be drawn in a kyoku, rinshan draws
+/// included.
+///
+/// The dead wall is kept at a fixed size by moving the last tile of the live
+/// wall into it after every rinshan draw, so a rinshan draw consumes exactly
+/// one tile of the live wall just like a normal draw, regardless of how many
+/// kans there are.
+pub const fn live_wall_size(players: u8) -> u8 {
+    // Sanma removes 2m-8m.
+    let total = if players == 3 { 108 } else { 136 };
+    total - players * 13 - DEAD_WALL_SIZE
+}
+
 const_assert!(ACTION_SPACE <= u64::BITS as usize);
+const_assert_eq!(TILES_LEFT_AT_START, 70);
+const_assert_eq!(live_wall_size(3), 55);
 
+#[cfg(feature = "python")]
 pub(crate) fn register_module(py: Python<'_>, prefix: &str, super_mod: &PyModule) -> PyResult<()> {
     let m = PyModule::new(py, "consts")?;
     m.add("OBS_SHAPE", OBS_SHAPE)?;
+    m.add("OBS_SHAPE_V2", OBS_SHAPE_V2)?;
+    m.add("OBS_SHAPE_V3", OBS_SHAPE_V3)?;
+    m.add("OBS_SHAPE_V4", OBS_SHAPE_V4)?;
     m.add("ORACLE_OBS_SHAPE", ORACLE_OBS_SHAPE)?;
     m.add("ACTION_SPACE", ACTION_SPACE)?;
     m.add("GRP_SIZE", GRP_SIZE)?;
+    m.add_class::<ObsVersion>()?;
     add_submodule(py, prefix, super_mod, m)
 }
@@ -0,0 +1,662 @@
+//! Converter for Majsoul game records.
+//!
+//! The input is the JSON dump of a decoded record, in which each action is
+//! wrapped as `{"name": "RecordDealTile", "data": {...}}` with the original
+//! protobuf field names. Fields holding default values may be omitted, as
+//! protobuf's JSON mapping does.
+use crate::mjai::{Event, RyukyokuReason};
+use crate::tile::Tile;
+use std::collections::HashMap;
+
+use anyhow::{bail, ensure, Context, Result};
+use serde::Deserialize;
+use tinyvec::ArrayVec;
+
+#[derive(Debug, Deserialize)]
+pub struct GameRecord {
+    #[serde(default)]
+    pub head: Head,
+    pub records: Vec<Record>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct Head {
+    #[serde(default)]
+    pub accounts: Vec<Account>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Account {
+    #[serde(default)]
+    pub seat: u8,
+    #[serde(default)]
+    pub nickname: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "name", content = "data")]
+pub enum Record {
+    RecordNewRound(NewRound),
+    RecordDealTile(DealTile),
+    RecordDiscardTile(DiscardTile),
+    RecordChiPengGang(ChiPengGang),
+    RecordAnGangAddGang(AnGangAddGang),
+    RecordBaBei(BaBei),
+    RecordHule(Hule),
+    RecordNoTile(NoTile),
+    RecordLiuJu(LiuJu),
+    #[serde(other)]
+    Unknown,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct NewRound {
+    #[serde(default)]
+    pub chang: u8,
+    #[serde(default)]
+    pub ju: u8,
+    #[serde(default)]
+    pub ben: u8,
+    #[serde(default)]
+    pub liqibang: u8,
+    pub scores: [i32; 4],
+    pub tiles0: Vec<String>,
+    pub tiles1: Vec<String>,
+    pub tiles2: Vec<String>,
+    pub tiles3: Vec<String>,
+    /// Only in records made before multiple dora indicators were recorded at
+    /// new round.
+    #[serde(default)]
+    pub dora: Option<String>,
+    #[serde(default)]
+    pub doras: Vec<String>,
+}
+
+/// Set on the first action after a riichi declaration passes.
+#[derive(Debug, Deserialize)]
+pub struct Liqi {
+    #[serde(default)]
+    pub seat: u8,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DealTile {
+    #[serde(default)]
+    pub seat: u8,
+    pub tile: String,
+    #[serde(default)]
+    pub doras: Vec<String>,
+    #[serde(default)]
+    pub liqi: Option<Liqi>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DiscardTile {
+    #[serde(default)]
+    pub seat: u8,
+    pub tile: String,
+    #[serde(default)]
+    pub is_liqi: bool,
+    #[serde(default)]
+    pub is_wliqi: bool,
+    #[serde(default)]
+    pub moqie: bool,
+    #[serde(default)]
+    pub doras: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ChiPengGang {
+    #[serde(default)]
+    pub seat: u8,
+    /// 0 for chi, 1 for pon and 2 for daiminkan.
+    #[serde(default, rename = "type")]
+    pub ty: u8,
+    pub tiles: Vec<String>,
+    pub froms: Vec<u8>,
+    #[serde(default)]
+    pub liqi: Option<Liqi>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AnGangAddGang {
+    #[serde(default)]
+    pub seat: u8,
+    /// 2 for kakan and 3 for ankan.
+    #[serde(default, rename = "type")]
+    pub ty: u8,
+    pub tiles: String,
+    #[serde(default)]
+    pub doras: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BaBei {
+    #[serde(default)]
+    pub seat: u8,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Hule {
+    pub hules: Vec<HuleInfo>,
+    pub delta_scores: [i32; 4],
+}
+
+#[derive(Debug, Deserialize)]
+pub struct HuleInfo {
+    #[serde(default)]
+    pub seat: u8,
+    #[serde(default)]
+    pub zimo: bool,
+    #[serde(default)]
+    pub li_doras: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct NoTile {
+    #[serde(default)]
+    pub scores: Vec<NoTileScore>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct NoTileScore {
+    #[serde(default)]
+    pub delta_scores: Vec<i32>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LiuJu {
+    /// 1 to 5 for 九種九牌, 四風連打, 四槓散了, 四家立直 and 三家和了.
+    #[serde(default, rename = "type")]
+    pub kind: u8,
+}
+
+/// Parses a Majsoul game record dump into a full mjai log.
+///
+/// Like `tenhou::parse_mjlog`, the returned log is omniscient.
+pub fn parse_record(raw_record: &str) -> Result<Vec<Event>> {
+    let record: GameRecord = serde_json::from_str(raw_record).context("invalid record")?;
+    convert(&record)
+}
+
+pub fn convert(record: &GameRecord) -> Result<Vec<Event>> {
+    let mut names: [String; 4] = Default::default();
+    for account in &record.head.accounts {
+        let name = names
+            .get_mut(account.seat as usize)
+            .with_context(|| format!("invalid seat {}", account.seat))?;
+        name.clone_from(&account.nickname);
+    }
+
+    let mut converter = Converter {
+        events: vec![Event::StartGame {
+            names,
+            seed: None,
+            meta: None,
+        }],
+        ..Default::default()
+    };
+    for (i, rec) in record.records.iter().enumerate() {
+        converter
+            .feed(rec)
+            .with_context(|| format!("failed to convert record #{i}: {rec:?}"))?;
+    }
+    converter.finish()
+}
+
+#[derive(Default)]
+struct Converter {
+    events: Vec<Event>,
+    in_kyoku: bool,
+    dora_count: usize,
+    tehais: [Vec<Tile>; 4],
+    /// Pons of each player, keyed by deaka'd tile, kept for kakan.
+    pons: [HashMap<Tile, [Tile; 3]>; 4],
+}
+
+impl Converter {
+    fn feed(&mut self, rec: &Record) -> Result<()> {
+        match rec {
+            Record::RecordNewRound(r) => self.start_kyoku(r)?,
+            Record::RecordDealTile(r) => {
+                self.accept_reach(r.liqi.as_ref());
+                let pai = tile(&r.tile)?;
+                self.tehais[r.seat as usize].push(pai);
+                self.events.push(Event::Tsumo { actor: r.seat, pai });
+                self.add_doras(&r.doras)?;
+            }
+            Record::RecordDiscardTile(r) => {
+                if r.is_liqi || r.is_wliqi {
+                    self.events.push(Event::Reach {
+                        actor: r.seat,
+                        open: false,
+                    });
+                }
+                let pai = tile(&r.tile)?;
+                self.take_from_tehai(r.seat, &[pai])?;
+                self.events.push(Event::Dahai {
+                    actor: r.seat,
+                    pai,
+                    tsumogiri: r.moqie,
+                });
+                self.add_doras(&r.doras)?;
+            }
+            Record::RecordChiPengGang(r) => {
+                self.accept_reach(r.liqi.as_ref());
+                self.call(r)?;
+            }
+            Record::RecordAnGangAddGang(r) => {
+                let actor = r.seat;
+                let pai = tile(&r.tiles)?;
+                match r.ty {
+                    2 => {
+                        self.take_from_tehai(actor, &[pai])?;
+                        let consumed = self.pons[actor as usize]
+                            .remove(&pai.deaka())
+                            .context("kakan without pon")?;
+                        self.events.push(Event::Kakan {
+                            actor,
+                            pai,
+                            consumed,
+                        });
+                    }
+                    3 => {
+                        let tehai = &mut self.tehais[actor as usize];
+                        let mut consumed = ArrayVec::<[Tile; 4]>::new();
+                        tehai.retain(|&t| {
+                            if t.deaka() == pai.deaka() && consumed.len() < 4 {
+                                consumed.push(t);
+                                false
+                            } else {
+                                true
+                            }
+                        });
+                        ensure!(consumed.len() == 4, "not enough tiles for ankan");
+                        self.events.push(Event::Ankan {
+                            actor,
+                            consumed: consumed.into_inner(),
+                        });
+                    }
+                    ty => bail!("invalid kan type {ty}"),
+                }
+                self.add_doras(&r.doras)?;
+            }
+            Record::RecordBaBei(_) => bail!("sanma is not supported yet"),
+            Record::RecordHule(r) => self.hora(r)?,
+            Record::RecordNoTile(r) => {
+                let mut deltas = [0; 4];
+                for s in &r.scores {
+                    for (d, &v) in deltas.iter_mut().zip(&s.delta_scores) {
+                        *d += v;
+                    }
+                }
+                self.events.push(Event::Ryukyoku {
+                    deltas: Some(deltas),
+                    reason: Some(RyukyokuReason::Fanpai),
+                });
+            }
+            Record::RecordLiuJu(r) => {
+                let reason = match r.kind {
+                    1 => Some(RyukyokuReason::Kyushukyuhai),
+                    2 => Some(RyukyokuReason::Suufonrenta),
+                    3 => Some(RyukyokuReason::Suukaikan),
+                    4 => Some(RyukyokuReason::Suuchariichi),
+                    5 => Some(RyukyokuReason::Sanchaho),
+                    _ => None,
+                };
+                self.events.push(Event::Ryukyoku {
+                    deltas: Some([0; 4]),
+                    reason,
+                });
+            }
+            Record::Unknown => (),
+        }
+        Ok(())
+    }
+
+    fn start_kyoku(&mut self, r: &NewRound) -> Result<()> {
+        if self.in_kyoku {
+            self.events.push(Event::EndKyoku);
+        }
+        self.in_kyoku = true;
+        self.pons = Default::default();
+
+        ensure!(
+            r.chang < 4 && r.ju < 4,
+            "invalid round {}-{}",
+            r.chang,
+            r.ju
+        );
+        let oya = r.ju;
+        let dora_marker = match (&r.dora, r.doras.first()) {
+            (_, Some(d)) | (Some(d), None) => tile(d)?,
+            (None, None) => bail!("missing dora"),
+        };
+        self.dora_count = 1;
+
+        // The oya is dealt 14 tiles, the last of which is the first tsumo.
+        let mut first_tsumo = None;
+        let mut tehais = [[Tile::default(); 13]; 4];
+        for (i, tiles) in [&r.tiles0, &r.tiles1, &r.tiles2, &r.tiles3]
+            .into_iter()
+            .enumerate()
+        {
+            let expected_len = if i as u8 == oya { 14 } else { 13 };
+            ensure!(tiles.len() == expected_len, "invalid tiles{i}");
+            let tiles = tiles.iter().map(|t| tile(t)).collect::<Result<Vec<_>>>()?;
+            tehais[i].copy_from_slice(&tiles[..13]);
+            if tiles.len() == 14 {
+                first_tsumo = Some(tiles[13]);
+            }
+            self.tehais[i] = tiles;
+        }
+
+        self.events.push(Event::StartKyoku {
+            bakaze: Tile::try_from(27 + r.chang)?,
+            dora_marker,
+            kyoku: r.ju + 1,
+            honba: r.ben,
+            kyotaku: r.liqibang,
+            oya,
+            scores: r.scores,
+            tehais,
+        });
+        if let Some(pai) = first_tsumo {
+            self.events.push(Event::Tsumo { actor: oya, pai });
+        }
+        Ok(())
+    }
+
+    fn call(&mut self, r: &ChiPengGang) -> Result<()> {
+        let actor = r.seat;
+        ensure!(r.tiles.len() == r.froms.len(), "tiles and froms mismatch");
+        let called_idx = r
+            .froms
+            .iter()
+            .position(|&f| f != actor)
+            .context("no called tile")?;
+        let target = r.froms[called_idx];
+        let pai = tile(&r.tiles[called_idx])?;
+        let consumed = r
+            .tiles
+            .iter()
+            .enumerate()
+            .filter(|&(i, _)| i != called_idx)
+            .map(|(_, t)| tile(t))
+            .collect::<Result<Vec<_>>>()?;
+        self.take_from_tehai(actor, &consumed)?;
+
+        let ev = match (r.ty, consumed.as_slice()) {
+            (0, &[a, b]) => Event::Chi {
+                actor,
+                target,
+                pai,
+                consumed: [a, b],
+            },
+            (1, &[a, b]) => {
+                self.pons[actor as usize].insert(pai.deaka(), [pai, a, b]);
+                Event::Pon {
+                    actor,
+                    target,
+                    pai,
+                    consumed: [a, b],
+                }
+            }
+            (2, &[a, b, c]) => Event::Daiminkan {
+                actor,
+                target,
+                pai,
+                consumed: [a, b, c],
+            },
+            (ty, _) => bail!("invalid call type {ty} with {} tiles", r.tiles.len()),
+        };
+        self.events.push(ev);
+        Ok(())
+    }
+
+    fn hora(&mut self, r: &Hule) -> Result<()> {
+        let winners: Vec<_> = r.hules.iter().map(|h| h.seat).collect();
+        ensure!(!winners.is_empty(), "no winner");
+
+        let target = if r.hules[0].zimo {
+            winners[0]
+        } else {
+            // The deal-in player is the only one who loses points. Riichi
+            // sticks of this kyoku have already been paid on acceptance.
+            (0..4)
+                .find(|&i| r.delta_scores[i as usize] < 0)
+                .context("no deal-in player")?
+        };
+
+        // `delta_scores` is the sum for all the winners. Split it per winner,
+        // giving the kyotakus to the first one, who is the closest to the
+        // deal-in player.
+        let mut kyotaku_gain = r.delta_scores[target as usize]
+            + winners
+                .iter()
+                .map(|&w| r.delta_scores[w as usize])
+                .sum::<i32>();
+        for h in &r.hules {
+            let deltas = if h.zimo {
+                r.delta_scores
+            } else {
+                let mut deltas = [0; 4];
+                let gain = r.delta_scores[h.seat as usize];
+                deltas[h.seat as usize] = gain;
+                deltas[target as usize] = kyotaku_gain - gain;
+                kyotaku_gain = 0;
+                deltas
+            };
+            let ura_markers = if h.li_doras.is_empty() {
+                None
+            } else {
+                Some(h.li_doras.iter().map(|t| tile(t)).collect::<Result<_>>()?)
+            };
+            self.events.push(Event::Hora {
+                actor: h.seat,
+                target,
+                deltas: Some(deltas),
+                ura_markers,
+            });
+        }
+        Ok(())
+    }
+
+    fn accept_reach(&mut self, liqi: Option<&Liqi>) {
+        if let Some(liqi) = liqi {
+            self.events.push(Event::ReachAccepted { actor: liqi.seat });
+        }
+    }
+
+    fn add_doras(&mut self, doras: &[String]) -> Result<()> {
+        for d in doras.iter().skip(self.dora_count) {
+            self.events.push(Event::Dora {
+                dora_marker: tile(d)?,
+            });
+        }
+        self.dora_count = self.dora_count.max(doras.len());
+        Ok(())
+    }
+
+    fn take_from_tehai(&mut self, actor: u8, tiles: &[Tile]) -> Result<()> {
+        let tehai = &mut self.tehais[actor as usize];
+        for &t in tiles {
+            let idx = tehai
+                .iter()
+                .position(|&h| h == t)
+                .with_context(|| format!("{t} is not in the tehai of {actor}"))?;
+            tehai.swap_remove(idx);
+        }
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<Vec<Event>> {
+        ensure!(self.in_kyoku, "no kyoku in the record");
+        self.events.push(Event::EndKyoku);
+        self.events.push(Event::EndGame);
+        Ok(self.events)
+    }
+}
+
+/// Converts Majsoul tile notations like `3m`, `0p` (aka) and `5z` into
+/// `Tile`.
+fn tile(s: &str) -> Result<Tile> {
+    let &[num, suit] = s.as_bytes() else {
+        bail!("invalid tile {s:?}");
+    };
+    ensure!(num.is_ascii_digit(), "invalid tile {s:?}");
+    let num = num - b'0';
+    let id = match (suit, num) {
+        (b'm', 0) => 34,
+        (b'p', 0) => 35,
+        (b's', 0) => 36,
+        (b'm', 1..=9) => num - 1,
+        (b'p', 1..=9) => 9 + num - 1,
+        (b's', 1..=9) => 18 + num - 1,
+        (b'z', 1..=7) => 27 + num - 1,
+        _ => bail!("invalid tile {s:?}"),
+    };
+    Ok(Tile::try_from(id)?)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::state::PlayerState;
+
+    use serde_json as json;
+
+    fn events_from_lines(lines: &str) -> Vec<Event> {
+        lines
+            .trim()
+            .lines()
+            .map(|l| json::from_str(l).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn record() {
+        let raw = r#"{
+            "head": {"accounts": [
+                {"nickname": "A"},
+                {"seat": 1, "nickname": "B"},
+                {"seat": 2, "nickname": "C"},
+                {"seat": 3, "nickname": "D"}
+            ]},
+            "records": [
+                {"name": "RecordNewRound", "data": {
+                    "chang": 1, "ju": 1, "ben": 1, "liqibang": 1,
+                    "scores": [25000, 24000, 25000, 25000],
+                    "doras": ["1m"],
+                    "tiles0": ["4p","6p","7p","8p","1s","2s","3s","6s","7s","8s","1z","2z","3z"],
+                    "tiles1": ["4z","1m","2m","3m","4m","0m","6m","7m","8m","9m","2p","3p","4p","9p"],
+                    "tiles2": ["4z","4z","5z","5z","5z","6z","6z","7z","7z","1z","2z","3z","1m"],
+                    "tiles3": ["1m","2m","3m","4m","5m","6m","7m","8m","9m","1p","1p","2p","3p"]
+                }},
+                {"name": "RecordDiscardTile", "data": {"seat": 1, "tile": "4z"}},
+                {"name": "RecordChiPengGang", "data": {"seat": 2, "type": 1, "tiles": ["4z","4z","4z"], "froms": [2,2,1]}},
+                {"name": "RecordDiscardTile", "data": {"seat": 2, "tile": "1m"}},
+                {"name": "RecordDealTile", "data": {"seat": 3, "tile": "9s"}},
+                {"name": "RecordDiscardTile", "data": {"seat": 3, "tile": "9s", "is_liqi": true, "moqie": true}},
+                {"name": "RecordDealTile", "data": {"seat": 0, "tile": "4s", "liqi": {"seat": 3, "score": 24000, "liqibang": 2}}},
+                {"name": "RecordDiscardTile", "data": {"seat": 0, "tile": "4p"}},
+                {"name": "RecordHule", "data": {
+                    "hules": [{"seat": 3, "li_doras": ["2m"]}],
+                    "delta_scores": [-4200, 0, 0, 6200]
+                }},
+                {"name": "RecordNewRound", "data": {
+                    "chang": 1, "ju": 2, "ben": 0, "liqibang": 0,
+                    "scores": [20800, 24000, 25000, 30200],
+                    "doras": ["0p"],
+                    "tiles0": ["2p","2p","2p","2p","3p","3p","3p","3p","4p","4p","4p","4p","5p"],
+                    "tiles1": ["5p","5p","6p","6p","6p","6p","7p","7p","7p","7p","8p","8p","8p"],
+                    "tiles2": ["1z","1z","1z","1m","2m","3m","4m","5m","6m","7m","8m","9m","1p","1z"],
+                    "tiles3": ["8p","9p","9p","9p","9p","1s","1s","1s","1s","2s","2s","2s","2s"]
+                }},
+                {"name": "RecordAnGangAddGang", "data": {"seat": 2, "type": 3, "tiles": "1z", "doras": ["0p","2z"]}},
+                {"name": "RecordDealTile", "data": {"seat": 2, "tile": "7z"}},
+                {"name": "RecordDiscardTile", "data": {"seat": 2, "tile": "7z", "moqie": true}},
+                {"name": "RecordNoTile", "data": {"scores": [{"delta_scores": [-1000, -1000, 3000, -1000]}]}}
+            ]
+        }"#;
+
+        let expected = events_from_lines(
+            r#"
+            {"type":"start_game","names":["A","B","C","D"]}
+            {"type":"start_kyoku","bakaze":"S","dora_marker":"1m","kyoku":2,"honba":1,"kyotaku":1,"oya":1,"scores":[25000,24000,25000,25000],"tehais":[["4p","6p","7p","8p","1s","2s","3s","6s","7s","8s","E","S","W"],["N","1m","2m","3m","4m","5mr","6m","7m","8m","9m","2p","3p","4p"],["N","N","P","P","P","F","F","C","C","E","S","W","1m"],["1m","2m","3m","4m","5m","6m","7m","8m","9m","1p","1p","2p","3p"]]}
+            {"type":"tsumo","actor":1,"pai":"9p"}
+            {"type":"dahai","actor":1,"pai":"N","tsumogiri":false}
+            {"type":"pon","actor":2,"target":1,"pai":"N","consumed":["N","N"]}
+            {"type":"dahai","actor":2,"pai":"1m","tsumogiri":false}
+            {"type":"tsumo","actor":3,"pai":"9s"}
+            {"type":"reach","actor":3}
+            {"type":"dahai","actor":3,"pai":"9s","tsumogiri":true}
+            {"type":"reach_accepted","actor":3}
+            {"type":"tsumo","actor":0,"pai":"4s"}
+            {"type":"dahai","actor":0,"pai":"4p","tsumogiri":false}
+            {"type":"hora","actor":3,"target":0,"deltas":[-4200,0,0,6200],"ura_markers":["2m"]}
+            {"type":"end_kyoku"}
+            {"type":"start_kyoku","bakaze":"S","dora_marker":"5pr","kyoku":3,"honba":0,"kyotaku":0,"oya":2,"scores":[20800,24000,25000,30200],"tehais":[["2p","2p","2p","2p","3p","3p","3p","3p","4p","4p","4p","4p","5p"],["5p","5p","6p","6p","6p","6p","7p","7p","7p","7p","8p","8p","8p"],["E","E","E","1m","2m","3m","4m","5m","6m","7m","8m","9m","1p"],["8p","9p","9p","9p","9p","1s","1s","1s","1s","2s","2s","2s","2s"]]}
+            {"type":"tsumo","actor":2,"pai":"E"}
+            {"type":"ankan","actor":2,"consumed":["E","E","E","E"]}
+            {"type":"dora","dora_marker":"S"}
+            {"type":"tsumo","actor":2,"pai":"C"}
+            {"type":"dahai","actor":2,"pai":"C","tsumogiri":true}
+            {"type":"ryukyoku","deltas":[-1000,-1000,3000,-1000],"reason":"fanpai"}
+            {"type":"end_kyoku"}
+            {"type":"end_game"}
+            "#,
+        );
+
+        let events = parse_record(raw).unwrap();
+        assert_eq!(events, expected);
+
+        for player_id in 0..4 {
+            let mut state = PlayerState::new(player_id);
+            for ev in &events {
+                state.update(ev).unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn double_ron() {
+        let hule = Hule {
+            hules: vec![
+                HuleInfo {
+                    seat: 2,
+                    zimo: false,
+                    li_doras: vec![],
+                },
+                HuleInfo {
+                    seat: 3,
+                    zimo: false,
+                    li_doras: vec![],
+                },
+            ],
+            // 2 wins 8000 + 300 + 1000 kyotaku, 3 wins 3900 + 300.
+            delta_scores: [0, -12500, 9300, 4200],
+        };
+        let mut conv = Converter::default();
+        conv.hora(&hule).unwrap();
+
+        let expected = events_from_lines(
+            r#"
+            {"type":"hora","actor":2,"target":1,"deltas":[0,-8300,9300,0]}
+            {"type":"hora","actor":3,"target":1,"deltas":[0,-4200,0,4200]}
+            "#,
+        );
+        assert_eq!(conv.events, expected);
+    }
+
+    #[test]
+    fn tile_notation() {
+        assert_eq!(tile("0m").unwrap(), Tile::try_from(34_u8).unwrap());
+        assert_eq!(tile("0s").unwrap(), Tile::try_from(36_u8).unwrap());
+        assert_eq!(tile("9p").unwrap(), Tile::try_from(17_u8).unwrap());
+        assert_eq!(tile("7z").unwrap(), Tile::try_from(33_u8).unwrap());
+        tile("8z").unwrap_err();
+        tile("0z").unwrap_err();
+        tile("10m").unwrap_err();
+    }
+}
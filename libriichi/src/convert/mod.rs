@@ -0,0 +1,76 @@
+//! Converters between mjai logs and other log formats.
+
+pub mod majsoul;
+pub mod tenhou;
+pub mod tenhou6;
+
+use crate::mjai::Event;
+#[cfg(feature = "python")]
+use crate::py_helper::add_submodule;
+
+use anyhow::{Context, Result};
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+use serde_json as json;
+
+/// Converts a raw tenhou.net mjlog XML into mjai events, one JSON per line,
+/// which can be fed to `GameplayLoader.load_log` directly.
+#[cfg(feature = "python")]
+#[pyfunction]
+#[pyo3(text_signature = "(raw_log, /)")]
+fn tenhou_to_mjai(raw_log: &str) -> Result<Vec<String>> {
+    tenhou::parse_mjlog(raw_log)?
+        .iter()
+        .map(|ev| Ok(json::to_string(ev)?))
+        .collect()
+}
+
+/// Converts a Majsoul game record dump in JSON into mjai events, one JSON per
+/// line.
+#[cfg(feature = "python")]
+#[pyfunction]
+#[pyo3(text_signature = "(raw_record, /)")]
+fn majsoul_to_mjai(raw_record: &str) -> Result<Vec<String>> {
+    majsoul::parse_record(raw_record)?
+        .iter()
+        .map(|ev| Ok(json::to_string(ev)?))
+        .collect()
+}
+
+/// Converts a full mjai log in JSON lines, such as the ones from the arena,
+/// into the JSON of the tenhou.net/6 log viewer.
+#[cfg(feature = "python")]
+#[pyfunction]
+#[pyo3(text_signature = "(mjai_log, /)")]
+fn mjai_to_tenhou6(mjai_log: &str) -> Result<String> {
+    let events = parse_events(mjai_log)?;
+    Ok(tenhou6::mjai_to_tenhou6(&events)?.to_string())
+}
+
+/// Converts a full mjai log in JSON lines into a URL that opens it in the
+/// tenhou.net/6 log viewer.
+#[cfg(feature = "python")]
+#[pyfunction]
+#[pyo3(text_signature = "(mjai_log, /)")]
+fn mjai_to_tenhou6_url(mjai_log: &str) -> Result<String> {
+    let events = parse_events(mjai_log)?;
+    Ok(tenhou6::tenhou6_url(&tenhou6::mjai_to_tenhou6(&events)?))
+}
+
+fn parse_events(log: &str) -> Result<Vec<Event>> {
+    log.lines()
+        .filter(|l| !l.trim().is_empty())
+        .map(json::from_str)
+        .collect::<Result<_, _>>()
+        .context("failed to parse log")
+}
+
+#[cfg(feature = "python")]
+pub(crate) fn register_module(py: Python<'_>, prefix: &str, super_mod: &PyModule) -> PyResult<()> {
+    let m = PyModule::new(py, "convert")?;
+    m.add_function(wrap_pyfunction!(tenhou_to_mjai, m)?)?;
+    m.add_function(wrap_pyfunction!(majsoul_to_mjai, m)?)?;
+    m.add_function(wrap_pyfunction!(mjai_to_tenhou6, m)?)?;
+    m.add_function(wrap_pyfunction!(mjai_to_tenhou6_url, m)?)?;
+    add_submodule(py, prefix, super_mod, m)
+}
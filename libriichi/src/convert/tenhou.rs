@@ -0,0 +1,589 @@
+//! Converter for the raw mjlog XML format of tenhou.net.
+//!
+//! The format is not documented officially. The implementation follows the
+//! logs that can be downloaded from `https://tenhou.net/0/log/?{log_id}`.
+use crate::mjai::{Event, GameMeta, Room, RyukyokuReason};
+use crate::rule::RuleSet;
+use crate::tile::Tile;
+use std::collections::HashMap;
+use std::mem;
+
+use anyhow::{bail, ensure, Context, Result};
+
+/// Bit of the `type` attribute of `GO` that indicates aka doras are disabled.
+const GO_TYPE_NO_AKA: u32 = 0x02;
+/// Bit of the `type` attribute of `GO` that indicates kuitan is disabled.
+const GO_TYPE_NO_KUITAN: u32 = 0x04;
+/// Bit of the `type` attribute of `GO` that indicates a hanchan.
+const GO_TYPE_HANCHAN: u32 = 0x08;
+/// Bit of the `type` attribute of `GO` that indicates a sanma game.
+const GO_TYPE_SANMA: u32 = 0x10;
+/// Bits of the `type` attribute of `GO` that indicate the room.
+const GO_TYPE_ROOM: u32 = 0xa0;
+
+/// Parses a raw mjlog XML into a full mjai log.
+///
+/// The returned log is omniscient, i.e. every tehai and tsumo is visible, just
+/// like the logs `GameplayLoader` and `PlayerState` consume, so that the view
+/// of any seat can be derived from it.
+pub fn parse_mjlog(raw_log: &str) -> Result<Vec<Event>> {
+    let mut converter = Converter::default();
+    for tag in Tags::new(raw_log) {
+        let tag = tag?;
+        converter
+            .feed(&tag)
+            .with_context(|| format!("failed to convert tag <{}>", tag.name))?;
+    }
+    converter.finish()
+}
+
+struct Tag<'a> {
+    name: &'a str,
+    attrs: HashMap<&'a str, &'a str>,
+}
+
+impl Tag<'_> {
+    fn attr(&self, key: &str) -> Result<&str> {
+        self.attrs
+            .get(key)
+            .copied()
+            .with_context(|| format!("missing attribute {key}"))
+    }
+
+    fn attr_num<T>(&self, key: &str) -> Result<T>
+    where
+        T: std::str::FromStr,
+        T::Err: std::error::Error + Send + Sync + 'static,
+    {
+        self.attr(key)?
+            .parse()
+            .with_context(|| format!("invalid attribute {key}"))
+    }
+
+    fn attr_list<T>(&self, key: &str) -> Result<Vec<T>>
+    where
+        T: std::str::FromStr,
+        T::Err: std::error::Error + Send + Sync + 'static,
+    {
+        let value = self.attr(key)?;
+        if value.is_empty() {
+            return Ok(vec![]);
+        }
+        value
+            .split(',')
+            .map(|v| {
+                v.parse()
+                    .with_context(|| format!("invalid attribute {key}"))
+            })
+            .collect()
+    }
+}
+
+/// A minimal tokenizer that is just enough for mjlog, which only consists of
+/// flat, attribute-only elements.
+struct Tags<'a> {
+    rest: &'a str,
+}
+
+impl<'a> Tags<'a> {
+    const fn new(raw: &'a str) -> Self {
+        Self { rest: raw }
+    }
+
+    fn parse_tag(content: &'a str) -> Result<Tag<'a>> {
+        let content = content.trim_end_matches('/').trim();
+        let (name, mut rest) = content
+            .split_once(char::is_whitespace)
+            .unwrap_or((content, ""));
+
+        let mut attrs = HashMap::new();
+        loop {
+            rest = rest.trim_start();
+            if rest.is_empty() {
+                break;
+            }
+            let (key, after) = rest
+                .split_once("=\"")
+                .with_context(|| format!("malformed attributes in <{name}>"))?;
+            let (value, after) = after
+                .split_once('"')
+                .with_context(|| format!("unterminated attribute {key} in <{name}>"))?;
+            attrs.insert(key.trim(), value);
+            rest = after;
+        }
+
+        Ok(Tag { name, attrs })
+    }
+}
+
+impl<'a> Iterator for Tags<'a> {
+    type Item = Result<Tag<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let start = self.rest.find('<')?;
+            let Some(len) = self.rest[start..].find('>') else {
+                self.rest = "";
+                return Some(Err(anyhow::anyhow!("unterminated tag")));
+            };
+            let content = &self.rest[start + 1..start + len];
+            self.rest = &self.rest[start + len + 1..];
+
+            // Skip closing tags, XML declarations and comments.
+            if content.starts_with(['/', '?', '!']) {
+                continue;
+            }
+            return Some(Self::parse_tag(content));
+        }
+    }
+}
+
+#[derive(Default)]
+struct Converter {
+    events: Vec<Event>,
+    names: Option<[String; 4]>,
+    meta: GameMeta,
+    aka_enabled: bool,
+    in_kyoku: bool,
+    /// The tile ID each player has just drawn, used to tell tsumogiri.
+    last_tsumo: [Option<u8>; 4],
+}
+
+impl Converter {
+    fn feed(&mut self, tag: &Tag<'_>) -> Result<()> {
+        match tag.name {
+            "GO" => {
+                let ty: u32 = tag.attr_num("type")?;
+                ensure!(ty & GO_TYPE_SANMA == 0, "sanma is not supported yet");
+                self.aka_enabled = ty & GO_TYPE_NO_AKA == 0;
+                self.meta.room = Some(match ty & GO_TYPE_ROOM {
+                    0x00 => Room::Ippan,
+                    0x80 => Room::Joukyuu,
+                    0x20 => Room::Tokujou,
+                    _ => Room::Houou,
+                });
+                self.meta.rule = Some(RuleSet {
+                    aka_count: if self.aka_enabled { 3 } else { 0 },
+                    kuitan: ty & GO_TYPE_NO_KUITAN == 0,
+                    tonpuusen: ty & GO_TYPE_HANCHAN == 0,
+                    ..RuleSet::tenhou()
+                });
+            }
+            "UN" => {
+                // `UN` also appears on reconnection, with only the name of the
+                // one who reconnected.
+                if self.names.is_none() && tag.attrs.contains_key("n3") {
+                    let mut names: [String; 4] = Default::default();
+                    for (i, name) in names.iter_mut().enumerate() {
+                        *name = percent_decode(tag.attr(&format!("n{i}"))?)?;
+                    }
+                    self.names = Some(names);
+
+                    if tag.attrs.contains_key("dan") {
+                        let dans: Vec<u8> = tag.attr_list("dan")?;
+                        self.meta.dans = Some(dans.try_into().ok().context("invalid dan")?);
+                    }
+                    if tag.attrs.contains_key("rate") {
+                        let rates: Vec<f64> = tag.attr_list("rate")?;
+                        let rates: [f64; 4] = rates.try_into().ok().context("invalid rate")?;
+                        self.meta.rates = Some(rates.map(|r| r.round() as u16));
+                    }
+                }
+            }
+            "INIT" => self.start_kyoku(tag)?,
+            "N" => self.naki(tag)?,
+            "REACH" => {
+                let actor = tag.attr_num("who")?;
+                let step: u8 = tag.attr_num("step")?;
+                let ev = match step {
+                    1 => Event::Reach { actor, open: false },
+                    2 => Event::ReachAccepted { actor },
+                    _ => bail!("invalid reach step {step}"),
+                };
+                self.events.push(ev);
+            }
+            "DORA" => {
+                let dora_marker = self.tile(tag.attr_num("hai")?)?;
+                self.events.push(Event::Dora { dora_marker });
+            }
+            "AGARI" => {
+                let actor = tag.attr_num("who")?;
+                let target = tag.attr_num("fromWho")?;
+                let deltas = Some(parse_deltas(tag)?);
+                let ura_markers = if tag.attrs.contains_key("doraHaiUra") {
+                    let markers = tag
+                        .attr_list("doraHaiUra")?
+                        .into_iter()
+                        .map(|id| self.tile(id))
+                        .collect::<Result<_>>()?;
+                    Some(markers)
+                } else {
+                    None
+                };
+                self.events.push(Event::Hora {
+                    actor,
+                    target,
+                    deltas,
+                    ura_markers,
+                });
+            }
+            "RYUUKYOKU" => {
+                let deltas = Some(parse_deltas(tag)?);
+                let reason = match tag.attrs.get("type").copied() {
+                    None => RyukyokuReason::Fanpai,
+                    Some("nm") => RyukyokuReason::Nagashimangan,
+                    Some("yao9") => RyukyokuReason::Kyushukyuhai,
+                    Some("kaze4") => RyukyokuReason::Suufonrenta,
+                    Some("reach4") => RyukyokuReason::Suuchariichi,
+                    Some("kan4") => RyukyokuReason::Suukaikan,
+                    Some("ron3") => RyukyokuReason::Sanchaho,
+                    Some(kind) => bail!("unknown ryukyoku type {kind}"),
+                };
+                self.events.push(Event::Ryukyoku {
+                    deltas,
+                    reason: Some(reason),
+                });
+            }
+            name => {
+                if let Some((actor, id, is_tsumo)) = parse_draw_or_discard(name) {
+                    let pai = self.tile(id)?;
+                    let actor_idx = actor as usize;
+                    if is_tsumo {
+                        self.last_tsumo[actor_idx] = Some(id);
+                        self.events.push(Event::Tsumo { actor, pai });
+                    } else {
+                        let tsumogiri = self.last_tsumo[actor_idx].take() == Some(id);
+                        self.events.push(Event::Dahai {
+                            actor,
+                            pai,
+                            tsumogiri,
+                        });
+                    }
+                }
+                // Other tags like SHUFFLE, TAIKYOKU and BYE are irrelevant.
+            }
+        }
+        Ok(())
+    }
+
+    fn start_kyoku(&mut self, tag: &Tag<'_>) -> Result<()> {
+        if self.in_kyoku {
+            self.events.push(Event::EndKyoku);
+        } else if self.events.is_empty() {
+            self.events.push(Event::StartGame {
+                names: self.names.clone().unwrap_or_default(),
+                seed: None,
+                meta: Some(self.meta.clone()),
+            });
+        }
+        self.in_kyoku = true;
+        self.last_tsumo = [None; 4];
+
+        let seed: Vec<u8> = tag.attr_list("seed")?;
+        ensure!(seed.len() == 6, "invalid seed {seed:?}");
+        let kyoku_idx = seed[0];
+        ensure!(kyoku_idx < 16, "invalid kyoku index {kyoku_idx}");
+        let bakaze = Tile::try_from(27 + kyoku_idx / 4)?;
+        let dora_marker = self.tile(seed[5])?;
+
+        let ten: Vec<i32> = tag.attr_list("ten")?;
+        let scores = ten
+            .into_iter()
+            .map(|s| s * 100)
+            .collect::<Vec<_>>()
+            .try_into()
+            .ok()
+            .context("invalid ten")?;
+
+        let mut tehais = [[Tile::default(); 13]; 4];
+        for (i, tehai) in tehais.iter_mut().enumerate() {
+            let ids: Vec<u8> = tag.attr_list(&format!("hai{i}"))?;
+            ensure!(ids.len() == 13, "invalid hai{i}");
+            for (tile, id) in tehai.iter_mut().zip(ids) {
+                *tile = self.tile(id)?;
+            }
+        }
+
+        self.events.push(Event::StartKyoku {
+            bakaze,
+            dora_marker,
+            kyoku: kyoku_idx % 4 + 1,
+            honba: seed[1],
+            kyotaku: seed[2],
+            oya: tag.attr_num("oya")?,
+            scores,
+            tehais,
+        });
+        Ok(())
+    }
+
+    /// Decodes the meld bitfield `m` of tag `N`.
+    fn naki(&mut self, tag: &Tag<'_>) -> Result<()> {
+        let actor: u8 = tag.attr_num("who")?;
+        let m: u32 = tag.attr_num("m")?;
+        let target = (actor + (m & 0b11) as u8) % 4;
+        self.last_tsumo[actor as usize] = None;
+
+        let ev = if m & 0x4 != 0 {
+            // chi
+            let pattern = m >> 10;
+            let called = (pattern % 3) as usize;
+            let kind = pattern / 3;
+            let base = kind / 7 * 9 + kind % 7;
+            let offsets = [(m >> 3) & 0b11, (m >> 5) & 0b11, (m >> 7) & 0b11];
+            let mut ids = [0; 3];
+            for (i, id) in ids.iter_mut().enumerate() {
+                *id = ((base + i as u32) * 4 + offsets[i]) as u8;
+            }
+            let (pai, consumed) = self.split_called(&ids, called)?;
+            Event::Chi {
+                actor,
+                target,
+                pai,
+                consumed,
+            }
+        } else if m & 0x18 != 0 {
+            // pon or kakan
+            let unused = (m >> 5) & 0b11;
+            let pattern = m >> 9;
+            let called = (pattern % 3) as usize;
+            let kind = pattern / 3;
+            let ids: Vec<u8> = (0..4)
+                .filter(|&i| i != unused)
+                .map(|i| (kind * 4 + i) as u8)
+                .collect();
+            if m & 0x8 != 0 {
+                let (pai, consumed) = self.split_called(&ids, called)?;
+                Event::Pon {
+                    actor,
+                    target,
+                    pai,
+                    consumed,
+                }
+            } else {
+                Event::Kakan {
+                    actor,
+                    pai: self.tile((kind * 4 + unused) as u8)?,
+                    consumed: [self.tile(ids[0])?, self.tile(ids[1])?, self.tile(ids[2])?],
+                }
+            }
+        } else if m & 0x20 != 0 {
+            bail!("nukidora is not supported yet");
+        } else {
+            // ankan or daiminkan
+            let called_id = m >> 8;
+            let kind = called_id / 4;
+            let mut ids = [0; 4];
+            for (i, id) in ids.iter_mut().enumerate() {
+                *id = (kind * 4 + i as u32) as u8;
+            }
+            if actor == target {
+                let mut consumed = [Tile::default(); 4];
+                for (tile, id) in consumed.iter_mut().zip(ids) {
+                    *tile = self.tile(id)?;
+                }
+                Event::Ankan { actor, consumed }
+            } else {
+                let (pai, consumed) = self.split_called(&ids, (called_id % 4) as usize)?;
+                Event::Daiminkan {
+                    actor,
+                    target,
+                    pai,
+                    consumed,
+                }
+            }
+        };
+
+        self.events.push(ev);
+        Ok(())
+    }
+
+    fn split_called<const N: usize>(&self, ids: &[u8], called: usize) -> Result<(Tile, [Tile; N])> {
+        let pai = self.tile(ids[called])?;
+        let mut consumed = [Tile::default(); N];
+        let others = ids
+            .iter()
+            .enumerate()
+            .filter(|&(i, _)| i != called)
+            .map(|(_, &id)| id);
+        for (tile, id) in consumed.iter_mut().zip(others) {
+            *tile = self.tile(id)?;
+        }
+        Ok((pai, consumed))
+    }
+
+    /// Converts a tile ID in `[0, 136)` into `Tile`.
+    fn tile(&self, id: u8) -> Result<Tile> {
+        ensure!(id < 136, "invalid tile ID {id}");
+        let tile = match id {
+            16 | 52 | 88 if self.aka_enabled => Tile::try_from(34 + (id - 16) / 36)?,
+            _ => Tile::try_from(id / 4)?,
+        };
+        Ok(tile)
+    }
+
+    fn finish(mut self) -> Result<Vec<Event>> {
+        ensure!(self.in_kyoku, "no kyoku in the log");
+        self.events.push(Event::EndKyoku);
+        self.events.push(Event::EndGame);
+        Ok(mem::take(&mut self.events))
+    }
+}
+
+/// Returns `(actor, tile ID, is_tsumo)` for tags like `T52` or `D52`.
+fn parse_draw_or_discard(name: &str) -> Option<(u8, u8, bool)> {
+    let mut chars = name.chars();
+    let (actor, is_tsumo) = match chars.next()? {
+        'T' => (0, true),
+        'U' => (1, true),
+        'V' => (2, true),
+        'W' => (3, true),
+        'D' => (0, false),
+        'E' => (1, false),
+        'F' => (2, false),
+        'G' => (3, false),
+        _ => return None,
+    };
+    let id = chars.as_str().parse().ok()?;
+    Some((actor, id, is_tsumo))
+}
+
+/// `sc` consists of (score, delta) pairs in the unit of 100.
+fn parse_deltas(tag: &Tag<'_>) -> Result<[i32; 4]> {
+    let sc: Vec<i32> = tag.attr_list("sc")?;
+    ensure!(sc.len() == 8, "invalid sc {sc:?}");
+    let mut deltas = [0; 4];
+    for (d, pair) in deltas.iter_mut().zip(sc.chunks_exact(2)) {
+        *d = pair[1] * 100;
+    }
+    Ok(deltas)
+}
+
+fn percent_decode(s: &str) -> Result<String> {
+    let mut bytes = Vec::with_capacity(s.len());
+    let mut iter = s.bytes();
+    while let Some(b) = iter.next() {
+        if b == b'%' {
+            let hex = [
+                iter.next().context("truncated escape")?,
+                iter.next().context("truncated escape")?,
+            ];
+            let hex = std::str::from_utf8(&hex)?;
+            bytes.push(u8::from_str_radix(hex, 16).context("invalid escape")?);
+        } else {
+            bytes.push(b);
+        }
+    }
+    Ok(String::from_utf8(bytes)?)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::state::PlayerState;
+
+    use serde_json as json;
+
+    const MJLOG: &str = r#"<mjloggm ver="2.3">
+        <SHUFFLE seed="mt19937ar-sha512-n288-base64,AAAA" ref=""/>
+        <GO type="169" lobby="0"/>
+        <UN n0="A" n1="B" n2="%E3%81%82" n3="D" dan="16,16,16,16" rate="2000.00,2000.00,2000.00,2000.00" sx="M,M,M,M"/>
+        <TAIKYOKU oya="0"/>
+        <INIT seed="0,0,0,1,2,3" ten="250,250,250,250" oya="0"
+            hai0="48,56,60,64,72,76,80,92,96,100,108,112,116"
+            hai1="122,2,6,10,14,18,22,26,30,34,42,46,50"
+            hai2="120,121,124,125,126,128,129,132,133,109,113,117,0"
+            hai3="1,5,9,13,17,21,25,29,33,37,38,41,45"/>
+        <T68/><D68/>
+        <U84/><E122/>
+        <N who="2" m="47211"/><F0/>
+        <W104/><REACH who="3" step="1"/><G104/><REACH who="3" ten="250,250,250,240" step="2"/>
+        <T85/><D48/>
+        <AGARI ba="0,1" hai="1,5,9,13,17,21,25,29,33,37,38,41,45,48" machi="48" ten="30,3900,0" yaku="1,1,24,2" doraHai="3" doraHaiUra="7" who="3" fromWho="0" sc="250,-39,250,0,250,0,240,49"/>
+        <INIT seed="1,0,0,4,5,52" ten="211,250,250,289" oya="1"
+            hai0="40,41,42,43,44,45,46,47,48,49,50,51,53"
+            hai1="108,109,110,1,5,9,13,17,21,25,29,33,37"
+            hai2="54,55,56,57,58,59,60,61,62,63,64,65,66"
+            hai3="67,68,69,70,71,72,73,74,75,76,77,78,79"/>
+        <U111/><N who="1" m="27648"/><DORA hai="112"/><U135/><E135/>
+        <RYUUKYOKU ba="0,0" sc="211,0,250,0,250,0,289,0" owari="211,-19.0,250,5.0,250,-5.0,289,39.0"/>
+        </mjloggm>"#;
+
+    #[test]
+    fn mjlog() {
+        let expected = r#"
+            {"type":"start_game","names":["A","B","あ","D"],"meta":{"dans":[16,16,16,16],"rates":[2000,2000,2000,2000],"room":"houou","rule":{"aka_count":3,"kuitan":true,"atozuke":true,"double_ron":true,"honba_per_winner":false,"kazoe_yakuman":true,"tonpuusen":false,"starting_points":25000,"goal_points":30000,"extra_kyokus":4,"nagashi_mangan":true,"rinshan_pao":false,"abortive_ryukyoku":true,"kuikae":"strict","noten_bappu":true,"keishiki_tenpai":true,"agari_yame":true,"tenpai_yame":true,"ryanhan_shibari":false,"renhou":false,"daisharin":false,"open_riichi":false,"chombo":"disabled","chombo_points":4000}}}
+            {"type":"start_kyoku","bakaze":"E","dora_marker":"1m","kyoku":1,"honba":0,"kyotaku":0,"oya":0,"scores":[25000,25000,25000,25000],"tehais":[["4p","6p","7p","8p","1s","2s","3s","6s","7s","8s","E","S","W"],["N","1m","2m","3m","4m","5m","6m","7m","8m","9m","2p","3p","4p"],["N","N","P","P","P","F","F","C","C","E","S","W","1m"],["1m","2m","3m","4m","5m","6m","7m","8m","9m","1p","1p","2p","3p"]]}
+            {"type":"tsumo","actor":0,"pai":"9p"}
+            {"type":"dahai","actor":0,"pai":"9p","tsumogiri":true}
+            {"type":"tsumo","actor":1,"pai":"4s"}
+            {"type":"dahai","actor":1,"pai":"N","tsumogiri":false}
+            {"type":"pon","actor":2,"target":1,"pai":"N","consumed":["N","N"]}
+            {"type":"dahai","actor":2,"pai":"1m","tsumogiri":false}
+            {"type":"tsumo","actor":3,"pai":"9s"}
+            {"type":"reach","actor":3}
+            {"type":"dahai","actor":3,"pai":"9s","tsumogiri":true}
+            {"type":"reach_accepted","actor":3}
+            {"type":"tsumo","actor":0,"pai":"4s"}
+            {"type":"dahai","actor":0,"pai":"4p","tsumogiri":false}
+            {"type":"hora","actor":3,"target":0,"deltas":[-3900,0,0,4900],"ura_markers":["2m"]}
+            {"type":"end_kyoku"}
+            {"type":"start_kyoku","bakaze":"E","dora_marker":"5pr","kyoku":2,"honba":0,"kyotaku":0,"oya":1,"scores":[21100,25000,25000,28900],"tehais":[["2p","2p","2p","2p","3p","3p","3p","3p","4p","4p","4p","4p","5p"],["E","E","E","1m","2m","3m","4m","5m","6m","7m","8m","9m","1p"],["5p","5p","6p","6p","6p","6p","7p","7p","7p","7p","8p","8p","8p"],["8p","9p","9p","9p","9p","1s","1s","1s","1s","2s","2s","2s","2s"]]}
+            {"type":"tsumo","actor":1,"pai":"E"}
+            {"type":"ankan","actor":1,"consumed":["E","E","E","E"]}
+            {"type":"dora","dora_marker":"S"}
+            {"type":"tsumo","actor":1,"pai":"C"}
+            {"type":"dahai","actor":1,"pai":"C","tsumogiri":true}
+            {"type":"ryukyoku","deltas":[0,0,0,0],"reason":"fanpai"}
+            {"type":"end_kyoku"}
+            {"type":"end_game"}
+        "#
+        .trim()
+        .lines()
+        .map(|l| json::from_str::<Event>(l).unwrap())
+        .collect::<Vec<_>>();
+
+        let events = parse_mjlog(MJLOG).unwrap();
+        assert_eq!(events, expected);
+
+        for player_id in 0..4 {
+            let mut state = PlayerState::new(player_id);
+            for ev in &events {
+                state.update(ev).unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn meld_decoding() {
+        let mut conv = Converter {
+            aka_enabled: true,
+            ..Default::default()
+        };
+
+        let melds = [
+            // 3-4-5p chi with the aka 5p, calling 3p from kamicha.
+            (1, (9 * 3) << 10 | 0x4 | 3),
+            // kakan on 5s, adding the aka 5s.
+            (0, (22 * 3) << 9 | 0x10 | 2),
+            // daiminkan on 7m from toimen.
+            (3, (6 * 4 + 1) << 8 | 2),
+        ];
+        for (who, m) in melds {
+            let raw = format!(r#"<N who="{who}" m="{m}"/>"#);
+            let tag = Tags::new(&raw).next().unwrap().unwrap();
+            conv.naki(&tag).unwrap();
+        }
+
+        let expected = r#"
+            {"type":"chi","actor":1,"target":0,"pai":"3p","consumed":["4p","5pr"]}
+            {"type":"kakan","actor":0,"pai":"5sr","consumed":["5s","5s","5s"]}
+            {"type":"daiminkan","actor":3,"target":1,"pai":"7m","consumed":["7m","7m","7m"]}
+        "#
+        .trim()
+        .lines()
+        .map(|l| json::from_str::<Event>(l).unwrap())
+        .collect::<Vec<_>>();
+        assert_eq!(conv.events, expected);
+    }
+}
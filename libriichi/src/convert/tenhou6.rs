@@ -0,0 +1,426 @@
+//! Converter from mjai logs into the JSON format of the tenhou.net/6 log
+//! viewer, so that games such as the ones from the arena can be stepped
+//! through there.
+//!
+//! The format is not documented officially either. The implementation follows
+//! the JSON the viewer itself exports. Yakus are not included in the agari
+//! results, which the viewer is fine with, but the points are.
+use crate::algo::agari::Agari;
+use crate::algo::point::Point;
+use crate::mjai::{Event, RyukyokuReason};
+use crate::rule::RuleSet;
+use crate::state::PlayerState;
+use crate::tile::Tile;
+use crate::tu8;
+use std::fmt::Write;
+
+use anyhow::{bail, ensure, Context, Result};
+use serde_json::{json, Value};
+
+const VIEWER_URL: &str = "https://tenhou.net/6/#json=";
+
+/// Converts a full mjai log, such as the ones from the arena, into the JSON
+/// of the tenhou.net/6 log viewer.
+///
+/// Every tehai and tsumo must be visible in `events`, as the agari results are
+/// recalculated from the hands.
+pub fn mjai_to_tenhou6(events: &[Event]) -> Result<Value> {
+    let mut converter = Converter::default();
+    for (i, ev) in events.iter().enumerate() {
+        converter
+            .feed(ev)
+            .with_context(|| format!("failed to convert event {i}: {ev:?}"))?;
+    }
+    ensure!(converter.kyoku.is_none(), "unterminated kyoku");
+
+    let rule = converter.rule;
+    let disp = format!(
+        "般{}{}{}",
+        if rule.tonpuusen { "東" } else { "南" },
+        if rule.kuitan { "喰" } else { "" },
+        if rule.aka_count > 0 { "赤" } else { "" },
+    );
+    Ok(json!({
+        "title": ["", ""],
+        "name": converter.names,
+        "rule": {"disp": disp, "aka": u8::from(rule.aka_count > 0)},
+        "log": converter.log,
+    }))
+}
+
+/// Returns the URL that opens `tenhou6` in the tenhou.net/6 log viewer.
+#[must_use]
+pub fn tenhou6_url(tenhou6: &Value) -> String {
+    let mut ret = VIEWER_URL.to_owned();
+    for b in tenhou6.to_string().bytes() {
+        if b.is_ascii_alphanumeric() || b"-_.!~*'()".contains(&b) {
+            ret.push(b as char);
+        } else {
+            write!(ret, "%{b:02X}").unwrap();
+        }
+    }
+    ret
+}
+
+#[derive(Default)]
+struct Converter {
+    names: [String; 4],
+    rule: RuleSet,
+    /// Omniscient states of all seats, for recalculating the agaris.
+    states: Vec<PlayerState>,
+    kyoku: Option<Kyoku>,
+    log: Vec<Value>,
+}
+
+/// A kyoku in the making.
+#[derive(Default)]
+struct Kyoku {
+    header: [u8; 3],
+    scores: [i32; 4],
+    dora_indicators: Vec<u8>,
+    ura_indicators: Vec<u8>,
+    haipais: [Vec<u8>; 4],
+    takes: [Vec<Value>; 4],
+    discards: [Vec<Value>; 4],
+    /// Pons by the seat and the deaka'd tile, so that kakans can be told in
+    /// the same layout.
+    pons: Vec<(u8, Tile, String)>,
+    riichi_declared: [bool; 4],
+    result: Vec<Value>,
+}
+
+impl Converter {
+    fn feed(&mut self, ev: &Event) -> Result<()> {
+        if let Event::StartGame { names, meta, .. } = ev {
+            self.names = names.clone();
+            self.rule = meta.as_ref().and_then(|m| m.rule).unwrap_or_default();
+            self.states = (0..4)
+                .map(|i| PlayerState::with_rule(i, self.rule))
+                .collect();
+            return Ok(());
+        }
+        ensure!(self.states.len() == 4, "missing start_game");
+
+        match *ev {
+            Event::StartKyoku {
+                bakaze,
+                dora_marker,
+                kyoku,
+                honba,
+                kyotaku,
+                scores,
+                ref tehais,
+                ..
+            } => {
+                ensure!(self.kyoku.is_none(), "start_kyoku before end_kyoku");
+                self.kyoku = Some(Kyoku {
+                    header: [(bakaze.as_u8() - tu8!(E)) * 4 + kyoku - 1, honba, kyotaku],
+                    scores,
+                    dora_indicators: vec![code(dora_marker)],
+                    haipais: tehais.map(|t| t.iter().copied().map(code).collect()),
+                    ..Default::default()
+                });
+            }
+            Event::EndKyoku => {
+                let kyoku = self.kyoku.take().context("end_kyoku outside of a kyoku")?;
+                self.log.push(kyoku.into_json());
+            }
+            Event::Hora {
+                actor,
+                target,
+                deltas,
+                ref ura_markers,
+            } => {
+                let ura_markers = ura_markers.as_deref().unwrap_or_default();
+                let state = &self.states[actor as usize];
+                let is_ron = actor != target;
+                let agari = state.agari(is_ron, ura_markers)?;
+                let text = point_text(agari, state.is_oya(), is_ron);
+                let deltas = deltas.context("hora without deltas")?;
+
+                let kyoku = self.kyoku_mut()?;
+                if kyoku.result.is_empty() {
+                    kyoku.result.push(json!("和了"));
+                }
+                if kyoku.ura_indicators.is_empty() {
+                    kyoku.ura_indicators = ura_markers.iter().copied().map(code).collect();
+                }
+                kyoku.result.push(json!(deltas));
+                kyoku.result.push(json!([actor, target, actor, text,]));
+            }
+            Event::Ryukyoku { deltas, reason } => {
+                let name = match reason.unwrap_or(RyukyokuReason::Fanpai) {
+                    RyukyokuReason::Fanpai => "流局",
+                    RyukyokuReason::Nagashimangan => "流し満貫",
+                    RyukyokuReason::Kyushukyuhai => "九種九牌",
+                    RyukyokuReason::Suufonrenta => "四風連打",
+                    RyukyokuReason::Suuchariichi => "四家立直",
+                    RyukyokuReason::Suukaikan => "四槓散了",
+                    RyukyokuReason::Sanchaho => "三家和了",
+                };
+                let kyoku = self.kyoku_mut()?;
+                kyoku.result.push(json!(name));
+                if let Some(deltas) = deltas.filter(|d| d.iter().any(|&p| p != 0)) {
+                    kyoku.result.push(json!(deltas));
+                }
+            }
+            Event::Chombo { .. } => bail!("chombo cannot be expressed in tenhou.net/6"),
+            Event::EndGame => (),
+            _ => self.kyoku_mut()?.feed(ev)?,
+        }
+
+        // The states are left as they were before a hora, for the other
+        // winners of a double ron.
+        if !matches!(ev, Event::Hora { .. }) {
+            for state in &mut self.states {
+                state.update(ev)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn kyoku_mut(&mut self) -> Result<&mut Kyoku> {
+        self.kyoku.as_mut().context("event outside of a kyoku")
+    }
+}
+
+impl Kyoku {
+    /// Takes the events within a kyoku that go into the takes and discards.
+    fn feed(&mut self, ev: &Event) -> Result<()> {
+        match *ev {
+            Event::Tsumo { actor, pai } => self.takes[actor as usize].push(json!(code(pai))),
+            Event::Dahai {
+                actor,
+                pai,
+                tsumogiri,
+            } => {
+                let tile = if tsumogiri { 60 } else { code(pai) };
+                let discard = if self.riichi_declared[actor as usize] {
+                    self.riichi_declared[actor as usize] = false;
+                    json!(format!("r{tile}"))
+                } else {
+                    json!(tile)
+                };
+                self.discards[actor as usize].push(discard);
+            }
+            Event::Reach { actor, .. } => self.riichi_declared[actor as usize] = true,
+            Event::Chi {
+                actor,
+                pai,
+                consumed,
+                ..
+            } => {
+                let meld = format!("c{}{}{}", code(pai), code(consumed[0]), code(consumed[1]));
+                self.takes[actor as usize].push(json!(meld));
+            }
+            Event::Pon {
+                actor,
+                target,
+                pai,
+                consumed,
+            } => {
+                let meld = called(actor, target, 'p', pai, &consumed)?;
+                self.pons.push((actor, pai.deaka(), meld.clone()));
+                self.takes[actor as usize].push(json!(meld));
+            }
+            Event::Daiminkan {
+                actor,
+                target,
+                pai,
+                consumed,
+            } => {
+                let meld = called(actor, target, 'm', pai, &consumed)?;
+                self.takes[actor as usize].push(json!(meld));
+                // The rinshan tsumo comes next, so there is no discard for
+                // this take.
+                self.discards[actor as usize].push(json!(0));
+            }
+            Event::Kakan { actor, pai, .. } => {
+                let (_, _, pon) = self
+                    .pons
+                    .iter()
+                    .find(|(a, t, _)| *a == actor && *t == pai.deaka())
+                    .context("kakan without pon")?;
+                let meld = pon.replacen('p', &format!("k{}", code(pai)), 1);
+                self.discards[actor as usize].push(json!(meld));
+            }
+            Event::Ankan { actor, consumed } => {
+                let meld = format!(
+                    "{}{}{}a{}",
+                    code(consumed[0]),
+                    code(consumed[1]),
+                    code(consumed[2]),
+                    code(consumed[3]),
+                );
+                self.discards[actor as usize].push(json!(meld));
+            }
+            Event::Dora { dora_marker } => self.dora_indicators.push(code(dora_marker)),
+            Event::ReachAccepted { .. } => (),
+            Event::Nukidora { .. } => bail!("sanma is not supported yet"),
+            _ => bail!("unexpected event within a kyoku"),
+        }
+        Ok(())
+    }
+
+    fn into_json(self) -> Value {
+        let mut ret = vec![
+            json!(self.header),
+            json!(self.scores),
+            json!(self.dora_indicators),
+            json!(self.ura_indicators),
+        ];
+        for ((haipai, takes), discards) in
+            self.haipais.into_iter().zip(self.takes).zip(self.discards)
+        {
+            ret.push(json!(haipai));
+            ret.push(json!(takes));
+            ret.push(json!(discards));
+        }
+        ret.push(json!(self.result));
+        Value::Array(ret)
+    }
+}
+
+/// The tile code of tenhou.net/6, which is 11-19 for m, 21-29 for p, 31-39
+/// for s, 41-47 for ESWNPFC and 51-53 for the aka 5s.
+fn code(tile: Tile) -> u8 {
+    let id = tile.as_u8();
+    if tile.is_aka() {
+        51 + id - tu8!(5mr)
+    } else {
+        (id / 9 + 1) * 10 + id % 9 + 1
+    }
+}
+
+/// Formats a pon or daiminkan, where the position of `marker` tells where the
+/// called tile comes from: first for kamicha, second for toimen and last for
+/// shimocha.
+fn called(actor: u8, target: u8, marker: char, pai: Tile, consumed: &[Tile]) -> Result<String> {
+    let mut tiles: Vec<_> = consumed.iter().map(|&t| code(t).to_string()).collect();
+    let pos = match (target + 4 - actor) % 4 {
+        3 => 0,
+        2 => 1,
+        1 => tiles.len(),
+        _ => bail!("calling from oneself"),
+    };
+    tiles.insert(pos, format!("{marker}{}", code(pai)));
+    Ok(tiles.concat())
+}
+
+/// Formats the points of an agari, without honba, e.g. `30符1飜1000点` for
+/// ron, `30符1飜300-500点` for tsumo by ko and `満貫4000点∀` for tsumo by oya.
+fn point_text(agari: Agari, is_oya: bool, is_ron: bool) -> String {
+    let point = agari.into_point(is_oya);
+    let label = match agari {
+        Agari::Yakuman(_) | Agari::Normal { han: 13.., .. } => "役満".to_owned(),
+        Agari::Normal { han: 11..=12, .. } => "三倍満".to_owned(),
+        Agari::Normal { han: 8..=10, .. } => "倍満".to_owned(),
+        Agari::Normal { han: 6..=7, .. } => "跳満".to_owned(),
+        Agari::Normal { .. } if point.ron >= Point::mangan(is_oya).ron => "満貫".to_owned(),
+        Agari::Normal { fu, han } => format!("{fu}符{han}飜"),
+    };
+    if is_ron {
+        format!("{label}{}点", point.ron)
+    } else if is_oya {
+        format!("{label}{}点∀", point.tsumo_ko)
+    } else {
+        format!("{label}{}-{}点", point.tsumo_ko, point.tsumo_oya)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::t;
+
+    use serde_json as json;
+
+    #[test]
+    fn tile_code() {
+        assert_eq!(code(t!(1m)), 11);
+        assert_eq!(code(t!(9p)), 29);
+        assert_eq!(code(t!(5s)), 35);
+        assert_eq!(code(t!(E)), 41);
+        assert_eq!(code(t!(C)), 47);
+        assert_eq!(code(t!(5mr)), 51);
+        assert_eq!(code(t!(5sr)), 53);
+    }
+
+    #[test]
+    fn meld_layout() {
+        let consumed = [t!(5p), t!(5p)];
+        assert_eq!(called(1, 0, 'p', t!(5pr), &consumed).unwrap(), "p522525");
+        assert_eq!(called(1, 3, 'p', t!(5pr), &consumed).unwrap(), "25p5225");
+        assert_eq!(called(1, 2, 'p', t!(5pr), &consumed).unwrap(), "2525p52");
+        assert!(called(1, 1, 'p', t!(5pr), &consumed).is_err());
+
+        let consumed = [t!(E), t!(E), t!(E)];
+        assert_eq!(called(0, 2, 'm', t!(E), &consumed).unwrap(), "41m414141");
+    }
+
+    #[test]
+    fn convert() {
+        let events = r#"
+            {"type":"start_game","names":["A","B","C","D"]}
+            {"type":"start_kyoku","bakaze":"E","dora_marker":"1m","kyoku":1,"honba":0,"kyotaku":0,"oya":0,"scores":[25000,25000,25000,25000],"tehais":[["4p","6p","7p","8p","1s","2s","3s","6s","7s","8s","E","S","W"],["N","1m","2m","3m","4m","5m","6m","7m","8m","9m","2p","3p","4p"],["N","N","P","P","P","F","F","C","C","E","S","W","1m"],["1m","2m","3m","4m","5m","6m","7m","8m","9m","1p","1p","2p","3p"]]}
+            {"type":"tsumo","actor":0,"pai":"9p"}
+            {"type":"dahai","actor":0,"pai":"9p","tsumogiri":true}
+            {"type":"tsumo","actor":1,"pai":"4s"}
+            {"type":"dahai","actor":1,"pai":"N","tsumogiri":false}
+            {"type":"pon","actor":2,"target":1,"pai":"N","consumed":["N","N"]}
+            {"type":"dahai","actor":2,"pai":"1m","tsumogiri":false}
+            {"type":"tsumo","actor":3,"pai":"9s"}
+            {"type":"reach","actor":3}
+            {"type":"dahai","actor":3,"pai":"9s","tsumogiri":true}
+            {"type":"reach_accepted","actor":3}
+            {"type":"tsumo","actor":0,"pai":"4s"}
+            {"type":"dahai","actor":0,"pai":"4p","tsumogiri":false}
+            {"type":"hora","actor":3,"target":0,"deltas":[-12000,0,0,13000],"ura_markers":["2m"]}
+            {"type":"end_kyoku"}
+            {"type":"start_kyoku","bakaze":"E","dora_marker":"5pr","kyoku":2,"honba":0,"kyotaku":0,"oya":1,"scores":[13000,25000,25000,37000],"tehais":[["2p","2p","2p","2p","3p","3p","3p","3p","4p","4p","4p","4p","5p"],["E","E","E","1m","2m","3m","4m","5m","6m","7m","8m","9m","1p"],["5p","5p","6p","6p","6p","6p","7p","7p","7p","7p","8p","8p","8p"],["8p","9p","9p","9p","9p","1s","1s","1s","1s","2s","2s","2s","2s"]]}
+            {"type":"tsumo","actor":1,"pai":"E"}
+            {"type":"ankan","actor":1,"consumed":["E","E","E","E"]}
+            {"type":"dora","dora_marker":"S"}
+            {"type":"tsumo","actor":1,"pai":"C"}
+            {"type":"dahai","actor":1,"pai":"C","tsumogiri":true}
+            {"type":"ryukyoku","deltas":[0,0,0,0],"reason":"fanpai"}
+            {"type":"end_kyoku"}
+            {"type":"end_game"}
+        "#
+        .trim()
+        .lines()
+        .map(|l| json::from_str::<Event>(l).unwrap())
+        .collect::<Vec<_>>();
+        let tenhou6 = mjai_to_tenhou6(&events).unwrap();
+        assert_eq!(tenhou6["name"], json!(["A", "B", "C", "D"]));
+        assert_eq!(tenhou6["rule"], json!({"disp": "般南喰赤", "aka": 1}));
+
+        let log = tenhou6["log"].as_array().unwrap();
+        assert_eq!(log.len(), 2);
+        // The header, scores, doras and uras, then the haipai, takes and
+        // discards of each seat, and the result.
+        let first = log[0].as_array().unwrap();
+        assert_eq!(first.len(), 4 + 4 * 3 + 1);
+        assert_eq!(first[0], json!([0, 0, 0]));
+        assert_eq!(first[2], json!([11]));
+        assert_eq!(first[3], json!([12]));
+        assert_eq!(first[4 + 1], json!([29, 34]));
+        assert_eq!(first[4 + 2], json!([60, 24]));
+        assert_eq!(first[4 + 2 * 3 + 1], json!(["p444444"]));
+        assert_eq!(first[4 + 3 * 3 + 2], json!(["r60"]));
+        assert_eq!(
+            first[16],
+            json!(["和了", [-12000, 0, 0, 13000], [3, 0, 3, "跳満12000点"]]),
+        );
+
+        let second = log[1].as_array().unwrap();
+        assert_eq!(second[2], json!([52, 42]));
+        assert_eq!(second[4 + 3 + 1], json!([41, 47]));
+        assert_eq!(second[4 + 3 + 2], json!(["414141a41", 60]));
+        assert_eq!(second[16], json!(["流局"]));
+
+        let url = tenhou6_url(&tenhou6);
+        assert!(url.starts_with(VIEWER_URL));
+        assert!(url.contains("%22log%22%3A%5B%5B%5B0%2C0%2C0%5D"));
+    }
+}
@@ -1,19 +1,29 @@
 use super::invisible::Invisible;
 use super::player_list::{TENHOUI, TOP300_2K_GAMES};
-use super::Grp;
+#[cfg(feature = "python")]
+use super::PackedWriter;
+use super::{Grp, RewardTable, SampleWeights};
+use crate::agent::BatchAgent;
+#[cfg(feature = "python")]
+use crate::agent::MortalBatchAgent;
 use crate::chi_type::ChiType;
-use crate::mjai::Event;
-use crate::state::PlayerState;
-use std::fs::File;
-use std::io::prelude::*;
+use crate::consts::{ObsVersion, ACTION_SPACE};
+use crate::log_io;
+use crate::mjai::{Event, GameMeta, Metadata, Timing};
+use crate::par::*;
+use crate::rule::RuleSet;
+use crate::state::{PlayerState, SuitPerm};
 use std::mem;
 
 use anyhow::{bail, ensure, Context, Result};
-use flate2::read::GzDecoder;
 use ndarray::prelude::*;
+#[cfg(feature = "python")]
 use numpy::{PyArray1, PyArray2};
+#[cfg(feature = "python")]
 use pyo3::prelude::*;
-use rayon::prelude::*;
+use rand::prelude::*;
+use riichi_macros::{pyclass, pymethods};
+use serde::Deserialize;
 use serde_json as json;
 use tinyvec::ArrayVec;
 
@@ -25,6 +35,17 @@ use tinyvec::ArrayVec;
     excludes = None,
     trust_seed = False,
     always_include_kan_select = True,
+    reward_table = None,
+    sample_weights = None,
+    augment_suits = False,
+    obs_version = ObsVersion.V1,
+    seats = None,
+    min_dan = None,
+    min_rate = None,
+    rooms = None,
+    tonpuusen = None,
+    rule = None,
+    teacher = None,
 )")]
 #[derive(Debug, Clone, Default)]
 pub struct GameplayLoader {
@@ -38,8 +59,52 @@ pub struct GameplayLoader {
     pub trust_seed: bool,
     #[pyo3(get, set)]
     pub always_include_kan_select: bool,
+    /// If set, `Gameplay::kyoku_rewards` is filled in with it.
+    #[pyo3(get, set)]
+    pub reward_table: Option<RewardTable>,
+    /// If set, `Gameplay::weights` is filled in with it.
+    #[pyo3(get, set)]
+    pub sample_weights: Option<SampleWeights>,
+    /// Whether to encode each loaded game under a random permutation of the
+    /// suits, recorded in `Gameplay::suit_perm`.
+    #[pyo3(get, set)]
+    pub augment_suits: bool,
+    #[pyo3(get, set)]
+    pub obs_version: ObsVersion,
+
+    // Filters below are applied on top of `player_name` and `excludes`. Logs
+    // without the metadata needed by any of the filters set are skipped.
+    /// Seats to load, all if `None`.
+    #[pyo3(get, set)]
+    pub seats: Option<Vec<u8>>,
+    /// Minimum Tenhou dan of the seats to load.
+    #[pyo3(get, set)]
+    pub min_dan: Option<u8>,
+    /// Minimum Tenhou rate of the seats to load.
+    #[pyo3(get, set)]
+    pub min_rate: Option<u16>,
+    /// Tenhou rooms of the logs to load, such as `"houou"`, all if empty.
+    #[pyo3(get, set)]
+    pub rooms: Vec<String>,
+    /// Whether to load only tonpuusen or only hanchan logs, both if `None`.
+    #[pyo3(get, set)]
+    pub tonpuusen: Option<bool>,
+    /// The exact rule of the logs to load, any if `None`.
+    #[pyo3(get, set)]
+    pub rule: Option<RuleSet>,
+
+    /// A Python engine, the same kind as the ones of the arena, whose q
+    /// values on every decision fill `Gameplay::teacher_qs` for distillation.
+    /// Each game of a player is evaluated in one batch.
+    #[cfg(feature = "python")]
+    #[pyo3(get, set)]
+    pub teacher: Option<PyObject>,
 }
 
+/// Creates a teacher `BatchAgent` for the given player ID of each index, see
+/// `GameplayLoader::load_events_with_teacher`.
+pub type TeacherFactory<'a> = dyn Fn(&[u8]) -> Result<Box<dyn BatchAgent>> + Sync + 'a;
+
 #[pyclass]
 #[derive(Clone, Default)]
 pub struct Gameplay {
@@ -53,11 +118,30 @@ pub struct Gameplay {
     pub apply_gamma: Vec<bool>,
     pub at_turns: Vec<u8>,
     pub shantens: Vec<i8>,
+    /// Milliseconds the player took on the action, if the log has the
+    /// `timing` of it.
+    pub think_ms: Vec<Option<u32>>,
+    /// Weight of each sample by `GameplayLoader::sample_weights`, empty if it
+    /// is not set.
+    pub weights: Vec<f32>,
+    /// Q values of the teacher in the action space, where only those set in
+    /// `teacher_masks` are meaningful. Its policy is the softmax over them.
+    /// Empty if the loader has no teacher.
+    pub teacher_qs: Vec<Array1<f32>>,
+    /// The actions that have a q value from the teacher, all `false` if the
+    /// teacher reports none for the decision, for example a kan select where
+    /// the teacher would not kan.
+    pub teacher_masks: Vec<Array1<bool>>,
+    /// The state of each entry to be evaluated by the teacher, where a kan
+    /// select shares the one of the entry before it and has `None`.
+    pub(super) teacher_scenes: Vec<Option<PlayerState>>,
 
     // one per kyoku
     pub grp: Grp,
+    pub kyoku_rewards: Vec<f64>,
 
     // one per game
+    pub suit_perm: SuitPerm,
     pub player_id: u8,
     pub player_name: String,
     pub quality: Quality,
@@ -73,9 +157,19 @@ pub enum Quality {
     Tenhoui,
 }
 
+/// The `timing` of a line in a log, with everything else skipped.
+#[derive(Deserialize)]
+struct LineTiming {
+    timing: Option<Timing>,
+}
+
 struct LoaderContext<'a> {
     config: &'a GameplayLoader,
     invisibles: Option<&'a [Invisible]>,
+    /// Same length as the events, or empty if the log has no timing at all.
+    timings: &'a [Option<Timing>],
+    suit_perm: SuitPerm,
+    record_scenes: bool,
 
     state: PlayerState,
     kyoku_idx: usize,
@@ -88,6 +182,7 @@ struct LoaderContext<'a> {
 
 #[pymethods]
 impl GameplayLoader {
+    #[cfg(feature = "python")]
     #[new]
     #[args(
         "*",
@@ -95,40 +190,100 @@ impl GameplayLoader {
         player_name = "None",
         excludes = "None",
         trust_seed = "false",
-        always_include_kan_select = "true"
+        always_include_kan_select = "true",
+        reward_table = "None",
+        sample_weights = "None",
+        augment_suits = "false",
+        obs_version = "ObsVersion::V1",
+        seats = "None",
+        min_dan = "None",
+        min_rate = "None",
+        rooms = "None",
+        tonpuusen = "None",
+        rule = "None",
+        teacher = "None"
     )]
+    #[allow(clippy::too_many_arguments)]
     fn new(
         oracle: bool,
         player_name: Option<String>,
         excludes: Option<Vec<String>>,
         trust_seed: bool,
         always_include_kan_select: bool,
+        reward_table: Option<RewardTable>,
+        sample_weights: Option<SampleWeights>,
+        augment_suits: bool,
+        obs_version: ObsVersion,
+        seats: Option<Vec<u8>>,
+        min_dan: Option<u8>,
+        min_rate: Option<u16>,
+        rooms: Option<Vec<String>>,
+        tonpuusen: Option<bool>,
+        rule: Option<RuleSet>,
+        teacher: Option<PyObject>,
     ) -> Self {
         let excludes = excludes.unwrap_or_default();
+        let rooms = rooms.unwrap_or_default();
         Self {
             oracle,
             player_name,
             excludes,
             trust_seed,
             always_include_kan_select,
+            reward_table,
+            sample_weights,
+            augment_suits,
+            obs_version,
+            seats,
+            min_dan,
+            min_rate,
+            rooms,
+            tonpuusen,
+            rule,
+            teacher,
         }
     }
 
     // Nested result is too hard to handle...
+    #[cfg(feature = "python")]
+    #[pyo3(name = "load_log")]
     #[pyo3(text_signature = "($self, raw_log, /)")]
-    fn load_log(&self, raw_log: &str) -> Result<Vec<Gameplay>> {
-        let events = raw_log
-            .lines()
-            .map(json::from_str)
-            .collect::<Result<Vec<Event>, _>>()
-            .context("failed to parse log")?;
-        self.load_events(&events)
+    fn load_log_py(&self, raw_log: &str, py: Python<'_>) -> Result<Vec<Gameplay>> {
+        // The teacher takes the GIL from the worker threads.
+        py.allow_threads(|| self.load_log(raw_log))
     }
 
+    #[cfg(feature = "python")]
     #[pyo3(name = "load_gz_log_files")]
     #[pyo3(text_signature = "($self, gzip_filenames, /)")]
-    fn load_gz_log_files_py(&self, gzip_filenames: Vec<String>) -> Result<Vec<Gameplay>> {
-        self.load_gz_log_files(gzip_filenames)
+    fn load_gz_log_files_py(
+        &self,
+        gzip_filenames: Vec<String>,
+        py: Python<'_>,
+    ) -> Result<Vec<Gameplay>> {
+        py.allow_threads(|| self.load_gz_log_files(gzip_filenames))
+    }
+
+    /// Loads the logs like `load_gz_log_files`, but writes the games into the
+    /// packed file `out_filename` instead, to be read by `GameplayReader`.
+    /// Returns the number of games written.
+    #[cfg(feature = "python")]
+    #[pyo3(text_signature = "($self, gzip_filenames, out_filename, /)")]
+    fn pack_gz_log_files(
+        &self,
+        gzip_filenames: Vec<String>,
+        out_filename: &str,
+        py: Python<'_>,
+    ) -> Result<usize> {
+        py.allow_threads(|| {
+            let games = self.load_gz_log_files(gzip_filenames)?;
+            let mut writer = PackedWriter::create(out_filename)?;
+            for game in &games {
+                writer.write(game)?;
+            }
+            writer.close()?;
+            Ok(games.len())
+        })
     }
 
     fn __repr__(&self) -> String {
@@ -137,6 +292,26 @@ impl GameplayLoader {
 }
 
 impl GameplayLoader {
+    pub fn load_log(&self, raw_log: &str) -> Result<Vec<Gameplay>> {
+        let events = raw_log
+            .lines()
+            .map(json::from_str)
+            .collect::<Result<Vec<Event>, _>>()
+            .context("failed to parse log")?;
+        // Most logs have no timing, so do not bother parsing them twice.
+        let timings = if raw_log.contains(r#""timing""#) {
+            raw_log
+                .lines()
+                .map(|l| json::from_str(l).map(|t: LineTiming| t.timing))
+                .collect::<Result<Vec<_>, _>>()
+                .context("failed to parse timing")?
+        } else {
+            vec![]
+        };
+        self.load_timed_events(&events, &timings)
+    }
+
+    /// Despite the name, the files may be plain, gzip or zstd compressed.
     pub fn load_gz_log_files<V, S>(&self, gzip_filenames: V) -> Result<Vec<Gameplay>>
     where
         V: IntoParallelIterator<Item = S>,
@@ -147,10 +322,7 @@ impl GameplayLoader {
             .map(|f| {
                 let filename = f.as_ref();
                 let inner = || {
-                    let file = File::open(filename)?;
-                    let mut gz = GzDecoder::new(file);
-                    let mut raw = String::new();
-                    gz.read_to_string(&mut raw)?;
+                    let raw = log_io::read_log(filename)?;
                     self.load_log(&raw)
                 };
                 inner().with_context(|| format!("error when reading {filename}"))
@@ -160,33 +332,139 @@ impl GameplayLoader {
     }
 
     pub fn load_events(&self, events: &[Event]) -> Result<Vec<Gameplay>> {
+        self.load_timed_events(events, &[])
+    }
+
+    /// Like `load_events`, where `timings` is either empty or the `timing` of
+    /// each event, which fills `Gameplay::think_ms`.
+    pub fn load_timed_events(
+        &self,
+        events: &[Event],
+        timings: &[Option<Timing>],
+    ) -> Result<Vec<Gameplay>> {
+        #[cfg(feature = "python")]
+        if let Some(engine) = &self.teacher {
+            let new_teacher = |player_ids: &[u8]| -> Result<Box<dyn BatchAgent>> {
+                Ok(Box::new(MortalBatchAgent::new(engine.clone(), player_ids)?))
+            };
+            return self.load_with_teacher(events, timings, Some(&new_teacher));
+        }
+        self.load_with_teacher(events, timings, None)
+    }
+
+    /// Like `load_events`, but fills `Gameplay::teacher_qs` with the teacher
+    /// made by `new_teacher` instead of the Python one.
+    pub fn load_events_with_teacher(
+        &self,
+        events: &[Event],
+        new_teacher: &TeacherFactory<'_>,
+    ) -> Result<Vec<Gameplay>> {
+        self.load_with_teacher(events, &[], Some(new_teacher))
+    }
+
+    fn load_with_teacher(
+        &self,
+        events: &[Event],
+        timings: &[Option<Timing>],
+        new_teacher: Option<&TeacherFactory<'_>>,
+    ) -> Result<Vec<Gameplay>> {
+        ensure!(
+            timings.is_empty() || timings.len() == events.len(),
+            "got {} timings for {} events",
+            timings.len(),
+            events.len(),
+        );
         let invisibles = self.oracle.then(|| Invisible::new(events, self.trust_seed));
 
-        let idxs: ArrayVec<[u8; 4]> = match &events[0] {
-            Event::StartGame { names, .. } => names
-                .iter()
-                .enumerate()
-                .filter(|(_, name)| {
-                    if let Some(player_name) = &self.player_name {
-                        return player_name == name.as_str();
-                    }
-                    !self.excludes.contains(name)
-                })
-                .map(|(i, _)| i as u8)
-                .collect(),
+        let (names, meta) = match &events[0] {
+            Event::StartGame { names, meta, .. } => (names, meta.as_ref()),
             _ => bail!("the first event is not StartGame, got {:?}", events[0]),
         };
+        if !self.accepts_game(meta) {
+            return Ok(vec![]);
+        }
+        let idxs: ArrayVec<[u8; 4]> = names
+            .iter()
+            .enumerate()
+            .filter(|&(i, name)| {
+                if !self.accepts_seat(meta, i) {
+                    return false;
+                }
+                if let Some(player_name) = &self.player_name {
+                    return player_name == name.as_str();
+                }
+                !self.excludes.contains(name)
+            })
+            .map(|(i, _)| i as u8)
+            .collect();
 
-        idxs.into_par_iter()
+        idxs.par_iter()
             .map(|&player_id| {
-                Gameplay::load_events_by_player(self, events, player_id, invisibles.as_deref())
+                let mut data = Gameplay::load_events_by_player(
+                    self,
+                    events,
+                    timings,
+                    player_id,
+                    invisibles.as_deref(),
+                    new_teacher.is_some(),
+                )?;
+                if let Some(new_teacher) = new_teacher {
+                    let decisions = data.teacher_scenes.iter().flatten().count();
+                    let mut teacher = new_teacher(&vec![player_id; decisions])?;
+                    data.distill(&mut *teacher)?;
+                }
+                Ok(data)
             })
             .collect()
     }
+
+    fn accepts_game(&self, meta: Option<&GameMeta>) -> bool {
+        let needs_meta = !self.rooms.is_empty()
+            || self.tonpuusen.is_some()
+            || self.rule.is_some()
+            || self.min_dan.is_some()
+            || self.min_rate.is_some();
+        let Some(meta) = meta else {
+            return !needs_meta;
+        };
+
+        if !self.rooms.is_empty() {
+            let Some(room) = meta.room else {
+                return false;
+            };
+            if !self.rooms.iter().any(|r| r == room.as_str()) {
+                return false;
+            }
+        }
+        if let Some(tonpuusen) = self.tonpuusen {
+            if meta.rule.map(|r| r.tonpuusen) != Some(tonpuusen) {
+                return false;
+            }
+        }
+        if self.rule.is_some() && meta.rule != self.rule {
+            return false;
+        }
+        true
+    }
+
+    fn accepts_seat(&self, meta: Option<&GameMeta>, seat: usize) -> bool {
+        let dans = meta.and_then(|m| m.dans);
+        let rates = meta.and_then(|m| m.rates);
+        self.seats
+            .as_ref()
+            .is_none_or(|s| s.contains(&(seat as u8)))
+            && self
+                .min_dan
+                .is_none_or(|min| dans.is_some_and(|d| d[seat] >= min))
+            && self
+                .min_rate
+                .is_none_or(|min| rates.is_some_and(|r| r[seat] >= min))
+    }
 }
 
 #[pymethods]
 impl Gameplay {
+    #[cfg(feature = "python")]
     #[pyo3(text_signature = "($self, /)")]
     fn take_obs<'py>(&mut self, py: Python<'py>) -> Vec<&'py PyArray2<f32>> {
         mem::take(&mut self.obs)
@@ -194,6 +472,7 @@ impl Gameplay {
             .map(|v| PyArray2::from_owned_array(py, v))
             .collect()
     }
+    #[cfg(feature = "python")]
     #[pyo3(text_signature = "($self, /)")]
     fn take_invisible_obs<'py>(&mut self, py: Python<'py>) -> Vec<&'py PyArray2<f32>> {
         mem::take(&mut self.invisible_obs)
@@ -205,6 +484,7 @@ impl Gameplay {
     fn take_actions(&mut self) -> Vec<i64> {
         mem::take(&mut self.actions)
     }
+    #[cfg(feature = "python")]
     #[pyo3(text_signature = "($self, /)")]
     fn take_masks<'py>(&mut self, py: Python<'py>) -> Vec<&'py PyArray1<bool>> {
         mem::take(&mut self.masks)
@@ -232,12 +512,46 @@ impl Gameplay {
     fn take_shantens(&mut self) -> Vec<i8> {
         mem::take(&mut self.shantens)
     }
+    #[pyo3(text_signature = "($self, /)")]
+    fn take_think_ms(&mut self) -> Vec<Option<u32>> {
+        mem::take(&mut self.think_ms)
+    }
+    #[pyo3(text_signature = "($self, /)")]
+    fn take_weights(&mut self) -> Vec<f32> {
+        mem::take(&mut self.weights)
+    }
+    #[cfg(feature = "python")]
+    #[pyo3(text_signature = "($self, /)")]
+    fn take_teacher_qs<'py>(&mut self, py: Python<'py>) -> Vec<&'py PyArray1<f32>> {
+        mem::take(&mut self.teacher_qs)
+            .into_iter()
+            .map(|v| PyArray1::from_owned_array(py, v))
+            .collect()
+    }
+    #[cfg(feature = "python")]
+    #[pyo3(text_signature = "($self, /)")]
+    fn take_teacher_masks<'py>(&mut self, py: Python<'py>) -> Vec<&'py PyArray1<bool>> {
+        mem::take(&mut self.teacher_masks)
+            .into_iter()
+            .map(|v| PyArray1::from_owned_array(py, v))
+            .collect()
+    }
 
     #[pyo3(text_signature = "($self, /)")]
     fn take_grp(&mut self) -> Grp {
         mem::take(&mut self.grp)
     }
+    #[pyo3(text_signature = "($self, /)")]
+    fn take_kyoku_rewards(&mut self) -> Vec<f64> {
+        mem::take(&mut self.kyoku_rewards)
+    }
 
+    /// The permutation of the suits the game is encoded under, see
+    /// `SuitPerm` in Rust.
+    #[pyo3(text_signature = "($self, /)")]
+    const fn take_suit_perm(&self) -> [u8; 3] {
+        self.suit_perm.as_array()
+    }
     #[pyo3(text_signature = "($self, /)")]
     const fn take_player_id(&self) -> u8 {
         self.player_id
@@ -252,8 +566,10 @@ impl Gameplay {
     fn load_events_by_player(
         config: &GameplayLoader,
         events: &[Event],
+        timings: &[Option<Timing>],
         player_id: u8,
         invisibles: Option<&[Invisible]>,
+        record_scenes: bool,
     ) -> Result<Self> {
         let grp = Grp::load_events(events)?;
 
@@ -273,8 +589,21 @@ impl Gameplay {
             bail!("first event is not start_game");
         };
 
+        let kyoku_rewards = config
+            .reward_table
+            .map(|table| table.kyoku_rewards(&grp, player_id))
+            .unwrap_or_default();
+
+        let suit_perm = if config.augment_suits {
+            *SuitPerm::ALL.choose(&mut thread_rng()).unwrap()
+        } else {
+            SuitPerm::IDENTITY
+        };
+
         let mut data = Self {
             grp,
+            kyoku_rewards,
+            suit_perm,
             player_id,
             quality,
             ..Default::default()
@@ -283,6 +612,9 @@ impl Gameplay {
         let mut ctx = LoaderContext {
             config,
             invisibles,
+            timings,
+            suit_perm,
+            record_scenes,
             state: PlayerState::new(player_id),
             kyoku_idx: 0,
             opponent_states: [
@@ -297,19 +629,26 @@ impl Gameplay {
 
         // It is guaranteed that there are at least 4 events.
         // tsumo/dahai -> ryukyoku/hora -> end kyoku -> end game
-        for wnd in events.windows(4) {
-            data.extend_from_event_window(&mut ctx, wnd.try_into().unwrap());
+        for (idx, wnd) in events.windows(4).enumerate() {
+            data.extend_from_event_window(&mut ctx, idx, wnd.try_into().unwrap())?;
         }
 
         data.dones = data.at_kyoku.windows(2).map(|w| w[1] > w[0]).collect();
-        data.dones.push(true);
+        if !data.at_kyoku.is_empty() {
+            data.dones.push(true);
+        }
 
         Ok(data)
     }
 
     // Inlined because its callsite is extremely hot.
     #[inline(always)]
-    fn extend_from_event_window(&mut self, ctx: &mut LoaderContext<'_>, wnd: &[Event; 4]) {
+    fn extend_from_event_window(
+        &mut self,
+        ctx: &mut LoaderContext<'_>,
+        idx: usize,
+        wnd: &[Event; 4],
+    ) -> Result<()> {
         let LoaderContext {
             config,
             invisibles,
@@ -319,14 +658,16 @@ impl Gameplay {
             from_rinshan,
             yama_idx,
             rinshan_idx,
+            ..
         } = ctx;
 
         let cur = &wnd[0];
-        let next = if matches!(wnd[1], Event::ReachAccepted { .. } | Event::Dora { .. }) {
-            &wnd[2]
+        let next_offset = if matches!(wnd[1], Event::ReachAccepted { .. } | Event::Dora { .. }) {
+            2
         } else {
-            &wnd[1]
+            1
         };
+        let next = &wnd[next_offset];
 
         match cur {
             Event::StartGame { names, .. } => {
@@ -360,13 +701,13 @@ impl Gameplay {
             };
 
             for s in opponent_states {
-                s.update(cur);
+                s.update(cur)?;
             }
         }
 
-        let cans = state.update(cur);
+        let cans = state.update(cur)?;
         if !cans.can_act() {
-            return;
+            return Ok(());
         }
 
         let mut kan_select = None;
@@ -444,31 +785,273 @@ impl Gameplay {
         };
 
         if let Some(label) = label_opt {
-            self.add_entry(ctx, false, label);
+            // Only the player's own action has its think time.
+            let think_ms = ctx
+                .timings
+                .get(idx + next_offset)
+                .filter(|_| next.actor() == Some(self.player_id))
+                .and_then(|t| t.and_then(|t| t.think_ms));
+            self.add_entry(ctx, false, label, think_ms);
             if let Some(kan) = kan_select {
-                self.add_entry(ctx, true, kan);
+                self.add_entry(ctx, true, kan, think_ms);
             }
         }
+
+        Ok(())
     }
 
-    fn add_entry(&mut self, ctx: &LoaderContext<'_>, at_kan_select: bool, label: usize) {
-        let (feature, mask) = ctx.state.encode_obs(at_kan_select);
+    fn add_entry(
+        &mut self,
+        ctx: &LoaderContext<'_>,
+        at_kan_select: bool,
+        label: usize,
+        think_ms: Option<u32>,
+    ) {
+        let (feature, mask) =
+            ctx.state
+                .encode_obs_with(at_kan_select, ctx.config.obs_version, ctx.suit_perm);
         self.obs.push(feature);
-        self.actions.push(label as i64);
+        self.actions
+            .push(ctx.suit_perm.action(label, at_kan_select) as i64);
+        if let Some(weights) = &ctx.config.sample_weights {
+            let legal_actions = mask.iter().filter(|&&m| m).count();
+            let cans = ctx.state.last_cans();
+            self.weights
+                .push(weights.weight(&cans, legal_actions, at_kan_select));
+        }
         self.masks.push(mask);
         self.at_kyoku.push(ctx.kyoku_idx as u8);
         // only discard and kan will discount
         self.apply_gamma.push(label <= 37);
         self.at_turns.push(ctx.state.at_turn());
         self.shantens.push(ctx.state.shanten());
+        self.think_ms.push(think_ms);
+        if ctx.record_scenes {
+            let scene = (!at_kan_select).then(|| ctx.state.clone());
+            self.teacher_scenes.push(scene);
+        }
 
         if let Some(invisibles) = ctx.invisibles {
             let invisible_obs = invisibles[ctx.kyoku_idx].encode(
                 &ctx.opponent_states,
                 ctx.yama_idx,
                 ctx.rinshan_idx,
+                ctx.suit_perm,
             );
             self.invisible_obs.push(invisible_obs);
         }
     }
 }
+
+impl Gameplay {
+    /// Fills in `teacher_qs` and `teacher_masks` by evaluating every recorded
+    /// decision on `teacher` in one batch, which must have as many indices.
+    ///
+    /// A teacher that needs oracle obs gets `invisible_obs`, which is only
+    /// possible without suit augmentation, as it sees the states as they are.
+    fn distill(&mut self, teacher: &mut dyn BatchAgent) -> Result<()> {
+        let scenes = mem::take(&mut self.teacher_scenes);
+        let len = self.actions.len();
+        ensure!(
+            scenes.len() == len,
+            "got {} scenes for {len} entries",
+            scenes.len(),
+        );
+        let need_oracle = teacher.need_oracle_obs();
+        ensure!(
+            !need_oracle || self.suit_perm.is_identity() && self.invisible_obs.len() == len,
+            "the teacher needs oracle obs, which requires `oracle` without `augment_suits`",
+        );
+        let invisible = |i: usize| need_oracle.then(|| self.invisible_obs[i].clone());
+
+        let decisions: Vec<_> = scenes
+            .iter()
+            .enumerate()
+            .filter_map(|(i, s)| s.as_ref().map(|s| (i, s)))
+            .collect();
+        for (index, &(i, state)) in decisions.iter().enumerate() {
+            teacher.set_scene(index, &[], state, invisible(i))?;
+        }
+
+        let metas = decisions
+            .iter()
+            .enumerate()
+            .map(|(index, &(i, state))| {
+                let reaction = teacher.get_reaction(index, &[], state, invisible(i))?;
+                Ok((i, reaction.meta))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        self.teacher_qs = vec![Array1::zeros(ACTION_SPACE); len];
+        self.teacher_masks = vec![Array1::from_elem(ACTION_SPACE, false); len];
+        for (i, meta) in metas {
+            let Some(meta) = meta else {
+                continue;
+            };
+            self.set_teacher_target(i, &meta, false);
+            if let (Some(kan), Some(None)) = (&meta.kan_select, scenes.get(i + 1)) {
+                self.set_teacher_target(i + 1, kan, true);
+            }
+        }
+        Ok(())
+    }
+
+    fn set_teacher_target(&mut self, i: usize, meta: &Metadata, at_kan_select: bool) {
+        let (Some(q_values), Some(mask_bits)) = (&meta.q_values, meta.mask_bits) else {
+            return;
+        };
+        let actions = (0..ACTION_SPACE).filter(|a| mask_bits & (1 << a) != 0);
+        for (action, &q) in actions.zip(q_values) {
+            let action = self.suit_perm.action(action, at_kan_select);
+            self.teacher_qs[i][action] = q;
+            self.teacher_masks[i][action] = true;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::agent::InvisibleState;
+    use crate::dataset::packed::{PackedReader, PackedWriter};
+    use crate::mjai::{EventExt, Room};
+
+    #[test]
+    fn filters() {
+        let meta = GameMeta {
+            dans: Some([16, 15, 17, 18]),
+            rates: Some([2100, 1990, 2200, 2000]),
+            room: Some(Room::Houou),
+            rule: Some(RuleSet::tenhou()),
+        };
+
+        let loader = GameplayLoader::default();
+        assert!(loader.accepts_game(None));
+        assert!(loader.accepts_game(Some(&meta)));
+        assert!((0..4).all(|seat| loader.accepts_seat(None, seat)));
+
+        let loader = GameplayLoader {
+            rooms: vec!["tokujou".to_owned(), "houou".to_owned()],
+            tonpuusen: Some(false),
+            ..Default::default()
+        };
+        assert!(!loader.accepts_game(None));
+        assert!(loader.accepts_game(Some(&meta)));
+        let loader = GameplayLoader {
+            tonpuusen: Some(true),
+            ..Default::default()
+        };
+        assert!(!loader.accepts_game(Some(&meta)));
+
+        let loader = GameplayLoader {
+            seats: Some(vec![1, 2, 3]),
+            min_dan: Some(16),
+            min_rate: Some(2050),
+            ..Default::default()
+        };
+        assert!(loader.accepts_game(Some(&meta)));
+        let accepted: Vec<_> = (0..4)
+            .filter(|&seat| loader.accepts_seat(Some(&meta), seat))
+            .collect();
+        assert_eq!(accepted, [2]);
+        assert!(!loader.accepts_seat(None, 2));
+    }
+    /// Reports the action ID as the q value of every legal action.
+    struct IdTeacher;
+
+    impl BatchAgent for IdTeacher {
+        fn name(&self) -> String {
+            "id".to_owned()
+        }
+        fn set_scene(
+            &mut self,
+            _: usize,
+            _: &[EventExt],
+            _: &PlayerState,
+            _: Option<InvisibleState>,
+        ) -> Result<()> {
+            Ok(())
+        }
+        fn get_reaction(
+            &mut self,
+            _: usize,
+            _: &[EventExt],
+            state: &PlayerState,
+            _: Option<InvisibleState>,
+        ) -> Result<EventExt> {
+            let (_, mask) = state.encode_obs_with(false, ObsVersion::V1, SuitPerm::IDENTITY);
+            let legal: Vec<_> = (0..ACTION_SPACE).filter(|&a| mask[a]).collect();
+            Ok(EventExt {
+                meta: Some(Metadata {
+                    q_values: Some(legal.iter().map(|&a| a as f32).collect()),
+                    mask_bits: Some(legal.iter().fold(0, |acc, a| acc | (1 << a))),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            })
+        }
+    }
+
+    #[test]
+    fn teacher() {
+        let events: Vec<Event> = include_str!("../../tests/data/pack_test.json")
+            .lines()
+            .map(|l| json::from_str(l).unwrap())
+            .collect();
+        let new_teacher = |_: &[u8]| -> Result<Box<dyn BatchAgent>> { Ok(Box::new(IdTeacher)) };
+
+        let loader = GameplayLoader {
+            augment_suits: true,
+            ..Default::default()
+        };
+        let games = loader
+            .load_events_with_teacher(&events, &new_teacher)
+            .unwrap();
+        for game in &games {
+            assert_eq!(game.teacher_qs.len(), game.actions.len());
+            // The targets are permuted the same way as the masks.
+            assert_eq!(game.teacher_masks, game.masks);
+            for (qs, masks) in game.teacher_qs.iter().zip(&game.teacher_masks) {
+                for a in (0..ACTION_SPACE).filter(|&a| masks[a]) {
+                    let orig = (0..ACTION_SPACE)
+                        .find(|&o| game.suit_perm.action(o, false) == a)
+                        .unwrap();
+                    assert_eq!(qs[a] as usize, orig);
+                }
+            }
+        }
+
+        let mut writer = PackedWriter::new(vec![]).unwrap();
+        for game in &games {
+            writer.write(game).unwrap();
+        }
+        let buf = writer.finish().unwrap();
+        let read: Vec<_> = PackedReader::new(&buf[..])
+            .unwrap()
+            .collect::<Result<_>>()
+            .unwrap();
+        for (a, b) in games.iter().zip(&read) {
+            assert_eq!(a.teacher_qs, b.teacher_qs);
+            assert_eq!(a.teacher_masks, b.teacher_masks);
+        }
+
+        let games = GameplayLoader::default().load_events(&events).unwrap();
+        assert!(games.iter().all(|g| g.teacher_qs.is_empty()));
+    }
+
+    #[test]
+    fn think_ms() {
+        let raw = include_str!("../../tests/data/pack_test.json");
+        let loader = GameplayLoader::default();
+        let games = loader.load_log(raw).unwrap();
+        assert!(games.iter().all(|g| g.think_ms.iter().all(Option::is_none)));
+
+        let timed = raw.replace(
+            r#""tsumogiri":true}"#,
+            r#""tsumogiri":true,"timing":{"timestamp_ms":1000,"think_ms":1500}}"#,
+        );
+        let games = loader.load_log(&timed).unwrap();
+        assert_eq!(games[0].think_ms, [Some(1500)]);
+        assert_eq!(games[1].think_ms, [None]);
+    }
+}
@@ -1,19 +1,22 @@
+use super::RewardTable;
 use crate::consts::GRP_SIZE;
 use crate::mjai::Event;
-use crate::tu8;
+use crate::par::*;
+use crate::state::PlayerState;
+use crate::tile::Tile;
 use crate::vec_ops::vec_add_assign;
-use std::fs::File;
-use std::io::prelude::*;
+use crate::{log_io, tu8};
+#[cfg(feature = "python")]
 use std::mem;
 
-use anyhow::{Context, Result};
-use flate2::read::GzDecoder;
+use anyhow::{ensure, Context, Result};
 use ndarray::prelude::*;
-use numpy::PyArray2;
+#[cfg(feature = "python")]
+use numpy::{PyArray1, PyArray2};
+#[cfg(feature = "python")]
 use pyo3::prelude::*;
-use rayon::prelude::*;
+use riichi_macros::{pyclass, pymethods};
 use serde_json as json;
-use tinyvec::array_vec;
 
 #[pyclass]
 #[derive(Clone, Default)]
@@ -24,6 +27,19 @@ pub struct Grp {
     pub final_scores: [i32; 4],
 }
 
+/// What GRP takes of a kyoku, as of its start.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GrpKyoku {
+    /// Counts from 0 at E1.
+    pub grand_kyoku: u8,
+    pub honba: u8,
+    pub kyotaku: u8,
+    /// Must be `grand_kyoku % 4`, as seat 0 is the oya of E1.
+    pub oya: u8,
+    /// In the absolute seat order.
+    pub scores: [i32; 4],
+}
+
 #[pymethods]
 impl Grp {
     #[staticmethod]
@@ -44,7 +60,71 @@ impl Grp {
         Self::load_gz_log_files(gzip_filenames)
     }
 
+    /// Encodes the GRP feature of one kyoku, the same as a row of
+    /// `take_feature`, where `grand_kyoku` counts from 0 at E1 and `scores`
+    /// are in the absolute seat order with seat 0 being the oya of E1.
+    #[cfg(feature = "python")]
+    #[staticmethod]
+    #[pyo3(name = "encode_kyoku")]
+    #[pyo3(text_signature = "(grand_kyoku, honba, kyotaku, oya, scores, /)")]
+    fn encode_kyoku_py(
+        grand_kyoku: u8,
+        honba: u8,
+        kyotaku: u8,
+        oya: u8,
+        scores: [i32; 4],
+        py: Python<'_>,
+    ) -> Result<&PyArray1<f64>> {
+        let kyoku = GrpKyoku {
+            grand_kyoku,
+            honba,
+            kyotaku,
+            oya,
+            scores,
+        };
+        let feature = kyoku.encode()?;
+        Ok(PyArray1::from_slice(py, &feature))
+    }
+
+    /// Batch version of `encode_kyoku`, taking a list of tuples of its
+    /// arguments and returning a 2D array.
+    #[cfg(feature = "python")]
+    #[staticmethod]
+    #[pyo3(name = "encode_kyoku_batch")]
+    #[pyo3(text_signature = "(kyokus, /)")]
+    fn encode_kyoku_batch_py(
+        kyokus: Vec<(u8, u8, u8, u8, [i32; 4])>,
+        py: Python<'_>,
+    ) -> Result<&PyArray2<f64>> {
+        let kyokus: Vec<_> = kyokus
+            .into_iter()
+            .map(|(grand_kyoku, honba, kyotaku, oya, scores)| GrpKyoku {
+                grand_kyoku,
+                honba,
+                kyotaku,
+                oya,
+                scores,
+            })
+            .collect();
+        Ok(PyArray2::from_owned_array(py, Self::encode_batch(&kyokus)?))
+    }
+
+    /// Encodes the GRP feature of the kyoku `state` is in, from the scores,
+    /// honba and kyotaku as of now.
+    #[cfg(feature = "python")]
+    #[staticmethod]
+    #[pyo3(name = "encode_player_state")]
+    #[pyo3(text_signature = "(state, /)")]
+    fn encode_player_state_py<'py>(
+        state: &PlayerState,
+        py: Python<'py>,
+    ) -> Result<&'py PyArray1<f64>> {
+        let feature = GrpKyoku::from_player_state(state)?.encode()?;
+        Ok(PyArray1::from_slice(py, &feature))
+    }
+
     /// Returns List[List[np.ndarray]]
+    #[cfg(feature = "python")]
     #[pyo3(text_signature = "($self, /)")]
     pub fn take_feature<'py>(&mut self, py: Python<'py>) -> &'py PyArray2<f64> {
         PyArray2::from_owned_array(py, mem::take(&mut self.feature))
@@ -57,6 +137,14 @@ impl Grp {
     pub fn take_final_scores(&mut self) -> [i32; 4] {
         self.final_scores
     }
+
+    /// Returns the reward of each kyoku to `player_id` under `table`, which
+    /// must be called before `take_feature`.
+    #[pyo3(name = "kyoku_rewards")]
+    #[pyo3(text_signature = "($self, player_id, table, /)")]
+    fn kyoku_rewards_py(&self, player_id: u8, table: RewardTable) -> Vec<f64> {
+        table.kyoku_rewards(self, player_id)
+    }
 }
 
 impl Grp {
@@ -70,6 +158,17 @@ impl Grp {
         self.len() == 0
     }
 
+    /// Encodes `kyokus` into a feature of shape `(kyokus.len(), GRP_SIZE)`,
+    /// the same as the one of a loaded game.
+    pub fn encode_batch(kyokus: &[GrpKyoku]) -> Result<Array2<f64>> {
+        let mut feature = Array2::zeros((kyokus.len(), GRP_SIZE));
+        for (mut row, kyoku) in feature.rows_mut().into_iter().zip(kyokus) {
+            row.assign(&aview1(&kyoku.encode()?));
+        }
+        Ok(feature)
+    }
+
+    /// Despite the name, the files may be plain, gzip or zstd compressed.
     pub fn load_gz_log_files<V, S>(gzip_filenames: V) -> Result<Vec<Self>>
     where
         V: IntoParallelIterator<Item = S>,
@@ -80,10 +179,7 @@ impl Grp {
             .map(|f| {
                 let filename = f.as_ref();
                 let inner = || {
-                    let file = File::open(filename)?;
-                    let mut gz = GzDecoder::new(file);
-                    let mut raw = String::new();
-                    gz.read_to_string(&mut raw)?;
+                    let raw = log_io::read_log(filename)?;
                     Self::load_log(&raw)
                 };
                 inner().with_context(|| format!("error when reading {filename}"))
@@ -92,7 +188,7 @@ impl Grp {
     }
 
     pub fn load_events(events: &[Event]) -> Result<Self> {
-        let mut game_info = vec![];
+        let mut kyokus = vec![];
         let mut rank_by_player_opt = None;
         let mut final_deltas = [0; 4];
         let mut final_scores = [0; 4];
@@ -113,12 +209,7 @@ impl Grp {
                     }
                 }
                 Event::StartKyoku {
-                    bakaze,
-                    kyoku,
-                    honba,
-                    kyotaku,
-                    scores,
-                    ..
+                    kyotaku, scores, ..
                 } => {
                     if rank_by_player_opt.is_none() {
                         final_scores = scores;
@@ -140,20 +231,9 @@ impl Grp {
                         rank_by_player_opt = Some(rank_by_player);
                     }
 
-                    let mut kyoku_info = array_vec!([_; GRP_SIZE]);
-                    let grand_kyoku = match bakaze.as_u8() {
-                        tu8!(E) => kyoku - 1,
-                        tu8!(S) => 3 + kyoku,
-                        _ => 7 + kyoku,
-                    };
-                    kyoku_info.push(grand_kyoku as f64);
-                    kyoku_info.push(honba as f64);
-                    kyoku_info.push(kyotaku as f64);
-                    // assume player 0 is the oya at E1
-                    kyoku_info.extend(scores.iter().map(|&score| score as f64 / 10000.));
-                    assert_eq!(kyoku_info.len(), GRP_SIZE);
-
-                    game_info.insert(0, kyoku_info);
+                    let kyoku = GrpKyoku::from_start_kyoku(ev)
+                        .context("invalid log: start_kyoku of an unexpected kyoku")?;
+                    kyokus.push(kyoku);
                 }
 
                 _ => (),
@@ -162,8 +242,8 @@ impl Grp {
 
         let rank_by_player =
             rank_by_player_opt.context("invalid log: no Hora or Ryukyoku after a StartKyoku")?;
-        let shape = (game_info.len(), GRP_SIZE);
-        let feature = Array::from_iter(game_info.into_iter().flatten()).into_shape(shape)?;
+        kyokus.reverse();
+        let feature = Self::encode_batch(&kyokus)?;
 
         Ok(Self {
             feature,
@@ -172,3 +252,143 @@ impl Grp {
         })
     }
 }
+
+impl GrpKyoku {
+    /// Returns `None` if `ev` is not a `StartKyoku`.
+    #[must_use]
+    pub fn from_start_kyoku(ev: &Event) -> Option<Self> {
+        let Event::StartKyoku {
+            bakaze,
+            kyoku,
+            honba,
+            kyotaku,
+            oya,
+            scores,
+            ..
+        } = *ev
+        else {
+            return None;
+        };
+        Some(Self {
+            grand_kyoku: grand_kyoku_of(bakaze, kyoku - 1)?,
+            honba,
+            kyotaku,
+            oya,
+            scores,
+        })
+    }
+
+    /// The kyoku `state` is in, with the scores, honba and kyotaku as of now.
+    pub fn from_player_state(state: &PlayerState) -> Result<Self> {
+        let player_id = state.player_id();
+        let rel_scores = state.scores();
+        let scores = [0, 1, 2, 3].map(|seat| rel_scores[(seat + 4 - player_id as usize) % 4]);
+        let grand_kyoku = grand_kyoku_of(state.bakaze(), state.kyoku() - 1)
+            .with_context(|| format!("unexpected bakaze {}", state.bakaze()))?;
+        Ok(Self {
+            grand_kyoku,
+            honba: state.honba(),
+            kyotaku: state.kyotaku(),
+            oya: (state.oya() + player_id) % 4,
+            scores,
+        })
+    }
+
+    /// `[grand_kyoku, honba, kyotaku, scores[i] / 10000 for each i]`.
+    pub fn encode(&self) -> Result<[f64; GRP_SIZE]> {
+        ensure!(
+            self.oya == self.grand_kyoku % 4,
+            "oya is {} in grand kyoku {}, expected seat 0 to be the oya of E1",
+            self.oya,
+            self.grand_kyoku,
+        );
+        let [a, b, c, d] = self.scores.map(|s| s as f64 / 10000.);
+        Ok([
+            self.grand_kyoku as f64,
+            self.honba as f64,
+            self.kyotaku as f64,
+            a,
+            b,
+            c,
+            d,
+        ])
+    }
+}
+
+/// `kyoku` counts from 0.
+fn grand_kyoku_of(bakaze: Tile, kyoku: u8) -> Option<u8> {
+    let bakaze = bakaze.as_u8();
+    (tu8!(E)..=tu8!(N))
+        .contains(&bakaze)
+        .then(|| (bakaze - tu8!(E)) * 4 + kyoku)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::t;
+
+    #[test]
+    fn encode() {
+        let kyoku = GrpKyoku {
+            grand_kyoku: 5,
+            honba: 2,
+            kyotaku: 1,
+            oya: 1,
+            scores: [25000, 32000, 18000, 24000],
+        };
+        let feature = kyoku.encode().unwrap();
+        let expected = [5., 2., 1., 2.5, 3.2, 1.8, 2.4];
+        assert!(feature
+            .iter()
+            .zip(expected)
+            .all(|(a, b)| (a - b).abs() < f64::EPSILON));
+        let wrong_oya = GrpKyoku { oya: 0, ..kyoku };
+        assert!(wrong_oya.encode().is_err());
+
+        // The same kyoku from the view of seat 2.
+        let mut tehais = [[t!(?); 13]; 4];
+        tehais[2] = [
+            t!(1m),
+            t!(2m),
+            t!(3m),
+            t!(4m),
+            t!(5m),
+            t!(6m),
+            t!(7m),
+            t!(8m),
+            t!(9m),
+            t!(1p),
+            t!(2p),
+            t!(3p),
+            t!(4p),
+        ];
+        let mut state = PlayerState::new(2);
+        state
+            .update(&Event::StartKyoku {
+                bakaze: t!(S),
+                dora_marker: t!(1m),
+                kyoku: 2,
+                honba: 2,
+                kyotaku: 1,
+                oya: 1,
+                scores: kyoku.scores,
+                tehais,
+            })
+            .unwrap();
+        assert_eq!(GrpKyoku::from_player_state(&state).unwrap(), kyoku);
+
+        let events: Vec<Event> = include_str!("../../tests/data/pack_test.json")
+            .lines()
+            .map(|l| json::from_str(l).unwrap())
+            .collect();
+        let grp = Grp::load_events(&events).unwrap();
+        let kyoku = GrpKyoku::from_start_kyoku(&events[1]).unwrap();
+        let batch = Grp::encode_batch(&[kyoku]).unwrap();
+        assert!(grp
+            .feature
+            .iter()
+            .zip(&batch)
+            .all(|(a, b)| (a - b).abs() < f64::EPSILON));
+    }
+}
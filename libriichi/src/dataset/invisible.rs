@@ -1,7 +1,7 @@
 use crate::arena::Board;
 use crate::consts::ORACLE_OBS_SHAPE;
 use crate::mjai::Event;
-use crate::state::PlayerState;
+use crate::state::{PlayerState, SuitPerm};
 use crate::tile::Tile;
 use crate::{must_tile, tu8, tuz};
 use std::iter;
@@ -152,11 +152,13 @@ impl Invisible {
         opponent_states: &[PlayerState; 3],
         yama_idx: usize,
         rinshan_idx: usize,
+        perm: SuitPerm,
     ) -> Array2<f32> {
         let mut arr = Array2::zeros(ORACLE_OBS_SHAPE);
         let mut idx = 0;
+        let mut aka_rows = [0; 3];
 
-        for state in opponent_states {
+        for (i, state) in opponent_states.iter().enumerate() {
             state
                 .tehai()
                 .iter()
@@ -168,11 +170,12 @@ impl Invisible {
                 });
             idx += 4;
 
+            aka_rows[i] = idx;
             state
                 .akas_in_hand()
                 .iter()
                 .enumerate()
-                .filter(|(_, &has_it)| has_it)
+                .filter(|(_, &n)| n > 0)
                 .for_each(|(i, _)| {
                     arr.slice_mut(s![idx + i, ..]).fill(1.);
                 });
@@ -229,6 +232,7 @@ impl Invisible {
         }
 
         assert_eq!(idx, ORACLE_OBS_SHAPE.0);
+        perm.apply_to_obs(arr.view_mut(), &aka_rows);
         arr
     }
 }
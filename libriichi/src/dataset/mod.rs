@@ -3,20 +3,37 @@
 mod gameplay;
 mod grp;
 mod invisible;
+mod packed;
 mod player_list;
+mod reward;
+mod shard;
+mod weight;
 
+#[cfg(feature = "python")]
 use crate::py_helper::add_submodule;
 pub use gameplay::{Gameplay, GameplayLoader, Quality};
 pub use grp::Grp;
 pub use invisible::Invisible;
+pub use packed::PackedWriter;
+#[cfg(feature = "python")]
+pub use packed::{GameplayReader, GameplayWriter};
+pub use reward::RewardTable;
+pub use shard::EncodeSummary;
+pub use weight::SampleWeights;
 
+#[cfg(feature = "python")]
 use pyo3::prelude::*;
 
+#[cfg(feature = "python")]
 pub(crate) fn register_module(py: Python<'_>, prefix: &str, super_mod: &PyModule) -> PyResult<()> {
     let m = PyModule::new(py, "dataset")?;
     m.add_class::<Gameplay>()?;
     m.add_class::<GameplayLoader>()?;
+    m.add_class::<GameplayReader>()?;
+    m.add_class::<GameplayWriter>()?;
     m.add_class::<Quality>()?;
     m.add_class::<Grp>()?;
+    m.add_class::<RewardTable>()?;
+    m.add_class::<SampleWeights>()?;
     add_submodule(py, prefix, super_mod, m)
 }
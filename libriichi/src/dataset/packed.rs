@@ -0,0 +1,497 @@
+//! A compact on-disk format of [`Gameplay`], so that the logs do not have to
+//! be parsed and encoded again on every training run.
+//!
+//! A file is a gzip stream of a header followed by the games one after
+//! another, which is read back game by game. The obs are stored row by row,
+//! where most of the rows are either all zeros, filled with a single value, or
+//! one-hot-like, and only the rest are stored as dense `f32`s.
+
+use super::{Gameplay, Grp, Quality};
+use crate::consts::ACTION_SPACE;
+use crate::state::SuitPerm;
+use std::fs::File;
+use std::io::{self, prelude::*, BufReader, BufWriter};
+
+use anyhow::{bail, ensure, Context, Result};
+use byteorder::{LittleEndian as LE, ReadBytesExt, WriteBytesExt};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use ndarray::prelude::*;
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+use riichi_macros::{pyclass, pymethods};
+
+const MAGIC: &[u8; 4] = b"MRPK";
+const FORMAT_VERSION: u8 = 1;
+
+// Tags of the rows of an obs.
+const ROW_ZEROS: u8 = 0;
+const ROW_FILLED: u8 = 1;
+const ROW_ONES_AT: u8 = 2;
+const ROW_DENSE: u8 = 3;
+
+const FLAG_DONE: u8 = 0b01;
+const FLAG_APPLY_GAMMA: u8 = 0b10;
+
+/// `think_ms` of `None`.
+const NO_THINK_MS: u32 = u32::MAX;
+
+pub struct PackedWriter<W: Write> {
+    inner: W,
+}
+
+pub struct PackedReader<R: Read> {
+    inner: R,
+}
+
+/// Writes `Gameplay`s into a packed file, see `GameplayReader` for reading
+/// them back.
+#[pyclass]
+#[pyo3(text_signature = "(filename, /)")]
+pub struct GameplayWriter {
+    writer: Option<PackedWriter<GzEncoder<BufWriter<File>>>>,
+}
+
+/// Iterates over the `Gameplay`s in a packed file written by
+/// `GameplayWriter` or `GameplayLoader.pack_gz_log_files`.
+#[pyclass]
+#[pyo3(text_signature = "(filename, /)")]
+pub struct GameplayReader {
+    reader: PackedReader<GzDecoder<BufReader<File>>>,
+}
+
+#[pymethods]
+impl GameplayWriter {
+    #[new]
+    fn new(filename: &str) -> Result<Self> {
+        Ok(Self {
+            writer: Some(PackedWriter::create(filename)?),
+        })
+    }
+
+    #[pyo3(text_signature = "($self, gameplay, /)")]
+    fn write(&mut self, gameplay: &Gameplay) -> Result<()> {
+        let Some(writer) = &mut self.writer else {
+            bail!("the writer is closed");
+        };
+        writer.write(gameplay)
+    }
+
+    /// Flushes and closes the file. Nothing can be written after it.
+    #[pyo3(text_signature = "($self, /)")]
+    fn close(&mut self) -> Result<()> {
+        if let Some(writer) = self.writer.take() {
+            writer.close()?;
+        }
+        Ok(())
+    }
+}
+
+#[pymethods]
+impl GameplayReader {
+    #[new]
+    fn new(filename: &str) -> Result<Self> {
+        Ok(Self {
+            reader: PackedReader::open(filename)?,
+        })
+    }
+
+    #[cfg(feature = "python")]
+    const fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+    #[cfg(feature = "python")]
+    fn __next__(mut slf: PyRefMut<'_, Self>) -> Result<Option<Gameplay>> {
+        slf.reader.next().transpose()
+    }
+}
+
+impl<W: Write> PackedWriter<W> {
+    pub fn new(mut inner: W) -> Result<Self> {
+        inner.write_all(MAGIC)?;
+        inner.write_u8(FORMAT_VERSION)?;
+        Ok(Self { inner })
+    }
+
+    pub fn write(&mut self, game: &Gameplay) -> Result<()> {
+        let w = &mut self.inner;
+
+        w.write_u8(game.player_id)?;
+        w.write_u8(game.quality as u8)?;
+        w.write_all(&game.suit_perm.as_array())?;
+        let name = game.player_name.as_bytes();
+        w.write_u16::<LE>(name.len().try_into()?)?;
+        w.write_all(name)?;
+
+        let (rows, cols) = game.grp.feature.dim();
+        w.write_u16::<LE>(rows.try_into()?)?;
+        w.write_u16::<LE>(cols.try_into()?)?;
+        for &v in &game.grp.feature {
+            w.write_f64::<LE>(v)?;
+        }
+        w.write_all(&game.grp.rank_by_player)?;
+        for score in game.grp.final_scores {
+            w.write_i32::<LE>(score)?;
+        }
+        w.write_u16::<LE>(game.kyoku_rewards.len().try_into()?)?;
+        for &v in &game.kyoku_rewards {
+            w.write_f64::<LE>(v)?;
+        }
+
+        let len = game.obs.len();
+        let has_oracle = !game.invisible_obs.is_empty();
+        let has_teacher = !game.teacher_qs.is_empty();
+        let has_weights = !game.weights.is_empty();
+        ensure!(
+            [
+                game.actions.len(),
+                game.masks.len(),
+                game.at_kyoku.len(),
+                game.dones.len(),
+                game.apply_gamma.len(),
+                game.at_turns.len(),
+                game.shantens.len(),
+                game.think_ms.len(),
+            ]
+            .iter()
+            .all(|&l| l == len)
+                && (!has_oracle || game.invisible_obs.len() == len)
+                && (!has_teacher
+                    || game.teacher_qs.len() == len && game.teacher_masks.len() == len)
+                && (!has_weights || game.weights.len() == len),
+            "the entries of the gameplay are of different lengths",
+        );
+        w.write_u32::<LE>(len.try_into()?)?;
+        w.write_u8(has_oracle as u8)?;
+        w.write_u8(has_teacher as u8)?;
+        w.write_u8(has_weights as u8)?;
+
+        for i in 0..len {
+            w.write_u8(game.actions[i].try_into()?)?;
+            let mask_bits = game.masks[i]
+                .iter()
+                .enumerate()
+                .filter(|(_, &b)| b)
+                .fold(0_u64, |acc, (a, _)| acc | (1 << a));
+            w.write_u64::<LE>(mask_bits)?;
+            w.write_u8(game.at_kyoku[i])?;
+            let mut flags = 0;
+            if game.dones[i] {
+                flags |= FLAG_DONE;
+            }
+            if game.apply_gamma[i] {
+                flags |= FLAG_APPLY_GAMMA;
+            }
+            w.write_u8(flags)?;
+            w.write_u8(game.at_turns[i])?;
+            w.write_i8(game.shantens[i])?;
+            w.write_u32::<LE>(game.think_ms[i].unwrap_or(NO_THINK_MS))?;
+            write_obs(w, &game.obs[i])?;
+            if has_oracle {
+                write_obs(w, &game.invisible_obs[i])?;
+            }
+            if has_weights {
+                w.write_f32::<LE>(game.weights[i])?;
+            }
+            if has_teacher {
+                // Only the q values in the mask are stored.
+                let targets = || {
+                    game.teacher_qs[i]
+                        .iter()
+                        .zip(&game.teacher_masks[i])
+                        .enumerate()
+                        .filter(|(_, (_, &m))| m)
+                };
+                let mask_bits = targets().fold(0_u64, |acc, (a, _)| acc | (1 << a));
+                w.write_u64::<LE>(mask_bits)?;
+                for (_, (&q, _)) in targets() {
+                    w.write_f32::<LE>(q)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Flushes and returns the inner writer.
+    pub fn finish(mut self) -> Result<W> {
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+impl PackedWriter<GzEncoder<BufWriter<File>>> {
+    /// Creates a packed file at `filename`, which must be `close`d.
+    pub fn create(filename: &str) -> Result<Self> {
+        let file =
+            File::create(filename).with_context(|| format!("failed to create {filename}"))?;
+        Self::new(GzEncoder::new(BufWriter::new(file), Compression::default()))
+    }
+
+    pub fn close(self) -> Result<()> {
+        self.finish()?.finish()?.flush()?;
+        Ok(())
+    }
+}
+
+impl PackedReader<GzDecoder<BufReader<File>>> {
+    pub fn open(filename: &str) -> Result<Self> {
+        let file = File::open(filename).with_context(|| format!("failed to open {filename}"))?;
+        Self::new(GzDecoder::new(BufReader::new(file)))
+    }
+}
+
+impl<R: Read> PackedReader<R> {
+    pub fn new(mut inner: R) -> Result<Self> {
+        let mut magic = [0; 4];
+        inner
+            .read_exact(&mut magic)
+            .context("failed to read the header")?;
+        ensure!(&magic == MAGIC, "not a packed gameplay file");
+        let version = inner.read_u8()?;
+        ensure!(
+            version == FORMAT_VERSION,
+            "unsupported format version {version}, expected {FORMAT_VERSION}",
+        );
+        Ok(Self { inner })
+    }
+
+    /// Reads the next game, or returns `None` at the end of the file.
+    pub fn read(&mut self) -> Result<Option<Gameplay>> {
+        let r = &mut self.inner;
+
+        let player_id = match r.read_u8() {
+            Ok(v) => v,
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+        let quality = match r.read_u8()? {
+            0 => Quality::LastPlace,
+            1 => Quality::Normal,
+            2 => Quality::Top300,
+            3 => Quality::Tenhoui,
+            v => bail!("invalid quality {v}"),
+        };
+        let mut perm = [0; 3];
+        r.read_exact(&mut perm)?;
+        let suit_perm = SuitPerm::new(perm)?;
+        let mut name = vec![0; r.read_u16::<LE>()? as usize];
+        r.read_exact(&mut name)?;
+        let player_name = String::from_utf8(name)?;
+
+        let rows = r.read_u16::<LE>()? as usize;
+        let cols = r.read_u16::<LE>()? as usize;
+        let mut feature = Array2::zeros((rows, cols));
+        for v in &mut feature {
+            *v = r.read_f64::<LE>()?;
+        }
+        let mut rank_by_player = [0; 4];
+        r.read_exact(&mut rank_by_player)?;
+        let mut final_scores = [0; 4];
+        for score in &mut final_scores {
+            *score = r.read_i32::<LE>()?;
+        }
+        let kyoku_rewards = (0..r.read_u16::<LE>()?)
+            .map(|_| r.read_f64::<LE>())
+            .collect::<io::Result<_>>()?;
+
+        let len = r.read_u32::<LE>()? as usize;
+        let has_oracle = r.read_u8()? != 0;
+        let has_teacher = r.read_u8()? != 0;
+        let has_weights = r.read_u8()? != 0;
+        let mut game = Gameplay {
+            grp: Grp {
+                feature,
+                rank_by_player,
+                final_scores,
+            },
+            kyoku_rewards,
+            suit_perm,
+            player_id,
+            player_name,
+            quality,
+            ..Default::default()
+        };
+
+        for _ in 0..len {
+            let action = r.read_u8()?;
+            ensure!((action as usize) < ACTION_SPACE, "invalid action {action}");
+            game.actions.push(action as i64);
+            let mask_bits = r.read_u64::<LE>()?;
+            game.masks.push(
+                (0..ACTION_SPACE)
+                    .map(|a| mask_bits & (1 << a) != 0)
+                    .collect(),
+            );
+            game.at_kyoku.push(r.read_u8()?);
+            let flags = r.read_u8()?;
+            game.dones.push(flags & FLAG_DONE != 0);
+            game.apply_gamma.push(flags & FLAG_APPLY_GAMMA != 0);
+            game.at_turns.push(r.read_u8()?);
+            game.shantens.push(r.read_i8()?);
+            let think_ms = r.read_u32::<LE>()?;
+            game.think_ms
+                .push(Some(think_ms).filter(|&v| v != NO_THINK_MS));
+            game.obs.push(read_obs(r)?);
+            if has_oracle {
+                game.invisible_obs.push(read_obs(r)?);
+            }
+            if has_weights {
+                game.weights.push(r.read_f32::<LE>()?);
+            }
+            if has_teacher {
+                let mask_bits = r.read_u64::<LE>()?;
+                let mut qs = Array1::zeros(ACTION_SPACE);
+                let mut masks = Array1::from_elem(ACTION_SPACE, false);
+                for a in (0..ACTION_SPACE).filter(|a| mask_bits & (1 << a) != 0) {
+                    qs[a] = r.read_f32::<LE>()?;
+                    masks[a] = true;
+                }
+                game.teacher_qs.push(qs);
+                game.teacher_masks.push(masks);
+            }
+        }
+
+        Ok(Some(game))
+    }
+}
+
+impl<R: Read> Iterator for PackedReader<R> {
+    type Item = Result<Gameplay>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read().transpose()
+    }
+}
+
+fn write_obs<W: Write>(w: &mut W, obs: &Array2<f32>) -> Result<()> {
+    let (rows, cols) = obs.dim();
+    ensure!(cols <= u8::MAX as usize, "too many columns: {cols}");
+    w.write_u16::<LE>(rows.try_into()?)?;
+    w.write_u8(cols as u8)?;
+
+    for row in obs.rows() {
+        let first = row[0];
+        if row.iter().all(|&v| v.to_bits() == first.to_bits()) {
+            if first.to_bits() == 0 {
+                w.write_u8(ROW_ZEROS)?;
+            } else {
+                w.write_u8(ROW_FILLED)?;
+                w.write_f32::<LE>(first)?;
+            }
+        } else if row
+            .iter()
+            .all(|&v| v.to_bits() == 0 || v.to_bits() == 1_f32.to_bits())
+        {
+            w.write_u8(ROW_ONES_AT)?;
+            let ones = row.iter().filter(|&&v| v.to_bits() != 0).count();
+            w.write_u8(ones as u8)?;
+            for (col, _) in row.iter().enumerate().filter(|(_, &v)| v.to_bits() != 0) {
+                w.write_u8(col as u8)?;
+            }
+        } else {
+            w.write_u8(ROW_DENSE)?;
+            for &v in row {
+                w.write_f32::<LE>(v)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn read_obs<R: Read>(r: &mut R) -> Result<Array2<f32>> {
+    let rows = r.read_u16::<LE>()? as usize;
+    let cols = r.read_u8()? as usize;
+    let mut obs = Array2::zeros((rows, cols));
+
+    for mut row in obs.rows_mut() {
+        match r.read_u8()? {
+            ROW_ZEROS => (),
+            ROW_FILLED => row.fill(r.read_f32::<LE>()?),
+            ROW_ONES_AT => {
+                for _ in 0..r.read_u8()? {
+                    let col = r.read_u8()? as usize;
+                    ensure!(col < cols, "column {col} out of range");
+                    row[col] = 1.;
+                }
+            }
+            ROW_DENSE => {
+                for v in &mut row {
+                    *v = r.read_f32::<LE>()?;
+                }
+            }
+            tag => bail!("invalid row tag {tag}"),
+        }
+    }
+
+    Ok(obs)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::dataset::{GameplayLoader, SampleWeights};
+    use crate::mjai::Event;
+
+    #[test]
+    fn write_and_read_back() {
+        let events: Vec<Event> = include_str!("../../tests/data/pack_test.json")
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        let loader = GameplayLoader {
+            oracle: true,
+            sample_weights: Some(SampleWeights::default()),
+            ..Default::default()
+        };
+        let games = loader.load_events(&events).unwrap();
+        assert_eq!(games.len(), 4);
+        // The oya discards and the next player aborts with kyuushu kyuuhai.
+        assert_eq!(games[0].actions.len(), 1);
+        assert_eq!(games[1].actions, [44]);
+        // Kyuushu kyuuhai or not.
+        assert_eq!(games[1].weights, [1.]);
+
+        let mut writer = PackedWriter::new(vec![]).unwrap();
+        for game in &games {
+            writer.write(game).unwrap();
+        }
+        let buf = writer.finish().unwrap();
+        let dense_size: usize = games
+            .iter()
+            .flat_map(|g| g.obs.iter().chain(&g.invisible_obs))
+            .map(|obs| obs.len() * 4)
+            .sum();
+        assert!(buf.len() * 10 < dense_size);
+
+        let read: Vec<_> = PackedReader::new(&buf[..])
+            .unwrap()
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(read.len(), games.len());
+        for (a, b) in games.iter().zip(&read) {
+            assert_eq!(a.obs, b.obs);
+            assert_eq!(a.invisible_obs, b.invisible_obs);
+            assert_eq!(a.actions, b.actions);
+            assert_eq!(a.masks, b.masks);
+            assert_eq!(a.at_kyoku, b.at_kyoku);
+            assert_eq!(a.dones, b.dones);
+            assert_eq!(a.apply_gamma, b.apply_gamma);
+            assert_eq!(a.at_turns, b.at_turns);
+            assert_eq!(a.shantens, b.shantens);
+            assert_eq!(a.think_ms, b.think_ms);
+            assert_eq!(a.weights, b.weights);
+            assert_eq!(a.teacher_qs, b.teacher_qs);
+            assert_eq!(a.teacher_masks, b.teacher_masks);
+            assert_eq!(a.grp.feature, b.grp.feature);
+            assert_eq!(a.grp.rank_by_player, b.grp.rank_by_player);
+            assert_eq!(a.player_id, b.player_id);
+            assert_eq!(a.player_name, b.player_name);
+        }
+
+        assert!(PackedReader::new(&b"MRPK\x00"[..]).is_err());
+        assert!(PackedReader::new(&b"MRPK\x02"[..]).is_err());
+    }
+}
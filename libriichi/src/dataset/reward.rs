@@ -0,0 +1,117 @@
+use super::Grp;
+
+use riichi_macros::{pyclass, pymethods};
+
+/// How the outcome of a game is turned into rewards for the player, split
+/// into each kyoku.
+///
+/// The reward of a kyoku is its score delta times `score_weight`, and the last
+/// kyoku additionally gets `rank_pts` of the final rank. For example, Majsoul
+/// room pts are the uma of the room as `rank_pts` with `score_weight = 0.001`.
+#[pyclass]
+#[pyo3(text_signature = "(
+    *,
+    rank_pts = [90.0, 45.0, 0.0, -135.0],
+    score_weight = 0.0,
+)")]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RewardTable {
+    /// Pts for finishing 1st to 4th.
+    #[pyo3(get, set)]
+    pub rank_pts: [f64; 4],
+    /// Pts for each point of score delta.
+    #[pyo3(get, set)]
+    pub score_weight: f64,
+}
+
+impl Default for RewardTable {
+    fn default() -> Self {
+        Self::tenhou_houou()
+    }
+}
+
+#[pymethods]
+impl RewardTable {
+    #[new]
+    #[args("*", rank_pts = "[90., 45., 0., -135.]", score_weight = "0.")]
+    const fn new(rank_pts: [f64; 4], score_weight: f64) -> Self {
+        Self {
+            rank_pts,
+            score_weight,
+        }
+    }
+
+    /// Placement pts of Tenhou's houou room, 90/45/0/-135.
+    #[staticmethod]
+    #[pyo3(text_signature = "()")]
+    #[must_use]
+    pub const fn tenhou_houou() -> Self {
+        Self::new([90., 45., 0., -135.], 0.)
+    }
+
+    /// Raw score deltas, ignoring the placement.
+    #[staticmethod]
+    #[pyo3(text_signature = "()")]
+    #[must_use]
+    pub const fn score_delta() -> Self {
+        Self::new([0.; 4], 1.)
+    }
+
+    fn __repr__(&self) -> String {
+        format!("{self:?}")
+    }
+}
+
+impl RewardTable {
+    /// Returns the reward of each kyoku in `grp` to `player_id`.
+    #[must_use]
+    pub fn kyoku_rewards(&self, grp: &Grp, player_id: u8) -> Vec<f64> {
+        let player_id = player_id as usize;
+        let mut scores: Vec<_> = grp
+            .feature
+            .rows()
+            .into_iter()
+            .map(|row| (row[3 + player_id] * 10000.).round())
+            .collect();
+        scores.push(grp.final_scores[player_id] as f64);
+
+        let mut ret: Vec<_> = scores
+            .windows(2)
+            .map(|w| (w[1] - w[0]) * self.score_weight)
+            .collect();
+        if let Some(last) = ret.last_mut() {
+            *last += self.rank_pts[grp.rank_by_player[player_id] as usize];
+        }
+        ret
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ndarray::array;
+
+    #[test]
+    fn kyoku_rewards() {
+        let grp = Grp {
+            feature: array![
+                [0., 0., 0., 2.5, 2.5, 2.5, 2.5],
+                [1., 0., 0., 3.3, 1.7, 2.5, 2.5],
+            ],
+            rank_by_player: [0, 3, 1, 2],
+            final_scores: [29000, 21700, 25000, 24300],
+        };
+
+        let rewards = RewardTable::tenhou_houou().kyoku_rewards(&grp, 1);
+        assert_eq!(rewards, [0., -135.]);
+
+        let rewards = RewardTable::score_delta().kyoku_rewards(&grp, 0);
+        assert_eq!(rewards, [8000., -4000.]);
+
+        let table = RewardTable::new([15., 5., -5., -15.], 0.001);
+        let rewards = table.kyoku_rewards(&grp, 3);
+        assert_eq!(rewards.len(), 2);
+        assert!((rewards[0] - 0.).abs() < 1e-9);
+        assert!((rewards[1] - (-0.7 - 5.)).abs() < 1e-9);
+    }
+}
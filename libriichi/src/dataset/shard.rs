@@ -0,0 +1,188 @@
+//! Encoding of whole directories of logs into packed shards, which uses all
+//! the cores instead of one game at a time from Python.
+
+use super::{GameplayLoader, PackedWriter};
+use crate::log_io::{self, glob_logs};
+use crate::par::*;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{ensure, Context, Result};
+use indicatif::{ProgressBar, ProgressStyle};
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+
+/// The outcome of `GameplayLoader::encode_logs`.
+#[derive(Debug, Clone, Default)]
+pub struct EncodeSummary {
+    /// The packed files written, in order.
+    pub shards: Vec<PathBuf>,
+    pub logs: usize,
+    pub games: usize,
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl GameplayLoader {
+    /// Encodes all the logs under `log_dir` recursively into packed files
+    /// under `out_dir`, each of which holds the games of `logs_per_shard`
+    /// logs, to be read by `GameplayReader`. Returns the filenames of the
+    /// shards.
+    #[pyo3(name = "encode_logs")]
+    #[args(logs_per_shard = "1000", "*", disable_progress_bar = "false")]
+    #[pyo3(
+        text_signature = "($self, log_dir, out_dir, logs_per_shard=1000, *, disable_progress_bar=False)"
+    )]
+    fn encode_logs_py(
+        &self,
+        log_dir: &str,
+        out_dir: &str,
+        logs_per_shard: usize,
+        disable_progress_bar: bool,
+        py: Python<'_>,
+    ) -> Result<Vec<String>> {
+        py.allow_threads(|| {
+            let summary =
+                self.encode_logs(log_dir, out_dir, logs_per_shard, disable_progress_bar)?;
+            Ok(summary
+                .shards
+                .iter()
+                .map(|p| p.to_string_lossy().into_owned())
+                .collect())
+        })
+    }
+}
+
+impl GameplayLoader {
+    /// See `encode_logs_py`. The logs are sorted by their paths, so the
+    /// shards are the same across runs given the same logs.
+    pub fn encode_logs(
+        &self,
+        log_dir: &str,
+        out_dir: &str,
+        logs_per_shard: usize,
+        disable_progress_bar: bool,
+    ) -> Result<EncodeSummary> {
+        ensure!(logs_per_shard > 0, "logs_per_shard must be positive");
+        let mut filenames = glob_logs(log_dir)?.collect::<Result<Vec<_>>>()?;
+        filenames.sort_unstable();
+        fs::create_dir_all(out_dir).with_context(|| format!("failed to create {out_dir}"))?;
+
+        let bar = if disable_progress_bar {
+            ProgressBar::hidden()
+        } else {
+            ProgressBar::new(filenames.len() as u64)
+        };
+        bar.set_style(
+            ProgressStyle::default_bar()
+                .template("{spinner:.cyan} [{elapsed_precise}] [{wide_bar}] {pos}/{len} {percent:>3}% ({per_sec})")
+                .tick_chars(".oOo")
+                .progress_chars("#-"),
+        );
+        bar.enable_steady_tick(150);
+
+        let results = filenames
+            .par_chunks(logs_per_shard)
+            .enumerate()
+            .map(|(i, chunk)| {
+                let shard = Path::new(out_dir).join(format!("{i:06}.pk.gz"));
+                let games = self.encode_shard(chunk, &shard, &bar)?;
+                Ok((shard, games))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        bar.abandon();
+
+        let mut summary = EncodeSummary {
+            logs: filenames.len(),
+            ..Default::default()
+        };
+        for (shard, games) in results {
+            summary.shards.push(shard);
+            summary.games += games;
+        }
+        log::info!(
+            "encoded {} games of {} logs into {} shards",
+            summary.games,
+            summary.logs,
+            summary.shards.len(),
+        );
+        Ok(summary)
+    }
+
+    /// Returns the number of games written.
+    fn encode_shard(
+        &self,
+        filenames: &[PathBuf],
+        shard: &Path,
+        bar: &ProgressBar,
+    ) -> Result<usize> {
+        let games = filenames
+            .par_iter()
+            .map(|path| {
+                let games = log_io::read_log(path)
+                    .and_then(|raw| self.load_log(&raw))
+                    .with_context(|| format!("error when reading {}", path.display()));
+                bar.inc(1);
+                games
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut writer = PackedWriter::create(&shard.to_string_lossy())?;
+        let mut count = 0;
+        for game in games.iter().flatten() {
+            writer.write(game)?;
+            count += 1;
+        }
+        writer.close()?;
+        Ok(count)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::dataset::packed::PackedReader;
+    use crate::log_io::write_log;
+
+    #[test]
+    fn encode_logs() {
+        let log = include_str!("../../tests/data/pack_test.json");
+        let dir = std::env::temp_dir().join(format!("riichi_encode_logs_{}", std::process::id()));
+        let log_dir = dir.join("logs");
+        let out_dir = dir.join("out");
+        fs::create_dir_all(log_dir.join("nested")).unwrap();
+        for name in ["a.json", "b.json.gz", "nested/c.json"] {
+            write_log(log_dir.join(name), log).unwrap();
+        }
+
+        let loader = GameplayLoader {
+            oracle: true,
+            ..Default::default()
+        };
+        let expected = loader.load_log(log).unwrap().len();
+        let summary = loader
+            .encode_logs(
+                &log_dir.to_string_lossy(),
+                &out_dir.to_string_lossy(),
+                2,
+                true,
+            )
+            .unwrap();
+        assert_eq!(summary.logs, 3);
+        assert_eq!(summary.shards.len(), 2);
+        assert_eq!(summary.games, expected * 3);
+
+        let read = summary
+            .shards
+            .iter()
+            .map(|shard| {
+                PackedReader::open(&shard.to_string_lossy())
+                    .unwrap()
+                    .count()
+            })
+            .sum::<usize>();
+        assert_eq!(read, summary.games);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
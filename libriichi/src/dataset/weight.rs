@@ -0,0 +1,154 @@
+use crate::state::ActionCandidate;
+
+use riichi_macros::{pyclass, pymethods};
+
+/// How much each sample of a `Gameplay` weighs in training, by the kind of
+/// decision as told by the `ActionCandidate` and the legal actions of it.
+///
+/// A sample with only one legal action gets `forced`, and a kan select gets
+/// `kan_select`. Otherwise it gets the largest of the weights of the options
+/// it has, which is `discard` for a plain discard.
+#[pyclass]
+#[pyo3(text_signature = "(
+    *,
+    forced = 0.0,
+    discard = 1.0,
+    riichi = 1.0,
+    call = 1.0,
+    agari = 1.0,
+    ryukyoku = 1.0,
+    kan_select = 1.0,
+)")]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SampleWeights {
+    #[pyo3(get, set)]
+    pub forced: f32,
+    #[pyo3(get, set)]
+    pub discard: f32,
+    #[pyo3(get, set)]
+    pub riichi: f32,
+    /// Chi, pon, daiminkan, kakan or ankan.
+    #[pyo3(get, set)]
+    pub call: f32,
+    /// Tsumo or ron.
+    #[pyo3(get, set)]
+    pub agari: f32,
+    /// Kyuushu kyuuhai.
+    #[pyo3(get, set)]
+    pub ryukyoku: f32,
+    #[pyo3(get, set)]
+    pub kan_select: f32,
+}
+
+impl Default for SampleWeights {
+    fn default() -> Self {
+        Self::new(0., 1., 1., 1., 1., 1., 1.)
+    }
+}
+
+#[pymethods]
+impl SampleWeights {
+    #[new]
+    #[args(
+        "*",
+        forced = "0.",
+        discard = "1.",
+        riichi = "1.",
+        call = "1.",
+        agari = "1.",
+        ryukyoku = "1.",
+        kan_select = "1."
+    )]
+    const fn new(
+        forced: f32,
+        discard: f32,
+        riichi: f32,
+        call: f32,
+        agari: f32,
+        ryukyoku: f32,
+        kan_select: f32,
+    ) -> Self {
+        Self {
+            forced,
+            discard,
+            riichi,
+            call,
+            agari,
+            ryukyoku,
+            kan_select,
+        }
+    }
+
+    fn __repr__(&self) -> String {
+        format!("{self:?}")
+    }
+}
+
+impl SampleWeights {
+    /// Returns the weight of a sample, where `legal_actions` is the number of
+    /// actions set in its mask.
+    #[must_use]
+    pub fn weight(&self, cans: &ActionCandidate, legal_actions: usize, at_kan_select: bool) -> f32 {
+        if legal_actions <= 1 {
+            return self.forced;
+        }
+        if at_kan_select {
+            return self.kan_select;
+        }
+
+        let options = [
+            (cans.can_discard, self.discard),
+            (cans.can_riichi, self.riichi),
+            (
+                cans.can_chi()
+                    || cans.can_pon
+                    || cans.can_daiminkan
+                    || cans.can_kakan
+                    || cans.can_ankan,
+                self.call,
+            ),
+            (cans.can_tsumo_agari || cans.can_ron_agari, self.agari),
+            (cans.can_ryukyoku, self.ryukyoku),
+        ];
+        options
+            .into_iter()
+            .filter(|&(can, _)| can)
+            .map(|(_, w)| w)
+            .reduce(f32::max)
+            .unwrap_or(self.discard)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn weight() {
+        let weights = SampleWeights {
+            call: 2.,
+            agari: 3.,
+            ..Default::default()
+        };
+        let discard = ActionCandidate {
+            can_discard: true,
+            ..Default::default()
+        };
+        assert!((weights.weight(&discard, 1, false) - 0.).abs() < f32::EPSILON);
+        assert!((weights.weight(&discard, 5, false) - 1.).abs() < f32::EPSILON);
+
+        let pon_or_ron = ActionCandidate {
+            can_pon: true,
+            can_ron_agari: true,
+            ..Default::default()
+        };
+        assert!((weights.weight(&pon_or_ron, 3, false) - 3.).abs() < f32::EPSILON);
+        let ankan = ActionCandidate {
+            can_discard: true,
+            can_ankan: true,
+            ..Default::default()
+        };
+        assert!((weights.weight(&ankan, 10, false) - 2.).abs() < f32::EPSILON);
+        assert!((weights.weight(&ankan, 2, true) - 1.).abs() < f32::EPSILON);
+    }
+}
@@ -0,0 +1,120 @@
+use crate::state::InvalidReaction;
+use std::error::Error as StdError;
+use std::fmt;
+
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+use serde_json as json;
+
+/// The error of the entry points of the crate, such as `Bot::react` and
+/// `PlayerState.update`, that tells the caller what went wrong without
+/// having to look into the message.
+#[derive(Debug)]
+pub enum Error {
+    /// The input is not a well-formed mjai event.
+    Parse(json::Error),
+    /// The event is well-formed, but it cannot happen in the current state.
+    InvalidEvent(anyhow::Error),
+    /// The action is not a valid reaction to the current state.
+    IllegalAction(InvalidReaction),
+    /// The agent failed to produce a reaction.
+    Engine(anyhow::Error),
+    Other(anyhow::Error),
+}
+
+// pyo3's `create_exception` checks a cfg that is unknown to newer rustc.
+#[cfg(feature = "python")]
+#[allow(unexpected_cfgs)]
+mod exceptions {
+    use pyo3::create_exception;
+    use pyo3::exceptions::{PyRuntimeError, PyValueError};
+
+    create_exception!(libriichi, ParseError, PyValueError);
+    create_exception!(libriichi, InvalidEventError, PyValueError);
+    create_exception!(libriichi, EngineError, PyRuntimeError);
+}
+
+#[cfg(feature = "python")]
+pub use exceptions::{EngineError, InvalidEventError, ParseError};
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Parse(err) => write!(f, "failed to parse event: {err}"),
+            Self::InvalidEvent(err) => write!(f, "invalid event: {err:#}"),
+            Self::IllegalAction(err) => write!(f, "illegal action: {err}"),
+            Self::Engine(err) => write!(f, "engine error: {err:#}"),
+            Self::Other(err) => write!(f, "{err:#}"),
+        }
+    }
+}
+
+impl StdError for Error {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            Self::Parse(err) => Some(err),
+            Self::IllegalAction(err) => Some(err),
+            Self::InvalidEvent(err) | Self::Engine(err) | Self::Other(err) => Some(err.as_ref()),
+        }
+    }
+}
+
+impl From<InvalidReaction> for Error {
+    fn from(err: InvalidReaction) -> Self {
+        Self::IllegalAction(err)
+    }
+}
+
+#[cfg(feature = "python")]
+impl From<Error> for PyErr {
+    fn from(err: Error) -> Self {
+        let msg = err.to_string();
+        match err {
+            Error::Parse(_) => ParseError::new_err(msg),
+            Error::InvalidEvent(_) => InvalidEventError::new_err(msg),
+            Error::IllegalAction(err) => err.into(),
+            Error::Engine(_) => EngineError::new_err(msg),
+            Error::Other(err) => err.into(),
+        }
+    }
+}
+
+#[cfg(feature = "python")]
+pub(crate) fn register_exceptions(py: Python<'_>, m: &PyModule) -> PyResult<()> {
+    m.add("ParseError", py.get_type::<ParseError>())?;
+    m.add("InvalidEventError", py.get_type::<InvalidEventError>())?;
+    m.add("EngineError", py.get_type::<EngineError>())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::t;
+    use anyhow::anyhow;
+
+    #[test]
+    fn source_and_message() {
+        let err = Error::Parse(json::from_str::<json::Value>("{").unwrap_err());
+        assert!(err.to_string().starts_with("failed to parse event: "));
+        assert!(err.source().unwrap().is::<json::Error>());
+
+        let tile = t!(5m);
+        let err = Error::from(InvalidReaction::TileNotInHand { tile });
+        assert_eq!(err.to_string(), "illegal action: 5m is not in hand");
+        assert_eq!(
+            err.source().unwrap().downcast_ref(),
+            Some(&InvalidReaction::TileNotInHand { tile }),
+        );
+
+        let err = Error::Engine(anyhow!("timeout").context("failed to get reaction"));
+        assert_eq!(
+            err.to_string(),
+            "engine error: failed to get reaction: timeout",
+        );
+
+        // It converts into `anyhow::Error` without losing the variant.
+        let err = anyhow::Error::from(Error::InvalidEvent(anyhow!("no such tile")));
+        assert!(matches!(err.downcast_ref(), Some(Error::InvalidEvent(_)),));
+    }
+}
@@ -4,11 +4,28 @@
 //! with tenhou.net/2 format tile description (like 0m 123z) instead of mjai (like
 //! 5mr ESW).
 
-use crate::tile::Tile;
+use crate::algo::score::Meld;
+#[cfg(feature = "python")]
+use crate::py_helper::add_submodule;
+use crate::rule::MAX_AKAS_PER_SUIT;
+use crate::tile::{Tile, TileStyle};
 use crate::vec_ops::vec_add_assign;
-use crate::{must_tile, tuz};
+use crate::{must_tile, tu8, tuz};
 
-use anyhow::{bail, ensure, Result};
+use anyhow::{bail, ensure, Context, Result};
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+
+/// A hand parsed by `tiles_from_string`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParsedHand {
+    /// The concealed tiles, where akas are counted as the normal 5s.
+    pub tehai: [u8; 34],
+    /// Number of each of the aka 5m, 5p and 5s in the hand, including the
+    /// melds.
+    pub akas: [u8; 3],
+    pub melds: Vec<Meld>,
+}
 
 /// Spaces are allowed.
 pub fn hand_with_aka(s: &str) -> Result<[u8; 37]> {
@@ -66,6 +83,133 @@ pub fn hand(s: &str) -> Result<[u8; 34]> {
     Ok(ret)
 }
 
+/// Parses a hand in the shorthand notation, such as `123m 406p 789s 11z`, as
+/// the inverse of `tiles_to_string`.
+///
+/// An aka can be written as either `0` or `r5`, e.g. `r5m` is the same as
+/// `0m`. Melds follow the concealed tiles, each in brackets, where `[...]` is
+/// an open one and `(...)` is an ankan, e.g. `123m 11z [789s] [5550p] (7777z)`.
+/// Spaces are allowed.
+pub fn tiles_from_string(s: &str) -> Result<ParsedHand> {
+    let mut ret = ParsedHand {
+        tehai: [0; 34],
+        akas: [0; 3],
+        melds: vec![],
+    };
+    let mut counts = [0; 34];
+    let mut count_tile = |ret: &mut ParsedHand, tile: Tile| -> Result<()> {
+        counts[tile.deaka().as_usize()] += 1;
+        ensure!(
+            counts[tile.deaka().as_usize()] <= 4,
+            "more than 4 {} in {s}",
+            tile.deaka(),
+        );
+        if tile.is_aka() {
+            let i = tile.as_usize() - tuz!(5mr);
+            ensure!(
+                ret.akas[i] < MAX_AKAS_PER_SUIT[i],
+                "more than {} {tile} in {s}",
+                MAX_AKAS_PER_SUIT[i],
+            );
+            ret.akas[i] += 1;
+        }
+        Ok(())
+    };
+
+    let (concealed, mut rest) = s.split_once(['[', '(']).map_or((s, ""), |(c, _)| {
+        let at = c.len();
+        (c, &s[at..])
+    });
+    for tile in parse_tiles(concealed)? {
+        count_tile(&mut ret, tile)?;
+        ret.tehai[tile.deaka().as_usize()] += 1;
+    }
+
+    loop {
+        rest = rest.trim_start();
+        let Some(open) = rest.chars().next() else {
+            break;
+        };
+        let (close, is_ankan) = match open {
+            '[' => (']', false),
+            '(' => (')', true),
+            _ => bail!("unexpected {rest} after the melds in {s}"),
+        };
+        let (inner, after) = rest[1..]
+            .split_once(close)
+            .with_context(|| format!("unclosed meld in {s}"))?;
+        rest = after;
+
+        let mut tiles = parse_tiles(inner)?;
+        for &tile in &tiles {
+            count_tile(&mut ret, tile)?;
+        }
+        for t in &mut tiles {
+            *t = t.deaka();
+        }
+        tiles.sort_unstable_by_key(|t| t.as_u8());
+        let first = tiles[0].as_u8();
+        let same = tiles.iter().all(|&t| t == tiles[0]);
+        let meld = match tiles.len() {
+            3 if same && !is_ankan => Meld::Pon(first),
+            3 if !is_ankan
+                && first < tu8!(E)
+                && first % 9 <= 6
+                && tiles[1].as_u8() == first + 1
+                && tiles[2].as_u8() == first + 2 =>
+            {
+                Meld::Chi(first)
+            }
+            4 if same && is_ankan => Meld::Ankan(first),
+            4 if same => Meld::Minkan(first),
+            _ => bail!("{inner} is not a valid meld"),
+        };
+        ret.melds.push(meld);
+    }
+
+    Ok(ret)
+}
+
+/// Parses the tiles in shorthand notation in order.
+fn parse_tiles(s: &str) -> Result<Vec<Tile>> {
+    ensure!(s.is_ascii(), "hand {s} contains non-ascii content");
+
+    let mut ret = vec![];
+    let mut stack = vec![];
+    let mut bytes = s.bytes();
+    while let Some(b) = bytes.next() {
+        match b {
+            b'0'..=b'9' => stack.push(b - b'0'),
+            b'r' => {
+                ensure!(bytes.next() == Some(b'5'), "r is not followed by 5 in {s}");
+                stack.push(0);
+            }
+            b' ' | b'\t' | b'\n' => (),
+            b'm' | b'p' | b's' | b'z' => {
+                let kind = match b {
+                    b'm' => 0,
+                    b'p' => 1,
+                    b's' => 2,
+                    _ => 3,
+                };
+                for n in stack.drain(..) {
+                    let tid = match n {
+                        0 if kind < 3 => tu8!(5mr) + kind,
+                        1..=9 if kind < 3 => kind * 9 + n - 1,
+                        1..=7 => tu8!(E) + n - 1,
+                        _ => bail!("{n}{} is not a valid tile", b as char),
+                    };
+                    ret.push(must_tile!(tid));
+                }
+            }
+            _ => bail!("unexpected byte {b} in {s}"),
+        }
+    }
+    ensure!(stack.is_empty(), "{s} ends without a suit");
+
+    Ok(ret)
+}
+
 #[must_use]
 pub fn tile37_to_vec(tiles: &[u8; 37]) -> Vec<Tile> {
     let mut ret = vec![];
@@ -74,11 +218,7 @@ pub fn tile37_to_vec(tiles: &[u8; 37]) -> Vec<Tile> {
         .enumerate()
         .filter(|(_, &count)| count > 0)
         .for_each(|(tid, &count)| {
-            if tid < 34 {
-                ret.resize(ret.len() + count as usize, must_tile!(tid));
-            } else {
-                ret.push(must_tile!(tid));
-            }
+            ret.resize(ret.len() + count as usize, must_tile!(tid));
         });
     ret
 }
@@ -97,7 +237,7 @@ pub fn tile34_to_vec(tiles: &[u8; 34]) -> Vec<Tile> {
 }
 
 #[must_use]
-pub fn tiles_to_string(tiles: &[u8; 34], aka: [bool; 3]) -> String {
+pub fn tiles_to_string(tiles: &[u8; 34], aka: [u8; 3]) -> String {
     let suhai = tiles[..3 * 9]
         .chunks_exact(9)
         .enumerate()
@@ -110,9 +250,10 @@ pub fn tiles_to_string(tiles: &[u8; 34], aka: [bool; 3]) -> String {
                 .filter(|(_, &count)| count > 0)
                 .for_each(|(num, &count)| {
                     let literal_num = num + 1;
-                    if literal_num == 5 && aka[kind] {
-                        partial.push('0');
-                        partial += &literal_num.to_string().repeat(count as usize - 1);
+                    if literal_num == 5 && aka[kind] > 0 {
+                        let n = aka[kind].min(count);
+                        partial += &"0".repeat(n as usize);
+                        partial += &literal_num.to_string().repeat((count - n) as usize);
                     } else {
                         partial += &literal_num.to_string().repeat(count as usize);
                     }
@@ -148,6 +289,90 @@ pub fn tiles_to_string(tiles: &[u8; 34], aka: [bool; 3]) -> String {
     }
 }
 
+/// Like `tiles_to_string`, but in `style`.
+///
+/// `TileStyle::Shorthand` is the same as `tiles_to_string`, while the other
+/// styles write the tiles one by one, in the order of `tile34_to_vec` with
+/// each aka before the other 5s of its suit, with a space in between for
+/// `TileStyle::Aligned`.
+#[must_use]
+pub fn tiles_to_string_styled(tiles: &[u8; 34], aka: [u8; 3], style: TileStyle) -> String {
+    let sep = match style {
+        TileStyle::Shorthand => return tiles_to_string(tiles, aka),
+        TileStyle::Unicode => "",
+        TileStyle::Aligned => " ",
+    };
+
+    let mut seen_aka = [0; 3];
+    tile34_to_vec(tiles)
+        .into_iter()
+        .map(|tile| {
+            let tile = match tile.as_u8() {
+                tu8!(5m) | tu8!(5p) | tu8!(5s) => {
+                    let kind = tile.as_usize() / 9;
+                    if seen_aka[kind] < aka[kind] {
+                        seen_aka[kind] += 1;
+                        tile.akaize()
+                    } else {
+                        tile
+                    }
+                }
+                _ => tile,
+            };
+            tile.styled(style).to_string()
+        })
+        .collect::<Vec<_>>()
+        .join(sep)
+}
+
+type PyParsedHand = ([u8; 34], [u8; 3], Vec<(&'static str, u8)>);
+
+/// Parses a hand in the shorthand notation, such as `123m 406p 789s 11z`,
+/// where an aka can also be written as `r5`. Melds follow in brackets, where
+/// `[...]` is an open one and `(...)` is an ankan.
+///
+/// Returns the 34-D counts of the concealed tiles, the number of each of the
+/// aka 5m, 5p and 5s in the hand, and the melds as `(kind, tile)`, which can be
+/// passed to `algo.calc_score` directly.
+#[cfg(feature = "python")]
+#[pyfunction]
+#[pyo3(name = "tiles_from_string")]
+#[pyo3(text_signature = "(s, /)")]
+fn tiles_from_string_py(s: &str) -> Result<PyParsedHand> {
+    let hand = tiles_from_string(s)?;
+    let melds = hand
+        .melds
+        .iter()
+        .map(|&m| match m {
+            Meld::Chi(t) => ("chi", t),
+            Meld::Pon(t) => ("pon", t),
+            Meld::Minkan(t) => ("minkan", t),
+            Meld::Ankan(t) => ("ankan", t),
+        })
+        .collect();
+    Ok((hand.tehai, hand.akas, melds))
+}
+
+/// The inverse of `tiles_from_string` for the concealed tiles.
+///
+/// `style` is one of `shorthand`, `unicode` and `aligned`, where only the
+/// default `shorthand` can be parsed back.
+#[cfg(feature = "python")]
+#[pyfunction(tehai, akas, "*", style = "\"shorthand\"")]
+#[pyo3(name = "tiles_to_string")]
+#[pyo3(text_signature = "(tehai, akas, *, style = \"shorthand\")")]
+fn tiles_to_string_py(tehai: [u8; 34], akas: [u8; 3], style: &str) -> Result<String> {
+    Ok(tiles_to_string_styled(&tehai, akas, style.parse()?))
+}
+
+#[cfg(feature = "python")]
+pub(crate) fn register_module(py: Python<'_>, prefix: &str, super_mod: &PyModule) -> PyResult<()> {
+    let m = PyModule::new(py, "hand")?;
+    m.add_function(wrap_pyfunction!(tiles_from_string_py, m)?)?;
+    m.add_function(wrap_pyfunction!(tiles_to_string_py, m)?)?;
+    add_submodule(py, prefix, super_mod, m)
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -196,9 +421,71 @@ mod test {
                     0, 0, 0, 0, 0, 1, 1, 1, 0, // s
                     0, 0, 0, 0, 0, 0, 0, // z
                 ],
-                [true, false, false]
+                [1, 0, 0]
             ),
             "33067m 345678p 678s"
         );
     }
+
+    #[test]
+    fn string_styled() {
+        let parsed = tiles_from_string("055m 1p 17z").unwrap();
+        let to_string = |style| tiles_to_string_styled(&parsed.tehai, parsed.akas, style);
+        assert_eq!(to_string(TileStyle::Shorthand), "055m 1p 17z");
+        assert_eq!(to_string(TileStyle::Unicode), "🀋🀋🀋🀙🀀🀄");
+        assert_eq!(to_string(TileStyle::Aligned), "5mr 5m  5m  1p  E   C  ");
+    }
+
+    #[test]
+    fn from_string() {
+        let tehai = [
+            0, 0, 2, 0, 1, 1, 1, 0, 0, // m
+            0, 0, 1, 1, 1, 1, 1, 1, 0, // p
+            0, 0, 0, 0, 0, 1, 1, 1, 0, // s
+            0, 0, 0, 0, 0, 0, 0, // z
+        ];
+        let akas = [1, 0, 0];
+        let parsed = tiles_from_string(&tiles_to_string(&tehai, akas)).unwrap();
+        assert_eq!(
+            parsed,
+            ParsedHand {
+                tehai,
+                akas,
+                melds: vec![],
+            },
+        );
+        assert_eq!(tiles_from_string("33r567m 345678p 678s").unwrap(), parsed);
+
+        let parsed = tiles_from_string("123m 11z [978s] [5505p] (7777z)").unwrap();
+        assert_eq!(parsed.tehai, hand("123m 11z").unwrap());
+        assert_eq!(parsed.akas, [0, 1, 0]);
+        assert_eq!(
+            parsed.melds,
+            [
+                Meld::Chi(tuz!(7s) as u8),
+                Meld::Minkan(tuz!(5p) as u8),
+                Meld::Ankan(tuz!(C) as u8),
+            ],
+        );
+
+        // The second aka 5p of 4 akas.
+        let parsed = tiles_from_string("00p 123s 11z").unwrap();
+        assert_eq!(parsed.akas, [0, 2, 0]);
+        assert_eq!(tiles_to_string(&parsed.tehai, parsed.akas), "00p 123s 11z");
+        let parsed = tiles_from_string("0p 11z [406p]").unwrap();
+        assert_eq!(parsed.akas, [0, 2, 0]);
+
+        tiles_from_string("11111m").unwrap_err();
+        tiles_from_string("00m").unwrap_err();
+        tiles_from_string("000p").unwrap_err();
+        tiles_from_string("8z").unwrap_err();
+        tiles_from_string("0z").unwrap_err();
+        tiles_from_string("123").unwrap_err();
+        tiles_from_string("r6m").unwrap_err();
+        tiles_from_string("11m [789s").unwrap_err();
+        tiles_from_string("11m [135s]").unwrap_err();
+        tiles_from_string("11m [123z]").unwrap_err();
+        tiles_from_string("11m (111z)").unwrap_err();
+        tiles_from_string("11m [111z] 2m").unwrap_err();
+    }
 }
@@ -41,19 +41,41 @@
     clippy::borrow_as_ptr,
     clippy::ptr_as_ptr
 )]
+// Much of the arena, the dataset and the ratings is only used by their Python
+// classes.
+#![cfg_attr(not(feature = "python"), allow(dead_code))]
+// `multiple-pymethods` of pyo3 0.16 registers the methods from a static.
+#![cfg_attr(feature = "python", allow(non_local_definitions))]
 
 mod arena;
+mod capi;
 mod consts;
 mod dataset;
 mod macros;
+#[cfg(feature = "python")]
 mod py_helper;
+mod rating;
 mod vec_ops;
+#[cfg(feature = "wasm")]
+mod wasm;
 
 // pub for bins
 pub mod chi_type;
+pub mod convert;
+pub mod error;
+pub mod log_io;
 pub mod mjai;
+pub mod par;
+pub mod render;
+pub mod review;
+pub mod rule;
 pub mod stat;
 pub mod state;
+pub mod trace;
+pub mod validate;
+pub use arena::GameState;
+pub use dataset::{EncodeSummary, GameplayLoader};
+pub use error::Error;
 
 // pub for non-cfg(test) tests
 pub mod agent;
@@ -63,6 +85,7 @@ pub mod tile;
 pub mod algo;
 pub mod hand;
 
+#[cfg(feature = "python")]
 use pyo3::prelude::*;
 
 #[cfg(feature = "mimalloc")]
@@ -76,10 +99,23 @@ static GLOBAL: mimalloc::MiMalloc = mimalloc::MiMalloc;
 ///   `state.PlayerState`).
 /// - Read mjai logs and produce a batch of instances for training (via
 ///   `dataset`).
-/// - Self-play under standard Tenhou rules (via `arena`).
+/// - Self-play under standard Tenhou rules (via `arena`), and hosting games
+///   with a full-information referee (via `arena.GameState`).
+/// - Rule variants of other platforms (via `rule.RuleSet`).
 /// - Definitions of observation and action space for Mortal (via `consts`).
 /// - Statistical works on mjai logs (via `stat.Stat`).
 /// - mjai interface (via `mjai.Bot`).
+/// - Per-decision review of mjai logs by an engine (via `review.Reviewer`), and
+///   its agreement with the players over a corpus (via `review.Evaluator`).
+/// - Shanten and agari calculation on a bare hand (via `algo`).
+/// - Parsing of hands in shorthand notation such as `123m 406p` (via `hand`).
+/// - Conversion from Tenhou and Majsoul logs into mjai logs, and from mjai
+///   logs into the tenhou.net/6 viewer format (via `convert`).
+/// - Reading and writing of gzip or zstd compressed logs (via `log_io`).
+/// - Validation of mjai logs (via `validate`).
+/// - SVG and HTML board diagrams of a state or a log position (via `render`).
+/// - Spans with timings around the hot paths for diagnosis (via `trace`).
+#[cfg(feature = "python")]
 #[pymodule]
 fn libriichi(py: Python<'_>, m: &PyModule) -> PyResult<()> {
     pyo3_log::init();
@@ -96,12 +132,23 @@ fn libriichi(py: Python<'_>, m: &PyModule) -> PyResult<()> {
     algo::shanten::ensure_init();
     algo::agari::ensure_init();
 
+    error::register_exceptions(py, m)?;
     consts::register_module(py, name, m)?;
     state::register_module(py, name, m)?;
     dataset::register_module(py, name, m)?;
     arena::register_module(py, name, m)?;
     stat::register_module(py, name, m)?;
     mjai::register_module(py, name, m)?;
+    review::register_module(py, name, m)?;
+    rule::register_module(py, name, m)?;
+    convert::register_module(py, name, m)?;
+    log_io::register_module(py, name, m)?;
+    validate::register_module(py, name, m)?;
+    render::register_module(py, name, m)?;
+    trace::register_module(py, name, m)?;
+    algo::register_module(py, name, m)?;
+    hand::register_module(py, name, m)?;
+    rating::register_module(py, name, m)?;
 
     Ok(())
 }
@@ -0,0 +1,204 @@
+//! Reading and writing of mjai log files, which may be compressed with gzip
+//! or zstd. Zstd needs the `zstd` feature, as it is a C library that cannot be
+//! built for WASM.
+//!
+//! The compression of a file being read is told by its magic bytes, so
+//! misnamed files are read fine. The compression of a file being written is
+//! told by its extension.
+
+use crate::mjai::Event;
+#[cfg(feature = "python")]
+use crate::py_helper::add_submodule;
+use std::fs::{self, File};
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+
+#[cfg(not(feature = "zstd"))]
+use anyhow::bail;
+use anyhow::{Context, Result};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use glob::glob;
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+use serde_json as json;
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+#[cfg(feature = "zstd")]
+const ZSTD_LEVEL: i32 = 19;
+
+/// Extensions of the log files to look for in a directory.
+pub const LOG_EXTENSIONS: [&str; 3] = ["json", "json.gz", "json.zst"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Compression {
+    #[default]
+    None,
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    /// Tells the compression by the extension of `path`, which is `.gz` for
+    /// gzip and `.zst` for zstd.
+    pub fn from_path(path: impl AsRef<Path>) -> Self {
+        match path.as_ref().extension().and_then(|s| s.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("gz") => Self::Gzip,
+            Some(ext) if ext.eq_ignore_ascii_case("zst") => Self::Zstd,
+            _ => Self::None,
+        }
+    }
+
+    fn from_magic(head: &[u8]) -> Self {
+        if head.starts_with(GZIP_MAGIC) {
+            Self::Gzip
+        } else if head.starts_with(ZSTD_MAGIC) {
+            Self::Zstd
+        } else {
+            Self::None
+        }
+    }
+
+    pub fn compress(self, raw: &[u8]) -> Result<Vec<u8>> {
+        let ret = match self {
+            Self::None => raw.to_vec(),
+            Self::Gzip => {
+                let mut enc = GzEncoder::new(vec![], flate2::Compression::best());
+                enc.write_all(raw)?;
+                enc.finish()?
+            }
+            #[cfg(feature = "zstd")]
+            Self::Zstd => zstd::encode_all(raw, ZSTD_LEVEL)?,
+            #[cfg(not(feature = "zstd"))]
+            Self::Zstd => bail!("zstd compression needs the `zstd` feature"),
+        };
+        Ok(ret)
+    }
+
+    pub fn decompress(self, data: &[u8]) -> Result<Vec<u8>> {
+        let ret = match self {
+            Self::None => data.to_vec(),
+            Self::Gzip => {
+                let mut raw = vec![];
+                GzDecoder::new(data).read_to_end(&mut raw)?;
+                raw
+            }
+            #[cfg(feature = "zstd")]
+            Self::Zstd => zstd::decode_all(data)?,
+            #[cfg(not(feature = "zstd"))]
+            Self::Zstd => bail!("zstd decompression needs the `zstd` feature"),
+        };
+        Ok(ret)
+    }
+}
+
+/// Reads a log file as a string, decompressing it if needed.
+pub fn read_log(path: impl AsRef<Path>) -> Result<String> {
+    let path = path.as_ref();
+    let inner = || {
+        let data = fs::read(path)?;
+        let raw = Compression::from_magic(&data).decompress(&data)?;
+        anyhow::Ok(String::from_utf8(raw)?)
+    };
+    inner().with_context(|| format!("failed to read log {}", path.display()))
+}
+
+/// Reads a log file and parses it into events.
+pub fn read_events(path: impl AsRef<Path>) -> Result<Vec<Event>> {
+    let path = path.as_ref();
+    read_log(path)?
+        .lines()
+        .filter(|l| !l.trim().is_empty())
+        .map(json::from_str)
+        .collect::<Result<_, _>>()
+        .with_context(|| format!("failed to parse log {}", path.display()))
+}
+
+/// Writes `log` into a file, compressed as told by the extension of `path`.
+pub fn write_log(path: impl AsRef<Path>, log: &str) -> Result<()> {
+    let path = path.as_ref();
+    let inner = || {
+        let data = Compression::from_path(path).compress(log.as_bytes())?;
+        let mut f = File::create(path)?;
+        f.write_all(&data)?;
+        f.sync_all()?;
+        anyhow::Ok(())
+    };
+    inner().with_context(|| format!("failed to write log {}", path.display()))
+}
+
+/// Finds the log files of all `LOG_EXTENSIONS` under `dir` recursively.
+pub fn glob_logs(dir: &str) -> Result<impl Iterator<Item = Result<PathBuf>>> {
+    let paths = LOG_EXTENSIONS
+        .iter()
+        .map(|ext| glob(&format!("{dir}/**/*.{ext}")))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(paths.into_iter().flatten().map(|p| Ok(p?)))
+}
+
+/// Reads a log file as a string, which may be compressed with gzip or zstd.
+#[cfg(feature = "python")]
+#[pyfunction]
+#[pyo3(name = "read_log")]
+#[pyo3(text_signature = "(filename, /)")]
+fn read_log_py(filename: &str) -> Result<String> {
+    read_log(filename)
+}
+
+/// Writes `log` into a file, compressed with gzip if `filename` ends with
+/// `.gz`, or zstd if it ends with `.zst`.
+#[cfg(feature = "python")]
+#[pyfunction]
+#[pyo3(name = "write_log")]
+#[pyo3(text_signature = "(filename, log, /)")]
+fn write_log_py(filename: &str, log: &str) -> Result<()> {
+    write_log(filename, log)
+}
+
+#[cfg(feature = "python")]
+pub(crate) fn register_module(py: Python<'_>, prefix: &str, super_mod: &PyModule) -> PyResult<()> {
+    let m = PyModule::new(py, "log_io")?;
+    m.add_function(wrap_pyfunction!(read_log_py, m)?)?;
+    m.add_function(wrap_pyfunction!(write_log_py, m)?)?;
+    add_submodule(py, prefix, super_mod, m)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let log = "{\"type\":\"start_game\",\"names\":[\"a\",\"b\",\"c\",\"d\"]}\n{\"type\":\"end_game\"}\n";
+        let dir = std::env::temp_dir().join(format!("riichi_log_io_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        for ext in LOG_EXTENSIONS {
+            let path = dir.join(format!("log.{ext}"));
+            if !cfg!(feature = "zstd") && Compression::from_path(&path) == Compression::Zstd {
+                let err = write_log(&path, log).unwrap_err();
+                assert!(format!("{err:#}").contains("`zstd` feature"), "{err:#}");
+                fs::write(&path, ZSTD_MAGIC).unwrap();
+                read_log(&path).unwrap_err();
+                continue;
+            }
+            write_log(&path, log).unwrap();
+            assert_eq!(read_log(&path).unwrap(), log);
+            assert_eq!(read_events(&path).unwrap().len(), 2);
+        }
+        assert_eq!(
+            Compression::from_magic(&fs::read(dir.join("log.json.zst")).unwrap()),
+            Compression::Zstd,
+        );
+
+        // Misnamed files are still read by their magic bytes.
+        let misnamed = dir.join("misnamed.json");
+        fs::copy(dir.join("log.json.gz"), &misnamed).unwrap();
+        assert_eq!(read_log(&misnamed).unwrap(), log);
+
+        let found = glob_logs(dir.to_str().unwrap()).unwrap().count();
+        assert_eq!(found, LOG_EXTENSIONS.len() + 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,233 @@
+use super::{Event, EventExt, EventWithCanAct, Timing};
+use crate::agent::BatchAgent;
+#[cfg(feature = "python")]
+use crate::agent::{MctsBatchAgent, MctsConfig, MortalBatchAgent};
+use crate::state::PlayerState;
+use std::time::Instant;
+
+use anyhow::{ensure, Context, Result};
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+use riichi_macros::{pyclass, pymethods};
+use serde_json as json;
+
+/// `BatchBot` plays one seat on each of many independent tables with one
+/// single agent, so that bot farms running concurrent matches get the
+/// benefit of batching.
+///
+/// Reactions of all the tables given in one `react_batch` call are evaluated
+/// in one batch.
+#[pyclass]
+#[pyo3(text_signature = "(engine, player_ids, *, mcts=None)")]
+pub struct BatchBot {
+    agent: Box<dyn BatchAgent + Send>,
+    tables: Vec<Table>,
+}
+
+struct Table {
+    state: PlayerState,
+    log: Vec<EventExt>,
+}
+
+#[pymethods]
+impl BatchBot {
+    /// `player_ids[i]` is the seat of the bot on table `i`.
+    #[cfg(feature = "python")]
+    #[new]
+    #[args("*", mcts = "None")]
+    fn py_new(engine: PyObject, player_ids: Vec<u8>, mcts: Option<MctsConfig>) -> Result<Self> {
+        let agent = MortalBatchAgent::new(engine, &player_ids)?;
+        let agent: Box<dyn BatchAgent + Send> = match mcts {
+            Some(config) => Box::new(MctsBatchAgent::new(Box::new(agent), &player_ids, config)),
+            None => Box::new(agent),
+        };
+        Ok(Self::new(agent, &player_ids))
+    }
+
+    /// Returns a list of the same length as `lines`, which consists of the
+    /// reaction of each table to its line, or `None` if the line is `None`
+    /// or the bot cannot react on that table.
+    ///
+    /// Set `line_json['can_act']` to `False` to force the bot to only update
+    /// the state of that table without making any reaction.
+    ///
+    /// Both the lines and the reactions are JSON strings representing one
+    /// single mjai event.
+    #[cfg(feature = "python")]
+    #[pyo3(name = "react_batch")]
+    #[pyo3(text_signature = "($self, lines, /)")]
+    fn react_batch_py(
+        &mut self,
+        lines: Vec<Option<String>>,
+        py: Python<'_>,
+    ) -> Result<Vec<Option<String>>> {
+        py.allow_threads(move || self.react_batch(&lines))
+    }
+
+    #[getter]
+    fn num_tables(&self) -> usize {
+        self.tables.len()
+    }
+}
+
+impl BatchBot {
+    /// `agent` must have been created with `player_ids`, in which order the
+    /// tables are indexed.
+    #[must_use]
+    pub fn new(agent: Box<dyn BatchAgent + Send>, player_ids: &[u8]) -> Self {
+        let tables = player_ids
+            .iter()
+            .map(|&id| Table {
+                state: PlayerState::new(id),
+                log: vec![],
+            })
+            .collect();
+        Self { agent, tables }
+    }
+
+    pub fn react_batch<S: AsRef<str>>(
+        &mut self,
+        lines: &[Option<S>],
+    ) -> Result<Vec<Option<String>>> {
+        ensure!(
+            lines.len() == self.tables.len(),
+            "expected {} lines, got {}",
+            self.tables.len(),
+            lines.len(),
+        );
+
+        let mut can_act = vec![false; lines.len()];
+        for (i, line) in lines.iter().enumerate() {
+            if let Some(line) = line {
+                can_act[i] = self
+                    .apply(i, line.as_ref())
+                    .with_context(|| format!("failed to apply the line of table {i}"))?;
+            }
+        }
+
+        let start = Instant::now();
+        for (i, table) in self.tables.iter().enumerate() {
+            if can_act[i] {
+                self.agent
+                    .set_scene(i, &table.log, &table.state, None)
+                    .with_context(|| format!("failed to add state of table {i}"))?;
+            }
+        }
+
+        let mut ret = vec![None; lines.len()];
+        for (i, table) in self.tables.iter().enumerate() {
+            if can_act[i] {
+                let mut reaction = self
+                    .agent
+                    .get_reaction(i, &table.log, &table.state, None)
+                    .with_context(|| format!("failed to get reaction of table {i}"))?;
+                reaction.timing = Some(Timing {
+                    think_ms: start.elapsed().as_millis().try_into().ok(),
+                    ..Timing::now()
+                });
+                ret[i] = Some(json::to_string(&reaction)?);
+            }
+        }
+
+        Ok(ret)
+    }
+
+    /// Updates table `index` with `line`, returning whether the bot is to
+    /// react to it.
+    fn apply(&mut self, index: usize, line: &str) -> Result<bool> {
+        let data: EventWithCanAct =
+            json::from_str(line).with_context(|| format!("failed to parse event {line}"))?;
+        let table = &mut self.tables[index];
+
+        match data.event {
+            Event::StartGame { .. } => {
+                self.agent.start_game(index)?;
+            }
+            Event::EndKyoku => {
+                table.log.clear();
+                self.agent.end_kyoku(index)?;
+            }
+            Event::EndGame => {
+                self.agent.end_game(index, &Default::default())?;
+            }
+            _ => {
+                table.log.push(EventExt {
+                    event: data.event.clone(),
+                    meta: None,
+                    timing: data.timing,
+                });
+            }
+        };
+
+        let cans = table.state.update(&data.event)?;
+        Ok(!matches!(data.can_act, Some(false)) && cans.can_act())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::agent::Tsumogiri;
+    use crate::t;
+
+    #[test]
+    fn react_per_table() {
+        let agent = Tsumogiri::new_batched(&[0, 2]).unwrap();
+        let mut bot = BatchBot::new(Box::new(agent), &[0, 2]);
+
+        let lines = r#"
+            {"type":"start_game","names":["a","b","c","d"]}
+            {"type":"start_kyoku","bakaze":"E","dora_marker":"1m","kyoku":1,"honba":0,"kyotaku":0,"oya":0,"scores":[25000,25000,25000,25000],"tehais":[["1m","2m","3m","4p","5p","6p","7s","8s","9s","E","E","S","S"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["1p","2p","3p","4s","5s","6s","7m","8m","9m","P","P","F","F"],["?","?","?","?","?","?","?","?","?","?","?","?","?"]]}
+        "#;
+        for line in lines.trim().lines() {
+            let ret = bot
+                .react_batch(&[Some(line.trim()), Some(line.trim())])
+                .unwrap();
+            assert_eq!(ret, [None, None]);
+        }
+
+        // Table 1 lags behind.
+        let ret = bot
+            .react_batch(&[Some(r#"{"type":"tsumo","actor":0,"pai":"W"}"#), None])
+            .unwrap();
+        assert!(ret[1].is_none());
+        let ev: Event = json::from_str(ret[0].as_ref().unwrap()).unwrap();
+        assert_eq!(
+            ev,
+            Event::Dahai {
+                actor: 0,
+                pai: t!(W),
+                tsumogiri: true,
+            },
+        );
+
+        let ret = bot
+            .react_batch(&[
+                Some(r#"{"type":"dahai","actor":0,"pai":"W","tsumogiri":true}"#),
+                Some(r#"{"type":"tsumo","actor":0,"pai":"?"}"#),
+            ])
+            .unwrap();
+        assert_eq!(ret, [None, None]);
+
+        let ret = bot
+            .react_batch(&[
+                Some(r#"{"type":"tsumo","actor":1,"pai":"?"}"#),
+                Some(r#"{"type":"dahai","actor":0,"pai":"P","tsumogiri":true}"#),
+            ])
+            .unwrap();
+        assert!(ret[0].is_none());
+        // Table 1 can pon, but Tsumogiri always passes.
+        let ev: Event = json::from_str(ret[1].as_ref().unwrap()).unwrap();
+        assert_eq!(ev, Event::None);
+
+        let ret = bot
+            .react_batch(&[
+                None,
+                Some(r#"{"type":"tsumo","actor":1,"pai":"?","can_act":false}"#),
+            ])
+            .unwrap();
+        assert_eq!(ret, [None, None]);
+
+        assert!(bot.react_batch::<&str>(&[None]).is_err());
+    }
+}
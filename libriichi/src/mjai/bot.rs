@@ -1,31 +1,98 @@
-use super::EventWithCanAct;
-use super::{Event, EventExt};
-use crate::agent::{BatchAgent, MortalBatchAgent};
-use crate::state::PlayerState;
+use super::replay::{self, Divergence, Recorder};
+use super::{Event, EventExt, EventWithCanAct, Metadata, Timing};
+use crate::agent::{AsyncBatchAgent, BatchAgent};
+#[cfg(feature = "python")]
+use crate::agent::{MctsBatchAgent, MctsConfig, MortalBatchAgent};
+use crate::arena::GameResult;
+use crate::error::Error;
+use crate::state::{ActionCandidate, PlayerState};
+use std::fs::File;
+#[cfg(feature = "python")]
+use std::io::BufReader;
+use std::io::{BufRead, BufWriter, Write};
+use std::time::{Duration, Instant};
 
-use anyhow::{Context, Result};
+use anyhow::{anyhow, Context, Result};
+#[cfg(feature = "python")]
 use pyo3::prelude::*;
+use riichi_macros::{pyclass, pymethods};
+use serde::Serialize;
 use serde_json as json;
+use serde_with::skip_serializing_none;
 
 #[pyclass]
-#[pyo3(text_signature = "(engine, player_id)")]
+#[pyo3(text_signature = "(engine, player_id, *, mcts=None)")]
 pub struct Bot {
-    agent: MortalBatchAgent,
+    agent: BotAgent,
     state: PlayerState,
     log: Vec<EventExt>,
+    game_log: AnnotatedLog,
+    emit_meta: bool,
+    recorder: Option<Recorder>,
+}
+
+/// The agent of a `Bot`, which is called either synchronously or
+/// asynchronously.
+enum BotAgent {
+    Sync(Box<dyn BatchAgent + Send>),
+    Async(Box<dyn AsyncBatchAgent + Send>),
+}
+
+/// The whole game log as seen by the bot, where the bot's own actions are
+/// annotated with the metadata of the reaction that produced them.
+#[derive(Default)]
+struct AnnotatedLog {
+    events: Vec<EventExt>,
+    /// The last reaction of the bot, waiting to be echoed back.
+    pending: Option<EventExt>,
+}
+
+/// The reaction emitted when `emit_meta` is on.
+#[derive(Serialize)]
+struct ReactionWithMeta<'a> {
+    action: &'a Event,
+    meta: Option<ReactionMeta>,
+}
+
+#[skip_serializing_none]
+#[derive(Debug, Serialize)]
+struct ReactionMeta {
+    entropy: f32,
+    /// Q values of the legal actions, see `mask_bits` for their action IDs.
+    q_values: Vec<f32>,
+    /// Probabilities of the legal actions, in the order of `q_values`.
+    probs: Vec<f32>,
+    mask_bits: Option<u64>,
+    /// Probability of the action actually chosen.
+    prob: Option<f32>,
+    top_action_prob: f32,
 }
 
 #[pymethods]
 impl Bot {
+    /// Reactions are searched by `MctsBatchAgent` on top of the engine if
+    /// `mcts` is set to an `MctsConfig`.
+    #[cfg(feature = "python")]
     #[new]
-    fn new(engine: PyObject, player_id: u8) -> Result<Self> {
+    #[args("*", mcts = "None")]
+    fn py_new(engine: PyObject, player_id: u8, mcts: Option<MctsConfig>) -> Result<Self> {
         let agent = MortalBatchAgent::new(engine, &[player_id])?;
-        let state = PlayerState::new(player_id);
-        Ok(Self {
-            agent,
-            state,
-            log: vec![],
-        })
+        let agent: Box<dyn BatchAgent + Send> = match mcts {
+            Some(config) => Box::new(MctsBatchAgent::new(Box::new(agent), &[player_id], config)),
+            None => Box::new(agent),
+        };
+        Ok(Self::new(agent, player_id))
+    }
+
+    /// When set to `True`, `react` returns a JSON object in the form of
+    /// `{"action": <mjai event>, "meta": {"entropy": ..., "q_values": [...],
+    /// "probs": [...], "mask_bits": ..., "prob": ..., "top_action_prob": ...}}`
+    /// instead of a bare mjai event, where the probabilities are derived from
+    /// the softmax of the q values of all the legal actions, and `prob` is the
+    /// one of the chosen action.
+    #[pyo3(text_signature = "($self, emit_meta, /)")]
+    pub fn set_emit_meta(&mut self, emit_meta: bool) {
+        self.emit_meta = emit_meta;
     }
 
     /// Returns the reaction to `line`, if it can react, `None` otherwise.
@@ -33,51 +100,997 @@ impl Bot {
     /// Set `can_act` or `line_json['can_act']` to `False` to force the bot to
     /// only update its state without making any reaction.
     ///
+    /// `line_json['timing']`, if any, is kept in the log seen by the engine,
+    /// where `total_ms` and `remaining_ms` tell the time budget of the
+    /// reaction. The reaction itself carries a `timing` with its `think_ms`.
+    ///
     /// Both `line` and the return value are JSON strings representing one
     /// single mjai event.
+    #[cfg(feature = "python")]
     #[pyo3(name = "react")]
     #[pyo3(text_signature = "($self, line, /, *, can_act=True)")]
     #[args("*", can_act = "true")]
-    fn react_py(&mut self, line: &str, can_act: bool, py: Python<'_>) -> Result<Option<String>> {
+    fn react_py(
+        &mut self,
+        line: &str,
+        can_act: bool,
+        py: Python<'_>,
+    ) -> Result<Option<String>, Error> {
         py.allow_threads(move || self.react(line, can_act))
     }
+
+    /// Same as `react`, but the engine is told to react within `deadline_ms`
+    /// milliseconds from now, see `react_with_deadline` in Rust.
+    #[cfg(feature = "python")]
+    #[pyo3(name = "react_with_deadline")]
+    #[pyo3(text_signature = "($self, line, deadline_ms, /, *, can_act=True)")]
+    #[args("*", can_act = "true")]
+    fn react_with_deadline_py(
+        &mut self,
+        line: &str,
+        deadline_ms: u64,
+        can_act: bool,
+        py: Python<'_>,
+    ) -> Result<Option<String>, Error> {
+        py.allow_threads(move || {
+            self.react_with_deadline(line, Duration::from_millis(deadline_ms), can_act)
+        })
+    }
+
+    /// Catches up with `lines` in a batch, e.g. the replay on reconnection,
+    /// and returns the reaction to the last line only, if it can react.
+    ///
+    /// All the lines but the last one only update the state and the log,
+    /// without invoking the engine.
+    #[cfg(feature = "python")]
+    #[pyo3(name = "sync")]
+    #[pyo3(text_signature = "($self, lines, /)")]
+    fn sync_py(&mut self, lines: Vec<String>, py: Python<'_>) -> Result<Option<String>, Error> {
+        py.allow_threads(move || self.sync(&lines))
+    }
+
+    /// Starts writing every decision to a replay file at `path`, one JSON line
+    /// per decision with the lines received before it, the hash of the obs
+    /// and the action chosen, see `ReplayEntry` in Rust.
+    ///
+    /// It should be called before the first event, so that the replay can be
+    /// fed to a fresh bot by `verify_replay`.
+    #[pyo3(text_signature = "($self, path, /)")]
+    fn start_recording(&mut self, path: &str) -> Result<()> {
+        let file = File::create(path).with_context(|| format!("failed to create {path}"))?;
+        self.record_to(Box::new(BufWriter::new(file)));
+        Ok(())
+    }
+
+    #[pyo3(text_signature = "($self, /)")]
+    fn stop_recording(&mut self) -> Result<()> {
+        if let Some(mut recorder) = self.recorder.take() {
+            recorder.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Replays the file at `path` written by `start_recording` and returns the
+    /// divergences as JSON strings, see `verify_replay` in Rust.
+    #[cfg(feature = "python")]
+    #[pyo3(name = "verify_replay")]
+    #[pyo3(text_signature = "($self, path, /)")]
+    fn verify_replay_py(&mut self, path: &str, py: Python<'_>) -> Result<Vec<String>> {
+        let file = File::open(path).with_context(|| format!("failed to open {path}"))?;
+        let divergences = py.allow_threads(|| self.verify_replay(BufReader::new(file)))?;
+        let ret = divergences
+            .iter()
+            .map(json::to_string)
+            .collect::<Result<_, _>>()?;
+        Ok(ret)
+    }
+
+    /// Returns all the events received so far in this game as JSON lines.
+    /// The bot's own actions carry the `meta` of the reaction that produced
+    /// them.
+    #[pyo3(text_signature = "($self, /)")]
+    pub fn dump_log(&self) -> Result<Vec<String>> {
+        self.game_log.dump()
+    }
 }
 
 impl Bot {
-    fn react(&mut self, line: &str, can_act: bool) -> Result<Option<String>> {
-        let data: EventWithCanAct =
-            json::from_str(line).with_context(|| format!("failed to parse event {line}"))?;
+    /// `agent` must have been created with `player_id` as its only index.
+    #[must_use]
+    pub fn new(agent: Box<dyn BatchAgent + Send>, player_id: u8) -> Self {
+        Self::with_agent(BotAgent::Sync(agent), player_id)
+    }
+
+    /// A bot that can only react by `react_async`.
+    ///
+    /// `agent` must have been created with `player_id` as its only index.
+    #[must_use]
+    pub fn new_async(agent: Box<dyn AsyncBatchAgent + Send>, player_id: u8) -> Self {
+        Self::with_agent(BotAgent::Async(agent), player_id)
+    }
+
+    fn with_agent(agent: BotAgent, player_id: u8) -> Self {
+        Self {
+            agent,
+            state: PlayerState::new(player_id),
+            log: vec![],
+            game_log: AnnotatedLog::default(),
+            emit_meta: false,
+            recorder: None,
+        }
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn state(&self) -> &PlayerState {
+        &self.state
+    }
+
+    /// Writes every decision from now on to `writer`, see `ReplayEntry`.
+    pub fn record_to(&mut self, writer: Box<dyn Write + Send>) {
+        self.recorder = Some(Recorder::new(writer));
+    }
+
+    /// Feeds the replay written by `record_to` to this bot, which must be
+    /// fresh, and returns the decisions where either the obs hash or the
+    /// action differs from the record.
+    ///
+    /// The agent must react deterministically for the actions to be
+    /// comparable.
+    pub fn verify_replay(&mut self, reader: impl BufRead) -> Result<Vec<Divergence>> {
+        self.emit_meta = false;
+        replay::verify(self, reader)
+    }
+
+    /// The error tells whether `line` is malformed (`Error::Parse`) or
+    /// inconsistent with the state (`Error::InvalidEvent`), or the agent
+    /// failed (`Error::Engine`).
+    pub fn react(&mut self, line: &str, can_act: bool) -> Result<Option<String>, Error> {
+        self.react_by(line, can_act, None)
+    }
+
+    /// Like `react`, but the agent is given a deadline of `budget` from now,
+    /// by which search-based agents cut off and fall back to the greedy
+    /// reaction.
+    pub fn react_with_deadline(
+        &mut self,
+        line: &str,
+        budget: Duration,
+        can_act: bool,
+    ) -> Result<Option<String>, Error> {
+        let start = Instant::now();
+        self.react_by(line, can_act, start.checked_add(budget))
+    }
+
+    /// Like `react`, but awaits the agent created by `new_async` instead,
+    /// without blocking the executor. The agent of `new` is called in place.
+    pub async fn react_async(
+        &mut self,
+        line: &str,
+        can_act: bool,
+    ) -> Result<Option<String>, Error> {
+        if !self.apply_for_reaction(line, can_act)? {
+            return Ok(None);
+        }
 
-        match data.event {
-            Event::StartGame { .. } => {
-                self.agent.start_game(0)?;
+        let start = Instant::now();
+        let reaction = match &mut self.agent {
+            BotAgent::Sync(agent) => react_sync(agent.as_mut(), &self.log, &self.state),
+            BotAgent::Async(agent) => {
+                async {
+                    agent
+                        .set_scene(0, &self.log, &self.state, None)
+                        .await
+                        .context("failed to add state")?;
+                    agent
+                        .get_reaction(0, &self.log, &self.state, None)
+                        .await
+                        .context("failed to get reaction")
+                }
+                .await
             }
+        };
+        self.finish_reaction(reaction.map_err(Error::Engine)?, start)
+            .map(Some)
+    }
+
+    fn react_by(
+        &mut self,
+        line: &str,
+        can_act: bool,
+        deadline: Option<Instant>,
+    ) -> Result<Option<String>, Error> {
+        if !matches!(self.agent, BotAgent::Sync(_)) {
+            return Err(Error::Other(anyhow!(
+                "the agent is async, use `react_async` instead",
+            )));
+        }
+        if !self.apply_for_reaction(line, can_act)? {
+            return Ok(None);
+        }
+
+        let start = Instant::now();
+        let BotAgent::Sync(agent) = &mut self.agent else {
+            unreachable!();
+        };
+        agent.set_deadline(0, deadline);
+        let reaction = react_sync(agent.as_mut(), &self.log, &self.state);
+        agent.set_deadline(0, None);
+        self.finish_reaction(reaction.map_err(Error::Engine)?, start)
+            .map(Some)
+    }
+
+    /// Applies `line` and returns whether the bot is to react to it.
+    fn apply_for_reaction(&mut self, line: &str, can_act: bool) -> Result<bool, Error> {
+        let (cans, line_can_act) = self.apply(line)?;
+        Ok(can_act && !matches!(line_can_act, Some(false)) && cans.can_act())
+    }
+
+    /// Stamps the timing of `reaction`, which has been thought since
+    /// `start`, and serializes it.
+    fn finish_reaction(&mut self, mut reaction: EventExt, start: Instant) -> Result<String, Error> {
+        reaction.timing = Some(Timing {
+            think_ms: start.elapsed().as_millis().try_into().ok(),
+            ..Timing::now()
+        });
+        self.game_log.expect(reaction.clone());
+        if let Some(recorder) = &mut self.recorder {
+            recorder
+                .record(&self.state, &reaction.event)
+                .context("failed to record the decision")
+                .map_err(Error::Other)?;
+        }
+
+        let ret = if self.emit_meta {
+            json::to_string(&ReactionWithMeta {
+                action: &reaction.event,
+                meta: reaction.meta.as_ref().and_then(ReactionMeta::from_metadata),
+            })
+        } else {
+            json::to_string(&reaction)
+        };
+        ret.map_err(|err| Error::Other(err.into()))
+    }
+
+    /// Catches up with `lines` in a batch, e.g. the replay on reconnection,
+    /// and returns the reaction to the last line only, see `react`.
+    ///
+    /// All the lines but the last one only update the state and the log, so
+    /// the engine is invoked at most once.
+    pub fn sync<S: AsRef<str>>(&mut self, lines: &[S]) -> Result<Option<String>, Error> {
+        let Some((last, replayed)) = lines.split_last() else {
+            return Ok(None);
+        };
+        for line in replayed {
+            self.apply(line.as_ref())?;
+        }
+        self.react(last.as_ref(), true)
+    }
+
+    /// Updates the state and the log with `line`, returning the action
+    /// candidates and the `can_act` of the line.
+    fn apply(&mut self, line: &str) -> Result<(ActionCandidate, Option<bool>), Error> {
+        let data: EventWithCanAct = json::from_str(line).map_err(Error::Parse)?;
+        if let Some(recorder) = &mut self.recorder {
+            recorder.push_line(line);
+        }
+        let ev = self.game_log.record(&data.event, data.timing);
+
+        let notified = match data.event {
+            Event::StartGame { .. } => self.agent.start_game(),
             Event::EndKyoku => {
                 self.log.clear();
-                self.agent.end_kyoku(0)?;
-            }
-            Event::EndGame { .. } => {
-                self.agent.end_game(0, &Default::default())?;
+                self.agent.end_kyoku()
             }
+            Event::EndGame { .. } => self.agent.end_game(&Default::default()),
             _ => {
-                self.log.push(EventExt::no_meta(data.event.clone()));
+                self.log.push(ev);
+                Ok(())
             }
         };
+        notified.map_err(Error::Engine)?;
 
-        let cans = self.state.update(&data.event);
-        if !can_act || matches!(data.can_act, Some(false)) || !cans.can_act() {
-            return Ok(None);
+        let cans = self
+            .state
+            .update(&data.event)
+            .map_err(Error::InvalidEvent)?;
+        Ok((cans, data.can_act))
+    }
+}
+
+impl BotAgent {
+    fn start_game(&mut self) -> Result<()> {
+        match self {
+            Self::Sync(agent) => agent.start_game(0),
+            Self::Async(agent) => agent.start_game(0),
+        }
+    }
+
+    fn end_kyoku(&mut self) -> Result<()> {
+        match self {
+            Self::Sync(agent) => agent.end_kyoku(0),
+            Self::Async(agent) => agent.end_kyoku(0),
+        }
+    }
+
+    fn end_game(&mut self, game_result: &GameResult) -> Result<()> {
+        match self {
+            Self::Sync(agent) => agent.end_game(0, game_result),
+            Self::Async(agent) => agent.end_game(0, game_result),
+        }
+    }
+}
+
+fn react_sync(
+    agent: &mut (dyn BatchAgent + Send),
+    log: &[EventExt],
+    state: &PlayerState,
+) -> Result<EventExt> {
+    agent
+        .set_scene(0, log, state, None)
+        .context("failed to add state")?;
+    agent
+        .get_reaction(0, log, state, None)
+        .context("failed to get reaction")
+}
+
+impl AnnotatedLog {
+    /// Records `event`, attaching the metadata of the pending reaction if
+    /// `event` is the echo of it. `timing` from the host takes precedence over
+    /// the one of the reaction.
+    fn record(&mut self, event: &Event, timing: Option<Timing>) -> EventExt {
+        let mut ev = match self.pending.take() {
+            Some(reaction) if reaction.event == *event => reaction,
+            _ => EventExt::no_meta(event.clone()),
+        };
+        if timing.is_some() {
+            ev.timing = timing;
+        }
+        if matches!(event, Event::StartGame { .. }) {
+            self.events.clear();
         }
+        self.events.push(ev.clone());
+        ev
+    }
+
+    fn expect(&mut self, reaction: EventExt) {
+        if !matches!(reaction.event, Event::None) {
+            self.pending = Some(reaction);
+        }
+    }
+
+    fn dump(&self) -> Result<Vec<String>> {
+        self.events
+            .iter()
+            .map(|ev| json::to_string(ev).context("failed to serialize event"))
+            .collect()
+    }
+}
 
-        self.agent
-            .set_scene(0, &self.log, &self.state, None)
-            .context("failed to add state")?;
-        let reaction = self
-            .agent
-            .get_reaction(0, &self.log, &self.state, None)
-            .context("failed to get reaction")?;
+impl ReactionMeta {
+    fn from_metadata(meta: &Metadata) -> Option<Self> {
+        let q_values = meta.q_values.as_ref().filter(|q| !q.is_empty())?;
 
-        let ret = json::to_string(&reaction)?;
-        Ok(Some(ret))
+        let max_q = q_values.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+        let exps: Vec<_> = q_values.iter().map(|&q| (q - max_q).exp()).collect();
+        let sum: f32 = exps.iter().sum();
+
+        let probs: Vec<_> = exps.into_iter().map(|e| e / sum).collect();
+
+        let mut entropy = 0.;
+        let mut top_action_prob = 0_f32;
+        for &p in &probs {
+            if p > 0. {
+                entropy -= p * p.ln();
+            }
+            top_action_prob = top_action_prob.max(p);
+        }
+
+        Some(Self {
+            entropy,
+            q_values: q_values.clone(),
+            probs,
+            mask_bits: meta.mask_bits,
+            prob: meta.prob,
+            top_action_prob,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::agent::{
+        BatchReaction, BoxFuture, EngineBackend, EngineConfig, InvisibleState, MortalBatchAgent,
+        Tsumogiri,
+    };
+    use crate::consts::ACTION_SPACE;
+    use crate::mjai::ReplayEntry;
+    use crate::t;
+    use crate::tu8;
+    use ndarray::{Array2, Array3};
+    use std::future::Future;
+    use std::pin::{pin, Pin};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::task::{self, Poll, Wake, Waker};
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn reaction_with_meta() {
+        let meta = Metadata {
+            q_values: Some(vec![1.5, -0.25, 3., 0.]),
+            ..Default::default()
+        };
+        let rm = ReactionMeta::from_metadata(&meta).unwrap();
+        assert_eq!(rm.q_values, meta.q_values.clone().unwrap());
+
+        let exps: Vec<f32> = rm.q_values.iter().map(|q| q.exp()).collect();
+        let sum: f32 = exps.iter().sum();
+        let probs: Vec<f32> = exps.iter().map(|e| e / sum).collect();
+        assert!(probs.iter().all(|&p| (0. ..=1.).contains(&p)));
+        assert!((probs.iter().sum::<f32>() - 1.).abs() < 1e-5);
+        assert!((rm.top_action_prob - probs[2]).abs() < 1e-5);
+        let entropy: f32 = probs.iter().map(|p| -p * p.ln()).sum();
+        assert!((rm.entropy - entropy).abs() < 1e-5);
+        assert!(rm.entropy > 0. && rm.entropy < (probs.len() as f32).ln());
+        assert!(rm
+            .probs
+            .iter()
+            .zip(&probs)
+            .all(|(a, b)| (a - b).abs() < 1e-5));
+
+        let action = Event::Dahai {
+            actor: 0,
+            pai: t!(5m),
+            tsumogiri: false,
+        };
+        let value = json::to_value(ReactionWithMeta {
+            action: &action,
+            meta: Some(rm),
+        })
+        .unwrap();
+        assert_eq!(value["action"]["type"], "dahai");
+        assert_eq!(value["meta"]["q_values"].as_array().unwrap().len(), 4);
+        assert!(value["meta"]["entropy"].is_number());
+        assert!(value["meta"]["top_action_prob"].is_number());
+        assert_eq!(value["meta"]["probs"].as_array().unwrap().len(), 4);
+        assert!(value["meta"].get("prob").is_none());
+
+        assert!(ReactionMeta::from_metadata(&Metadata::default()).is_none());
+    }
+
+    #[test]
+    fn annotated_log() {
+        let meta = |prob| Metadata {
+            q_values: Some(vec![0.5, 0.25]),
+            prob: Some(prob),
+            ..Default::default()
+        };
+        let mut log = AnnotatedLog::default();
+
+        let lines = r#"
+            {"type":"start_game","names":["a","b","c","d"]}
+            {"type":"start_kyoku","bakaze":"E","dora_marker":"1m","kyoku":1,"honba":0,"kyotaku":0,"oya":0,"scores":[25000,25000,25000,25000],"tehais":[["1m","2m","3m","4p","5p","6p","7s","8s","9s","E","E","S","S"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"]]}
+            {"type":"tsumo","actor":0,"pai":"N"}
+        "#;
+        for line in lines.trim().lines() {
+            log.record(&json::from_str(line).unwrap(), None);
+        }
+
+        // Bot discards N, and the server echoes it back.
+        let dahai = Event::Dahai {
+            actor: 0,
+            pai: t!(N),
+            tsumogiri: true,
+        };
+        log.expect(EventExt {
+            event: dahai.clone(),
+            meta: Some(meta(0.75)),
+            timing: None,
+        });
+        let ev = log.record(&dahai, None);
+        assert_eq!(ev.meta.unwrap().prob, Some(0.75));
+
+        // Bot declines to pon, which is never echoed.
+        log.record(
+            &Event::Tsumo {
+                actor: 1,
+                pai: t!(?),
+            },
+            None,
+        );
+        log.record(
+            &Event::Dahai {
+                actor: 1,
+                pai: t!(S),
+                tsumogiri: true,
+            },
+            None,
+        );
+        log.expect(EventExt {
+            event: Event::None,
+            meta: Some(meta(0.5)),
+            timing: None,
+        });
+        log.record(
+            &Event::Tsumo {
+                actor: 2,
+                pai: t!(?),
+            },
+            None,
+        );
+
+        // The reaction is overridden by someone else's action.
+        let pon = Event::Pon {
+            actor: 0,
+            target: 2,
+            pai: t!(E),
+            consumed: t![E, E],
+        };
+        log.expect(EventExt {
+            event: pon,
+            meta: Some(meta(0.25)),
+            timing: None,
+        });
+        let ev = log.record(
+            &Event::Hora {
+                actor: 3,
+                target: 2,
+                deltas: None,
+                ura_markers: None,
+            },
+            None,
+        );
+        assert!(ev.meta.is_none());
+
+        let dumped = log.dump().unwrap();
+        assert_eq!(dumped.len(), 8);
+        let values: Vec<json::Value> = dumped.iter().map(|l| json::from_str(l).unwrap()).collect();
+        assert_eq!(values[3]["type"], "dahai");
+        assert_eq!(values[3]["meta"]["prob"], 0.75);
+        assert!(values
+            .iter()
+            .enumerate()
+            .filter(|&(i, _)| i != 3)
+            .all(|(_, v)| v.get("meta").is_none()));
+    }
+
+    /// Always prefers to discard N.
+    struct DiscardN;
+
+    impl EngineBackend for DiscardN {
+        fn react_batch(
+            &mut self,
+            _: Array3<f32>,
+            masks: Array2<bool>,
+            _: Option<Array3<f32>>,
+        ) -> Result<BatchReaction> {
+            let n = masks.nrows();
+            let masks: Vec<[bool; ACTION_SPACE]> = masks
+                .outer_iter()
+                .map(|row| row.to_vec().try_into().unwrap())
+                .collect();
+            let mut q_values = [0.; ACTION_SPACE];
+            q_values[tu8!(N) as usize] = 1.;
+            Ok((
+                vec![tu8!(N) as usize; n],
+                vec![q_values; n],
+                masks,
+                vec![true; n],
+            ))
+        }
+    }
+
+    /// `DiscardN` that counts the calls to the engine.
+    struct CountingDiscardN(Arc<AtomicUsize>);
+
+    impl EngineBackend for CountingDiscardN {
+        fn react_batch(
+            &mut self,
+            obs: Array3<f32>,
+            masks: Array2<bool>,
+            invisible_obs: Option<Array3<f32>>,
+        ) -> Result<BatchReaction> {
+            self.0.fetch_add(1, Ordering::Relaxed);
+            DiscardN.react_batch(obs, masks, invisible_obs)
+        }
+    }
+
+    #[test]
+    fn sync_reacts_once() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let backend = CountingDiscardN(Arc::clone(&calls));
+        let agent =
+            MortalBatchAgent::with_backend(Box::new(backend), EngineConfig::default(), &[0])
+                .unwrap();
+        let mut bot = Bot::new(Box::new(agent), 0);
+
+        // The bot could act on its own tsumo and on the pons of E and S, but
+        // those are replayed.
+        let lines = r#"
+            {"type":"start_game","names":["a","b","c","d"]}
+            {"type":"start_kyoku","bakaze":"E","dora_marker":"1m","kyoku":1,"honba":0,"kyotaku":0,"oya":0,"scores":[25000,25000,25000,25000],"tehais":[["1m","2m","3m","4p","5p","6p","7s","8s","9s","E","E","S","S"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"]]}
+            {"type":"tsumo","actor":0,"pai":"N"}
+            {"type":"dahai","actor":0,"pai":"N","tsumogiri":true}
+            {"type":"tsumo","actor":1,"pai":"?"}
+            {"type":"dahai","actor":1,"pai":"E","tsumogiri":true}
+            {"type":"tsumo","actor":2,"pai":"?"}
+            {"type":"dahai","actor":2,"pai":"S","tsumogiri":true}
+            {"type":"tsumo","actor":3,"pai":"?"}
+            {"type":"dahai","actor":3,"pai":"W","tsumogiri":true}
+            {"type":"tsumo","actor":0,"pai":"N"}
+        "#;
+        let lines: Vec<_> = lines.trim().lines().map(str::trim).collect();
+        let reaction = bot.sync(&lines).unwrap().unwrap();
+        let ev: Event = json::from_str(&reaction).unwrap();
+        assert!(matches!(ev, Event::Dahai { pai, .. } if pai == t!(N)));
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
+        assert_eq!(bot.dump_log().unwrap().len(), lines.len());
+    }
+
+    #[test]
+    fn dumped_meta() {
+        let agent =
+            MortalBatchAgent::with_backend(Box::new(DiscardN), EngineConfig::default(), &[0])
+                .unwrap();
+        let mut bot = Bot::new(Box::new(agent), 0);
+
+        let lines = r#"
+            {"type":"start_game","names":["a","b","c","d"]}
+            {"type":"start_kyoku","bakaze":"E","dora_marker":"1m","kyoku":1,"honba":0,"kyotaku":0,"oya":0,"scores":[25000,25000,25000,25000],"tehais":[["1m","2m","3m","4p","5p","6p","7s","8s","9s","E","E","S","S"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"]]}
+            {"type":"tsumo","actor":0,"pai":"N"}
+        "#;
+        let lines: Vec<_> = lines.trim().lines().map(str::trim).collect();
+        bot.sync(&lines).unwrap().unwrap();
+        bot.react(
+            r#"{"type":"dahai","actor":0,"pai":"N","tsumogiri":true}"#,
+            false,
+        )
+        .unwrap();
+
+        let dumped = bot.dump_log().unwrap();
+        assert_eq!(dumped.len(), 4);
+        let ev: EventExt = json::from_str(&dumped[3]).unwrap();
+        let meta = ev.meta.unwrap();
+        assert_eq!(meta.value, Some(1.));
+        // N scores 1 and any other legal action 0.
+        let q_values = meta.q_values.unwrap();
+        let others = q_values.len() as f32 - 1.;
+        let expected = 1f32.exp() / (1f32.exp() + others);
+        assert!((meta.prob.unwrap() - expected).abs() < 1e-6);
+        assert!(dumped[..3]
+            .iter()
+            .all(|l| json::from_str::<EventExt>(l).unwrap().meta.is_none()));
+    }
+
+    #[test]
+    fn emit_meta() {
+        let agent =
+            MortalBatchAgent::with_backend(Box::new(DiscardN), EngineConfig::default(), &[0])
+                .unwrap();
+        let mut bot = Bot::new(Box::new(agent), 0);
+        bot.set_emit_meta(true);
+
+        let lines = r#"
+            {"type":"start_game","names":["a","b","c","d"]}
+            {"type":"start_kyoku","bakaze":"E","dora_marker":"1m","kyoku":1,"honba":0,"kyotaku":0,"oya":0,"scores":[25000,25000,25000,25000],"tehais":[["1m","2m","3m","4p","5p","6p","7s","8s","9s","E","E","S","S"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"]]}
+            {"type":"tsumo","actor":0,"pai":"N"}
+        "#;
+        let lines: Vec<_> = lines.trim().lines().map(str::trim).collect();
+        let reaction = bot.sync(&lines).unwrap().unwrap();
+        let value: json::Value = json::from_str(&reaction).unwrap();
+        assert_eq!(value["action"]["type"], "dahai");
+        assert_eq!(value["action"]["pai"], "N");
+
+        let meta = &value["meta"];
+        let q_values = meta["q_values"].as_array().unwrap();
+        let probs: Vec<_> = meta["probs"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|p| p.as_f64().unwrap())
+            .collect();
+        assert_eq!(probs.len(), q_values.len());
+        assert!((probs.iter().sum::<f64>() - 1.).abs() < 1e-5);
+        let top = probs.iter().copied().fold(0., f64::max);
+        assert!((meta["top_action_prob"].as_f64().unwrap() - top).abs() < 1e-6);
+        assert!((meta["prob"].as_f64().unwrap() - top).abs() < 1e-6);
+    }
+
+    #[test]
+    fn rust_agent() {
+        let agent = Tsumogiri::new_batched(&[0]).unwrap();
+        let mut bot = Bot::new(Box::new(agent), 0);
+
+        let lines = r#"
+            {"type":"start_game","names":["a","b","c","d"]}
+            {"type":"start_kyoku","bakaze":"E","dora_marker":"1m","kyoku":1,"honba":0,"kyotaku":0,"oya":0,"scores":[25000,25000,25000,25000],"tehais":[["1m","2m","3m","4p","5p","6p","7s","8s","9s","E","E","S","S"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"]]}
+            {"type":"tsumo","actor":0,"pai":"N"}
+        "#;
+        let lines: Vec<_> = lines.trim().lines().map(str::trim).collect();
+        let reaction = bot.sync(&lines).unwrap().unwrap();
+        let ev: Event = json::from_str(&reaction).unwrap();
+        assert_eq!(
+            ev,
+            Event::Dahai {
+                actor: 0,
+                pai: t!(N),
+                tsumogiri: true,
+            },
+        );
+    }
+    #[test]
+    fn timing() {
+        let agent = Tsumogiri::new_batched(&[0]).unwrap();
+        let mut bot = Bot::new(Box::new(agent), 0);
+
+        let lines = r#"
+            {"type":"start_game","names":["a","b","c","d"]}
+            {"type":"start_kyoku","bakaze":"E","dora_marker":"1m","kyoku":1,"honba":0,"kyotaku":0,"oya":0,"scores":[25000,25000,25000,25000],"tehais":[["1m","2m","3m","4p","5p","6p","7s","8s","9s","E","E","S","S"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"]]}
+            {"type":"tsumo","actor":0,"pai":"N","timing":{"timestamp_ms":1000,"total_ms":60000,"remaining_ms":45000}}
+        "#;
+        let lines: Vec<_> = lines.trim().lines().map(str::trim).collect();
+        let reaction = bot.sync(&lines).unwrap().unwrap();
+        let ev: EventExt = json::from_str(&reaction).unwrap();
+        let timing = ev.timing.unwrap();
+        assert!(timing.think_ms.is_some());
+        assert!(timing.timestamp_ms.is_some());
+        assert_eq!(
+            Timing::budget(&bot.log),
+            Some((Duration::from_secs(60), Duration::from_secs(45))),
+        );
+
+        // The echo carries the timing from the host.
+        let echo = r#"{"type":"dahai","actor":0,"pai":"N","tsumogiri":true,"timing":{"timestamp_ms":3000,"think_ms":2000}}"#;
+        bot.react(echo, true).unwrap();
+        let dumped = bot.game_log.dump().unwrap();
+        let values: Vec<json::Value> = dumped.iter().map(|l| json::from_str(l).unwrap()).collect();
+        assert_eq!(values[2]["timing"]["remaining_ms"], 45000);
+        assert_eq!(values[3]["timing"]["think_ms"], 2000);
+        assert!(values[1].get("timing").is_none());
+    }
+    #[test]
+    fn record_and_verify() {
+        /// A writer that can be read back after being handed to the bot.
+        #[derive(Clone, Default)]
+        struct SharedBuf(Arc<Mutex<Vec<u8>>>);
+        impl Write for SharedBuf {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.0.lock().unwrap().write(buf)
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let buf = SharedBuf::default();
+        let mut bot = Bot::new(Box::new(Tsumogiri::new_batched(&[0]).unwrap()), 0);
+        bot.record_to(Box::new(buf.clone()));
+
+        let lines = r#"
+            {"type":"start_game","names":["a","b","c","d"]}
+            {"type":"start_kyoku","bakaze":"E","dora_marker":"1m","kyoku":1,"honba":0,"kyotaku":0,"oya":0,"scores":[25000,25000,25000,25000],"tehais":[["1m","2m","3m","4p","5p","6p","7s","8s","9s","E","E","S","S"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"]]}
+            {"type":"tsumo","actor":0,"pai":"N"}
+            {"type":"dahai","actor":0,"pai":"N","tsumogiri":true}
+            {"type":"tsumo","actor":1,"pai":"?"}
+            {"type":"dahai","actor":1,"pai":"W","tsumogiri":true}
+            {"type":"tsumo","actor":2,"pai":"?"}
+            {"type":"dahai","actor":2,"pai":"W","tsumogiri":true}
+            {"type":"tsumo","actor":3,"pai":"?"}
+            {"type":"dahai","actor":3,"pai":"W","tsumogiri":true}
+            {"type":"tsumo","actor":0,"pai":"P"}
+        "#;
+        for line in lines.trim().lines() {
+            bot.react(line.trim(), true).unwrap();
+        }
+        let recorded = String::from_utf8(buf.0.lock().unwrap().clone()).unwrap();
+        let entries: Vec<ReplayEntry> = recorded
+            .lines()
+            .map(|l| json::from_str(l).unwrap())
+            .collect();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].lines.len(), 3);
+        assert_eq!(entries[1].lines.len(), 8);
+        assert_eq!(entries[0].obs_hash.len(), 64);
+        assert_ne!(entries[0].obs_hash, entries[1].obs_hash);
+
+        let mut fresh = Bot::new(Box::new(Tsumogiri::new_batched(&[0]).unwrap()), 0);
+        assert!(fresh.verify_replay(recorded.as_bytes()).unwrap().is_empty());
+
+        // A different decision in the record shows up as a divergence.
+        let tampered = recorded.replacen(
+            r#""pai":"P","tsumogiri":true"#,
+            r#""pai":"E","tsumogiri":false"#,
+            1,
+        );
+        let mut fresh = Bot::new(Box::new(Tsumogiri::new_batched(&[0]).unwrap()), 0);
+        let divergences = fresh.verify_replay(tampered.as_bytes()).unwrap();
+        assert_eq!(divergences.len(), 1);
+        let div = &divergences[0];
+        assert_eq!(div.index, 1);
+        assert_eq!(div.expected_obs_hash, div.actual_obs_hash);
+        assert_eq!(
+            div.actual_action,
+            Some(Event::Dahai {
+                actor: 0,
+                pai: t!(P),
+                tsumogiri: true,
+            }),
+        );
+    }
+
+    /// Polls `fut` on the current thread until it is ready.
+    fn block_on<F: Future>(fut: F) -> F::Output {
+        struct ThreadWaker(thread::Thread);
+        impl Wake for ThreadWaker {
+            fn wake(self: Arc<Self>) {
+                self.0.unpark();
+            }
+        }
+
+        let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+        let mut cx = task::Context::from_waker(&waker);
+        let mut fut = pin!(fut);
+        loop {
+            match fut.as_mut().poll(&mut cx) {
+                Poll::Ready(ret) => return ret,
+                Poll::Pending => thread::park(),
+            }
+        }
+    }
+
+    /// Reacts like `Tsumogiri`, after yielding once.
+    struct AsyncTsumogiri(Box<dyn BatchAgent + Send>);
+
+    /// A future that is pending on its first poll.
+    struct YieldOnce(bool);
+
+    impl Future for YieldOnce {
+        type Output = ();
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<()> {
+            if self.0 {
+                Poll::Ready(())
+            } else {
+                self.0 = true;
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            }
+        }
+    }
+
+    impl AsyncBatchAgent for AsyncTsumogiri {
+        fn name(&self) -> String {
+            "async_tsumogiri".to_owned()
+        }
+
+        fn set_scene<'a>(
+            &'a mut self,
+            index: usize,
+            log: &'a [EventExt],
+            state: &'a PlayerState,
+            invisible_state: Option<InvisibleState>,
+        ) -> BoxFuture<'a, Result<()>> {
+            Box::pin(async move {
+                YieldOnce(false).await;
+                self.0.set_scene(index, log, state, invisible_state)
+            })
+        }
+
+        fn get_reaction<'a>(
+            &'a mut self,
+            index: usize,
+            log: &'a [EventExt],
+            state: &'a PlayerState,
+            invisible_state: Option<InvisibleState>,
+        ) -> BoxFuture<'a, Result<EventExt>> {
+            Box::pin(async move {
+                YieldOnce(false).await;
+                self.0.get_reaction(index, log, state, invisible_state)
+            })
+        }
+    }
+
+    /// Fails on every call of `get_reaction`.
+    struct FailingAgent;
+
+    impl BatchAgent for FailingAgent {
+        fn name(&self) -> String {
+            "failing".to_owned()
+        }
+
+        fn set_scene(
+            &mut self,
+            _: usize,
+            _: &[EventExt],
+            _: &PlayerState,
+            _: Option<InvisibleState>,
+        ) -> Result<()> {
+            Ok(())
+        }
+
+        fn get_reaction(
+            &mut self,
+            _: usize,
+            _: &[EventExt],
+            _: &PlayerState,
+            _: Option<InvisibleState>,
+        ) -> Result<EventExt> {
+            Err(anyhow!("out of memory"))
+        }
+    }
+
+    #[test]
+    fn error_kinds() {
+        let mut bot = Bot::new(Box::new(FailingAgent), 0);
+
+        let err = bot.react(r#"{"type":"tsumo""#, true).unwrap_err();
+        assert!(matches!(err, Error::Parse(_)));
+        let err = bot.react(r#"{"type":"dance"}"#, true).unwrap_err();
+        assert!(matches!(err, Error::Parse(_)));
+
+        let lines = r#"
+            {"type":"start_game","names":["a","b","c","d"]}
+            {"type":"start_kyoku","bakaze":"E","dora_marker":"1m","kyoku":1,"honba":0,"kyotaku":0,"oya":0,"scores":[25000,25000,25000,25000],"tehais":[["1m","2m","3m","4p","5p","6p","7s","8s","9s","E","E","S","S"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"]]}
+        "#;
+        for line in lines.trim().lines() {
+            assert!(bot.react(line.trim(), true).unwrap().is_none());
+        }
+
+        // Sanma is not supported.
+        let err = bot
+            .react(r#"{"type":"nukidora","actor":1,"pai":"N"}"#, true)
+            .unwrap_err();
+        assert!(matches!(err, Error::InvalidEvent(_)));
+
+        let err = bot
+            .react(r#"{"type":"tsumo","actor":0,"pai":"N"}"#, true)
+            .unwrap_err();
+        assert!(matches!(err, Error::Engine(_)));
+        assert!(err.to_string().contains("out of memory"));
+    }
+
+    #[test]
+    fn react_async() {
+        let lines = r#"
+            {"type":"start_game","names":["a","b","c","d"]}
+            {"type":"start_kyoku","bakaze":"E","dora_marker":"1m","kyoku":1,"honba":0,"kyotaku":0,"oya":0,"scores":[25000,25000,25000,25000],"tehais":[["1m","2m","3m","4p","5p","6p","7s","8s","9s","E","E","S","S"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"]]}
+            {"type":"tsumo","actor":0,"pai":"N"}
+        "#;
+        let lines: Vec<_> = lines.trim().lines().map(str::trim).collect();
+        let expected = Event::Dahai {
+            actor: 0,
+            pai: t!(N),
+            tsumogiri: true,
+        };
+
+        let agent = AsyncTsumogiri(Box::new(Tsumogiri::new_batched(&[0]).unwrap()));
+        let mut bot = Bot::new_async(Box::new(agent), 0);
+        for line in &lines[..2] {
+            assert!(block_on(bot.react_async(line, true)).unwrap().is_none());
+        }
+        // The async agent cannot be called synchronously.
+        assert!(bot.react(lines[2], true).is_err());
+        // It can be spawned on multi-threaded runtimes.
+        fn assert_send<T: Send>(t: T) -> T {
+            t
+        }
+        let reaction = block_on(assert_send(bot.react_async(lines[2], true)))
+            .unwrap()
+            .unwrap();
+        let ev: Event = json::from_str(&reaction).unwrap();
+        assert_eq!(ev, expected);
+
+        // The sync agent works in async too.
+        let agent = Tsumogiri::new_batched(&[0]).unwrap();
+        let mut bot = Bot::new(Box::new(agent), 0);
+        let mut reaction = None;
+        for line in &lines {
+            reaction = block_on(bot.react_async(line, true)).unwrap();
+        }
+        let ev: Event = json::from_str(&reaction.unwrap()).unwrap();
+        assert_eq!(ev, expected);
     }
 }
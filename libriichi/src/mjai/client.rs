@@ -0,0 +1,191 @@
+use super::Bot;
+use std::io::prelude::*;
+use std::io::BufReader;
+use std::net::TcpStream;
+
+use anyhow::{bail, Context, Result};
+use serde_json::{self as json, json};
+
+/// How to join a game on an mjai server.
+#[derive(Debug, Clone)]
+pub struct JoinOptions {
+    /// Name to send in `join`.
+    pub name: String,
+    /// Room to send in `join`.
+    pub room: String,
+    /// Player ID to use if the server does not send `id` in `start_game`.
+    pub player_id: Option<u8>,
+}
+
+impl Default for JoinOptions {
+    fn default() -> Self {
+        Self {
+            name: "mortal".to_owned(),
+            room: "default".to_owned(),
+            player_id: None,
+        }
+    }
+}
+
+/// Connects to the mjai server at `url` and plays one game on it.
+///
+/// `url` is either `mjsonp://host:port/room` as used by gimite/mjai, in which
+/// case the room overrides `opts.room`, or a bare `host:port`.
+pub fn connect<F>(url: &str, opts: &JoinOptions, new_bot: F) -> Result<()>
+where
+    F: FnMut(u8) -> Result<Bot>,
+{
+    let (addr, room) = parse_url(url)?;
+    let opts = JoinOptions {
+        room: room.unwrap_or(&opts.room).to_owned(),
+        ..opts.clone()
+    };
+
+    let stream =
+        TcpStream::connect(addr).with_context(|| format!("failed to connect to {addr}"))?;
+    stream.set_nodelay(true)?;
+    let reader = BufReader::new(stream.try_clone()?);
+    play(reader, stream, &opts, new_bot)
+}
+
+/// Plays one game over the mjai protocol, where `reader` and `writer` are the
+/// messages from and to the server respectively, one JSON per line.
+///
+/// Every message is replied to, either with `join` to `hello`, or with the
+/// reaction of the bot, or `none`. A new bot is created by `new_bot` with the
+/// player ID on every `start_game`, and it returns after `end_game`.
+pub fn play<R, W, F>(reader: R, mut writer: W, opts: &JoinOptions, mut new_bot: F) -> Result<()>
+where
+    R: BufRead,
+    W: Write,
+    F: FnMut(u8) -> Result<Bot>,
+{
+    let mut bot = None;
+    for line in reader.lines() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let value: json::Value =
+            json::from_str(line).with_context(|| format!("failed to parse {line}"))?;
+        let reply = match value["type"].as_str() {
+            Some("hello") => json!({
+                "type": "join",
+                "name": opts.name,
+                "room": opts.room,
+            })
+            .to_string(),
+            Some("error") => bail!("received error from server: {line}"),
+            Some(ty) => {
+                if ty == "start_game" {
+                    let player_id = value["id"]
+                        .as_u64()
+                        .and_then(|id| u8::try_from(id).ok())
+                        .or(opts.player_id)
+                        .context("player ID is given by neither `start_game` nor the options")?;
+                    bot = Some(new_bot(player_id)?);
+                }
+                let bot = bot.as_mut().context("received event before start_game")?;
+                let reaction = bot.react(line, true)?;
+                reaction.unwrap_or_else(|| r#"{"type":"none"}"#.to_owned())
+            }
+            None => bail!("message without type: {line}"),
+        };
+        writeln!(writer, "{reply}")?;
+        writer.flush()?;
+
+        if value["type"] == "end_game" {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+/// Splits `url` into the address and the room, if any.
+fn parse_url(url: &str) -> Result<(&str, Option<&str>)> {
+    let Some(rest) = url.strip_prefix("mjsonp://") else {
+        if url.contains("://") {
+            bail!("unsupported scheme of {url}, expected `mjsonp://`");
+        }
+        return Ok((url, None));
+    };
+    let ret = match rest.split_once('/') {
+        Some((addr, room)) if !room.is_empty() => (addr, Some(room)),
+        Some((addr, _)) => (addr, None),
+        None => (rest, None),
+    };
+    Ok(ret)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::agent::Tsumogiri;
+    use std::net::TcpListener;
+    use std::thread;
+
+    #[test]
+    fn url() {
+        assert_eq!(
+            parse_url("mjsonp://localhost:11600/default").unwrap(),
+            ("localhost:11600", Some("default")),
+        );
+        assert_eq!(
+            parse_url("mjsonp://localhost:11600").unwrap(),
+            ("localhost:11600", None),
+        );
+        assert_eq!(
+            parse_url("127.0.0.1:11600").unwrap(),
+            ("127.0.0.1:11600", None),
+        );
+        assert!(parse_url("http://localhost:11600").is_err());
+    }
+
+    #[test]
+    fn tcp_game() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut writer = stream;
+
+            let messages = [
+                r#"{"type":"hello","protocol":"mjsonp","protocol_version":3}"#,
+                r#"{"type":"start_game","id":0,"names":["a","b","c","d"]}"#,
+                r#"{"type":"start_kyoku","bakaze":"E","dora_marker":"1m","kyoku":1,"honba":0,"kyotaku":0,"oya":0,"scores":[25000,25000,25000,25000],"tehais":[["1m","2m","3m","4p","5p","6p","7s","8s","9s","E","E","S","S"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"]]}"#,
+                r#"{"type":"tsumo","actor":0,"pai":"N"}"#,
+                r#"{"type":"end_game"}"#,
+            ];
+            let mut replies = vec![];
+            for msg in messages {
+                writeln!(writer, "{msg}").unwrap();
+                let mut reply = String::new();
+                reader.read_line(&mut reply).unwrap();
+                replies.push(json::from_str::<json::Value>(&reply).unwrap());
+            }
+            replies
+        });
+
+        let url = format!("mjsonp://{addr}/test");
+        connect(&url, &JoinOptions::default(), |id| {
+            Ok(Bot::new(Box::new(Tsumogiri::new_batched(&[id])?), id))
+        })
+        .unwrap();
+
+        let replies = server.join().unwrap();
+        assert_eq!(
+            replies[0],
+            json!({"type": "join", "name": "mortal", "room": "test"}),
+        );
+        assert_eq!(replies[1]["type"], "none");
+        assert_eq!(replies[2]["type"], "none");
+        assert_eq!(replies[3]["type"], "dahai");
+        assert_eq!(replies[3]["pai"], "N");
+        assert_eq!(replies[4]["type"], "none");
+    }
+}
@@ -1,6 +1,8 @@
+use crate::rule::RuleSet;
 use crate::tile::Tile;
 use std::error::Error;
 use std::fmt;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use derivative::Derivative;
 use serde::{Deserialize, Serialize};
@@ -30,6 +32,10 @@ pub enum Event {
         /// Consists of (nonce, key).
         #[serde(default)]
         seed: Option<(u64, u64)>,
+        /// Extension: what is known about the game and the players, such as
+        /// the ones of a converted Tenhou log.
+        #[serde(default)]
+        meta: Option<GameMeta>,
     },
     StartKyoku {
         bakaze: Tile,
@@ -99,12 +105,27 @@ pub enum Event {
     Reach {
         #[serde_as(deserialize_as = "TryFromInto<Actor>")]
         actor: u8,
+        /// Extension: open riichi, where the hand is shown to everyone. Only
+        /// meaningful with `RuleSet::open_riichi`.
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        open: bool,
     },
     ReachAccepted {
         #[serde_as(deserialize_as = "TryFromInto<Actor>")]
         actor: u8,
     },
 
+    /// Sanma only. Setting aside a N as nukidora, which is followed by a
+    /// rinshan tsumo.
+    ///
+    /// Sanma itself is not supported: the event is only parsed so that
+    /// `PlayerState` and the converters can refuse sanma logs by name.
+    Nukidora {
+        #[serde_as(deserialize_as = "TryFromInto<Actor>")]
+        actor: u8,
+        pai: Tile,
+    },
+
     Hora {
         #[serde_as(deserialize_as = "TryFromInto<Actor>")]
         actor: u8,
@@ -118,12 +139,85 @@ pub enum Event {
     Ryukyoku {
         #[serde(default)]
         deltas: Option<[i32; 4]>,
+        #[serde(default)]
+        reason: Option<RyukyokuReason>,
+    },
+    /// Extension: a chombo of `actor`, which ends the kyoku without an
+    /// outcome. The kyoku is then replayed with the same honba, and the
+    /// riichi sticks of it go back to their owners. See `RuleSet::chombo`.
+    Chombo {
+        #[serde_as(deserialize_as = "TryFromInto<Actor>")]
+        actor: u8,
+        #[serde(default)]
+        deltas: Option<[i32; 4]>,
     },
 
     EndKyoku,
     EndGame,
 }
 
+/// Metadata of a game, where unknown fields are `None`.
+#[skip_serializing_none]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GameMeta {
+    /// Tenhou dan of each player, from 0 for 新人 to 20 for 天鳳位.
+    #[serde(default)]
+    pub dans: Option<[u8; 4]>,
+    /// Tenhou rate of each player, rounded.
+    #[serde(default)]
+    pub rates: Option<[u16; 4]>,
+    #[serde(default)]
+    pub room: Option<Room>,
+    #[serde(default)]
+    pub rule: Option<RuleSet>,
+}
+
+/// Tenhou rooms.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Room {
+    /// 一般
+    Ippan,
+    /// 上級
+    Joukyuu,
+    /// 特上
+    Tokujou,
+    /// 鳳凰
+    Houou,
+}
+
+impl Room {
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Ippan => "ippan",
+            Self::Joukyuu => "joukyuu",
+            Self::Tokujou => "tokujou",
+            Self::Houou => "houou",
+        }
+    }
+}
+
+/// The reasons of `ryukyoku`, spelled the same as in the original mjai.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RyukyokuReason {
+    /// 荒牌平局
+    Fanpai,
+    /// 流し満貫
+    Nagashimangan,
+    /// 九種九牌
+    Kyushukyuhai,
+    /// 四風連打
+    Suufonrenta,
+    /// 四家立直
+    Suuchariichi,
+    /// 四槓散了
+    Suukaikan,
+    /// 三家和了
+    Sanchaho,
+}
+
 #[derive(Deserialize)]
 struct BoundedU8<const MIN: u8, const MAX: u8>(u8);
 
@@ -139,6 +233,26 @@ pub struct EventExt {
     #[serde(flatten)]
     pub event: Event,
     pub meta: Option<Metadata>,
+    pub timing: Option<Timing>,
+}
+
+/// When an event happened and how long its actor took, as recorded by whoever
+/// hosted the game. All of them are optional, as hosts differ in what they
+/// can tell.
+///
+/// When sent to a bot on an event it can react to, `total_ms` and
+/// `remaining_ms` are the time budget for the reaction.
+#[skip_serializing_none]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Timing {
+    /// Milliseconds since the Unix epoch.
+    pub timestamp_ms: Option<u64>,
+    /// Milliseconds the actor spent on deciding the event.
+    pub think_ms: Option<u32>,
+    /// The whole time bank of the actor for the game, in milliseconds.
+    pub total_ms: Option<u32>,
+    /// What is left of `total_ms`, in milliseconds.
+    pub remaining_ms: Option<u32>,
 }
 
 #[skip_serializing_none]
@@ -151,7 +265,20 @@ pub struct Metadata {
     pub eval_time_ns: Option<u64>,
     pub shanten: Option<i8>,
     pub at_furiten: Option<bool>,
+    /// Probability of the action chosen by the engine among all the legal
+    /// actions.
+    pub prob: Option<f32>,
+    /// Value estimate of the situation for the acting player, which is the
+    /// largest Q value among the legal actions. It is a single value rather
+    /// than one per seat, as the engine only estimates the Q values of the
+    /// player who acts, and has nothing to say about the other seats.
+    pub value: Option<f32>,
     pub kan_select: Option<Box<Metadata>>,
+    /// The error of the engine when the reaction is made by a fallback
+    /// instead.
+    pub engine_error: Option<String>,
+    /// Number of engine failures of the agent so far, including this one.
+    pub engine_error_count: Option<u32>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -159,6 +286,7 @@ pub struct EventWithCanAct {
     #[serde(flatten)]
     pub event: Event,
     pub can_act: Option<bool>,
+    pub timing: Option<Timing>,
 }
 
 impl Event {
@@ -175,7 +303,8 @@ impl Event {
             | Self::Ankan { actor, .. }
             | Self::Reach { actor, .. }
             | Self::ReachAccepted { actor, .. }
-            | Self::Hora { actor, .. } => Some(actor),
+            | Self::Hora { actor, .. }
+            | Self::Nukidora { actor, .. } => Some(actor),
             _ => None,
         }
     }
@@ -205,7 +334,42 @@ impl EventExt {
     #[inline]
     #[must_use]
     pub const fn no_meta(event: Event) -> Self {
-        Self { event, meta: None }
+        Self {
+            event,
+            meta: None,
+            timing: None,
+        }
+    }
+}
+
+impl Timing {
+    /// Returns the timing stamped with the current time.
+    #[must_use]
+    pub fn now() -> Self {
+        let timestamp_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .ok()
+            .and_then(|d| d.as_millis().try_into().ok());
+        Self {
+            timestamp_ms,
+            ..Default::default()
+        }
+    }
+
+    /// The time budget of the reaction to the last event of `log`, as
+    /// `(total, remaining)`, if the host has told it.
+    ///
+    /// `total` falls back to `remaining` if the host has only told the
+    /// latter.
+    #[must_use]
+    pub fn budget(log: &[EventExt]) -> Option<(Duration, Duration)> {
+        let timing = log.last()?.timing?;
+        let remaining = timing.remaining_ms?;
+        let total = timing.total_ms.unwrap_or(remaining);
+        Some((
+            Duration::from_millis(total.into()),
+            Duration::from_millis(remaining.into()),
+        ))
     }
 }
 
@@ -237,9 +401,11 @@ mod test {
             {"type":"dora","dora_marker":"3s"}
             {"type":"reach","actor":1}
             {"type":"reach_accepted","actor":2}
+            {"type":"nukidora","actor":1,"pai":"N"}
             {"type":"hora","actor":3,"target":1,"deltas":[0,-8000,0,9000],"ura_markers":["4p"]}
             {"type":"hora","actor":3,"target":1}
             {"type":"ryukyoku","deltas":[0,1500,0,-1500]}
+            {"type":"ryukyoku","deltas":[0,0,0,0],"reason":"kyushukyuhai"}
             {"type":"ryukyoku"}
             {"type":"end_kyoku"}
             {"type":"end_game"}
@@ -1,15 +1,34 @@
+mod batch_bot;
 mod bot;
 mod event;
+mod multi_bot;
+mod replay;
 
-pub use event::{Event, EventExt, EventWithCanAct, Metadata, OutOfBoundError};
+pub mod client;
 
+pub use batch_bot::BatchBot;
+pub use bot::Bot;
+pub use event::{
+    Event, EventExt, EventWithCanAct, GameMeta, Metadata, OutOfBoundError, Room, RyukyokuReason,
+    Timing,
+};
+pub use multi_bot::MultiBot;
+pub use replay::{obs_hash, Divergence, ReplayEntry};
+
+#[cfg(feature = "python")]
+use crate::agent::MctsConfig;
+#[cfg(feature = "python")]
 use crate::py_helper::add_submodule;
-use bot::Bot;
 
+#[cfg(feature = "python")]
 use pyo3::prelude::*;
 
+#[cfg(feature = "python")]
 pub(crate) fn register_module(py: Python<'_>, prefix: &str, super_mod: &PyModule) -> PyResult<()> {
     let m = PyModule::new(py, "mjai")?;
     m.add_class::<Bot>()?;
+    m.add_class::<BatchBot>()?;
+    m.add_class::<MultiBot>()?;
+    m.add_class::<MctsConfig>()?;
     add_submodule(py, prefix, super_mod, m)
 }
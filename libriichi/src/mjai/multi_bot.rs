@@ -0,0 +1,159 @@
+use super::{Event, EventExt};
+use crate::agent::BatchAgent;
+#[cfg(feature = "python")]
+use crate::agent::MortalBatchAgent;
+use crate::state::PlayerState;
+
+use anyhow::{Context, Result};
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+use riichi_macros::pyclass;
+use serde_json as json;
+
+/// `MultiBot` plays all four seats with one single agent, consuming the
+/// global event stream, i.e. the one where every tile is visible.
+///
+/// Reactions of all the seats are evaluated in one batch.
+#[pyclass]
+#[pyo3(text_signature = "(engine)")]
+pub struct MultiBot {
+    agent: Box<dyn BatchAgent + Send>,
+    states: [PlayerState; 4],
+    log: Vec<EventExt>,
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl MultiBot {
+    #[new]
+    fn py_new(engine: PyObject) -> Result<Self> {
+        let agent = MortalBatchAgent::new(engine, &[0, 1, 2, 3])?;
+        Ok(Self::new(Box::new(agent)))
+    }
+
+    /// Returns a list of length 4, which consists of the reaction of each
+    /// seat to `line`, or `None` if the seat cannot react.
+    ///
+    /// Both `line` and the reactions are JSON strings representing one single
+    /// mjai event.
+    #[pyo3(name = "react")]
+    #[pyo3(text_signature = "($self, line, /)")]
+    fn react_py(&mut self, line: &str, py: Python<'_>) -> Result<[Option<String>; 4]> {
+        py.allow_threads(move || self.react(line))
+    }
+}
+
+impl MultiBot {
+    /// `agent` must have been created with player IDs `[0, 1, 2, 3]`, in
+    /// which order the seats are indexed.
+    #[must_use]
+    pub fn new(agent: Box<dyn BatchAgent + Send>) -> Self {
+        Self {
+            agent,
+            states: [
+                PlayerState::new(0),
+                PlayerState::new(1),
+                PlayerState::new(2),
+                PlayerState::new(3),
+            ],
+            log: vec![],
+        }
+    }
+
+    pub fn react(&mut self, line: &str) -> Result<[Option<String>; 4]> {
+        let event: Event =
+            json::from_str(line).with_context(|| format!("failed to parse event {line}"))?;
+
+        match event {
+            Event::StartGame { .. } => {
+                for i in 0..4 {
+                    self.agent.start_game(i)?;
+                }
+            }
+            Event::EndKyoku => {
+                self.log.clear();
+                for i in 0..4 {
+                    self.agent.end_kyoku(i)?;
+                }
+            }
+            Event::EndGame => {
+                for i in 0..4 {
+                    self.agent.end_game(i, &Default::default())?;
+                }
+            }
+            _ => {
+                self.log.push(EventExt::no_meta(event.clone()));
+            }
+        };
+
+        let mut can_act = [false; 4];
+        for (state, can_act) in self.states.iter_mut().zip(&mut can_act) {
+            *can_act = state.update(&event)?.can_act();
+        }
+
+        for (i, state) in self.states.iter().enumerate() {
+            if can_act[i] {
+                self.agent
+                    .set_scene(i, &self.log, state, None)
+                    .with_context(|| format!("failed to add state of seat {i}"))?;
+            }
+        }
+
+        let mut ret: [Option<String>; 4] = Default::default();
+        for (i, state) in self.states.iter().enumerate() {
+            if can_act[i] {
+                let reaction = self
+                    .agent
+                    .get_reaction(i, &self.log, state, None)
+                    .with_context(|| format!("failed to get reaction of seat {i}"))?;
+                ret[i] = Some(json::to_string(&reaction)?);
+            }
+        }
+
+        Ok(ret)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::agent::Tsumogiri;
+    use crate::t;
+
+    #[test]
+    fn react_per_seat() {
+        let agent = Tsumogiri::new_batched(&[0, 1, 2, 3]).unwrap();
+        let mut bot = MultiBot::new(Box::new(agent));
+
+        let lines = r#"
+            {"type":"start_game","names":["a","b","c","d"]}
+            {"type":"start_kyoku","bakaze":"E","dora_marker":"1m","kyoku":1,"honba":0,"kyotaku":0,"oya":0,"scores":[25000,25000,25000,25000],"tehais":[["1m","2m","3m","4p","5p","6p","7s","8s","9s","E","E","S","S"],["1m","2m","3m","4p","5p","6p","7s","8s","9s","W","W","N","N"],["1p","2p","3p","4s","5s","6s","7m","8m","9m","P","P","F","F"],["1p","2p","3p","4s","5s","6s","7m","8m","9m","C","C","N","6m"]]}
+        "#;
+        for line in lines.trim().lines() {
+            let ret = bot.react(line.trim()).unwrap();
+            assert!(ret.iter().all(Option::is_none));
+        }
+
+        let ret = bot
+            .react(r#"{"type":"tsumo","actor":0,"pai":"W"}"#)
+            .unwrap();
+        assert!(ret[1..].iter().all(Option::is_none));
+        let ev: Event = json::from_str(ret[0].as_ref().unwrap()).unwrap();
+        assert_eq!(
+            ev,
+            Event::Dahai {
+                actor: 0,
+                pai: t!(W),
+                tsumogiri: true,
+            },
+        );
+
+        // Seat 1 can pon, but Tsumogiri always passes.
+        let ret = bot
+            .react(r#"{"type":"dahai","actor":0,"pai":"W","tsumogiri":true}"#)
+            .unwrap();
+        assert!(ret[0].is_none() && ret[2..].iter().all(Option::is_none));
+        let ev: Event = json::from_str(ret[1].as_ref().unwrap()).unwrap();
+        assert_eq!(ev, Event::None);
+    }
+}
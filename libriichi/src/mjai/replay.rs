@@ -0,0 +1,131 @@
+use super::{Bot, Event, EventExt};
+use crate::consts::ObsVersion;
+use crate::state::{PlayerState, SuitPerm};
+use std::fmt::Write as _;
+use std::io::{BufRead, Write};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json as json;
+use sha3::{Digest, Sha3_256};
+
+/// One decision of a `Bot`, which makes up one line of a replay file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReplayEntry {
+    /// The lines received since the previous entry, where the last one is the
+    /// line reacted to.
+    pub lines: Vec<String>,
+    /// See `obs_hash`.
+    pub obs_hash: String,
+    pub action: Event,
+}
+
+/// An entry of which the replay does not match the record.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Divergence {
+    /// Index of the entry in the replay file.
+    pub index: usize,
+    pub expected_obs_hash: String,
+    pub actual_obs_hash: String,
+    pub expected_action: Event,
+    /// `None` if the bot did not react at all.
+    pub actual_action: Option<Event>,
+}
+
+/// Writes the decisions of a `Bot` as JSON lines of `ReplayEntry`.
+pub(super) struct Recorder {
+    writer: Box<dyn Write + Send>,
+    pending: Vec<String>,
+}
+
+impl Recorder {
+    pub(super) fn new(writer: Box<dyn Write + Send>) -> Self {
+        Self {
+            writer,
+            pending: vec![],
+        }
+    }
+
+    pub(super) fn push_line(&mut self, line: &str) {
+        self.pending.push(line.to_owned());
+    }
+
+    /// Writes an entry of `action` made on `state`, with the lines pushed so
+    /// far, and flushes it, so that a crash loses nothing.
+    pub(super) fn record(&mut self, state: &PlayerState, action: &Event) -> Result<()> {
+        let entry = ReplayEntry {
+            lines: std::mem::take(&mut self.pending),
+            obs_hash: obs_hash(state),
+            action: action.clone(),
+        };
+        json::to_writer(&mut self.writer, &entry)?;
+        self.writer.write_all(b"\n")?;
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    pub(super) fn flush(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Returns the SHA3-256 in hex of the obs and the mask of `state` in every
+/// `ObsVersion`, which changes whenever any of the encoders does.
+#[must_use]
+pub fn obs_hash(state: &PlayerState) -> String {
+    let mut hasher = Sha3_256::new();
+    for version in [
+        ObsVersion::V1,
+        ObsVersion::V2,
+        ObsVersion::V3,
+        ObsVersion::V4,
+    ] {
+        let (obs, mask) = state.encode_obs_with(false, version, SuitPerm::IDENTITY);
+        for v in &obs {
+            hasher.update(v.to_le_bytes());
+        }
+        for &b in &mask {
+            hasher.update([b as u8]);
+        }
+    }
+    hasher
+        .finalize()
+        .iter()
+        .fold(String::with_capacity(64), |mut s, b| {
+            write!(s, "{b:02x}").unwrap();
+            s
+        })
+}
+
+/// See `Bot::verify_replay`.
+pub(super) fn verify(bot: &mut Bot, reader: impl BufRead) -> Result<Vec<Divergence>> {
+    let mut ret = vec![];
+    for (index, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let entry: ReplayEntry =
+            json::from_str(&line).with_context(|| format!("invalid entry {index}"))?;
+
+        let reaction = bot
+            .sync(&entry.lines)
+            .with_context(|| format!("failed to replay entry {index}"))?;
+        let actual_action = reaction
+            .map(|r| json::from_str::<EventExt>(&r).map(|ev| ev.event))
+            .transpose()?;
+        let actual_obs_hash = obs_hash(bot.state());
+
+        if actual_obs_hash != entry.obs_hash || actual_action.as_ref() != Some(&entry.action) {
+            ret.push(Divergence {
+                index,
+                expected_obs_hash: entry.obs_hash,
+                actual_obs_hash,
+                expected_action: entry.action,
+                actual_action,
+            });
+        }
+    }
+    Ok(ret)
+}
@@ -0,0 +1,78 @@
+//! The parallel iterators of rayon with the `parallel` feature, or the serial
+//! iterators of std under the same names without it, such as for WASM where
+//! there are no threads.
+//!
+//! Only the parts of rayon used in this crate are provided, so that the same
+//! code builds either way.
+
+#[cfg(feature = "parallel")]
+pub use indicatif::ParallelProgressIterator;
+#[cfg(feature = "parallel")]
+pub use rayon::prelude::*;
+
+#[cfg(not(feature = "parallel"))]
+pub use indicatif::ProgressIterator as ParallelProgressIterator;
+#[cfg(not(feature = "parallel"))]
+pub use serial::*;
+
+#[cfg(not(feature = "parallel"))]
+mod serial {
+    use std::slice::Chunks;
+
+    pub trait IntoParallelIterator {
+        type Iter: Iterator<Item = Self::Item>;
+        type Item;
+
+        fn into_par_iter(self) -> Self::Iter;
+    }
+
+    impl<I: IntoIterator> IntoParallelIterator for I {
+        type Iter = I::IntoIter;
+        type Item = I::Item;
+
+        #[inline]
+        fn into_par_iter(self) -> Self::Iter {
+            self.into_iter()
+        }
+    }
+
+    pub trait IntoParallelRefIterator<'a> {
+        type Iter: Iterator<Item = Self::Item>;
+        type Item: 'a;
+
+        fn par_iter(&'a self) -> Self::Iter;
+    }
+
+    impl<'a, I: 'a + ?Sized> IntoParallelRefIterator<'a> for I
+    where
+        &'a I: IntoIterator,
+    {
+        type Iter = <&'a I as IntoIterator>::IntoIter;
+        type Item = <&'a I as IntoIterator>::Item;
+
+        #[inline]
+        fn par_iter(&'a self) -> Self::Iter {
+            self.into_iter()
+        }
+    }
+
+    pub trait ParallelBridge: Iterator + Sized {
+        #[inline]
+        fn par_bridge(self) -> Self {
+            self
+        }
+    }
+
+    impl<I: Iterator> ParallelBridge for I {}
+
+    pub trait ParallelSlice<T> {
+        fn par_chunks(&self, chunk_size: usize) -> Chunks<'_, T>;
+    }
+
+    impl<T> ParallelSlice<T> for [T] {
+        #[inline]
+        fn par_chunks(&self, chunk_size: usize) -> Chunks<'_, T> {
+            self.chunks(chunk_size)
+        }
+    }
+}
@@ -0,0 +1,698 @@
+//! Rating ladders of the platforms, which turn a sequence of placements into
+//! a rating trajectory, and the Monte Carlo simulation of them given the
+//! placement distribution of a player.
+//!
+//! Only 4-player games are modeled, with the rules of each platform at the
+//! time of writing.
+
+#[cfg(feature = "python")]
+use crate::py_helper::add_submodule;
+use std::fmt;
+
+use anyhow::{bail, ensure, Result};
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+use rand::distributions::{Distribution, WeightedIndex};
+use rand::SeedableRng;
+use rand_chacha::ChaCha12Rng;
+use riichi_macros::{pyclass, pymethods};
+use serde::Serialize;
+#[cfg(feature = "python")]
+use serde_json as json;
+
+const TENHOU_LEVELS: [&str; 21] = [
+    "新人",
+    "9級",
+    "8級",
+    "7級",
+    "6級",
+    "5級",
+    "4級",
+    "3級",
+    "2級",
+    "1級",
+    "初段",
+    "二段",
+    "三段",
+    "四段",
+    "五段",
+    "六段",
+    "七段",
+    "八段",
+    "九段",
+    "十段",
+    "天鳳位",
+];
+/// Points to be promoted from each level.
+const TENHOU_THRESHOLDS: [i32; 21] = [
+    20, 20, 20, 20, 40, 60, 80, 100, 100, 100, 400, 800, 1200, 1600, 2000, 2400, 2800, 3200, 3600,
+    4000, 0,
+];
+const TENHOU_SHODAN: u8 = 10;
+const TENHOUI: u8 = 20;
+
+const MAJSOUL_LEVELS: [&str; 16] = [
+    "初心1", "初心2", "初心3", "雀士1", "雀士2", "雀士3", "雀傑1", "雀傑2", "雀傑3", "雀豪1",
+    "雀豪2", "雀豪3", "雀聖1", "雀聖2", "雀聖3", "魂天",
+];
+/// Points to be promoted from each level.
+const MAJSOUL_THRESHOLDS: [i32; 16] = [
+    20, 80, 200, 600, 800, 1000, 1200, 1400, 2000, 2800, 3200, 3600, 4000, 6000, 9000, 0,
+];
+const MAJSOUL_JANSHI: u8 = 3;
+const MAJSOUL_JANKETSU: u8 = 6;
+const MAJSOUL_CELESTIAL: u8 = 15;
+
+/// A rating of a platform, which is updated game by game.
+pub trait Ladder: Clone {
+    /// `rank` counts from 0. `score` is the final score, which only some
+    /// ladders take into account.
+    fn update(&mut self, rank: u8, score: i32);
+
+    /// A scalar of the standing, higher the better, such as the level plus
+    /// the fraction of the way to the next one.
+    fn progress(&self) -> f64;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TenhouRoom {
+    Ippan,
+    Joukyuu,
+    Tokujou,
+    Houou,
+}
+
+/// Tenhou dan and its points.
+#[pyclass]
+#[pyo3(text_signature = "(*, room = 'houou', hanchan = True, level = '新人', pt = None)")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TenhouDan {
+    pub room: TenhouRoom,
+    pub hanchan: bool,
+    /// Index of `TenhouDan.levels()`.
+    pub level: u8,
+    pub pt: i32,
+}
+
+/// Tenhou R.
+#[pyclass]
+#[pyo3(text_signature = "(*, table_rate = 2000.0, rate = 1500.0, games = 0)")]
+#[derive(Debug, Clone, PartialEq)]
+pub struct TenhouRate {
+    /// The average R of the tables played at, which is assumed to be fixed.
+    pub table_rate: f64,
+    pub rate: f64,
+    /// Number of games played, which makes the changes smaller up to 400.
+    pub games: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MajsoulRoom {
+    Bronze,
+    Silver,
+    Gold,
+    Jade,
+    Throne,
+}
+
+/// Majsoul rank and its points, of hanchan only. Celestial is the end of it.
+#[pyclass]
+#[pyo3(text_signature = "(*, room = 'jade', level = '初心1', pt = None)")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MajsoulRank {
+    pub room: MajsoulRoom,
+    /// Index of `MajsoulRank.levels()`.
+    pub level: u8,
+    pub pt: i32,
+}
+
+/// The distribution of the placements of a player, which are independent
+/// from game to game.
+#[derive(Debug, Clone)]
+pub struct Placements {
+    pub rank_probs: [f64; 4],
+    /// The average final score of each rank, see `Ladder::update`.
+    pub scores: [i32; 4],
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Band {
+    pub mean: f64,
+    pub std: f64,
+    pub p5: f64,
+    pub p50: f64,
+    pub p95: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SimReport {
+    /// The `Ladder::progress` after each game, over the trials.
+    pub bands: Vec<Band>,
+    /// Fraction of the trials that reach the target progress within the
+    /// games, if there is a target.
+    pub reached: Option<f64>,
+    /// Number of games to reach the target, over the trials that reach it.
+    pub games_to_target: Option<Band>,
+}
+
+impl TenhouRoom {
+    /// The points of 1st and 2nd in tonpuu.
+    const fn tonpuu_gains(self) -> [i32; 2] {
+        match self {
+            Self::Ippan => [20, 10],
+            Self::Joukyuu => [40, 10],
+            Self::Tokujou => [50, 20],
+            Self::Houou => [60, 30],
+        }
+    }
+}
+
+impl TenhouDan {
+    pub fn new(room: TenhouRoom, hanchan: bool, level: u8, pt: Option<i32>) -> Result<Self> {
+        ensure!(
+            (level as usize) < TENHOU_LEVELS.len(),
+            "level {level} is out of range",
+        );
+        Ok(Self {
+            room,
+            hanchan,
+            level,
+            pt: pt.unwrap_or_else(|| Self::initial_pt(level)),
+        })
+    }
+
+    /// Dans start with half of their thresholds, kyus from zero.
+    const fn initial_pt(level: u8) -> i32 {
+        if level >= TENHOU_SHODAN {
+            TENHOU_THRESHOLDS[level as usize] / 2
+        } else {
+            0
+        }
+    }
+}
+
+impl Ladder for TenhouDan {
+    fn update(&mut self, rank: u8, _score: i32) {
+        if self.level == TENHOUI {
+            return;
+        }
+        let delta = match rank {
+            0 | 1 => self.room.tonpuu_gains()[rank as usize],
+            2 => 0,
+            _ if self.level >= TENHOU_SHODAN => -20 * (self.level - TENHOU_SHODAN + 1) as i32,
+            _ => 0,
+        };
+        // Hanchan is worth 1.5 times as much.
+        self.pt += if self.hanchan { delta * 3 / 2 } else { delta };
+
+        if self.pt >= TENHOU_THRESHOLDS[self.level as usize] {
+            self.level += 1;
+            self.pt = Self::initial_pt(self.level);
+        } else if self.pt < 0 {
+            // Shodan and the kyus are never demoted.
+            if self.level > TENHOU_SHODAN {
+                self.level -= 1;
+                self.pt = Self::initial_pt(self.level);
+            } else {
+                self.pt = 0;
+            }
+        }
+    }
+
+    fn progress(&self) -> f64 {
+        let threshold = TENHOU_THRESHOLDS[self.level as usize];
+        if threshold == 0 {
+            return self.level as f64;
+        }
+        self.level as f64 + self.pt as f64 / threshold as f64
+    }
+}
+
+impl Ladder for TenhouRate {
+    fn update(&mut self, rank: u8, _score: i32) {
+        const BASE: [f64; 4] = [30., 10., -10., -30.];
+        let correction = 0.002_f64.mul_add(-(self.games as f64), 1.).max(0.2);
+        self.rate += ((self.table_rate - self.rate) / 40. + BASE[rank as usize]) * correction;
+        self.games += 1;
+    }
+
+    fn progress(&self) -> f64 {
+        self.rate
+    }
+}
+
+impl MajsoulRoom {
+    /// The uma of 1st and 2nd.
+    const fn gains(self) -> [i32; 2] {
+        match self {
+            Self::Bronze => [20, 10],
+            Self::Silver => [40, 20],
+            Self::Gold => [80, 40],
+            Self::Jade => [110, 55],
+            Self::Throne => [120, 60],
+        }
+    }
+}
+
+impl MajsoulRank {
+    pub fn new(room: MajsoulRoom, level: u8, pt: Option<i32>) -> Result<Self> {
+        ensure!(
+            (level as usize) < MAJSOUL_LEVELS.len(),
+            "level {level} is out of range",
+        );
+        Ok(Self {
+            room,
+            level,
+            pt: pt.unwrap_or_else(|| Self::initial_pt(level)),
+        })
+    }
+
+    /// Every level starts with half of its threshold, except for the very
+    /// first one.
+    const fn initial_pt(level: u8) -> i32 {
+        if level == 0 {
+            0
+        } else {
+            MAJSOUL_THRESHOLDS[level as usize] / 2
+        }
+    }
+}
+
+impl Ladder for MajsoulRank {
+    fn update(&mut self, rank: u8, score: i32) {
+        if self.level == MAJSOUL_CELESTIAL {
+            return;
+        }
+        let uma = match rank {
+            0 | 1 => self.room.gains()[rank as usize],
+            2 => 0,
+            _ if self.level >= MAJSOUL_JANSHI => -20 * (self.level - MAJSOUL_JANSHI + 1) as i32,
+            _ => 0,
+        };
+        self.pt += (score - 25000) / 1000 + uma;
+
+        if self.pt >= MAJSOUL_THRESHOLDS[self.level as usize] {
+            self.level += 1;
+            self.pt = Self::initial_pt(self.level);
+        } else if self.pt < 0 {
+            // Only Expert and above are demoted.
+            if self.level >= MAJSOUL_JANKETSU {
+                self.level -= 1;
+                self.pt = Self::initial_pt(self.level);
+            } else {
+                self.pt = 0;
+            }
+        }
+    }
+
+    fn progress(&self) -> f64 {
+        let threshold = MAJSOUL_THRESHOLDS[self.level as usize];
+        if threshold == 0 {
+            return self.level as f64;
+        }
+        self.level as f64 + self.pt as f64 / threshold as f64
+    }
+}
+
+/// Returns the ladder after each of `ranks`, where `scores` is either empty
+/// or the final score of each game.
+pub fn trajectory<L: Ladder>(start: &L, ranks: &[u8], scores: &[i32]) -> Result<Vec<L>> {
+    ensure!(
+        scores.is_empty() || scores.len() == ranks.len(),
+        "scores must be empty or as long as ranks",
+    );
+    let mut ladder = start.clone();
+    ranks
+        .iter()
+        .enumerate()
+        .map(|(i, &rank)| {
+            ensure!(rank < 4, "rank {rank} is out of range");
+            ladder.update(rank, scores.get(i).copied().unwrap_or(25000));
+            Ok(ladder.clone())
+        })
+        .collect()
+}
+
+/// Plays `games` games from `start` for each of `trials` trials, and
+/// summarizes the progress after each game. `target` is a progress to reach,
+/// such as the `Ladder::progress` of a level.
+pub fn simulate<L: Ladder>(
+    start: &L,
+    placements: &Placements,
+    games: usize,
+    trials: usize,
+    target: Option<f64>,
+    seed: u64,
+) -> Result<SimReport> {
+    ensure!(trials > 0, "trials must be positive");
+    let dist = WeightedIndex::new(placements.rank_probs)?;
+    let mut rng = ChaCha12Rng::seed_from_u64(seed);
+
+    let mut progresses = vec![Vec::with_capacity(trials); games];
+    let mut games_to_target = vec![];
+    for _ in 0..trials {
+        let mut ladder = start.clone();
+        let mut reached = false;
+        for (game, progress) in progresses.iter_mut().enumerate() {
+            let rank = dist.sample(&mut rng);
+            ladder.update(rank as u8, placements.scores[rank]);
+            let p = ladder.progress();
+            progress.push(p);
+            if !reached && target.is_some_and(|t| p >= t) {
+                reached = true;
+                games_to_target.push((game + 1) as f64);
+            }
+        }
+    }
+
+    Ok(SimReport {
+        bands: progresses.iter_mut().map(|p| band(p)).collect(),
+        reached: target.map(|_| games_to_target.len() as f64 / trials as f64),
+        games_to_target: (!games_to_target.is_empty()).then(|| band(&mut games_to_target)),
+    })
+}
+
+fn band(values: &mut [f64]) -> Band {
+    values.sort_unstable_by(f64::total_cmp);
+    let n = values.len() as f64;
+    let mean = values.iter().sum::<f64>() / n;
+    let var = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / n;
+    let percentile = |p: f64| values[((n - 1.) * p).round() as usize];
+    Band {
+        mean,
+        std: var.sqrt(),
+        p5: percentile(0.05),
+        p50: percentile(0.5),
+        p95: percentile(0.95),
+    }
+}
+
+fn parse_level(levels: &[&str], level: &str) -> Result<u8> {
+    match levels.iter().position(|&l| l == level) {
+        Some(idx) => Ok(idx as u8),
+        None => bail!("unknown level {level}, expected one of {levels:?}"),
+    }
+}
+
+#[pymethods]
+impl TenhouDan {
+    /// `room` is one of `"ippan"`, `"joukyuu"`, `"tokujou"` and `"houou"`.
+    /// `pt` defaults to the initial points of `level`.
+    #[new]
+    #[args(
+        "*",
+        room = "\"houou\"",
+        hanchan = "true",
+        level = "\"新人\"",
+        pt = "None"
+    )]
+    fn py_new(room: &str, hanchan: bool, level: &str, pt: Option<i32>) -> Result<Self> {
+        let room = match room {
+            "ippan" => TenhouRoom::Ippan,
+            "joukyuu" => TenhouRoom::Joukyuu,
+            "tokujou" => TenhouRoom::Tokujou,
+            "houou" => TenhouRoom::Houou,
+            _ => bail!("unknown room {room}"),
+        };
+        Self::new(room, hanchan, parse_level(&TENHOU_LEVELS, level)?, pt)
+    }
+
+    #[staticmethod]
+    #[pyo3(text_signature = "()")]
+    fn levels() -> Vec<&'static str> {
+        TENHOU_LEVELS.to_vec()
+    }
+
+    #[getter]
+    const fn name(&self) -> &'static str {
+        TENHOU_LEVELS[self.level as usize]
+    }
+    #[getter(pt)]
+    const fn pt_py(&self) -> i32 {
+        self.pt
+    }
+
+    /// Returns the trajectory over `ranks`, which count from 0, as a list of
+    /// `TenhouDan`.
+    #[pyo3(name = "trajectory")]
+    #[pyo3(text_signature = "($self, ranks, /)")]
+    fn trajectory_py(&self, ranks: Vec<u8>) -> Result<Vec<Self>> {
+        trajectory(self, &ranks, &[])
+    }
+
+    #[pyo3(name = "progress")]
+    #[pyo3(text_signature = "($self, /)")]
+    fn progress_py(&self) -> f64 {
+        self.progress()
+    }
+
+    fn __repr__(&self) -> String {
+        self.to_string()
+    }
+}
+
+#[pymethods]
+impl TenhouRate {
+    #[new]
+    #[args("*", table_rate = "2000.", rate = "1500.", games = "0")]
+    const fn py_new(table_rate: f64, rate: f64, games: u32) -> Self {
+        Self {
+            table_rate,
+            rate,
+            games,
+        }
+    }
+
+    #[getter(rate)]
+    const fn rate_py(&self) -> f64 {
+        self.rate
+    }
+    #[getter(games)]
+    const fn games_py(&self) -> u32 {
+        self.games
+    }
+
+    /// Returns the trajectory over `ranks`, which count from 0, as a list of
+    /// `TenhouRate`.
+    #[pyo3(name = "trajectory")]
+    #[pyo3(text_signature = "($self, ranks, /)")]
+    fn trajectory_py(&self, ranks: Vec<u8>) -> Result<Vec<Self>> {
+        trajectory(self, &ranks, &[])
+    }
+
+    #[pyo3(name = "progress")]
+    #[pyo3(text_signature = "($self, /)")]
+    fn progress_py(&self) -> f64 {
+        self.progress()
+    }
+
+    fn __repr__(&self) -> String {
+        self.to_string()
+    }
+}
+
+#[pymethods]
+impl MajsoulRank {
+    /// `room` is one of `"bronze"`, `"silver"`, `"gold"`, `"jade"` and
+    /// `"throne"`. `pt` defaults to the initial points of `level`.
+    #[new]
+    #[args("*", room = "\"jade\"", level = "\"初心1\"", pt = "None")]
+    fn py_new(room: &str, level: &str, pt: Option<i32>) -> Result<Self> {
+        let room = match room {
+            "bronze" => MajsoulRoom::Bronze,
+            "silver" => MajsoulRoom::Silver,
+            "gold" => MajsoulRoom::Gold,
+            "jade" => MajsoulRoom::Jade,
+            "throne" => MajsoulRoom::Throne,
+            _ => bail!("unknown room {room}"),
+        };
+        Self::new(room, parse_level(&MAJSOUL_LEVELS, level)?, pt)
+    }
+
+    #[staticmethod]
+    #[pyo3(text_signature = "()")]
+    fn levels() -> Vec<&'static str> {
+        MAJSOUL_LEVELS.to_vec()
+    }
+
+    #[getter]
+    const fn name(&self) -> &'static str {
+        MAJSOUL_LEVELS[self.level as usize]
+    }
+    #[getter(pt)]
+    const fn pt_py(&self) -> i32 {
+        self.pt
+    }
+
+    /// Returns the trajectory over `ranks`, which count from 0, and the final
+    /// `scores` of the games, as a list of `MajsoulRank`.
+    #[pyo3(name = "trajectory")]
+    #[pyo3(text_signature = "($self, ranks, scores, /)")]
+    fn trajectory_py(&self, ranks: Vec<u8>, scores: Vec<i32>) -> Result<Vec<Self>> {
+        trajectory(self, &ranks, &scores)
+    }
+
+    #[pyo3(name = "progress")]
+    #[pyo3(text_signature = "($self, /)")]
+    fn progress_py(&self) -> f64 {
+        self.progress()
+    }
+
+    fn __repr__(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl fmt::Display for TenhouDan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {}/{}",
+            TENHOU_LEVELS[self.level as usize], self.pt, TENHOU_THRESHOLDS[self.level as usize],
+        )
+    }
+}
+
+impl fmt::Display for TenhouRate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "R{:.0} ({} games)", self.rate, self.games)
+    }
+}
+
+impl fmt::Display for MajsoulRank {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {}/{}",
+            MAJSOUL_LEVELS[self.level as usize], self.pt, MAJSOUL_THRESHOLDS[self.level as usize],
+        )
+    }
+}
+
+/// Simulates `ladder`, which is a `TenhouDan`, `TenhouRate` or
+/// `MajsoulRank`, with the placements drawn from `rank_probs`. `scores` is
+/// the average final score of each rank, which is 25000 for all by default.
+/// Returns the report as a JSON string.
+#[cfg(feature = "python")]
+#[pyfunction(trials = "1000", "*", scores = "None", target = "None", seed = "0")]
+#[pyo3(name = "simulate")]
+#[pyo3(
+    text_signature = "(ladder, rank_probs, games, trials=1000, *, scores=None, target=None, seed=0)"
+)]
+fn simulate_py(
+    ladder: &PyAny,
+    rank_probs: [f64; 4],
+    games: usize,
+    trials: usize,
+    scores: Option<[i32; 4]>,
+    target: Option<f64>,
+    seed: u64,
+) -> Result<String> {
+    let placements = Placements {
+        rank_probs,
+        scores: scores.unwrap_or([25000; 4]),
+    };
+    let report = if let Ok(l) = ladder.extract::<TenhouDan>() {
+        simulate(&l, &placements, games, trials, target, seed)?
+    } else if let Ok(l) = ladder.extract::<TenhouRate>() {
+        simulate(&l, &placements, games, trials, target, seed)?
+    } else if let Ok(l) = ladder.extract::<MajsoulRank>() {
+        simulate(&l, &placements, games, trials, target, seed)?
+    } else {
+        bail!("unsupported ladder {ladder}");
+    };
+    Ok(json::to_string(&report)?)
+}
+
+#[cfg(feature = "python")]
+pub(crate) fn register_module(py: Python<'_>, prefix: &str, super_mod: &PyModule) -> PyResult<()> {
+    let m = PyModule::new(py, "rating")?;
+    m.add_class::<TenhouDan>()?;
+    m.add_class::<TenhouRate>()?;
+    m.add_class::<MajsoulRank>()?;
+    m.add_function(wrap_pyfunction!(simulate_py, m)?)?;
+    add_submodule(py, prefix, super_mod, m)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn tenhou_dan() {
+        // Houou hanchan: +90, +45, 0, and -210 for a 4th at 7-dan.
+        let start = TenhouDan::new(TenhouRoom::Houou, true, 16, Some(100)).unwrap();
+        let traj = trajectory(&start, &[0, 1, 2, 3], &[]).unwrap();
+        let pts: Vec<_> = traj.iter().map(|d| d.pt).collect();
+        assert_eq!(pts, [190, 235, 235, 25]);
+
+        // Demoted to the initial points of 6-dan.
+        let mut dan = traj[3].clone();
+        dan.update(3, 0);
+        assert_eq!((dan.level, dan.pt), (15, 1200));
+
+        // Promoted to shodan with 200 points, and never demoted from it.
+        let mut dan = TenhouDan::new(TenhouRoom::Ippan, false, 9, Some(90)).unwrap();
+        dan.update(0, 0);
+        assert_eq!((dan.level, dan.pt), (TENHOU_SHODAN, 200));
+        for _ in 0..20 {
+            dan.update(3, 0);
+        }
+        assert_eq!((dan.level, dan.pt), (TENHOU_SHODAN, 0));
+
+        let mut dan = TenhouDan::new(TenhouRoom::Houou, true, TENHOUI, None).unwrap();
+        dan.update(3, 0);
+        assert!((dan.progress() - TENHOUI as f64).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn tenhou_rate() {
+        let mut rate = TenhouRate::py_new(1500., 1500., 0);
+        rate.update(0, 0);
+        assert!((rate.rate - 1530.).abs() < 1e-9);
+        // (1500 - 1530) / 40 - 30, corrected by 1 - 0.002
+        rate.update(3, 0);
+        assert!((rate.rate - 1499.3115).abs() < 1e-9);
+        assert_eq!(rate.games, 2);
+    }
+
+    #[test]
+    fn majsoul_rank() {
+        // Jade: +110 and the score, and -120 for a 4th at Master 1.
+        let start = MajsoulRank::new(MajsoulRoom::Jade, 9, None).unwrap();
+        assert_eq!(start.pt, 1400);
+        let traj = trajectory(&start, &[0, 3], &[45000, 5000]).unwrap();
+        assert_eq!(traj[0].pt, 1400 + 20 + 110);
+        assert_eq!(traj[1].pt, 1400 + 20 + 110 - 20 - 140);
+
+        // Adepts are never demoted.
+        let mut rank = MajsoulRank::new(MajsoulRoom::Gold, MAJSOUL_JANSHI, Some(10)).unwrap();
+        rank.update(3, 0);
+        assert_eq!((rank.level, rank.pt), (MAJSOUL_JANSHI, 0));
+
+        assert!(trajectory(&start, &[0, 3], &[45000]).is_err());
+    }
+
+    #[test]
+    fn simulate() {
+        let start = TenhouDan::new(TenhouRoom::Houou, true, 16, None).unwrap();
+        let placements = Placements {
+            rank_probs: [0.3, 0.27, 0.24, 0.19],
+            scores: [25000; 4],
+        };
+        let report = super::simulate(&start, &placements, 200, 100, Some(17.), 0).unwrap();
+        assert_eq!(report.bands.len(), 200);
+        let last = report.bands.last().unwrap();
+        assert!(last.p5 <= last.p50 && last.p50 <= last.p95);
+        // +10.8 points on average per game from 1400 of 2800.
+        assert!(last.mean > start.progress());
+        let reached = report.reached.unwrap();
+        assert!(reached > 0. && reached <= 1.);
+        let games = report.games_to_target.unwrap();
+        assert!(games.p5 >= 1. && games.p95 <= 200.);
+
+        // Same seed, same report.
+        let again = super::simulate(&start, &placements, 200, 100, Some(17.), 0).unwrap();
+        assert!((again.bands[199].mean - last.mean).abs() < f64::EPSILON);
+    }
+}
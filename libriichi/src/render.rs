@@ -0,0 +1,434 @@
+//! Rendering of a board into an SVG or a self-contained HTML diagram, from a
+//! `PlayerState` or a position within an mjai log.
+//!
+//! The board is drawn from the perspective of one seat, which sits at the
+//! bottom, with shimocha on the right, toimen on the top and kamicha on the
+//! left. Each seat has its hand along the edge with the melds to the right,
+//! and its kawa in discard order in front of the center, where the riichi
+//! tile lies sideways and tsumogiri tiles are shaded.
+
+use crate::mjai::Event;
+#[cfg(feature = "python")]
+use crate::py_helper::add_submodule;
+use crate::state::PlayerState;
+use crate::tile::Tile;
+use crate::{must_tile, tu8};
+use std::fmt::Write;
+
+use anyhow::{ensure, Context, Result};
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+use serde_json as json;
+
+const SIZE: i32 = 800;
+const CENTER: i32 = SIZE / 2;
+const TILE_W: i32 = 26;
+const TILE_H: i32 = 36;
+/// Half of the side of the info box in the center.
+const INFO_HALF: i32 = 84;
+const HAND_LEFT: i32 = 120;
+const MELD_RIGHT: i32 = SIZE - 60;
+
+const SUIT_COLORS: [&str; 3] = ["#b3261e", "#1a3f8f", "#1e7a3c"];
+const HONOR_LABELS: [&str; 7] = ["東", "南", "西", "北", "白", "發", "中"];
+const HONOR_COLORS: [&str; 7] = [
+    "#222", "#222", "#222", "#222", "#1a3f8f", "#1e7a3c", "#b3261e",
+];
+
+/// The concealed tiles of a hand, where `tsumo` is drawn apart from the rest.
+struct Hand {
+    tiles: Vec<Tile>,
+    tsumo: Option<Tile>,
+}
+
+impl Hand {
+    /// The hand of the owner of `state`.
+    fn of(state: &PlayerState) -> Self {
+        let mut tehai = state.tehai();
+        let mut akas = state.akas_in_hand();
+        let tsumo = state
+            .last_self_tsumo()
+            .filter(|_| tehai.iter().sum::<u8>() % 3 == 2);
+        if let Some(tsumo) = tsumo {
+            tehai[tsumo.deaka().as_usize()] -= 1;
+            if tsumo.is_aka() {
+                akas[tsumo.as_usize() - tu8!(5mr) as usize] -= 1;
+            }
+        }
+
+        let mut tiles = vec![];
+        for (tid, &count) in tehai.iter().enumerate() {
+            let mut count = count as usize;
+            let aka_idx = match tid {
+                4 => Some(0),
+                13 => Some(1),
+                22 => Some(2),
+                _ => None,
+            };
+            if let Some(i) = aka_idx {
+                let n = (akas[i] as usize).min(count);
+                tiles.extend((0..n).map(|_| must_tile!(tu8!(5mr) as usize + i)));
+                count -= n;
+            }
+            tiles.extend((0..count).map(|_| must_tile!(tid)));
+        }
+        Self { tiles, tsumo }
+    }
+}
+
+/// Renders the board as seen by the owner of `state` into an SVG.
+#[must_use]
+pub fn state_to_svg(state: &PlayerState) -> String {
+    let mut hands: [Option<Hand>; 4] = Default::default();
+    hands[0] = Some(Hand::of(state));
+    board_svg(state, &hands)
+}
+
+/// Renders the board after the first `index` events of `events` as seen by
+/// `seat` into an SVG. If `reveal` is true, the hands of all seats are shown.
+pub fn log_to_svg(events: &[Event], index: usize, seat: u8, reveal: bool) -> Result<String> {
+    ensure!(seat < 4, "{seat} is not a valid seat");
+    ensure!(
+        index <= events.len(),
+        "index {index} is out of the log of {} events",
+        events.len(),
+    );
+
+    let mut states = [0, 1, 2, 3].map(PlayerState::new);
+    let mut started = false;
+    for (i, ev) in events[..index].iter().enumerate() {
+        started |= matches!(ev, Event::StartKyoku { .. });
+        for (s, state) in states.iter_mut().enumerate() {
+            if reveal || s == seat as usize {
+                state
+                    .update(ev)
+                    .with_context(|| format!("failed to replay event {i}: {ev:?}"))?;
+            }
+        }
+    }
+    ensure!(started, "no kyoku has started by index {index}");
+
+    let hands = [0, 1, 2, 3].map(|rel| {
+        let abs = (seat as usize + rel) % 4;
+        (reveal || rel == 0).then(|| Hand::of(&states[abs]))
+    });
+    Ok(board_svg(&states[seat as usize], &hands))
+}
+
+/// Wraps `svg` into a self-contained HTML document.
+#[must_use]
+pub fn to_html(svg: &str, title: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n</head>\n\
+         <body style=\"margin:0;background:#111;display:flex;justify-content:center\">\n{svg}\n</body>\n</html>\n",
+        escape(title),
+    )
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn board_svg(state: &PlayerState, hands: &[Option<Hand>; 4]) -> String {
+    let mut svg = String::new();
+    writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{SIZE}" height="{SIZE}" viewBox="0 0 {SIZE} {SIZE}" font-family="sans-serif">"#,
+    )
+    .unwrap();
+    writeln!(
+        svg,
+        r##"<rect width="{SIZE}" height="{SIZE}" fill="#1b5e3b"/>"##
+    )
+    .unwrap();
+
+    info_box(&mut svg, state);
+    for rel in 0..4 {
+        // Seats are drawn as if at the bottom and then rotated counter-clockwise
+        // into place.
+        writeln!(
+            svg,
+            r#"<g transform="rotate({} {CENTER} {CENTER})">"#,
+            -90 * rel as i32,
+        )
+        .unwrap();
+        seat_area(&mut svg, state, rel, hands[rel as usize].as_ref());
+        svg.push_str("</g>\n");
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+fn info_box(svg: &mut String, state: &PlayerState) {
+    let left = CENTER - INFO_HALF;
+    writeln!(
+        svg,
+        r##"<rect x="{left}" y="{left}" width="{}" height="{}" rx="6" fill="#0f3d26"/>"##,
+        INFO_HALF * 2,
+        INFO_HALF * 2,
+    )
+    .unwrap();
+    writeln!(
+        svg,
+        r##"<text x="{CENTER}" y="{}" fill="#fff" font-size="20" text-anchor="middle">{}{}-{}</text>"##,
+        CENTER - 30,
+        state.bakaze(),
+        state.kyoku(),
+        state.honba(),
+    )
+    .unwrap();
+    writeln!(
+        svg,
+        r##"<text x="{CENTER}" y="{}" fill="#cde" font-size="12" text-anchor="middle">kyotaku {}  left {}</text>"##,
+        CENTER - 10,
+        state.kyotaku(),
+        state.tiles_left(),
+    )
+    .unwrap();
+
+    let doras = state.dora_indicators();
+    let x0 = CENTER - TILE_W * 5 / 2;
+    for i in 0..5 {
+        let tile = doras.get(i).copied().unwrap_or(must_tile!(tu8!(?)));
+        draw_tile(svg, x0 + TILE_W * i as i32, CENTER, tile, false, false);
+    }
+}
+
+fn seat_area(svg: &mut String, state: &PlayerState, rel: u8, hand: Option<&Hand>) {
+    let wind = must_tile!(tu8!(E) + (rel + 4 - state.oya()) % 4);
+    let riichi = state.riichi_declared()[rel as usize];
+    writeln!(
+        svg,
+        r##"<text x="{CENTER}" y="{}" fill="{}" font-size="14" text-anchor="middle">{} {}</text>"##,
+        CENTER + INFO_HALF - 8,
+        if rel == state.oya() {
+            "#ffd54f"
+        } else {
+            "#fff"
+        },
+        HONOR_LABELS[wind.as_usize() - tu8!(E) as usize],
+        state.scores()[rel as usize],
+    )
+    .unwrap();
+    if riichi {
+        writeln!(
+            svg,
+            r##"<rect x="{}" y="{}" width="60" height="6" rx="3" fill="#fff"/><circle cx="{CENTER}" cy="{}" r="2" fill="#b3261e"/>"##,
+            CENTER - 30,
+            CENTER + INFO_HALF - 36,
+            CENTER + INFO_HALF - 33,
+        )
+        .unwrap();
+    }
+
+    // Kawa, 6 tiles a row, where the third row goes on without wrapping.
+    let x0 = CENTER - TILE_W * 3;
+    let y0 = CENTER + INFO_HALF + 6;
+    let (mut row, mut x) = (0, x0);
+    for (i, sutehai) in state.sutehais(rel).enumerate() {
+        if i > 0 && i % 6 == 0 && row < 2 {
+            row += 1;
+            x = x0;
+        }
+        let y = y0 + TILE_H * row;
+        draw_tile(
+            svg,
+            x,
+            y,
+            sutehai.tile,
+            sutehai.is_riichi,
+            !sutehai.is_tedashi,
+        );
+        x += if sutehai.is_riichi { TILE_H } else { TILE_W };
+    }
+
+    // Melds, from the right edge inwards.
+    let y = SIZE - TILE_H - 14;
+    let mut x = MELD_RIGHT;
+    for fuuro in &state.fuuro_overview()[rel as usize] {
+        for &tile in fuuro.iter().rev() {
+            x -= TILE_W;
+            draw_tile(svg, x, y, tile, false, false);
+        }
+        x -= 6;
+    }
+    let mut meld_count = state.fuuro_overview()[rel as usize].len();
+    for &tile in &state.ankan_overview()[rel as usize] {
+        for i in (0..4).rev() {
+            x -= TILE_W;
+            let shown = if i == 0 || i == 3 {
+                must_tile!(tu8!(?))
+            } else {
+                tile
+            };
+            draw_tile(svg, x, y, shown, false, false);
+        }
+        x -= 6;
+        meld_count += 1;
+    }
+
+    // Hand, from the left.
+    let mut x = HAND_LEFT;
+    match hand {
+        Some(hand) => {
+            for &tile in &hand.tiles {
+                draw_tile(svg, x, y, tile, false, false);
+                x += TILE_W;
+            }
+            if let Some(tsumo) = hand.tsumo {
+                draw_tile(svg, x + 10, y, tsumo, false, false);
+            }
+        }
+        None => {
+            for _ in 0..13_usize.saturating_sub(meld_count * 3) {
+                draw_tile(svg, x, y, must_tile!(tu8!(?)), false, false);
+                x += TILE_W;
+            }
+        }
+    }
+}
+
+/// Draws a tile with its top left corner at `(x, y)`. A sideways tile takes
+/// `TILE_H` in width and sits at the same baseline as upright ones. Unknown
+/// tiles are drawn face down.
+fn draw_tile(svg: &mut String, x: i32, y: i32, tile: Tile, sideways: bool, shaded: bool) {
+    if sideways {
+        let (cx, cy) = (x + TILE_H / 2, y + TILE_H - TILE_W / 2);
+        write!(svg, r#"<g transform="rotate(90 {cx} {cy})">"#).unwrap();
+        draw_tile(svg, cx - TILE_W / 2, cy - TILE_H / 2, tile, false, shaded);
+        svg.push_str("</g>\n");
+        return;
+    }
+
+    let (w, h) = (TILE_W - 2, TILE_H - 2);
+    let (x, y) = (x + 1, y + 1);
+    if tile.as_u8() == tu8!(?) {
+        writeln!(
+            svg,
+            r##"<rect x="{x}" y="{y}" width="{w}" height="{h}" rx="3" fill="#d98c2b" stroke="#333"/>"##,
+        )
+        .unwrap();
+        return;
+    }
+
+    let fill = if tile.is_aka() {
+        "#ffe9e6"
+    } else if shaded {
+        "#cfcfcf"
+    } else {
+        "#fafafa"
+    };
+    writeln!(
+        svg,
+        r##"<rect x="{x}" y="{y}" width="{w}" height="{h}" rx="3" fill="{fill}" stroke="#333"/>"##,
+    )
+    .unwrap();
+
+    let cx = x + w / 2;
+    let tid = tile.deaka().as_usize();
+    if tile.is_jihai() {
+        let i = tid - tu8!(E) as usize;
+        writeln!(
+            svg,
+            r#"<text x="{cx}" y="{}" fill="{}" font-size="16" text-anchor="middle">{}</text>"#,
+            y + h / 2 + 6,
+            HONOR_COLORS[i],
+            HONOR_LABELS[i],
+        )
+        .unwrap();
+    } else {
+        let color = if tile.is_aka() {
+            "#e00000"
+        } else {
+            SUIT_COLORS[tid / 9]
+        };
+        writeln!(
+            svg,
+            r#"<text x="{cx}" y="{}" fill="{color}" font-size="16" font-weight="bold" text-anchor="middle">{}</text><text x="{cx}" y="{}" fill="{color}" font-size="10" text-anchor="middle">{}</text>"#,
+            y + h / 2 + 1,
+            tid % 9 + 1,
+            y + h - 4,
+            ["m", "p", "s"][tid / 9],
+        )
+        .unwrap();
+    }
+}
+
+fn parse_log(log: &str) -> Result<Vec<Event>> {
+    log.lines()
+        .filter(|l| !l.trim().is_empty())
+        .map(json::from_str)
+        .collect::<Result<_, _>>()
+        .context("failed to parse log")
+}
+
+/// Renders the board as seen by the owner of `state` into an SVG, or a
+/// self-contained HTML document if `html` is true.
+#[cfg(feature = "python")]
+#[pyfunction(state, "*", html = "false")]
+#[pyo3(text_signature = "(state, *, html = False)")]
+fn render_state(state: &PlayerState, html: bool) -> String {
+    let svg = state_to_svg(state);
+    if html {
+        to_html(&svg, "board")
+    } else {
+        svg
+    }
+}
+
+/// Renders the board after the first `index` events of `log`, an mjai log in
+/// JSON lines, as seen by `seat` into an SVG, or a self-contained HTML
+/// document if `html` is true. If `reveal` is true, the hands of all seats
+/// are shown.
+#[cfg(feature = "python")]
+#[pyfunction(log, index, seat, "*", reveal = "false", html = "false")]
+#[pyo3(text_signature = "(log, index, seat, *, reveal = False, html = False)")]
+fn render_log(log: &str, index: usize, seat: u8, reveal: bool, html: bool) -> Result<String> {
+    let svg = log_to_svg(&parse_log(log)?, index, seat, reveal)?;
+    if html {
+        Ok(to_html(&svg, &format!("board at event {index}")))
+    } else {
+        Ok(svg)
+    }
+}
+
+#[cfg(feature = "python")]
+pub(crate) fn register_module(py: Python<'_>, prefix: &str, super_mod: &PyModule) -> PyResult<()> {
+    let m = PyModule::new(py, "render")?;
+    m.add_function(wrap_pyfunction!(render_state, m)?)?;
+    m.add_function(wrap_pyfunction!(render_log, m)?)?;
+    add_submodule(py, prefix, super_mod, m)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::log_io;
+
+    #[test]
+    fn render() {
+        let events = log_io::read_events("tests/data/pack_test.json").unwrap();
+        let index = events
+            .iter()
+            .position(|ev| matches!(ev, Event::Hora { .. } | Event::Ryukyoku { .. }))
+            .unwrap();
+
+        let svg = log_to_svg(&events, index, 0, false).unwrap();
+        assert!(svg.starts_with("<svg") && svg.ends_with("</svg>\n"));
+        let backs = svg.matches("#d98c2b").count();
+
+        // Revealing the others' hands turns their backs into faces.
+        let revealed = log_to_svg(&events, index, 0, true).unwrap();
+        assert!(revealed.matches("#d98c2b").count() < backs);
+
+        let html = to_html(&svg, "<test>");
+        assert!(html.contains("<title>&lt;test&gt;</title>"));
+
+        assert!(log_to_svg(&events, 1, 0, false).is_err());
+        assert!(log_to_svg(&events, events.len() + 1, 0, false).is_err());
+        assert!(log_to_svg(&events, index, 4, false).is_err());
+    }
+}
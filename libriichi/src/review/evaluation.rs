@@ -0,0 +1,266 @@
+#[cfg(feature = "python")]
+use super::Reviewer;
+use super::{Entry, Review, Situation};
+#[cfg(feature = "python")]
+use crate::agent::MortalBatchAgent;
+#[cfg(feature = "python")]
+use crate::log_io;
+use crate::mjai::Event;
+use std::collections::BTreeMap;
+
+#[cfg(feature = "python")]
+use anyhow::Context;
+use anyhow::{bail, Result};
+#[cfg(feature = "python")]
+use indicatif::{ProgressBar, ProgressStyle};
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+use serde::Serialize;
+#[cfg(feature = "python")]
+use serde_json as json;
+
+/// `Evaluator` reviews a corpus of mjai logs, typically of strong human
+/// players, and measures how much the engine agrees with the players, overall
+/// and broken down by `Situation`.
+#[cfg(feature = "python")]
+#[pyclass]
+#[pyo3(text_signature = "(
+    engine,
+    *,
+    player_name = None,
+    temperature = 1.0,
+    disable_progress_bar = False,
+)")]
+pub struct Evaluator {
+    engine: PyObject,
+    player_name: Option<String>,
+    temperature: f32,
+    disable_progress_bar: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Evaluation {
+    /// Number of logs containing any reviewed seat.
+    pub games: usize,
+    /// Number of seats reviewed, which can be more than `games`.
+    pub seats: usize,
+    pub overall: Agreement,
+    pub situations: BTreeMap<Situation, Agreement>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Agreement {
+    pub reviewed: usize,
+    pub matches: usize,
+    /// `matches / reviewed`, 0 if nothing is reviewed.
+    pub rate: f64,
+    /// Number of disagreements where the engine reports q values.
+    pub rated_disagreements: usize,
+    /// Average of the q value of the best action minus the q value of the
+    /// actual action, over `rated_disagreements`.
+    pub avg_q_gap: f64,
+    #[serde(skip)]
+    q_gap_sum: f64,
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl Evaluator {
+    #[new]
+    #[args(
+        "*",
+        player_name = "None",
+        temperature = "1.",
+        disable_progress_bar = "false"
+    )]
+    const fn new(
+        engine: PyObject,
+        player_name: Option<String>,
+        temperature: f32,
+        disable_progress_bar: bool,
+    ) -> Self {
+        Self {
+            engine,
+            player_name,
+            temperature,
+            disable_progress_bar,
+        }
+    }
+
+    /// Reviews every log in `dir` from the view of the seats named
+    /// `player_name`, or all the four seats if `player_name` is `None`.
+    /// Returns the evaluation as a JSON string.
+    #[pyo3(name = "evaluate")]
+    #[pyo3(text_signature = "($self, dir, /)")]
+    fn evaluate_py(&self, dir: &str, py: Python<'_>) -> Result<String> {
+        py.allow_threads(move || {
+            let evaluation = self.evaluate(dir)?;
+            Ok(json::to_string(&evaluation)?)
+        })
+    }
+}
+
+#[cfg(feature = "python")]
+impl Evaluator {
+    pub fn evaluate(&self, dir: &str) -> Result<Evaluation> {
+        let bar = if self.disable_progress_bar {
+            ProgressBar::hidden()
+        } else {
+            ProgressBar::new_spinner().with_style(
+                ProgressStyle::default_spinner()
+                    .template("{spinner:.cyan} [{elapsed_precise}] {pos} ({per_sec})")
+                    .tick_chars(".oOo"),
+            )
+        };
+        bar.enable_steady_tick(150);
+
+        let mut evaluation = Evaluation::default();
+        for path in log_io::glob_logs(dir)? {
+            let path = path?;
+            let events = log_io::read_events(&path)?;
+            let player_ids = seats(&events, self.player_name.as_deref())
+                .with_context(|| format!("invalid log {}", path.display()))?;
+            for &player_id in &player_ids {
+                let engine = Python::with_gil(|py| self.engine.clone_ref(py));
+                let agent = MortalBatchAgent::new(engine, &[player_id])?;
+                let mut reviewer = Reviewer::new(Box::new(agent), player_id, self.temperature)?;
+                let review = reviewer.review(&events).with_context(|| {
+                    format!("failed to review {} as {player_id}", path.display())
+                })?;
+                evaluation.add(&review);
+            }
+            if !player_ids.is_empty() {
+                evaluation.games += 1;
+            }
+            bar.inc(1);
+        }
+
+        bar.abandon();
+        Ok(evaluation)
+    }
+}
+
+impl Evaluation {
+    /// Adds the review of one seat.
+    pub fn add(&mut self, review: &Review) {
+        self.seats += 1;
+        for entry in review.kyokus.iter().flat_map(|k| &k.entries) {
+            self.overall.add(entry);
+            self.situations
+                .entry(entry.situation)
+                .or_default()
+                .add(entry);
+        }
+    }
+}
+
+impl Agreement {
+    fn add(&mut self, entry: &Entry) {
+        self.reviewed += 1;
+        if entry.is_equal {
+            self.matches += 1;
+        } else if let (Some(best), Some(actual)) = (entry.details.first(), entry.actual_index) {
+            self.rated_disagreements += 1;
+            self.q_gap_sum += (best.q_value - entry.details[actual].q_value) as f64;
+            self.avg_q_gap = self.q_gap_sum / self.rated_disagreements as f64;
+        }
+        self.rate = self.matches as f64 / self.reviewed as f64;
+    }
+}
+
+/// Returns the seats named `player_name` in `events`, or all of them if
+/// `player_name` is `None`.
+fn seats(events: &[Event], player_name: Option<&str>) -> Result<Vec<u8>> {
+    let Some(Event::StartGame { names, .. }) = events.first() else {
+        bail!("first event is not start_game, got {:?}", events.first());
+    };
+    let ret = (0..4)
+        .filter(|&i| player_name.is_none_or(|n| names[i as usize] == n))
+        .collect();
+    Ok(ret)
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::{Detail, Reviewer};
+    use super::*;
+    use crate::agent::Tsumogiri;
+
+    use serde_json as json;
+
+    #[test]
+    fn aggregate() {
+        let log = r#"
+            {"type":"start_game","names":["a","b","c","a"]}
+            {"type":"start_kyoku","bakaze":"E","dora_marker":"1m","kyoku":1,"honba":0,"kyotaku":0,"oya":0,"scores":[25000,25000,25000,25000],"tehais":[["1m","2m","3m","4p","5p","6p","7s","8s","9s","E","E","S","W"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"]]}
+            {"type":"tsumo","actor":0,"pai":"N"}
+            {"type":"dahai","actor":0,"pai":"N","tsumogiri":true}
+            {"type":"tsumo","actor":1,"pai":"?"}
+            {"type":"dahai","actor":1,"pai":"E","tsumogiri":true}
+            {"type":"tsumo","actor":2,"pai":"?"}
+            {"type":"dahai","actor":2,"pai":"1p","tsumogiri":true}
+            {"type":"tsumo","actor":3,"pai":"?"}
+            {"type":"dahai","actor":3,"pai":"2p","tsumogiri":true}
+            {"type":"tsumo","actor":0,"pai":"S"}
+            {"type":"dahai","actor":0,"pai":"W","tsumogiri":false}
+        "#;
+        let events: Vec<Event> = log
+            .trim()
+            .lines()
+            .map(|l| json::from_str(l).unwrap())
+            .collect();
+
+        assert_eq!(seats(&events, Some("a")).unwrap(), [0, 3]);
+        assert_eq!(seats(&events, None).unwrap(), [0, 1, 2, 3]);
+        assert!(seats(&events, Some("e")).unwrap().is_empty());
+        assert!(seats(&events[1..], None).is_err());
+
+        let agent = Tsumogiri::new_batched(&[0]).unwrap();
+        let mut reviewer = Reviewer::new(Box::new(agent), 0, 1.).unwrap();
+        let mut review = reviewer.review(&events).unwrap();
+        // Tsumogiri N as it actually did, passing the pon on E as it actually
+        // did, and then tsumogiri S, but W was actually discarded.
+        assert_eq!(review.total_reviewed, 3);
+        assert_eq!(review.total_matches, 2);
+
+        // Fake q values for the disagreement.
+        let entry = &mut review.kyokus[0].entries[2];
+        assert!(!entry.is_equal);
+        entry.details = vec![
+            Detail {
+                action: 30,
+                q_value: 1.5,
+                prob: 0.6,
+            },
+            Detail {
+                action: 28,
+                q_value: 1.,
+                prob: 0.4,
+            },
+        ];
+        entry.actual_index = Some(1);
+
+        let mut evaluation = Evaluation::default();
+        evaluation.add(&review);
+        assert_eq!(evaluation.seats, 1);
+        assert_eq!(evaluation.overall.reviewed, 3);
+        assert_eq!(evaluation.overall.matches, 2);
+        assert!((evaluation.overall.rate - 2. / 3.).abs() < 1e-9);
+        assert_eq!(evaluation.overall.rated_disagreements, 1);
+        assert!((evaluation.overall.avg_q_gap - 0.5).abs() < 1e-9);
+
+        let call = &evaluation.situations[&Situation::Call];
+        assert_eq!((call.reviewed, call.matches), (1, 1));
+        let discard = &evaluation.situations[&Situation::Discard];
+        assert_eq!((discard.reviewed, discard.matches), (1, 1));
+        // Discarding W makes tenpai on E and S.
+        let riichi = &evaluation.situations[&Situation::Riichi];
+        assert_eq!((riichi.reviewed, riichi.matches), (1, 0));
+        assert!((riichi.avg_q_gap - 0.5).abs() < 1e-9);
+        assert!(!evaluation.situations.contains_key(&Situation::PushFold));
+
+        let value = json::to_value(&evaluation).unwrap();
+        assert_eq!(value["situations"]["riichi"]["reviewed"], 1);
+        assert!(value["overall"].get("q_gap_sum").is_none());
+    }
+}
@@ -0,0 +1,416 @@
+mod evaluation;
+
+#[cfg(feature = "python")]
+pub use evaluation::Evaluator;
+pub use evaluation::{Agreement, Evaluation};
+
+use crate::agent::BatchAgent;
+#[cfg(feature = "python")]
+use crate::agent::MortalBatchAgent;
+use crate::consts::ACTION_SPACE;
+use crate::mjai::{Event, EventExt, Metadata};
+#[cfg(feature = "python")]
+use crate::py_helper::add_submodule;
+use crate::state::{ActionCandidate, PlayerState};
+use crate::tile::Tile;
+use crate::{t, tu8};
+
+use anyhow::{ensure, Context, Result};
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+use riichi_macros::pyclass;
+use serde::Serialize;
+#[cfg(feature = "python")]
+use serde_json as json;
+
+/// `Reviewer` replays a full mjai log from the view of one player, and
+/// annotates every decision point of the player with the evaluation of the
+/// engine.
+///
+/// The output follows the schema of mjai-reviewer, except that the actions in
+/// `details` are action IDs instead of mjai events.
+#[pyclass]
+#[pyo3(text_signature = "(engine, player_id, *, temperature = 1.0)")]
+pub struct Reviewer {
+    agent: Box<dyn BatchAgent + Send>,
+    player_id: u8,
+    temperature: f32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Review {
+    pub total_reviewed: usize,
+    pub total_matches: usize,
+    /// Average probability of the actual actions, over the entries where the
+    /// engine reports q values. 0 if there is no such entry.
+    pub rating: f64,
+    pub temperature: f32,
+    pub kyokus: Vec<KyokuReview>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct KyokuReview {
+    /// Counts from 0, for example 4 for S1.
+    pub kyoku: u8,
+    pub honba: u8,
+    pub entries: Vec<Entry>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Entry {
+    pub junme: u8,
+    pub tiles_left: u8,
+    pub last_actor: Option<u8>,
+    /// The tile of the event that the player is reacting to, if any.
+    pub tile: Option<Tile>,
+    pub expected: Event,
+    pub actual: Event,
+    pub is_equal: bool,
+    /// Sorted by q values in descending order.
+    pub details: Vec<Detail>,
+    /// Index of the actual action in `details`.
+    pub actual_index: Option<usize>,
+    pub shanten: i8,
+    pub at_furiten: bool,
+    pub situation: Situation,
+}
+
+/// The kind of decision at a decision point, in the order of precedence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Situation {
+    /// Tsumo or ron is possible.
+    Agari,
+    /// Riichi is possible.
+    Riichi,
+    /// Chi, pon or daiminkan on a discard of others.
+    Call,
+    /// A discard while not in riichi but against a riichi of others.
+    PushFold,
+    /// Any other discard.
+    Discard,
+    /// Anything else, such as kyushukyuhai.
+    Other,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Detail {
+    pub action: usize,
+    pub q_value: f32,
+    pub prob: f32,
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl Reviewer {
+    #[new]
+    #[args("*", temperature = "1.")]
+    fn py_new(engine: PyObject, player_id: u8, temperature: f32) -> Result<Self> {
+        let agent = MortalBatchAgent::new(engine, &[player_id])?;
+        Self::new(Box::new(agent), player_id, temperature)
+    }
+
+    /// `log` is a full mjai log in JSON lines. Returns the review as a JSON
+    /// string.
+    #[pyo3(name = "review")]
+    #[pyo3(text_signature = "($self, log, /)")]
+    fn review_py(&mut self, log: &str, py: Python<'_>) -> Result<String> {
+        py.allow_threads(move || {
+            let events = log
+                .lines()
+                .filter(|l| !l.trim().is_empty())
+                .map(json::from_str)
+                .collect::<Result<Vec<Event>, _>>()
+                .context("failed to parse log")?;
+            let review = self.review(&events)?;
+            Ok(json::to_string(&review)?)
+        })
+    }
+}
+
+impl Reviewer {
+    /// `agent` must have been created with `player_id` as its only index.
+    pub fn new(agent: Box<dyn BatchAgent + Send>, player_id: u8, temperature: f32) -> Result<Self> {
+        ensure!(temperature > 0., "temperature must be positive");
+        Ok(Self {
+            agent,
+            player_id,
+            temperature,
+        })
+    }
+
+    pub fn review(&mut self, events: &[Event]) -> Result<Review> {
+        let mut state = PlayerState::new(self.player_id);
+        let mut log = vec![];
+        let mut kyokus = vec![];
+
+        for (i, event) in events.iter().enumerate() {
+            match *event {
+                Event::StartGame { .. } => {
+                    self.agent.start_game(0)?;
+                }
+                Event::StartKyoku {
+                    bakaze,
+                    kyoku,
+                    honba,
+                    ..
+                } => {
+                    kyokus.push(KyokuReview {
+                        kyoku: (bakaze.as_u8() - tu8!(E)) * 4 + kyoku - 1,
+                        honba,
+                        entries: vec![],
+                    });
+                    log.push(EventExt::no_meta(event.clone()));
+                }
+                Event::EndKyoku => {
+                    log.clear();
+                    self.agent.end_kyoku(0)?;
+                }
+                Event::EndGame => {
+                    self.agent.end_game(0, &Default::default())?;
+                }
+                _ => {
+                    log.push(EventExt::no_meta(event.clone()));
+                }
+            };
+
+            let cans = state
+                .update(event)
+                .with_context(|| format!("failed to update state at line {}", i + 1))?;
+            if !cans.can_act() {
+                continue;
+            }
+
+            self.agent
+                .set_scene(0, &log, &state, None)
+                .context("failed to add state")?;
+            let expected = self
+                .agent
+                .get_reaction(0, &log, &state, None)
+                .context("failed to get reaction")?;
+            let actual = actual_reaction(&events[i + 1..], self.player_id, cans.can_ryukyoku);
+
+            let situation = Situation::of(&state, cans);
+            let entry = self.entry(&state, event, expected, actual, situation);
+            kyokus
+                .last_mut()
+                .context("decision point before start_kyoku")?
+                .entries
+                .push(entry);
+        }
+
+        let entries = || kyokus.iter().flat_map(|k| &k.entries);
+        let total_reviewed = entries().count();
+        let total_matches = entries().filter(|e| e.is_equal).count();
+        let actual_probs: Vec<_> = entries()
+            .filter_map(|e| Some(e.details[e.actual_index?].prob as f64))
+            .collect();
+        let rating = if actual_probs.is_empty() {
+            0.
+        } else {
+            actual_probs.iter().sum::<f64>() / actual_probs.len() as f64
+        };
+
+        Ok(Review {
+            total_reviewed,
+            total_matches,
+            rating,
+            temperature: self.temperature,
+            kyokus,
+        })
+    }
+
+    fn entry(
+        &self,
+        state: &PlayerState,
+        last_event: &Event,
+        expected: EventExt,
+        actual: Event,
+        situation: Situation,
+    ) -> Entry {
+        let details = expected
+            .meta
+            .as_ref()
+            .map(|meta| details(meta, self.temperature))
+            .unwrap_or_default();
+
+        let expected_id = state.action_id_of(&expected.event);
+        let actual_id = state.action_id_of(&actual);
+        let actual_index = details.iter().position(|d| Some(d.action) == actual_id);
+        // There can be multiple kan choices sharing the same action ID.
+        let is_equal = match expected_id {
+            Some(42) | None => expected.event == actual,
+            _ => expected_id == actual_id,
+        };
+
+        let tile = match *last_event {
+            Event::Tsumo { pai, .. } | Event::Dahai { pai, .. } | Event::Kakan { pai, .. }
+                if pai != t!(?) =>
+            {
+                Some(pai)
+            }
+            _ => None,
+        };
+
+        Entry {
+            junme: state.at_turn(),
+            tiles_left: state.tiles_left(),
+            last_actor: last_event.actor(),
+            tile,
+            expected: expected.event,
+            actual,
+            is_equal,
+            details,
+            actual_index,
+            shanten: state.shanten(),
+            at_furiten: state.at_furiten(),
+            situation,
+        }
+    }
+}
+
+impl Situation {
+    fn of(state: &PlayerState, cans: ActionCandidate) -> Self {
+        let riichi = state.riichi_accepted();
+        if cans.can_tsumo_agari || cans.can_ron_agari {
+            Self::Agari
+        } else if cans.can_riichi {
+            Self::Riichi
+        } else if cans.can_chi() || cans.can_pon || cans.can_daiminkan {
+            Self::Call
+        } else if cans.can_discard && !riichi[0] && riichi[1..].contains(&true) {
+            Self::PushFold
+        } else if cans.can_discard {
+            Self::Discard
+        } else {
+            Self::Other
+        }
+    }
+}
+
+/// Finds the reaction the player actually made, from the events following a
+/// decision point. It is `none` if the player did not take the next event.
+fn actual_reaction(following: &[Event], player_id: u8, can_ryukyoku: bool) -> Event {
+    for ev in following {
+        match *ev {
+            // Multiple ron are consecutive `hora`s.
+            Event::Hora { actor, .. } if actor == player_id => return ev.clone(),
+            Event::Hora { .. } => continue,
+            Event::Ryukyoku { .. } if can_ryukyoku => return ev.clone(),
+            Event::Tsumo { .. } | Event::ReachAccepted { .. } => (),
+            _ if ev.actor() == Some(player_id) => return ev.clone(),
+            _ => (),
+        };
+        break;
+    }
+    Event::None
+}
+
+/// Returns the legal actions along with their q values and probabilities,
+/// which are the softmax of the q values divided by `temperature`.
+fn details(meta: &Metadata, temperature: f32) -> Vec<Detail> {
+    let (Some(q_values), Some(mask_bits)) = (&meta.q_values, meta.mask_bits) else {
+        return vec![];
+    };
+
+    let max_q = q_values.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+    let mut details: Vec<_> = (0..ACTION_SPACE)
+        .filter(|i| mask_bits & (1 << i) != 0)
+        .zip(q_values)
+        .map(|(action, &q_value)| Detail {
+            action,
+            q_value,
+            prob: ((q_value - max_q) / temperature).exp(),
+        })
+        .collect();
+    let sum: f32 = details.iter().map(|d| d.prob).sum();
+    for d in &mut details {
+        d.prob /= sum;
+    }
+    details.sort_by(|l, r| r.q_value.total_cmp(&l.q_value));
+    details
+}
+
+#[cfg(feature = "python")]
+pub(crate) fn register_module(py: Python<'_>, prefix: &str, super_mod: &PyModule) -> PyResult<()> {
+    let m = PyModule::new(py, "review")?;
+    m.add_class::<Reviewer>()?;
+    m.add_class::<Evaluator>()?;
+    add_submodule(py, prefix, super_mod, m)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::agent::Tsumogiri;
+
+    use serde_json as json;
+
+    #[test]
+    fn review_tsumogiri() {
+        let log = r#"
+            {"type":"start_game","names":["a","b","c","d"]}
+            {"type":"start_kyoku","bakaze":"S","dora_marker":"1m","kyoku":2,"honba":1,"kyotaku":0,"oya":1,"scores":[25000,25000,25000,25000],"tehais":[["1m","2m","3m","4p","5p","6p","7s","8s","9s","E","E","S","S"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"]]}
+            {"type":"tsumo","actor":1,"pai":"?"}
+            {"type":"dahai","actor":1,"pai":"E","tsumogiri":false}
+            {"type":"tsumo","actor":2,"pai":"?"}
+            {"type":"dahai","actor":2,"pai":"1p","tsumogiri":true}
+            {"type":"tsumo","actor":3,"pai":"?"}
+            {"type":"dahai","actor":3,"pai":"2p","tsumogiri":true}
+            {"type":"tsumo","actor":0,"pai":"W"}
+            {"type":"dahai","actor":0,"pai":"S","tsumogiri":false}
+        "#;
+        let events: Vec<Event> = log
+            .trim()
+            .lines()
+            .map(|l| json::from_str(l).unwrap())
+            .collect();
+
+        let agent = Tsumogiri::new_batched(&[0]).unwrap();
+        let mut reviewer = Reviewer::new(Box::new(agent), 0, 1.).unwrap();
+        let review = reviewer.review(&events).unwrap();
+
+        assert_eq!(review.kyokus.len(), 1);
+        assert_eq!(review.kyokus[0].kyoku, 5);
+        assert_eq!(review.kyokus[0].honba, 1);
+        assert_eq!(review.total_reviewed, 2);
+        assert_eq!(review.total_matches, 1);
+        // Tsumogiri does not report q values.
+        assert!(review.rating.abs() < 1e-9);
+
+        let entries = &review.kyokus[0].entries;
+        // Passes the pon on E, as it actually did.
+        assert_eq!(entries[0].last_actor, Some(1));
+        assert_eq!(entries[0].tile, Some(t!(E)));
+        assert_eq!(entries[0].actual, Event::None);
+        assert!(entries[0].is_equal);
+        assert_eq!(entries[0].situation, Situation::Call);
+        // Tsumogiri W, but S was actually discarded.
+        assert_eq!(entries[1].tile, Some(t!(W)));
+        assert!(matches!(entries[1].actual, Event::Dahai { pai, .. } if pai == t!(S)));
+        assert!(!entries[1].is_equal);
+        // Tenpai with shanpon on E and S after discarding W.
+        assert_eq!(entries[1].situation, Situation::Riichi);
+
+        assert!(Reviewer::new(Box::new(Tsumogiri::new_batched(&[0]).unwrap()), 0, 0.).is_err());
+    }
+
+    #[test]
+    fn ranked_details() {
+        let meta = Metadata {
+            // Actions 1, 37 and 45.
+            q_values: Some(vec![0.5, 2., -1.]),
+            mask_bits: Some(1 << 1 | 1 << 37 | 1 << 45),
+            ..Default::default()
+        };
+        let details = details(&meta, 1.);
+        let actions: Vec<_> = details.iter().map(|d| d.action).collect();
+        assert_eq!(actions, [37, 1, 45]);
+        assert!((details.iter().map(|d| d.prob).sum::<f32>() - 1.).abs() < 1e-5);
+        assert!(details[0].prob > details[1].prob && details[1].prob > details[2].prob);
+
+        // Higher temperatures flatten the distribution.
+        let flat = super::details(&meta, 10.);
+        assert!(flat[0].prob < details[0].prob);
+    }
+}
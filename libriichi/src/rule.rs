@@ -0,0 +1,487 @@
+#[cfg(feature = "python")]
+use crate::py_helper::add_submodule;
+use std::fmt;
+use std::str::FromStr;
+
+use anyhow::{bail, ensure, Result};
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+use riichi_macros::{pyclass, pymethods};
+use serde::{Deserialize, Serialize};
+
+/// Rule variants that differ between platforms.
+///
+/// The default is Tenhou's rule, which is also what everything else in this
+/// crate assumes when no `RuleSet` is given.
+#[pyclass]
+#[pyo3(text_signature = "(
+    *,
+    aka_count = 3,
+    kuitan = True,
+    atozuke = True,
+    double_ron = True,
+    honba_per_winner = False,
+    kazoe_yakuman = True,
+    tonpuusen = False,
+    starting_points = 25000,
+    goal_points = 30000,
+    extra_kyokus = 4,
+    nagashi_mangan = True,
+    rinshan_pao = False,
+    abortive_ryukyoku = True,
+    kuikae = 'strict',
+    noten_bappu = True,
+    keishiki_tenpai = True,
+    agari_yame = True,
+    tenpai_yame = True,
+    ryanhan_shibari = False,
+    renhou = False,
+    daisharin = False,
+    open_riichi = False,
+    chombo = 'disabled',
+    chombo_points = 4000,
+)")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RuleSet {
+    /// Number of aka doras in the wall, one at most for each suit in the order
+    /// of 5m, 5p and 5s, and 4 for a second aka 5p on top of them. 0 is a
+    /// rule without aka. See `akas_per_suit`.
+    #[pyo3(get, set)]
+    pub aka_count: u8,
+    /// Whether tanyao counts as a yaku for an open hand.
+    #[pyo3(get, set)]
+    pub kuitan: bool,
+    /// Whether a hand can win on a tile when only some of its waits have yaku.
+    /// If `false`, every wait of the hand must have a yaku that does not come
+    /// from the situation (riichi, menzen tsumo, haitei, etc.).
+    #[pyo3(get, set)]
+    pub atozuke: bool,
+    /// Whether multiple players can ron the same tile. If `false`, only the
+    /// one closest to the discarder wins (head bump).
+    #[pyo3(get, set)]
+    pub double_ron: bool,
+    /// Whether every winner of a multi-ron gets the honba bonus from the
+    /// discarder. If `false`, only the one closest to the discarder gets it.
+    /// Kyotaku always goes to the closest one.
+    ///
+    /// A triple ron is an abortive ryukyoku when `abortive_ryukyoku` is
+    /// enabled, and otherwise settled the same way as a double ron.
+    #[pyo3(get, set)]
+    pub honba_per_winner: bool,
+    /// Whether 13 or more han counts as a yakuman. If `false`, it is capped at
+    /// sanbaiman.
+    #[pyo3(get, set)]
+    pub kazoe_yakuman: bool,
+    /// Whether the game is an east-only game instead of a hanchan.
+    #[pyo3(get, set)]
+    pub tonpuusen: bool,
+    #[pyo3(get, set)]
+    pub starting_points: i32,
+    /// Points to reach for the game to end after the last kyoku of `length`,
+    /// which also applies to the agari-yame of the oya at all-last.
+    #[pyo3(get, set)]
+    pub goal_points: i32,
+    /// Maximum number of kyokus played after the last kyoku of `length` while
+    /// no one reaches `goal_points`, with sudden death. 4 means until W4 (or
+    /// S4 for tonpuusen), and 0 means the game always ends at the last kyoku.
+    #[pyo3(get, set)]
+    pub extra_kyokus: u8,
+    /// Whether nagashi mangan is paid at an exhaustive ryukyoku.
+    #[pyo3(get, set)]
+    pub nagashi_mangan: bool,
+    /// Whether the discarder of a daiminkan pays the whole of a rinshan
+    /// kaihou off it, as if it were a ron. Pao of daisangen and daisuushii
+    /// always applies regardless.
+    #[pyo3(get, set)]
+    pub rinshan_pao: bool,
+    /// Whether abortive ryukyokus (九種九牌, 四風連打, 四家立直, 四槓散了
+    /// and 三家和了) are enabled. 三家和了 only applies when `double_ron` is
+    /// also enabled.
+    #[pyo3(get, set)]
+    pub abortive_ryukyoku: bool,
+    /// Which discards are forbidden right after a chi or pon, one of
+    /// `"strict"`, `"loose"` and `"disabled"` in Python.
+    pub kuikae: Kuikae,
+    /// Whether the noten players pay the tenpai players 3000 points in total
+    /// at an exhaustive ryukyoku.
+    #[pyo3(get, set)]
+    pub noten_bappu: bool,
+    /// Whether a tenpai hand without any yaku on all its waits counts as
+    /// tenpai at an exhaustive ryukyoku, for both the payment and the renchan
+    /// of the oya. A menzen hand always has menzen tsumo as the yaku.
+    #[pyo3(get, set)]
+    pub keishiki_tenpai: bool,
+    /// Whether the game ends when the oya wins at all-last and is the top
+    /// with at least `goal_points`.
+    #[pyo3(get, set)]
+    pub agari_yame: bool,
+    /// Same as `agari_yame`, but for the oya being tenpai at an exhaustive
+    /// ryukyoku.
+    #[pyo3(get, set)]
+    pub tenpai_yame: bool,
+    /// Whether a hora needs at least 2 han without doras from 5 honba on.
+    #[pyo3(get, set)]
+    pub ryanhan_shibari: bool,
+    /// Local yaku: whether a ko winning by ron before their first tsumo,
+    /// without any call made before it, is a yakuman, like tenhou and
+    /// chiihou.
+    #[pyo3(get, set)]
+    pub renhou: bool,
+    /// Local yaku: whether 22334455667788p in menzen is a yakuman.
+    #[pyo3(get, set)]
+    pub daisharin: bool,
+    /// Local yaku: whether riichi can be declared open, which is worth 1 more
+    /// han than riichi. See `Event::Reach`.
+    #[pyo3(get, set)]
+    pub open_riichi: bool,
+    /// How a chombo (錯和), such as a false hora or an illegal call, is
+    /// settled by `GameState`, one of `"disabled"`, `"reverse_mangan"` and
+    /// `"fixed"` in Python. See `Chombo`.
+    pub chombo: Chombo,
+    /// Points the offender pays to each of the others for a chombo under
+    /// `Chombo::Fixed`.
+    #[pyo3(get, set)]
+    pub chombo_points: i32,
+}
+
+/// Restriction of kuikae (喰い替え), discarding a tile right after a chi or
+/// pon that could have formed the same kind of set with the consumed tiles.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Kuikae {
+    /// Both the called tile and the suji swap are forbidden, e.g. neither 4s
+    /// nor 7s can be discarded after 56s chi 4s, as on Tenhou and Mahjong Soul.
+    Strict,
+    /// Only the called tile is forbidden, while the suji swap is allowed.
+    Loose,
+    /// Anything can be discarded.
+    Disabled,
+}
+
+/// Settlement of a chombo, after which the kyoku is replayed with a new deal
+/// and the same honba, while the riichi sticks of the kyoku go back to their
+/// owners.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Chombo {
+    /// There is no chombo, and the illegal action is rejected instead.
+    Disabled,
+    /// The offender pays as if everyone else won a mangan by tsumo, which is
+    /// 4000 all as the oya, or 4000 to the oya and 2000 to each ko as a ko.
+    ReverseMangan,
+    /// The offender pays `RuleSet::chombo_points` to each of the others.
+    Fixed,
+}
+
+impl Default for RuleSet {
+    fn default() -> Self {
+        Self::tenhou()
+    }
+}
+
+#[pymethods]
+impl RuleSet {
+    #[new]
+    #[args(
+        "*",
+        aka_count = "3",
+        kuitan = "true",
+        atozuke = "true",
+        double_ron = "true",
+        honba_per_winner = "false",
+        kazoe_yakuman = "true",
+        tonpuusen = "false",
+        starting_points = "25000",
+        goal_points = "30000",
+        extra_kyokus = "4",
+        nagashi_mangan = "true",
+        rinshan_pao = "false",
+        abortive_ryukyoku = "true",
+        kuikae = "\"strict\"",
+        noten_bappu = "true",
+        keishiki_tenpai = "true",
+        agari_yame = "true",
+        tenpai_yame = "true",
+        ryanhan_shibari = "false",
+        renhou = "false",
+        daisharin = "false",
+        open_riichi = "false",
+        chombo = "\"disabled\"",
+        chombo_points = "4000"
+    )]
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        aka_count: u8,
+        kuitan: bool,
+        atozuke: bool,
+        double_ron: bool,
+        honba_per_winner: bool,
+        kazoe_yakuman: bool,
+        tonpuusen: bool,
+        starting_points: i32,
+        goal_points: i32,
+        extra_kyokus: u8,
+        nagashi_mangan: bool,
+        rinshan_pao: bool,
+        abortive_ryukyoku: bool,
+        kuikae: &str,
+        noten_bappu: bool,
+        keishiki_tenpai: bool,
+        agari_yame: bool,
+        tenpai_yame: bool,
+        ryanhan_shibari: bool,
+        renhou: bool,
+        daisharin: bool,
+        open_riichi: bool,
+        chombo: &str,
+        chombo_points: i32,
+    ) -> Result<Self> {
+        let rule = Self {
+            aka_count,
+            kuitan,
+            atozuke,
+            double_ron,
+            honba_per_winner,
+            kazoe_yakuman,
+            tonpuusen,
+            starting_points,
+            goal_points,
+            extra_kyokus,
+            nagashi_mangan,
+            rinshan_pao,
+            abortive_ryukyoku,
+            kuikae: kuikae.parse()?,
+            noten_bappu,
+            keishiki_tenpai,
+            agari_yame,
+            tenpai_yame,
+            ryanhan_shibari,
+            renhou,
+            daisharin,
+            open_riichi,
+            chombo: chombo.parse()?,
+            chombo_points,
+        };
+        rule.validate()?;
+        Ok(rule)
+    }
+
+    #[getter(kuikae)]
+    fn kuikae_py(&self) -> String {
+        self.kuikae.to_string()
+    }
+    #[setter(kuikae)]
+    fn set_kuikae_py(&mut self, kuikae: &str) -> Result<()> {
+        self.kuikae = kuikae.parse()?;
+        Ok(())
+    }
+
+    #[getter(chombo)]
+    fn chombo_py(&self) -> String {
+        self.chombo.to_string()
+    }
+    #[setter(chombo)]
+    fn set_chombo_py(&mut self, chombo: &str) -> Result<()> {
+        self.chombo = chombo.parse()?;
+        Ok(())
+    }
+
+    /// Number of kyokus excluding extra rounds, 8 for hanchan and 4 for
+    /// tonpuusen.
+    #[getter]
+    #[must_use]
+    pub const fn length(&self) -> u8 {
+        if self.tonpuusen {
+            4
+        } else {
+            8
+        }
+    }
+
+    /// Whether the kyoku, counting from 0 from E1, can be the last one of the
+    /// game, which is the last kyoku of `length` and any extra kyoku after it.
+    #[must_use]
+    pub const fn is_all_last(&self, kyoku: u8) -> bool {
+        kyoku + 1 >= self.length()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("{self:?}")
+    }
+}
+
+/// Number of akas of 5m, 5p and 5s with `aka_count = 4`, which is the most
+/// any rule has.
+pub const MAX_AKAS_PER_SUIT: [u8; 3] = [1, 2, 1];
+
+/// Returns the number of akas of 5m, 5p and 5s in the wall for `aka_count`,
+/// see `RuleSet::aka_count`.
+#[must_use]
+pub const fn akas_per_suit(aka_count: u8) -> [u8; 3] {
+    match aka_count {
+        0 => [0, 0, 0],
+        1 => [1, 0, 0],
+        2 => [1, 1, 0],
+        3 => [1, 1, 1],
+        _ => MAX_AKAS_PER_SUIT,
+    }
+}
+
+impl RuleSet {
+    #[inline]
+    #[must_use]
+    pub const fn akas_per_suit(&self) -> [u8; 3] {
+        akas_per_suit(self.aka_count)
+    }
+
+    #[must_use]
+    pub const fn tenhou() -> Self {
+        Self {
+            aka_count: 3,
+            kuitan: true,
+            atozuke: true,
+            double_ron: true,
+            honba_per_winner: false,
+            kazoe_yakuman: true,
+            tonpuusen: false,
+            starting_points: 25000,
+            goal_points: 30000,
+            extra_kyokus: 4,
+            nagashi_mangan: true,
+            rinshan_pao: false,
+            abortive_ryukyoku: true,
+            kuikae: Kuikae::Strict,
+            noten_bappu: true,
+            keishiki_tenpai: true,
+            agari_yame: true,
+            tenpai_yame: true,
+            ryanhan_shibari: false,
+            renhou: false,
+            daisharin: false,
+            open_riichi: false,
+            chombo: Chombo::Disabled,
+            chombo_points: 4000,
+        }
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        ensure!(
+            self.aka_count <= 4,
+            "aka_count must be in range [0, 4], got {}",
+            self.aka_count,
+        );
+        ensure!(
+            self.starting_points > 0,
+            "starting_points must be positive, got {}",
+            self.starting_points,
+        );
+        ensure!(
+            self.goal_points > 0,
+            "goal_points must be positive, got {}",
+            self.goal_points,
+        );
+        ensure!(
+            self.extra_kyokus <= 4,
+            "extra_kyokus must be in range [0, 4], got {}",
+            self.extra_kyokus,
+        );
+        ensure!(
+            self.chombo_points >= 0,
+            "chombo_points must not be negative, got {}",
+            self.chombo_points,
+        );
+        Ok(())
+    }
+}
+
+impl Kuikae {
+    /// Returns whether discarding the called tile itself is forbidden.
+    #[inline]
+    #[must_use]
+    pub const fn forbids_genbutsu(self) -> bool {
+        !matches!(self, Self::Disabled)
+    }
+    /// Returns whether discarding the suji swap of a chi is forbidden.
+    #[inline]
+    #[must_use]
+    pub const fn forbids_suji(self) -> bool {
+        matches!(self, Self::Strict)
+    }
+}
+
+impl FromStr for Kuikae {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "strict" => Ok(Self::Strict),
+            "loose" => Ok(Self::Loose),
+            "disabled" => Ok(Self::Disabled),
+            _ => bail!("unknown kuikae {s}, expected strict, loose or disabled"),
+        }
+    }
+}
+
+impl fmt::Display for Kuikae {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Strict => "strict",
+            Self::Loose => "loose",
+            Self::Disabled => "disabled",
+        })
+    }
+}
+
+impl Chombo {
+    /// Returns the deltas of a chombo of `actor`, where `oya` is the oya of
+    /// the kyoku, or `None` if chombo is disabled.
+    #[must_use]
+    pub fn deltas(self, actor: u8, oya: u8, points: i32) -> Option<[i32; 4]> {
+        if self == Self::Disabled {
+            return None;
+        }
+        let mut deltas = [0; 4];
+        for (seat, d) in (0..4).zip(&mut deltas) {
+            if seat != actor {
+                *d = match self {
+                    Self::Fixed => points,
+                    _ if actor == oya || seat == oya => 4000,
+                    _ => 2000,
+                };
+            }
+        }
+        deltas[actor as usize] = -deltas.iter().sum::<i32>();
+        Some(deltas)
+    }
+}
+
+impl FromStr for Chombo {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "disabled" => Ok(Self::Disabled),
+            "reverse_mangan" => Ok(Self::ReverseMangan),
+            "fixed" => Ok(Self::Fixed),
+            _ => bail!("unknown chombo {s}, expected disabled, reverse_mangan or fixed"),
+        }
+    }
+}
+
+impl fmt::Display for Chombo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Disabled => "disabled",
+            Self::ReverseMangan => "reverse_mangan",
+            Self::Fixed => "fixed",
+        })
+    }
+}
+
+#[cfg(feature = "python")]
+pub(crate) fn register_module(py: Python<'_>, prefix: &str, super_mod: &PyModule) -> PyResult<()> {
+    let m = PyModule::new(py, "rule")?;
+    m.add_class::<RuleSet>()?;
+    add_submodule(py, prefix, super_mod, m)
+}
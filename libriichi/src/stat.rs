@@ -1,18 +1,20 @@
 use crate::algo::point::Point;
+use crate::arena::GameResult;
 use crate::mjai::Event;
+use crate::par::*;
+#[cfg(feature = "python")]
 use crate::py_helper::add_submodule;
+use crate::state::{PlayerState, PushFold};
 use crate::vec_ops::vec_add_assign;
+use crate::{log_io, t};
 use std::fmt;
-use std::fs::File;
-use std::io::prelude::*;
 
 use anyhow::{bail, Context, Result};
 use derive_more::{Add, AddAssign, Sum};
-use flate2::read::GzDecoder;
-use glob::glob;
 use indicatif::{ProgressBar, ProgressStyle};
+#[cfg(feature = "python")]
 use pyo3::prelude::*;
-use rayon::prelude::*;
+use riichi_macros::{pyclass, pymethods};
 use serde_json as json;
 
 /// Notes:
@@ -23,6 +25,8 @@ use serde_json as json;
 ///   discarded.
 /// - Every other Δscore cover kyotakus.
 /// - Ankan is not recognized as fuuro.
+/// - Push and fold are classified by `PlayerState::push_fold`, only in the
+///   kyokus where the starting hand of the player is known.
 #[pyclass]
 #[derive(Debug, Clone, Default, PartialEq, Eq, Add, AddAssign, Sum)]
 pub struct Stat {
@@ -121,6 +125,15 @@ pub struct Stat {
     pub yakuman: i64,
     #[pyo3(get, set)]
     pub nagashi_mangan: i64,
+
+    #[pyo3(get, set)]
+    pub threatened_dahai: i64,
+    #[pyo3(get, set)]
+    pub push: i64,
+    #[pyo3(get, set)]
+    pub push_tenpai: i64,
+    #[pyo3(get, set)]
+    pub fold: i64,
 }
 
 impl fmt::Display for Stat {
@@ -180,6 +193,12 @@ Winning rate after call {:.6}
 Deal-in rate after call {:.6}
 Avg call Δscore         {:.6}
 
+Discards under threat    {}
+Push rate                {:.6}
+Fold rate                {:.6}
+Ambiguous rate           {:.6}
+Tenpai pushes/all pushes {:.6}
+
 Dealer wins/all dealer rounds  {:.6}
 Dealer wins/all wins           {:.6}
 Deal-in to dealer/all deal-ins {:.6}
@@ -243,6 +262,12 @@ Nagashi mangan (rate) {} ({:.9})"#,
             self.houjuu_rate_after_fuuro(),
             self.avg_fuuro_point(),
             //
+            self.threatened_dahai,
+            self.push_rate(),
+            self.fold_rate(),
+            self.ambiguous_rate(),
+            self.push_tenpai_rate(),
+            //
             self.agari_rate_as_oya(),
             self.agari_as_oya_rate(),
             self.houjuu_to_oya_rate(),
@@ -306,7 +331,7 @@ impl Stat {
                 fuuro_num += 1;
             }
 
-            Event::Reach { actor } => {
+            Event::Reach { actor, .. } => {
                 if actor == player_id {
                     riichi_declared = true;
                     stat.riichi += 1;
@@ -393,7 +418,7 @@ impl Stat {
                 }
             }
 
-            Event::Ryukyoku { deltas } => {
+            Event::Ryukyoku { deltas, .. } => {
                 let deltas = deltas.expect("deltas is required for analyzing");
                 vec_add_assign(&mut cur_scores, &deltas);
 
@@ -426,6 +451,8 @@ impl Stat {
             *cur_scores.iter_mut().min_by_key(|s| -**s).unwrap() += cur_kyotaku as i32 * 1000;
         }
 
+        stat.count_push_fold(events, player_id);
+
         let final_score = cur_scores[player_id as usize];
         stat.point = final_score as i64 - 25000;
         if final_score < 0 {
@@ -449,6 +476,129 @@ impl Stat {
 
         stat
     }
+
+    fn count_push_fold(&mut self, events: &[Event], player_id: u8) {
+        let mut state = PlayerState::new(player_id);
+        let mut hand_known = false;
+        for ev in events {
+            if let Event::StartKyoku { tehais, .. } = ev {
+                hand_known = !tehais[player_id as usize].contains(&t!(?));
+            }
+            if !hand_known {
+                continue;
+            }
+
+            let push_fold = match *ev {
+                Event::Dahai { actor, pai, .. } if actor == player_id => state.push_fold(pai),
+                _ => None,
+            };
+            hand_known = state.update(ev).is_ok();
+
+            if let Some(push_fold) = push_fold {
+                self.threatened_dahai += 1;
+                match push_fold {
+                    PushFold::Push => {
+                        self.push += 1;
+                        if state.shanten() == 0 {
+                            self.push_tenpai += 1;
+                        }
+                    }
+                    PushFold::Fold => self.fold += 1,
+                    PushFold::Ambiguous => (),
+                }
+            }
+        }
+    }
+
+    /// Same as `from_game`, on a game played in the arena.
+    #[must_use]
+    pub fn from_game_result(game_result: &GameResult, player_id: u8) -> Self {
+        let events: Vec<_> = game_result
+            .game_log
+            .iter()
+            .flatten()
+            .map(|ev| ev.event.clone())
+            .collect();
+        Self::from_game(&events, player_id)
+    }
+}
+
+/// `StatCollector` accumulates the `Stat` of a player from a live stream of
+/// mjai events, which may span multiple games.
+///
+/// The stream must contain `start_game` and `end_game`, and the player is
+/// identified by the names in `start_game`. A game is counted only after its
+/// `end_game` is received.
+#[pyclass]
+#[pyo3(text_signature = "(player_name)")]
+#[derive(Debug, Clone)]
+pub struct StatCollector {
+    player_name: String,
+    player_ids: Vec<u8>,
+    events: Vec<Event>,
+
+    #[pyo3(get)]
+    stat: Stat,
+}
+
+#[pymethods]
+impl StatCollector {
+    #[new]
+    #[must_use]
+    pub fn new(player_name: String) -> Self {
+        Self {
+            player_name,
+            player_ids: vec![],
+            events: vec![],
+            stat: Stat::default(),
+        }
+    }
+
+    /// `line` is a JSON string representing one single mjai event.
+    #[pyo3(name = "push")]
+    #[pyo3(text_signature = "($self, line, /)")]
+    fn push_py(&mut self, line: &str) -> Result<()> {
+        let event =
+            json::from_str(line).with_context(|| format!("failed to parse event {line}"))?;
+        self.push(event);
+        Ok(())
+    }
+
+    fn __repr__(&self) -> String {
+        format!("{:?}", self.stat)
+    }
+}
+
+impl StatCollector {
+    pub fn push(&mut self, event: Event) {
+        match event {
+            Event::StartGame { ref names, .. } => {
+                self.player_ids = names
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, name)| **name == self.player_name)
+                    .map(|(i, _)| i as u8)
+                    .collect();
+                self.events.clear();
+                self.events.push(event);
+            }
+            Event::EndGame => {
+                self.events.push(event);
+                for &player_id in &self.player_ids {
+                    self.stat += Stat::from_game(&self.events, player_id);
+                }
+                self.player_ids.clear();
+                self.events.clear();
+            }
+            _ => self.events.push(event),
+        }
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn stat(&self) -> &Stat {
+        &self.stat
+    }
 }
 
 #[pymethods]
@@ -468,27 +618,11 @@ impl Stat {
         };
         bar.enable_steady_tick(150);
 
-        let stat = glob(&format!("{dir}/**/*.json"))?
-            .chain(glob(&format!("{dir}/**/*.json.gz"))?)
+        let stat = log_io::glob_logs(dir)?
             .par_bridge()
             .map(|path| {
                 bar.inc(1);
-                let path = path?;
-
-                let mut raw_log = String::new();
-                if matches!(path.extension(), Some(s) if s.eq_ignore_ascii_case("gz")) {
-                    let mut gz = GzDecoder::new(File::open(path)?);
-                    gz.read_to_string(&mut raw_log)?;
-                } else {
-                    let mut f = File::open(path)?;
-                    f.read_to_string(&mut raw_log)?;
-                }
-
-                let events = raw_log
-                    .lines()
-                    .map(json::from_str)
-                    .collect::<Result<Vec<Event>, _>>()
-                    .context("failed to parse log")?;
+                let events = log_io::read_events(path?)?;
 
                 match events.get(0) {
                     Some(Event::StartGame { names, .. }) => {
@@ -800,6 +934,31 @@ impl Stat {
         self.nagashi_mangan as f64 / self.round as f64
     }
 
+    #[getter]
+    #[inline]
+    #[must_use]
+    pub fn push_rate(&self) -> f64 {
+        self.push as f64 / self.threatened_dahai as f64
+    }
+    #[getter]
+    #[inline]
+    #[must_use]
+    pub fn fold_rate(&self) -> f64 {
+        self.fold as f64 / self.threatened_dahai as f64
+    }
+    #[getter]
+    #[inline]
+    #[must_use]
+    pub fn ambiguous_rate(&self) -> f64 {
+        (self.threatened_dahai - self.push - self.fold) as f64 / self.threatened_dahai as f64
+    }
+    #[getter]
+    #[inline]
+    #[must_use]
+    pub fn push_tenpai_rate(&self) -> f64 {
+        self.push_tenpai as f64 / self.push as f64
+    }
+
     fn __str__(&self) -> String {
         self.to_string()
     }
@@ -808,8 +967,109 @@ impl Stat {
     }
 }
 
+#[cfg(feature = "python")]
 pub(crate) fn register_module(py: Python<'_>, prefix: &str, super_mod: &PyModule) -> PyResult<()> {
     let m = PyModule::new(py, "stat")?;
     m.add_class::<Stat>()?;
+    m.add_class::<StatCollector>()?;
     add_submodule(py, prefix, super_mod, m)
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const LOG: &str = r#"
+        {"type":"start_game","names":["a","b","a","c"]}
+        {"type":"start_kyoku","bakaze":"E","dora_marker":"1m","kyoku":1,"honba":0,"kyotaku":0,"oya":0,"scores":[25000,25000,25000,25000],"tehais":[["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"]]}
+        {"type":"tsumo","actor":0,"pai":"1m"}
+        {"type":"reach","actor":0}
+        {"type":"dahai","actor":0,"pai":"1m","tsumogiri":true}
+        {"type":"reach_accepted","actor":0}
+        {"type":"tsumo","actor":1,"pai":"2m"}
+        {"type":"dahai","actor":1,"pai":"2m","tsumogiri":true}
+        {"type":"hora","actor":0,"target":1,"deltas":[9000,-8000,0,0]}
+        {"type":"end_kyoku"}
+        {"type":"end_game"}
+    "#;
+
+    #[test]
+    fn collector() {
+        let lines: Vec<_> = LOG.trim().lines().map(str::trim).collect();
+        let log = lines.join("\n");
+
+        let mut collector = StatCollector::new("a".to_owned());
+        for _ in 0..2 {
+            for line in &lines {
+                collector.push(json::from_str(line).unwrap());
+            }
+        }
+        // Unfinished games are not counted.
+        collector.push(json::from_str(lines[0]).unwrap());
+
+        let one_game = Stat::from_log(&log, 0).unwrap() + Stat::from_log(&log, 2).unwrap();
+        let expected = one_game.clone() + one_game;
+        assert_eq!(collector.stat(), &expected);
+
+        let stat = collector.stat();
+        assert_eq!(stat.game, 4);
+        assert_eq!(stat.riichi, 2);
+        assert_eq!(stat.riichi_agari, 2);
+        assert_eq!(stat.riichi_agari_point, 8000 * 2);
+        assert_eq!(stat.rank_1 + stat.rank_2, 4);
+    }
+
+    #[test]
+    fn push_fold() {
+        let log = r#"
+            {"type":"start_game","names":["a","b","c","d"]}
+            {"type":"start_kyoku","bakaze":"E","dora_marker":"9p","kyoku":1,"honba":0,"kyotaku":0,"oya":0,"scores":[25000,25000,25000,25000],"tehais":[["1m","2m","3m","4p","5p","6p","7s","8s","9s","E","E","3s","C"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"]]}
+            {"type":"tsumo","actor":0,"pai":"5s"}
+            {"type":"dahai","actor":0,"pai":"C","tsumogiri":false}
+            {"type":"tsumo","actor":1,"pai":"?"}
+            {"type":"dahai","actor":1,"pai":"4m","tsumogiri":false}
+            {"type":"tsumo","actor":2,"pai":"?"}
+            {"type":"dahai","actor":2,"pai":"1p","tsumogiri":true}
+            {"type":"tsumo","actor":3,"pai":"?"}
+            {"type":"dahai","actor":3,"pai":"9m","tsumogiri":true}
+            {"type":"tsumo","actor":0,"pai":"N"}
+            {"type":"dahai","actor":0,"pai":"N","tsumogiri":true}
+            {"type":"tsumo","actor":1,"pai":"?"}
+            {"type":"reach","actor":1}
+            {"type":"dahai","actor":1,"pai":"6s","tsumogiri":false}
+            {"type":"reach_accepted","actor":1}
+            {"type":"tsumo","actor":2,"pai":"?"}
+            {"type":"dahai","actor":2,"pai":"2s","tsumogiri":true}
+            {"type":"tsumo","actor":3,"pai":"?"}
+            {"type":"dahai","actor":3,"pai":"5m","tsumogiri":true}
+            {"type":"tsumo","actor":0,"pai":"P"}
+            {"type":"dahai","actor":0,"pai":"P","tsumogiri":true}
+            {"type":"tsumo","actor":1,"pai":"?"}
+            {"type":"dahai","actor":1,"pai":"W","tsumogiri":true}
+            {"type":"tsumo","actor":2,"pai":"?"}
+            {"type":"dahai","actor":2,"pai":"W","tsumogiri":true}
+            {"type":"tsumo","actor":3,"pai":"?"}
+            {"type":"dahai","actor":3,"pai":"W","tsumogiri":true}
+            {"type":"tsumo","actor":0,"pai":"2s"}
+            {"type":"dahai","actor":0,"pai":"2s","tsumogiri":true}
+            {"type":"tsumo","actor":1,"pai":"?"}
+            {"type":"hora","actor":1,"target":1,"deltas":[-2000,5000,-1000,-1000]}
+            {"type":"end_kyoku"}
+            {"type":"end_game"}
+        "#;
+        let log: Vec<_> = log.trim().lines().map(str::trim).collect();
+        let log = log.join("\n");
+
+        // P is live against the riichi while being tenpai, and 2s is genbutsu.
+        let stat = Stat::from_log(&log, 0).unwrap();
+        assert_eq!(stat.threatened_dahai, 2);
+        assert_eq!(stat.push, 1);
+        assert_eq!(stat.push_tenpai, 1);
+        assert_eq!(stat.fold, 1);
+        assert!(stat.ambiguous_rate().abs() < 1e-9);
+
+        // The hands of others are unknown.
+        let stat = Stat::from_log(&log, 2).unwrap();
+        assert_eq!(stat.threatened_dahai, 0);
+    }
+}
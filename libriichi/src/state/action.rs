@@ -3,13 +3,18 @@ use crate::chi_type::ChiType;
 use crate::mjai::Event;
 use crate::tile::Tile;
 use crate::tuz;
+use std::error::Error;
+use std::fmt;
 
-use anyhow::{bail, ensure, Result};
+#[cfg(feature = "python")]
 use pyo3::prelude::*;
-use serde::Serialize;
+#[cfg(feature = "python")]
+use pyo3::types::PyDict;
+use riichi_macros::{pyclass, pymethods};
+use serde::{Deserialize, Serialize};
 
 #[pyclass]
-#[derive(Debug, Default, Clone, Copy, Serialize)]
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize)]
 pub struct ActionCandidate {
     #[pyo3(get)]
     pub can_discard: bool,
@@ -65,19 +70,204 @@ impl ActionCandidate {
             || self.can_ryukyoku
     }
 
+    /// Whether the player must respond, i.e. `none` is not a legal reaction.
+    /// This is the case when the tehai is 3n+2 and a discard is due.
+    #[getter]
+    #[inline]
+    #[must_use]
+    pub const fn is_forced(&self) -> bool {
+        self.can_discard
+    }
+
+    /// Whether the player may react to someone else's action (chi, pon,
+    /// daiminkan or ron, including chankan) but is free to skip with `none`.
+    #[getter]
+    #[inline]
+    #[must_use]
+    pub const fn is_optional_call(&self) -> bool {
+        !self.can_discard && self.can_act()
+    }
+
+    /// `"tsumo"` or `"ron"` if the player can agari now, `None` otherwise.
+    #[getter]
+    #[must_use]
+    pub const fn agari_type(&self) -> Option<&'static str> {
+        if self.can_tsumo_agari {
+            Some("tsumo")
+        } else if self.can_ron_agari {
+            Some("ron")
+        } else {
+            None
+        }
+    }
+
+    /// Returns every flag, including the derived ones, along with
+    /// `target_actor` as a `dict`.
+    #[cfg(feature = "python")]
+    #[pyo3(text_signature = "($self, /)")]
+    #[allow(clippy::wrong_self_convention)]
+    fn to_dict<'py>(&self, py: Python<'py>) -> PyResult<&'py PyDict> {
+        let dict = PyDict::new(py);
+        for (key, flag) in [
+            ("can_discard", self.can_discard),
+            ("can_chi_low", self.can_chi_low),
+            ("can_chi_mid", self.can_chi_mid),
+            ("can_chi_high", self.can_chi_high),
+            ("can_pon", self.can_pon),
+            ("can_daiminkan", self.can_daiminkan),
+            ("can_kakan", self.can_kakan),
+            ("can_ankan", self.can_ankan),
+            ("can_riichi", self.can_riichi),
+            ("can_tsumo_agari", self.can_tsumo_agari),
+            ("can_ron_agari", self.can_ron_agari),
+            ("can_ryukyoku", self.can_ryukyoku),
+            ("can_chi", self.can_chi()),
+            ("can_act", self.can_act()),
+            ("is_forced", self.is_forced()),
+            ("is_optional_call", self.is_optional_call()),
+        ] {
+            dict.set_item(key, flag)?;
+        }
+        dict.set_item("target_actor", self.target_actor)?;
+        dict.set_item("agari_type", self.agari_type())?;
+        Ok(dict)
+    }
+
     fn __repr__(&self) -> String {
         format!("{self:?}")
     }
 }
 
+/// The reason why a reaction is invalid, see `PlayerState::validate_reaction`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InvalidReaction {
+    /// The action is not a reaction at all, such as `tsumo`.
+    NotAReaction,
+    /// The actor of the action is not the player.
+    NotYourTurn {
+        actor: u8,
+    },
+    /// The action is not among the current action candidates.
+    Unavailable {
+        action: &'static str,
+    },
+    TileNotInHand {
+        tile: Tile,
+    },
+    /// `tsumogiri` is set, but the tile is not the last tsumo.
+    NotTsumogiri {
+        tile: Tile,
+    },
+    /// The called tile is not the last discard.
+    NotLastDiscard {
+        tile: Tile,
+    },
+    /// Chi from a seat other than kamicha.
+    ChiFromNonKamicha {
+        target: u8,
+    },
+    /// Ron on a winning tile while in furiten.
+    WouldBeFuriten,
+    /// Discarding a tile that is forbidden right after a chi or pon.
+    KuikaeForbidden {
+        tile: Tile,
+    },
+    /// Discarding a tile that is not allowed under riichi, which is anything
+    /// but the tsumo after the riichi is accepted, or anything that breaks
+    /// tenpai on the riichi declaration.
+    RiichiDiscard {
+        tile: Tile,
+    },
+    NotKanCandidate {
+        tile: Tile,
+    },
+}
+
+// pyo3's `create_exception` checks a cfg that is unknown to newer rustc.
+#[cfg(feature = "python")]
+#[allow(unexpected_cfgs)]
+mod exceptions {
+    use pyo3::create_exception;
+    use pyo3::exceptions::PyValueError;
+
+    create_exception!(state, InvalidReactionError, PyValueError);
+    create_exception!(state, NotYourTurnError, InvalidReactionError);
+    create_exception!(state, ActionUnavailableError, InvalidReactionError);
+    create_exception!(state, TileNotInHandError, InvalidReactionError);
+    create_exception!(state, FuritenError, InvalidReactionError);
+    create_exception!(state, KuikaeError, InvalidReactionError);
+}
+
+#[cfg(feature = "python")]
+pub use exceptions::{
+    ActionUnavailableError, FuritenError, InvalidReactionError, KuikaeError, NotYourTurnError,
+    TileNotInHandError,
+};
+
+impl fmt::Display for InvalidReaction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotAReaction => write!(f, "action is not a reaction"),
+            Self::NotYourTurn { actor } => write!(f, "actor is {actor}, not self"),
+            Self::Unavailable { action } => write!(f, "cannot {action}"),
+            Self::TileNotInHand { tile } => write!(f, "{tile} is not in hand"),
+            Self::NotTsumogiri { tile } => write!(f, "cannot tsumogiri {tile}"),
+            Self::NotLastDiscard { tile } => write!(f, "{tile} is not the last kawa tile"),
+            Self::ChiFromNonKamicha { target } => write!(f, "chi from non-kamicha {target}"),
+            Self::WouldBeFuriten => write!(f, "cannot ron agari in furiten"),
+            Self::KuikaeForbidden { tile } => write!(f, "cannot discard {tile} due to kuikae"),
+            Self::RiichiDiscard { tile } => write!(f, "cannot discard {tile} under riichi"),
+            Self::NotKanCandidate { tile } => write!(f, "cannot kan {tile}"),
+        }
+    }
+}
+
+impl Error for InvalidReaction {}
+
+impl InvalidReaction {
+    /// Whether the reaction would be a chombo if it were played on the table,
+    /// which is a false hora, an illegal call, riichi or kan, or a discard
+    /// forbidden by kuikae or riichi, as opposed to a malformed reaction or
+    /// one out of turn.
+    #[must_use]
+    pub fn is_chombo(&self) -> bool {
+        match self {
+            Self::Unavailable { action } => !matches!(*action, "discard" | "ryukyoku"),
+            Self::WouldBeFuriten
+            | Self::KuikaeForbidden { .. }
+            | Self::RiichiDiscard { .. }
+            | Self::NotKanCandidate { .. } => true,
+            _ => false,
+        }
+    }
+}
+
+#[cfg(feature = "python")]
+impl From<InvalidReaction> for PyErr {
+    fn from(err: InvalidReaction) -> Self {
+        let msg = err.to_string();
+        match err {
+            InvalidReaction::NotYourTurn { .. } => NotYourTurnError::new_err(msg),
+            InvalidReaction::Unavailable { .. } => ActionUnavailableError::new_err(msg),
+            InvalidReaction::TileNotInHand { .. } => TileNotInHandError::new_err(msg),
+            InvalidReaction::WouldBeFuriten => FuritenError::new_err(msg),
+            InvalidReaction::KuikaeForbidden { .. } => KuikaeError::new_err(msg),
+            _ => InvalidReactionError::new_err(msg),
+        }
+    }
+}
+
 impl PlayerState {
     /// Check if `action` is a valid reaction to the current state.
-    pub fn validate_reaction(&self, action: &Event) -> Result<()> {
+    pub fn validate_reaction(&self, action: &Event) -> Result<(), InvalidReaction> {
         let cans = self.last_cans;
+        let unavailable = |action| Err(InvalidReaction::Unavailable { action });
 
         match action {
             Event::Ryukyoku { .. } => {
-                ensure!(cans.can_ryukyoku, "cannot ryukyoku");
+                if !cans.can_ryukyoku {
+                    return unavailable("ryukyoku");
+                }
                 return Ok(());
             }
             Event::None => {
@@ -86,31 +276,49 @@ impl PlayerState {
             _ => (),
         };
 
-        if let Some(actor) = action.actor() {
-            ensure!(
-                actor == self.player_id,
-                "actor is {actor}, not self ({})",
-                self.player_id,
-            );
-        } else {
-            bail!("action does not have actor and is not ryukyoku");
+        match action.actor() {
+            Some(actor) if actor != self.player_id => {
+                return Err(InvalidReaction::NotYourTurn { actor });
+            }
+            Some(_) => (),
+            None => return Err(InvalidReaction::NotAReaction),
         }
 
         match *action {
             Event::Dahai { pai, tsumogiri, .. } => {
-                ensure!(cans.can_discard, "cannot discard");
+                if !cans.can_discard {
+                    return unavailable("discard");
+                }
                 self.ensure_tiles_in_hand(&[pai])?;
-                if tsumogiri {
-                    if let Some(tile) = self.last_self_tsumo {
-                        ensure!(tile == pai, "cannot tsumogiri");
+                if tsumogiri && self.last_self_tsumo != Some(pai) {
+                    return Err(InvalidReaction::NotTsumogiri { tile: pai });
+                }
+                let tid = pai.deaka().as_usize();
+                if self.riichi_accepted[0] && self.last_self_tsumo != Some(pai) {
+                    return Err(InvalidReaction::RiichiDiscard { tile: pai });
+                }
+                if self.riichi_declared[0] && !self.riichi_accepted[0] {
+                    let allowed = if self.shanten == 1 {
+                        self.next_shanten_discards[tid]
                     } else {
-                        bail!("tsumogiri but the player has not dealed any tile yet");
+                        self.keep_shanten_discards[tid]
+                    };
+                    if !allowed {
+                        return Err(InvalidReaction::RiichiDiscard { tile: pai });
                     }
                 }
+                if self.forbidden_tiles[tid] {
+                    return Err(InvalidReaction::KuikaeForbidden { tile: pai });
+                }
             }
 
-            Event::Reach { .. } => {
-                ensure!(cans.can_riichi, "cannot riichi");
+            Event::Reach { open, .. } => {
+                if !cans.can_riichi {
+                    return unavailable("riichi");
+                }
+                if open && !self.rule.open_riichi {
+                    return unavailable("open riichi");
+                }
             }
 
             Event::Chi {
@@ -119,92 +327,117 @@ impl PlayerState {
                 pai,
                 consumed,
             } => {
-                ensure!((target + 1) % 4 == actor, "chi from non-kamicha");
-                ensure!(
-                    matches!(self.last_kawa_tile, Some(tile) if tile == pai),
-                    "chi target is not the last kawa tile",
-                );
+                if (target + 1) % 4 != actor {
+                    return Err(InvalidReaction::ChiFromNonKamicha { target });
+                }
+                self.ensure_last_kawa_tile(pai)?;
                 self.ensure_tiles_in_hand(&consumed)?;
 
-                match ChiType::new(consumed, pai) {
-                    ChiType::Low => ensure!(cans.can_chi_low, "cannot chi low"),
-                    ChiType::Mid => ensure!(cans.can_chi_mid, "cannot chi mid"),
-                    ChiType::High => ensure!(cans.can_chi_high, "cannot chi high"),
+                let ok = match ChiType::new(consumed, pai) {
+                    ChiType::Low => cans.can_chi_low,
+                    ChiType::Mid => cans.can_chi_mid,
+                    ChiType::High => cans.can_chi_high,
+                };
+                if !ok {
+                    return unavailable("chi");
                 }
             }
-            Event::Pon {
-                actor,
-                target,
-                pai,
-                consumed,
-            } => {
-                ensure!(target != actor, "pon from itself");
-                ensure!(
-                    matches!(self.last_kawa_tile, Some(tile) if tile == pai),
-                    "pon target is not the last kawa tile",
-                );
-                ensure!(cans.can_pon, "cannot pon");
+            Event::Pon { pai, consumed, .. } => {
+                self.ensure_last_kawa_tile(pai)?;
+                if !cans.can_pon {
+                    return unavailable("pon");
+                }
                 self.ensure_tiles_in_hand(&consumed)?;
             }
 
-            Event::Daiminkan {
-                actor,
-                target,
-                pai,
-                consumed,
-            } => {
-                ensure!(target != actor, "daiminkan from itself");
-                ensure!(
-                    matches!(self.last_kawa_tile, Some(tile) if tile == pai),
-                    "daiminkan target is not the last kawa tile",
-                );
-                ensure!(cans.can_daiminkan, "cannot daiminkan");
+            Event::Daiminkan { pai, consumed, .. } => {
+                self.ensure_last_kawa_tile(pai)?;
+                if !cans.can_daiminkan {
+                    return unavailable("daiminkan");
+                }
                 self.ensure_tiles_in_hand(&consumed)?;
             }
             Event::Kakan { pai, .. } => {
-                ensure!(cans.can_kakan, "cannot kakan");
-                ensure!(
-                    self.kakan_candidates.contains(&pai.deaka()),
-                    "cannot kakan {pai}",
-                );
+                if !cans.can_kakan {
+                    return unavailable("kakan");
+                }
+                if !self.kakan_candidates.contains(&pai.deaka()) {
+                    return Err(InvalidReaction::NotKanCandidate { tile: pai });
+                }
                 self.ensure_tiles_in_hand(&[pai])?;
             }
             Event::Ankan { consumed, .. } => {
-                ensure!(cans.can_ankan, "cannot ankan");
+                if !cans.can_ankan {
+                    return unavailable("ankan");
+                }
                 let tile = consumed[0].deaka();
-                ensure!(self.ankan_candidates.contains(&tile), "cannot ankan {tile}");
+                if !self.ankan_candidates.contains(&tile) {
+                    return Err(InvalidReaction::NotKanCandidate { tile });
+                }
                 self.ensure_tiles_in_hand(&consumed)?;
             }
 
             Event::Hora { target, .. } => {
                 if target == self.player_id {
-                    ensure!(cans.can_tsumo_agari, "cannot tsumo agari");
-                } else {
-                    ensure!(cans.can_ron_agari, "cannot ron agari");
+                    if !cans.can_tsumo_agari {
+                        return unavailable("tsumo agari");
+                    }
+                } else if !cans.can_ron_agari {
+                    let winning_tile = self.chankan_chance.or(self.last_kawa_tile);
+                    let on_wait = matches!(
+                        winning_tile,
+                        Some(tile) if self.waits[tile.deaka().as_usize()],
+                    );
+                    if self.at_furiten && on_wait {
+                        return Err(InvalidReaction::WouldBeFuriten);
+                    }
+                    return unavailable("ron agari");
                 }
             }
 
-            Event::None => return Ok(()),
-
-            _ => bail!("unexpected action {:?}", action),
+            _ => return Err(InvalidReaction::NotAReaction),
         };
 
         Ok(())
     }
 
-    fn ensure_tiles_in_hand(&self, tiles: &[Tile]) -> Result<()> {
+    /// Maps a reaction to its action ID in the action space of Mortal, or
+    /// `None` if it is not a reaction at all.
+    #[must_use]
+    pub fn action_id_of(&self, action: &Event) -> Option<usize> {
+        let id = match *action {
+            Event::Dahai { pai, .. } => pai.as_usize(),
+            Event::Reach { .. } => 37,
+            Event::Chi { pai, consumed, .. } => match ChiType::new(consumed, pai) {
+                ChiType::Low => 38,
+                ChiType::Mid => 39,
+                ChiType::High => 40,
+            },
+            Event::Pon { .. } => 41,
+            Event::Daiminkan { .. } | Event::Kakan { .. } | Event::Ankan { .. } => 42,
+            Event::Hora { .. } => 43,
+            Event::Ryukyoku { .. } if self.last_cans.can_ryukyoku => 44,
+            Event::None => 45,
+            _ => return None,
+        };
+        Some(id)
+    }
+
+    fn ensure_tiles_in_hand(&self, tiles: &[Tile]) -> Result<(), InvalidReaction> {
         for &tile in tiles {
-            ensure!(
-                self.tehai[tile.deaka().as_usize()] > 0,
-                "{tile} is not in hand",
-            );
-            if tile.is_aka() {
-                ensure!(
-                    self.akas_in_hand[tile.as_usize() - tuz!(5mr)],
-                    "{tile} is not in hand",
-                );
+            let in_hand = self.tehai[tile.deaka().as_usize()] > 0
+                && (!tile.is_aka() || self.akas_in_hand[tile.as_usize() - tuz!(5mr)] > 0);
+            if !in_hand {
+                return Err(InvalidReaction::TileNotInHand { tile });
             }
         }
         Ok(())
     }
+
+    fn ensure_last_kawa_tile(&self, tile: Tile) -> Result<(), InvalidReaction> {
+        if self.last_kawa_tile != Some(tile) {
+            return Err(InvalidReaction::NotLastDiscard { tile });
+        }
+        Ok(())
+    }
 }
@@ -1,10 +1,12 @@
 use super::PlayerState;
-use crate::algo::agari::AgariCalculator;
+use crate::algo::agari::{Agari, AgariCalculator, Decomposition};
 use crate::algo::point::Point;
 use crate::algo::shanten;
+use crate::mjai::Event;
 use crate::tile::Tile;
 use crate::vec_ops::vec_add_assign;
 use crate::{must_tile, t, tuz};
+use std::array;
 
 use anyhow::{ensure, Context, Result};
 use tinyvec::array_vec;
@@ -60,22 +62,43 @@ impl PlayerState {
             };
         }
 
-        if ret[tuz!(5m)] && self.akas_in_hand[0] {
+        if ret[tuz!(5m)] && self.akas_in_hand[0] > 0 {
             ret[tuz!(5mr)] = true;
-            ret[tuz!(5m)] = self.tehai[tuz!(5m)] > 1;
+            ret[tuz!(5m)] = self.tehai[tuz!(5m)] > self.akas_in_hand[0];
         }
-        if ret[tuz!(5p)] && self.akas_in_hand[1] {
+        if ret[tuz!(5p)] && self.akas_in_hand[1] > 0 {
             ret[tuz!(5pr)] = true;
-            ret[tuz!(5p)] = self.tehai[tuz!(5p)] > 1;
+            ret[tuz!(5p)] = self.tehai[tuz!(5p)] > self.akas_in_hand[1];
         }
-        if ret[tuz!(5s)] && self.akas_in_hand[2] {
+        if ret[tuz!(5s)] && self.akas_in_hand[2] > 0 {
             ret[tuz!(5sr)] = true;
-            ret[tuz!(5s)] = self.tehai[tuz!(5s)] > 1;
+            ret[tuz!(5s)] = self.tehai[tuz!(5s)] > self.akas_in_hand[2];
         }
 
         ret
     }
 
+    /// Returns the tiles that can be discarded along with a riichi
+    /// declaration, i.e. the discards that keep tenpai, aka doras
+    /// distinguished. Empty if riichi cannot be declared now.
+    #[must_use]
+    pub fn riichi_candidates(&self) -> Vec<Tile> {
+        if !self.last_cans.can_riichi {
+            return vec![];
+        }
+        self.discard_candidates_aka()
+            .iter()
+            .enumerate()
+            .filter(|(_, &b)| b)
+            .map(|(tid, _)| must_tile!(tid))
+            .filter(|tile| {
+                let mut tehai = self.tehai;
+                tehai[tile.deaka().as_usize()] -= 1;
+                shanten::calc_all(&tehai, self.tehai_len_div3) == 0
+            })
+            .collect()
+    }
+
     /// Must be called at 3n+2.
     ///
     /// The return value indicates the tiles which can make the hand tenpai for
@@ -173,27 +196,196 @@ impl PlayerState {
                         jikaze: self.jikaze.as_u8(),
                         winning_tile: tsumo as u8,
                         is_ron: true,
+                        kuitan: self.rule.kuitan,
                     };
                     ret[discard] = agari_calc.has_yaku();
                 }
             });
 
-        if ret[tuz!(5m)] && self.akas_in_hand[0] {
+        if ret[tuz!(5m)] && self.akas_in_hand[0] > 0 {
             ret[tuz!(5mr)] = true;
-            ret[tuz!(5m)] = self.tehai[tuz!(5m)] > 1;
+            ret[tuz!(5m)] = self.tehai[tuz!(5m)] > self.akas_in_hand[0];
         }
-        if ret[tuz!(5p)] && self.akas_in_hand[1] {
+        if ret[tuz!(5p)] && self.akas_in_hand[1] > 0 {
             ret[tuz!(5pr)] = true;
-            ret[tuz!(5p)] = self.tehai[tuz!(5p)] > 1;
+            ret[tuz!(5p)] = self.tehai[tuz!(5p)] > self.akas_in_hand[1];
         }
-        if ret[tuz!(5s)] && self.akas_in_hand[2] {
+        if ret[tuz!(5s)] && self.akas_in_hand[2] > 0 {
             ret[tuz!(5sr)] = true;
-            ret[tuz!(5s)] = self.tehai[tuz!(5s)] > 1;
+            ret[tuz!(5s)] = self.tehai[tuz!(5s)] > self.akas_in_hand[2];
+        }
+
+        ret
+    }
+
+    /// Returns every legal chi, pon and daiminkan on the last kawa tile, one
+    /// for each possible `consumed`, which differ only in the use of aka
+    /// doras. Choices with aka come first.
+    #[must_use]
+    pub fn call_candidates(&self) -> Vec<Event> {
+        let cans = self.last_cans;
+        let Some(pai) = self.last_kawa_tile else {
+            return vec![];
+        };
+        let actor = self.player_id;
+        let target = cans.target_actor;
+        let base = pai.deaka();
+        let mut ret = vec![];
+
+        let chis = [
+            (cans.can_chi_low, [base.next(), base.next().next()]),
+            (cans.can_chi_mid, [base.prev(), base.next()]),
+            (cans.can_chi_high, [base.prev().prev(), base.prev()]),
+        ];
+        for (_, tiles) in chis.into_iter().filter(|(can, _)| *can) {
+            ret.extend(
+                self.consumed_choices(tiles)
+                    .into_iter()
+                    .map(|consumed| Event::Chi {
+                        actor,
+                        target,
+                        pai,
+                        consumed,
+                    }),
+            );
+        }
+        if cans.can_pon {
+            ret.extend(
+                self.consumed_choices([base; 2])
+                    .into_iter()
+                    .map(|consumed| Event::Pon {
+                        actor,
+                        target,
+                        pai,
+                        consumed,
+                    }),
+            );
+        }
+        if cans.can_daiminkan {
+            ret.extend(
+                self.consumed_choices([base; 3])
+                    .into_iter()
+                    .map(|consumed| Event::Daiminkan {
+                        actor,
+                        target,
+                        pai,
+                        consumed,
+                    }),
+            );
         }
 
         ret
     }
 
+    /// Returns all the distinct reactions the player can make now, including
+    /// `none` if it can pass.
+    #[must_use]
+    pub fn reaction_candidates(&self) -> Vec<Event> {
+        let actor = self.player_id;
+        let cans = self.last_cans;
+        let mut ret = vec![];
+
+        if cans.can_discard {
+            let tsumo = self.last_self_tsumo;
+            for (tid, &flag) in self.discard_candidates_aka().iter().enumerate() {
+                if flag {
+                    let pai = must_tile!(tid);
+                    ret.push(Event::Dahai {
+                        actor,
+                        pai,
+                        tsumogiri: tsumo == Some(pai),
+                    });
+                }
+            }
+        }
+        if cans.can_riichi {
+            ret.push(Event::Reach { actor, open: false });
+        }
+        ret.extend(self.call_candidates());
+        if cans.can_ankan {
+            for &tile in self.ankan_candidates() {
+                ret.push(Event::Ankan {
+                    actor,
+                    consumed: with_akas(tile, self.akas_of(tile)),
+                });
+            }
+        }
+        if cans.can_kakan {
+            for &tile in self.kakan_candidates() {
+                // All the other copies are in the pon.
+                let in_hand = self.akas_of(tile);
+                let in_pon = self.akas_of_wall(tile).saturating_sub(in_hand);
+                let pai = if in_hand > 0 { tile.akaize() } else { tile };
+                let consumed = with_akas(tile, in_pon);
+                ret.push(Event::Kakan {
+                    actor,
+                    pai,
+                    consumed,
+                });
+            }
+        }
+        if cans.can_tsumo_agari || cans.can_ron_agari {
+            ret.push(Event::Hora {
+                actor,
+                target: cans.target_actor,
+                deltas: None,
+                ura_markers: None,
+            });
+        }
+        if cans.can_ryukyoku {
+            ret.push(Event::Ryukyoku {
+                deltas: None,
+                reason: None,
+            });
+        }
+        if !cans.can_discard {
+            ret.push(Event::None);
+        }
+
+        ret
+    }
+
+    /// `tiles` must be deaka'd and contain at most one kind of 5.
+    fn consumed_choices<const N: usize>(&self, tiles: [Tile; N]) -> Vec<[Tile; N]> {
+        let Some(five_idx) = tiles.iter().position(|&t| t.akaize() != t) else {
+            return vec![tiles];
+        };
+        let five = tiles[five_idx];
+        let need = tiles.iter().filter(|&&t| t == five).count() as u8;
+        let akas = self.akas_of(five);
+        let plain = self.tehai[five.as_usize()] - akas;
+
+        // The ones with more akas first.
+        (0..=akas.min(need))
+            .rev()
+            .filter(|&n| plain + n >= need)
+            .map(|n| {
+                let mut ret = tiles;
+                ret.iter_mut()
+                    .filter(|t| **t == five)
+                    .take(n as usize)
+                    .for_each(|t| *t = five.akaize());
+                ret
+            })
+            .collect()
+    }
+
+    /// Number of the akas of `tile` in the hand, 0 if it is not a 5.
+    fn akas_of(&self, tile: Tile) -> u8 {
+        if tile.akaize() == tile {
+            return 0;
+        }
+        self.akas_in_hand[tile.as_usize() / 9]
+    }
+
+    /// Number of the akas of `tile` in the wall, 0 if it is not a 5.
+    fn akas_of_wall(&self, tile: Tile) -> u8 {
+        if tile.akaize() == tile {
+            return 0;
+        }
+        self.rule.akas_per_suit()[tile.as_usize() / 9]
+    }
+
     #[inline]
     #[must_use]
     pub fn yaokyuu_kind_count(&self) -> u8 {
@@ -366,15 +558,71 @@ impl PlayerState {
     ///
     /// `ura_indicators` is only used when the actor has an accepted riichi.
     pub fn agari_points(&self, is_ron: bool, ura_indicators: &[Tile]) -> Result<Point> {
+        Ok(self
+            .agari(is_ron, ura_indicators)?
+            .into_point(self.oya == 0))
+    }
+
+    /// Same as `agari_points`, but returns the han and fu, or the yakuman
+    /// count, instead of the points.
+    pub fn agari(&self, is_ron: bool, ura_indicators: &[Tile]) -> Result<Agari> {
         ensure!(
             is_ron && self.last_cans.can_ron_agari || self.last_cans.can_tsumo_agari,
             "cannot agari"
         );
+        self.agari_unchecked(is_ron, ura_indicators, true)
+    }
 
+    /// Returns every reading of the hand completed by `winning_tile` together
+    /// with the melds, the highest scoring first, see
+    /// `AgariCalculator::decompositions`. `winning_tile` is added to `tehai`
+    /// at 3n+1, and must already be in it at 3n+2, i.e. after the tsumo.
+    ///
+    /// Err is returned if the hand is not agari with `winning_tile`.
+    pub fn agari_decompositions(
+        &self,
+        winning_tile: Tile,
+        is_ron: bool,
+    ) -> Result<Vec<Decomposition>> {
+        let tid = winning_tile.deaka().as_usize();
+        let mut tehai = self.tehai;
+        if tehai.iter().sum::<u8>() % 3 == 1 {
+            ensure!(tehai[tid] < 4, "{winning_tile} is already all in tehai");
+            tehai[tid] += 1;
+        } else {
+            ensure!(tehai[tid] > 0, "{winning_tile} is not in tehai");
+        }
+
+        let agari_calc = AgariCalculator {
+            tehai: &tehai,
+            is_menzen: self.is_menzen,
+            chis: &self.chis,
+            pons: &self.pons,
+            minkans: &self.minkans,
+            ankans: &self.ankans,
+            bakaze: self.bakaze.as_u8(),
+            jikaze: self.jikaze.as_u8(),
+            winning_tile: tid as u8,
+            is_ron,
+            kuitan: self.rule.kuitan,
+        };
+        let ret = agari_calc.decompositions();
+        ensure!(!ret.is_empty(), "not an agari of normal shape or chitoi");
+        Ok(ret)
+    }
+
+    /// Same as `agari`, without checking the hora candidate, and optionally
+    /// without doras.
+    pub(super) fn agari_unchecked(
+        &self,
+        is_ron: bool,
+        ura_indicators: &[Tile],
+        with_doras: bool,
+    ) -> Result<Agari> {
         // 天和, 地和 are special cases that are handled individually, and there
         // is no multi yakuman for these two.
-        if !is_ron && self.can_w_riichi {
-            return Ok(Point::yakuman(self.oya == 0, 1));
+        if !is_ron && self.can_w_riichi || is_ron && self.is_renhou_chance() {
+            return Ok(Agari::Yakuman(1));
         }
 
         let winning_tile = if is_ron {
@@ -388,6 +636,7 @@ impl PlayerState {
             [
                 self.riichi_accepted[0],       // 立直
                 self.is_w_riichi,              // 両立直
+                self.open_riichi[0],           // オープン立直
                 self.at_ippatsu,               // 一发
                 self.tiles_left == 0,          // 河底撈魚
                 self.chankan_chance.is_some(), // 槍槓
@@ -397,12 +646,13 @@ impl PlayerState {
             .count() as u8
         } else {
             [
-                self.riichi_accepted[0],                  // 立直
-                self.is_w_riichi,                         // 両立直
-                self.at_ippatsu,                          // 一发
-                self.is_menzen,                           // 門前清自摸和
-                self.tiles_left == 0 && !self.at_rinshan, // 海底摸月
-                self.at_rinshan,                          // 嶺上開花
+                self.riichi_accepted[0], // 立直
+                self.is_w_riichi,        // 両立直
+                self.open_riichi[0],     // オープン立直
+                self.at_ippatsu,         // 一发
+                self.is_menzen,          // 門前清自摸和
+                self.is_haitei_draw(),   // 海底摸月
+                self.at_rinshan,         // 嶺上開花
             ]
             .iter()
             .filter(|&&b| b)
@@ -423,7 +673,7 @@ impl PlayerState {
             final_doras_owned += ura_indicators
                 .iter()
                 .map(|&ura| {
-                    let next = ura.next();
+                    let next = Tile::dora_from_indicator(ura);
                     let mut count = tehai[next.as_usize()];
                     if self.ankan_overview[0].contains(&next) {
                         count += 4;
@@ -444,11 +694,76 @@ impl PlayerState {
             jikaze: self.jikaze.as_u8(),
             winning_tile: winning_tile.deaka().as_u8(),
             is_ron,
+            kuitan: self.rule.kuitan,
         };
-        let agari = agari_calc
+        if !with_doras {
+            final_doras_owned = 0;
+        }
+        // 大車輪
+        if self.rule.daisharin
+            && self.is_menzen
+            && self.ankans.is_empty()
+            && tehai[tuz!(1p)..=tuz!(9p)] == [0, 2, 2, 2, 2, 2, 2, 2, 0]
+        {
+            return Ok(Agari::Yakuman(1));
+        }
+        let agari = match agari_calc
             .agari(additional_hans, final_doras_owned)
-            .context("not a hora hand")?;
+            .context("not a hora hand")?
+        {
+            // 数え役満 capped at 三倍満
+            Agari::Normal { fu, han } if han >= 13 && !self.rule.kazoe_yakuman => {
+                Agari::Normal { fu, han: 12 }
+            }
+            agari => agari,
+        };
 
-        Ok(agari.into_point(self.oya == 0))
+        Ok(agari)
     }
+
+    /// Returns the point transfer of an agari in absolute seats, including
+    /// honba (300 per honba on ron, 100 from each payer on tsumo) and the
+    /// kyotaku on the table, which goes to the winner as a whole.
+    ///
+    /// `loser` is `None` for tsumo. Pao is not considered.
+    #[must_use]
+    pub fn settle(&self, agari: &Agari, winner: u8, loser: Option<u8>) -> [i32; 4] {
+        let oya = (self.oya + self.player_id) % 4;
+        let point = agari.into_point(winner == oya);
+        let honba = self.honba as i32;
+
+        let mut deltas = [0; 4];
+        if let Some(loser) = loser {
+            deltas[loser as usize] = -point.ron - honba * 300;
+            deltas[winner as usize] = point.ron + honba * 300;
+        } else {
+            for (seat, delta) in deltas.iter_mut().enumerate() {
+                let seat = seat as u8;
+                if seat == winner {
+                    continue;
+                }
+                let pay = if seat == oya {
+                    point.tsumo_oya
+                } else {
+                    point.tsumo_ko
+                };
+                *delta = -pay - honba * 100;
+            }
+            deltas[winner as usize] = point.tsumo_total(winner == oya) + honba * 300;
+        }
+        deltas[winner as usize] += self.kyotaku as i32 * 1000;
+
+        deltas
+    }
+}
+
+/// Returns `N` copies of `tile`, the first `akas` of which are akaized.
+fn with_akas<const N: usize>(tile: Tile, akas: u8) -> [Tile; N] {
+    array::from_fn(|i| {
+        if i < akas as usize {
+            tile.akaize()
+        } else {
+            tile
+        }
+    })
 }
@@ -0,0 +1,245 @@
+use super::{BaselineOpponentModel, PlayerState};
+use crate::must_tile;
+use crate::tile::Tile;
+use std::fmt;
+
+use anyhow::{ensure, Result};
+use riichi_macros::{pyclass, pymethods};
+
+/// Classification of a tile's safety against one opponent, from the safest
+/// to the most dangerous.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SafetyKind {
+    /// The opponent cannot ron on it, as it is in their kawa, or has been
+    /// discarded by anyone after their riichi, or after their last discard.
+    Genbutsu,
+    /// Every ryanmen wait on it is ruled out by genbutsu.
+    Suji,
+    /// Every ryanmen wait on it that is not ruled out by genbutsu needs a tile
+    /// of which all 4 copies are visible.
+    NoChance,
+    /// Same as `NoChance`, except that at most 1 copy is left.
+    OneChance,
+    Live,
+}
+
+/// Classification of a discard against the threats of opponents, see
+/// `PlayerState::push_fold`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PushFold {
+    /// The tile is `OneChance` or `Live` against any threat.
+    Push,
+    /// The tile is `Genbutsu` against every threat.
+    Fold,
+    /// Anything in between, i.e. `Suji` or `NoChance` against some threat.
+    Ambiguous,
+}
+
+/// The safety of a tile against one opponent.
+#[pyclass]
+#[derive(Debug, Clone, PartialEq)]
+pub struct TileDanger {
+    pub tile: Tile,
+    pub kind: SafetyKind,
+    /// A rough deal-in rate in percent, assuming the opponent is tenpai.
+    pub score: f32,
+}
+
+impl fmt::Display for SafetyKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Genbutsu => "genbutsu",
+            Self::Suji => "suji",
+            Self::NoChance => "no_chance",
+            Self::OneChance => "one_chance",
+            Self::Live => "live",
+        })
+    }
+}
+
+#[pymethods]
+impl TileDanger {
+    #[getter]
+    fn tile(&self) -> String {
+        self.tile.to_string()
+    }
+    /// One of `"genbutsu"`, `"suji"`, `"no_chance"`, `"one_chance"` and
+    /// `"live"`.
+    #[getter]
+    fn kind(&self) -> String {
+        self.kind.to_string()
+    }
+    #[getter]
+    const fn score(&self) -> f32 {
+        self.score
+    }
+
+    fn __repr__(&self) -> String {
+        format!("{self:?}")
+    }
+}
+
+#[pymethods]
+impl PlayerState {
+    /// Returns a list of 34 `TileDanger` against the opponent at `rel_seat`
+    /// (relative to `player_id`), one for each tile.
+    ///
+    /// If `opponent_model` is true, the scores are the wait probabilities
+    /// estimated by `BaselineOpponentModel` instead, see `tile_danger_with`.
+    #[pyo3(name = "tile_danger")]
+    #[pyo3(text_signature = "($self, rel_seat, /, *, opponent_model = False)")]
+    #[args("*", opponent_model = "false")]
+    fn tile_danger_py(&self, rel_seat: u8, opponent_model: bool) -> Result<Vec<TileDanger>> {
+        ensure!(
+            (1..4).contains(&rel_seat),
+            "{rel_seat} is not in range [1, 3]"
+        );
+        let danger = if opponent_model {
+            self.tile_danger_with(rel_seat, &BaselineOpponentModel)
+        } else {
+            self.tile_danger(rel_seat)
+        };
+        Ok(danger)
+    }
+}
+
+impl PlayerState {
+    /// Returns the safety of each of the 34 tiles against the opponent at
+    /// `rel_seat`, derived from the kawa, the riichi state and `tiles_seen`.
+    ///
+    /// Only ryanmen waits are considered for suji and kabe, so that kanchan,
+    /// shanpon and tanki waits are still possible on `Suji` and `NoChance`
+    /// tiles, which is reflected in their scores.
+    ///
+    /// Panics if `rel_seat` is outside of range [1, 3].
+    #[must_use]
+    pub fn tile_danger(&self, rel_seat: u8) -> Vec<TileDanger> {
+        assert!(
+            (1..4).contains(&rel_seat),
+            "{rel_seat} is not in range [1, 3]"
+        );
+        let genbutsu = self.genbutsu(rel_seat as usize);
+
+        (0..34)
+            .map(|tid| {
+                let (kind, score) = if genbutsu[tid] {
+                    (SafetyKind::Genbutsu, 0.)
+                } else if tid >= 27 {
+                    let score = match self.tiles_seen[tid] {
+                        0 => 8.,
+                        1 => 6.5,
+                        2 => 2.5,
+                        _ => 0.5,
+                    };
+                    (SafetyKind::Live, score)
+                } else {
+                    self.number_tile_danger(tid, &genbutsu)
+                };
+                TileDanger {
+                    tile: must_tile!(tid),
+                    kind,
+                    score,
+                }
+            })
+            .collect()
+    }
+
+    /// Classifies discarding `tile` as a push or a fold against the
+    /// opponents that are a threat, which are the ones that have declared
+    /// riichi or made 2 or more calls.
+    ///
+    /// Returns `None` if there is no threat, or the player has declared riichi
+    /// and thus has no choice.
+    #[must_use]
+    pub fn push_fold(&self, tile: Tile) -> Option<PushFold> {
+        if self.riichi_declared[0] {
+            return None;
+        }
+        let tid = tile.deaka().as_usize();
+        let worst = (1..4)
+            .filter(|&rel| self.riichi_declared[rel] || self.fuuro_overview[rel].len() >= 2)
+            .map(|rel| self.tile_danger(rel as u8)[tid].kind)
+            .max()?;
+        let ret = match worst {
+            SafetyKind::Genbutsu => PushFold::Fold,
+            SafetyKind::Suji | SafetyKind::NoChance => PushFold::Ambiguous,
+            SafetyKind::OneChance | SafetyKind::Live => PushFold::Push,
+        };
+        Some(ret)
+    }
+
+    fn number_tile_danger(&self, tid: usize, genbutsu: &[bool; 34]) -> (SafetyKind, f32) {
+        let num = tid % 9;
+        // Deal-in rates of musuji tiles, by the number.
+        let musuji = [6., 7.5, 8.5, 10., 12., 10., 8.5, 7.5, 6.][num];
+
+        // The tiles forming each ryanmen wait on `tid` that is not ruled out
+        // by suji.
+        let mut open_sides = vec![];
+        if num >= 3 && !genbutsu[tid - 3] {
+            open_sides.push([tid - 2, tid - 1]);
+        }
+        if num <= 5 && !genbutsu[tid + 3] {
+            open_sides.push([tid + 1, tid + 2]);
+        }
+        if open_sides.is_empty() {
+            let score = match num {
+                0 | 8 => 1.5,
+                1 | 7 => 2.5,
+                2 | 6 => 4.,
+                // Nakasuji
+                _ => 3.5,
+            };
+            return (SafetyKind::Suji, score);
+        }
+
+        let chance = open_sides
+            .iter()
+            .map(|side| {
+                side.iter()
+                    .map(|&t| 4 - self.tiles_seen[t])
+                    .min()
+                    .unwrap_or_default()
+            })
+            .max()
+            .unwrap_or_default();
+        match chance {
+            0 => (SafetyKind::NoChance, 2.),
+            1 => (SafetyKind::OneChance, musuji / 2.),
+            // Katasuji
+            _ if (3..=5).contains(&num) && open_sides.len() == 1 => (SafetyKind::Live, 6.),
+            _ => (SafetyKind::Live, musuji),
+        }
+    }
+
+    /// Tiles the opponent at `rel_seat` cannot ron on due to furiten, which
+    /// are the ones in their kawa, plus the ones discarded by anyone after
+    /// their riichi, or after their last discard if they have not declared
+    /// riichi.
+    fn genbutsu(&self, rel_seat: usize) -> [bool; 34] {
+        let mut ret = [false; 34];
+        for tile in &self.kawa_overview[rel_seat] {
+            ret[tile.deaka().as_usize()] = true;
+        }
+
+        // `kawa` is padded, so that the items of the same index are in the
+        // same go-around, in the order of the relative seats.
+        let kawa = &self.kawa[rel_seat];
+        let since = if self.riichi_declared[rel_seat] {
+            kawa.iter()
+                .position(|item| matches!(item, Some(item) if item.sutehai.is_riichi))
+        } else {
+            kawa.iter().rposition(Option::is_some)
+        };
+        if let Some(since) = since {
+            for (seat, kawa) in self.kawa.iter().enumerate() {
+                let start = if seat > rel_seat { since } else { since + 1 };
+                for item in kawa.iter().skip(start).flatten() {
+                    ret[item.sutehai.tile.deaka().as_usize()] = true;
+                }
+            }
+        }
+
+        ret
+    }
+}
@@ -0,0 +1,110 @@
+use super::PlayerState;
+use crate::algo::shanten;
+use crate::must_tile;
+use crate::tile::Tile;
+use std::fmt;
+
+use riichi_macros::{pyclass, pymethods};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FuritenKind {
+    /// One of the waits is in the player's own kawa. It lasts as long as the
+    /// wait does.
+    Discard,
+    /// A wait discarded by others was passed on, or could not be ronned due
+    /// to yakunashi. It lasts until the player's next discard.
+    SameCycle,
+    /// A wait was passed on after riichi. It lasts until the end of the
+    /// kyoku.
+    Riichi,
+}
+
+/// The active furiten and the tile that caused it.
+#[pyclass]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FuritenInfo {
+    pub kind: FuritenKind,
+    /// The wait in the kawa for `Discard`, otherwise the wait passed on.
+    pub tile: Tile,
+}
+
+impl fmt::Display for FuritenKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Discard => "discard",
+            Self::SameCycle => "same_cycle",
+            Self::Riichi => "riichi",
+        })
+    }
+}
+
+#[pymethods]
+impl FuritenInfo {
+    /// One of `"discard"`, `"same_cycle"` and `"riichi"`.
+    #[getter]
+    fn kind(&self) -> String {
+        self.kind.to_string()
+    }
+    #[getter]
+    fn tile(&self) -> String {
+        self.tile.to_string()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("{self:?}")
+    }
+}
+
+#[pymethods]
+impl PlayerState {
+    /// Returns a `FuritenInfo`, or `None` if not at furiten.
+    #[pyo3(name = "furiten_info")]
+    #[pyo3(text_signature = "($self, /)")]
+    fn furiten_info_py(&self) -> Option<FuritenInfo> {
+        self.furiten_info()
+    }
+}
+
+impl PlayerState {
+    /// Tells why `at_furiten` holds, or returns `None` if it does not.
+    ///
+    /// Discard furiten takes precedence over the others. A same-cycle
+    /// furiten that is pending, i.e. right after a ron chance is given, is
+    /// not reported until the next event, same as `at_furiten`.
+    #[must_use]
+    pub fn furiten_info(&self) -> Option<FuritenInfo> {
+        if !self.at_furiten {
+            return None;
+        }
+
+        // The waits are of the 3n+1 hand before the tsumo.
+        let mut tehai = self.tehai;
+        if tehai.iter().sum::<u8>() % 3 == 2 {
+            if let Some(tsumo) = self.last_self_tsumo {
+                tehai[tsumo.deaka().as_usize()] -= 1;
+            }
+        }
+        let is_3n1 = tehai.iter().sum::<u8>() % 3 == 1;
+        let discarded_wait = (0..34).filter(|_| is_3n1).find(|&tid| {
+            if !self.discarded_tiles[tid] || tehai[tid] == 4 {
+                return false;
+            }
+            let mut tehai_after = tehai;
+            tehai_after[tid] += 1;
+            shanten::calc_all(&tehai_after, self.tehai_len_div3) == -1
+        });
+        if let Some(tid) = discarded_wait {
+            return Some(FuritenInfo {
+                kind: FuritenKind::Discard,
+                tile: must_tile!(tid),
+            });
+        }
+
+        let kind = if self.riichi_accepted[0] {
+            FuritenKind::Riichi
+        } else {
+            FuritenKind::SameCycle
+        };
+        self.missed_wait.map(|tile| FuritenInfo { kind, tile })
+    }
+}
@@ -1,5 +1,129 @@
+use super::item::{KawaMark, Sutehai};
 use super::{ActionCandidate, PlayerState};
+use crate::rule::RuleSet;
 use crate::tile::Tile;
+use crate::tu8;
+
+use riichi_macros::pymethods;
+use tinyvec::ArrayVec;
+
+#[pymethods]
+impl PlayerState {
+    /// Number of the visible copies of each tile, in the 34-D tile ID space.
+    #[getter(tiles_seen)]
+    const fn tiles_seen_py(&self) -> [u8; 34] {
+        self.tiles_seen
+    }
+    /// Number of the unseen copies of each tile, in the 37-D tile ID space
+    /// where aka doras are counted separately from the normal 5s.
+    #[getter(tiles_remaining)]
+    fn tiles_remaining_py(&self) -> [u8; 37] {
+        self.tiles_remaining()
+    }
+    /// The `ActionCandidate` returned by the last `update`.
+    #[getter(last_cans)]
+    const fn last_cans_py(&self) -> ActionCandidate {
+        self.last_cans
+    }
+    #[getter(doras_seen)]
+    const fn doras_seen_py(&self) -> u8 {
+        self.doras_seen
+    }
+    #[getter(tiles_left)]
+    const fn tiles_left_py(&self) -> u8 {
+        self.tiles_left
+    }
+    #[getter(rinshan_draws_left)]
+    const fn rinshan_draws_left_py(&self) -> u8 {
+        self.rinshan_draws_left()
+    }
+    #[getter(pending_kan_doras)]
+    fn pending_kan_doras_py(&self) -> u8 {
+        self.pending_kan_doras()
+    }
+    #[getter(is_haitei_chance)]
+    const fn is_haitei_chance_py(&self) -> bool {
+        self.is_haitei_chance()
+    }
+    #[getter(is_houtei_chance)]
+    const fn is_houtei_chance_py(&self) -> bool {
+        self.is_houtei_chance()
+    }
+    #[getter(nagashi_mangan)]
+    const fn nagashi_mangan_py(&self) -> [bool; 4] {
+        self.nagashi_mangan()
+    }
+
+    /// Relative to `player_id`, e.g. `scores[0]` is the player's own score.
+    #[getter(scores)]
+    const fn scores_py(&self) -> [i32; 4] {
+        self.scores
+    }
+    #[getter(dora_indicators)]
+    fn dora_indicators_py(&self) -> Vec<String> {
+        self.dora_indicators.iter().map(|t| t.to_string()).collect()
+    }
+    /// Relative to `player_id`.
+    #[getter(riichi_declared)]
+    const fn riichi_declared_py(&self) -> [bool; 4] {
+        self.riichi_declared
+    }
+    /// Relative to `player_id`.
+    #[getter(open_riichi)]
+    const fn open_riichi_py(&self) -> [bool; 4] {
+        self.open_riichi
+    }
+    /// The discards of each seat relative to `player_id`, in order.
+    #[getter(kawa_overview)]
+    fn kawa_overview_py(&self) -> [Vec<String>; 4] {
+        self.kawa_overview
+            .each_ref()
+            .map(|kawa| kawa.iter().map(|t| t.to_string()).collect())
+    }
+    /// The discards of each seat relative to `player_id`, in order, as
+    /// `Sutehai`s with the metadata of each.
+    #[getter(kawa)]
+    fn kawa_py(&self) -> Vec<Vec<Sutehai>> {
+        self.kawa
+            .iter()
+            .map(|kawa| kawa.iter().flatten().map(|item| item.sutehai).collect())
+            .collect()
+    }
+    /// The `KawaMark` of the tile each seat relative to `player_id` declared
+    /// riichi with, or `None` if it has not declared riichi.
+    #[getter(riichi_tiles)]
+    fn riichi_tiles_py(&self) -> Vec<Option<KawaMark>> {
+        self.riichi_tiles.to_vec()
+    }
+    /// The `KawaMark`s of the tiles each seat relative to `player_id` called
+    /// by chi, pon and daiminkan, in order.
+    #[getter(called_tiles)]
+    fn called_tiles_py(&self) -> Vec<Vec<KawaMark>> {
+        self.called_tiles
+            .iter()
+            .map(|calls| calls.to_vec())
+            .collect()
+    }
+    /// The chis, pons, daiminkans and kakans of each seat relative to
+    /// `player_id`, each as a list of its tiles.
+    #[getter(fuuro_overview)]
+    fn fuuro_overview_py(&self) -> [Vec<Vec<String>>; 4] {
+        self.fuuro_overview.each_ref().map(|fuuros| {
+            fuuros
+                .iter()
+                .map(|f| f.iter().map(|t| t.to_string()).collect())
+                .collect()
+        })
+    }
+    /// The ankans of each seat relative to `player_id`, each as its deaka'd
+    /// tile.
+    #[getter(ankan_overview)]
+    fn ankan_overview_py(&self) -> [Vec<String>; 4] {
+        self.ankan_overview
+            .each_ref()
+            .map(|ankans| ankans.iter().map(|t| t.to_string()).collect())
+    }
+}
 
 impl PlayerState {
     #[inline]
@@ -19,7 +143,7 @@ impl PlayerState {
     }
     #[inline]
     #[must_use]
-    pub const fn akas_in_hand(&self) -> [bool; 3] {
+    pub const fn akas_in_hand(&self) -> [u8; 3] {
         self.akas_in_hand
     }
 
@@ -44,6 +168,45 @@ impl PlayerState {
         &self.ankans
     }
 
+    #[inline]
+    #[must_use]
+    pub const fn bakaze(&self) -> Tile {
+        self.bakaze
+    }
+    /// Counts from 1, same as mjai.
+    #[inline]
+    #[must_use]
+    pub const fn kyoku(&self) -> u8 {
+        self.kyoku + 1
+    }
+    #[inline]
+    #[must_use]
+    pub const fn honba(&self) -> u8 {
+        self.honba
+    }
+    #[inline]
+    #[must_use]
+    pub const fn kyotaku(&self) -> u8 {
+        self.kyotaku
+    }
+    /// Relative to `player_id`.
+    #[inline]
+    #[must_use]
+    pub const fn scores(&self) -> [i32; 4] {
+        self.scores
+    }
+    /// Relative to `player_id`.
+    #[inline]
+    #[must_use]
+    pub const fn oya(&self) -> u8 {
+        self.oya
+    }
+    #[inline]
+    #[must_use]
+    pub fn dora_indicators(&self) -> &[Tile] {
+        &self.dora_indicators
+    }
+
     #[inline]
     #[must_use]
     pub const fn at_turn(&self) -> u8 {
@@ -77,6 +240,27 @@ impl PlayerState {
     }
     #[inline]
     #[must_use]
+    pub const fn is_all_last(&self) -> bool {
+        self.is_all_last
+    }
+    #[inline]
+    #[must_use]
+    pub const fn rule(&self) -> RuleSet {
+        self.rule
+    }
+    /// Whether each seat, relative to `player_id`, still qualifies for
+    /// nagashi mangan. All `false` if the rule does not have it.
+    #[inline]
+    #[must_use]
+    pub const fn nagashi_mangan(&self) -> [bool; 4] {
+        if self.rule.nagashi_mangan {
+            self.nagashi_mangan
+        } else {
+            [false; 4]
+        }
+    }
+    #[inline]
+    #[must_use]
     pub fn ankan_candidates(&self) -> &[Tile] {
         &self.ankan_candidates
     }
@@ -102,9 +286,187 @@ impl PlayerState {
         self.riichi_accepted[0]
     }
 
+    /// Relative to `player_id`.
+    #[inline]
+    #[must_use]
+    pub const fn riichi_declared(&self) -> [bool; 4] {
+        self.riichi_declared
+    }
+    /// Relative to `player_id`.
+    #[inline]
+    #[must_use]
+    pub const fn riichi_accepted(&self) -> [bool; 4] {
+        self.riichi_accepted
+    }
+    /// Relative to `player_id`.
+    #[inline]
+    #[must_use]
+    pub const fn open_riichi(&self) -> [bool; 4] {
+        self.open_riichi
+    }
+
+    /// Relative to `player_id`.
+    #[inline]
+    #[must_use]
+    pub fn kawa_overview(&self) -> &[ArrayVec<[Tile; 24]>; 4] {
+        &self.kawa_overview
+    }
+    /// The discards of `rel_seat` in order, which is relative to
+    /// `player_id`.
+    pub fn sutehais(&self, rel_seat: u8) -> impl Iterator<Item = &Sutehai> {
+        self.kawa[rel_seat as usize]
+            .iter()
+            .flatten()
+            .map(|item| &item.sutehai)
+    }
+    /// Relative to `player_id`.
+    #[inline]
+    #[must_use]
+    pub const fn riichi_tiles(&self) -> [Option<KawaMark>; 4] {
+        self.riichi_tiles
+    }
+    /// Relative to `player_id`, in order.
+    #[inline]
+    #[must_use]
+    pub const fn called_tiles(&self) -> &[ArrayVec<[KawaMark; 4]>; 4] {
+        &self.called_tiles
+    }
+    /// Relative to `player_id`, not including ankans.
+    #[inline]
+    #[must_use]
+    pub const fn fuuro_overview(&self) -> &[ArrayVec<[ArrayVec<[Tile; 4]>; 4]>; 4] {
+        &self.fuuro_overview
+    }
+    /// Relative to `player_id`, all deaka'd.
+    #[inline]
+    #[must_use]
+    pub const fn ankan_overview(&self) -> &[ArrayVec<[Tile; 4]>; 4] {
+        &self.ankan_overview
+    }
+    /// Including the tiles in the player's own hand.
+    #[inline]
+    #[must_use]
+    pub const fn tiles_seen(&self) -> [u8; 34] {
+        self.tiles_seen
+    }
+
+    /// Number of tiles left in the live wall.
+    #[inline]
+    #[must_use]
+    pub const fn tiles_left(&self) -> u8 {
+        self.tiles_left
+    }
+
+    /// Including the ones in the player's own hand.
+    #[inline]
+    #[must_use]
+    pub const fn akas_seen(&self) -> [u8; 3] {
+        self.akas_seen
+    }
+    #[inline]
+    #[must_use]
+    pub const fn doras_seen(&self) -> u8 {
+        self.doras_seen
+    }
+    /// Returns the number of copies of each tile that have not been seen by
+    /// the player, in the 37-D tile ID space where aka doras are counted
+    /// separately from the normal 5s.
+    #[must_use]
+    pub fn tiles_remaining(&self) -> [u8; 37] {
+        let mut ret = [0; 37];
+        for (tid, r) in ret.iter_mut().take(34).enumerate() {
+            *r = 4 - self.tiles_seen[tid];
+        }
+        for (i, (&total, &seen)) in self
+            .rule
+            .akas_per_suit()
+            .iter()
+            .zip(&self.akas_seen)
+            .enumerate()
+        {
+            let n = total.saturating_sub(seen);
+            ret[34 + i] = n;
+            // The akas are some of the copies of the 5.
+            ret[tu8!(5m) as usize + 9 * i] -= n;
+        }
+        ret
+    }
+
     #[inline]
     #[must_use]
     pub const fn at_furiten(&self) -> bool {
         self.at_furiten
     }
+
+    #[inline]
+    #[must_use]
+    pub const fn at_ippatsu(&self) -> bool {
+        self.at_ippatsu
+    }
+    /// Returns whether the event just processed by `update` cancelled the
+    /// player's outstanding ippatsu, for example by a call from any seat.
+    /// Ippatsu ending with the player's own next discard is not counted.
+    #[inline]
+    #[must_use]
+    pub const fn event_broke_ippatsu(&self) -> bool {
+        self.ippatsu_broken
+    }
+
+    /// Returns the tile of the kakan that was just declared, if the player
+    /// can rob it by chankan.
+    #[inline]
+    #[must_use]
+    pub const fn chankan_tile(&self) -> Option<Tile> {
+        self.chankan_chance
+    }
+
+    /// Returns whether the player's last tsumo is haitei, i.e. the last tile
+    /// of the live wall which is not a rinshan draw.
+    #[inline]
+    #[must_use]
+    pub const fn is_haitei_draw(&self) -> bool {
+        self.tiles_left == 0 && !self.at_rinshan
+    }
+
+    /// Number of kans that can still be declared in this kyoku, each of which
+    /// comes with a rinshan draw from the dead wall.
+    #[inline]
+    #[must_use]
+    pub const fn rinshan_draws_left(&self) -> u8 {
+        4 - self.kans_on_board
+    }
+    /// Number of kan doras that have been earned by a kan but not yet
+    /// revealed, which happens after the discard for daiminkan and kakan.
+    #[inline]
+    #[must_use]
+    pub fn pending_kan_doras(&self) -> u8 {
+        (self.kans_on_board + 1).saturating_sub(self.dora_indicators.len() as u8)
+    }
+
+    /// Returns the seat relative to `player_id` that draws the haitei tile
+    /// if no call or kan is made before it, or that is holding it right now.
+    #[inline]
+    #[must_use]
+    pub const fn haitei_seat(&self) -> u8 {
+        (self.next_tsumo_seat + self.tiles_left + 3) % 4
+    }
+    /// Returns whether the kyoku is in its last go-around and the player is
+    /// the one to draw the haitei tile, assuming no call or kan is made
+    /// before it, so that it is the player's last chance of haitei raoyue.
+    ///
+    /// This includes the moment the player is holding the haitei tile.
+    #[inline]
+    #[must_use]
+    pub const fn is_haitei_chance(&self) -> bool {
+        self.tiles_left < 4 && self.haitei_seat() == 0
+    }
+    /// Returns whether the kyoku is in its last go-around and someone else is
+    /// the one to draw the haitei tile, assuming no call or kan is made
+    /// before it, so that the player's last chance to win is ronning the
+    /// houtei discard, and the player will not draw again.
+    #[inline]
+    #[must_use]
+    pub const fn is_houtei_chance(&self) -> bool {
+        self.tiles_left < 4 && self.haitei_seat() != 0
+    }
 }
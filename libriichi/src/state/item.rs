@@ -1,25 +1,93 @@
 use crate::tile::Tile;
 use std::fmt;
 
-use serde::Serialize;
+use riichi_macros::{pyclass, pymethods};
+use serde::{Deserialize, Serialize};
 use tinyvec::ArrayVec;
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub(super) struct KawaItem {
     pub(super) chi_pon: Option<ChiPon>,
     pub(super) kan: ArrayVec<[Tile; 4]>,
     pub(super) sutehai: Sutehai,
 }
 
-#[derive(Debug, Clone, Serialize)]
-pub(super) struct Sutehai {
-    pub(super) tile: Tile,
-    pub(super) is_dora: bool,
-    pub(super) is_tedashi: bool,
-    pub(super) is_riichi: bool,
+/// A tile in kawa.
+#[pyclass]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Sutehai {
+    pub tile: Tile,
+    /// Whether it was a dora at the time it was discarded.
+    pub is_dora: bool,
+    pub is_tedashi: bool,
+    /// Whether it is the tile that declared riichi.
+    pub is_riichi: bool,
+    /// The seat that called it by chi, pon or daiminkan, relative to
+    /// `player_id`.
+    #[serde(default)]
+    pub claimed_by: Option<u8>,
 }
 
-#[derive(Debug, Clone, Serialize)]
+// pyo3 does not take `self` by value.
+#[allow(clippy::trivially_copy_pass_by_ref)]
+#[pymethods]
+impl Sutehai {
+    #[getter]
+    fn tile(&self) -> String {
+        self.tile.to_string()
+    }
+    #[getter]
+    const fn is_dora(&self) -> bool {
+        self.is_dora
+    }
+    #[getter]
+    const fn is_tedashi(&self) -> bool {
+        self.is_tedashi
+    }
+    #[getter]
+    const fn is_riichi(&self) -> bool {
+        self.is_riichi
+    }
+    #[getter]
+    const fn claimed_by(&self) -> Option<u8> {
+        self.claimed_by
+    }
+
+    fn __repr__(&self) -> String {
+        format!("{self:?}")
+    }
+}
+
+/// A tile that marks a point in the kawa of a seat, see
+/// `PlayerState::riichi_tiles` and `PlayerState::called_tiles`.
+#[pyclass]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KawaMark {
+    pub tile: Tile,
+    /// Number of the discards of the seat before it, i.e. its index in
+    /// `kawa_overview`.
+    pub kawa_index: u8,
+}
+
+// pyo3 does not take `self` by value.
+#[allow(clippy::trivially_copy_pass_by_ref)]
+#[pymethods]
+impl KawaMark {
+    #[getter]
+    fn tile(&self) -> String {
+        self.tile.to_string()
+    }
+    #[getter]
+    const fn kawa_index(&self) -> u8 {
+        self.kawa_index
+    }
+
+    fn __repr__(&self) -> String {
+        format!("{self:?}")
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub(super) struct ChiPon {
     pub(super) consumed: [Tile; 2],
     pub(super) target_tile: Tile,
@@ -0,0 +1,214 @@
+use super::PlayerState;
+use crate::algo::shanten;
+use crate::must_tile;
+use crate::tile::Tile;
+use std::fmt;
+
+use riichi_macros::{pyclass, pymethods};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KanKind {
+    Ankan,
+    Kakan,
+    Daiminkan,
+}
+
+/// The consequences of a legal kan.
+///
+/// For ankan and kakan, the hand is compared against discarding the kan tile
+/// instead, and for daiminkan, against passing on the call. Both sides are
+/// 3n+1, before the rinshan draw.
+#[pyclass]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KanAnalysis {
+    pub kind: KanKind,
+    /// The deaka'd kan tile.
+    pub tile: Tile,
+    pub shanten_before: i8,
+    pub shanten_after: i8,
+    /// Tiles that complete the hand, empty if not tenpai.
+    pub waits_before: Vec<Tile>,
+    pub waits_after: Vec<Tile>,
+    /// Number of the unseen copies of the waits.
+    pub live_waits_before: u8,
+    pub live_waits_after: u8,
+    /// Doras, including aka doras, among the four tiles of the kan, which are
+    /// exposed to the others unless it is an ankan. A new dora indicator is
+    /// flipped by any kan regardless.
+    pub doras_in_kan: u8,
+    /// Whether the kan opens a closed hand, losing the eligibility of riichi.
+    pub breaks_menzen: bool,
+}
+
+impl fmt::Display for KanKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Ankan => "ankan",
+            Self::Kakan => "kakan",
+            Self::Daiminkan => "daiminkan",
+        })
+    }
+}
+
+#[pymethods]
+impl KanAnalysis {
+    /// One of `"ankan"`, `"kakan"` and `"daiminkan"`.
+    #[getter]
+    fn kind(&self) -> String {
+        self.kind.to_string()
+    }
+    #[getter]
+    fn tile(&self) -> String {
+        self.tile.to_string()
+    }
+    #[getter]
+    const fn shanten_before(&self) -> i8 {
+        self.shanten_before
+    }
+    #[getter]
+    const fn shanten_after(&self) -> i8 {
+        self.shanten_after
+    }
+    #[getter]
+    fn waits_before(&self) -> Vec<String> {
+        self.waits_before.iter().map(|t| t.to_string()).collect()
+    }
+    #[getter]
+    fn waits_after(&self) -> Vec<String> {
+        self.waits_after.iter().map(|t| t.to_string()).collect()
+    }
+    #[getter]
+    const fn live_waits_before(&self) -> u8 {
+        self.live_waits_before
+    }
+    #[getter]
+    const fn live_waits_after(&self) -> u8 {
+        self.live_waits_after
+    }
+    #[getter]
+    const fn doras_in_kan(&self) -> u8 {
+        self.doras_in_kan
+    }
+    #[getter]
+    const fn breaks_menzen(&self) -> bool {
+        self.breaks_menzen
+    }
+
+    fn __repr__(&self) -> String {
+        format!("{self:?}")
+    }
+}
+
+#[pymethods]
+impl PlayerState {
+    /// Returns a list of `KanAnalysis`, one for each legal ankan, kakan and
+    /// daiminkan.
+    #[pyo3(name = "kan_analysis")]
+    #[pyo3(text_signature = "($self, /)")]
+    fn kan_analysis_py(&self) -> Vec<KanAnalysis> {
+        self.kan_analysis()
+    }
+}
+
+impl PlayerState {
+    /// Analyzes each kan in `last_cans`, in the order of ankan, kakan and
+    /// daiminkan. Returns an empty `Vec` if no kan is available.
+    #[must_use]
+    pub fn kan_analysis(&self) -> Vec<KanAnalysis> {
+        let mut ret = vec![];
+        let cans = self.last_cans;
+
+        if cans.can_ankan {
+            for &tile in self.ankan_candidates() {
+                let tid = tile.deaka().as_usize();
+                let mut before = self.tehai;
+                before[tid] -= 1;
+                let mut after = self.tehai;
+                after[tid] -= 4;
+                ret.push(self.analyze_kan(KanKind::Ankan, tid, &before, &after, false));
+            }
+        }
+        if cans.can_kakan {
+            for &tile in self.kakan_candidates() {
+                let tid = tile.deaka().as_usize();
+                let mut tehai = self.tehai;
+                tehai[tid] -= 1;
+                // The pon already took the tiles out of the hand, so the kakan
+                // only trades the discard for the rinshan draw.
+                ret.push(self.analyze_kan(KanKind::Kakan, tid, &tehai, &tehai, true));
+            }
+        }
+        if cans.can_daiminkan {
+            if let Some(tile) = self.last_kawa_tile {
+                let tid = tile.deaka().as_usize();
+                let mut after = self.tehai;
+                after[tid] -= 3;
+                ret.push(self.analyze_kan(KanKind::Daiminkan, tid, &self.tehai, &after, false));
+            }
+        }
+
+        ret
+    }
+
+    /// `before` and `after` must be 3n+1. `same_len` is set if the kan does
+    /// not take tiles out of `before`, i.e. kakan.
+    fn analyze_kan(
+        &self,
+        kind: KanKind,
+        tid: usize,
+        before: &[u8; 34],
+        after: &[u8; 34],
+        same_len: bool,
+    ) -> KanAnalysis {
+        let len_div3_after = if same_len {
+            self.tehai_len_div3
+        } else {
+            self.tehai_len_div3 - 1
+        };
+        let (shanten_before, waits_before) = shanten_and_waits(before, self.tehai_len_div3);
+        let (shanten_after, waits_after) = shanten_and_waits(after, len_div3_after);
+        let live = |waits: &[Tile]| {
+            waits
+                .iter()
+                .map(|t| 4 - self.tiles_seen[t.as_usize()])
+                .sum::<u8>()
+        };
+
+        let tile = must_tile!(tid);
+        let akas = if tile.akaize() != tile {
+            self.rule.akas_per_suit()[tid / 9]
+        } else {
+            0
+        };
+        let doras_in_kan = self.dora_factor[tid] * 4 + akas;
+
+        KanAnalysis {
+            kind,
+            tile,
+            shanten_before,
+            shanten_after,
+            live_waits_before: live(&waits_before),
+            live_waits_after: live(&waits_after),
+            waits_before,
+            waits_after,
+            doras_in_kan,
+            breaks_menzen: kind == KanKind::Daiminkan && self.is_menzen,
+        }
+    }
+}
+
+fn shanten_and_waits(tehai: &[u8; 34], len_div3: u8) -> (i8, Vec<Tile>) {
+    let shanten = shanten::calc_all(tehai, len_div3);
+    if shanten != 0 {
+        return (shanten, vec![]);
+    }
+    let waits = (0..34)
+        .filter(|&w| {
+            let mut tehai_after = *tehai;
+            tehai_after[w] += 1;
+            tehai[w] < 4 && shanten::calc_all(&tehai_after, len_div3) == -1
+        })
+        .map(|w| must_tile!(w))
+        .collect();
+    (shanten, waits)
+}
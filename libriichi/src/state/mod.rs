@@ -1,23 +1,78 @@
 mod action;
 mod agent_helper;
+mod danger;
+mod furiten;
 mod getter;
 mod item;
+mod kan_analysis;
 mod obs_repr;
+mod opponent;
+mod placement;
 mod player_state;
+mod riichi_ev;
+mod shared;
+mod suit_perm;
+mod ukeire;
 mod update;
+mod verify;
+mod win_probability;
+mod yaku;
 
 #[cfg(test)]
 mod test;
 
+#[cfg(feature = "python")]
 use crate::py_helper::add_submodule;
-pub use action::ActionCandidate;
+pub use action::{ActionCandidate, InvalidReaction};
+#[cfg(feature = "python")]
+pub use action::{
+    ActionUnavailableError, FuritenError, InvalidReactionError, KuikaeError, NotYourTurnError,
+    TileNotInHandError,
+};
+pub use danger::{PushFold, SafetyKind, TileDanger};
+pub use furiten::{FuritenInfo, FuritenKind};
+pub use item::{KawaMark, Sutehai};
+pub use kan_analysis::{KanAnalysis, KanKind};
+pub use opponent::{BaselineOpponentModel, OpponentEstimate, OpponentModel};
+pub use placement::PlacementEv;
 pub use player_state::PlayerState;
+pub use riichi_ev::{EvEstimate, RiichiEv};
+pub use suit_perm::SuitPerm;
+pub use ukeire::Ukeire;
+pub use win_probability::WinProbability;
+pub use yaku::PossibleYaku;
 
+#[cfg(feature = "python")]
 use pyo3::prelude::*;
 
+#[cfg(feature = "python")]
 pub(crate) fn register_module(py: Python<'_>, prefix: &str, super_mod: &PyModule) -> PyResult<()> {
     let m = PyModule::new(py, "state")?;
     m.add_class::<ActionCandidate>()?;
     m.add_class::<PlayerState>()?;
+    m.add_class::<PlacementEv>()?;
+    m.add_class::<RiichiEv>()?;
+    m.add_class::<EvEstimate>()?;
+    m.add_class::<WinProbability>()?;
+    m.add_class::<TileDanger>()?;
+    m.add_class::<OpponentEstimate>()?;
+    m.add_class::<FuritenInfo>()?;
+    m.add_class::<Ukeire>()?;
+    m.add_class::<KanAnalysis>()?;
+    m.add_class::<PossibleYaku>()?;
+    m.add_class::<Sutehai>()?;
+    m.add_class::<KawaMark>()?;
+    m.add(
+        "InvalidReactionError",
+        py.get_type::<InvalidReactionError>(),
+    )?;
+    m.add("NotYourTurnError", py.get_type::<NotYourTurnError>())?;
+    m.add(
+        "ActionUnavailableError",
+        py.get_type::<ActionUnavailableError>(),
+    )?;
+    m.add("TileNotInHandError", py.get_type::<TileNotInHandError>())?;
+    m.add("FuritenError", py.get_type::<FuritenError>())?;
+    m.add("KuikaeError", py.get_type::<KuikaeError>())?;
     add_submodule(py, prefix, super_mod, m)
 }
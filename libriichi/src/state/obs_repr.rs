@@ -1,36 +1,141 @@
-use super::PlayerState;
-use crate::consts::{ACTION_SPACE, OBS_SHAPE};
+use super::{BaselineOpponentModel, OpponentModel, PlayerState, SuitPerm};
+use crate::consts::{ObsVersion, ACTION_SPACE, OBS_SHAPE, TILES_LEFT_AT_START};
 use crate::state::item::KawaItem;
 use crate::{tu8, tuz};
 
+#[cfg(feature = "python")]
+use anyhow::{ensure, Result};
 use ndarray::prelude::*;
+#[cfg(feature = "python")]
 use numpy::{PyArray1, PyArray2};
+#[cfg(feature = "python")]
 use pyo3::prelude::*;
+use tinyvec::ArrayVec;
+use tracing::trace_span;
 
+#[cfg(feature = "python")]
 #[pymethods]
 impl PlayerState {
-    /// Returns `(obs, mask)`
+    /// Returns `(obs, mask)` in the encoding of `version`. If `suit_perm` is
+    /// given, such as `[1, 2, 0]`, they are encoded as if the suits were
+    /// permuted, see `SuitPerm` in Rust.
     #[pyo3(name = "encode_obs")]
-    #[pyo3(text_signature = "($self, at_kan_select)")]
+    #[pyo3(text_signature = "($self, at_kan_select, suit_perm=None, version=ObsVersion.V1)")]
+    #[args(suit_perm = "None", version = "ObsVersion::V1")]
     fn encode_obs_py<'py>(
         &self,
         at_kan_select: bool,
+        suit_perm: Option<[u8; 3]>,
+        version: ObsVersion,
         py: Python<'py>,
-    ) -> (&'py PyArray2<f32>, &'py PyArray1<bool>) {
-        let (obs, mask) = self.encode_obs(at_kan_select);
+    ) -> Result<(&'py PyArray2<f32>, &'py PyArray1<bool>)> {
+        let perm = suit_perm
+            .map(SuitPerm::new)
+            .transpose()?
+            .unwrap_or_default();
+        let (obs, mask) = self.encode_obs_with(at_kan_select, version, perm);
         let obs = PyArray2::from_owned_array(py, obs);
         let mask = PyArray1::from_owned_array(py, mask);
-        (obs, mask)
+        Ok((obs, mask))
+    }
+
+    /// Same as `encode_obs`, but writes into the numpy arrays `obs` and
+    /// `mask` provided by the caller, of shapes `version.obs_shape()` and
+    /// `(ACTION_SPACE,)`, instead of allocating new ones.
+    #[pyo3(name = "encode_obs_into")]
+    #[pyo3(
+        text_signature = "($self, obs, mask, at_kan_select, suit_perm=None, version=ObsVersion.V1)"
+    )]
+    #[args(suit_perm = "None", version = "ObsVersion::V1")]
+    fn encode_obs_into_py(
+        &self,
+        obs: &PyArray2<f32>,
+        mask: &PyArray1<bool>,
+        at_kan_select: bool,
+        suit_perm: Option<[u8; 3]>,
+        version: ObsVersion,
+    ) -> Result<()> {
+        let shape = version.obs_shape();
+        ensure!(
+            obs.shape() == [shape.0, shape.1],
+            "expected obs of shape {shape:?}, got {:?}",
+            obs.shape(),
+        );
+        ensure!(
+            mask.shape() == [ACTION_SPACE],
+            "expected mask of shape ({ACTION_SPACE},), got {:?}",
+            mask.shape(),
+        );
+        let perm = suit_perm
+            .map(SuitPerm::new)
+            .transpose()?
+            .unwrap_or_default();
+
+        // SAFETY: The GIL is held and no other views of the arrays exist in
+        // Rust.
+        let mut obs = unsafe { obs.as_array_mut() };
+        let mut mask_view = unsafe { mask.as_array_mut() };
+        obs.fill(0.);
+        let mask = self.encode_obs_into(at_kan_select, version, perm, obs);
+        mask_view.assign(&ArrayView1::from(&mask));
+        Ok(())
+    }
+
+    /// Returns the mask of the legal actions as a numpy array, see
+    /// `legal_action_mask` in Rust.
+    #[pyo3(name = "legal_action_mask")]
+    #[pyo3(text_signature = "($self, at_kan_select=False, /)")]
+    #[args(at_kan_select = "false")]
+    fn legal_action_mask_py<'py>(
+        &self,
+        at_kan_select: bool,
+        py: Python<'py>,
+    ) -> &'py PyArray1<bool> {
+        PyArray1::from_slice(py, &self.legal_action_mask(at_kan_select))
     }
 }
 
 impl PlayerState {
-    /// Returns `(obs, mask)`
+    /// Returns `(obs, mask)` in the encoding of `ObsVersion::V1`.
     #[must_use]
     pub fn encode_obs(&self, at_kan_select: bool) -> (Array2<f32>, Array1<bool>) {
-        let mut arr = Array2::zeros(OBS_SHAPE);
-        let mut mask = Array1::default(ACTION_SPACE);
+        self.encode_obs_with(at_kan_select, ObsVersion::V1, SuitPerm::IDENTITY)
+    }
+
+    /// Same as `encode_obs`, but in the encoding of `version`, and as if the
+    /// suits were permuted by `perm`. The mask is permuted accordingly, and
+    /// labels must be mapped with `SuitPerm::action` to match.
+    #[must_use]
+    pub fn encode_obs_with(
+        &self,
+        at_kan_select: bool,
+        version: ObsVersion,
+        perm: SuitPerm,
+    ) -> (Array2<f32>, Array1<bool>) {
+        let mut arr = Array2::zeros(version.obs_shape());
+        let mask = self.encode_obs_into(at_kan_select, version, perm, arr.view_mut());
+        (arr, Array1::from(mask.to_vec()))
+    }
+
+    /// Encodes the obs into `arr` without allocating, and returns the mask,
+    /// both permuted by `perm`. This is the hot path for batched inference,
+    /// where `arr` is usually a slot in a buffer of the whole batch.
+    ///
+    /// `arr` must be of shape `version.obs_shape()` and filled with zeros, as
+    /// only the non-zero features are written.
+    #[must_use]
+    pub fn encode_obs_into(
+        &self,
+        at_kan_select: bool,
+        version: ObsVersion,
+        perm: SuitPerm,
+        mut arr: ArrayViewMut2<'_, f32>,
+    ) -> [bool; ACTION_SPACE] {
+        let _span = trace_span!("encode_obs", ?version).entered();
+        assert_eq!(arr.dim(), version.obs_shape());
         let mut idx = 0;
+        // Rows of the aka features indexed by suit, to be permuted as rows.
+        let mut aka_rows = ArrayVec::<[usize; 6]>::new();
         let cans = self.last_cans;
 
         self.tehai
@@ -43,10 +148,11 @@ impl PlayerState {
             });
         idx += 4;
 
+        aka_rows.push(idx);
         self.akas_in_hand
             .into_iter()
             .enumerate()
-            .filter(|&(_, has_it)| has_it)
+            .filter(|&(_, n)| n > 0)
             .for_each(|(i, _)| {
                 arr.slice_mut(s![idx + i, ..]).fill(1.);
             });
@@ -78,6 +184,7 @@ impl PlayerState {
         arr[[idx + 1, self.jikaze.as_usize()]] = 1.;
         idx += 2;
 
+        aka_rows.push(idx + 4);
         for tile in self.dora_indicators {
             let tile_id = tile.deaka().as_usize();
             let i = (0..4).find(|&i| arr[[idx + i, tile_id]] == 0.).unwrap();
@@ -177,7 +284,7 @@ impl PlayerState {
             idx += (18 - player_kawa.len().min(18)) * 8;
         }
 
-        let v = self.tiles_left as f32 / 69.;
+        let v = self.tiles_left as f32 / (TILES_LEFT_AT_START - 1) as f32;
         arr.slice_mut(s![idx, ..]).fill(v);
         idx += 1;
 
@@ -193,6 +300,7 @@ impl PlayerState {
         idx += 5 * 4 + 3;
 
         for player_kawa_overview in &self.kawa_overview {
+            aka_rows.push(idx + 4);
             for tile in player_kawa_overview {
                 let tile_id = tile.deaka().as_usize();
                 let i = (0..4).find(|&i| arr[[idx + i, tile_id]] == 0.).unwrap();
@@ -283,13 +391,6 @@ impl PlayerState {
             if self.dora_factor[tile.deaka().as_usize()] > 0 {
                 arr.slice_mut(s![idx + 2, ..]).fill(1.);
             }
-
-            // pass
-            if !at_kan_select {
-                mask[ACTION_SPACE - 1] = true;
-            } else if cans.can_daiminkan {
-                mask[tile_id] = true;
-            }
         }
         idx += 3;
 
@@ -306,9 +407,6 @@ impl PlayerState {
                         _ => t,
                     };
                     arr[[idx, deaka_t]] = 1.;
-                    if !at_kan_select {
-                        mask[t] = true;
-                    }
                 });
 
             self.keep_shanten_discards
@@ -338,57 +436,33 @@ impl PlayerState {
 
         if cans.can_riichi {
             arr.slice_mut(s![idx, ..]).fill(1.);
-            if !at_kan_select {
-                mask[37] = true;
-            }
         }
         idx += 1;
 
         if cans.can_chi_low {
             arr.slice_mut(s![idx, ..]).fill(1.);
-            if !at_kan_select {
-                mask[38] = true;
-            }
         }
         if cans.can_chi_mid {
             arr.slice_mut(s![idx + 1, ..]).fill(1.);
-            if !at_kan_select {
-                mask[39] = true;
-            }
         }
         if cans.can_chi_high {
             arr.slice_mut(s![idx + 2, ..]).fill(1.);
-            if !at_kan_select {
-                mask[40] = true;
-            }
         }
         idx += 3;
 
         if cans.can_pon {
             arr.slice_mut(s![idx, ..]).fill(1.);
-            if !at_kan_select {
-                mask[41] = true;
-            }
         }
         idx += 1;
 
         if cans.can_daiminkan {
             arr.slice_mut(s![idx, ..]).fill(1.);
-            if !at_kan_select {
-                mask[42] = true;
-            }
         }
         idx += 1;
 
         if cans.can_ankan {
             for tile in self.ankan_candidates {
                 arr[[idx, tile.as_usize()]] = 1.;
-                if at_kan_select {
-                    mask[tile.as_usize()] = true;
-                }
-            }
-            if !at_kan_select {
-                mask[42] = true;
             }
         }
         idx += 1;
@@ -396,33 +470,169 @@ impl PlayerState {
         if cans.can_kakan {
             for tile in self.kakan_candidates {
                 arr[[idx, tile.as_usize()]] = 1.;
-                if at_kan_select {
-                    mask[tile.as_usize()] = true;
-                }
-            }
-            if !at_kan_select {
-                mask[42] = true;
             }
         }
         idx += 1;
 
         if cans.can_tsumo_agari || cans.can_ron_agari {
             arr.slice_mut(s![idx, ..]).fill(1.);
-            if !at_kan_select {
-                mask[43] = true;
-            }
         }
         idx += 1;
 
         if cans.can_ryukyoku {
             arr.slice_mut(s![idx, ..]).fill(1.);
-            if !at_kan_select {
-                mask[44] = true;
-            }
         }
         idx += 1;
 
         assert_eq!(idx, OBS_SHAPE.0);
-        (arr, mask)
+        if version != ObsVersion::V1 {
+            idx = self.encode_v2_channels(&mut arr, idx);
+        }
+        if matches!(version, ObsVersion::V3 | ObsVersion::V4) {
+            idx = self.encode_v3_channels(&mut arr, idx);
+        }
+        if version == ObsVersion::V4 {
+            idx = self.encode_v4_channels(&mut arr, idx);
+        }
+
+        assert_eq!(idx, version.obs_shape().0);
+        let mut mask = self.legal_action_mask(at_kan_select);
+        perm.apply_to_obs(arr, &aka_rows);
+        perm.apply_to_mask(&mut mask, at_kan_select);
+        mask
+    }
+
+    /// Encodes the channels `ObsVersion::V2` appends to V1 from `idx`, and
+    /// returns the index after them.
+    fn encode_v2_channels(&self, arr: &mut ArrayViewMut2<'_, f32>, mut idx: usize) -> usize {
+        // The kawa of each player as a whole, unlike V1 which only has the
+        // first 6 and the last 18 discards in detail. The index in the kawa is
+        // the turn, as it is padded for the turns skipped by calls.
+        for player_kawa in &self.kawa {
+            for (turn, item) in player_kawa.iter().enumerate() {
+                let Some(k) = item else {
+                    continue;
+                };
+                let tile_id = k.sutehai.tile.deaka().as_usize();
+                // Turn of the latest discard of the tile.
+                arr[[idx, tile_id]] = (turn + 1) as f32 / 24.;
+                if k.sutehai.is_tedashi {
+                    arr[[idx + 1, tile_id]] = 1.;
+                } else {
+                    arr[[idx + 2, tile_id]] = 1.;
+                }
+            }
+            idx += 3;
+        }
+
+        // Doras, and the tiles next to them in the same suit, which are more
+        // likely to be kept for the shapes they form with the doras.
+        for (tile_id, &factor) in self.dora_factor.iter().enumerate() {
+            if factor == 0 {
+                continue;
+            }
+            arr[[idx, tile_id]] = factor.min(4) as f32 / 4.;
+            if tile_id < 3 * 9 {
+                let num = tile_id % 9;
+                for dist in 1..=2 {
+                    if num >= dist {
+                        arr[[idx + dist, tile_id - dist]] = 1.;
+                    }
+                    if num + dist < 9 {
+                        arr[[idx + dist, tile_id + dist]] = 1.;
+                    }
+                }
+            }
+        }
+        idx += 3;
+
+        idx
+    }
+
+    /// Encodes the channels `ObsVersion::V3` appends to V2 from `idx`, and
+    /// returns the index after them.
+    fn encode_v3_channels(&self, arr: &mut ArrayViewMut2<'_, f32>, mut idx: usize) -> usize {
+        for rel_seat in 1..4 {
+            let estimate = BaselineOpponentModel.estimate(self, rel_seat);
+            arr.slice_mut(s![idx, ..]).fill(estimate.tenpai);
+            for tid in 0..34 {
+                arr[[idx + 1, tid]] = estimate.holds[tid];
+                arr[[idx + 2, tid]] = estimate.waits[tid];
+            }
+            if self.open_riichi[rel_seat as usize] {
+                arr.slice_mut(s![idx + 3, ..]).fill(1.);
+            }
+            idx += 4;
+        }
+        idx
+    }
+
+    /// Encodes the channels `ObsVersion::V4` appends to V3 from `idx`, and
+    /// returns the index after them.
+    fn encode_v4_channels(&self, arr: &mut ArrayViewMut2<'_, f32>, mut idx: usize) -> usize {
+        for rel_seat in 1..4 {
+            // The discards before the riichi tile were made before the hand
+            // was ready, so they tell more about the wait than the ones after.
+            if let Some(riichi) = self.riichi_tiles[rel_seat] {
+                arr[[idx, riichi.tile.deaka().as_usize()]] = (riichi.kawa_index + 1) as f32 / 24.;
+                let kawa = &self.kawa_overview[rel_seat];
+                for tile in &kawa[..riichi.kawa_index as usize] {
+                    arr[[idx + 1, tile.deaka().as_usize()]] = 1.;
+                }
+            }
+            // Turn of the latest call of the tile.
+            for call in &self.called_tiles[rel_seat] {
+                arr[[idx + 2, call.tile.deaka().as_usize()]] = (call.kawa_index + 1) as f32 / 24.;
+            }
+            idx += 3;
+        }
+        idx
+    }
+
+    /// Returns the mask of the legal actions over the action space, which is
+    /// the same as the mask returned by `encode_obs`.
+    ///
+    /// When `at_kan_select` is true, the mask is over the tiles to kan
+    /// instead, where 0 to 33 are the tiles of ankan, kakan or daiminkan.
+    #[must_use]
+    pub fn legal_action_mask(&self, at_kan_select: bool) -> [bool; ACTION_SPACE] {
+        let mut mask = [false; ACTION_SPACE];
+        let cans = self.last_cans;
+
+        if at_kan_select {
+            if cans.can_daiminkan {
+                let tile = self
+                    .last_kawa_tile
+                    .expect("building daiminkan mask without any kawa tile");
+                mask[tile.deaka().as_usize()] = true;
+            }
+            if cans.can_ankan {
+                for tile in self.ankan_candidates {
+                    mask[tile.as_usize()] = true;
+                }
+            }
+            if cans.can_kakan {
+                for tile in self.kakan_candidates {
+                    mask[tile.as_usize()] = true;
+                }
+            }
+            return mask;
+        }
+
+        if cans.can_discard {
+            mask[..37].copy_from_slice(&self.discard_candidates_aka());
+        }
+        mask[37] = cans.can_riichi;
+        mask[38] = cans.can_chi_low;
+        mask[39] = cans.can_chi_mid;
+        mask[40] = cans.can_chi_high;
+        mask[41] = cans.can_pon;
+        mask[42] = cans.can_daiminkan || cans.can_ankan || cans.can_kakan;
+        mask[43] = cans.can_tsumo_agari || cans.can_ron_agari;
+        mask[44] = cans.can_ryukyoku;
+        // pass
+        mask[45] = cans.can_chi() || cans.can_pon || cans.can_daiminkan || cans.can_ron_agari;
+
+        mask
     }
 }
@@ -0,0 +1,167 @@
+use super::{PlayerState, TileDanger};
+
+use anyhow::{ensure, Result};
+use riichi_macros::{pyclass, pymethods};
+
+/// Something that infers the concealed hand of an opponent from what the
+/// player can see.
+pub trait OpponentModel {
+    /// Estimates the hand of the opponent at `rel_seat`, which is relative to
+    /// `player_id` and in range [1, 3].
+    fn estimate(&self, state: &PlayerState, rel_seat: u8) -> OpponentEstimate;
+}
+
+/// The estimate of an opponent's hand, all in probabilities.
+#[pyclass]
+#[derive(Debug, Clone, PartialEq)]
+pub struct OpponentEstimate {
+    /// Probability that the opponent is tenpai.
+    pub tenpai: f32,
+    /// Probability that the opponent holds at least one copy of each of the
+    /// 34 tiles in the concealed hand.
+    pub holds: [f32; 34],
+    /// Probability that each of the 34 tiles is a winning tile of the
+    /// opponent, with `tenpai` taken into account.
+    pub waits: [f32; 34],
+}
+
+/// A heuristic `OpponentModel` that needs no training.
+///
+/// - `tenpai` is 1 after riichi, otherwise it grows with the calls and the
+///   discards of the opponent.
+/// - `holds` assumes the unseen tiles are dealt uniformly at random to the
+///   concealed hand and the wall.
+/// - `waits` is the deal-in rate of `PlayerState::tile_danger` weighted by
+///   `tenpai`.
+///
+/// Both `holds` and `waits` are then weighted by the tedashi pattern, where
+/// the tiles around the last 3 tedashis after the 6th discard, or the riichi
+/// sengenhai, are more likely to be held and waited on, and those around a
+/// tedashi within the first 6 discards are less likely.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BaselineOpponentModel;
+
+impl OpponentModel for BaselineOpponentModel {
+    fn estimate(&self, state: &PlayerState, rel_seat: u8) -> OpponentEstimate {
+        let rel = rel_seat as usize;
+        let calls = state.fuuro_overview()[rel].len() + state.ankan_overview()[rel].len();
+        let sutehais: Vec<_> = state.sutehais(rel_seat).collect();
+
+        let tenpai = if state.riichi_declared()[rel] || calls == 4 {
+            1.
+        } else {
+            let turns = sutehais.len() as f32;
+            let (base, per_turn): (f32, f32) =
+                [(0., 0.02), (0.1, 0.04), (0.3, 0.05), (0.6, 0.05)][calls];
+            per_turn.mul_add(turns, base).min(0.95)
+        };
+
+        let mut weights = [1_f32; 34];
+        let tedashis: Vec<_> = sutehais
+            .iter()
+            .enumerate()
+            .filter(|(_, s)| s.is_tedashi)
+            .collect();
+        for (i, (turn, sutehai)) in tedashis.iter().enumerate() {
+            let tid = sutehai.tile.deaka().as_usize();
+            if tid >= 3 * 9 {
+                continue;
+            }
+            let is_recent = i + 3 >= tedashis.len() && *turn >= 6;
+            let factor = if sutehai.is_riichi || is_recent {
+                1.5
+            } else if *turn < 6 {
+                0.8
+            } else {
+                continue;
+            };
+            let num = tid % 9;
+            for dist in 1..=2 {
+                if num >= dist {
+                    weights[tid - dist] *= factor;
+                }
+                if num + dist < 9 {
+                    weights[tid + dist] *= factor;
+                }
+            }
+        }
+
+        let tiles_seen = state.tiles_seen();
+        let unseen = tiles_seen.map(|n| 4 - n.min(4));
+        let unseen_total: u32 = unseen.iter().map(|&n| n as u32).sum();
+        let concealed = 13 - 3 * calls as u32;
+        // Chance that one unseen tile is in the concealed hand.
+        let ratio = if unseen_total == 0 {
+            0.
+        } else {
+            (concealed as f32 / unseen_total as f32).min(1.)
+        };
+
+        let danger = state.tile_danger(rel_seat);
+        let mut holds = [0.; 34];
+        let mut waits = [0.; 34];
+        for tid in 0..34 {
+            let prior = 1. - (1. - ratio).powi(unseen[tid] as i32);
+            holds[tid] = (prior * weights[tid]).min(1.);
+            waits[tid] = (tenpai * danger[tid].score / 100. * weights[tid]).min(1.);
+        }
+
+        OpponentEstimate {
+            tenpai,
+            holds,
+            waits,
+        }
+    }
+}
+
+#[pymethods]
+impl OpponentEstimate {
+    #[getter]
+    const fn tenpai(&self) -> f32 {
+        self.tenpai
+    }
+    #[getter]
+    const fn holds(&self) -> [f32; 34] {
+        self.holds
+    }
+    #[getter]
+    const fn waits(&self) -> [f32; 34] {
+        self.waits
+    }
+
+    fn __repr__(&self) -> String {
+        format!("{self:?}")
+    }
+}
+
+#[pymethods]
+impl PlayerState {
+    /// Estimates the hand of the opponent at `rel_seat` (relative to
+    /// `player_id`) by `BaselineOpponentModel`.
+    #[pyo3(name = "estimate_opponent")]
+    #[pyo3(text_signature = "($self, rel_seat, /)")]
+    fn estimate_opponent_py(&self, rel_seat: u8) -> Result<OpponentEstimate> {
+        ensure!(
+            (1..4).contains(&rel_seat),
+            "{rel_seat} is not in range [1, 3]"
+        );
+        Ok(BaselineOpponentModel.estimate(self, rel_seat))
+    }
+}
+
+impl PlayerState {
+    /// Same as `tile_danger`, except that the score is the wait probability
+    /// estimated by `model` in percent, so that it accounts for how likely
+    /// the opponent is tenpai and how the hand is shaped.
+    ///
+    /// Panics if `rel_seat` is outside of range [1, 3].
+    #[must_use]
+    pub fn tile_danger_with(&self, rel_seat: u8, model: &dyn OpponentModel) -> Vec<TileDanger> {
+        let estimate = model.estimate(self, rel_seat);
+        let mut danger = self.tile_danger(rel_seat);
+        for (d, wait) in danger.iter_mut().zip(estimate.waits) {
+            d.score = wait * 100.;
+        }
+        danger
+    }
+}
@@ -0,0 +1,346 @@
+use super::PlayerState;
+use crate::algo::point::{exhaustive_ryukyoku_deltas, Point};
+use crate::algo::shanten;
+use crate::consts::TILES_LEFT_AT_START;
+use crate::mjai::Event;
+use crate::vec_ops::vec_add_assign;
+
+use anyhow::Result;
+use riichi_macros::{pyclass, pymethods};
+use serde_json as json;
+
+/// Hand values of a win, as (fu, han, weight), used for every seat.
+const WIN_TIERS: [(u8, u8, f32); 4] = [(30, 2, 0.3), (30, 3, 0.3), (30, 5, 0.3), (30, 6, 0.1)];
+/// Chance of a discard of an opponent to be one of the waits, relative to a
+/// tile drawn from the unseen pool.
+const RON_FACTOR: f32 = 0.5;
+/// Win rates at the start of a kyoku by shanten, from 1 to 3 or more.
+const SHANTEN_WIN_RATES: [f32; 3] = [0.3, 0.12, 0.04];
+/// Chance of the kyoku ending in an exhaustive ryukyoku, at the start of it.
+const RYUKYOKU_RATE: f32 = 0.15;
+/// Chance of an opponent not in riichi to be tenpai at ryukyoku.
+const OPPONENT_TENPAI_RATE: f32 = 0.45;
+/// Chance of an opponent not in riichi to be tenpai when dealt in, relative to
+/// an opponent in riichi.
+const DAMA_DANGER_FACTOR: f32 = 0.3;
+
+/// The final placement probabilities of a candidate action at all-last.
+#[pyclass]
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlacementEv {
+    pub action: Event,
+    /// Estimated chance of the player to win the kyoku.
+    pub win_rate: f32,
+    /// Estimated chance of the action itself to deal in.
+    pub deal_in_rate: f32,
+    /// Probabilities of finishing 1st to 4th.
+    pub rank_probs: [f32; 4],
+}
+
+/// The prospects of the player after an action.
+struct Prospect {
+    shanten: i8,
+    /// Number of the unseen copies of the tiles that reduce the shanten.
+    accepted: u32,
+    /// Deal-in rate of the action against each opponent, indexed by relative
+    /// seat.
+    deal_in: [f32; 4],
+}
+
+#[pymethods]
+impl PlacementEv {
+    /// The action as a mjai JSON string.
+    #[getter]
+    fn action(&self) -> Result<String> {
+        Ok(json::to_string(&self.action)?)
+    }
+    #[getter]
+    const fn win_rate(&self) -> f32 {
+        self.win_rate
+    }
+    #[getter]
+    const fn deal_in_rate(&self) -> f32 {
+        self.deal_in_rate
+    }
+    #[getter]
+    const fn rank_probs(&self) -> [f32; 4] {
+        self.rank_probs
+    }
+    /// Expected final rank, counting from 1.
+    #[getter]
+    #[must_use]
+    pub fn avg_rank(&self) -> f32 {
+        self.rank_probs
+            .iter()
+            .enumerate()
+            .map(|(rank, &p)| (rank + 1) as f32 * p)
+            .sum()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("{self:?}")
+    }
+}
+
+#[pymethods]
+impl PlayerState {
+    /// Returns a list of `PlacementEv`, one for each candidate action. The
+    /// list is empty if it is not all-last or the player cannot act now.
+    #[pyo3(name = "placement_ev")]
+    #[pyo3(text_signature = "($self, /)")]
+    fn placement_ev_py(&self) -> Vec<PlacementEv> {
+        self.placement_ev()
+    }
+}
+
+impl PlayerState {
+    /// Estimates the final placement probabilities of each candidate action
+    /// at all-last, from `scores`, `honba` and `kyotaku`. Returns an empty
+    /// `Vec` if it is not all-last or the player cannot act now.
+    ///
+    /// The candidates are hora, each discard, and passing when reacting to
+    /// the discard of others. Calls and riichi are not evaluated.
+    ///
+    /// The outcomes enumerated are the tsumo and ron of every seat at the
+    /// hand values of `WIN_TIERS`, and the exhaustive ryukyoku with every
+    /// combination of tenpai players. Their probabilities come from a rough
+    /// model:
+    /// - The win rate of the player is derived from the shanten and the tile
+    ///   acceptance after the action.
+    /// - The action deals in at the `tile_danger` score against each opponent,
+    ///   discounted for the ones not in riichi.
+    /// - The rest is split between ryukyoku, which gets likelier as the wall
+    ///   runs out, and the wins of the opponents, where the ones in riichi are
+    ///   twice as likely to win.
+    ///
+    /// Every outcome is treated as the end of the game, including the ones
+    /// where the oya renchans.
+    #[must_use]
+    pub fn placement_ev(&self) -> Vec<PlacementEv> {
+        let cans = self.last_cans;
+        if !self.is_all_last || !cans.can_act() {
+            return vec![];
+        }
+
+        let actor = self.player_id;
+        let mut ret = vec![];
+        if cans.can_tsumo_agari || cans.can_ron_agari {
+            if let Ok(point) = self.agari_points(cans.can_ron_agari, &[]) {
+                let mut rank_probs = [0.; 4];
+                let target = self.rel(cans.target_actor);
+                rank_probs[self.rank_after_win(0, target, point) as usize] = 1.;
+                ret.push(PlacementEv {
+                    action: Event::Hora {
+                        actor,
+                        target: cans.target_actor,
+                        deltas: None,
+                        ura_markers: None,
+                    },
+                    win_rate: 1.,
+                    deal_in_rate: 0.,
+                    rank_probs,
+                });
+            }
+        }
+
+        if cans.can_discard {
+            let candidates = self.discard_candidates_aka();
+            for ukeire in self.ukeire() {
+                let tid = ukeire.discard.as_usize();
+                let aka = ukeire.discard.akaize();
+                let pai = if aka != ukeire.discard && candidates[aka.as_usize()] && !candidates[tid]
+                {
+                    aka
+                } else {
+                    ukeire.discard
+                };
+
+                let mut deal_in = [0.; 4];
+                for (rel, rate) in deal_in.iter_mut().enumerate().skip(1) {
+                    let score = self.tile_danger(rel as u8)[tid].score / 100.;
+                    *rate = if self.riichi_declared[rel] {
+                        score
+                    } else {
+                        score * DAMA_DANGER_FACTOR
+                    };
+                }
+                let prospect = Prospect {
+                    shanten: ukeire.shanten,
+                    accepted: ukeire.total(),
+                    deal_in,
+                };
+                ret.push(self.placement_of(
+                    Event::Dahai {
+                        actor,
+                        pai,
+                        tsumogiri: self.last_self_tsumo == Some(pai),
+                    },
+                    &prospect,
+                ));
+            }
+        } else {
+            let shanten = shanten::calc_all(&self.tehai, self.tehai_len_div3);
+            let mut tehai = self.tehai;
+            let accepted = (0..34)
+                .map(|tid| {
+                    if tehai[tid] >= 4 {
+                        return 0;
+                    }
+                    tehai[tid] += 1;
+                    let shanten_after = shanten::calc_all(&tehai, self.tehai_len_div3);
+                    tehai[tid] -= 1;
+                    if shanten_after < shanten {
+                        4 - self.tiles_seen[tid] as u32
+                    } else {
+                        0
+                    }
+                })
+                .sum();
+            let prospect = Prospect {
+                shanten,
+                accepted,
+                deal_in: [0.; 4],
+            };
+            ret.push(self.placement_of(Event::None, &prospect));
+        }
+
+        ret
+    }
+
+    fn placement_of(&self, action: Event, prospect: &Prospect) -> PlacementEv {
+        let unseen_total = self.tiles_seen.iter().map(|&n| (4 - n) as f32).sum::<f32>();
+        let own_draws = self.tiles_left.div_ceil(4) as i32;
+        let opponent_discards = self.tiles_left as i32 - own_draws;
+        let progress = self.tiles_left as f32 / TILES_LEFT_AT_START as f32;
+
+        let deal_in_rate = prospect.deal_in.iter().sum::<f32>().min(1.);
+        let (win_rate, tsumo_share) = if prospect.shanten <= 0 {
+            let hit = prospect.accepted as f32 / unseen_total;
+            let miss_rate =
+                (1. - hit).powi(own_draws) * (1. - hit * RON_FACTOR).powi(opponent_discards);
+            let tsumo_weight = own_draws as f32;
+            let ron_weight = opponent_discards as f32 * RON_FACTOR;
+            let tsumo_share = if tsumo_weight + ron_weight > 0. {
+                tsumo_weight / (tsumo_weight + ron_weight)
+            } else {
+                1.
+            };
+            (1. - miss_rate, tsumo_share)
+        } else {
+            let idx = (prospect.shanten as usize - 1).min(2);
+            (SHANTEN_WIN_RATES[idx] * progress, 0.5)
+        };
+        let win_rate = win_rate.min(1. - deal_in_rate);
+
+        let rest = 1. - win_rate - deal_in_rate;
+        let exhausted = 1. - progress;
+        let ryukyoku_rate = (1. - RYUKYOKU_RATE).mul_add(exhausted * exhausted, RYUKYOKU_RATE);
+        let ryukyoku = rest * ryukyoku_rate;
+        let others_win = rest - ryukyoku;
+
+        let mut rank_probs = [0.; 4];
+        let mut add = |prob: f32, winner: usize, target: usize| {
+            for (fu, han, weight) in WIN_TIERS {
+                let point = Point::calc(fu, han, winner == self.oya as usize);
+                rank_probs[self.rank_after_win(winner, target, point) as usize] += prob * weight;
+            }
+        };
+
+        // The wins of the player.
+        add(win_rate * tsumo_share, 0, 0);
+        for target in 1..4 {
+            add(win_rate * (1. - tsumo_share) / 3., 0, target);
+        }
+        // Deal-ins by the action.
+        for (winner, &rate) in prospect.deal_in.iter().enumerate().skip(1) {
+            add(rate, winner, 0);
+        }
+        // The wins of the opponents, where the player does not deal in.
+        let weights: Vec<_> = (1..4)
+            .map(|rel| if self.riichi_declared[rel] { 2. } else { 1. })
+            .collect();
+        let weight_sum = weights.iter().sum::<f32>();
+        for (winner, weight) in (1..4).zip(weights) {
+            let prob = others_win * weight / weight_sum;
+            add(prob / 2., winner, winner);
+            for target in (1..4).filter(|&t| t != winner) {
+                add(prob / 4., winner, target);
+            }
+        }
+
+        // Exhaustive ryukyoku with every combination of tenpai players.
+        let own_tenpai = match prospect.shanten {
+            ..=0 => 1.,
+            1 => 0.5,
+            _ => 0.1,
+        };
+        for tenpai_bits in 0..16_u8 {
+            let tenpai = [0, 1, 2, 3].map(|rel| tenpai_bits & (1 << rel) != 0);
+            let prob = (0..4)
+                .map(|rel| {
+                    let rate = if rel == 0 {
+                        own_tenpai
+                    } else if self.riichi_declared[rel] {
+                        1.
+                    } else {
+                        OPPONENT_TENPAI_RATE
+                    };
+                    if tenpai[rel] {
+                        rate
+                    } else {
+                        1. - rate
+                    }
+                })
+                .product::<f32>();
+            if prob > 0. {
+                // Nagashi mangan is too rare to be worth counting.
+                let paid_tenpai = if self.rule.noten_bappu {
+                    tenpai
+                } else {
+                    [false; 4]
+                };
+                let deltas = exhaustive_ryukyoku_deltas(paid_tenpai, [false; 4], self.oya as usize);
+                let mut scores = self.scores;
+                vec_add_assign(&mut scores, &deltas);
+                rank_probs[self.get_rank(&scores) as usize] += ryukyoku * prob;
+            }
+        }
+
+        PlacementEv {
+            action,
+            win_rate,
+            deal_in_rate,
+            rank_probs,
+        }
+    }
+
+    /// Final rank of the player after `winner` wins with `point` from
+    /// `target`, which is a tsumo if they are the same. Both are relative
+    /// seats.
+    fn rank_after_win(&self, winner: usize, target: usize, point: Point) -> u8 {
+        let honba = self.honba as i32;
+        let mut deltas = [0; 4];
+        if winner == target {
+            let is_oya = winner == self.oya as usize;
+            for (rel, delta) in deltas.iter_mut().enumerate() {
+                if rel != winner {
+                    let pay = if rel == self.oya as usize {
+                        point.tsumo_oya
+                    } else {
+                        point.tsumo_ko
+                    };
+                    *delta = -pay - honba * 100;
+                }
+            }
+            deltas[winner] = point.tsumo_total(is_oya) + honba * 300;
+        } else {
+            deltas[target] = -point.ron - honba * 300;
+            deltas[winner] = point.ron + honba * 300;
+        }
+        deltas[winner] += self.kyotaku as i32 * 1000;
+
+        let mut scores = self.scores;
+        vec_add_assign(&mut scores, &deltas);
+        self.get_rank(&scores)
+    }
+}
@@ -1,14 +1,25 @@
 use super::action::ActionCandidate;
-use super::item::{ChiPon, KawaItem};
-use crate::hand::tiles_to_string;
-use crate::must_tile;
+use super::item::{ChiPon, KawaItem, KawaMark};
+use super::shared::Shared;
+use super::verify::Settlement;
+use crate::algo::agari::Decomposition;
+use crate::error::Error;
+use crate::hand::{tile34_to_vec, tile37_to_vec, tiles_to_string};
+use crate::rule::RuleSet;
 use crate::tile::Tile;
+use crate::{must_tile, tu8};
 use std::iter;
 
-use anyhow::Result;
+use anyhow::{ensure, Result};
 use derivative::Derivative;
+#[cfg(feature = "python")]
 use pyo3::prelude::*;
+#[cfg(feature = "python")]
+use pyo3::types::PyBytes;
+use riichi_macros::{pyclass, pymethods};
+use serde::{Deserialize, Serialize};
 use serde_json as json;
+use serde_with::serde_as;
 use tinyvec::ArrayVec;
 
 /// `PlayerState` is the core of the lib, which holds all the observable game
@@ -17,41 +28,51 @@ use tinyvec::ArrayVec;
 /// mjai event, along with some helper functions to build an actual agent.
 /// Notably, `PlayerState` encodes observation features into numpy arrays which
 /// serve as inputs for deep learning model.
+#[serde_as]
 #[pyclass]
-#[pyo3(text_signature = "(player_id)")]
-#[derive(Debug, Clone, Derivative)]
+#[pyo3(text_signature = "(player_id, rule=None, *, strict=False)")]
+#[derive(Debug, Clone, Derivative, Serialize, Deserialize)]
 #[derivative(Default)]
 pub struct PlayerState {
     #[pyo3(get)]
     pub(super) player_id: u8,
+    pub(super) rule: RuleSet,
 
     /// Does not include aka.
     #[derivative(Default(value = "[0; 34]"))]
+    #[serde_as(as = "[_; 34]")]
     pub(super) tehai: [u8; 34],
 
     /// Does not consider yakunashi, but does consider other kinds of
     /// furiten.
     #[derivative(Default(value = "[false; 34]"))]
+    #[serde_as(as = "[_; 34]")]
     pub(super) waits: [bool; 34],
 
     #[derivative(Default(value = "[0; 34]"))]
+    #[serde_as(as = "[_; 34]")]
     pub(super) dora_factor: [u8; 34],
 
     /// For calculating `waits` and `doras_seen`.
     #[derivative(Default(value = "[0; 34]"))]
+    #[serde_as(as = "[_; 34]")]
     pub(super) tiles_seen: [u8; 34],
 
     #[derivative(Default(value = "[false; 34]"))]
+    #[serde_as(as = "[_; 34]")]
     pub(super) keep_shanten_discards: [bool; 34],
 
     #[derivative(Default(value = "[false; 34]"))]
+    #[serde_as(as = "[_; 34]")]
     pub(super) next_shanten_discards: [bool; 34],
 
     #[derivative(Default(value = "[false; 34]"))]
+    #[serde_as(as = "[_; 34]")]
     pub(super) forbidden_tiles: [bool; 34],
 
     /// Used for furiten check.
     #[derivative(Default(value = "[false; 34]"))]
+    #[serde_as(as = "[_; 34]")]
     pub(super) discarded_tiles: [bool; 34],
 
     pub(super) bakaze: Tile,
@@ -72,17 +93,27 @@ pub struct PlayerState {
     /// 24 is the theoretical max size of kawa.
     ///
     /// Reference: <https://detail.chiebukuro.yahoo.co.jp/qa/question_detail/q1020002370>
-    pub(super) kawa: [ArrayVec<[Option<KawaItem>; 24]>; 4],
+    pub(super) kawa: Shared<[ArrayVec<[Option<KawaItem>; 24]>; 4]>,
 
     /// Using 34-D arrays here may be more efficient, but I don't want to mess up
     /// with aka doras.
-    pub(super) kawa_overview: [ArrayVec<[Tile; 24]>; 4],
+    pub(super) kawa_overview: Shared<[ArrayVec<[Tile; 24]>; 4]>,
     pub(super) fuuro_overview: [ArrayVec<[ArrayVec<[Tile; 4]>; 4]>; 4],
     /// In this field all `Tile` are deaka'd.
     pub(super) ankan_overview: [ArrayVec<[Tile; 4]>; 4],
 
     pub(super) riichi_declared: [bool; 4],
     pub(super) riichi_accepted: [bool; 4],
+    /// Whether each riichi declared is an open riichi, only with
+    /// `RuleSet::open_riichi`.
+    pub(super) open_riichi: [bool; 4],
+    /// Whether each seat still qualifies for nagashi mangan, i.e. every discard
+    /// of it is a terminal or honor and none of them has been called.
+    pub(super) nagashi_mangan: [bool; 4],
+    /// The tile each seat declared riichi with.
+    pub(super) riichi_tiles: [Option<KawaMark>; 4],
+    /// The tiles each seat took from the others by chi, pon and daiminkan.
+    pub(super) called_tiles: [ArrayVec<[KawaMark; 4]>; 4],
 
     pub(super) at_turn: u8,
     pub(super) tiles_left: u8,
@@ -98,17 +129,29 @@ pub struct PlayerState {
     /// Both deaka'd
     pub(super) ankan_candidates: ArrayVec<[Tile; 3]>,
     pub(super) kakan_candidates: ArrayVec<[Tile; 3]>,
-    pub(super) chankan_chance: Option<()>,
+    /// The tile that can be robbed by chankan right now, deaka'd.
+    pub(super) chankan_chance: Option<Tile>,
 
     pub(super) can_w_riichi: bool,
     pub(super) is_w_riichi: bool,
     pub(super) at_rinshan: bool,
     pub(super) at_ippatsu: bool,
+    /// Whether the last event cancelled our ippatsu.
+    pub(super) ippatsu_broken: bool,
     pub(super) at_furiten: bool,
+    /// Serialized as a bool, since JSON has no way to tell `Some(())` from
+    /// `None`.
+    #[serde(with = "unit_flag")]
     pub(super) to_mark_same_cycle_furiten: Option<()>,
+    /// The wait passed on that caused the current or the pending furiten,
+    /// unless it is a discard furiten.
+    pub(super) missed_wait: Option<Tile>,
 
     /// Used for 4-kan check.
     pub(super) kans_on_board: u8,
+    /// The seat relative to `player_id` that draws the next tile from the
+    /// wall, assuming no call is made in between.
+    pub(super) next_tsumo_seat: u8,
 
     pub(super) is_menzen: bool,
     /// For agari calc, all deaka'd.
@@ -122,42 +165,370 @@ pub struct PlayerState {
     pub(super) doras_owned: [u8; 4],
     pub(super) doras_seen: u8,
 
-    pub(super) akas_in_hand: [bool; 3],
+    /// Number of each of the aka 5m, 5p and 5s, which can be 2 for 5p with 4
+    /// akas.
+    pub(super) akas_in_hand: [u8; 3],
+    /// Including the ones in the player's own hand.
+    pub(super) akas_seen: [u8; 3],
 
     /// For shanten calc.
     pub(super) tehai_len_div3: u8,
 
     /// Used in can_riichi.
     pub(super) has_next_shanten_discard: bool,
+
+    /// See `set_strict`.
+    #[pyo3(get, set)]
+    #[serde(default)]
+    pub(super) strict: bool,
+    #[serde(default)]
+    pub(super) settlement: Option<Settlement>,
+}
+
+/// The observable state in a structured form, see `PlayerState::to_json`.
+///
+/// All the per-seat arrays are relative to `player_id`.
+#[derive(Serialize)]
+struct StateView<'a> {
+    player_id: u8,
+    bakaze: Tile,
+    jikaze: Tile,
+    /// Counts from 1, same as mjai.
+    kyoku: u8,
+    honba: u8,
+    kyotaku: u8,
+    oya: u8,
+    scores: [i32; 4],
+    rank: u8,
+    at_turn: u8,
+    tiles_left: u8,
+    dora_indicators: &'a [Tile],
+
+    tehai: Vec<Tile>,
+    last_self_tsumo: Option<Tile>,
+    last_kawa_tile: Option<Tile>,
+    shanten: i8,
+    waits: Vec<Tile>,
+    at_furiten: bool,
+    cans: ActionCandidate,
+
+    kawa: [&'a [Option<KawaItem>]; 4],
+    fuuro: [&'a [ArrayVec<[Tile; 4]>]; 4],
+    /// Deaka'd.
+    ankan: [&'a [Tile]; 4],
+    riichi_declared: [bool; 4],
+    riichi_accepted: [bool; 4],
 }
 
-#[pymethods]
 impl PlayerState {
     /// Panics if `player_id` is outside of range [0, 3].
-    #[new]
     #[must_use]
     pub fn new(player_id: u8) -> Self {
+        Self::with_rule(player_id, RuleSet::default())
+    }
+
+    /// Panics if `player_id` is outside of range [0, 3].
+    #[must_use]
+    pub fn with_rule(player_id: u8, rule: RuleSet) -> Self {
         assert!(player_id < 4, "{player_id} is not in range [0, 3]");
         Self {
             player_id,
+            rule,
             ..Default::default()
         }
     }
 
+    /// In strict mode, `update` cross-checks the deltas of `hora`, `ryukyoku`
+    /// and `chombo` events against its own calculation, and the honba,
+    /// kyotaku and scores of the following `start_kyoku` against them,
+    /// returning Err on any discrepancy instead of trusting the log. It
+    /// catches both converter bugs and rule mismatches between the log and
+    /// `rule`.
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+        self.settlement = None;
+    }
+
+    /// Serializes the full state, so that it can be restored later by
+    /// `from_bytes` without replaying the events.
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        Ok(json::to_vec(self)?)
+    }
+
+    fn view(&self) -> StateView<'_> {
+        let mut tehai = [0; 37];
+        tehai[..34].copy_from_slice(&self.tehai);
+        for (i, &n) in self.akas_in_hand.iter().enumerate() {
+            tehai[tu8!(5m) as usize + 9 * i] -= n;
+            tehai[34 + i] = n;
+        }
+
+        StateView {
+            player_id: self.player_id,
+            bakaze: self.bakaze,
+            jikaze: self.jikaze,
+            kyoku: self.kyoku + 1,
+            honba: self.honba,
+            kyotaku: self.kyotaku,
+            oya: self.oya,
+            scores: self.scores,
+            rank: self.rank,
+            at_turn: self.at_turn,
+            tiles_left: self.tiles_left,
+            dora_indicators: &self.dora_indicators,
+
+            tehai: tile37_to_vec(&tehai),
+            last_self_tsumo: self.last_self_tsumo,
+            last_kawa_tile: self.last_kawa_tile,
+            shanten: self.shanten,
+            waits: tile34_to_vec(&self.waits.map(|b| b as u8)),
+            at_furiten: self.at_furiten,
+            cans: self.last_cans,
+
+            kawa: [0, 1, 2, 3].map(|i| &self.kawa[i][..]),
+            fuuro: [0, 1, 2, 3].map(|i| &self.fuuro_overview[i][..]),
+            ankan: [0, 1, 2, 3].map(|i| &self.ankan_overview[i][..]),
+            riichi_declared: self.riichi_declared,
+            riichi_accepted: self.riichi_accepted,
+        }
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        let state: Self = json::from_slice(data)?;
+        ensure!(
+            state.player_id < 4,
+            "{} is not in range [0, 3]",
+            state.player_id,
+        );
+        state.rule.validate()?;
+        Ok(state)
+    }
+}
+
+#[pymethods]
+impl PlayerState {
+    /// `rule` defaults to Tenhou's rule. See `set_strict` for `strict`.
+    #[new]
+    #[args(rule = "None", "*", strict = "false")]
+    fn py_new(player_id: u8, rule: Option<RuleSet>, strict: bool) -> Result<Self> {
+        ensure!(player_id < 4, "{player_id} is not in range [0, 3]");
+        let rule = rule.unwrap_or_default();
+        rule.validate()?;
+        let mut state = Self::with_rule(player_id, rule);
+        state.set_strict(strict);
+        Ok(state)
+    }
+
     /// Returns an `ActionCandidate`.
+    ///
+    /// Raises a `ParseError` if `mjai_json` is not a valid mjai event, or an
+    /// `InvalidEventError` if the event cannot happen in the current state.
     #[pyo3(name = "update")]
     #[pyo3(text_signature = "($self, mjai_json, /)")]
-    pub(super) fn update_json(&mut self, mjai_json: &str) -> Result<ActionCandidate> {
-        let event = json::from_str(mjai_json)?;
-        Ok(self.update(&event))
+    pub(super) fn update_json(&mut self, mjai_json: &str) -> Result<ActionCandidate, Error> {
+        let event = json::from_str(mjai_json).map_err(Error::Parse)?;
+        self.update(&event).map_err(Error::InvalidEvent)
     }
 
     /// Raises an exception if the action is not valid.
+    ///
+    /// The exception is an `InvalidReactionError`, or one of its subclasses
+    /// for the common reasons, such as `TileNotInHandError`.
     #[pyo3(name = "validate_reaction")]
     #[pyo3(text_signature = "($self, mjai_json, /)")]
-    pub(super) fn validate_reaction_json(&self, mjai_json: &str) -> Result<()> {
-        let action = json::from_str(mjai_json)?;
-        self.validate_reaction(&action)
+    pub(super) fn validate_reaction_json(&self, mjai_json: &str) -> Result<(), Error> {
+        let action = json::from_str(mjai_json).map_err(Error::Parse)?;
+        Ok(self.validate_reaction(&action)?)
+    }
+
+    #[cfg(feature = "python")]
+    #[pyo3(name = "to_bytes")]
+    #[pyo3(text_signature = "($self, /)")]
+    fn to_bytes_py<'py>(&self, py: Python<'py>) -> Result<&'py PyBytes> {
+        let data = self.to_bytes()?;
+        Ok(PyBytes::new(py, &data))
+    }
+
+    #[staticmethod]
+    #[pyo3(name = "from_bytes")]
+    #[pyo3(text_signature = "(data, /)")]
+    fn from_bytes_py(data: &[u8]) -> Result<Self> {
+        Self::from_bytes(data)
+    }
+
+    /// Returns the board as seen by the player at `rel_seat` (relative to
+    /// `player_id`), built from public information only.
+    ///
+    /// The returned state is rotated so that the target seat becomes
+    /// `player_id`, with its tehai left empty (unknown). Hidden information of
+    /// the original player, such as the tiles in their hand, is removed from
+    /// `tiles_seen`, `doras_seen` and `doras_owned`.
+    ///
+    /// Panics if `rel_seat` is outside of range [0, 3].
+    #[pyo3(text_signature = "($self, rel_seat, /)")]
+    #[must_use]
+    pub fn public_view_from(&self, rel_seat: u8) -> Self {
+        assert!(rel_seat < 4, "{rel_seat} is not in range [0, 3]");
+        let shift = rel_seat as usize;
+
+        let hidden_doras = self
+            .tehai
+            .iter()
+            .zip(self.dora_factor)
+            .map(|(&count, factor)| count * factor)
+            .sum::<u8>()
+            + self.akas_in_hand.iter().sum::<u8>();
+        let mut tiles_seen = self.tiles_seen;
+        tiles_seen
+            .iter_mut()
+            .zip(self.tehai)
+            .for_each(|(seen, count)| *seen -= count);
+        let mut doras_owned = self.doras_owned;
+        doras_owned[0] -= hidden_doras;
+
+        let mut ret = Self {
+            player_id: (self.player_id + rel_seat) % 4,
+            rule: self.rule,
+            dora_factor: self.dora_factor,
+            tiles_seen,
+            bakaze: self.bakaze,
+            kyoku: self.kyoku,
+            honba: self.honba,
+            kyotaku: self.kyotaku,
+            scores: self.scores,
+            oya: (self.oya + 4 - rel_seat) % 4,
+            is_all_last: self.is_all_last,
+            dora_indicators: self.dora_indicators,
+            kawa: self.kawa.clone(),
+            kawa_overview: self.kawa_overview.clone(),
+            fuuro_overview: self.fuuro_overview,
+            ankan_overview: self.ankan_overview,
+            riichi_declared: self.riichi_declared,
+            riichi_accepted: self.riichi_accepted,
+            open_riichi: self.open_riichi,
+            nagashi_mangan: self.nagashi_mangan,
+            riichi_tiles: self.riichi_tiles,
+            called_tiles: self.called_tiles,
+            tiles_left: self.tiles_left,
+            last_kawa_tile: self.last_kawa_tile,
+            kans_on_board: self.kans_on_board,
+            next_tsumo_seat: (self.next_tsumo_seat + 4 - rel_seat) % 4,
+            doras_owned,
+            doras_seen: self.doras_seen - hidden_doras,
+            akas_seen: [0, 1, 2].map(|i| self.akas_seen[i] - self.akas_in_hand[i]),
+            ..Default::default()
+        };
+        ret.scores.rotate_left(shift);
+        ret.kawa.rotate_left(shift);
+        ret.kawa_overview.rotate_left(shift);
+        ret.fuuro_overview.rotate_left(shift);
+        ret.ankan_overview.rotate_left(shift);
+        ret.riichi_declared.rotate_left(shift);
+        ret.riichi_accepted.rotate_left(shift);
+        ret.open_riichi.rotate_left(shift);
+        ret.nagashi_mangan.rotate_left(shift);
+        ret.riichi_tiles.rotate_left(shift);
+        ret.called_tiles.rotate_left(shift);
+        ret.doras_owned.rotate_left(shift);
+
+        ret.jikaze = must_tile!(tu8!(E) + (4 - ret.oya) % 4);
+        for fuuro in &ret.fuuro_overview[0] {
+            let tile = fuuro[0].deaka().as_u8();
+            if fuuro.len() == 4 {
+                ret.minkans.push(tile);
+            } else if fuuro[1].deaka().as_u8() == tile {
+                ret.pons.push(tile);
+            } else {
+                let min = fuuro.iter().map(|t| t.deaka().as_u8()).min().unwrap();
+                ret.chis.push(min);
+            }
+        }
+        ret.ankans
+            .extend(ret.ankan_overview[0].iter().map(|t| t.as_u8()));
+        ret.is_menzen = ret.fuuro_overview[0].is_empty();
+        ret.tehai_len_div3 = 4 - ret.fuuro_overview[0].len() as u8 - ret.ankans.len() as u8;
+        ret.update_rank();
+
+        ret
+    }
+
+    /// Returns the observable state as a JSON string, including the tehai,
+    /// the kawa with the metadata of each discard, the fuuro, the riichi
+    /// flags, the action candidates and the waits.
+    ///
+    /// Unlike `brief_info`, the output is meant for tools such as GUIs and
+    /// debuggers. Every per-seat array is relative to `player_id`, same as
+    /// `oya` and `scores`.
+    #[pyo3(text_signature = "($self, /)")]
+    pub fn to_json(&self) -> Result<String> {
+        Ok(json::to_string(&self.view())?)
+    }
+
+    /// Returns the tiles that can be discarded now, aka doras distinguished.
+    ///
+    /// Raises an exception if not called at 3n+2.
+    #[pyo3(name = "discard_candidates")]
+    #[pyo3(text_signature = "($self, /)")]
+    fn discard_candidates_py(&self) -> Result<Vec<String>> {
+        ensure!(self.last_cans.can_discard, "tehai is not 3n+2");
+        let ret = self
+            .discard_candidates_aka()
+            .iter()
+            .enumerate()
+            .filter(|(_, &b)| b)
+            .map(|(tid, _)| must_tile!(tid).to_string())
+            .collect();
+        Ok(ret)
+    }
+
+    /// Returns the tiles that can be discarded along with a riichi
+    /// declaration, see `riichi_candidates` in Rust.
+    #[pyo3(name = "riichi_candidates")]
+    #[pyo3(text_signature = "($self, /)")]
+    fn riichi_candidates_py(&self) -> Vec<String> {
+        self.riichi_candidates()
+            .iter()
+            .map(|t| t.to_string())
+            .collect()
+    }
+
+    /// Returns every legal chi, pon and daiminkan as mjai JSON strings, see
+    /// `call_candidates` in Rust.
+    #[pyo3(name = "call_candidates")]
+    #[pyo3(text_signature = "($self, /)")]
+    fn call_candidates_py(&self) -> Result<Vec<String>> {
+        let ret = self
+            .call_candidates()
+            .iter()
+            .map(json::to_string)
+            .collect::<Result<_, _>>()?;
+        Ok(ret)
+    }
+
+    /// Returns all the distinct reactions the player can make now as mjai
+    /// JSON strings, including `none` if it can pass.
+    #[pyo3(name = "reaction_candidates")]
+    #[pyo3(text_signature = "($self, /)")]
+    fn reaction_candidates_py(&self) -> Result<Vec<String>> {
+        let ret = self
+            .reaction_candidates()
+            .iter()
+            .map(json::to_string)
+            .collect::<Result<_, _>>()?;
+        Ok(ret)
+    }
+
+    /// Returns every reading of the hand completed by `winning_tile` as a
+    /// list of `Decomposition`, the highest scoring first, see
+    /// `agari_decompositions` in Rust.
+    #[pyo3(name = "agari_decompositions")]
+    #[pyo3(text_signature = "($self, winning_tile, is_ron, /)")]
+    fn agari_decompositions_py(
+        &self,
+        winning_tile: &str,
+        is_ron: bool,
+    ) -> Result<Vec<Decomposition>> {
+        self.agari_decompositions(winning_tile.parse()?, is_ron)
     }
 
     /// For debug only.
@@ -174,26 +545,34 @@ impl PlayerState {
             .map(|(i, _)| must_tile!(i))
             .collect::<Vec<_>>();
 
-        let zipped_kawa = self.kawa[0]
+        let kawa_rows = self.kawa[0]
             .iter()
             .chain(iter::repeat(&None))
             .zip(self.kawa[1].iter().chain(iter::repeat(&None)))
             .zip(self.kawa[2].iter().chain(iter::repeat(&None)))
             .zip(self.kawa[3].iter().chain(iter::repeat(&None)))
             .take_while(|row| !matches!(row, &(((None, None), None), None)))
+            .map(|(((a, b), c), d)| {
+                [a, b, c, d].map(|item| {
+                    item.as_ref()
+                        .map_or_else(|| "-".to_owned(), |item| item.to_string())
+                })
+            })
+            .collect::<Vec<_>>();
+        // Pad every column to the widest item so that the seats line up.
+        let width = kawa_rows
+            .iter()
+            .flatten()
+            .map(|s| s.chars().count())
+            .max()
+            .unwrap_or_default();
+        let zipped_kawa = kawa_rows
+            .iter()
             .enumerate()
-            .map(|(i, (((a, b), c), d))| {
-                format!(
-                    "{i:2}. {}\t{}\t{}\t{}",
-                    a.as_ref()
-                        .map_or_else(|| "-".to_owned(), |item| item.to_string()),
-                    b.as_ref()
-                        .map_or_else(|| "-".to_owned(), |item| item.to_string()),
-                    c.as_ref()
-                        .map_or_else(|| "-".to_owned(), |item| item.to_string()),
-                    d.as_ref()
-                        .map_or_else(|| "-".to_owned(), |item| item.to_string()),
-                )
+            .map(|(i, [a, b, c, d])| {
+                format!("{i:2}. {a:<width$}  {b:<width$}  {c:<width$}  {d}")
+                    .trim_end()
+                    .to_owned()
             })
             .collect::<Vec<_>>()
             .join("\n");
@@ -244,4 +623,93 @@ kawa:
             self.tiles_left,
         )
     }
+
+    /// Return the table as seen by the player in plain text, laid out for a
+    /// human to play on: a line for each seat, starting from the player, and
+    /// then the tehai, where the tsumo is set apart.
+    ///
+    /// The kawa and the fuuro are written the same way as in `brief_info`.
+    #[pyo3(text_signature = "($self, /)")]
+    #[must_use]
+    pub fn table_info(&self) -> String {
+        let mut ret = format!(
+            "{}{}-{}  kyotaku: {}  tiles left: {}  dora indicators: {}\n",
+            self.bakaze,
+            self.kyoku + 1,
+            self.honba,
+            self.kyotaku,
+            self.tiles_left,
+            self.dora_indicators
+                .iter()
+                .map(|t| t.to_string())
+                .collect::<Vec<_>>()
+                .join(" "),
+        );
+
+        for rel in 0..4 {
+            let seat_wind = must_tile!(tu8!(E) + (rel as u8 + 4 - self.oya) % 4);
+            let kawa = self.kawa[rel]
+                .iter()
+                .flatten()
+                .map(|item| item.to_string())
+                .collect::<Vec<_>>()
+                .join(" ");
+            let mut fuuro: Vec<_> = self.fuuro_overview[rel]
+                .iter()
+                .map(|f| f.iter().map(|t| t.to_string()).collect::<String>())
+                .collect();
+            fuuro.extend(
+                self.ankan_overview[rel]
+                    .iter()
+                    .map(|t| format!("{{{}}}", t.to_string().repeat(4))),
+            );
+            ret += &format!(
+                "{} {:>6}{}  kawa: {kawa}{}\n",
+                seat_wind,
+                self.scores[rel],
+                if self.riichi_declared[rel] {
+                    " riichi"
+                } else {
+                    ""
+                },
+                if fuuro.is_empty() {
+                    String::new()
+                } else {
+                    format!("  fuuro: {}", fuuro.join(" "))
+                },
+            );
+        }
+
+        let mut tehai = self.tehai;
+        let mut akas = self.akas_in_hand;
+        let tsumo = self
+            .last_self_tsumo
+            .filter(|_| tehai.iter().sum::<u8>() % 3 == 2);
+        if let Some(tsumo) = tsumo {
+            tehai[tsumo.deaka().as_usize()] -= 1;
+            if tsumo.is_aka() {
+                akas[tsumo.as_usize() - tu8!(5mr) as usize] -= 1;
+            }
+        }
+        ret += &format!("tehai: {}", tiles_to_string(&tehai, akas));
+        if let Some(tsumo) = tsumo {
+            ret += &format!(" + {tsumo}");
+        }
+        ret
+    }
+}
+
+/// `Option<()>` as a bool.
+mod unit_flag {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    // serde passes the field by reference.
+    #[allow(clippy::trivially_copy_pass_by_ref)]
+    pub fn serialize<S: Serializer>(flag: &Option<()>, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bool(flag.is_some())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<()>, D::Error> {
+        Ok(bool::deserialize(deserializer)?.then_some(()))
+    }
 }
@@ -0,0 +1,347 @@
+use super::PlayerState;
+use crate::algo::agari::AgariCalculator;
+use crate::algo::shanten;
+use crate::must_tile;
+use crate::tile::Tile;
+
+use riichi_macros::{pyclass, pymethods};
+
+/// Chance of a discard of an opponent to be one of the waits, relative to a
+/// tile drawn from the unseen pool. Opponents tend to avoid discarding
+/// dangerous tiles, even more so against a riichi.
+pub(super) const DAMA_RON_FACTOR: f32 = 0.6;
+pub(super) const RIICHI_RON_FACTOR: f32 = 0.3;
+/// Average loss of a deal-in against a riichi.
+const DEAL_IN_LOSS: f32 = 5500.;
+
+/// The comparison between riichi and dama of a tenpai discard.
+#[pyclass]
+#[derive(Debug, Clone, PartialEq)]
+pub struct RiichiEv {
+    pub discard: Tile,
+    /// Tiles that complete the hand after the discard, regardless of yaku.
+    pub waits: Vec<Tile>,
+    /// Number of the unseen copies of `waits`.
+    pub live_tiles: u8,
+    /// Furiten makes both riichi and dama tsumo only.
+    pub furiten: bool,
+    pub riichi: EvEstimate,
+    pub dama: EvEstimate,
+}
+
+#[pyclass]
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EvEstimate {
+    #[pyo3(get)]
+    pub win_rate: f32,
+    /// Average points gained on a win, including honba, kyotaku and the
+    /// player's own riichi stick.
+    #[pyo3(get)]
+    pub win_value: f32,
+    #[pyo3(get)]
+    pub deal_in_rate: f32,
+    /// Expected point delta of the rest of the kyoku.
+    #[pyo3(get)]
+    pub ev: f32,
+}
+
+/// Sums of the points of the waits, each weighted by its live copies.
+#[derive(Default)]
+pub(super) struct Values {
+    pub(super) ron: f32,
+    pub(super) tsumo: f32,
+    /// Live copies of the waits that can be ronned, i.e. with yaku.
+    pub(super) ron_tiles: u8,
+}
+
+/// Every unseen tile is equally likely to be drawn by anyone, see
+/// `PlayerState::riichi_ev`.
+pub(super) struct DrawModel {
+    pub(super) unseen_total: f32,
+    pub(super) own_draws: i32,
+    pub(super) opponent_discards: i32,
+    /// Honba and kyotaku, which go to the winner.
+    pub(super) stakes: f32,
+}
+
+impl DrawModel {
+    /// Returns the win rate and the average points gained on a win, plus
+    /// `extra`. `ron_factor` is the chance of a discard of an opponent to hit
+    /// the waits, relative to a draw.
+    pub(super) fn estimate(
+        &self,
+        values: &Values,
+        live: u8,
+        ron_factor: f32,
+        extra: f32,
+    ) -> (f32, f32) {
+        let tsumo_hit = live as f32 / self.unseen_total;
+        let ron_hit = values.ron_tiles as f32 / self.unseen_total * ron_factor;
+        let miss_rate =
+            (1. - tsumo_hit).powi(self.own_draws) * (1. - ron_hit).powi(self.opponent_discards);
+        let win_rate = 1. - miss_rate;
+
+        let tsumo_weight = tsumo_hit * self.own_draws as f32;
+        let ron_weight = ron_hit * self.opponent_discards as f32;
+        let mut win_value = self.stakes + extra;
+        if tsumo_weight + ron_weight > 0. {
+            // Both sums of values are weighted by live copies.
+            let tsumo_avg = values.tsumo / live.max(1) as f32;
+            let ron_avg = values.ron / values.ron_tiles.max(1) as f32;
+            win_value +=
+                tsumo_weight.mul_add(tsumo_avg, ron_weight * ron_avg) / (tsumo_weight + ron_weight);
+        }
+        (win_rate, win_value)
+    }
+}
+
+#[pymethods]
+impl RiichiEv {
+    #[getter]
+    fn discard(&self) -> String {
+        self.discard.to_string()
+    }
+    #[getter]
+    fn waits(&self) -> Vec<String> {
+        self.waits.iter().map(|t| t.to_string()).collect()
+    }
+    #[getter]
+    const fn live_tiles(&self) -> u8 {
+        self.live_tiles
+    }
+    #[getter]
+    const fn furiten(&self) -> bool {
+        self.furiten
+    }
+    #[getter]
+    fn riichi(&self) -> EvEstimate {
+        self.riichi.clone()
+    }
+    #[getter]
+    fn dama(&self) -> EvEstimate {
+        self.dama.clone()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("{self:?}")
+    }
+}
+
+#[pymethods]
+impl EvEstimate {
+    fn __repr__(&self) -> String {
+        format!("{self:?}")
+    }
+}
+
+#[pymethods]
+impl PlayerState {
+    /// Returns a list of `RiichiEv`, one for each discard that keeps tenpai.
+    /// The list is empty if riichi cannot be declared now.
+    #[pyo3(name = "riichi_ev")]
+    #[pyo3(text_signature = "($self, /)")]
+    fn riichi_ev_py(&self) -> Vec<RiichiEv> {
+        self.riichi_ev()
+    }
+}
+
+impl PlayerState {
+    /// Estimates the EV of declaring riichi and of staying dama, for each
+    /// discard that keeps tenpai. Returns an empty `Vec` if riichi cannot be
+    /// declared now.
+    ///
+    /// This is a rough model meant for explaining decisions:
+    /// - Every unseen tile is equally likely to be drawn by anyone, and the
+    ///   discards of the opponents hit the waits less often, see
+    ///   `DAMA_RON_FACTOR` and `RIICHI_RON_FACTOR`.
+    /// - Ura doras are counted as at most one han, weighted by the expected
+    ///   number of them.
+    /// - After riichi, every draw is discarded, and each discard deals in at
+    ///   the average `tile_danger` score against the opponents in riichi. With
+    ///   dama, the player is assumed to fold when needed.
+    #[must_use]
+    pub fn riichi_ev(&self) -> Vec<RiichiEv> {
+        if !self.last_cans.can_riichi {
+            return vec![];
+        }
+
+        let unseen = self.tiles_seen.map(|seen| 4 - seen);
+        let unseen_total = unseen.iter().map(|&n| n as f32).sum::<f32>();
+        let own_draws = self.tiles_left.div_ceil(4) as i32;
+        let opponent_discards = self.tiles_left as i32 - own_draws;
+
+        // Average deal-in rate of one locked discard after riichi.
+        let deal_in_per_discard = (1..4)
+            .filter(|&rel| self.riichi_declared[rel as usize])
+            .map(|rel| {
+                self.tile_danger(rel)
+                    .iter()
+                    .map(|d| unseen[d.tile.as_usize()] as f32 * d.score / 100.)
+                    .sum::<f32>()
+                    / unseen_total
+            })
+            .sum::<f32>()
+            .min(1.);
+
+        let model = DrawModel {
+            unseen_total,
+            own_draws,
+            opponent_discards,
+            stakes: (self.honba as u32 * 300 + self.kyotaku as u32 * 1000) as f32,
+        };
+
+        (0..34)
+            .filter(|&tid| self.tehai[tid] > 0)
+            .filter_map(|tid| {
+                let mut tehai = self.tehai;
+                tehai[tid] -= 1;
+                if shanten::calc_all(&tehai, self.tehai_len_div3) != 0 {
+                    return None;
+                }
+
+                let tile = must_tile!(tid);
+                let aka_only =
+                    tile.akaize() != tile && self.akas_in_hand[tid / 9] == self.tehai[tid];
+                let discard = if aka_only { tile.akaize() } else { tile };
+                let waits: Vec<_> = (0..34)
+                    .filter(|&w| {
+                        let mut tehai_after = tehai;
+                        tehai_after[w] += 1;
+                        tehai[w] < 4 && shanten::calc_all(&tehai_after, self.tehai_len_div3) == -1
+                    })
+                    .collect();
+                let furiten = waits.iter().any(|&w| w == tid || self.discarded_tiles[w]);
+                let live_tiles = waits.iter().map(|&w| unseen[w]).sum::<u8>();
+
+                let doras = self.doras_owned[0] - self.dora_factor[tid] - discard.is_aka() as u8;
+                let (mut dama_values, mut riichi_values) = (Values::default(), Values::default());
+                for &w in &waits {
+                    self.add_wait_values(
+                        &tehai,
+                        w,
+                        doras,
+                        &unseen,
+                        false,
+                        &mut dama_values,
+                        &mut riichi_values,
+                    );
+                }
+                if furiten {
+                    dama_values.ron_tiles = 0;
+                    riichi_values.ron_tiles = 0;
+                }
+
+                let (win_rate, win_value) =
+                    model.estimate(&dama_values, live_tiles, DAMA_RON_FACTOR, 0.);
+                let dama = EvEstimate {
+                    win_rate,
+                    win_value,
+                    deal_in_rate: 0.,
+                    ev: win_rate * win_value,
+                };
+
+                // The riichi stick comes back on a win.
+                let (win_rate, win_value) =
+                    model.estimate(&riichi_values, live_tiles, RIICHI_RON_FACTOR, 1000.);
+                let deal_in_rate =
+                    (1. - win_rate) * (1. - (1. - deal_in_per_discard).powi(own_draws));
+                let riichi = EvEstimate {
+                    win_rate,
+                    win_value,
+                    deal_in_rate,
+                    ev: win_rate.mul_add(win_value, -deal_in_rate.mul_add(DEAL_IN_LOSS, 1000.)),
+                };
+
+                Some(RiichiEv {
+                    discard,
+                    waits: waits.into_iter().map(|w| must_tile!(w)).collect(),
+                    live_tiles,
+                    furiten,
+                    riichi,
+                    dama,
+                })
+            })
+            .collect()
+    }
+
+    /// Adds the points of winning on `wait` with `tehai` (3n+1) to `dama` and
+    /// `riichi`, weighted by the live copies of `wait`. `riichi` counts riichi
+    /// and the expected ura doras, and so does `dama` for riichi itself if
+    /// `in_riichi`, which makes it the value of the hand as it is.
+    #[allow(clippy::too_many_arguments)]
+    pub(super) fn add_wait_values(
+        &self,
+        tehai: &[u8; 34],
+        wait: usize,
+        doras: u8,
+        unseen: &[u8; 34],
+        in_riichi: bool,
+        dama: &mut Values,
+        riichi: &mut Values,
+    ) {
+        let mut tehai_full = *tehai;
+        tehai_full[wait] += 1;
+        let doras = doras + self.dora_factor[wait];
+
+        // Expected number of ura doras, where each indicator is a random
+        // unseen tile.
+        let mut counts = tehai_full;
+        for t in &self.ankan_overview[0] {
+            counts[t.as_usize()] += 4;
+        }
+        let unseen_total = unseen.iter().map(|&n| n as f32).sum::<f32>();
+        let ura_per_indicator = counts
+            .iter()
+            .enumerate()
+            .map(|(tid, &n)| n as f32 * unseen[must_tile!(tid).prev().as_usize()] as f32)
+            .sum::<f32>()
+            / unseen_total;
+        let ura_weight = (ura_per_indicator * self.dora_indicators.len() as f32).min(1.);
+
+        let is_oya = self.oya == 0;
+        let points = |is_ron: bool, additional_hans: u8, doras: u8| {
+            let calc = AgariCalculator {
+                tehai: &tehai_full,
+                is_menzen: self.is_menzen,
+                chis: &self.chis,
+                pons: &self.pons,
+                minkans: &self.minkans,
+                ankans: &self.ankans,
+                bakaze: self.bakaze.as_u8(),
+                jikaze: self.jikaze.as_u8(),
+                winning_tile: wait as u8,
+                is_ron,
+                kuitan: self.rule.kuitan,
+            };
+            calc.agari(additional_hans, doras).map(|agari| {
+                let point = agari.into_point(is_oya);
+                if is_ron {
+                    point.ron as f32
+                } else {
+                    point.tsumo_total(is_oya) as f32
+                }
+            })
+        };
+        let with_ura = |is_ron: bool, additional_hans: u8| {
+            let without = points(is_ron, additional_hans, doras).unwrap_or_default();
+            let with = points(is_ron, additional_hans, doras + 1).unwrap_or_default();
+            (with - without).mul_add(ura_weight, without)
+        };
+
+        let live = unseen[wait];
+        let live_f = live as f32;
+        let riichi_hans = in_riichi as u8;
+        if let Some(ron) = points(true, riichi_hans, doras) {
+            dama.ron += ron * live_f;
+            dama.ron_tiles += live;
+        }
+        // 門前清自摸和
+        let menzen_tsumo = self.is_menzen as u8;
+        dama.tsumo += points(false, riichi_hans + menzen_tsumo, doras).unwrap_or_default() * live_f;
+
+        // 立直, plus 門前清自摸和 for tsumo.
+        riichi.ron += with_ura(true, 1) * live_f;
+        riichi.ron_tiles += live;
+        riichi.tsumo += with_ura(false, 2) * live_f;
+    }
+}
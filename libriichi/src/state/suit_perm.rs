@@ -0,0 +1,112 @@
+use anyhow::{ensure, Result};
+use ndarray::prelude::*;
+
+/// A permutation of the three suits, used for data augmentation.
+///
+/// Suit `s`, which is 0 for m, 1 for p and 2 for s, is mapped to suit
+/// `self.0[s]`. Jihai are left as is. Since doras are computed within a suit
+/// and akas are 5s, a game played under a permutation is still a valid game
+/// with the same outcome.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SuitPerm([u8; 3]);
+
+impl Default for SuitPerm {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl SuitPerm {
+    pub const IDENTITY: Self = Self([0, 1, 2]);
+    pub const ALL: [Self; 6] = [
+        Self([0, 1, 2]),
+        Self([0, 2, 1]),
+        Self([1, 0, 2]),
+        Self([1, 2, 0]),
+        Self([2, 0, 1]),
+        Self([2, 1, 0]),
+    ];
+
+    pub fn new(perm: [u8; 3]) -> Result<Self> {
+        let mut sorted = perm;
+        sorted.sort_unstable();
+        ensure!(
+            sorted == [0, 1, 2],
+            "{perm:?} is not a permutation of suits"
+        );
+        Ok(Self(perm))
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn as_array(self) -> [u8; 3] {
+        self.0
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn is_identity(self) -> bool {
+        self.0[0] == 0 && self.0[1] == 1 && self.0[2] == 2
+    }
+
+    /// Maps a tile ID of the 37 tiles, where the akas are 34 to 36.
+    #[inline]
+    #[must_use]
+    pub const fn tile_id(self, tid: usize) -> usize {
+        match tid {
+            0..=26 => self.0[tid / 9] as usize * 9 + tid % 9,
+            34..=36 => 34 + self.0[tid - 34] as usize,
+            _ => tid,
+        }
+    }
+
+    /// Maps an action, or a tile of kan when `at_kan_select` is true. Only
+    /// the discards and the kans carry a tile.
+    #[inline]
+    #[must_use]
+    pub const fn action(self, action: usize, at_kan_select: bool) -> usize {
+        if at_kan_select || action < 37 {
+            self.tile_id(action)
+        } else {
+            action
+        }
+    }
+
+    /// Applies the permutation to an encoded observation, in place.
+    ///
+    /// The 34 columns are permuted on every row, which leaves the rows filled
+    /// as a whole untouched. `aka_rows` are the first rows of the groups of 3
+    /// rows indexed by the suit of an aka, which are permuted as rows.
+    pub fn apply_to_obs(self, mut arr: ArrayViewMut2<'_, f32>, aka_rows: &[usize]) {
+        if self.is_identity() {
+            return;
+        }
+
+        let orig = arr.slice(s![.., ..27]).to_owned();
+        for (suit, &to) in self.0.iter().enumerate() {
+            let to = to as usize;
+            arr.slice_mut(s![.., to * 9..to * 9 + 9])
+                .assign(&orig.slice(s![.., suit * 9..suit * 9 + 9]));
+        }
+
+        for &row in aka_rows {
+            let orig = arr.slice(s![row..row + 3, ..]).to_owned();
+            for (suit, &to) in self.0.iter().enumerate() {
+                arr.row_mut(row + to as usize).assign(&orig.row(suit));
+            }
+        }
+    }
+
+    /// Applies the permutation to a mask returned by `legal_action_mask`.
+    pub fn apply_to_mask(self, mask: &mut [bool], at_kan_select: bool) {
+        if self.is_identity() {
+            return;
+        }
+
+        let len = if at_kan_select { 34 } else { 37 };
+        let orig = mask[..len].to_vec();
+        for (tid, b) in orig.into_iter().enumerate() {
+            mask[self.tile_id(tid)] = b;
+        }
+    }
+}
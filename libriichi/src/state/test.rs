@@ -1,18 +1,35 @@
-use super::{ActionCandidate, PlayerState};
+use super::{
+    ActionCandidate, BaselineOpponentModel, FuritenKind, InvalidReaction, KanKind, KawaMark,
+    OpponentModel, PlayerState, PushFold, SafetyKind, SuitPerm, TileDanger,
+};
+use crate::algo::agari::Agari;
+use crate::consts::{
+    ObsVersion, ACTION_SPACE, OBS_SHAPE, OBS_SHAPE_V2, OBS_SHAPE_V3, OBS_SHAPE_V4,
+};
 use crate::hand::{hand, hand_with_aka, tile37_to_vec};
 use crate::mjai::Event;
-use crate::{must_tile, t, tuz};
+use crate::rule::{Kuikae, RuleSet};
+use crate::tile::Tile;
+use crate::{must_tile, t, tu8, tuz};
 use std::convert::TryInto;
 
+use ndarray::{s, ArrayView2, ArrayViewMut2};
+
 // This is not only a helper but it also tests `encode_obs`.
 fn state_from_log(player_id: u8, log: &str) -> PlayerState {
-    let mut ps = PlayerState::new(player_id);
+    state_from_log_with_rule(player_id, RuleSet::default(), log)
+}
+
+fn state_from_log_with_rule(player_id: u8, rule: RuleSet, log: &str) -> PlayerState {
+    let mut ps = PlayerState::with_rule(player_id, rule);
     for line in log.trim().split('\n') {
         let cans = ps.update_json(line).unwrap();
         if cans.can_act() {
-            let _encoded = ps.encode_obs(false);
+            let (_, mask) = ps.encode_obs(false);
+            assert_eq!(mask.to_vec(), ps.legal_action_mask(false));
             if cans.can_daiminkan || cans.can_kakan || cans.can_ankan {
-                let _encoded = ps.encode_obs(true);
+                let (_, mask) = ps.encode_obs(true);
+                assert_eq!(mask.to_vec(), ps.legal_action_mask(true));
             }
         }
     }
@@ -191,18 +208,21 @@ fn furiten() {
             [t!(?); 13],
             [t!(?); 13],
         ],
-    });
+    })
+    .unwrap();
     ps.update(&Event::Tsumo {
         actor: 0,
         pai: t!(8s),
-    });
+    })
+    .unwrap();
     assert!(ps.shanten == 1);
     assert!(ps.waits.iter().all(|&b| !b));
     ps.update(&Event::Dahai {
         actor: 0,
         pai: t!(5s),
         tsumogiri: false,
-    });
+    })
+    .unwrap();
     assert!(ps.shanten == 0);
     assert!(ps.waits[tuz!(1m)] && ps.waits[tuz!(4m)] && ps.waits[tuz!(7m)]);
     assert!(!ps.at_furiten);
@@ -210,35 +230,43 @@ fn furiten() {
     ps.update(&Event::Tsumo {
         actor: 1,
         pai: t!(?),
-    });
-    let cans = ps.update(&Event::Dahai {
-        actor: 1,
-        pai: t!(1m),
-        tsumogiri: false,
-    });
+    })
+    .unwrap();
+    let cans = ps
+        .update(&Event::Dahai {
+            actor: 1,
+            pai: t!(1m),
+            tsumogiri: false,
+        })
+        .unwrap();
     assert!(!ps.at_furiten);
     assert!(cans.can_ron_agari);
 
     ps.update(&Event::Tsumo {
         actor: 2,
         pai: t!(?),
-    });
+    })
+    .unwrap();
     assert!(ps.at_furiten);
     ps.update(&Event::Dahai {
         actor: 2,
         pai: t!(1s),
         tsumogiri: true,
-    });
+    })
+    .unwrap();
 
     ps.update(&Event::Tsumo {
         actor: 3,
         pai: t!(?),
-    });
-    let cans = ps.update(&Event::Dahai {
-        actor: 3,
-        pai: t!(1m),
-        tsumogiri: false,
-    });
+    })
+    .unwrap();
+    let cans = ps
+        .update(&Event::Dahai {
+            actor: 3,
+            pai: t!(1m),
+            tsumogiri: false,
+        })
+        .unwrap();
     assert!(ps.shanten == 0);
     assert!(ps.waits[tuz!(1m)] && ps.waits[tuz!(4m)] && ps.waits[tuz!(7m)]);
     assert!(ps.at_furiten);
@@ -247,94 +275,118 @@ fn furiten() {
     ps.update(&Event::Tsumo {
         actor: 0,
         pai: t!(3s),
-    });
+    })
+    .unwrap();
     assert!(ps.at_furiten);
     ps.update(&Event::Dahai {
         actor: 0,
         pai: t!(3s),
         tsumogiri: true,
-    });
+    })
+    .unwrap();
     assert!(!ps.at_furiten);
 
     ps.update(&Event::Tsumo {
         actor: 1,
         pai: t!(?),
-    });
+    })
+    .unwrap();
     ps.update(&Event::Dahai {
         actor: 1,
         pai: t!(P),
         tsumogiri: true,
-    });
+    })
+    .unwrap();
 
     ps.update(&Event::Tsumo {
         actor: 2,
         pai: t!(?),
-    });
+    })
+    .unwrap();
     ps.update(&Event::Dahai {
         actor: 2,
         pai: t!(C),
         tsumogiri: true,
-    });
+    })
+    .unwrap();
     ps.update(&Event::Tsumo {
         actor: 3,
         pai: t!(?),
-    });
-    let cans = ps.update(&Event::Dahai {
-        actor: 3,
-        pai: t!(1m),
-        tsumogiri: false,
-    });
+    })
+    .unwrap();
+    let cans = ps
+        .update(&Event::Dahai {
+            actor: 3,
+            pai: t!(1m),
+            tsumogiri: false,
+        })
+        .unwrap();
     assert!(!ps.at_furiten);
     assert!(cans.can_ron_agari);
     assert_eq!(ps.agari_points(true, &[]).unwrap().ron, 5800);
 
     // riichi furiten test
-    let cans = ps.update(&Event::Tsumo {
-        actor: 0,
-        pai: t!(N),
-    });
+    let cans = ps
+        .update(&Event::Tsumo {
+            actor: 0,
+            pai: t!(N),
+        })
+        .unwrap();
     assert!(cans.can_riichi);
-    ps.update(&Event::Reach { actor: 0 });
+    ps.update(&Event::Reach {
+        actor: 0,
+        open: false,
+    })
+    .unwrap();
     ps.update(&Event::Dahai {
         actor: 0,
         pai: t!(N),
         tsumogiri: true,
-    });
-    ps.update(&Event::ReachAccepted { actor: 0 });
+    })
+    .unwrap();
+    ps.update(&Event::ReachAccepted { actor: 0 }).unwrap();
 
     ps.update(&Event::Tsumo {
         actor: 1,
         pai: t!(?),
-    });
+    })
+    .unwrap();
     ps.update(&Event::Dahai {
         actor: 1,
         pai: t!(9m),
         tsumogiri: true,
-    });
+    })
+    .unwrap();
     ps.update(&Event::Tsumo {
         actor: 2,
         pai: t!(?),
-    });
+    })
+    .unwrap();
     ps.update(&Event::Dahai {
         actor: 2,
         pai: t!(9m),
         tsumogiri: true,
-    });
+    })
+    .unwrap();
     ps.update(&Event::Tsumo {
         actor: 3,
         pai: t!(?),
-    });
+    })
+    .unwrap();
     ps.update(&Event::Dahai {
         actor: 3,
         pai: t!(9m),
         tsumogiri: true,
-    });
+    })
+    .unwrap();
 
     // tsumo agari minogashi
-    let cans = ps.update(&Event::Tsumo {
-        actor: 0,
-        pai: t!(1m),
-    });
+    let cans = ps
+        .update(&Event::Tsumo {
+            actor: 0,
+            pai: t!(1m),
+        })
+        .unwrap();
     assert!(ps.waits[tuz!(1m)] && ps.waits[tuz!(4m)] && ps.waits[tuz!(7m)]);
     assert!(!ps.at_furiten);
     assert!(cans.can_tsumo_agari);
@@ -342,36 +394,44 @@ fn furiten() {
         actor: 0,
         pai: t!(1m),
         tsumogiri: true,
-    });
+    })
+    .unwrap();
     assert!(ps.at_furiten); // furiten forever from now on
 
     ps.update(&Event::Tsumo {
         actor: 1,
         pai: t!(?),
-    });
+    })
+    .unwrap();
     ps.update(&Event::Dahai {
         actor: 1,
         pai: t!(4s),
         tsumogiri: true,
-    });
+    })
+    .unwrap();
     ps.update(&Event::Tsumo {
         actor: 2,
         pai: t!(?),
-    });
+    })
+    .unwrap();
     ps.update(&Event::Dahai {
         actor: 2,
         pai: t!(4s),
         tsumogiri: true,
-    });
+    })
+    .unwrap();
     ps.update(&Event::Tsumo {
         actor: 3,
         pai: t!(?),
-    });
-    let cans = ps.update(&Event::Dahai {
-        actor: 3,
-        pai: t!(7m),
-        tsumogiri: true,
-    });
+    })
+    .unwrap();
+    let cans = ps
+        .update(&Event::Dahai {
+            actor: 3,
+            pai: t!(7m),
+            tsumogiri: true,
+        })
+        .unwrap();
     assert!(ps.waits[tuz!(1m)] && ps.waits[tuz!(4m)] && ps.waits[tuz!(7m)]);
     assert!(ps.at_furiten);
     assert!(!cans.can_ron_agari);
@@ -379,49 +439,60 @@ fn furiten() {
     ps.update(&Event::Tsumo {
         actor: 0,
         pai: t!(8m),
-    });
+    })
+    .unwrap();
     ps.update(&Event::Dahai {
         actor: 0,
         pai: t!(8m),
         tsumogiri: true,
-    });
+    })
+    .unwrap();
     assert!(ps.at_furiten); // still furiten
 
     ps.update(&Event::Tsumo {
         actor: 1,
         pai: t!(?),
-    });
+    })
+    .unwrap();
     ps.update(&Event::Dahai {
         actor: 1,
         pai: t!(E),
         tsumogiri: true,
-    });
+    })
+    .unwrap();
     ps.update(&Event::Tsumo {
         actor: 2,
         pai: t!(?),
-    });
-    let cans = ps.update(&Event::Dahai {
-        actor: 2,
-        pai: t!(4m),
-        tsumogiri: true,
-    });
+    })
+    .unwrap();
+    let cans = ps
+        .update(&Event::Dahai {
+            actor: 2,
+            pai: t!(4m),
+            tsumogiri: true,
+        })
+        .unwrap();
     assert!(ps.at_furiten);
     assert!(!cans.can_ron_agari);
     ps.update(&Event::Tsumo {
         actor: 3,
         pai: t!(?),
-    });
+    })
+    .unwrap();
     ps.update(&Event::Dahai {
         actor: 3,
         pai: t!(E),
         tsumogiri: true,
-    });
+    })
+    .unwrap();
 
     // tsumo agari is always possible regardless of furiten
-    let cans = ps.update(&Event::Tsumo {
-        actor: 0,
-        pai: t!(4m),
-    });
+    let cans = ps
+        .update(&Event::Tsumo {
+            actor: 0,
+            pai: t!(4m),
+        })
+        .unwrap();
     assert!(ps.waits[0] && ps.waits[3] && ps.waits[6]);
     assert!(ps.at_furiten);
     assert!(cans.can_tsumo_agari);
@@ -447,86 +518,103 @@ fn dora_count_after_kan() {
             [t!(?); 13],
             [t!(?); 13],
         ],
-    });
+    })
+    .unwrap();
     ps.update(&Event::Tsumo {
         actor: 0,
         pai: t!(8s),
-    });
+    })
+    .unwrap();
     assert_eq!(ps.doras_owned[0], 2);
 
     ps.update(&Event::Ankan {
         actor: 0,
         consumed: [t!(1s); 4],
-    });
+    })
+    .unwrap();
     ps.update(&Event::Dora {
         dora_marker: t!(9s),
-    });
+    })
+    .unwrap();
     ps.update(&Event::Tsumo {
         actor: 0,
         pai: t!(5pr),
-    });
+    })
+    .unwrap();
     assert_eq!(ps.doras_owned[0], 7);
     ps.update(&Event::Dahai {
         actor: 0,
         pai: t!(E),
         tsumogiri: true,
-    });
+    })
+    .unwrap();
     assert_eq!(ps.doras_owned[0], 6);
 
     ps.update(&Event::Tsumo {
         actor: 1,
         pai: t!(?),
-    });
+    })
+    .unwrap();
     ps.update(&Event::Dahai {
         actor: 1,
         pai: t!(5p),
         tsumogiri: true,
-    });
+    })
+    .unwrap();
 
     ps.update(&Event::Pon {
         actor: 0,
         target: 1,
         pai: t!(5p),
         consumed: t![5pr, 5p],
-    });
+    })
+    .unwrap();
     assert_eq!(ps.doras_owned[0], 6);
     ps.update(&Event::Dahai {
         actor: 0,
         pai: t!(E),
         tsumogiri: false,
-    });
+    })
+    .unwrap();
     assert_eq!(ps.doras_owned[0], 5);
 
     ps.update(&Event::Tsumo {
         actor: 1,
         pai: t!(?),
-    });
+    })
+    .unwrap();
     ps.update(&Event::Dahai {
         actor: 1,
         pai: t!(P),
         tsumogiri: true,
-    });
+    })
+    .unwrap();
     ps.update(&Event::Tsumo {
         actor: 2,
         pai: t!(?),
-    });
+    })
+    .unwrap();
     ps.update(&Event::Dahai {
         actor: 2,
         pai: t!(P),
         tsumogiri: true,
-    });
+    })
+    .unwrap();
 
     ps.update(&Event::Tsumo {
         actor: 3,
         pai: t!(?),
-    });
+    })
+    .unwrap();
     ps.update(&Event::Ankan {
         actor: 3,
         consumed: [t!(1m); 4],
-    });
+    })
+    .unwrap();
     ps.update(&Event::Dora {
         dora_marker: t!(4p),
-    });
+    })
+    .unwrap();
     assert_eq!(ps.doras_owned[0], 8);
 }
 
@@ -1217,3 +1305,2023 @@ fn double_chankan_ron() {
         .unwrap();
     assert!(!cans.can_ron_agari);
 }
+
+#[test]
+fn forced_and_optional_call() {
+    let mut ps = PlayerState::new(0);
+    ps.update(&Event::StartKyoku {
+        bakaze: t!(E),
+        kyoku: 1,
+        honba: 0,
+        kyotaku: 0,
+        oya: 0,
+        scores: [25000; 4],
+        dora_marker: t!(9m),
+        tehais: [
+            tile37_to_vec(&hand_with_aka("1139m 258p 369s 567z").unwrap())
+                .try_into()
+                .unwrap(),
+            [t!(?); 13],
+            [t!(?); 13],
+            [t!(?); 13],
+        ],
+    })
+    .unwrap();
+
+    let cans = ps
+        .update(&Event::Tsumo {
+            actor: 0,
+            pai: t!(4s),
+        })
+        .unwrap();
+    assert!(cans.can_discard);
+    assert!(cans.is_forced());
+    assert!(!cans.is_optional_call());
+
+    let cans = ps
+        .update(&Event::Dahai {
+            actor: 0,
+            pai: t!(4s),
+            tsumogiri: true,
+        })
+        .unwrap();
+    assert!(!cans.is_forced());
+    assert!(!cans.is_optional_call());
+
+    ps.update(&Event::Tsumo {
+        actor: 1,
+        pai: t!(?),
+    })
+    .unwrap();
+    let cans = ps
+        .update(&Event::Dahai {
+            actor: 1,
+            pai: t!(1m),
+            tsumogiri: true,
+        })
+        .unwrap();
+    assert!(cans.can_pon);
+    assert!(!cans.is_forced());
+    assert!(cans.is_optional_call());
+}
+
+#[test]
+fn settle() {
+    // player 2's perspective, with player 1 being the oya
+    let ps = PlayerState {
+        player_id: 2,
+        oya: 3,
+        honba: 2,
+        kyotaku: 1,
+        ..Default::default()
+    };
+
+    // ko 30 fu 3 han tsumo: 1000/2000
+    let deltas = ps.settle(&Agari::Normal { fu: 30, han: 3 }, 3, None);
+    assert_eq!(deltas, [-1200, -2200, -1200, 4000 + 600 + 1000]);
+    assert_eq!(deltas.iter().sum::<i32>(), 1000);
+
+    // oya 40 fu 2 han ron: 3900
+    let deltas = ps.settle(&Agari::Normal { fu: 40, han: 2 }, 1, Some(0));
+    assert_eq!(deltas, [-3900 - 600, 3900 + 600 + 1000, 0, 0]);
+
+    // oya yakuman tsumo
+    let deltas = ps.settle(&Agari::Yakuman(1), 1, None);
+    assert_eq!(deltas, [-16200, 48600 + 1000, -16200, -16200]);
+}
+
+#[test]
+fn public_view_from() {
+    let log = r#"
+        {"type":"start_kyoku","bakaze":"E","dora_marker":"4p","kyoku":2,"honba":1,"kyotaku":0,"oya":1,"scores":[24000,26000,27000,23000],"tehais":[["1m","2m","3m","5pr","5p","6p","7s","8s","9s","E","E","S","W"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"]]}
+        {"type":"tsumo","actor":1,"pai":"?"}
+        {"type":"dahai","actor":1,"pai":"E","tsumogiri":false}
+        {"type":"pon","actor":0,"target":1,"pai":"E","consumed":["E","E"]}
+        {"type":"dahai","actor":0,"pai":"W","tsumogiri":false}
+        {"type":"tsumo","actor":1,"pai":"?"}
+        {"type":"dahai","actor":1,"pai":"9p","tsumogiri":true}
+    "#;
+    let ps = state_from_log(0, log);
+    assert_eq!(ps.scores, [24000, 26000, 27000, 23000]);
+    assert_eq!(ps.oya, 1);
+
+    let view = ps.public_view_from(1);
+    assert_eq!(view.player_id, 1);
+    assert_eq!(view.oya, 0);
+    assert_eq!(view.jikaze, t!(E));
+    assert_eq!(view.scores, [26000, 27000, 23000, 24000]);
+    assert_eq!(view.rank, 1);
+    assert_eq!(view.tehai, [0; 34]);
+    assert_eq!(view.akas_in_hand, [0; 3]);
+    assert_eq!(view.kawa_overview[0].as_slice(), &t![E, 9p]);
+    assert_eq!(view.kawa_overview[3].as_slice(), &[t!(W)]);
+    assert_eq!(view.fuuro_overview[3].len(), 1);
+    assert_eq!(view.pons.as_slice(), &[] as &[u8]);
+    assert_eq!(view.doras_owned[3], 0);
+
+    // The original player's hidden tiles must not leak.
+    assert_eq!(view.tiles_seen[tuz!(E)], 3);
+    assert_eq!(view.tiles_seen[tuz!(5p)], 0); // both in the original hand
+    assert_eq!(view.tiles_seen[tuz!(4p)], 1);
+    assert_eq!(view.doras_seen, 0);
+
+    let own = ps.public_view_from(0);
+    assert_eq!(own.scores, ps.scores);
+    assert_eq!(own.oya, ps.oya);
+    assert_eq!(own.pons.as_slice(), &[tu8!(E)]);
+    assert!(!own.is_menzen);
+    assert_eq!(own.tehai_len_div3, 3);
+    assert_eq!(own.tehai, [0; 34]);
+}
+
+#[test]
+fn kawa_overflow() {
+    let mut ps = PlayerState::new(0);
+    ps.update(&Event::StartKyoku {
+        bakaze: t!(E),
+        kyoku: 1,
+        honba: 0,
+        kyotaku: 0,
+        oya: 0,
+        scores: [25000; 4],
+        dora_marker: t!(1m),
+        tehais: [
+            tile37_to_vec(&hand_with_aka("1139m 258p 369s 567z").unwrap())
+                .try_into()
+                .unwrap(),
+            [t!(?); 13],
+            [t!(?); 13],
+            [t!(?); 13],
+        ],
+    })
+    .unwrap();
+
+    // A malformed log where one seat keeps discarding without anyone else
+    // getting a turn.
+    for tid in 0..24_u8 {
+        ps.update(&Event::Tsumo {
+            actor: 1,
+            pai: t!(?),
+        })
+        .unwrap();
+        ps.update(&Event::Dahai {
+            actor: 1,
+            pai: must_tile!(tid),
+            tsumogiri: true,
+        })
+        .unwrap();
+    }
+    assert_eq!(ps.kawa[1].len(), 24);
+
+    ps.update(&Event::Tsumo {
+        actor: 1,
+        pai: t!(?),
+    })
+    .unwrap();
+    let res = ps.update(&Event::Dahai {
+        actor: 1,
+        pai: t!(C),
+        tsumogiri: true,
+    });
+    let err = res.unwrap_err().to_string();
+    assert!(err.contains("kawa"), "{err}");
+    assert_eq!(ps.kawa[1].len(), 24);
+}
+
+#[test]
+fn kawa_overflow_on_pon() {
+    let mut ps = PlayerState::new(0);
+    ps.update(&Event::StartKyoku {
+        bakaze: t!(E),
+        kyoku: 1,
+        honba: 0,
+        kyotaku: 0,
+        oya: 0,
+        scores: [25000; 4],
+        dora_marker: t!(1m),
+        tehais: [
+            tile37_to_vec(&hand_with_aka("1139m 258p 369s 567z").unwrap())
+                .try_into()
+                .unwrap(),
+            [t!(?); 13],
+            [t!(?); 13],
+            [t!(?); 13],
+        ],
+    })
+    .unwrap();
+
+    // A malformed log where seat 2 fills its kawa.
+    for tid in 0..24_u8 {
+        ps.update(&Event::Tsumo {
+            actor: 2,
+            pai: t!(?),
+        })
+        .unwrap();
+        ps.update(&Event::Dahai {
+            actor: 2,
+            pai: must_tile!(tid),
+            tsumogiri: true,
+        })
+        .unwrap();
+    }
+    ps.update(&Event::Tsumo {
+        actor: 0,
+        pai: t!(9p),
+    })
+    .unwrap();
+    ps.update(&Event::Dahai {
+        actor: 0,
+        pai: t!(9p),
+        tsumogiri: true,
+    })
+    .unwrap();
+
+    // The pon would pad seat 1 and then seat 2, which is full, so nothing
+    // must be changed, not even seat 1.
+    let before = ps.clone();
+    let res = ps.update(&Event::Pon {
+        actor: 3,
+        target: 0,
+        pai: t!(9p),
+        consumed: [t!(9p), t!(9p)],
+    });
+    let err = res.unwrap_err().to_string();
+    assert!(err.contains("kawa"), "{err}");
+    assert_eq!(
+        ps.kawa.iter().map(|k| k.len()).collect::<Vec<_>>(),
+        [1, 0, 24, 0],
+    );
+    assert!(ps.fuuro_overview[3].is_empty());
+    assert!(ps.called_tiles[3].is_empty());
+    assert!(ps.intermediate_chi_pon.is_none());
+    assert_eq!(ps.next_tsumo_seat, before.next_tsumo_seat);
+    let last = ps.kawa[0].last().unwrap().unwrap();
+    assert!(last.sutehai.claimed_by.is_none());
+}
+
+#[test]
+fn discard_missing_aka() {
+    let log = r#"
+        {"type":"start_kyoku","bakaze":"E","dora_marker":"1m","kyoku":1,"honba":0,"kyotaku":0,"oya":0,"scores":[25000,25000,25000,25000],"tehais":[["1m","2m","3m","5m","5pr","6p","7s","8s","9s","E","E","S","S"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"]]}
+        {"type":"tsumo","actor":0,"pai":"N"}
+    "#;
+    let mut ps = state_from_log(0, log);
+    let before = ps.clone();
+
+    // A malformed log discarding a tile that is not in hand: an aka, a plain
+    // 5 when only the aka is left, and any other tile.
+    for (pai, name) in [(t!(5mr), "5mr"), (t!(5p), "5p"), (t!(9p), "9p")] {
+        let res = ps.update(&Event::Dahai {
+            actor: 0,
+            pai,
+            tsumogiri: false,
+        });
+        let err = res.unwrap_err().to_string();
+        assert!(err.contains(name), "{err}");
+
+        assert_eq!(ps.akas_in_hand, before.akas_in_hand);
+        assert_eq!(ps.tehai, before.tehai);
+        assert_eq!(*ps.kawa_overview, *before.kawa_overview);
+        assert!(ps.kawa.iter().all(|k| k.is_empty()));
+        assert_eq!(ps.last_kawa_tile, before.last_kawa_tile);
+        assert_eq!(ps.forbidden_tiles, before.forbidden_tiles);
+    }
+
+    // So is a call consuming tiles that are not in hand.
+    ps.update(&Event::Dahai {
+        actor: 0,
+        pai: t!(N),
+        tsumogiri: true,
+    })
+    .unwrap();
+    let before = ps.clone();
+    let res = ps.update(&Event::Pon {
+        actor: 0,
+        target: 3,
+        pai: t!(9p),
+        consumed: [t!(9p), t!(9p)],
+    });
+    let err = res.unwrap_err().to_string();
+    assert!(err.contains("9p"), "{err}");
+    assert_eq!(ps.tehai, before.tehai);
+    assert!(ps.fuuro_overview[0].is_empty());
+    assert_eq!(
+        ps.kawa.iter().map(|k| k.len()).collect::<Vec<_>>(),
+        before.kawa.iter().map(|k| k.len()).collect::<Vec<_>>(),
+    );
+}
+
+#[test]
+fn kyotaku_carry_over() {
+    let log = r#"
+        {"type":"start_kyoku","bakaze":"E","dora_marker":"1m","kyoku":1,"honba":0,"kyotaku":0,"oya":0,"scores":[25000,25000,25000,25000],"tehais":[["1m","2m","3m","4p","5p","6p","7s","8s","9s","E","E","S","S"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"]]}
+        {"type":"tsumo","actor":0,"pai":"N"}
+        {"type":"reach","actor":0}
+        {"type":"dahai","actor":0,"pai":"N","tsumogiri":true}
+        {"type":"reach_accepted","actor":0}
+        {"type":"tsumo","actor":1,"pai":"?"}
+        {"type":"dahai","actor":1,"pai":"N","tsumogiri":true}
+        {"type":"tsumo","actor":2,"pai":"?"}
+        {"type":"dahai","actor":2,"pai":"N","tsumogiri":true}
+        {"type":"tsumo","actor":3,"pai":"?"}
+        {"type":"dahai","actor":3,"pai":"N","tsumogiri":true}
+        {"type":"ryukyoku","deltas":[0,0,0,0]}
+        {"type":"end_kyoku"}
+    "#;
+    // The four winds of the first go-around abort the kyoku.
+    let mut ps = PlayerState::new(0);
+    ps.set_strict(true);
+    for line in log.trim().lines() {
+        ps.update_json(line).unwrap();
+    }
+    assert_eq!(ps.kyotaku, 1);
+    assert_eq!(ps.scores, [24000, 25000, 25000, 25000]);
+
+    // The carried count is what the state expects of the next kyoku, so a
+    // `start_kyoku` that drops the stick is rejected.
+    let next_kyoku = |kyotaku: u8| {
+        format!(
+            r#"{{"type":"start_kyoku","bakaze":"E","dora_marker":"2m","kyoku":1,"honba":1,"kyotaku":{kyotaku},"oya":0,"scores":[24000,25000,25000,25000],"tehais":[["1m","2m","3m","4p","5p","6p","7s","8s","9s","E","E","S","S"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"]]}}"#,
+        )
+    };
+    let err = ps
+        .clone()
+        .update_json(&next_kyoku(0))
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("expected kyotaku 1"), "{err}");
+
+    ps.update_json(&next_kyoku(1)).unwrap();
+    assert_eq!(ps.kyotaku, 1);
+    assert_eq!(ps.honba, 1);
+
+    ps.update_json(r#"{"type":"tsumo","actor":0,"pai":"S"}"#)
+        .unwrap();
+    ps.update_json(r#"{"type":"hora","actor":0,"target":0}"#)
+        .unwrap();
+    assert_eq!(ps.kyotaku, 0);
+}
+
+#[test]
+fn event_broke_ippatsu() {
+    let log = r#"
+        {"type":"start_kyoku","bakaze":"E","dora_marker":"1m","kyoku":1,"honba":0,"kyotaku":0,"oya":0,"scores":[25000,25000,25000,25000],"tehais":[["1m","2m","3m","4p","5p","6p","7s","8s","9s","E","E","S","S"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"]]}
+        {"type":"tsumo","actor":0,"pai":"N"}
+        {"type":"reach","actor":0}
+        {"type":"dahai","actor":0,"pai":"N","tsumogiri":true}
+        {"type":"reach_accepted","actor":0}
+        {"type":"tsumo","actor":1,"pai":"?"}
+    "#;
+    let mut ps = state_from_log(0, log);
+    assert!(ps.at_ippatsu());
+    assert!(!ps.event_broke_ippatsu());
+
+    ps.update_json(r#"{"type":"dahai","actor":1,"pai":"P","tsumogiri":true}"#)
+        .unwrap();
+    assert!(ps.at_ippatsu());
+    assert!(!ps.event_broke_ippatsu());
+
+    ps.update_json(r#"{"type":"pon","actor":2,"target":1,"pai":"P","consumed":["P","P"]}"#)
+        .unwrap();
+    assert!(!ps.at_ippatsu());
+    assert!(ps.event_broke_ippatsu());
+
+    // Only the transition is reported.
+    ps.update_json(r#"{"type":"dahai","actor":2,"pai":"1p","tsumogiri":false}"#)
+        .unwrap();
+    assert!(!ps.event_broke_ippatsu());
+
+    // Ippatsu expiring with our own discard is not a break.
+    let log = r#"
+        {"type":"start_kyoku","bakaze":"E","dora_marker":"1m","kyoku":1,"honba":0,"kyotaku":0,"oya":0,"scores":[25000,25000,25000,25000],"tehais":[["1m","2m","3m","4p","5p","6p","7s","8s","9s","E","E","S","S"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"]]}
+        {"type":"tsumo","actor":0,"pai":"N"}
+        {"type":"reach","actor":0}
+        {"type":"dahai","actor":0,"pai":"N","tsumogiri":true}
+        {"type":"reach_accepted","actor":0}
+        {"type":"tsumo","actor":1,"pai":"?"}
+        {"type":"dahai","actor":1,"pai":"P","tsumogiri":true}
+        {"type":"tsumo","actor":2,"pai":"?"}
+        {"type":"dahai","actor":2,"pai":"P","tsumogiri":true}
+        {"type":"tsumo","actor":3,"pai":"?"}
+        {"type":"dahai","actor":3,"pai":"P","tsumogiri":true}
+        {"type":"tsumo","actor":0,"pai":"W"}
+    "#;
+    let mut ps = state_from_log(0, log);
+    assert!(ps.at_ippatsu());
+    ps.update_json(r#"{"type":"dahai","actor":0,"pai":"W","tsumogiri":true}"#)
+        .unwrap();
+    assert!(!ps.at_ippatsu());
+    assert!(!ps.event_broke_ippatsu());
+}
+
+#[test]
+fn chankan() {
+    let log = r#"
+        {"type":"start_kyoku","bakaze":"E","dora_marker":"1m","kyoku":1,"honba":0,"kyotaku":0,"oya":0,"scores":[25000,25000,25000,25000],"tehais":[["1m","2m","3m","4p","5p","7s","8s","9s","E","E","E","S","N"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"]]}
+        {"type":"tsumo","actor":0,"pai":"S"}
+        {"type":"dahai","actor":0,"pai":"N","tsumogiri":false}
+        {"type":"tsumo","actor":1,"pai":"?"}
+        {"type":"dahai","actor":1,"pai":"6p","tsumogiri":false}
+        {"type":"pon","actor":2,"target":1,"pai":"6p","consumed":["6p","6p"]}
+        {"type":"dahai","actor":2,"pai":"W","tsumogiri":false}
+        {"type":"tsumo","actor":3,"pai":"?"}
+        {"type":"dahai","actor":3,"pai":"W","tsumogiri":true}
+        {"type":"tsumo","actor":0,"pai":"C"}
+        {"type":"dahai","actor":0,"pai":"C","tsumogiri":true}
+        {"type":"tsumo","actor":1,"pai":"?"}
+        {"type":"dahai","actor":1,"pai":"F","tsumogiri":true}
+        {"type":"tsumo","actor":2,"pai":"?"}
+    "#;
+    let mut ps = state_from_log(0, log);
+    assert!(!ps.at_furiten);
+    assert_eq!(ps.chankan_tile(), None);
+
+    let cans = ps
+        .update_json(r#"{"type":"kakan","actor":2,"pai":"6p","consumed":["6p","6p","6p"]}"#)
+        .unwrap();
+    assert!(cans.can_ron_agari);
+    assert_eq!(cans.target_actor, 2);
+    assert_eq!(ps.chankan_tile(), Some(t!(6p)));
+    assert_eq!(ps.last_kawa_tile, Some(t!(6p)));
+
+    // Passing it marks same-cycle furiten and the chance is gone.
+    let cans = ps
+        .update_json(r#"{"type":"tsumo","actor":2,"pai":"?"}"#)
+        .unwrap();
+    assert!(!cans.can_ron_agari);
+    assert_eq!(ps.chankan_tile(), None);
+    assert!(ps.at_furiten);
+
+    // Standard furiten still blocks chankan.
+    let log = r#"
+        {"type":"start_kyoku","bakaze":"E","dora_marker":"1m","kyoku":1,"honba":0,"kyotaku":0,"oya":0,"scores":[25000,25000,25000,25000],"tehais":[["1m","2m","3m","4p","5p","7s","8s","9s","E","E","E","S","N"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"]]}
+        {"type":"tsumo","actor":0,"pai":"S"}
+        {"type":"dahai","actor":0,"pai":"N","tsumogiri":false}
+        {"type":"tsumo","actor":1,"pai":"?"}
+        {"type":"dahai","actor":1,"pai":"6p","tsumogiri":false}
+        {"type":"pon","actor":2,"target":1,"pai":"6p","consumed":["6p","6p"]}
+        {"type":"dahai","actor":2,"pai":"W","tsumogiri":false}
+        {"type":"tsumo","actor":3,"pai":"?"}
+        {"type":"dahai","actor":3,"pai":"W","tsumogiri":true}
+        {"type":"tsumo","actor":0,"pai":"3p"}
+        {"type":"dahai","actor":0,"pai":"3p","tsumogiri":true}
+        {"type":"tsumo","actor":1,"pai":"?"}
+        {"type":"dahai","actor":1,"pai":"F","tsumogiri":true}
+        {"type":"tsumo","actor":2,"pai":"?"}
+    "#;
+    let mut ps = state_from_log(0, log);
+    assert!(ps.at_furiten);
+    let cans = ps
+        .update_json(r#"{"type":"kakan","actor":2,"pai":"6p","consumed":["6p","6p","6p"]}"#)
+        .unwrap();
+    assert!(!cans.can_ron_agari);
+    assert_eq!(ps.chankan_tile(), None);
+}
+
+#[test]
+fn tiles_left_with_kans() {
+    // Enough tiles to keep `tiles_seen` sane throughout the kyoku.
+    let mut pool = (tu8!(5p)..=tu8!(9p))
+        .chain(tu8!(2s)..=tu8!(8s))
+        .chain(tu8!(E)..=tu8!(C))
+        .flat_map(|t| [must_tile!(t); 4]);
+    let mut next = || pool.next().unwrap();
+
+    let mut ps = PlayerState::new(3);
+    ps.update(&Event::StartKyoku {
+        bakaze: t!(E),
+        kyoku: 1,
+        honba: 0,
+        kyotaku: 0,
+        oya: 0,
+        scores: [25000; 4],
+        dora_marker: next(),
+        tehais: [
+            [t!(?); 13],
+            [t!(?); 13],
+            [t!(?); 13],
+            tile37_to_vec(&hand_with_aka("123456789m 1234p").unwrap())
+                .try_into()
+                .unwrap(),
+        ],
+    })
+    .unwrap();
+    assert_eq!(ps.tiles_left, 70);
+
+    let kans = [(1, t!(1s)), (3, t!(9s))];
+    let mut actor = 0;
+    let mut at_rinshan = false;
+    for draw in 0..70 {
+        let pai = if actor == 3 { next() } else { t!(?) };
+        ps.update(&Event::Tsumo { actor, pai }).unwrap();
+        assert_eq!(ps.tiles_left, 69 - draw);
+        if actor == 3 {
+            assert_eq!(ps.is_haitei_draw(), draw == 69);
+        }
+        // The haitei tile is already settled to be ours after the kans.
+        assert_eq!(ps.is_haitei_chance(), draw >= 66);
+        assert!(!ps.is_houtei_chance());
+        if draw == 69 {
+            break;
+        }
+
+        if !at_rinshan {
+            if let Some(&(_, kan)) = kans.iter().find(|&&(d, _)| d == draw) {
+                ps.update(&Event::Ankan {
+                    actor,
+                    consumed: [kan; 4],
+                })
+                .unwrap();
+                assert_eq!(ps.pending_kan_doras(), 1);
+                ps.update(&Event::Dora {
+                    dora_marker: next(),
+                })
+                .unwrap();
+                assert_eq!(ps.pending_kan_doras(), 0);
+                at_rinshan = true;
+                continue;
+            }
+        }
+        at_rinshan = false;
+
+        let discard = if actor == 3 { pai } else { next() };
+        ps.update(&Event::Dahai {
+            actor,
+            pai: discard,
+            tsumogiri: actor == 3,
+        })
+        .unwrap();
+        actor = (actor + 1) % 4;
+    }
+
+    // Two kans shift the haitei draw to seat 3.
+    assert_eq!(actor, 3);
+    assert!(ps.is_haitei_draw());
+    assert!(ps.last_cans.can_discard);
+    assert!(ps.ankan_candidates.is_empty() && ps.kakan_candidates.is_empty());
+    assert_eq!(ps.rinshan_draws_left(), 2);
+    assert_eq!(ps.haitei_seat(), 0);
+
+    // Seat 0 can only hope for houtei now.
+    let view = ps.public_view_from(1);
+    assert_eq!(view.haitei_seat(), 3);
+    assert!(view.is_houtei_chance());
+    assert!(!view.is_haitei_chance());
+}
+
+#[test]
+fn nukidora_unsupported() {
+    let log = r#"
+        {"type":"start_kyoku","bakaze":"E","dora_marker":"1m","kyoku":1,"honba":0,"kyotaku":0,"oya":0,"scores":[25000,25000,25000,25000],"tehais":[["1m","2m","3m","4p","5p","6p","7s","8s","9s","E","E","S","S"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"]]}
+        {"type":"tsumo","actor":0,"pai":"N"}
+    "#;
+    let mut ps = state_from_log(0, log);
+    let res = ps.update_json(r#"{"type":"nukidora","actor":0,"pai":"N"}"#);
+    assert!(res.unwrap_err().to_string().contains("sanma"));
+    assert_eq!(ps.tehai[tuz!(N)], 1);
+    assert!(ps.last_cans.can_discard);
+}
+
+#[test]
+fn rule_set() {
+    let log = r#"
+        {"type":"start_kyoku","bakaze":"E","dora_marker":"1m","kyoku":1,"honba":0,"kyotaku":0,"oya":0,"scores":[25000,25000,25000,25000],"tehais":[["2m","3m","4m","5m","6m","7m","2s","3s","2p","2p","6p","6p","8p"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"]]}
+        {"type":"tsumo","actor":0,"pai":"N"}
+        {"type":"dahai","actor":0,"pai":"N","tsumogiri":true}
+        {"type":"tsumo","actor":1,"pai":"?"}
+        {"type":"dahai","actor":1,"pai":"9m","tsumogiri":true}
+        {"type":"tsumo","actor":2,"pai":"?"}
+        {"type":"dahai","actor":2,"pai":"9m","tsumogiri":true}
+        {"type":"tsumo","actor":3,"pai":"?"}
+        {"type":"dahai","actor":3,"pai":"2p","tsumogiri":true}
+        {"type":"pon","actor":0,"target":3,"pai":"2p","consumed":["2p","2p"]}
+        {"type":"dahai","actor":0,"pai":"8p","tsumogiri":false}
+        {"type":"tsumo","actor":1,"pai":"?"}
+    "#;
+    // 23s waits for 1s and 4s, but only 4s makes tanyao.
+    let ron_4s = r#"{"type":"dahai","actor":1,"pai":"4s","tsumogiri":true}"#;
+    let ron_1s = r#"{"type":"dahai","actor":1,"pai":"1s","tsumogiri":true}"#;
+
+    let mut ps = state_from_log(0, log);
+    assert!(ps.waits[tuz!(1s)] && ps.waits[tuz!(4s)]);
+    assert!(ps.clone().update_json(ron_4s).unwrap().can_ron_agari);
+    assert!(!ps.update_json(ron_1s).unwrap().can_ron_agari);
+
+    let no_kuitan = RuleSet {
+        kuitan: false,
+        ..Default::default()
+    };
+    let mut ps = state_from_log_with_rule(0, no_kuitan, log);
+    assert!(!ps.update_json(ron_4s).unwrap().can_ron_agari);
+
+    let no_atozuke = RuleSet {
+        atozuke: false,
+        ..Default::default()
+    };
+    let mut ps = state_from_log_with_rule(0, no_atozuke, log);
+    assert!(!ps.update_json(ron_4s).unwrap().can_ron_agari);
+}
+
+#[test]
+fn kuikae_rule() {
+    let log = r#"
+        {"type":"start_kyoku","bakaze":"E","dora_marker":"1m","kyoku":4,"honba":0,"kyotaku":0,"oya":3,"scores":[25000,25000,25000,25000],"tehais":[["1m","2m","3m","4p","5p","6p","7p","8p","4s","5s","6s","7s","E"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"]]}
+        {"type":"tsumo","actor":3,"pai":"?"}
+        {"type":"dahai","actor":3,"pai":"4s","tsumogiri":true}
+        {"type":"chi","actor":0,"target":3,"pai":"4s","consumed":["5s","6s"]}
+    "#;
+    let discard = |pai| Event::Dahai {
+        actor: 0,
+        pai,
+        tsumogiri: false,
+    };
+
+    for (kuikae, forbidden) in [
+        (Kuikae::Strict, [true, true]),
+        (Kuikae::Loose, [true, false]),
+        (Kuikae::Disabled, [false, false]),
+    ] {
+        let rule = RuleSet {
+            kuikae,
+            ..Default::default()
+        };
+        let ps = state_from_log_with_rule(0, rule, log);
+        for (tile, forbidden) in [t!(4s), t!(7s)].into_iter().zip(forbidden) {
+            assert_eq!(ps.forbidden_tiles[tile.as_usize()], forbidden);
+            assert_eq!(
+                ps.validate_reaction(&discard(tile)).is_err(),
+                forbidden,
+                "{kuikae:?} {tile}",
+            );
+        }
+        assert!(ps.validate_reaction(&discard(t!(E))).is_ok());
+    }
+
+    assert_eq!("loose".parse::<Kuikae>().unwrap(), Kuikae::Loose);
+    assert_eq!(Kuikae::Disabled.to_string(), "disabled");
+    "lenient".parse::<Kuikae>().unwrap_err();
+}
+
+#[test]
+fn ryukyoku_rules() {
+    let log = |mentsu: &str| {
+        format!(
+            r#"
+            {{"type":"start_kyoku","bakaze":"S","dora_marker":"N","kyoku":4,"honba":5,"kyotaku":0,"oya":3,"scores":[25000,25000,25000,25000],"tehais":[[{mentsu},"5m","6m","7m","4p","4p","6s","6s","7s","8s","8p"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"]]}}
+            {{"type":"tsumo","actor":3,"pai":"?"}}
+            {{"type":"dahai","actor":3,"pai":"4p","tsumogiri":true}}
+            {{"type":"pon","actor":0,"target":3,"pai":"4p","consumed":["4p","4p"]}}
+            {{"type":"dahai","actor":0,"pai":"8p","tsumogiri":false}}
+            {{"type":"tsumo","actor":1,"pai":"?"}}
+            {{"type":"dahai","actor":1,"pai":"6s","tsumogiri":true}}
+            "#,
+        )
+    };
+    let shibari = RuleSet {
+        ryanhan_shibari: true,
+        ..Default::default()
+    };
+    let no_keishiki = RuleSet {
+        keishiki_tenpai: false,
+        ..Default::default()
+    };
+
+    // Tanyao only on 6s, which is not enough at 5 honba.
+    let tanyao = log(r#""2m","3m","4m""#);
+    let ps = state_from_log(0, &tanyao);
+    assert!(ps.last_cans.can_ron_agari);
+    let ps = state_from_log_with_rule(0, shibari, &tanyao);
+    assert!(!ps.last_cans.can_ron_agari);
+    let ps = state_from_log_with_rule(0, no_keishiki, &tanyao);
+    assert!(ps.is_ryukyoku_tenpai());
+
+    // No yaku on either 6s or 9s.
+    let yakunashi = log(r#""1m","2m","3m""#);
+    let ps = state_from_log(0, &yakunashi);
+    assert!(!ps.last_cans.can_ron_agari);
+    assert!(ps.is_ryukyoku_tenpai());
+    let ps = state_from_log_with_rule(0, no_keishiki, &yakunashi);
+    assert!(!ps.is_ryukyoku_tenpai());
+}
+
+#[test]
+fn local_yaku() {
+    let local = RuleSet {
+        renhou: true,
+        daisharin: true,
+        open_riichi: true,
+        ..Default::default()
+    };
+
+    // 人和 on a hand without any yaku.
+    let log = r#"
+        {"type":"start_kyoku","bakaze":"E","dora_marker":"N","kyoku":1,"honba":0,"kyotaku":0,"oya":0,"scores":[25000,25000,25000,25000],"tehais":[["?","?","?","?","?","?","?","?","?","?","?","?","?"],["1m","2m","3m","4m","5m","6m","7p","8p","9p","2s","3s","C","C"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"]]}
+        {"type":"tsumo","actor":0,"pai":"?"}
+        {"type":"dahai","actor":0,"pai":"4s","tsumogiri":true}
+    "#;
+    let ps = state_from_log(1, log);
+    assert!(!ps.last_cans.can_ron_agari);
+    let ps = state_from_log_with_rule(1, local, log);
+    assert!(ps.last_cans.can_ron_agari);
+    assert_eq!(ps.agari(true, &[]).unwrap(), Agari::Yakuman(1));
+
+    // 大車輪, which would be a sanbaiman otherwise.
+    let log = r#"
+        {"type":"start_kyoku","bakaze":"E","dora_marker":"N","kyoku":4,"honba":0,"kyotaku":0,"oya":3,"scores":[25000,25000,25000,25000],"tehais":[["2p","2p","3p","3p","4p","4p","5p","5p","6p","6p","7p","7p","8p"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"]]}
+        {"type":"tsumo","actor":3,"pai":"?"}
+        {"type":"dahai","actor":3,"pai":"8p","tsumogiri":true}
+    "#;
+    let ps = state_from_log(0, log);
+    assert!(matches!(ps.agari(true, &[]).unwrap(), Agari::Normal { .. }));
+    let ps = state_from_log_with_rule(0, local, log);
+    assert_eq!(ps.agari(true, &[]).unwrap(), Agari::Yakuman(1));
+
+    // Open riichi is worth 1 more han than riichi.
+    let open_riichi = |open| {
+        format!(
+            r#"
+            {{"type":"start_kyoku","bakaze":"E","dora_marker":"N","kyoku":1,"honba":0,"kyotaku":0,"oya":0,"scores":[25000,25000,25000,25000],"tehais":[["1m","2m","3m","4m","5m","6m","7p","8p","9p","2s","3s","C","C"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"]]}}
+            {{"type":"tsumo","actor":0,"pai":"N"}}
+            {{"type":"reach","actor":0,"open":{open}}}
+            {{"type":"dahai","actor":0,"pai":"N","tsumogiri":true}}
+            {{"type":"reach_accepted","actor":0}}
+            {{"type":"tsumo","actor":1,"pai":"?"}}
+            {{"type":"dahai","actor":1,"pai":"4s","tsumogiri":true}}
+            "#,
+        )
+    };
+    let han = |ps: &PlayerState| match ps.agari(true, &[]).unwrap() {
+        Agari::Normal { han, .. } => han,
+        Agari::Yakuman(_) => unreachable!(),
+    };
+    let closed = state_from_log_with_rule(0, local, &open_riichi(false));
+    let open = state_from_log_with_rule(0, local, &open_riichi(true));
+    assert_eq!(open.open_riichi(), [true, false, false, false]);
+    assert_eq!(han(&open), han(&closed) + 1);
+
+    let reach = Event::Reach {
+        actor: 0,
+        open: true,
+    };
+    let before_reach = open_riichi(false);
+    let before_reach = before_reach
+        .trim()
+        .lines()
+        .take(2)
+        .collect::<Vec<_>>()
+        .join("\n");
+    let ps = state_from_log(0, &before_reach);
+    ps.validate_reaction(&reach).unwrap_err();
+    let ps = state_from_log_with_rule(0, local, &before_reach);
+    ps.validate_reaction(&reach).unwrap();
+}
+
+#[test]
+fn to_bytes_and_back() {
+    let log = r#"
+        {"type":"start_kyoku","bakaze":"E","dora_marker":"1m","kyoku":1,"honba":0,"kyotaku":0,"oya":0,"scores":[25000,25000,25000,25000],"tehais":[["1m","2m","3m","4p","5pr","6p","7s","8s","9s","E","E","S","S"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"]]}
+        {"type":"tsumo","actor":0,"pai":"N"}
+        {"type":"reach","actor":0}
+        {"type":"dahai","actor":0,"pai":"N","tsumogiri":true}
+        {"type":"reach_accepted","actor":0}
+        {"type":"tsumo","actor":1,"pai":"?"}
+        {"type":"dahai","actor":1,"pai":"S","tsumogiri":true}
+    "#;
+    let mut ps = state_from_log(0, log);
+    let mut restored = PlayerState::from_bytes(&ps.to_bytes().unwrap()).unwrap();
+    assert_eq!(format!("{ps:?}"), format!("{restored:?}"));
+
+    let next = r#"{"type":"tsumo","actor":1,"pai":"?"}"#;
+    let cans = ps.update_json(next).unwrap();
+    let restored_cans = restored.update_json(next).unwrap();
+    assert_eq!(format!("{cans:?}"), format!("{restored_cans:?}"));
+    assert_eq!(format!("{ps:?}"), format!("{restored:?}"));
+
+    PlayerState::from_bytes(b"{}").unwrap_err();
+}
+
+#[test]
+fn ukeire() {
+    let log = r#"
+        {"type":"start_kyoku","bakaze":"E","dora_marker":"9p","kyoku":1,"honba":0,"kyotaku":0,"oya":0,"scores":[25000,25000,25000,25000],"tehais":[["1m","2m","3m","4p","5p","6p","7s","8s","9s","E","E","3s","C"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"]]}
+        {"type":"tsumo","actor":0,"pai":"5s"}
+    "#;
+    let ps = state_from_log(0, log);
+    let ukeire = ps.ukeire();
+    assert_eq!(ukeire.len(), 13);
+
+    // Discarding C makes tenpai on kanchan 4s.
+    let c = ukeire.iter().find(|u| u.discard == t!(C)).unwrap();
+    assert_eq!(c.shanten, 0);
+    assert_eq!(c.accepted, [(t!(4s), 4)]);
+    assert_eq!(c.total(), 4);
+
+    // Discarding 3s leaves 5s and C floating. Copies in hand are not counted.
+    let three_s = ukeire.iter().find(|u| u.discard == t!(3s)).unwrap();
+    assert_eq!(three_s.shanten, 1);
+    assert!(three_s.accepted.contains(&(t!(E), 2)));
+    assert!(three_s.accepted.contains(&(t!(C), 3)));
+    assert!(three_s.accepted.contains(&(t!(5s), 3)));
+    assert!(three_s.accepted.contains(&(t!(4s), 4)));
+
+    let one_m = ukeire.iter().find(|u| u.discard == t!(1m)).unwrap();
+    assert_eq!(one_m.shanten, 1);
+}
+
+#[test]
+fn tile_danger() {
+    let log = r#"
+        {"type":"start_kyoku","bakaze":"E","dora_marker":"9p","kyoku":1,"honba":0,"kyotaku":0,"oya":0,"scores":[25000,25000,25000,25000],"tehais":[["1m","2m","3m","4p","5p","6p","7s","8s","9s","E","E","3s","C"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"]]}
+        {"type":"tsumo","actor":0,"pai":"5s"}
+        {"type":"dahai","actor":0,"pai":"C","tsumogiri":false}
+        {"type":"tsumo","actor":1,"pai":"?"}
+        {"type":"dahai","actor":1,"pai":"4m","tsumogiri":false}
+        {"type":"tsumo","actor":2,"pai":"?"}
+        {"type":"dahai","actor":2,"pai":"1p","tsumogiri":true}
+        {"type":"tsumo","actor":3,"pai":"?"}
+        {"type":"dahai","actor":3,"pai":"9m","tsumogiri":true}
+        {"type":"tsumo","actor":0,"pai":"N"}
+        {"type":"dahai","actor":0,"pai":"N","tsumogiri":true}
+        {"type":"tsumo","actor":1,"pai":"?"}
+        {"type":"reach","actor":1}
+        {"type":"dahai","actor":1,"pai":"6s","tsumogiri":false}
+        {"type":"reach_accepted","actor":1}
+        {"type":"tsumo","actor":2,"pai":"?"}
+        {"type":"dahai","actor":2,"pai":"2s","tsumogiri":true}
+    "#;
+    let ps = state_from_log(0, log);
+
+    let danger = ps.tile_danger(1);
+    assert_eq!(danger.len(), 34);
+    let kind_of = |danger: &[TileDanger], tile: Tile| danger[tile.as_usize()].kind;
+
+    // In the kawa of the riichi player.
+    assert_eq!(kind_of(&danger, t!(4m)), SafetyKind::Genbutsu);
+    assert_eq!(kind_of(&danger, t!(6s)), SafetyKind::Genbutsu);
+    // Discarded after the riichi.
+    assert_eq!(kind_of(&danger, t!(2s)), SafetyKind::Genbutsu);
+    // Discarded before the riichi.
+    assert_eq!(kind_of(&danger, t!(1p)), SafetyKind::Live);
+    assert_eq!(kind_of(&danger, t!(N)), SafetyKind::Live);
+
+    assert_eq!(kind_of(&danger, t!(1m)), SafetyKind::Suji);
+    assert_eq!(kind_of(&danger, t!(7m)), SafetyKind::Suji);
+    assert_eq!(kind_of(&danger, t!(3s)), SafetyKind::Suji);
+    assert_eq!(kind_of(&danger, t!(9s)), SafetyKind::Suji);
+    // Nakasuji of 2s and 8s, only 2s is genbutsu.
+    assert_eq!(kind_of(&danger, t!(5s)), SafetyKind::Live);
+    assert!(danger[tuz!(5s)].score < danger[tuz!(5m)].score);
+    assert!(danger[tuz!(1m)].score < danger[tuz!(5m)].score);
+    assert!(danger[tuz!(4m)].score < danger[tuz!(1m)].score);
+
+    // Not in riichi, tiles discarded after the last discard of the seat are
+    // also genbutsu.
+    let danger = ps.tile_danger(3);
+    assert_eq!(kind_of(&danger, t!(9m)), SafetyKind::Genbutsu);
+    assert_eq!(kind_of(&danger, t!(N)), SafetyKind::Genbutsu);
+    assert_eq!(kind_of(&danger, t!(2s)), SafetyKind::Genbutsu);
+    assert_eq!(kind_of(&danger, t!(1p)), SafetyKind::Live);
+}
+
+#[test]
+fn opponent_estimate() {
+    let log = r#"
+        {"type":"start_kyoku","bakaze":"E","dora_marker":"9p","kyoku":1,"honba":0,"kyotaku":0,"oya":0,"scores":[25000,25000,25000,25000],"tehais":[["1m","2m","3m","4p","5p","6p","7s","8s","9s","E","E","3s","C"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"]]}
+        {"type":"tsumo","actor":0,"pai":"5s"}
+        {"type":"dahai","actor":0,"pai":"C","tsumogiri":false}
+        {"type":"tsumo","actor":1,"pai":"?"}
+        {"type":"dahai","actor":1,"pai":"4m","tsumogiri":false}
+        {"type":"tsumo","actor":2,"pai":"?"}
+        {"type":"dahai","actor":2,"pai":"1p","tsumogiri":true}
+        {"type":"tsumo","actor":3,"pai":"?"}
+        {"type":"dahai","actor":3,"pai":"9m","tsumogiri":true}
+        {"type":"tsumo","actor":0,"pai":"N"}
+        {"type":"dahai","actor":0,"pai":"N","tsumogiri":true}
+        {"type":"tsumo","actor":1,"pai":"?"}
+        {"type":"reach","actor":1}
+        {"type":"dahai","actor":1,"pai":"6s","tsumogiri":false}
+        {"type":"reach_accepted","actor":1}
+        {"type":"tsumo","actor":2,"pai":"?"}
+        {"type":"dahai","actor":2,"pai":"2s","tsumogiri":true}
+    "#;
+    let ps = state_from_log(0, log);
+
+    let riichi = BaselineOpponentModel.estimate(&ps, 1);
+    assert!((riichi.tenpai - 1.).abs() < f32::EPSILON);
+    // Genbutsu.
+    assert!(riichi.waits[tuz!(4m)].abs() < f32::EPSILON);
+    assert!(riichi.waits[tuz!(2s)].abs() < f32::EPSILON);
+    // Around the sengenhai 6s.
+    assert!(riichi.waits[tuz!(7s)] > riichi.waits[tuz!(7p)]);
+    assert!(riichi.holds[tuz!(7s)] > riichi.holds[tuz!(7p)]);
+    // One N is visible in the kawa.
+    assert!(riichi.holds[tuz!(N)] < riichi.holds[tuz!(P)]);
+    assert!(riichi
+        .holds
+        .iter()
+        .chain(&riichi.waits)
+        .all(|p| (0. ..=1.).contains(p)));
+
+    let quiet = BaselineOpponentModel.estimate(&ps, 2);
+    assert!(quiet.tenpai < 0.1);
+    assert!(quiet.waits.iter().sum::<f32>() < riichi.waits.iter().sum::<f32>());
+
+    let danger = ps.tile_danger_with(1, &BaselineOpponentModel);
+    let plain = ps.tile_danger(1);
+    for (d, p) in danger.iter().zip(&plain) {
+        assert_eq!(d.kind, p.kind);
+    }
+    assert!((danger[tuz!(5s)].score / 100. - riichi.waits[tuz!(5s)]).abs() < 1e-6);
+}
+
+#[test]
+fn push_fold() {
+    let log = r#"
+        {"type":"start_kyoku","bakaze":"E","dora_marker":"9p","kyoku":1,"honba":0,"kyotaku":0,"oya":0,"scores":[25000,25000,25000,25000],"tehais":[["1m","2m","3m","4p","5p","6p","7s","8s","9s","E","E","3s","C"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"]]}
+        {"type":"tsumo","actor":0,"pai":"5s"}
+        {"type":"dahai","actor":0,"pai":"C","tsumogiri":false}
+        {"type":"tsumo","actor":1,"pai":"?"}
+        {"type":"dahai","actor":1,"pai":"4m","tsumogiri":false}
+        {"type":"tsumo","actor":2,"pai":"?"}
+        {"type":"dahai","actor":2,"pai":"1p","tsumogiri":true}
+        {"type":"tsumo","actor":3,"pai":"?"}
+        {"type":"dahai","actor":3,"pai":"9m","tsumogiri":true}
+        {"type":"tsumo","actor":0,"pai":"N"}
+    "#;
+    let ps = state_from_log(0, log);
+    // No threat yet.
+    assert_eq!(ps.push_fold(t!(N)), None);
+
+    let log = r#"
+        {"type":"dahai","actor":0,"pai":"N","tsumogiri":true}
+        {"type":"tsumo","actor":1,"pai":"?"}
+        {"type":"reach","actor":1}
+        {"type":"dahai","actor":1,"pai":"6s","tsumogiri":false}
+        {"type":"reach_accepted","actor":1}
+        {"type":"tsumo","actor":2,"pai":"?"}
+        {"type":"dahai","actor":2,"pai":"2s","tsumogiri":true}
+        {"type":"tsumo","actor":3,"pai":"?"}
+        {"type":"dahai","actor":3,"pai":"5m","tsumogiri":true}
+        {"type":"tsumo","actor":0,"pai":"P"}
+    "#;
+    let mut ps = ps;
+    for line in log.trim().lines() {
+        ps.update_json(line).unwrap();
+    }
+    assert_eq!(ps.push_fold(t!(4m)), Some(PushFold::Fold));
+    assert_eq!(ps.push_fold(t!(1m)), Some(PushFold::Ambiguous));
+    assert_eq!(ps.push_fold(t!(P)), Some(PushFold::Push));
+    assert_eq!(ps.push_fold(t!(5s)), Some(PushFold::Push));
+}
+
+#[test]
+fn tiles_remaining() {
+    let log = r#"
+        {"type":"start_kyoku","bakaze":"E","dora_marker":"4p","kyoku":1,"honba":0,"kyotaku":0,"oya":0,"scores":[25000,25000,25000,25000],"tehais":[["1m","2m","3m","5mr","5m","6p","7s","8s","9s","E","E","3s","C"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"]]}
+        {"type":"tsumo","actor":0,"pai":"5s"}
+        {"type":"dahai","actor":0,"pai":"C","tsumogiri":false}
+        {"type":"tsumo","actor":1,"pai":"?"}
+        {"type":"dahai","actor":1,"pai":"5pr","tsumogiri":false}
+    "#;
+    let ps = state_from_log(0, log);
+
+    let remaining = ps.tiles_remaining();
+    assert_eq!(ps.akas_seen(), [1, 1, 0]);
+    assert_eq!(remaining[tuz!(5mr)], 0);
+    assert_eq!(remaining[tuz!(5m)], 2);
+    assert_eq!(remaining[tuz!(5pr)], 0);
+    assert_eq!(remaining[tuz!(5p)], 3);
+    // 5sr has not been seen, the 5s in hand is a normal one.
+    assert_eq!(remaining[tuz!(5sr)], 1);
+    assert_eq!(remaining[tuz!(5s)], 2);
+    assert_eq!(remaining[tuz!(C)], 3);
+    assert_eq!(remaining[tuz!(E)], 2);
+    assert_eq!(
+        remaining.iter().map(|&n| n as u32).sum::<u32>(),
+        136 - ps.tiles_seen().iter().map(|&n| n as u32).sum::<u32>(),
+    );
+
+    // 5mr in hand and 5pr in kawa, plus 5p as the dora.
+    assert_eq!(ps.doras_seen(), 3);
+
+    // The aka in hand is hidden from the other seats.
+    assert_eq!(ps.public_view_from(1).akas_seen(), [0, 1, 0]);
+}
+
+#[test]
+fn four_akas() {
+    let rule = RuleSet {
+        aka_count: 4,
+        ..Default::default()
+    };
+    let log = r#"
+        {"type":"start_kyoku","bakaze":"E","dora_marker":"4p","kyoku":4,"honba":0,"kyotaku":0,"oya":3,"scores":[25000,25000,25000,25000],"tehais":[["5pr","5pr","5p","1m","2m","3m","6s","7s","8s","E","E","S","C"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"]]}
+        {"type":"tsumo","actor":3,"pai":"?"}
+        {"type":"dahai","actor":3,"pai":"5p","tsumogiri":true}
+    "#;
+    let ps = state_from_log_with_rule(0, rule, log);
+    assert_eq!(ps.akas_in_hand(), [0, 2, 0]);
+    // Three 5p as doras and two of them as akas.
+    assert_eq!(ps.doras_owned[0], 5);
+    let pons: Vec<_> = ps
+        .call_candidates()
+        .into_iter()
+        .filter_map(|ev| match ev {
+            Event::Pon { consumed, .. } => Some(consumed),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(pons, [[t!(5pr), t!(5pr)], [t!(5pr), t!(5p)]]);
+
+    let log = r#"
+        {"type":"start_kyoku","bakaze":"E","dora_marker":"4p","kyoku":1,"honba":0,"kyotaku":0,"oya":0,"scores":[25000,25000,25000,25000],"tehais":[["5pr","5pr","5p","1m","2m","3m","6s","7s","8s","E","E","S","C"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"]]}
+        {"type":"tsumo","actor":0,"pai":"5p"}
+    "#;
+    let ps = state_from_log_with_rule(0, rule, log);
+    assert_eq!(ps.doras_owned[0], 6);
+    let discards = ps.discard_candidates_aka();
+    assert!(discards[tuz!(5pr)] && discards[tuz!(5p)]);
+    assert!(ps.reaction_candidates().contains(&Event::Ankan {
+        actor: 0,
+        consumed: [t!(5pr), t!(5pr), t!(5p), t!(5p)],
+    }));
+    let remaining = ps.tiles_remaining();
+    assert_eq!((remaining[tuz!(5pr)], remaining[tuz!(5p)]), (0, 0));
+    let info = ps.brief_info();
+    assert!(info.contains("tehai: 123m 0055p 678s 1127z\n"), "{info}");
+
+    // Without aka, an ankan of 5s consumes no aka.
+    let log = r#"
+        {"type":"start_kyoku","bakaze":"E","dora_marker":"4p","kyoku":1,"honba":0,"kyotaku":0,"oya":0,"scores":[25000,25000,25000,25000],"tehais":[["5p","5p","5p","1m","2m","3m","6s","7s","8s","E","E","S","C"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"]]}
+        {"type":"tsumo","actor":0,"pai":"5p"}
+    "#;
+    let rule = RuleSet {
+        aka_count: 0,
+        ..Default::default()
+    };
+    let ps = state_from_log_with_rule(0, rule, log);
+    assert!(ps.reaction_candidates().contains(&Event::Ankan {
+        actor: 0,
+        consumed: [t!(5p); 4],
+    }));
+    assert_eq!(ps.tiles_remaining()[tuz!(5pr)], 0);
+}
+
+#[test]
+fn to_json() {
+    let log = r#"
+        {"type":"start_kyoku","bakaze":"E","dora_marker":"9p","kyoku":2,"honba":1,"kyotaku":0,"oya":1,"scores":[25000,25000,25000,25000],"tehais":[["1m","2m","3m","4p","5pr","6p","7s","8s","9s","E","E","C","C"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"]]}
+        {"type":"tsumo","actor":1,"pai":"?"}
+        {"type":"reach","actor":1}
+        {"type":"dahai","actor":1,"pai":"C","tsumogiri":true}
+        {"type":"reach_accepted","actor":1}
+        {"type":"pon","actor":0,"target":1,"pai":"C","consumed":["C","C"]}
+    "#;
+    let ps = state_from_log(0, log);
+
+    let value: serde_json::Value = serde_json::from_str(&ps.to_json().unwrap()).unwrap();
+    assert_eq!(value["kyoku"], 2);
+    assert_eq!(value["honba"], 1);
+    assert_eq!(value["oya"], 1);
+    assert_eq!(value["tehai"].as_array().unwrap().len(), 11);
+    assert_eq!(value["tehai"].as_array().unwrap().last().unwrap(), "5pr");
+    assert_eq!(value["fuuro"][0][0], serde_json::json!(["C", "C", "C"]));
+    assert_eq!(
+        value["riichi_accepted"],
+        serde_json::json!([false, true, false, false])
+    );
+    assert_eq!(value["cans"]["can_discard"], true);
+
+    let riichi_sutehai = &value["kawa"][1][0]["sutehai"];
+    assert_eq!(riichi_sutehai["tile"], "C");
+    assert_eq!(riichi_sutehai["is_riichi"], true);
+    assert_eq!(riichi_sutehai["is_tedashi"], false);
+}
+
+#[test]
+fn sutehai_claimed_by() {
+    let log = r#"
+        {"type":"start_kyoku","bakaze":"E","dora_marker":"1s","kyoku":2,"honba":0,"kyotaku":0,"oya":1,"scores":[25000,25000,25000,25000],"tehais":[["1m","2m","3m","5mr","6m","4p","6p","7s","8s","9s","2s","E","E"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"]]}
+        {"type":"tsumo","actor":1,"pai":"?"}
+        {"type":"dahai","actor":1,"pai":"E","tsumogiri":false}
+        {"type":"pon","actor":0,"target":1,"pai":"E","consumed":["E","E"]}
+        {"type":"dahai","actor":0,"pai":"2s","tsumogiri":false}
+        {"type":"tsumo","actor":1,"pai":"?"}
+        {"type":"dahai","actor":1,"pai":"1p","tsumogiri":true}
+        {"type":"chi","actor":2,"target":1,"pai":"1p","consumed":["2p","3p"]}
+        {"type":"dahai","actor":2,"pai":"9p","tsumogiri":false}
+    "#;
+    let ps = state_from_log(0, log);
+
+    let sutehais = ps.sutehais(1).collect::<Vec<_>>();
+    assert_eq!(sutehais[0].tile, t!(E));
+    assert_eq!(sutehais[0].claimed_by, Some(0));
+    assert!(sutehais[0].is_tedashi);
+    assert_eq!(sutehais[1].tile, t!(1p));
+    assert_eq!(sutehais[1].claimed_by, Some(2));
+    assert!(!sutehais[1].is_tedashi);
+
+    let mine = ps.sutehais(0).collect::<Vec<_>>();
+    assert_eq!(mine[0].tile, t!(2s));
+    assert_eq!(mine[0].claimed_by, None);
+    assert!(mine[0].is_dora);
+}
+
+#[test]
+fn clone_per_branch() {
+    let log = r#"
+        {"type":"start_kyoku","bakaze":"E","dora_marker":"1s","kyoku":2,"honba":0,"kyotaku":0,"oya":1,"scores":[25000,25000,25000,25000],"tehais":[["1m","2m","3m","5mr","6m","4p","6p","7s","8s","9s","2s","E","E"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"]]}
+        {"type":"tsumo","actor":1,"pai":"?"}
+        {"type":"dahai","actor":1,"pai":"E","tsumogiri":false}
+    "#;
+    let ps = state_from_log(0, log);
+    let saved = ps.to_bytes().unwrap();
+
+    let branches = [
+        r#"
+            {"type":"pon","actor":0,"target":1,"pai":"E","consumed":["E","E"]}
+            {"type":"dahai","actor":0,"pai":"2s","tsumogiri":false}
+        "#,
+        r#"
+            {"type":"tsumo","actor":2,"pai":"?"}
+            {"type":"dahai","actor":2,"pai":"E","tsumogiri":true}
+            {"type":"tsumo","actor":3,"pai":"?"}
+            {"type":"reach","actor":3}
+        "#,
+    ];
+    for branch in branches {
+        let mut speculative = ps.clone();
+        for line in branch.trim().lines() {
+            speculative
+                .update(&serde_json::from_str(line).unwrap())
+                .unwrap();
+        }
+        assert_ne!(speculative.to_bytes().unwrap(), saved);
+        assert_eq!(ps.to_bytes().unwrap(), saved);
+        assert!(ps.last_cans().can_pon);
+        assert_eq!(ps.sutehais(1).next().unwrap().claimed_by, None);
+    }
+}
+
+#[test]
+fn shared_kawa() {
+    let log = r#"
+        {"type":"start_kyoku","bakaze":"E","dora_marker":"1s","kyoku":1,"honba":0,"kyotaku":0,"oya":0,"scores":[25000,25000,25000,25000],"tehais":[["1m","2m","3m","5mr","6m","4p","6p","7s","8s","9s","2s","E","E"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"]]}
+        {"type":"tsumo","actor":0,"pai":"N"}
+        {"type":"dahai","actor":0,"pai":"N","tsumogiri":true}
+    "#;
+    let ps = state_from_log(0, log);
+
+    let mut speculative = ps.clone();
+    for line in [
+        r#"{"type":"tsumo","actor":1,"pai":"?"}"#,
+        r#"{"type":"dahai","actor":1,"pai":"E","tsumogiri":true}"#,
+        r#"{"type":"pon","actor":0,"target":1,"pai":"E","consumed":["E","E"]}"#,
+    ] {
+        speculative
+            .update(&serde_json::from_str(line).unwrap())
+            .unwrap();
+    }
+    assert_eq!(speculative.sutehais(1).count(), 1);
+    assert_eq!(speculative.sutehais(1).next().unwrap().claimed_by, Some(0));
+
+    // The original one is untouched.
+    assert_eq!(ps.sutehais(0).count(), 1);
+    assert_eq!(ps.sutehais(1).count(), 0);
+    assert!(ps.kawa_overview()[1].is_empty());
+}
+
+#[test]
+fn invalid_reaction() {
+    let log = r#"
+        {"type":"start_kyoku","bakaze":"E","dora_marker":"9p","kyoku":4,"honba":0,"kyotaku":0,"oya":3,"scores":[25000,25000,25000,25000],"tehais":[["2m","3m","4m","5m","6p","7p","8p","E","E","S","S","W","W"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"]]}
+        {"type":"tsumo","actor":3,"pai":"?"}
+        {"type":"dahai","actor":3,"pai":"1m","tsumogiri":true}
+    "#;
+    let mut ps = state_from_log(0, log);
+    let check = |ps: &PlayerState, action: &str| {
+        ps.validate_reaction(&serde_json::from_str(action).unwrap())
+    };
+
+    assert!(check(&ps, r#"{"type":"none"}"#).is_ok());
+    assert!(check(
+        &ps,
+        r#"{"type":"chi","actor":0,"target":3,"pai":"1m","consumed":["2m","3m"]}"#
+    )
+    .is_ok());
+    assert_eq!(
+        check(
+            &ps,
+            r#"{"type":"chi","actor":0,"target":2,"pai":"1m","consumed":["2m","3m"]}"#
+        ),
+        Err(InvalidReaction::ChiFromNonKamicha { target: 2 }),
+    );
+    assert_eq!(
+        check(
+            &ps,
+            r#"{"type":"pon","actor":0,"target":3,"pai":"1m","consumed":["1m","1m"]}"#
+        ),
+        Err(InvalidReaction::Unavailable { action: "pon" }),
+    );
+    assert_eq!(
+        check(
+            &ps,
+            r#"{"type":"dahai","actor":1,"pai":"E","tsumogiri":false}"#
+        ),
+        Err(InvalidReaction::NotYourTurn { actor: 1 }),
+    );
+    assert_eq!(
+        check(&ps, r#"{"type":"tsumo","actor":0,"pai":"E"}"#),
+        Err(InvalidReaction::NotAReaction),
+    );
+
+    ps.update(
+        &serde_json::from_str(
+            r#"{"type":"chi","actor":0,"target":3,"pai":"1m","consumed":["2m","3m"]}"#,
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert!(check(
+        &ps,
+        r#"{"type":"dahai","actor":0,"pai":"E","tsumogiri":false}"#
+    )
+    .is_ok());
+    assert_eq!(
+        check(
+            &ps,
+            r#"{"type":"dahai","actor":0,"pai":"4m","tsumogiri":false}"#
+        ),
+        Err(InvalidReaction::KuikaeForbidden { tile: t!(4m) }),
+    );
+    assert_eq!(
+        check(
+            &ps,
+            r#"{"type":"dahai","actor":0,"pai":"9p","tsumogiri":false}"#
+        ),
+        Err(InvalidReaction::TileNotInHand { tile: t!(9p) }),
+    );
+    assert_eq!(
+        check(
+            &ps,
+            r#"{"type":"dahai","actor":0,"pai":"E","tsumogiri":true}"#
+        ),
+        Err(InvalidReaction::NotTsumogiri { tile: t!(E) }),
+    );
+}
+
+#[test]
+fn legal_action_mask() {
+    let log = r#"
+        {"type":"start_kyoku","bakaze":"E","dora_marker":"9p","kyoku":4,"honba":0,"kyotaku":0,"oya":3,"scores":[25000,25000,25000,25000],"tehais":[["2m","3m","4m","5mr","6p","7p","8p","E","E","S","S","W","W"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"]]}
+        {"type":"tsumo","actor":3,"pai":"?"}
+        {"type":"dahai","actor":3,"pai":"1m","tsumogiri":true}
+    "#;
+    let mut ps = state_from_log(0, log);
+    let legal = |mask: [bool; ACTION_SPACE]| -> Vec<_> {
+        mask.iter()
+            .enumerate()
+            .filter(|(_, &b)| b)
+            .map(|(i, _)| i)
+            .collect()
+    };
+    // Chi low or pass.
+    assert_eq!(legal(ps.legal_action_mask(false)), [38, 45]);
+    assert!(legal(ps.legal_action_mask(true)).is_empty());
+
+    ps.update(
+        &serde_json::from_str(
+            r#"{"type":"chi","actor":0,"target":3,"pai":"1m","consumed":["2m","3m"]}"#,
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    // 4m is kuikae, and 5mr is discarded as 37-D.
+    assert_eq!(
+        legal(ps.legal_action_mask(false)),
+        [
+            tuz!(6p),
+            tuz!(7p),
+            tuz!(8p),
+            tuz!(E),
+            tuz!(S),
+            tuz!(W),
+            tuz!(5mr)
+        ],
+    );
+}
+
+#[test]
+fn call_candidates() {
+    let log = r#"
+        {"type":"start_kyoku","bakaze":"E","dora_marker":"9p","kyoku":4,"honba":0,"kyotaku":0,"oya":3,"scores":[25000,25000,25000,25000],"tehais":[["3m","4m","5mr","5m","6m","E","E","S","S","W","W","N","N"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"]]}
+        {"type":"tsumo","actor":3,"pai":"?"}
+        {"type":"dahai","actor":3,"pai":"4m","tsumogiri":true}
+    "#;
+    let ps = state_from_log(0, log);
+    let chi = |consumed| Event::Chi {
+        actor: 0,
+        target: 3,
+        pai: t!(4m),
+        consumed,
+    };
+    assert_eq!(
+        ps.call_candidates(),
+        [
+            chi(t![5mr, 6m]),
+            chi(t![5m, 6m]),
+            chi(t![3m, 5mr]),
+            chi(t![3m, 5m]),
+        ],
+    );
+    for call in ps.call_candidates() {
+        assert!(ps.validate_reaction(&call).is_ok());
+    }
+
+    let log = r#"
+        {"type":"start_kyoku","bakaze":"E","dora_marker":"9p","kyoku":3,"honba":0,"kyotaku":0,"oya":2,"scores":[25000,25000,25000,25000],"tehais":[["3m","4m","5mr","5m","6m","E","E","S","S","W","W","N","N"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"]]}
+        {"type":"tsumo","actor":2,"pai":"?"}
+        {"type":"dahai","actor":2,"pai":"5m","tsumogiri":true}
+    "#;
+    let ps = state_from_log(0, log);
+    // Only one non-aka 5m is left for the pon.
+    assert_eq!(
+        ps.call_candidates(),
+        [Event::Pon {
+            actor: 0,
+            target: 2,
+            pai: t!(5m),
+            consumed: t![5mr, 5m],
+        }],
+    );
+
+    let log = r#"
+        {"type":"start_kyoku","bakaze":"E","dora_marker":"9p","kyoku":3,"honba":0,"kyotaku":0,"oya":2,"scores":[25000,25000,25000,25000],"tehais":[["3m","4m","5mr","5m","6m","E","E","S","S","W","W","N","N"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"]]}
+        {"type":"tsumo","actor":2,"pai":"?"}
+        {"type":"dahai","actor":2,"pai":"1p","tsumogiri":true}
+    "#;
+    assert!(state_from_log(0, log).call_candidates().is_empty());
+}
+
+#[test]
+fn riichi_ev() {
+    let log = r#"
+        {"type":"start_kyoku","bakaze":"E","dora_marker":"1p","kyoku":1,"honba":0,"kyotaku":0,"oya":0,"scores":[25000,25000,25000,25000],"tehais":[["2m","3m","4m","6m","7m","8m","3p","4p","5p","6s","8s","9s","9s"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"]]}
+        {"type":"tsumo","actor":0,"pai":"N"}
+    "#;
+    let ps = state_from_log(0, log);
+    let evs = ps.riichi_ev();
+    // Only discarding N keeps tenpai.
+    assert_eq!(evs.len(), 1);
+    let ev = &evs[0];
+    assert_eq!(ev.discard, t!(N));
+    assert_eq!(ev.waits, [t!(7s)]);
+    assert_eq!(ev.live_tiles, 4);
+    assert!(!ev.furiten);
+
+    // No yaku for dama ron, so riichi wins more often, and for more.
+    assert!(ev.riichi.win_rate > ev.dama.win_rate);
+    assert!(ev.riichi.win_value > ev.dama.win_value);
+    assert!(ev.dama.win_rate > 0.);
+    // Nobody else is in riichi.
+    assert!(ev.riichi.deal_in_rate.abs() < 1e-6);
+    assert!(ev.riichi.ev > ev.dama.ev);
+
+    let log = r#"
+        {"type":"start_kyoku","bakaze":"E","dora_marker":"1p","kyoku":1,"honba":0,"kyotaku":0,"oya":0,"scores":[25000,25000,25000,25000],"tehais":[["2m","3m","4m","6m","7m","8m","3p","4p","5p","6s","7s","9s","E"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"]]}
+        {"type":"tsumo","actor":0,"pai":"N"}
+    "#;
+    assert!(state_from_log(0, log).riichi_ev().is_empty());
+}
+
+#[test]
+fn kan_analysis() {
+    let log = r#"
+        {"type":"start_kyoku","bakaze":"E","dora_marker":"9m","kyoku":1,"honba":0,"kyotaku":0,"oya":0,"scores":[25000,25000,25000,25000],"tehais":[["1m","1m","1m","2p","3p","4p","5s","6s","7s","8s","8s","E","E"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"]]}
+        {"type":"tsumo","actor":0,"pai":"1m"}
+    "#;
+    let ps = state_from_log(0, log);
+    let analysis = ps.kan_analysis();
+    assert_eq!(analysis.len(), 1);
+    let ankan = &analysis[0];
+    assert_eq!(ankan.kind, KanKind::Ankan);
+    assert_eq!(ankan.tile, t!(1m));
+    // Shanpon on 8s and E either way.
+    assert_eq!(ankan.shanten_before, 0);
+    assert_eq!(ankan.shanten_after, 0);
+    assert_eq!(ankan.waits_before, [t!(8s), t!(E)]);
+    assert_eq!(ankan.waits_after, [t!(8s), t!(E)]);
+    assert_eq!(ankan.live_waits_before, 4);
+    assert_eq!(ankan.live_waits_after, 4);
+    assert_eq!(ankan.doras_in_kan, 4);
+    assert!(!ankan.breaks_menzen);
+
+    let log = r#"
+        {"type":"start_kyoku","bakaze":"E","dora_marker":"9p","kyoku":1,"honba":0,"kyotaku":0,"oya":0,"scores":[25000,25000,25000,25000],"tehais":[["?","?","?","?","?","?","?","?","?","?","?","?","?"],["5p","5p","5p","1m","2m","3m","4s","5s","6s","7s","8s","9s","E"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"]]}
+        {"type":"tsumo","actor":0,"pai":"?"}
+        {"type":"dahai","actor":0,"pai":"5pr","tsumogiri":true}
+    "#;
+    let ps = state_from_log(1, log);
+    let analysis = ps.kan_analysis();
+    assert_eq!(analysis.len(), 1);
+    let daiminkan = &analysis[0];
+    assert_eq!(daiminkan.kind, KanKind::Daiminkan);
+    assert_eq!(daiminkan.tile, t!(5p));
+    assert_eq!(daiminkan.waits_before, [t!(E)]);
+    assert_eq!(daiminkan.waits_after, [t!(E)]);
+    assert_eq!(daiminkan.live_waits_after, 3);
+    // The aka 5p, which is not a dora otherwise.
+    assert_eq!(daiminkan.doras_in_kan, 1);
+    assert!(daiminkan.breaks_menzen);
+
+    let log = r#"
+        {"type":"start_kyoku","bakaze":"E","dora_marker":"9p","kyoku":1,"honba":0,"kyotaku":0,"oya":0,"scores":[25000,25000,25000,25000],"tehais":[["1m","2m","3m","4p","5p","6p","7s","8s","9s","E","E","3s","C"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"]]}
+        {"type":"tsumo","actor":0,"pai":"5s"}
+    "#;
+    assert!(state_from_log(0, log).kan_analysis().is_empty());
+}
+
+#[test]
+fn riichi_candidates() {
+    let log = r#"
+        {"type":"start_kyoku","bakaze":"E","dora_marker":"1p","kyoku":1,"honba":0,"kyotaku":0,"oya":0,"scores":[25000,25000,25000,25000],"tehais":[["2m","3m","4m","6m","7m","8m","3p","4p","5pr","6s","7s","8s","9s"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"]]}
+        {"type":"tsumo","actor":0,"pai":"N"}
+    "#;
+    let ps = state_from_log(0, log);
+    // Either end of 6789s keeps tenpai, or the tsumo itself.
+    assert_eq!(ps.riichi_candidates(), [t!(6s), t!(9s), t!(N)]);
+    assert_eq!(ps.last_cans.agari_type(), None);
+
+    let log = r#"
+        {"type":"start_kyoku","bakaze":"E","dora_marker":"1p","kyoku":1,"honba":0,"kyotaku":0,"oya":0,"scores":[25000,25000,25000,25000],"tehais":[["2m","3m","4m","6m","7m","8m","3p","4p","5pr","6s","7s","8s","9s"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"]]}
+        {"type":"tsumo","actor":0,"pai":"9s"}
+    "#;
+    let ps = state_from_log(0, log);
+    assert_eq!(ps.last_cans.agari_type(), Some("tsumo"));
+
+    let log = r#"
+        {"type":"start_kyoku","bakaze":"E","dora_marker":"9p","kyoku":1,"honba":0,"kyotaku":0,"oya":0,"scores":[25000,25000,25000,25000],"tehais":[["1m","2m","3m","4p","5p","6p","7s","8s","9s","E","E","1s","C"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"]]}
+        {"type":"tsumo","actor":0,"pai":"5s"}
+    "#;
+    assert!(state_from_log(0, log).riichi_candidates().is_empty());
+}
+
+#[test]
+fn placement_ev() {
+    let log = r#"
+        {"type":"start_kyoku","bakaze":"S","dora_marker":"9s","kyoku":4,"honba":0,"kyotaku":0,"oya":3,"scores":[20000,26000,27000,27000],"tehais":[["1m","2m","3m","4m","5m","6m","7m","8m","9m","2p","3p","4p","5p"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"]]}
+        {"type":"tsumo","actor":3,"pai":"?"}
+        {"type":"dahai","actor":3,"pai":"N","tsumogiri":true}
+        {"type":"tsumo","actor":0,"pai":"E"}
+        {"type":"dahai","actor":0,"pai":"E","tsumogiri":true}
+        {"type":"tsumo","actor":1,"pai":"?"}
+        {"type":"dahai","actor":1,"pai":"W","tsumogiri":true}
+        {"type":"tsumo","actor":2,"pai":"?"}
+        {"type":"dahai","actor":2,"pai":"S","tsumogiri":true}
+        {"type":"tsumo","actor":3,"pai":"?"}
+        {"type":"dahai","actor":3,"pai":"C","tsumogiri":true}
+        {"type":"tsumo","actor":0,"pai":"5p"}
+    "#;
+    let ps = state_from_log(0, log);
+    let evs = ps.placement_ev();
+    // Hora, plus 13 distinct tiles to discard.
+    assert_eq!(evs.len(), 14);
+    for ev in &evs {
+        assert!((ev.rank_probs.iter().sum::<f32>() - 1.).abs() < 1e-4);
+    }
+
+    // 1000-2000 is not enough to avoid the last.
+    let hora = &evs[0];
+    assert!(matches!(
+        hora.action,
+        Event::Hora {
+            actor: 0,
+            target: 0,
+            ..
+        }
+    ));
+    assert!((hora.rank_probs[3] - 1.).abs() < 1e-6);
+    assert!((hora.avg_rank() - 4.).abs() < 1e-6);
+
+    // Keeping tenpai gives a chance to climb, unlike breaking the hand.
+    let rank_probs_of = |tile: Tile| {
+        evs.iter()
+            .find(|ev| matches!(ev.action, Event::Dahai { pai, .. } if pai == tile))
+            .unwrap()
+            .rank_probs
+    };
+    let tenpai = rank_probs_of(t!(5p));
+    let broken = rank_probs_of(t!(5m));
+    assert!(tenpai[3] < broken[3]);
+    assert!(tenpai[0] > 0.);
+
+    // Not all-last.
+    let log = log.replace(r#""bakaze":"S""#, r#""bakaze":"E""#);
+    assert!(state_from_log(0, &log).placement_ev().is_empty());
+}
+
+#[test]
+fn possible_yaku() {
+    let names = |ps: &PlayerState| {
+        ps.possible_yaku()
+            .into_iter()
+            .map(|y| (y.name, y.locked))
+            .collect::<Vec<_>>()
+    };
+
+    // Yakunashi on both waits, where the pair of E is not pinfu.
+    let log = r#"
+        {"type":"start_kyoku","bakaze":"E","dora_marker":"9s","kyoku":1,"honba":0,"kyotaku":0,"oya":0,"scores":[25000,25000,25000,25000],"tehais":[["1m","2m","3m","4p","5p","6p","7s","8s","9s","2s","3s","E","E"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"]]}
+    "#;
+    let ps = state_from_log(0, log);
+    assert_eq!(names(&ps), [("menzen_tsumo", false)]);
+
+    // Pinfu on both waits, but tanyao only on 4s.
+    let log = log.replace(
+        r#""1m","2m","3m","4p","5p","6p","7s","8s","9s","2s","3s","E","E""#,
+        r#""2m","3m","4m","4p","5p","6p","6s","7s","8s","2s","3s","5p","5p""#,
+    );
+    let ps = state_from_log(0, &log);
+    assert_eq!(
+        names(&ps),
+        [("menzen_tsumo", false), ("pinfu", true), ("tanyao", false)],
+    );
+
+    // An open hand far from tenpai, with a pon of C.
+    let log = r#"
+        {"type":"start_kyoku","bakaze":"E","dora_marker":"9s","kyoku":4,"honba":0,"kyotaku":0,"oya":3,"scores":[25000,25000,25000,25000],"tehais":[["1m","2m","3m","5m","6m","7m","2p","3p","9s","9s","C","C","N"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"]]}
+        {"type":"tsumo","actor":3,"pai":"?"}
+        {"type":"dahai","actor":3,"pai":"C","tsumogiri":true}
+        {"type":"pon","actor":0,"target":3,"pai":"C","consumed":["C","C"]}
+        {"type":"dahai","actor":0,"pai":"N","tsumogiri":false}
+    "#;
+    let ps = state_from_log(0, log);
+    assert_eq!(names(&ps), [("yakuhai_chun", true)]);
+}
+
+#[test]
+fn furiten_info() {
+    let log = r#"
+        {"type":"start_kyoku","bakaze":"E","dora_marker":"3p","kyoku":1,"honba":0,"kyotaku":0,"oya":0,"scores":[25000,25000,25000,25000],"tehais":[["2m","3m","4m","5mr","6m","4p","5p","6p","7p","8p","9p","5s","8s"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"]]}
+        {"type":"tsumo","actor":0,"pai":"8s"}
+        {"type":"dahai","actor":0,"pai":"5s","tsumogiri":false}
+        {"type":"tsumo","actor":1,"pai":"?"}
+        {"type":"dahai","actor":1,"pai":"1m","tsumogiri":false}
+    "#;
+    // Not reported while the ron chance is still there.
+    let ps = state_from_log(0, log);
+    assert!(ps.furiten_info().is_none());
+
+    let log = format!(
+        "{}\n{}",
+        log.trim(),
+        r#"
+        {"type":"tsumo","actor":2,"pai":"?"}
+        "#
+        .trim(),
+    );
+    let ps = state_from_log(0, &log);
+    let info = ps.furiten_info().unwrap();
+    assert_eq!((info.kind, info.tile), (FuritenKind::SameCycle, t!(1m)));
+
+    let log = format!(
+        "{}\n{}",
+        log.trim(),
+        r#"
+        {"type":"dahai","actor":2,"pai":"1s","tsumogiri":true}
+        {"type":"tsumo","actor":3,"pai":"?"}
+        {"type":"dahai","actor":3,"pai":"S","tsumogiri":true}
+        {"type":"tsumo","actor":0,"pai":"4m"}
+        "#
+        .trim(),
+    );
+    // Still same-cycle, and the tsumo does not change it.
+    let ps = state_from_log(0, &log);
+    assert_eq!(ps.furiten_info().unwrap().kind, FuritenKind::SameCycle);
+
+    let log = format!(
+        "{}\n{}",
+        log.trim(),
+        r#"
+        {"type":"dahai","actor":0,"pai":"4m","tsumogiri":true}
+        "#
+        .trim(),
+    );
+    let ps = state_from_log(0, &log);
+    let info = ps.furiten_info().unwrap();
+    assert_eq!((info.kind, info.tile), (FuritenKind::Discard, t!(4m)));
+
+    // Passing on a wait after riichi.
+    let log = r#"
+        {"type":"start_kyoku","bakaze":"E","dora_marker":"3p","kyoku":1,"honba":0,"kyotaku":0,"oya":0,"scores":[25000,25000,25000,25000],"tehais":[["2m","3m","4m","5mr","6m","4p","5p","6p","7p","8p","9p","5s","8s"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"]]}
+        {"type":"tsumo","actor":0,"pai":"8s"}
+        {"type":"reach","actor":0}
+        {"type":"dahai","actor":0,"pai":"5s","tsumogiri":false}
+        {"type":"reach_accepted","actor":0}
+        {"type":"tsumo","actor":1,"pai":"?"}
+        {"type":"dahai","actor":1,"pai":"7m","tsumogiri":false}
+        {"type":"tsumo","actor":2,"pai":"?"}
+    "#;
+    let ps = state_from_log(0, log);
+    let info = ps.furiten_info().unwrap();
+    assert_eq!((info.kind, info.tile), (FuritenKind::Riichi, t!(7m)));
+}
+
+#[test]
+fn nagashi_mangan() {
+    let log = r#"
+        {"type":"start_kyoku","bakaze":"E","dora_marker":"9s","kyoku":1,"honba":0,"kyotaku":0,"oya":0,"scores":[25000,25000,25000,25000],"tehais":[["1m","2m","3m","4p","5p","6p","7s","8s","9s","2s","3s","E","E"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"]]}
+        {"type":"tsumo","actor":0,"pai":"N"}
+        {"type":"dahai","actor":0,"pai":"N","tsumogiri":true}
+        {"type":"tsumo","actor":1,"pai":"?"}
+        {"type":"dahai","actor":1,"pai":"5m","tsumogiri":true}
+        {"type":"tsumo","actor":2,"pai":"?"}
+        {"type":"dahai","actor":2,"pai":"C","tsumogiri":true}
+        {"type":"pon","actor":3,"target":2,"pai":"C","consumed":["C","C"]}
+        {"type":"dahai","actor":3,"pai":"9p","tsumogiri":false}
+    "#;
+    let ps = state_from_log(0, log);
+    assert_eq!(ps.nagashi_mangan(), [true, false, false, true]);
+    // Relative to the seat.
+    assert_eq!(
+        ps.public_view_from(1).nagashi_mangan(),
+        [false, false, true, true],
+    );
+
+    let rule = RuleSet {
+        nagashi_mangan: false,
+        ..Default::default()
+    };
+    let ps = state_from_log_with_rule(0, rule, log);
+    assert_eq!(ps.nagashi_mangan(), [false; 4]);
+}
+
+#[test]
+fn is_all_last() {
+    let all_last = |rule, bakaze: &str, kyoku| {
+        let log = format!(
+            r#"{{"type":"start_kyoku","bakaze":"{bakaze}","dora_marker":"9s","kyoku":{kyoku},"honba":0,"kyotaku":0,"oya":{},"scores":[25000,25000,25000,25000],"tehais":[["1m","2m","3m","4p","5p","6p","7s","8s","9s","2s","3s","E","E"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"]]}}"#,
+            kyoku - 1,
+        );
+        state_from_log_with_rule(0, rule, &log).is_all_last()
+    };
+
+    let hanchan = RuleSet::default();
+    assert!(!all_last(hanchan, "E", 4));
+    assert!(!all_last(hanchan, "S", 3));
+    assert!(all_last(hanchan, "S", 4));
+    assert!(all_last(hanchan, "W", 1));
+
+    let tonpuusen = RuleSet {
+        tonpuusen: true,
+        ..Default::default()
+    };
+    assert!(!all_last(tonpuusen, "E", 3));
+    assert!(all_last(tonpuusen, "E", 4));
+    assert!(all_last(tonpuusen, "S", 2));
+}
+
+#[test]
+fn suit_perm() {
+    let log = r#"
+        {"type":"start_kyoku","bakaze":"E","dora_marker":"5pr","kyoku":1,"honba":0,"kyotaku":0,"oya":0,"scores":[25000,25000,25000,25000],"tehais":[["1m","2m","3m","5mr","6m","4p","6p","7s","8s","9s","2s","E","E"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"]]}
+        {"type":"tsumo","actor":0,"pai":"1s"}
+    "#;
+    let ps = state_from_log(0, log);
+    let (obs, mask) = ps.encode_obs(false);
+    let (same_obs, same_mask) = ps.encode_obs_with(false, ObsVersion::V1, SuitPerm::IDENTITY);
+    assert_eq!(obs, same_obs);
+    assert_eq!(mask, same_mask);
+
+    // m -> p, p -> s, s -> m
+    let perm = SuitPerm::new([1, 2, 0]).unwrap();
+    assert!(SuitPerm::new([1, 1, 0]).is_err());
+    assert_eq!(perm.tile_id(tuz!(3m)), tuz!(3p));
+    assert_eq!(perm.tile_id(tuz!(9s)), tuz!(9m));
+    assert_eq!(perm.tile_id(tuz!(5pr)), tuz!(5sr));
+    assert_eq!(perm.tile_id(tuz!(C)), tuz!(C));
+    assert_eq!(perm.action(tuz!(5mr), false), tuz!(5pr));
+    assert_eq!(perm.action(40, false), 40);
+
+    let (perm_obs, perm_mask) = ps.encode_obs_with(false, ObsVersion::V1, perm);
+    // The tehai is permuted by columns.
+    for tid in 0..34 {
+        assert_eq!(
+            perm_obs.slice(s![..4, perm.tile_id(tid)]),
+            obs.slice(s![..4, tid]),
+        );
+    }
+    // The aka rows of the hand are permuted as rows, where 5mr becomes 5pr.
+    for suit in 0..3 {
+        let to = perm.as_array()[suit] as usize;
+        assert_eq!(perm_obs.row(4 + to), obs.row(4 + suit));
+    }
+    for action in 0..ACTION_SPACE {
+        assert_eq!(perm_mask[perm.action(action, false)], mask[action]);
+    }
+    assert!(perm_mask[tuz!(5pr)]);
+    assert!(!perm_mask[tuz!(5mr)]);
+
+    // The permutations are distinct and cancel out with their inverses.
+    let mut arr = obs.clone();
+    for perm in SuitPerm::ALL {
+        let p = perm.as_array();
+        let mut inv = [0; 3];
+        for (suit, &to) in p.iter().enumerate() {
+            inv[to as usize] = suit as u8;
+        }
+        perm.apply_to_obs(arr.view_mut(), &[4]);
+        SuitPerm::new(inv)
+            .unwrap()
+            .apply_to_obs(arr.view_mut(), &[4]);
+        assert_eq!(arr, obs);
+    }
+}
+
+#[test]
+fn encode_obs_into() {
+    let log = r#"
+        {"type":"start_kyoku","bakaze":"E","dora_marker":"5pr","kyoku":1,"honba":0,"kyotaku":0,"oya":0,"scores":[25000,25000,25000,25000],"tehais":[["1m","2m","3m","5mr","6m","4p","6p","7s","8s","9s","2s","E","E"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"]]}
+        {"type":"tsumo","actor":0,"pai":"1s"}
+    "#;
+    let ps = state_from_log(0, log);
+    let (obs, mask) = ps.encode_obs(false);
+
+    // The second slot of a flat buffer of a batch.
+    let size = OBS_SHAPE.0 * OBS_SHAPE.1;
+    let mut buf = vec![0.; size * 2];
+    let arr = ArrayViewMut2::from_shape(OBS_SHAPE, &mut buf[size..]).unwrap();
+    let mask_into = ps.encode_obs_into(false, ObsVersion::V1, SuitPerm::IDENTITY, arr);
+    assert_eq!(mask_into.to_vec(), mask.to_vec());
+    assert!(buf[..size].iter().all(|&v| v == 0.));
+    assert_eq!(
+        ArrayView2::from_shape(OBS_SHAPE, &buf[size..]).unwrap(),
+        obs,
+    );
+}
+
+#[test]
+fn obs_version_v2() {
+    let log = r#"
+        {"type":"start_kyoku","bakaze":"E","dora_marker":"5pr","kyoku":1,"honba":0,"kyotaku":0,"oya":0,"scores":[25000,25000,25000,25000],"tehais":[["1m","2m","3m","5mr","6m","4p","6p","7s","8s","9s","2s","E","E"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"]]}
+        {"type":"tsumo","actor":0,"pai":"1s"}
+        {"type":"dahai","actor":0,"pai":"1s","tsumogiri":true}
+        {"type":"tsumo","actor":1,"pai":"?"}
+        {"type":"dahai","actor":1,"pai":"N","tsumogiri":false}
+    "#;
+    let ps = state_from_log(0, log);
+    let (v1, v1_mask) = ps.encode_obs(false);
+    let (v2, v2_mask) = ps.encode_obs_with(false, ObsVersion::V2, SuitPerm::IDENTITY);
+    assert_eq!(v2.dim(), OBS_SHAPE_V2);
+    assert_eq!(v2.slice(s![..OBS_SHAPE.0, ..]), v1);
+    assert_eq!(v2_mask, v1_mask);
+
+    let extra = v2.slice(s![OBS_SHAPE.0.., ..]);
+    let nonzero = |row: usize| -> Vec<_> {
+        (0..34)
+            .filter(|&tid| extra[[row, tid]] > 0.)
+            .map(|tid| must_tile!(tid))
+            .collect()
+    };
+    // Self: 1s tsumogiri at the first turn.
+    assert_eq!(nonzero(0), [t!(1s)]);
+    assert!(nonzero(1).is_empty());
+    assert_eq!(nonzero(2), [t!(1s)]);
+    // Shimocha: N tedashi.
+    assert_eq!(nonzero(3), [t!(N)]);
+    assert_eq!(nonzero(4), [t!(N)]);
+    assert!(nonzero(5).is_empty());
+    // The dora is 6p.
+    assert_eq!(nonzero(12), [t!(6p)]);
+    assert_eq!(nonzero(13), [t!(5p), t!(7p)]);
+    assert_eq!(nonzero(14), [t!(4p), t!(8p)]);
+}
+
+#[test]
+fn obs_version_v3() {
+    let log = r#"
+        {"type":"start_kyoku","bakaze":"E","dora_marker":"5pr","kyoku":1,"honba":0,"kyotaku":0,"oya":0,"scores":[25000,25000,25000,25000],"tehais":[["1m","2m","3m","5mr","6m","4p","6p","7s","8s","9s","2s","E","E"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"]]}
+        {"type":"tsumo","actor":0,"pai":"1s"}
+        {"type":"dahai","actor":0,"pai":"1s","tsumogiri":true}
+        {"type":"tsumo","actor":1,"pai":"?"}
+        {"type":"reach","actor":1,"open":true}
+        {"type":"dahai","actor":1,"pai":"N","tsumogiri":false}
+        {"type":"reach_accepted","actor":1}
+    "#;
+    let rule = RuleSet {
+        open_riichi: true,
+        ..Default::default()
+    };
+    let ps = state_from_log_with_rule(0, rule, log);
+    let (v2, _) = ps.encode_obs_with(false, ObsVersion::V2, SuitPerm::IDENTITY);
+    let (v3, _) = ps.encode_obs_with(false, ObsVersion::V3, SuitPerm::IDENTITY);
+    assert_eq!(v3.dim(), OBS_SHAPE_V3);
+    assert_eq!(v3.slice(s![..OBS_SHAPE_V2.0, ..]), v2);
+
+    let extra = v3.slice(s![OBS_SHAPE_V2.0.., ..]);
+    for rel_seat in 1..4 {
+        let estimate = BaselineOpponentModel.estimate(&ps, rel_seat);
+        let row = (rel_seat as usize - 1) * 4;
+        assert!(extra
+            .row(row)
+            .iter()
+            .all(|v| (v - estimate.tenpai).abs() < f32::EPSILON));
+        assert_eq!(extra.row(row + 1).to_vec(), estimate.holds);
+        assert_eq!(extra.row(row + 2).to_vec(), estimate.waits);
+        // Only shimocha is in open riichi.
+        let open = if rel_seat == 1 { 1. } else { 0. };
+        assert!(extra
+            .row(row + 3)
+            .iter()
+            .all(|v| (v - open).abs() < f32::EPSILON));
+    }
+    // Shimocha is in riichi.
+    assert!((extra[[0, 0]] - 1.).abs() < f32::EPSILON);
+}
+
+#[test]
+fn table_info() {
+    let log = r#"
+        {"type":"start_kyoku","bakaze":"E","dora_marker":"5pr","kyoku":2,"honba":1,"kyotaku":0,"oya":1,"scores":[25000,25000,25000,25000],"tehais":[["1m","2m","3m","5mr","6m","4p","6p","7s","8s","9s","2s","E","E"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"]]}
+        {"type":"tsumo","actor":1,"pai":"?"}
+        {"type":"dahai","actor":1,"pai":"E","tsumogiri":false}
+        {"type":"pon","actor":0,"target":1,"pai":"E","consumed":["E","E"]}
+        {"type":"dahai","actor":0,"pai":"2s","tsumogiri":false}
+        {"type":"tsumo","actor":1,"pai":"?"}
+        {"type":"dahai","actor":1,"pai":"1p","tsumogiri":true}
+        {"type":"tsumo","actor":2,"pai":"?"}
+        {"type":"dahai","actor":2,"pai":"9p","tsumogiri":false}
+        {"type":"tsumo","actor":3,"pai":"?"}
+        {"type":"dahai","actor":3,"pai":"C","tsumogiri":false}
+        {"type":"tsumo","actor":0,"pai":"5s"}
+    "#;
+    let ps = state_from_log(0, log);
+    let expected = "\
+E2-1  kyotaku: 0  tiles left: 65  dora indicators: 5pr
+N  25000  kawa: (EE+E)2s  fuuro: EEE
+E  25000  kawa: E 1p^
+S  25000  kawa: 9p
+W  25000  kawa: C
+tehai: 12306m 46p 789s + 5s";
+    assert_eq!(ps.table_info(), expected);
+}
+
+#[test]
+fn strict() {
+    let kyoku = r#"
+        {"type":"start_kyoku","bakaze":"E","dora_marker":"5s","kyoku":1,"honba":1,"kyotaku":1,"oya":0,"scores":[25000,25000,24000,25000],"tehais":[["1m","2m","3m","4p","5p","6p","7s","8s","9s","E","E","E","S"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"]]}
+        {"type":"tsumo","actor":0,"pai":"9p"}
+        {"type":"dahai","actor":0,"pai":"9p","tsumogiri":true}
+        {"type":"tsumo","actor":1,"pai":"?"}
+        {"type":"dahai","actor":1,"pai":"1p","tsumogiri":true}
+        {"type":"tsumo","actor":2,"pai":"?"}
+        {"type":"dahai","actor":2,"pai":"1p","tsumogiri":true}
+        {"type":"tsumo","actor":3,"pai":"?"}
+        {"type":"dahai","actor":3,"pai":"1p","tsumogiri":true}
+        {"type":"tsumo","actor":0,"pai":"S"}
+    "#;
+    let run = |hora_deltas: &str, next_kyoku: &str| {
+        let mut ps = PlayerState::new(0);
+        ps.set_strict(true);
+        for line in kyoku.trim().lines() {
+            ps.update_json(line).unwrap();
+        }
+        // oya 40 fu 3 han tsumo: 2600 all, plus 1 honba and 1 kyotaku
+        ps.update_json(&format!(
+            r#"{{"type":"hora","actor":0,"target":0,"deltas":{hora_deltas}}}"#,
+        ))?;
+        ps.update_json(r#"{"type":"end_kyoku"}"#)?;
+        ps.update_json(&format!(
+            r#"{{"type":"start_kyoku","bakaze":"E","dora_marker":"1m","kyoku":1,{next_kyoku},"oya":0,"tehais":[["1m","2m","3m","4p","5p","6p","7s","8s","9s","E","E","E","S"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"]]}}"#,
+        ))?;
+        Ok::<_, crate::error::Error>(())
+    };
+
+    let deltas = "[9100,-2700,-2700,-2700]";
+    let next = r#""honba":2,"kyotaku":0,"scores":[34100,22300,21300,22300]"#;
+    run(deltas, next).unwrap();
+
+    // The tsumo is not paid as a ron.
+    run("[8800,-2600,-2600,-2600]", next).unwrap_err();
+    // The oya won, so the honba goes on.
+    let no_renchan = r#""honba":0,"kyotaku":0,"scores":[34100,22300,21300,22300]"#;
+    run(deltas, no_renchan).unwrap_err();
+    // The kyotaku went to the winner.
+    let kept_kyotaku = r#""honba":2,"kyotaku":1,"scores":[34100,22300,21300,22300]"#;
+    run(deltas, kept_kyotaku).unwrap_err();
+    let wrong_scores = r#""honba":2,"kyotaku":0,"scores":[34100,22300,22300,21300]"#;
+    run(deltas, wrong_scores).unwrap_err();
+
+    // Nothing is checked unless strict.
+    let mut ps = PlayerState::new(0);
+    for line in kyoku.trim().lines() {
+        ps.update_json(line).unwrap();
+    }
+    ps.update_json(r#"{"type":"hora","actor":0,"target":0,"deltas":[0,0,0,0]}"#)
+        .unwrap();
+}
+
+#[test]
+fn riichi_and_called_tiles() {
+    let log = r#"
+        {"type":"start_kyoku","bakaze":"E","dora_marker":"5pr","kyoku":1,"honba":0,"kyotaku":0,"oya":0,"scores":[25000,25000,25000,25000],"tehais":[["1m","2m","3m","5mr","6m","4p","6p","7s","8s","9s","2s","E","E"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"]]}
+        {"type":"tsumo","actor":0,"pai":"1s"}
+        {"type":"dahai","actor":0,"pai":"1s","tsumogiri":true}
+        {"type":"tsumo","actor":1,"pai":"?"}
+        {"type":"dahai","actor":1,"pai":"9m","tsumogiri":true}
+        {"type":"tsumo","actor":2,"pai":"?"}
+        {"type":"dahai","actor":2,"pai":"W","tsumogiri":false}
+        {"type":"tsumo","actor":3,"pai":"?"}
+        {"type":"dahai","actor":3,"pai":"C","tsumogiri":false}
+        {"type":"pon","actor":2,"target":3,"pai":"C","consumed":["C","C"]}
+        {"type":"dahai","actor":2,"pai":"1p","tsumogiri":false}
+        {"type":"tsumo","actor":3,"pai":"?"}
+        {"type":"dahai","actor":3,"pai":"2p","tsumogiri":true}
+        {"type":"tsumo","actor":0,"pai":"9p"}
+        {"type":"dahai","actor":0,"pai":"9p","tsumogiri":true}
+        {"type":"tsumo","actor":1,"pai":"?"}
+        {"type":"reach","actor":1}
+        {"type":"dahai","actor":1,"pai":"N","tsumogiri":false}
+        {"type":"reach_accepted","actor":1}
+    "#;
+    let ps = state_from_log(0, log);
+    let riichi = KawaMark {
+        tile: t!(N),
+        kawa_index: 1,
+    };
+    let pon = KawaMark {
+        tile: t!(C),
+        kawa_index: 1,
+    };
+    assert_eq!(ps.riichi_tiles(), [None, Some(riichi), None, None]);
+    assert_eq!(ps.called_tiles()[2].as_slice(), [pon]);
+    assert!(ps.called_tiles()[1].is_empty());
+
+    let view = ps.public_view_from(1);
+    assert_eq!(view.riichi_tiles()[0], Some(riichi));
+    assert_eq!(view.called_tiles()[1].as_slice(), [pon]);
+
+    let (v3, _) = ps.encode_obs_with(false, ObsVersion::V3, SuitPerm::IDENTITY);
+    let (v4, _) = ps.encode_obs_with(false, ObsVersion::V4, SuitPerm::IDENTITY);
+    assert_eq!(v4.dim(), OBS_SHAPE_V4);
+    assert_eq!(v4.slice(s![..OBS_SHAPE_V3.0, ..]), v3);
+
+    let extra = v4.slice(s![OBS_SHAPE_V3.0.., ..]);
+    let mut expected = ndarray::Array2::<f32>::zeros(extra.dim());
+    // shimocha: the riichi tile and the discard before it
+    expected[[0, tuz!(N)]] = 2. / 24.;
+    expected[[1, tuz!(9m)]] = 1.;
+    // toimen: the pon
+    expected[[5, tuz!(C)]] = 2. / 24.;
+    assert_eq!(extra, expected);
+}
+
+#[test]
+fn win_probability_estimate() {
+    let log = r#"
+        {"type":"start_kyoku","bakaze":"E","dora_marker":"9p","kyoku":1,"honba":0,"kyotaku":0,"oya":0,"scores":[25000,25000,25000,25000],"tehais":[["?","?","?","?","?","?","?","?","?","?","?","?","?"],["1m","2m","3m","4p","5p","6p","7s","8s","9s","E","E","2s","3s"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"]]}
+    "#;
+    let mut ps = state_from_log(1, log);
+
+    let est = ps.win_probability_estimate(0).unwrap();
+    assert_eq!(est.waits, [t!(1s), t!(4s)]);
+    assert_eq!(est.live_tiles, 8);
+    assert_eq!(est.draws, 0);
+    assert!(est.win_rate.abs() < f32::EPSILON);
+
+    let est = ps.win_probability_estimate(5).unwrap();
+    assert_eq!(est.draws, 5);
+    let unseen = (136 - 13 - 1) as f32;
+    let tsumo_rate = 1. - (1. - 8. / unseen).powi(5);
+    assert!((est.tsumo_rate - tsumo_rate).abs() < 1e-6);
+    // No yaku for ron without riichi.
+    assert!((est.win_rate - est.tsumo_rate).abs() < 1e-6);
+    // menzen tsumo 30 fu: 300/500
+    assert!((est.win_value - 1100.).abs() < 1e-3);
+    assert!(est.win_value_riichi.unwrap() > est.win_value);
+
+    // Capped by the live wall.
+    assert_eq!(ps.win_probability_estimate(100).unwrap().draws, 18);
+
+    // 3n+2
+    ps.update_json(r#"{"type":"tsumo","actor":0,"pai":"?"}"#)
+        .unwrap();
+    ps.update_json(r#"{"type":"dahai","actor":0,"pai":"N","tsumogiri":true}"#)
+        .unwrap();
+    ps.update_json(r#"{"type":"tsumo","actor":1,"pai":"P"}"#)
+        .unwrap();
+    assert!(ps.win_probability_estimate(5).is_none());
+
+    // The riichi estimate of a dama hand matches `riichi_ev`, which assumes
+    // fewer ron chances against a riichi, less the riichi stick.
+    let log = r#"
+        {"type":"start_kyoku","bakaze":"E","dora_marker":"1p","kyoku":1,"honba":0,"kyotaku":0,"oya":0,"scores":[25000,25000,25000,25000],"tehais":[["2m","3m","4m","6m","7m","8m","3p","4p","5p","6s","8s","9s","9s"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"]]}
+        {"type":"tsumo","actor":0,"pai":"N"}
+    "#;
+    let mut ps = state_from_log(0, log);
+    let ev = ps.riichi_ev().remove(0);
+    ps.update_json(r#"{"type":"dahai","actor":0,"pai":"N","tsumogiri":true}"#)
+        .unwrap();
+    let est = ps.win_probability_estimate(100).unwrap();
+    assert!((est.win_value_riichi.unwrap() + 1000. - ev.riichi.win_value).abs() < 1e-3);
+}
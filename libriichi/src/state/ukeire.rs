@@ -0,0 +1,101 @@
+use super::PlayerState;
+use crate::algo::shanten;
+use crate::must_tile;
+use crate::tile::Tile;
+
+use anyhow::{ensure, Result};
+use riichi_macros::{pyclass, pymethods};
+
+/// The tile acceptance of a discard.
+#[pyclass]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ukeire {
+    pub discard: Tile,
+    /// Shanten of the hand after the discard.
+    pub shanten: i8,
+    /// Tiles that reduce the shanten, each with the number of its copies that
+    /// have not been seen yet. Tiles with no copies left are excluded.
+    pub accepted: Vec<(Tile, u8)>,
+}
+
+#[pymethods]
+impl Ukeire {
+    #[getter]
+    fn discard(&self) -> String {
+        self.discard.to_string()
+    }
+    #[getter]
+    const fn shanten(&self) -> i8 {
+        self.shanten
+    }
+    #[getter]
+    fn accepted(&self) -> Vec<(String, u8)> {
+        self.accepted
+            .iter()
+            .map(|(t, n)| (t.to_string(), *n))
+            .collect()
+    }
+    /// Total number of the accepted tiles left.
+    #[getter]
+    #[must_use]
+    pub fn total(&self) -> u32 {
+        self.accepted.iter().map(|&(_, n)| n as u32).sum()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("{self:?}")
+    }
+}
+
+#[pymethods]
+impl PlayerState {
+    /// Returns a list of `Ukeire`, one for each legal discard.
+    ///
+    /// Raises an exception if not called at 3n+2.
+    #[pyo3(name = "ukeire")]
+    #[pyo3(text_signature = "($self, /)")]
+    fn ukeire_py(&self) -> Result<Vec<Ukeire>> {
+        ensure!(self.last_cans.can_discard, "tehai is not 3n+2");
+        Ok(self.ukeire())
+    }
+}
+
+impl PlayerState {
+    /// Must be called at 3n+2.
+    ///
+    /// Returns the tile acceptance of each legal discard, where the number of
+    /// the copies left is derived from `tiles_seen`. Aka doras are not
+    /// distinguished.
+    #[must_use]
+    pub fn ukeire(&self) -> Vec<Ukeire> {
+        self.discard_candidates()
+            .iter()
+            .enumerate()
+            .filter(|(_, &b)| b)
+            .map(|(discard, _)| {
+                let mut tehai = self.tehai;
+                tehai[discard] -= 1;
+                let shanten = shanten::calc_all(&tehai, self.tehai_len_div3);
+
+                let accepted = (0..34)
+                    .filter_map(|tid| {
+                        let left = 4 - self.tiles_seen[tid];
+                        if left == 0 {
+                            return None;
+                        }
+                        tehai[tid] += 1;
+                        let shanten_after = shanten::calc_all(&tehai, self.tehai_len_div3);
+                        tehai[tid] -= 1;
+                        (shanten_after < shanten).then(|| (must_tile!(tid), left))
+                    })
+                    .collect();
+
+                Ukeire {
+                    discard: must_tile!(discard),
+                    shanten,
+                    accepted,
+                }
+            })
+            .collect()
+    }
+}
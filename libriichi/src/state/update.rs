@@ -1,15 +1,18 @@
 use super::action::ActionCandidate;
-use super::item::{ChiPon, KawaItem, Sutehai};
+use super::item::{ChiPon, KawaItem, KawaMark, Sutehai};
 use super::PlayerState;
-use crate::algo::agari::{self, AgariCalculator};
+use crate::algo::agari::{self, Agari, AgariCalculator};
 use crate::algo::shanten;
+use crate::consts::TILES_LEFT_AT_START;
 use crate::mjai::Event;
 use crate::tile::Tile;
 use crate::{must_tile, tu8};
 use std::cmp::Ordering;
 use std::mem;
 
+use anyhow::{bail, ensure, Result};
 use tinyvec::array_vec;
+use tracing::trace_span;
 
 #[derive(Clone, Copy)]
 pub(super) enum MoveType {
@@ -19,12 +22,43 @@ pub(super) enum MoveType {
 }
 
 impl PlayerState {
+    /// Err is returned if the event cannot be applied to the state, for
+    /// example when a seat's kawa would exceed its capacity, which can only
+    /// happen with a malformed log.
     #[inline]
-    pub fn update(&mut self, event: &Event) -> ActionCandidate {
+    pub fn update(&mut self, event: &Event) -> Result<ActionCandidate> {
         self.update_with_skip(event, false)
     }
 
-    pub fn update_with_skip(&mut self, event: &Event, skip_on_announce: bool) -> ActionCandidate {
+    pub fn update_with_skip(
+        &mut self,
+        event: &Event,
+        skip_on_announce: bool,
+    ) -> Result<ActionCandidate> {
+        let _span = trace_span!("update", player_id = self.player_id, ?event).entered();
+        if let Event::Nukidora { .. } = event {
+            bail!("sanma is not supported yet: {event:?}");
+        }
+        if self.strict {
+            self.verify_event(event)?;
+        }
+
+        let had_ippatsu = self.at_ippatsu;
+        let cans = self.apply_event(event, skip_on_announce)?;
+
+        // Our own discard after riichi ends ippatsu naturally, and a new kyoku
+        // resets everything, neither of which counts as breaking it.
+        let expired = match *event {
+            Event::Dahai { actor, .. } => actor == self.player_id,
+            Event::StartKyoku { .. } => true,
+            _ => false,
+        };
+        self.ippatsu_broken = had_ippatsu && !self.at_ippatsu && !expired;
+
+        Ok(cans)
+    }
+
+    fn apply_event(&mut self, event: &Event, skip_on_announce: bool) -> Result<ActionCandidate> {
         if !skip_on_announce
             || !matches!(
                 event,
@@ -72,11 +106,9 @@ impl PlayerState {
                 self.kyotaku = kyotaku;
                 self.oya = self.rel(oya) as u8;
                 self.jikaze = must_tile!(tu8!(E) + (4 - self.oya) % 4);
-                self.is_all_last = match self.bakaze.as_u8() {
-                    tu8!(S) => kyoku == 4,
-                    tu8!(W) => true,
-                    _ => false,
-                };
+                self.is_all_last = self
+                    .rule
+                    .is_all_last((bakaze.as_u8() - tu8!(E)) * 4 + kyoku - 1);
                 self.kyoku = kyoku - 1;
 
                 self.scores = scores;
@@ -85,7 +117,8 @@ impl PlayerState {
                 self.dora_indicators.clear();
                 self.doras_owned.fill(0);
                 self.doras_seen = 0;
-                self.akas_in_hand.fill(false);
+                self.akas_in_hand.fill(0);
+                self.akas_seen.fill(0);
 
                 self.ankan_candidates.clear();
                 self.kakan_candidates.clear();
@@ -95,6 +128,7 @@ impl PlayerState {
                 self.at_rinshan = false;
                 self.at_furiten = false;
                 self.to_mark_same_cycle_furiten = None;
+                self.missed_wait = None;
 
                 self.is_menzen = true;
                 self.can_w_riichi = true;
@@ -105,9 +139,10 @@ impl PlayerState {
                 self.ankans.clear();
 
                 self.kans_on_board = 0;
+                self.next_tsumo_seat = self.oya;
                 self.tehai_len_div3 = 4;
                 self.has_next_shanten_discard = false;
-                self.tiles_left = 70;
+                self.tiles_left = TILES_LEFT_AT_START;
                 self.at_turn = 0;
 
                 self.kawa.iter_mut().for_each(|k| k.clear());
@@ -119,6 +154,10 @@ impl PlayerState {
 
                 self.riichi_declared.fill(false);
                 self.riichi_accepted.fill(false);
+                self.open_riichi.fill(false);
+                self.nagashi_mangan.fill(true);
+                self.riichi_tiles.fill(None);
+                self.called_tiles.iter_mut().for_each(|k| k.clear());
 
                 self.last_self_tsumo = None;
                 self.last_kawa_tile = None;
@@ -137,9 +176,11 @@ impl PlayerState {
             }
 
             Event::Tsumo { actor, pai } => {
+                // Rinshan draws are no exception, see `live_wall_size`.
                 self.tiles_left -= 1;
+                self.next_tsumo_seat = (self.rel(actor) as u8 + 1) % 4;
                 if actor != self.player_id {
-                    return self.last_cans;
+                    return Ok(self.last_cans);
                 }
                 self.at_turn += 1;
 
@@ -149,7 +190,7 @@ impl PlayerState {
                 self.witness_tile(pai);
                 self.move_tile(pai, MoveType::Tsumo);
 
-                if self.can_w_riichi {
+                if self.can_w_riichi && self.rule.abortive_ryukyoku {
                     self.last_cans.can_ryukyoku = self.yaokyuu_kind_count() >= 9;
                 }
 
@@ -168,25 +209,17 @@ impl PlayerState {
                     {
                         self.last_cans.can_tsumo_agari = true;
                     } else {
-                        let agari_calc = AgariCalculator {
-                            tehai: &self.tehai,
-                            is_menzen: self.is_menzen,
-                            chis: &self.chis,
-                            pons: &self.pons,
-                            minkans: &self.minkans,
-                            ankans: &self.ankans,
-                            bakaze: self.bakaze.as_u8(),
-                            jikaze: self.jikaze.as_u8(),
-                            winning_tile: pai.deaka().as_u8(),
-                            is_ron: false,
-                        };
-                        self.last_cans.can_tsumo_agari = agari_calc.has_yaku();
+                        let mut tehai = self.tehai;
+                        tehai[pai.deaka().as_usize()] -= 1;
+                        self.last_cans.can_tsumo_agari =
+                            self.has_yaku_on(&tehai, pai.deaka().as_usize(), false);
                     }
                 }
+                self.apply_ryanhan_shibari(false);
 
                 // haitei tile cannot be used to kakan or ankan
                 if self.tiles_left == 0 {
-                    return self.last_cans;
+                    return Ok(self.last_cans);
                 }
 
                 if self.riichi_accepted[0] {
@@ -202,7 +235,7 @@ impl PlayerState {
                             self.ankan_candidates.push(pai.deaka());
                         }
                     }
-                    return self.last_cans;
+                    return Ok(self.last_cans);
                 }
 
                 if self.kans_on_board < 4 {
@@ -234,6 +267,15 @@ impl PlayerState {
                 tsumogiri,
             } => {
                 let actor_rel = self.rel(actor);
+                self.ensure_kawa_capacity(actor_rel)?;
+                if actor_rel == 0 {
+                    self.ensure_discardable(pai)?;
+                }
+                self.nagashi_mangan[actor_rel] &= pai.is_yaokyuu();
+                let is_riichi = self.riichi_declared[actor_rel] && !self.riichi_accepted[actor_rel];
+                if is_riichi {
+                    self.riichi_tiles[actor_rel] = Some(self.kawa_mark(actor_rel, pai));
+                }
                 self.kawa_overview[actor_rel].push(pai);
                 self.kawa[actor_rel].push(Some(KawaItem {
                     kan: mem::take(&mut self.intermediate_kan),
@@ -242,8 +284,8 @@ impl PlayerState {
                         tile: pai,
                         is_dora: self.dora_factor[pai.deaka().as_usize()] > 0,
                         is_tedashi: !tsumogiri,
-                        is_riichi: self.riichi_declared[actor_rel]
-                            && !self.riichi_accepted[actor_rel],
+                        is_riichi,
+                        claimed_by: None,
                     },
                 }));
                 self.last_kawa_tile = Some(pai);
@@ -275,34 +317,23 @@ impl PlayerState {
                     } else if !self.at_furiten && self.waits[pai.deaka().as_usize()] {
                         // Riichi furiten
                         self.at_furiten = true;
+                        self.missed_wait = Some(pai.deaka());
                     }
 
-                    return self.last_cans;
+                    return Ok(self.last_cans);
                 }
                 self.witness_tile(pai);
 
                 if !self.at_furiten && self.waits[pai.deaka().as_usize()] {
-                    if self.riichi_accepted[0] || self.tiles_left == 0 {
-                        // 立直 or 河底撈魚
+                    self.missed_wait = Some(pai.deaka());
+                    if self.riichi_accepted[0] || self.tiles_left == 0 || self.is_renhou_chance() {
+                        // 立直, 河底撈魚 or 人和
                         self.last_cans.can_ron_agari = true;
                     } else {
-                        let mut tehai_with_winning_tile = self.tehai;
-                        tehai_with_winning_tile[pai.deaka().as_usize()] += 1;
-
-                        let agari_calc = AgariCalculator {
-                            tehai: &tehai_with_winning_tile,
-                            is_menzen: self.is_menzen,
-                            chis: &self.chis,
-                            pons: &self.pons,
-                            minkans: &self.minkans,
-                            ankans: &self.ankans,
-                            bakaze: self.bakaze.as_u8(),
-                            jikaze: self.jikaze.as_u8(),
-                            winning_tile: pai.deaka().as_u8(),
-                            is_ron: true,
-                        };
-                        self.last_cans.can_ron_agari = agari_calc.has_yaku();
+                        self.last_cans.can_ron_agari =
+                            self.has_yaku_on(&self.tehai, pai.deaka().as_usize(), true);
                     }
+                    self.apply_ryanhan_shibari(true);
 
                     // Track same-cycle furiten
                     if self.last_cans.can_ron_agari {
@@ -326,7 +357,7 @@ impl PlayerState {
                 }
 
                 if self.riichi_accepted[0] || self.tiles_left == 0 {
-                    return self.last_cans;
+                    return Ok(self.last_cans);
                 }
 
                 if actor_rel == 3 && !pai.is_jihai() && self.tehai_len_div3 > 0 {
@@ -339,15 +370,22 @@ impl PlayerState {
 
             Event::Chi {
                 actor,
+                target,
                 consumed,
                 pai,
-                ..
             } => {
                 let actor_rel = self.rel(actor);
+                if actor_rel == 0 {
+                    self.ensure_consumable(&consumed)?;
+                }
+                self.next_tsumo_seat = (actor_rel as u8 + 1) % 4;
+                self.mark_claimed(actor, target);
+                self.nagashi_mangan[self.rel(target)] = false;
                 let mut result = array_vec!();
                 result.extend_from_slice(&consumed);
                 result.push(pai);
                 self.fuuro_overview[actor_rel].push(result);
+                self.called_tiles[actor_rel].push(self.kawa_mark(actor_rel, pai));
                 self.intermediate_chi_pon = Some(ChiPon {
                     consumed,
                     target_tile: pai,
@@ -360,7 +398,7 @@ impl PlayerState {
                         .for_each(|&t| self.update_doras_owned(actor_rel, t));
                     self.can_w_riichi = false;
                     self.at_ippatsu = false;
-                    return self.last_cans;
+                    return Ok(self.last_cans);
                 }
 
                 self.last_cans.can_discard = true;
@@ -371,9 +409,9 @@ impl PlayerState {
                 self.last_self_tsumo = None;
 
                 self.update_doras_owned(0, pai);
-                consumed
-                    .iter()
-                    .for_each(|&t| self.move_tile(t, MoveType::FuuroConsume));
+                for &t in &consumed {
+                    self.move_tile(t, MoveType::FuuroConsume);
+                }
 
                 let a = consumed[0].deaka().as_usize();
                 let b = consumed[1].deaka().as_usize();
@@ -383,22 +421,25 @@ impl PlayerState {
                 self.chis.push(min.min(deaka_tile_id) as u8);
 
                 // Forbid 喰い替え
-                if self.tehai[deaka_tile_id] > 0 {
+                let kuikae = self.rule.kuikae;
+                if kuikae.forbids_genbutsu() && self.tehai[deaka_tile_id] > 0 {
                     self.forbidden_tiles[deaka_tile_id] = true;
                 }
-                if deaka_tile_id < min {
-                    if max % 9 < 8 {
-                        // Like 56s chi 4s, then 7s is not allowed to discard
-                        let bigger = max + 1;
-                        if self.tehai[bigger] > 0 {
-                            self.forbidden_tiles[bigger] = true;
+                if kuikae.forbids_suji() {
+                    if deaka_tile_id < min {
+                        if max % 9 < 8 {
+                            // Like 56s chi 4s, then 7s is not allowed to discard
+                            let bigger = max + 1;
+                            if self.tehai[bigger] > 0 {
+                                self.forbidden_tiles[bigger] = true;
+                            }
+                        }
+                    } else if deaka_tile_id > max && min % 9 > 0 {
+                        // Like 56s chi 7s, then 4s is not allowed to discard
+                        let smaller = min - 1;
+                        if self.tehai[smaller] > 0 {
+                            self.forbidden_tiles[smaller] = true;
                         }
-                    }
-                } else if deaka_tile_id > max && min % 9 > 0 {
-                    // Like 56s chi 7s, then 4s is not allowed to discard
-                    let smaller = min - 1;
-                    if self.tehai[smaller] > 0 {
-                        self.forbidden_tiles[smaller] = true;
                     }
                 }
 
@@ -415,15 +456,23 @@ impl PlayerState {
                 pai,
             } => {
                 let actor_rel = self.rel(actor);
+                self.ensure_pad_capacity(actor, target)?;
+                if actor_rel == 0 {
+                    self.ensure_consumable(&consumed)?;
+                }
+                self.next_tsumo_seat = (actor_rel as u8 + 1) % 4;
                 let mut result = array_vec!();
                 result.extend_from_slice(&consumed);
                 result.push(pai);
                 self.fuuro_overview[actor_rel].push(result);
+                self.called_tiles[actor_rel].push(self.kawa_mark(actor_rel, pai));
                 self.intermediate_chi_pon = Some(ChiPon {
                     consumed,
                     target_tile: pai,
                 });
+                self.mark_claimed(actor, target);
                 self.pad_kawa_for_pon_or_daiminkan(actor, target);
+                self.nagashi_mangan[self.rel(target)] = false;
 
                 if actor_rel != 0 {
                     consumed.iter().for_each(|&t| self.witness_tile(t));
@@ -432,7 +481,7 @@ impl PlayerState {
                         .for_each(|&t| self.update_doras_owned(actor_rel, t));
                     self.can_w_riichi = false;
                     self.at_ippatsu = false;
-                    return self.last_cans;
+                    return Ok(self.last_cans);
                 }
 
                 self.last_cans.can_discard = true;
@@ -443,12 +492,12 @@ impl PlayerState {
                 self.last_self_tsumo = None;
 
                 self.update_doras_owned(0, pai);
-                consumed
-                    .iter()
-                    .for_each(|&t| self.move_tile(t, MoveType::FuuroConsume));
+                for &t in &consumed {
+                    self.move_tile(t, MoveType::FuuroConsume);
+                }
                 self.pons.push(pai.deaka().as_u8());
 
-                if self.tehai[pai.deaka().as_usize()] > 0 {
+                if self.rule.kuikae.forbids_genbutsu() && self.tehai[pai.deaka().as_usize()] > 0 {
                     self.forbidden_tiles[pai.deaka().as_usize()] = true;
                 }
 
@@ -465,13 +514,22 @@ impl PlayerState {
                 pai,
             } => {
                 let actor_rel = self.rel(actor);
+                self.ensure_pad_capacity(actor, target)?;
+                if actor_rel == 0 {
+                    self.ensure_consumable(&consumed)?;
+                }
                 let mut result = array_vec!();
                 result.extend_from_slice(&consumed);
                 result.push(pai);
                 self.fuuro_overview[actor_rel].push(result);
+                self.called_tiles[actor_rel].push(self.kawa_mark(actor_rel, pai));
                 self.intermediate_kan.push(pai);
+                self.mark_claimed(actor, target);
                 self.pad_kawa_for_pon_or_daiminkan(actor, target);
+                self.nagashi_mangan[self.rel(target)] = false;
                 self.kans_on_board += 1;
+                // For the rinshan draw
+                self.next_tsumo_seat = actor_rel as u8;
 
                 if actor_rel != 0 {
                     consumed.iter().for_each(|&t| self.witness_tile(t));
@@ -480,17 +538,18 @@ impl PlayerState {
                         .for_each(|&t| self.update_doras_owned(actor_rel, t));
                     self.can_w_riichi = false;
                     self.at_ippatsu = false;
-                    return self.last_cans;
+                    return Ok(self.last_cans);
                 }
 
                 self.at_rinshan = true;
                 self.is_menzen = false;
+                self.can_w_riichi = false; // no chihou on the rinshan tsumo
                 self.tehai_len_div3 -= 1;
 
                 self.update_doras_owned(0, pai);
-                consumed
-                    .iter()
-                    .for_each(|&t| self.move_tile(t, MoveType::FuuroConsume));
+                for &t in &consumed {
+                    self.move_tile(t, MoveType::FuuroConsume);
+                }
                 self.minkans.push(pai.deaka().as_u8());
 
                 // The shanten number and the shape of tenpai (if any) may be
@@ -503,6 +562,9 @@ impl PlayerState {
 
             Event::Kakan { actor, pai, .. } => {
                 let actor_rel = self.rel(actor);
+                if actor_rel == 0 {
+                    self.ensure_consumable(&[pai])?;
+                }
                 for fuuro in &mut self.fuuro_overview[actor_rel] {
                     if fuuro[0].deaka() == pai.deaka() {
                         fuuro.push(pai);
@@ -511,6 +573,8 @@ impl PlayerState {
                 }
                 self.intermediate_kan.push(pai);
                 self.kans_on_board += 1;
+                // For the rinshan draw
+                self.next_tsumo_seat = actor_rel as u8;
 
                 if actor_rel != 0 {
                     self.witness_tile(pai);
@@ -521,12 +585,14 @@ impl PlayerState {
                     if !self.at_furiten && self.waits[pai.deaka().as_usize()] {
                         self.last_cans.can_ron_agari = true;
                         self.to_mark_same_cycle_furiten = Some(());
-                        self.chankan_chance = Some(());
+                        self.missed_wait = Some(pai.deaka());
+                        self.chankan_chance = Some(pai.deaka());
+                        self.apply_ryanhan_shibari(true);
                     } else {
                         self.at_ippatsu = false;
                     }
 
-                    return self.last_cans;
+                    return Ok(self.last_cans);
                 }
 
                 self.at_rinshan = true;
@@ -547,10 +613,15 @@ impl PlayerState {
 
             Event::Ankan { actor, consumed } => {
                 let actor_rel = self.rel(actor);
+                if actor_rel == 0 {
+                    self.ensure_consumable(&consumed)?;
+                }
                 let tile = consumed[0].deaka();
                 self.ankan_overview[actor_rel].push(tile);
                 self.intermediate_kan.push(tile);
                 self.kans_on_board += 1;
+                // For the rinshan draw
+                self.next_tsumo_seat = actor_rel as u8;
 
                 self.can_w_riichi = false;
                 self.at_ippatsu = false;
@@ -560,14 +631,14 @@ impl PlayerState {
                         self.witness_tile(t);
                         self.update_doras_owned(actor_rel, t);
                     }
-                    return self.last_cans;
+                    return Ok(self.last_cans);
                 }
 
                 self.at_rinshan = true;
                 self.tehai_len_div3 -= 1;
-                consumed
-                    .iter()
-                    .for_each(|&t| self.move_tile(t, MoveType::FuuroConsume));
+                for &t in &consumed {
+                    self.move_tile(t, MoveType::FuuroConsume);
+                }
                 self.ankans.push(tile.as_u8());
 
                 if !self.riichi_accepted[0] {
@@ -582,9 +653,10 @@ impl PlayerState {
                 self.add_dora_indicator(dora_marker);
             }
 
-            Event::Reach { actor } => {
+            Event::Reach { actor, open } => {
                 let actor_rel = self.rel(actor);
                 self.riichi_declared[actor_rel] = true;
+                self.open_riichi[actor_rel] = open && self.rule.open_riichi;
                 if actor_rel == 0 {
                     // `self.is_w_riichi` should not be set at ReachAccepted as
                     // `self.can_w_riichi` will be set to `false` right after
@@ -605,22 +677,30 @@ impl PlayerState {
                 }
             }
 
+            // Riichi sticks on the table only go away when someone wins. On
+            // any kind of ryukyoku, including abortive ones, they are kept
+            // as they are and carried over to the next kyoku.
+            Event::Hora { .. } => {
+                self.kyotaku = 0;
+            }
+
             _ => (),
         };
 
-        self.last_cans
+        Ok(self.last_cans)
     }
 
     pub(super) const fn rel(&self, actor: u8) -> usize {
         ((actor + 4 - self.player_id) % 4) as usize
     }
 
-    /// Updates `tiles_seen` and `doras_seen`.
+    /// Updates `tiles_seen`, `akas_seen` and `doras_seen`.
     pub(super) fn witness_tile(&mut self, tile: Tile) {
         let tile_id = tile.deaka().as_usize();
         self.tiles_seen[tile_id] += 1;
         self.doras_seen += self.dora_factor[tile_id];
         if tile.is_aka() {
+            self.akas_seen[tile.as_usize() - 34] += 1;
             self.doras_seen += 1;
         }
     }
@@ -632,15 +712,15 @@ impl PlayerState {
             let aka_id = tile.as_usize() - 34;
             match move_type {
                 MoveType::Tsumo => {
-                    self.akas_in_hand[aka_id] = true;
+                    self.akas_in_hand[aka_id] += 1;
                     self.doras_owned[0] += 1;
                 }
                 MoveType::Discard => {
-                    self.akas_in_hand[aka_id] = false;
+                    self.akas_in_hand[aka_id] -= 1;
                     self.doras_owned[0] -= 1;
                 }
                 MoveType::FuuroConsume => {
-                    self.akas_in_hand[aka_id] = false;
+                    self.akas_in_hand[aka_id] = self.akas_in_hand[aka_id].saturating_sub(1);
                 }
             }
         }
@@ -661,6 +741,43 @@ impl PlayerState {
         }
     }
 
+    /// Checked before a discard of the player changes anything, telling an
+    /// aka from the plain 5 of the same suit.
+    fn ensure_discardable(&self, tile: Tile) -> Result<()> {
+        let plain = tile.deaka();
+        let tile_id = plain.as_usize();
+        let akas = if plain.akaize() == plain {
+            0
+        } else {
+            self.akas_in_hand[tile_id / 9]
+        };
+        let count = if tile.is_aka() {
+            akas
+        } else {
+            self.tehai[tile_id].saturating_sub(akas)
+        };
+        ensure!(
+            count > 0,
+            "discarding {tile} but there is none of it in hand"
+        );
+        Ok(())
+    }
+
+    /// Checked before a call of the player changes anything. Akas are not
+    /// told apart here, as `MoveType::FuuroConsume` does not either.
+    fn ensure_consumable(&self, tiles: &[Tile]) -> Result<()> {
+        let mut tehai = self.tehai;
+        for &tile in tiles {
+            let tile_id = tile.deaka().as_usize();
+            ensure!(
+                tehai[tile_id] > 0,
+                "consuming {tile} but there is not enough of it in hand",
+            );
+            tehai[tile_id] -= 1;
+        }
+        Ok(())
+    }
+
     /// Updates `dora_indicators`, witness the dora indicator itself and
     /// recounts doras (`doras_seen` and `doras_owned`) based on all the seen
     /// tiles.
@@ -671,7 +788,7 @@ impl PlayerState {
         // `doras_seen`. This must be done before adding `dora_factor`.
         self.witness_tile(tile);
 
-        let next = tile.next();
+        let next = Tile::dora_from_indicator(tile);
         self.dora_factor[next.as_usize()] += 1;
 
         // Count new dora in my tehai
@@ -694,6 +811,16 @@ impl PlayerState {
         self.doras_seen += self.tiles_seen[next.as_usize()];
     }
 
+    /// Sets `claimed_by` of the last discard of `abs_target`.
+    fn mark_claimed(&mut self, abs_actor: u8, abs_target: u8) {
+        let actor_rel = self.rel(abs_actor) as u8;
+        let target_rel = self.rel(abs_target);
+        if let Some(item) = self.kawa[target_rel].iter_mut().rev().flatten().next() {
+            item.sutehai.claimed_by = Some(actor_rel);
+        }
+    }
+
+    /// Must be called with `ensure_pad_capacity` checked beforehand.
     pub(super) fn pad_kawa_for_pon_or_daiminkan(&mut self, abs_actor: u8, abs_target: u8) {
         let mut i = (abs_target + 1) % 4;
         while i != abs_actor {
@@ -703,6 +830,126 @@ impl PlayerState {
         }
     }
 
+    /// Checks every seat `pad_kawa_for_pon_or_daiminkan` will pad, before a
+    /// pon or daiminkan changes anything.
+    fn ensure_pad_capacity(&self, abs_actor: u8, abs_target: u8) -> Result<()> {
+        let mut i = (abs_target + 1) % 4;
+        while i != abs_actor {
+            self.ensure_kawa_capacity(self.rel(i))?;
+            i = (i + 1) % 4;
+        }
+        Ok(())
+    }
+
+    /// Returns a `KawaMark` of `tile` at the next discard of `actor_rel`.
+    fn kawa_mark(&self, actor_rel: usize, tile: Tile) -> KawaMark {
+        KawaMark {
+            tile,
+            kawa_index: self.kawa_overview[actor_rel].len() as u8,
+        }
+    }
+
+    /// `kawa` is fixed-sized, so an overflowing push must be rejected before
+    /// it happens rather than panicking.
+    fn ensure_kawa_capacity(&self, actor_rel: usize) -> Result<()> {
+        let kawa = &self.kawa[actor_rel];
+        ensure!(
+            kawa.len() < kawa.capacity(),
+            "kawa of seat {} (rel {actor_rel}) exceeds the max size {}",
+            (self.player_id as usize + actor_rel) % 4,
+            kawa.capacity(),
+        );
+        Ok(())
+    }
+
+    /// Checks if the hand has any yaku that is not from the situation when it
+    /// wins on `winning_tile`, honoring `kuitan` and `atozuke` of the rule.
+    ///
+    /// `tehai` must not include the winning tile.
+    fn has_yaku_on(&self, tehai: &[u8; 34], winning_tile: usize, is_ron: bool) -> bool {
+        let has_yaku = |tile: usize| {
+            let mut tehai_with_winning_tile = *tehai;
+            tehai_with_winning_tile[tile] += 1;
+            AgariCalculator {
+                tehai: &tehai_with_winning_tile,
+                is_menzen: self.is_menzen,
+                chis: &self.chis,
+                pons: &self.pons,
+                minkans: &self.minkans,
+                ankans: &self.ankans,
+                bakaze: self.bakaze.as_u8(),
+                jikaze: self.jikaze.as_u8(),
+                winning_tile: tile as u8,
+                is_ron,
+                kuitan: self.rule.kuitan,
+            }
+            .has_yaku()
+        };
+
+        if self.rule.atozuke {
+            has_yaku(winning_tile)
+        } else {
+            // Every wait must have a yaku, which also covers `winning_tile`.
+            self.waits
+                .iter()
+                .enumerate()
+                .filter(|&(_, &w)| w)
+                .all(|(tile, _)| has_yaku(tile))
+        }
+    }
+
+    /// Returns whether the hand counts as tenpai at an exhaustive ryukyoku,
+    /// which also requires a yaku on any of its waits unless `keishiki_tenpai`
+    /// of the rule is enabled.
+    #[must_use]
+    pub fn is_ryukyoku_tenpai(&self) -> bool {
+        if self.shanten != 0 {
+            return false;
+        }
+        if self.rule.keishiki_tenpai || self.is_menzen {
+            return true;
+        }
+        self.waits
+            .iter()
+            .enumerate()
+            .filter(|&(_, &w)| w)
+            .any(|(tile, _)| self.has_yaku_on(&self.tehai, tile, false))
+    }
+
+    /// Returns whether a ron now would be renhou, which is before the
+    /// player's first tsumo as a ko, without any call made before it.
+    pub(super) const fn is_renhou_chance(&self) -> bool {
+        // `can_w_riichi` is only broken by calls before the player's first
+        // discard.
+        self.rule.renhou && self.can_w_riichi && self.oya != 0
+    }
+
+    /// Unsets the hora candidate if `ryanhan_shibari` of the rule applies and
+    /// the hora has less than 2 han without doras.
+    fn apply_ryanhan_shibari(&mut self, is_ron: bool) {
+        if !self.rule.ryanhan_shibari || self.honba < 5 {
+            return;
+        }
+        let can = if is_ron {
+            self.last_cans.can_ron_agari
+        } else {
+            self.last_cans.can_tsumo_agari
+        };
+        if !can {
+            return;
+        }
+        let agari = self.agari_unchecked(is_ron, &[], false);
+        let enough = matches!(
+            agari,
+            Ok(Agari::Yakuman(_) | Agari::Normal { han: 2.., .. })
+        );
+        if is_ron {
+            self.last_cans.can_ron_agari = enough;
+        } else {
+            self.last_cans.can_tsumo_agari = enough;
+        }
+    }
+
     pub(super) fn pad_kawa_at_start(&mut self) {
         self.kawa
             .iter_mut()
@@ -717,15 +964,18 @@ impl PlayerState {
 
         let tile_id = tile.deaka().as_usize();
         let literal_num = tile_id % 9 + 1;
+        let kuikae = self.rule.kuikae;
 
         // it considered case like 1111234 where you cannot chi 14
         if literal_num <= 7 && self.tehai[tile_id + 1] > 0 && self.tehai[tile_id + 2] > 0 {
             // TODO: check the conditions only when self.shanten == 0?
             let mut tehai_after = self.tehai;
-            tehai_after[tile_id] = 0;
             tehai_after[tile_id + 1] -= 1;
             tehai_after[tile_id + 2] -= 1;
-            if literal_num < 7 {
+            if kuikae.forbids_genbutsu() {
+                tehai_after[tile_id] = 0;
+            }
+            if kuikae.forbids_suji() && literal_num < 7 {
                 tehai_after[tile_id + 3] = 0;
             }
             self.last_cans.can_chi_low = tehai_after.iter().any(|&t| t > 0);
@@ -736,18 +986,22 @@ impl PlayerState {
             && self.tehai[tile_id + 1] > 0
         {
             let mut tehai_after = self.tehai;
-            tehai_after[tile_id] = 0;
             tehai_after[tile_id - 1] -= 1;
             tehai_after[tile_id + 1] -= 1;
+            if kuikae.forbids_genbutsu() {
+                tehai_after[tile_id] = 0;
+            }
             self.last_cans.can_chi_mid = tehai_after.iter().any(|&t| t > 0);
         }
 
         if literal_num >= 3 && self.tehai[tile_id - 2] > 0 && self.tehai[tile_id - 1] > 0 {
             let mut tehai_after = self.tehai;
-            tehai_after[tile_id] = 0;
             tehai_after[tile_id - 2] -= 1;
             tehai_after[tile_id - 1] -= 1;
-            if literal_num > 3 {
+            if kuikae.forbids_genbutsu() {
+                tehai_after[tile_id] = 0;
+            }
+            if kuikae.forbids_suji() && literal_num > 3 {
                 tehai_after[tile_id - 3] = 0;
             }
             self.last_cans.can_chi_high = tehai_after.iter().any(|&t| t > 0);
@@ -805,6 +1059,7 @@ impl PlayerState {
         // 1. clearing same-cycle furiten
         // 2. the fact that furiten is nonsense if we are no longer tenpai
         self.at_furiten = false;
+        self.missed_wait = None;
         self.waits.fill(false);
 
         if self.shanten > 0 {
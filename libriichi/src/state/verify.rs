@@ -0,0 +1,162 @@
+use super::PlayerState;
+use crate::mjai::Event;
+use crate::tile::Tile;
+
+use anyhow::{ensure, Context, Result};
+use serde::{Deserialize, Serialize};
+
+/// The outcome of the current kyoku as settled so far in strict mode, which
+/// the next `start_kyoku` is checked against.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub(super) struct Settlement {
+    honba: u8,
+    kyotaku: u8,
+    /// Relative to `player_id`. `None` if any of the deltas is missing.
+    scores: Option<[i32; 4]>,
+    has_hora: bool,
+}
+
+impl PlayerState {
+    /// Cross-checks `event` against the state before it is applied, in strict
+    /// mode. See `set_strict`.
+    ///
+    /// The deltas of our own hora are checked against our own calculation, and
+    /// the honba, kyotaku and scores of a `start_kyoku` against the outcome of
+    /// the previous kyoku. The hands of the others are never revealed in mjai,
+    /// so only the sum of the deltas of their hora and of any ryukyoku is
+    /// checked.
+    pub(super) fn verify_event(&mut self, event: &Event) -> Result<()> {
+        match *event {
+            Event::StartGame { .. } | Event::EndGame => self.settlement = None,
+
+            Event::StartKyoku {
+                honba,
+                kyotaku,
+                mut scores,
+                ..
+            } => {
+                let Some(settlement) = self.settlement.take() else {
+                    return Ok(());
+                };
+                ensure!(
+                    honba == settlement.honba,
+                    "score verification: expected honba {}, but got {honba}",
+                    settlement.honba,
+                );
+                ensure!(
+                    kyotaku == settlement.kyotaku,
+                    "score verification: expected kyotaku {}, but got {kyotaku}",
+                    settlement.kyotaku,
+                );
+                if let Some(expected) = settlement.scores {
+                    scores.rotate_left(self.player_id as usize);
+                    ensure!(
+                        scores == expected,
+                        "score verification: expected relative scores {expected:?}, but got {scores:?}",
+                    );
+                }
+            }
+
+            Event::Hora {
+                actor,
+                target,
+                deltas,
+                ref ura_markers,
+            } => {
+                let is_first = !self.settlement.is_some_and(|s| s.has_hora);
+                let mut settlement = self.settle_with(deltas, self.kyotaku as i32 * 1000)?;
+                settlement.has_hora = true;
+                settlement.kyotaku = 0;
+                // A renchan by any of the rons keeps the honba going.
+                settlement.honba = if self.rel(actor) == self.oya as usize {
+                    self.honba + 1
+                } else if is_first {
+                    0
+                } else {
+                    settlement.honba
+                };
+                self.settlement = Some(settlement);
+
+                // Only the first hora is checked, as the state has already
+                // moved on from the kawa tile for the following ones.
+                if let Some(deltas) = deltas.filter(|_| actor == self.player_id && is_first) {
+                    let ura_indicators = ura_markers.as_deref().unwrap_or_default();
+                    self.verify_own_hora(actor != target, ura_indicators, deltas[actor as usize])?;
+                }
+            }
+
+            Event::Ryukyoku { deltas, .. } => {
+                let mut settlement = self.settle_with(deltas, 0)?;
+                settlement.honba = self.honba + 1;
+                settlement.kyotaku = self.kyotaku;
+                self.settlement = Some(settlement);
+            }
+
+            Event::Chombo { deltas, .. } => {
+                // The riichi sticks of the kyoku go back to their owners.
+                let mut settlement = self.settle_with(deltas, 0)?;
+                let mut returned = 0;
+                for (i, _) in self.riichi_accepted.iter().enumerate().filter(|(_, &b)| b) {
+                    if let Some(scores) = &mut settlement.scores {
+                        scores[i] += 1000;
+                    }
+                    returned += 1;
+                }
+                settlement.honba = self.honba;
+                settlement.kyotaku = self.kyotaku - returned;
+                self.settlement = Some(settlement);
+            }
+
+            _ => (),
+        }
+
+        Ok(())
+    }
+
+    /// Adds `deltas` to the scores settled so far, and checks that they sum up
+    /// to `expected_sum`.
+    fn settle_with(&self, deltas: Option<[i32; 4]>, expected_sum: i32) -> Result<Settlement> {
+        let mut settlement = self.settlement.unwrap_or(Settlement {
+            scores: Some(self.scores),
+            ..Default::default()
+        });
+        let Some(deltas) = deltas else {
+            settlement.scores = None;
+            return Ok(settlement);
+        };
+
+        let sum = deltas.iter().sum::<i32>();
+        ensure!(
+            sum == expected_sum,
+            "score verification: deltas {deltas:?} sum up to {sum}, expected {expected_sum}",
+        );
+        if let Some(scores) = &mut settlement.scores {
+            for (abs, &delta) in deltas.iter().enumerate() {
+                scores[self.rel(abs as u8)] += delta;
+            }
+        }
+        Ok(settlement)
+    }
+
+    fn verify_own_hora(&self, is_ron: bool, ura_indicators: &[Tile], delta: i32) -> Result<()> {
+        let agari = self
+            .agari_unchecked(is_ron, ura_indicators, true)
+            .context("score verification: failed to calculate our own hora")?;
+        let is_oya = self.oya == 0;
+        let point = agari.into_point(is_oya);
+        let bonus = self.honba as i32 * 300 + self.kyotaku as i32 * 1000;
+
+        let expected = if is_ron {
+            point.ron
+        } else {
+            point.tsumo_total(is_oya)
+        } + bonus;
+        // A rinshan kaihou with `rinshan_pao` is paid as if it were a ron.
+        let as_pao = self.rule.rinshan_pao && self.at_rinshan && delta == point.ron + bonus;
+        ensure!(
+            delta == expected || as_pao,
+            "score verification: expected a delta of {expected} for our own {agari:?}, but got {delta}",
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,154 @@
+use super::riichi_ev::{DrawModel, Values, DAMA_RON_FACTOR, RIICHI_RON_FACTOR};
+use super::PlayerState;
+use crate::must_tile;
+use crate::tile::Tile;
+
+use riichi_macros::{pyclass, pymethods};
+
+/// A quick estimate of winning with the current tenpai hand within some
+/// turns, see `PlayerState::win_probability_estimate`.
+#[pyclass]
+#[derive(Debug, Clone, PartialEq)]
+pub struct WinProbability {
+    /// The live waits, regardless of yaku.
+    pub waits: Vec<Tile>,
+    /// Number of the unseen copies of `waits`.
+    pub live_tiles: u8,
+    /// Furiten makes it tsumo only.
+    pub furiten: bool,
+    /// Number of the player's own draws within the turns, which is capped by
+    /// the live wall.
+    pub draws: u8,
+    pub tsumo_rate: f32,
+    /// Chance of winning by either tsumo or ron.
+    pub win_rate: f32,
+    /// Average points gained on a win, including honba and kyotaku, with the
+    /// hand as it is, which counts no ura doras even in riichi.
+    pub win_value: f32,
+    /// Same as `win_value`, but with riichi, either declared already or
+    /// declared now, and the expected ura doras, with the opponents discarding
+    /// as against a riichi. `None` if the hand is open.
+    pub win_value_riichi: Option<f32>,
+}
+
+#[pymethods]
+impl WinProbability {
+    #[getter]
+    fn waits(&self) -> Vec<String> {
+        self.waits.iter().map(|t| t.to_string()).collect()
+    }
+    #[getter]
+    const fn live_tiles(&self) -> u8 {
+        self.live_tiles
+    }
+    #[getter]
+    const fn furiten(&self) -> bool {
+        self.furiten
+    }
+    #[getter]
+    const fn draws(&self) -> u8 {
+        self.draws
+    }
+    #[getter]
+    const fn tsumo_rate(&self) -> f32 {
+        self.tsumo_rate
+    }
+    #[getter]
+    const fn win_rate(&self) -> f32 {
+        self.win_rate
+    }
+    #[getter]
+    const fn win_value(&self) -> f32 {
+        self.win_value
+    }
+    #[getter]
+    const fn win_value_riichi(&self) -> Option<f32> {
+        self.win_value_riichi
+    }
+
+    fn __repr__(&self) -> String {
+        format!("{self:?}")
+    }
+}
+
+#[pymethods]
+impl PlayerState {
+    /// Returns a `WinProbability`, or `None` if the hand is not tenpai or is
+    /// not 3n+1.
+    #[pyo3(name = "win_probability_estimate")]
+    #[pyo3(text_signature = "($self, turns_ahead, /)")]
+    fn win_probability_estimate_py(&self, turns_ahead: u8) -> Option<WinProbability> {
+        self.win_probability_estimate(turns_ahead)
+    }
+}
+
+impl PlayerState {
+    /// Estimates the chance of winning with the current tenpai hand within
+    /// `turns_ahead` of the player's own turns, and the points of it, by the
+    /// same draw model as `riichi_ev`, without running any search. Returns
+    /// `None` if the hand is not tenpai or is not 3n+1, i.e. when it is the
+    /// player's turn to discard.
+    ///
+    /// The opponents are assumed to keep discarding, and the ones after the
+    /// player's last draw within the turns are not counted.
+    #[must_use]
+    pub fn win_probability_estimate(&self, turns_ahead: u8) -> Option<WinProbability> {
+        if self.shanten != 0 || self.last_cans.can_discard {
+            return None;
+        }
+
+        let unseen = self.tiles_seen.map(|seen| 4 - seen);
+        let unseen_total = unseen.iter().map(|&n| n as f32).sum::<f32>();
+        let draws = turns_ahead.min(self.tiles_left.div_ceil(4));
+        let model = DrawModel {
+            unseen_total,
+            own_draws: draws as i32,
+            opponent_discards: (draws as i32 * 3).min(self.tiles_left as i32 - draws as i32),
+            stakes: (self.honba as u32 * 300 + self.kyotaku as u32 * 1000) as f32,
+        };
+
+        let in_riichi = self.riichi_declared[0];
+        let (mut values, mut riichi_values) = (Values::default(), Values::default());
+        let mut live_tiles = 0;
+        let waits: Vec<_> = (0..34).filter(|&w| self.waits[w]).collect();
+        for &w in &waits {
+            live_tiles += unseen[w];
+            self.add_wait_values(
+                &self.tehai,
+                w,
+                self.doras_owned[0],
+                &unseen,
+                in_riichi,
+                &mut values,
+                &mut riichi_values,
+            );
+        }
+        if self.at_furiten {
+            values.ron_tiles = 0;
+            riichi_values.ron_tiles = 0;
+        }
+
+        let ron_factor = if in_riichi {
+            RIICHI_RON_FACTOR
+        } else {
+            DAMA_RON_FACTOR
+        };
+        let (win_rate, win_value) = model.estimate(&values, live_tiles, ron_factor, 0.);
+        let win_value_riichi = self.is_menzen.then(|| {
+            let (_, value) = model.estimate(&riichi_values, live_tiles, RIICHI_RON_FACTOR, 0.);
+            value
+        });
+        let tsumo_rate = 1. - (1. - live_tiles as f32 / unseen_total).powi(draws as i32);
+
+        Some(WinProbability {
+            waits: waits.into_iter().map(|w| must_tile!(w)).collect(),
+            live_tiles,
+            furiten: self.at_furiten,
+            draws,
+            tsumo_rate,
+            win_rate,
+            win_value,
+            win_value_riichi,
+        })
+    }
+}
@@ -0,0 +1,221 @@
+use super::PlayerState;
+use crate::algo::agari::AgariCalculator;
+use crate::{must_tile, tu8};
+
+use riichi_macros::{pyclass, pymethods};
+
+/// Tiles a hand may be away from a yaku for it to be still worth aiming at.
+const MAX_MISSING: u8 = 2;
+
+/// A yaku the hand is aiming at or already has.
+#[pyclass]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PossibleYaku {
+    pub name: &'static str,
+    /// Whether the yaku holds for every agari of the current hand, which
+    /// means any yakuhai kotsu held, riichi declared, and the yakus on every
+    /// wait when tenpai.
+    pub locked: bool,
+}
+
+#[pymethods]
+impl PossibleYaku {
+    #[getter]
+    const fn name(&self) -> &'static str {
+        self.name
+    }
+    #[getter]
+    const fn locked(&self) -> bool {
+        self.locked
+    }
+
+    fn __repr__(&self) -> String {
+        format!("{self:?}")
+    }
+}
+
+#[pymethods]
+impl PlayerState {
+    /// Returns a list of `PossibleYaku`, see the Rust method for details.
+    #[pyo3(name = "possible_yaku")]
+    #[pyo3(text_signature = "($self, /)")]
+    fn possible_yaku_py(&self) -> Vec<PossibleYaku> {
+        self.possible_yaku()
+    }
+}
+
+impl PlayerState {
+    /// Reports the yakus the hand is aiming at or already has, which is what
+    /// `waits` does not tell as it ignores yakunashi.
+    ///
+    /// The first entry is riichi if declared, or menzen tsumo, which is not
+    /// locked, if the hand is closed. If the hand is tenpai waiting for a
+    /// tile, the rest are exactly the yakus of a ron on each wait, where a yaku
+    /// is locked if it holds on every wait. Otherwise they are guessed from
+    /// the tiles, where only yakuhai kotsus are locked, and a yaku is listed
+    /// if the hand is at most `MAX_MISSING` tiles away from its shape.
+    #[must_use]
+    pub fn possible_yaku(&self) -> Vec<PossibleYaku> {
+        let mut ret = vec![];
+        if self.riichi_declared[0] {
+            let name = if self.is_w_riichi {
+                "double_riichi"
+            } else {
+                "riichi"
+            };
+            ret.push(PossibleYaku { name, locked: true });
+        } else if self.is_menzen {
+            ret.push(PossibleYaku {
+                name: "menzen_tsumo",
+                locked: false,
+            });
+        }
+
+        let tehai_len = self.tehai.iter().sum::<u8>();
+        if self.shanten == 0 && tehai_len == self.tehai_len_div3 * 3 + 1 {
+            self.add_yakus_on_waits(&mut ret);
+        } else {
+            self.add_guessed_yakus(&mut ret);
+        }
+        ret
+    }
+
+    fn add_yakus_on_waits(&self, ret: &mut Vec<PossibleYaku>) {
+        // Name and the number of waits it holds on.
+        let mut found: Vec<(&'static str, usize)> = vec![];
+        let mut waits_count = 0;
+        for (tid, _) in self.waits.iter().enumerate().filter(|&(_, &w)| w) {
+            waits_count += 1;
+            let mut tehai = self.tehai;
+            tehai[tid] += 1;
+            let calc = AgariCalculator {
+                tehai: &tehai,
+                is_menzen: self.is_menzen,
+                chis: &self.chis,
+                pons: &self.pons,
+                minkans: &self.minkans,
+                ankans: &self.ankans,
+                bakaze: self.bakaze.as_u8(),
+                jikaze: self.jikaze.as_u8(),
+                winning_tile: tid as u8,
+                is_ron: true,
+                kuitan: self.rule.kuitan,
+            };
+            let Some(detail) = calc.agari_detail(&[], 0) else {
+                continue;
+            };
+            for yaku in detail.yakus {
+                match found.iter_mut().find(|(name, _)| *name == yaku.name) {
+                    Some((_, n)) => *n += 1,
+                    None => found.push((yaku.name, 1)),
+                }
+            }
+        }
+        ret.extend(found.into_iter().map(|(name, n)| PossibleYaku {
+            name,
+            locked: n == waits_count,
+        }));
+    }
+
+    fn add_guessed_yakus(&self, ret: &mut Vec<PossibleYaku>) {
+        let mut add = |name, locked| ret.push(PossibleYaku { name, locked });
+        let kotsu_melds = || {
+            self.pons
+                .iter()
+                .chain(&self.minkans)
+                .chain(&self.ankans)
+                .copied()
+        };
+        // The closed tiles plus the chis, which can still form other
+        // shuntsus.
+        let mut tiles = self.tehai;
+        for &s in &self.chis {
+            for t in s..s + 3 {
+                tiles[t as usize] += 1;
+            }
+        }
+
+        // 役牌
+        let yakuhai = [
+            (self.bakaze.as_u8(), "yakuhai_bakaze"),
+            (self.jikaze.as_u8(), "yakuhai_jikaze"),
+            (tu8!(P), "yakuhai_haku"),
+            (tu8!(F), "yakuhai_hatsu"),
+            (tu8!(C), "yakuhai_chun"),
+        ];
+        for (tid, name) in yakuhai {
+            if self.tehai[tid as usize] >= 3 || kotsu_melds().any(|t| t == tid) {
+                add(name, true);
+            } else if self.tehai[tid as usize] == 2 {
+                add(name, false);
+            }
+        }
+
+        // 断幺九
+        let yaokyuu_in_hand = (0..34)
+            .filter(|&tid| must_tile!(tid).is_yaokyuu())
+            .map(|tid| self.tehai[tid])
+            .sum::<u8>();
+        let melds_simple = self.chis.iter().all(|&s| (1..6).contains(&(s % 9)))
+            && kotsu_melds().all(|t| !must_tile!(t).is_yaokyuu());
+        if (self.is_menzen || self.rule.kuitan) && melds_simple && yaokyuu_in_hand <= MAX_MISSING {
+            add("tanyao", false);
+        }
+
+        // 混一色, 清一色
+        let jihai = kotsu_melds().filter(|&t| t >= tu8!(E)).count() as u8 * 3
+            + self.tehai[tu8!(E) as usize..].iter().sum::<u8>();
+        for kind in 0..3 {
+            let off_suit = (0..3)
+                .filter(|&k| k != kind)
+                .map(|k| tiles[k * 9..k * 9 + 9].iter().sum::<u8>())
+                .sum::<u8>()
+                + kotsu_melds()
+                    .filter(|&t| t < tu8!(E) && t as usize / 9 != kind)
+                    .count() as u8
+                    * 3;
+            if off_suit + jihai <= MAX_MISSING {
+                add("chinitsu", false);
+            } else if off_suit <= MAX_MISSING {
+                add("honitsu", false);
+            }
+        }
+
+        // 対々和, 七対子
+        let pairs = self.tehai.iter().filter(|&&n| n >= 2).count() as u8;
+        if self.chis.is_empty() && pairs + 1 >= self.tehai_len_div3 {
+            add("toitoi", false);
+        }
+        if self.is_menzen && self.ankans.is_empty() && pairs + MAX_MISSING >= 7 {
+            add("chiitoitsu", false);
+        }
+
+        // 一気通貫
+        for kind in 0..3 {
+            let chis_fit = self
+                .chis
+                .iter()
+                .all(|&s| s as usize / 9 != kind || s % 3 == 0);
+            let missing = tiles[kind * 9..kind * 9 + 9]
+                .iter()
+                .filter(|&&n| n == 0)
+                .count() as u8;
+            if chis_fit && missing <= MAX_MISSING {
+                add("ittsuu", false);
+            }
+        }
+
+        // 三色同順
+        let has_sanshoku = (0..7).any(|num| {
+            let missing = (0..3)
+                .flat_map(|kind| kind * 9 + num..kind * 9 + num + 3)
+                .filter(|&tid| tiles[tid] == 0)
+                .count() as u8;
+            let chis_fit = self.chis.iter().all(|&s| s as usize % 9 == num);
+            chis_fit && missing <= MAX_MISSING
+        });
+        if has_sanshoku {
+            add("sanshoku_doujun", false);
+        }
+    }
+}
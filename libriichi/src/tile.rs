@@ -3,6 +3,7 @@ use std::error::Error;
 use std::fmt;
 use std::str::FromStr;
 
+use anyhow::bail;
 use boomphf::hashmap::BoomHashMap;
 use once_cell::sync::Lazy;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
@@ -18,6 +19,28 @@ const MJAI_PAI_STRINGS: &[&str] = &[
 ];
 const_assert_eq!(MJAI_PAI_STRINGS.len(), 3 * 9 + 4 + 3 + 3 + 1);
 
+const SHORTHAND_PAI_STRINGS: &[&str] = &[
+    "1m", "2m", "3m", "4m", "5m", "6m", "7m", "8m", "9m", // m
+    "1p", "2p", "3p", "4p", "5p", "6p", "7p", "8p", "9p", // p
+    "1s", "2s", "3s", "4s", "5s", "6s", "7s", "8s", "9s", // s
+    "1z", "2z", "3z", "4z", "5z", "6z", "7z", // z
+    "0m", "0p", "0s", // a
+    "?",  // unknown
+];
+const_assert_eq!(SHORTHAND_PAI_STRINGS.len(), MJAI_PAI_STRINGS.len());
+
+/// There is no glyph for akas, which share the ones of the normal 5s, and the
+/// unknown tile is the back.
+const UNICODE_PAI_STRINGS: &[&str] = &[
+    "🀇", "🀈", "🀉", "🀊", "🀋", "🀌", "🀍", "🀎", "🀏", // m
+    "🀙", "🀚", "🀛", "🀜", "🀝", "🀞", "🀟", "🀠", "🀡", // p
+    "🀐", "🀑", "🀒", "🀓", "🀔", "🀕", "🀖", "🀗", "🀘", // s
+    "🀀", "🀁", "🀂", "🀃", "🀆", "🀅", "🀄", // z
+    "🀋", "🀝", "🀔", // a
+    "🀫", // unknown
+];
+const_assert_eq!(UNICODE_PAI_STRINGS.len(), MJAI_PAI_STRINGS.len());
+
 static MJAI_PAI_STRINGS_MAP: Lazy<BoomHashMap<&'static str, Tile>> = Lazy::new(|| {
     let mut values = vec![];
     for id in 0..MJAI_PAI_STRINGS.len() {
@@ -124,6 +147,56 @@ impl Tile {
             Self(3 * 9 + 4 + (num - 4 + 3 - 1) % 3)
         }
     }
+
+    /// Returns the dora indicated by `indicator`, which also works for ura
+    /// doras. Aka indicators are treated as their normal counterparts.
+    ///
+    /// The wraparounds are 9 -> 1 for suited tiles, N -> E for winds and C -> P
+    /// for dragons.
+    #[inline]
+    #[must_use]
+    pub const fn dora_from_indicator(indicator: Self) -> Self {
+        let tile = indicator.deaka();
+        match tile.0 {
+            tu8!(9m) => t!(1m),
+            tu8!(9p) => t!(1p),
+            tu8!(9s) => t!(1s),
+            tu8!(N) => t!(E),
+            tu8!(C) => t!(P),
+            _ => Self(tile.0 + 1),
+        }
+    }
+}
+
+/// Output styles of tiles for humans, in addition to the mjai notation of
+/// `Display`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum TileStyle {
+    /// The ASCII shorthand, such as `1m`, `0p` for the aka 5p and `7z` for C.
+    #[default]
+    Shorthand,
+    /// The Unicode mahjong tile glyphs, such as 🀇. Akas look the same as the
+    /// normal 5s.
+    Unicode,
+    /// The mjai notation padded to 3 columns, such as `E  ` and `5mr`, for
+    /// tables.
+    Aligned,
+}
+
+/// A `Tile` written in a `TileStyle`, returned by `Tile::styled`.
+#[derive(Debug, Clone, Copy)]
+pub struct StyledTile {
+    tile: Tile,
+    style: TileStyle,
+}
+
+impl Tile {
+    /// Returns a `Display` of the tile in `style`.
+    #[inline]
+    #[must_use]
+    pub const fn styled(self, style: TileStyle) -> StyledTile {
+        StyledTile { tile: self, style }
+    }
 }
 
 #[derive(Debug)]
@@ -179,6 +252,34 @@ impl fmt::Display for Tile {
     }
 }
 
+impl fmt::Display for StyledTile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let id = self.tile.0 as usize;
+        // SAFETY: `Tile` is in-bound iff it is constructed safely, and the
+        // tables have the same length.
+        unsafe {
+            match self.style {
+                TileStyle::Shorthand => f.pad(SHORTHAND_PAI_STRINGS.get_unchecked(id)),
+                TileStyle::Unicode => f.pad(UNICODE_PAI_STRINGS.get_unchecked(id)),
+                TileStyle::Aligned => write!(f, "{:<3}", MJAI_PAI_STRINGS.get_unchecked(id)),
+            }
+        }
+    }
+}
+
+impl FromStr for TileStyle {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "shorthand" => Ok(Self::Shorthand),
+            "unicode" => Ok(Self::Unicode),
+            "aligned" => Ok(Self::Aligned),
+            _ => bail!("unknown tile style {s}"),
+        }
+    }
+}
+
 impl<'de> Deserialize<'de> for Tile {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
@@ -224,4 +325,49 @@ mod test {
             assert_eq!(tile.next().prev(), tile.deaka());
         });
     }
+
+    #[test]
+    fn dora_from_indicator() {
+        assert_eq!(Tile::dora_from_indicator(t!(9m)), t!(1m));
+        assert_eq!(Tile::dora_from_indicator(t!(9p)), t!(1p));
+        assert_eq!(Tile::dora_from_indicator(t!(9s)), t!(1s));
+        assert_eq!(Tile::dora_from_indicator(t!(N)), t!(E));
+        assert_eq!(Tile::dora_from_indicator(t!(C)), t!(P));
+
+        assert_eq!(Tile::dora_from_indicator(t!(1m)), t!(2m));
+        assert_eq!(Tile::dora_from_indicator(t!(5pr)), t!(6p));
+        assert_eq!(Tile::dora_from_indicator(t!(W)), t!(N));
+        assert_eq!(Tile::dora_from_indicator(t!(F)), t!(C));
+
+        MJAI_PAI_STRINGS.iter().take(37).for_each(|&s| {
+            let tile: Tile = s.parse().unwrap();
+            assert_eq!(Tile::dora_from_indicator(tile), tile.next());
+        });
+    }
+
+    #[test]
+    fn styled() {
+        let tiles = [t!(1m), t!(5pr), t!(E), t!(C), t!(?)];
+        let to_string = |style| {
+            tiles
+                .iter()
+                .map(|t| t.styled(style).to_string())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            to_string(TileStyle::Shorthand),
+            ["1m", "0p", "1z", "7z", "?"]
+        );
+        assert_eq!(to_string(TileStyle::Unicode), ["🀇", "🀝", "🀀", "🀄", "🀫"]);
+        assert_eq!(
+            to_string(TileStyle::Aligned),
+            ["1m ", "5pr", "E  ", "C  ", "?  "]
+        );
+
+        for (id, &s) in MJAI_PAI_STRINGS.iter().enumerate().take(37) {
+            let tile = Tile::try_from(id).unwrap();
+            assert_eq!(tile.styled(TileStyle::Aligned).to_string().trim_end(), s);
+        }
+    }
 }
@@ -0,0 +1,84 @@
+//! Spans of `tracing` around the hot paths, namely the event application of
+//! `PlayerState`, the obs encoding, the engine calls and the game loops of
+//! the arena, which are printed to stderr with their timings once
+//! `set_tracing` is called.
+
+#[cfg(feature = "python")]
+use crate::py_helper::add_submodule;
+
+use anyhow::{Context, Result};
+use once_cell::sync::OnceCell;
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+use tracing_subscriber::fmt::format::FmtSpan;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::{reload, EnvFilter, Registry};
+
+static FILTER: OnceCell<reload::Handle<EnvFilter, Registry>> = OnceCell::new();
+
+/// Sets up the subscriber printing spans and events to stderr, or replaces
+/// its filter if it has been set up already.
+///
+/// `filter` is in the syntax of `RUST_LOG`, such as `"debug"` or
+/// `"riichi::agent=trace,info"`. Spans are printed when they close, along
+/// with their busy and idle time.
+///
+/// No `log` logger is installed along with it, so this works after
+/// `pyo3_log::init` or any other logger has been set.
+pub fn set_tracing(filter: &str) -> Result<()> {
+    let filter = EnvFilter::try_new(filter).with_context(|| format!("invalid filter {filter}"))?;
+    if let Some(handle) = FILTER.get() {
+        return handle.reload(filter).context("failed to reload filter");
+    }
+
+    let (filter, handle) = reload::Layer::new(filter);
+    let fmt = tracing_subscriber::fmt::layer()
+        .with_writer(std::io::stderr)
+        .with_span_events(FmtSpan::CLOSE);
+    let subscriber = Registry::default().with(filter).with(fmt);
+    tracing::subscriber::set_global_default(subscriber)
+        .context("another subscriber has been set")?;
+    FILTER.set(handle).ok();
+    Ok(())
+}
+
+#[cfg(feature = "python")]
+#[pyfunction]
+#[pyo3(name = "set_tracing")]
+#[pyo3(text_signature = "(filter, /)")]
+fn set_tracing_py(filter: &str) -> Result<()> {
+    set_tracing(filter)
+}
+
+#[cfg(feature = "python")]
+pub(crate) fn register_module(py: Python<'_>, prefix: &str, super_mod: &PyModule) -> PyResult<()> {
+    let m = PyModule::new(py, "trace")?;
+    m.add_function(wrap_pyfunction!(set_tracing_py, m)?)?;
+    add_submodule(py, prefix, super_mod, m)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    struct NopLogger;
+
+    impl log::Log for NopLogger {
+        fn enabled(&self, _: &log::Metadata<'_>) -> bool {
+            false
+        }
+        fn log(&self, _: &log::Record<'_>) {}
+        fn flush(&self) {}
+    }
+
+    #[test]
+    fn reload_filter() {
+        // Like `pyo3_log::init` in the Python module.
+        log::set_logger(&NopLogger).unwrap();
+
+        set_tracing("off").unwrap();
+        assert!(FILTER.get().is_some());
+        set_tracing("riichi::arena=off,off").unwrap();
+        assert!(set_tracing("riichi=[").is_err());
+    }
+}
@@ -0,0 +1,717 @@
+//! Validation of mjai logs.
+//!
+//! A log is replayed through four `PlayerState`s, which check every action
+//! against what the actor could do, plus a referee that keeps track of what
+//! the states do not: the flow of the game, the scores and the kan doras.
+//! Every inconsistency found is reported as an `Issue` instead of an error,
+//! so that a corrupt log can be told apart from a bug of the state machine.
+
+use crate::chi_type::ChiType;
+use crate::mjai::Event;
+#[cfg(feature = "python")]
+use crate::py_helper::add_submodule;
+use crate::state::{ActionCandidate, PlayerState};
+use crate::tile::Tile;
+use crate::{log_io, matches_tu8, tu8};
+use std::panic::{self, AssertUnwindSafe};
+use std::{fmt, mem};
+
+#[cfg(feature = "python")]
+use anyhow::Context;
+use anyhow::Result;
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+use riichi_macros::{pyclass, pymethods};
+use serde_json as json;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IssueKind {
+    /// The events are not in the order of a game, for example a `tsumo`
+    /// outside of a kyoku or a kyoku without an outcome.
+    Structure,
+    /// A kyoku does not follow the previous one, or its oya does not match.
+    KyokuOrder,
+    /// An action that the actor could not take at that point.
+    ImpossibleAction,
+    /// The scores or the deltas do not add up.
+    ScoreMismatch,
+    /// A kan whose dora is never revealed.
+    MissingDora,
+    /// A dora revealed without a kan.
+    UnexpectedDora,
+    /// The event cannot be applied to a `PlayerState` at all, after which the
+    /// replay stops.
+    InvalidState,
+}
+
+/// An inconsistency found in a log.
+#[pyclass]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Issue {
+    /// The index of the event, counting from 1, which is also the line number
+    /// for a log without blank lines.
+    pub line: usize,
+    pub kind: IssueKind,
+    pub message: String,
+}
+
+impl fmt::Display for IssueKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Structure => "structure",
+            Self::KyokuOrder => "kyoku_order",
+            Self::ImpossibleAction => "impossible_action",
+            Self::ScoreMismatch => "score_mismatch",
+            Self::MissingDora => "missing_dora",
+            Self::UnexpectedDora => "unexpected_dora",
+            Self::InvalidState => "invalid_state",
+        })
+    }
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}: {}", self.line, self.kind, self.message)
+    }
+}
+
+#[pymethods]
+impl Issue {
+    #[getter]
+    const fn line(&self) -> usize {
+        self.line
+    }
+    /// One of `"structure"`, `"kyoku_order"`, `"impossible_action"`,
+    /// `"score_mismatch"`, `"missing_dora"`, `"unexpected_dora"` and
+    /// `"invalid_state"`.
+    #[getter]
+    fn kind(&self) -> String {
+        self.kind.to_string()
+    }
+    #[getter]
+    fn message(&self) -> String {
+        self.message.clone()
+    }
+
+    fn __str__(&self) -> String {
+        self.to_string()
+    }
+    fn __repr__(&self) -> String {
+        format!("{self:?}")
+    }
+}
+
+/// The referee's view of the kyoku in progress.
+#[derive(Default)]
+struct Kyoku {
+    /// `(bakaze - E) * 4 + kyoku - 1`, and honba.
+    index: u8,
+    honba: u8,
+    /// Scores and kyotaku as of now, `None` once an outcome without deltas is
+    /// seen.
+    scores: Option<[i32; 4]>,
+    kyotaku: u8,
+    /// Kyotaku in points that is yet to be taken by a hora.
+    kyotaku_left: i32,
+    /// Scores and kyotaku at the start, which a chombo goes back to.
+    start_scores: [i32; 4],
+    start_kyotaku: u8,
+    has_outcome: bool,
+    /// Set by a chombo, after which the kyoku is replayed.
+    is_chombo: bool,
+    kans: usize,
+    doras: usize,
+    /// Set by a kan until the rinshan tsumo.
+    at_rinshan: bool,
+    /// The actor who is to tsumo next unless someone calls.
+    next_tsumo: Option<u8>,
+}
+
+struct Validator {
+    states: [PlayerState; 4],
+    cans: [ActionCandidate; 4],
+    in_game: bool,
+    kyoku: Option<Kyoku>,
+    /// The kyoku that ended last, with its expected scores and kyotaku.
+    last_kyoku: Option<Kyoku>,
+    issues: Vec<Issue>,
+    line: usize,
+}
+
+/// Validates a log, returning the issues found, which is empty if the log is
+/// fine.
+#[must_use]
+pub fn validate(events: &[Event]) -> Vec<Issue> {
+    let mut validator = Validator {
+        states: [0, 1, 2, 3].map(PlayerState::new),
+        cans: Default::default(),
+        in_game: false,
+        kyoku: None,
+        last_kyoku: None,
+        issues: vec![],
+        line: 0,
+    };
+
+    for (idx, ev) in events.iter().enumerate() {
+        validator.line = idx + 1;
+        if !validator.feed(ev) {
+            return validator.issues;
+        }
+    }
+    if validator.in_game {
+        validator.report(IssueKind::Structure, "the log ends before end_game");
+    }
+    validator.issues
+}
+
+/// Reads a log file, which may be compressed, and validates it.
+pub fn validate_file(path: &str) -> Result<Vec<Issue>> {
+    Ok(validate(&log_io::read_events(path)?))
+}
+
+impl Validator {
+    fn report(&mut self, kind: IssueKind, message: impl Into<String>) {
+        self.issues.push(Issue {
+            line: self.line,
+            kind,
+            message: message.into(),
+        });
+    }
+
+    fn report_action(&mut self, actor: u8, what: &str) {
+        let message = format!(
+            "{what}\nstate:\n{}",
+            self.states[actor as usize].brief_info()
+        );
+        self.report(IssueKind::ImpossibleAction, message);
+    }
+
+    /// Returns false if the replay cannot go on.
+    fn feed(&mut self, ev: &Event) -> bool {
+        self.referee(ev);
+        if self.kyoku.is_some() {
+            self.check_action(ev);
+        }
+
+        if let Event::StartGame { meta, .. } = ev {
+            if let Some(rule) = meta.as_ref().and_then(|m| m.rule) {
+                self.states = [0, 1, 2, 3].map(|i| PlayerState::with_rule(i, rule));
+            }
+        }
+        for (s, c) in self.states.iter_mut().zip(&mut self.cans) {
+            // A malformed log may still hit an assertion somewhere deep in
+            // the state, which is reported as an issue all the same.
+            let result = panic::catch_unwind(AssertUnwindSafe(|| s.update_with_skip(ev, true)));
+            let err = match result {
+                Ok(Ok(cans)) => {
+                    *c = cans;
+                    continue;
+                }
+                Ok(Err(err)) => format!("{err:?}"),
+                Err(payload) => {
+                    let msg = payload
+                        .downcast_ref::<&str>()
+                        .map(|s| (*s).to_owned())
+                        .or_else(|| payload.downcast_ref::<String>().cloned())
+                        .unwrap_or_default();
+                    format!("panicked: {msg}")
+                }
+            };
+            self.report(
+                IssueKind::InvalidState,
+                format!("failed to update the state: {err}"),
+            );
+            return false;
+        }
+        true
+    }
+
+    fn check_action(&mut self, ev: &Event) {
+        let all_cans = self.cans;
+        let cans = |actor: u8| all_cans[actor as usize];
+        match *ev {
+            Event::Dahai { actor, pai, .. } => {
+                if !cans(actor).can_discard {
+                    self.report_action(actor, "discard while unable to");
+                } else if !self.states[actor as usize].discard_candidates_aka()[pai.as_usize()] {
+                    self.report_action(actor, &format!("discard of {pai}, which is not allowed"));
+                }
+            }
+            Event::Chi {
+                actor,
+                target,
+                pai,
+                consumed,
+            } => {
+                let ok = (target + 1) % 4 == actor
+                    && match ChiType::new(consumed, pai) {
+                        ChiType::Low => cans(actor).can_chi_low,
+                        ChiType::Mid => cans(actor).can_chi_mid,
+                        ChiType::High => cans(actor).can_chi_high,
+                    };
+                if !ok {
+                    self.report_action(actor, &format!("chi of {pai} from {target}"));
+                }
+            }
+            Event::Pon { actor, pai, .. } if !cans(actor).can_pon => {
+                self.report_action(actor, &format!("pon of {pai}"));
+            }
+            Event::Daiminkan { actor, pai, .. } if !cans(actor).can_daiminkan => {
+                self.report_action(actor, &format!("daiminkan of {pai}"));
+            }
+            Event::Ankan { actor, consumed }
+                if !cans(actor).can_ankan
+                    || !self.states[actor as usize]
+                        .ankan_candidates()
+                        .contains(&consumed[0].deaka()) =>
+            {
+                self.report_action(actor, &format!("ankan of {}", consumed[0].deaka()));
+            }
+            Event::Kakan { actor, pai, .. }
+                if !cans(actor).can_kakan
+                    || !self.states[actor as usize]
+                        .kakan_candidates()
+                        .contains(&pai.deaka()) =>
+            {
+                self.report_action(actor, &format!("kakan of {pai}"));
+            }
+            Event::Reach { actor, .. } if !cans(actor).can_riichi => {
+                self.report_action(actor, "riichi");
+            }
+            Event::Hora {
+                actor,
+                target,
+                ref ura_markers,
+                deltas,
+            } => self.check_hora(actor, target, ura_markers.as_deref(), deltas),
+            _ => (),
+        }
+    }
+
+    fn check_hora(
+        &mut self,
+        actor: u8,
+        target: u8,
+        ura: Option<&[Tile]>,
+        deltas: Option<[i32; 4]>,
+    ) {
+        let is_ron = actor != target;
+        let can = if is_ron {
+            self.cans[actor as usize].can_ron_agari
+        } else {
+            self.cans[actor as usize].can_tsumo_agari
+        };
+        if !can {
+            let what = if is_ron { "ron" } else { "tsumo agari" };
+            self.report_action(actor, what);
+            return;
+        }
+
+        // The points are only checked as a lower bound, since the deltas of
+        // multiple rons split honba and kyotaku differently across rules.
+        let (Some(ura), Some(deltas)) = (ura, deltas) else {
+            return;
+        };
+        let state = &self.states[actor as usize];
+        match state.agari_points(is_ron, ura) {
+            Ok(points) => {
+                let min = if is_ron {
+                    points.ron
+                } else {
+                    points.tsumo_total(state.is_oya())
+                };
+                if deltas[actor as usize] < min {
+                    self.report(
+                        IssueKind::ScoreMismatch,
+                        format!(
+                            "hora of {actor} gains {}, less than the {min} points of the hand",
+                            deltas[actor as usize],
+                        ),
+                    );
+                }
+            }
+            Err(err) => self.report_action(actor, &format!("hora without a yaku: {err}")),
+        }
+    }
+
+    fn referee(&mut self, ev: &Event) {
+        match *ev {
+            Event::StartGame { .. } => {
+                if self.in_game {
+                    self.report(IssueKind::Structure, "start_game in the middle of a game");
+                }
+                self.in_game = true;
+                self.last_kyoku = None;
+            }
+            Event::EndGame => {
+                if self.kyoku.is_some() {
+                    self.report(IssueKind::Structure, "end_game in the middle of a kyoku");
+                    self.kyoku = None;
+                }
+                self.in_game = false;
+            }
+            Event::StartKyoku {
+                bakaze,
+                kyoku,
+                honba,
+                kyotaku,
+                oya,
+                scores,
+                ..
+            } => {
+                if !self.in_game {
+                    self.report(IssueKind::Structure, "start_kyoku before start_game");
+                }
+                if self.kyoku.is_some() {
+                    self.report(IssueKind::Structure, "start_kyoku before end_kyoku");
+                }
+                let index = if matches_tu8!(bakaze.as_u8(), E | S | W | N) {
+                    (bakaze.as_u8() - tu8!(E)) * 4 + kyoku - 1
+                } else {
+                    self.report(IssueKind::KyokuOrder, format!("bakaze is {bakaze}"));
+                    0
+                };
+                self.check_next_kyoku(index, honba, kyotaku, scores);
+                if oya != kyoku - 1 {
+                    self.report(
+                        IssueKind::KyokuOrder,
+                        format!("oya is {oya} in kyoku {kyoku}"),
+                    );
+                }
+                self.kyoku = Some(Kyoku {
+                    index,
+                    honba,
+                    scores: Some(scores),
+                    kyotaku,
+                    start_scores: scores,
+                    start_kyotaku: kyotaku,
+                    next_tsumo: Some(oya),
+                    ..Default::default()
+                });
+            }
+            Event::EndKyoku => match self.kyoku.take() {
+                Some(kyoku) => {
+                    if !kyoku.has_outcome {
+                        self.report(
+                            IssueKind::Structure,
+                            "end_kyoku without hora, ryukyoku or chombo",
+                        );
+                    }
+                    self.last_kyoku = Some(kyoku);
+                }
+                None => self.report(IssueKind::Structure, "end_kyoku outside of a kyoku"),
+            },
+            _ => self.referee_in_kyoku(ev),
+        }
+    }
+
+    fn check_next_kyoku(&mut self, index: u8, honba: u8, kyotaku: u8, scores: [i32; 4]) {
+        let Some(last) = self.last_kyoku.take() else {
+            if index != 0 || honba != 0 || kyotaku != 0 {
+                self.report(
+                    IssueKind::KyokuOrder,
+                    format!("the game starts at kyoku {index} honba {honba} kyotaku {kyotaku}"),
+                );
+            }
+            return;
+        };
+
+        let ok = if last.is_chombo {
+            index == last.index && honba == last.honba
+        } else {
+            let renchan = index == last.index && honba == last.honba + 1;
+            let next = index == last.index + 1 && (honba == 0 || honba == last.honba + 1);
+            renchan || next
+        };
+        if !ok {
+            self.report(
+                IssueKind::KyokuOrder,
+                format!(
+                    "kyoku {index} honba {honba} follows kyoku {} honba {}",
+                    last.index, last.honba,
+                ),
+            );
+        }
+
+        if let Some(expected) = last.scores {
+            if scores != expected {
+                self.report(
+                    IssueKind::ScoreMismatch,
+                    format!("scores are {scores:?}, expected {expected:?}"),
+                );
+            }
+            if kyotaku != last.kyotaku {
+                self.report(
+                    IssueKind::ScoreMismatch,
+                    format!("kyotaku is {kyotaku}, expected {}", last.kyotaku),
+                );
+            }
+        }
+    }
+
+    fn referee_in_kyoku(&mut self, ev: &Event) {
+        let Some(mut kyoku) = self.kyoku.take() else {
+            self.report(
+                IssueKind::Structure,
+                format!("{} outside of a kyoku", event_name(ev)),
+            );
+            return;
+        };
+        if kyoku.has_outcome && (kyoku.is_chombo || !matches!(ev, Event::Hora { .. })) {
+            self.report(
+                IssueKind::Structure,
+                format!("{} after the outcome of the kyoku", event_name(ev)),
+            );
+        }
+
+        match *ev {
+            Event::Tsumo { actor, .. } => {
+                if kyoku.at_rinshan {
+                    kyoku.at_rinshan = false;
+                } else {
+                    if kyoku.doras < kyoku.kans {
+                        self.report(
+                            IssueKind::MissingDora,
+                            format!("{} kans but {} doras", kyoku.kans, kyoku.doras),
+                        );
+                        kyoku.doras = kyoku.kans;
+                    }
+                    if let Some(next) = kyoku.next_tsumo.filter(|&n| n != actor) {
+                        self.report_action(actor, &format!("tsumo out of turn, expected {next}"));
+                    }
+                }
+                kyoku.next_tsumo = None;
+            }
+            Event::Dahai { actor, .. } => {
+                // The dora of a minkan is revealed before or after the
+                // discard that follows, depending on the source of the log.
+                if kyoku.doras + 1 < kyoku.kans {
+                    self.report(
+                        IssueKind::MissingDora,
+                        format!("{} kans but {} doras", kyoku.kans, kyoku.doras),
+                    );
+                    kyoku.doras = kyoku.kans - 1;
+                }
+                kyoku.next_tsumo = Some((actor + 1) % 4);
+            }
+            Event::Chi { .. } | Event::Pon { .. } => kyoku.next_tsumo = None,
+            Event::Daiminkan { actor, .. }
+            | Event::Kakan { actor, .. }
+            | Event::Ankan { actor, .. } => {
+                kyoku.kans += 1;
+                kyoku.at_rinshan = true;
+                kyoku.next_tsumo = Some(actor);
+            }
+            Event::Dora { dora_marker } => {
+                if kyoku.doras < kyoku.kans {
+                    kyoku.doras += 1;
+                } else {
+                    self.report(
+                        IssueKind::UnexpectedDora,
+                        format!("dora {dora_marker} without a kan"),
+                    );
+                }
+            }
+            Event::ReachAccepted { actor } => {
+                if let Some(scores) = &mut kyoku.scores {
+                    scores[actor as usize] -= 1000;
+                }
+                kyoku.kyotaku += 1;
+            }
+            Event::Hora { deltas, .. } => {
+                if !kyoku.has_outcome {
+                    kyoku.kyotaku_left = kyoku.kyotaku as i32 * 1000;
+                    kyoku.kyotaku = 0;
+                } else if !self.states[0].rule().double_ron {
+                    self.report(
+                        IssueKind::Structure,
+                        "multiple rons while double ron is disabled",
+                    );
+                }
+                kyoku.has_outcome = true;
+                self.settle(&mut kyoku, deltas, true);
+            }
+            Event::Ryukyoku { deltas, .. } => {
+                kyoku.has_outcome = true;
+                self.settle(&mut kyoku, deltas, false);
+            }
+            Event::Chombo { deltas, .. } => {
+                // The kyoku is called off, riichi sticks included.
+                kyoku.has_outcome = true;
+                kyoku.is_chombo = true;
+                kyoku.scores = Some(kyoku.start_scores);
+                kyoku.kyotaku = kyoku.start_kyotaku;
+                self.settle(&mut kyoku, deltas, false);
+            }
+            _ => (),
+        }
+
+        self.kyoku = Some(kyoku);
+    }
+
+    fn settle(&mut self, kyoku: &mut Kyoku, deltas: Option<[i32; 4]>, is_hora: bool) {
+        let Some(deltas) = deltas else {
+            kyoku.scores = None;
+            return;
+        };
+        if let Some(scores) = &mut kyoku.scores {
+            for (s, d) in scores.iter_mut().zip(deltas) {
+                *s += d;
+            }
+        }
+
+        // The kyotaku goes to the first hora of a multiple ron, and the
+        // deltas are otherwise zero-sum.
+        let sum = deltas.iter().sum::<i32>();
+        let expected = if is_hora {
+            mem::take(&mut kyoku.kyotaku_left)
+        } else {
+            0
+        };
+        if sum != expected {
+            self.report(
+                IssueKind::ScoreMismatch,
+                format!("deltas {deltas:?} sum up to {sum}, expected {expected}"),
+            );
+        }
+    }
+}
+
+fn event_name(ev: &Event) -> String {
+    json::to_value(ev)
+        .ok()
+        .and_then(|v| v.get("type").and_then(|t| t.as_str()).map(str::to_owned))
+        .unwrap_or_default()
+}
+
+/// Validates a log in JSON lines, returning a list of `Issue`.
+#[cfg(feature = "python")]
+#[pyfunction]
+#[pyo3(name = "validate")]
+#[pyo3(text_signature = "(log, /)")]
+fn validate_py(log: &str) -> Result<Vec<Issue>> {
+    let events = log
+        .lines()
+        .filter(|l| !l.trim().is_empty())
+        .map(json::from_str)
+        .collect::<Result<Vec<Event>, _>>()
+        .context("failed to parse log")?;
+    Ok(validate(&events))
+}
+
+/// Reads a log file, which may be compressed, and validates it, returning a
+/// list of `Issue`.
+#[cfg(feature = "python")]
+#[pyfunction]
+#[pyo3(name = "validate_file")]
+#[pyo3(text_signature = "(filename, /)")]
+fn validate_file_py(filename: &str) -> Result<Vec<Issue>> {
+    validate_file(filename)
+}
+
+#[cfg(feature = "python")]
+pub(crate) fn register_module(py: Python<'_>, prefix: &str, super_mod: &PyModule) -> PyResult<()> {
+    let m = PyModule::new(py, "validate")?;
+    m.add_class::<Issue>()?;
+    m.add_function(wrap_pyfunction!(validate_py, m)?)?;
+    m.add_function(wrap_pyfunction!(validate_file_py, m)?)?;
+    add_submodule(py, prefix, super_mod, m)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::t;
+
+    fn events_of(log: &str) -> Vec<Event> {
+        log.lines().map(|l| json::from_str(l).unwrap()).collect()
+    }
+
+    fn kinds_of(events: &[Event]) -> Vec<(usize, IssueKind)> {
+        validate(events)
+            .into_iter()
+            .map(|i| (i.line, i.kind))
+            .collect()
+    }
+
+    #[test]
+    fn issues() {
+        let events = events_of(include_str!("../tests/data/pack_test.json"));
+        assert_eq!(kinds_of(&events), []);
+
+        let mut missing_end = events.clone();
+        missing_end.remove(6);
+        assert_eq!(kinds_of(&missing_end), [(7, IssueKind::Structure)],);
+
+        let mut bad_discard = events.clone();
+        bad_discard[3] = Event::Dahai {
+            actor: 0,
+            pai: t!(1p),
+            tsumogiri: false,
+        };
+        assert_eq!(kinds_of(&bad_discard)[0], (4, IssueKind::ImpossibleAction));
+
+        let mut stray_dora = events.clone();
+        stray_dora.insert(
+            3,
+            Event::Dora {
+                dora_marker: t!(1m),
+            },
+        );
+        assert_eq!(kinds_of(&stray_dora), [(4, IssueKind::UnexpectedDora)]);
+
+        let mut bad_deltas = events.clone();
+        bad_deltas[5] = Event::Ryukyoku {
+            deltas: Some([1000, 0, 0, 0]),
+            reason: None,
+        };
+        assert_eq!(kinds_of(&bad_deltas), [(6, IssueKind::ScoreMismatch)]);
+
+        // The second kyoku must be a renchan of the first after an abortive
+        // ryukyoku, with the same scores.
+        let mut next_kyoku = events.clone();
+        let kyoku: Vec<_> = events[1..7].to_vec();
+        next_kyoku.splice(7..7, kyoku);
+        assert_eq!(kinds_of(&next_kyoku), [(8, IssueKind::KyokuOrder)]);
+        if let Event::StartKyoku { honba, scores, .. } = &mut next_kyoku[7] {
+            *honba = 1;
+            scores[0] = 26000;
+        }
+        assert_eq!(kinds_of(&next_kyoku), [(8, IssueKind::ScoreMismatch)]);
+
+        // A chombo is followed by a replay of the kyoku with the same honba.
+        next_kyoku[5] = Event::Chombo {
+            actor: 1,
+            deltas: Some([4000, -8000, 2000, 2000]),
+        };
+        if let Event::StartKyoku { scores, .. } = &mut next_kyoku[7] {
+            *scores = [29000, 17000, 27000, 27000];
+        }
+        assert_eq!(kinds_of(&next_kyoku), [(8, IssueKind::KyokuOrder)]);
+        if let Event::StartKyoku { honba, .. } = &mut next_kyoku[7] {
+            *honba = 0;
+        }
+        assert_eq!(kinds_of(&next_kyoku), []);
+    }
+
+    #[test]
+    fn head_bump() {
+        let log = r#"{"type":"start_game","names":["a","b","c","d"]}
+{"type":"start_kyoku","bakaze":"E","dora_marker":"C","kyoku":1,"honba":0,"kyotaku":0,"oya":0,"scores":[25000,25000,25000,25000],"tehais":[["5pr","1p","1p","1p","9p","9p","9p","E","E","E","S","S","S"],["2m","3m","4m","4m","5m","6m","6m","7m","8m","2s","3s","4s","5p"],["2m","3m","4m","4s","5s","6s","6s","7s","8s","2p","3p","4p","5p"],["1m","1m","1m","9m","9m","9m","1s","1s","1s","9s","9s","9s","W"]]}
+{"type":"tsumo","actor":0,"pai":"N"}
+{"type":"dahai","actor":0,"pai":"5pr","tsumogiri":false}
+{"type":"hora","actor":1,"target":0}
+{"type":"hora","actor":2,"target":0}
+{"type":"end_kyoku"}
+{"type":"end_game"}"#;
+        let events = events_of(log);
+        assert_eq!(kinds_of(&events), []);
+
+        let mut head_bump = events;
+        head_bump[0] = json::from_str(
+            r#"{"type":"start_game","names":["a","b","c","d"],"meta":{"rule":{"double_ron":false}}}"#,
+        )
+        .unwrap();
+        assert_eq!(kinds_of(&head_bump), [(6, IssueKind::Structure)]);
+    }
+}
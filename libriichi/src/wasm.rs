@@ -0,0 +1,156 @@
+//! JavaScript bindings of `PlayerState` and the shanten and agari calculators,
+//! for running the state machine in browsers.
+//!
+//! Build it for `wasm32-unknown-unknown` with `--no-default-features
+//! --features wasm`, then generate the JavaScript glue with `wasm-bindgen`.
+//! Same as the C ABI in `capi`, all the events, candidates and reactions are
+//! mjai JSON strings, and tehais are 34-length arrays of tile counts. Errors
+//! are thrown as JavaScript `Error`s.
+
+use crate::algo::calculator::{AgariCalculator, AgariResult, ShantenCalculator};
+use crate::state::PlayerState;
+
+use anyhow::ensure;
+use serde_json as json;
+use wasm_bindgen::prelude::*;
+
+fn js_err(err: anyhow::Error) -> JsError {
+    JsError::new(&format!("{err:#}"))
+}
+
+#[wasm_bindgen(js_name = PlayerState)]
+pub struct JsPlayerState {
+    state: PlayerState,
+}
+
+#[wasm_bindgen(js_class = PlayerState)]
+impl JsPlayerState {
+    /// Creates a state of `player_id` under Tenhou's rule.
+    #[wasm_bindgen(constructor)]
+    pub fn new(player_id: u8) -> Result<JsPlayerState, JsError> {
+        let inner = || {
+            ensure!(player_id < 4, "{player_id} is not in range [0, 3]");
+            Ok(PlayerState::new(player_id))
+        };
+        let state = inner().map_err(js_err)?;
+        Ok(Self { state })
+    }
+
+    /// Updates the state with one mjai event, and returns the resulting
+    /// `ActionCandidate` in JSON.
+    pub fn update(&mut self, mjai_json: &str) -> Result<String, JsError> {
+        let event = json::from_str(mjai_json)?;
+        let cans = self.state.update(&event).map_err(js_err)?;
+        Ok(json::to_string(&cans)?)
+    }
+
+    /// Throws if the mjai event is not a valid reaction to the current state.
+    #[wasm_bindgen(js_name = validateReaction)]
+    pub fn validate_reaction(&self, mjai_json: &str) -> Result<(), JsError> {
+        let action = json::from_str(mjai_json)?;
+        Ok(self.state.validate_reaction(&action)?)
+    }
+
+    /// Returns the observable state in JSON, see `PlayerState::to_json`.
+    #[wasm_bindgen(js_name = toJson)]
+    pub fn to_json(&self) -> Result<String, JsError> {
+        self.state.to_json().map_err(js_err)
+    }
+}
+
+/// Returns the minimum shanten of `tehai` among normal, chitoi and kokushi
+/// shapes, where -1 means agari.
+#[wasm_bindgen]
+pub fn shanten(tehai: &[u8]) -> Result<i8, JsError> {
+    ShantenCalculator.calc_all(tehai.to_vec()).map_err(js_err)
+}
+
+/// Returns `undefined` if the hand is not agari or has no yaku, see
+/// `AgariCalculator::calc` for the arguments.
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn agari(
+    tehai: &[u8],
+    winning_tile: u8,
+    is_ron: bool,
+    bakaze: u8,
+    jikaze: u8,
+    kuitan: bool,
+    chis: &[u8],
+    pons: &[u8],
+    minkans: &[u8],
+    ankans: &[u8],
+    additional_hans: u8,
+    doras: u8,
+) -> Result<Option<AgariResult>, JsError> {
+    let inner = || {
+        AgariCalculator::new(bakaze, jikaze, kuitan)?.calc(
+            tehai.to_vec(),
+            winning_tile,
+            is_ron,
+            chis.to_vec(),
+            pons.to_vec(),
+            minkans.to_vec(),
+            ankans.to_vec(),
+            additional_hans,
+            doras,
+        )
+    };
+    inner().map_err(js_err)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::hand::hand;
+    use crate::tu8;
+
+    // Only the successful paths can run natively, as creating a `JsError`
+    // needs a JavaScript host, which also has no `Debug` for `unwrap`.
+    fn ok<T>(ret: Result<T, JsError>) -> T {
+        ret.unwrap_or_else(|_| panic!("unexpected error"))
+    }
+
+    #[test]
+    fn player_state() {
+        let mut state = ok(JsPlayerState::new(0));
+        let lines = [
+            r#"{"type":"start_game","names":["a","b","c","d"]}"#,
+            r#"{"type":"start_kyoku","bakaze":"E","dora_marker":"1m","kyoku":1,"honba":0,"kyotaku":0,"oya":0,"scores":[25000,25000,25000,25000],"tehais":[["1m","2m","3m","4p","5p","6p","7s","8s","9s","E","E","S","S"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"],["?","?","?","?","?","?","?","?","?","?","?","?","?"]]}"#,
+            r#"{"type":"tsumo","actor":0,"pai":"N"}"#,
+        ];
+        let mut cans = json::Value::Null;
+        for line in lines {
+            cans = json::from_str(&ok(state.update(line))).unwrap();
+        }
+        assert_eq!(cans["can_discard"], true);
+        ok(state.validate_reaction(r#"{"type":"dahai","actor":0,"pai":"N","tsumogiri":true}"#));
+        let view: json::Value = json::from_str(&ok(state.to_json())).unwrap();
+        assert!(view.is_object());
+    }
+
+    #[test]
+    fn calculators() {
+        let tehai = hand("123m 456p 789s 1122z").unwrap();
+        assert_eq!(ok(shanten(&tehai)), 0);
+
+        let tehai = hand("123m 456p 789s 11122z").unwrap();
+        let ret = ok(agari(
+            &tehai,
+            tu8!(S),
+            true,
+            tu8!(E),
+            tu8!(E),
+            true,
+            &[],
+            &[],
+            &[],
+            &[],
+            0,
+            0,
+        ))
+        .unwrap();
+        assert_eq!(ret.han, 2);
+        assert_eq!(ret.yakuman, 0);
+    }
+}
@@ -9,14 +9,14 @@ import time
 from os import path
 from model import Brain, DQN
 from player import TrainPlayer
-from common import send_msg, recv_msg
+from common import send_msg, recv_msg, config_obs_version
 from config import config
 
 def main():
     remote = (config['online']['remote']['host'], config['online']['remote']['port'])
     device = config['control']['device']
-    oracle = Brain(True, **config['resnet']).to(device).eval()
-    mortal = Brain(False, **config['resnet']).to(device).eval()
+    oracle = Brain(True, **config['resnet'], obs_version=config_obs_version()).to(device).eval()
+    mortal = Brain(False, **config['resnet'], obs_version=config_obs_version()).to(device).eval()
     dqn = DQN().to(device)
     train_player = TrainPlayer()
 
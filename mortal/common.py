@@ -4,6 +4,7 @@ import struct
 import time
 from typing import *
 from io import BytesIO
+from libriichi.consts import ObsVersion
 from config import config
 
 @torch.jit.script
@@ -11,6 +12,10 @@ def apply_masks(actions, masks, fill: float = -1e9):
     fill = torch.tensor(fill, dtype=actions.dtype, device=actions.device)
     return torch.where(masks, actions, fill)
 
+def config_obs_version():
+    # The brain, the engine and the dataloader have to agree on it.
+    return getattr(ObsVersion, f"V{config['control'].get('obs_version', 1)}")
+
 def hard_update(src, dst):
     dst.load_state_dict(src.state_dict())
 
@@ -4,7 +4,8 @@ import numpy as np
 from torch.utils.data import IterableDataset
 from model import GRP
 from reward_calculator import RewardCalculator
-from libriichi.dataset import GameplayLoader
+from libriichi.dataset import GameplayLoader, GameplayReader
+from common import config_obs_version
 from config import config
 
 class FileDatasetsIter(IterableDataset):
@@ -16,6 +17,7 @@ class FileDatasetsIter(IterableDataset):
         quality_threshold = 0,
         player_name = None,
         excludes = None,
+        packed = False, # files are written by `GameplayLoader.pack_gz_log_files` or `encode_logs`
     ):
         super().__init__()
         self.file_list = file_list
@@ -24,11 +26,17 @@ class FileDatasetsIter(IterableDataset):
         self.quality_threshold = int(quality_threshold)
         self.player_name = player_name
         self.excludes = excludes
+        self.packed = packed
         self.buffer = []
         self.iterator = None
 
     def build_iter(self):
-        self.loader = GameplayLoader(oracle=True, player_name=self.player_name, excludes=self.excludes)
+        self.loader = GameplayLoader(
+            oracle = True,
+            player_name = self.player_name,
+            excludes = self.excludes,
+            obs_version = config_obs_version(),
+        )
 
         # do not put it in __init__, it won't work on Windows
         grp = GRP(**config['grp']['network'])
@@ -46,7 +54,10 @@ class FileDatasetsIter(IterableDataset):
 
     def populate_buffer(self, start_idx):
         file_list = self.file_list[start_idx:start_idx + self.file_batch_size]
-        data = self.loader.load_gz_log_files(file_list)
+        if self.packed:
+            data = [game for filename in file_list for game in GameplayReader(filename)]
+        else:
+            data = self.loader.load_gz_log_files(file_list)
 
         for game in data:
             quality = game.take_quality()
@@ -17,6 +17,12 @@ class MortalEngine:
         name = 'NoName',
         boltzmann_epsilon = 0,
         boltzmann_temp = 1,
+        sampling_epsilon = None,
+        sampling_temp = None,
+        sampling_top_k = None,
+        sampling_seed = None,
+        obs_version = None,
+        fallback = None,
     ):
         self.device = device or torch.device('cpu')
         self.brain = brain.to(self.device).eval()
@@ -28,10 +34,23 @@ class MortalEngine:
         self.enable_quick_eval = enable_quick_eval
         self.enable_rule_based_agari_guard = enable_rule_based_agari_guard
         self.name = name
+        # `libriichi.consts.ObsVersion` the brain is trained with, V1 if None.
+        self.obs_version = obs_version
 
         self.boltzmann_epsilon = boltzmann_epsilon
         self.boltzmann_temp = boltzmann_temp
 
+        # Sampling done by libriichi on the returned q values, see
+        # `MortalBatchAgent`.
+        self.sampling_epsilon = sampling_epsilon
+        self.sampling_temp = sampling_temp
+        self.sampling_top_k = sampling_top_k
+        self.sampling_seed = sampling_seed
+
+        # 'tsumogiri' or 'rule_based' to react with instead of failing when
+        # `react_batch` raises, see `MortalBatchAgent`.
+        self.fallback = fallback
+
     def react_batch(self, obs, masks, invisible_obs):
         with (
             torch.autocast(self.device.type, enabled=self.enable_amp),
@@ -40,10 +59,12 @@ class MortalEngine:
             return self._react_batch(obs, masks, invisible_obs)
 
     def _react_batch(self, obs, masks, invisible_obs):
-        obs = torch.as_tensor(np.stack(obs, axis=0), device=self.device)
-        masks = torch.as_tensor(np.stack(masks, axis=0), device=self.device)
+        # The batches are already stacked on the Rust side, so `np.asarray`
+        # does not copy them.
+        obs = torch.as_tensor(np.asarray(obs), device=self.device)
+        masks = torch.as_tensor(np.asarray(masks), device=self.device)
         if self.is_oracle:
-            invisible_obs = torch.as_tensor(np.stack(invisible_obs, axis=0), device=self.device)
+            invisible_obs = torch.as_tensor(np.asarray(invisible_obs), device=self.device)
         else:
             invisible_obs = None
         batch_size = obs.shape[0]
@@ -0,0 +1,49 @@
+import prelude
+
+import json
+import torch
+from model import Brain, DQN
+from engine import MortalEngine
+from libriichi.review import Evaluator
+from common import config_obs_version
+from config import config
+
+def main():
+    cfg = config['evaluate']
+
+    mortal = Brain(False, **config['resnet'], obs_version=config_obs_version()).eval()
+    dqn = DQN().eval()
+    state = torch.load(cfg['engine']['state_file'], map_location=torch.device('cpu'))
+    mortal.load_state_dict(state['mortal'])
+    dqn.load_state_dict(state['current_dqn'])
+    engine = MortalEngine(
+        mortal,
+        dqn,
+        is_oracle = False,
+        device = torch.device(cfg['engine']['device']),
+        enable_amp = cfg['engine']['enable_amp'],
+        enable_rule_based_agari_guard = cfg['engine']['enable_rule_based_agari_guard'],
+        name = cfg['engine']['name'],
+        obs_version = config_obs_version(),
+    )
+
+    evaluator = Evaluator(
+        engine,
+        player_name = cfg['player_name'] or None,
+        temperature = cfg['temperature'],
+    )
+    evaluation = json.loads(evaluator.evaluate(cfg['log_dir']))
+
+    print(f'games: {evaluation["games"]}, seats: {evaluation["seats"]}')
+    rows = [('overall', evaluation['overall'])] + list(evaluation['situations'].items())
+    for name, a in rows:
+        print(
+            f'{name:>10}: {a["matches"]:>8}/{a["reviewed"]:<8} '
+            f'{a["rate"]:.2%}, avg q gap {a["avg_q_gap"]:.4f} over {a["rated_disagreements"]}'
+        )
+
+if __name__ == '__main__':
+    try:
+        main()
+    except KeyboardInterrupt:
+        pass
@@ -4,7 +4,7 @@ from torch.nn import functional as F
 from torch.nn.utils.rnn import pack_padded_sequence, pad_sequence
 from typing import *
 from itertools import permutations
-from libriichi.consts import OBS_SHAPE, ORACLE_OBS_SHAPE, ACTION_SPACE, GRP_SIZE
+from libriichi.consts import ORACLE_OBS_SHAPE, ACTION_SPACE, GRP_SIZE, ObsVersion
 from common import apply_masks
 
 class ChannelAttention(nn.Module):
@@ -75,10 +75,10 @@ class ResNet(nn.Module):
         return self.net(x)
 
 class Brain(nn.Module):
-    def __init__(self, is_oracle, conv_channels, num_blocks, enable_bn, bn_momentum):
+    def __init__(self, is_oracle, conv_channels, num_blocks, enable_bn, bn_momentum, obs_version=ObsVersion.V1):
         super().__init__()
         self.is_oracle = is_oracle
-        in_channels = OBS_SHAPE[0]
+        in_channels = obs_version.obs_shape()[0]
         if is_oracle:
             in_channels += ORACLE_OBS_SHAPE[0]
 
@@ -6,7 +6,7 @@ import json
 import torch
 from model import Brain, DQN, GRP
 from engine import MortalEngine
-from common import filtered_stripped_lines
+from common import filtered_stripped_lines, config_obs_version
 from libriichi.mjai import Bot
 from libriichi.dataset import Grp
 from config import config
@@ -26,7 +26,7 @@ def main():
     review_mode = os.environ.get('MORTAL_REVIEW_MODE', '0') == '1'
 
     device = torch.device('cpu')
-    mortal = Brain(False, **config['resnet']).eval()
+    mortal = Brain(False, **config['resnet'], obs_version=config_obs_version()).eval()
     dqn = DQN().eval()
     state = torch.load(config['control']['state_file'], map_location=torch.device('cpu'))
     mortal.load_state_dict(state['mortal'])
@@ -41,6 +41,7 @@ def main():
         enable_quick_eval = not review_mode,
         enable_rule_based_agari_guard = True,
         name = 'mortal',
+        obs_version = config_obs_version(),
     )
     bot = Bot(engine, player_id)
 
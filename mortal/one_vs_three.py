@@ -7,6 +7,7 @@ import os
 from model import Brain, DQN
 from engine import MortalEngine
 from libriichi.arena import OneVsThree
+from common import config_obs_version
 from config import config
 
 def main():
@@ -23,7 +24,7 @@ def main():
         os.environ['AKOCHAN_DIR'] = cfg['akochan']['dir']
         os.environ['AKOCHAN_TACTICS'] = cfg['akochan']['tactics']
     else:
-        mortal = Brain(False, **config['resnet']).eval()
+        mortal = Brain(False, **config['resnet'], obs_version=config_obs_version()).eval()
         dqn = DQN().eval()
         state = torch.load(cfg['champion']['state_file'], map_location=torch.device('cpu'))
         mortal.load_state_dict(state['mortal'])
@@ -37,9 +38,10 @@ def main():
             enable_amp = cfg['champion']['enable_amp'],
             enable_rule_based_agari_guard = cfg['champion']['enable_rule_based_agari_guard'],
             name = cfg['champion']['name'],
+            obs_version = config_obs_version(),
         )
 
-    mortal = Brain(False, **config['resnet']).eval()
+    mortal = Brain(False, **config['resnet'], obs_version=config_obs_version()).eval()
     dqn = DQN().eval()
     state = torch.load(cfg['challenger']['state_file'], map_location=torch.device('cpu'))
     mortal.load_state_dict(state['mortal'])
@@ -53,6 +55,7 @@ def main():
         enable_amp = cfg['challenger']['enable_amp'],
         enable_rule_based_agari_guard = cfg['challenger']['enable_rule_based_agari_guard'],
         name = cfg['challenger']['name'],
+        obs_version = config_obs_version(),
     )
 
     seed_start = 10000
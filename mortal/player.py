@@ -8,13 +8,14 @@ from model import Brain, DQN
 from engine import MortalEngine
 from libriichi.stat import Stat
 from libriichi.arena import OneVsThree
+from common import config_obs_version
 from config import config
 
 class TestPlayer:
     def __init__(self):
         device = torch.device(config['baseline']['device'])
 
-        stable_mortal = Brain(False, **config['resnet']).eval()
+        stable_mortal = Brain(False, **config['resnet'], obs_version=config_obs_version()).eval()
         stable_dqn = DQN().eval()
         state = torch.load(config['baseline']['state_file'], map_location=torch.device('cpu'))
         stable_mortal.load_state_dict(state['mortal'])
@@ -26,6 +27,7 @@ class TestPlayer:
             device = device,
             enable_amp = True,
             name = 'baseline',
+            obs_version = config_obs_version(),
         )
         self.log_dir = path.abspath(config['test_play']['log_dir'])
 
@@ -38,6 +40,7 @@ class TestPlayer:
             device = device,
             enable_amp = True,
             name = 'mortal',
+            obs_version = config_obs_version(),
         )
 
         if path.isdir(self.log_dir):
@@ -62,7 +65,7 @@ class TrainPlayer:
     def __init__(self):
         device = torch.device(config['baseline']['device'])
 
-        stable_mortal = Brain(False, **config['resnet']).eval()
+        stable_mortal = Brain(False, **config['resnet'], obs_version=config_obs_version()).eval()
         stable_dqn = DQN().eval()
         state = torch.load(config['baseline']['state_file'], map_location=torch.device('cpu'))
         stable_mortal.load_state_dict(state['mortal'])
@@ -74,6 +77,7 @@ class TrainPlayer:
             device = device,
             enable_amp = True,
             name = 'baseline',
+            obs_version = config_obs_version(),
         )
 
         profile = os.environ.get('TRAIN_PLAY_PROFILE', 'default')
@@ -102,6 +106,7 @@ class TrainPlayer:
             device = device,
             enable_amp = True,
             name = 'trainee',
+            obs_version = config_obs_version(),
         )
 
         if path.isdir(self.log_dir):
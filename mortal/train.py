@@ -17,7 +17,7 @@ def train():
     from torch.utils.data import DataLoader
     from torch.utils.tensorboard import SummaryWriter
     from tqdm.auto import tqdm
-    from common import submit_param, parameter_count, drain
+    from common import submit_param, parameter_count, drain, config_obs_version
     from player import TestPlayer
     from dataloader import FileDatasetsIter, worker_init_fn
     from model import Brain, DQN
@@ -39,8 +39,8 @@ def train():
     pts = config['env']['pts']
     gamma = config['env']['gamma']
 
-    mortal = Brain(False, **config['resnet']).to(device)
-    current_oracle = Brain(True, **config['resnet']).to(device)
+    mortal = Brain(False, **config['resnet'], obs_version=config_obs_version()).to(device)
+    current_oracle = Brain(True, **config['resnet'], obs_version=config_obs_version()).to(device)
     current_dqn = DQN().to(device)
     log_beta = torch.tensor(config['vlog']['beta_init'], dtype=torch.float32, device=device).log().requires_grad_(True)
 